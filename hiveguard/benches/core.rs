@@ -0,0 +1,53 @@
+use rusty_paseto::core::{Key, Local, Paseto, PasetoNonce, PasetoSymmetricKey, Payload, V4};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use argon2::password_hash::rand_core::OsRng;
+use std::hint::black_box;
+use argon2::Argon2;
+
+
+const PASSWORD: &str = "correct horse battery staple";
+/// the claims of a typical access token.
+const CLAIMS: &str = r#"{"sub":"01J8Z3Q4W5E6R7T8Y9U0I1O2P3","iat":"2026-01-01T00:00:00+00:00","exp":"2026-01-01T00:15:00+00:00"}"#;
+
+
+/// Hashing and verifying with the default Argon2id parameters.
+fn argon2(c: &mut Criterion) {
+    let argon2 = Argon2::default();
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = argon2.hash_password(PASSWORD.as_bytes(), &salt).unwrap().to_string();
+    let mut group = c.benchmark_group("argon2");
+    group.sample_size(10);
+    group.bench_function("hash", |b| b.iter(|| {
+        let salt = SaltString::generate(&mut OsRng);
+        argon2.hash_password(black_box(PASSWORD).as_bytes(), &salt).unwrap().to_string()
+    }));
+    for (name, password) in [("verify", PASSWORD), ("verify_wrong", "incorrect horse battery staple")] {
+        group.bench_with_input(BenchmarkId::from_parameter(name), password, |b, password| b.iter(|| {
+            let hash = PasswordHash::new(&hash).unwrap();
+            argon2.verify_password(black_box(password).as_bytes(), &hash).is_ok()
+        }));
+    }
+    group.finish();
+}
+
+
+/// Encrypting and decrypting a v4 local token with a fresh nonce each time.
+fn paseto(c: &mut Criterion) {
+    let key = PasetoSymmetricKey::<V4, Local>::from(Key::<32>::try_new_random().unwrap());
+    let nonce = Key::<32>::try_new_random().unwrap();
+    let token = Paseto::<V4, Local>::builder().set_payload(Payload::from(CLAIMS)).try_encrypt(&key, &PasetoNonce::from(&nonce)).unwrap();
+    let mut group = c.benchmark_group("paseto_v4_local");
+    group.bench_function("encrypt", |b| b.iter(|| {
+        let nonce = Key::<32>::try_new_random().unwrap();
+        Paseto::<V4, Local>::builder().set_payload(Payload::from(black_box(CLAIMS))).try_encrypt(&key, &PasetoNonce::from(&nonce)).unwrap()
+    }));
+    group.bench_function("decrypt", |b| b.iter(|| {
+        Paseto::<V4, Local>::try_decrypt(black_box(&token), &key, None, None).unwrap()
+    }));
+    group.finish();
+}
+
+
+criterion_group!(benches, argon2, paseto);
+criterion_main!(benches);
@@ -1 +1 @@
-mod outputs;
\ No newline at end of file
+pub mod outputs;
\ No newline at end of file
@@ -0,0 +1,90 @@
+use crate::types::{AuditEntry, ConversionError, DatabaseError, Id};
+use crate::ports::outputs::audit_log::AuditLog;
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+use aws_sdk_dynamodb::Client;
+use chrono::{DateTime, Utc};
+use tracing::instrument;
+
+
+/// Audit logs in a DynamoDB table keyed by `id`, with an `org_id-index` to find the entries of an organisation.
+///
+/// Every entry is stored as JSON in the `entry` attribute, along with the epoch seconds it occurred at in `occurred_at`.
+/// Entries are deleted by the `prune_audit_log` job rather than a time to live, as every organisation keeps them for its own time.
+pub struct DynamoDBAuditLog {
+    client: Client,
+    table: String,
+}
+
+
+impl DynamoDBAuditLog {
+    pub fn new(client: Client, table: String) -> Self {
+        Self { client, table }
+    }
+
+    /// The items of the organisation whose `occurred_at` passes `condition`, against `:at`.
+    async fn query(&self, org_id: Id, condition: &str, at: DateTime<Utc>) -> Result<Vec<HashMap<String, AttributeValue>>, DatabaseError> {
+        let mut items = Vec::new();
+        let mut start = None;
+        loop {
+            let output = self.client.query()
+                .table_name(&self.table)
+                .index_name("org_id-index")
+                .key_condition_expression("org_id = :org_id")
+                .filter_expression(format!("occurred_at {} :at", condition))
+                .expression_attribute_values(":org_id", org_id.into())
+                .expression_attribute_values(":at", AttributeValue::N(at.timestamp().to_string()))
+                .set_exclusive_start_key(start)
+                .send()
+                .await?;
+            items.extend(output.items.unwrap_or_default());
+            start = output.last_evaluated_key;
+            if start.is_none() {
+                break Ok(items);
+            }
+        }
+    }
+}
+
+
+impl AuditLog for DynamoDBAuditLog {
+    type Error = DatabaseError;
+
+    #[instrument(skip_all, fields(table = %self.table, org_id = %entry.org_id.to_hex()), err)]
+    async fn record(&self, entry: AuditEntry) -> Result<(), Self::Error> {
+        let json = serde_json::to_string(&entry).map_err(|_| ConversionError::UnexpectedDataType("entry"))?;
+        let item = HashMap::from([
+            (String::from("id"), entry.id.into()),
+            (String::from("org_id"), entry.org_id.into()),
+            (String::from("occurred_at"), AttributeValue::N(entry.occurred_at.timestamp().to_string())),
+            (String::from("entry"), AttributeValue::S(json)),
+        ]);
+        self.client.put_item().table_name(&self.table).set_item(Some(item)).send().await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(table = %self.table, org_id = %org_id.to_hex()), err)]
+    async fn entries(&self, org_id: Id, since: DateTime<Utc>) -> Result<Vec<AuditEntry>, Self::Error> {
+        let mut entries = Vec::new();
+        for mut item in self.query(org_id, ">=", since).await? {
+            match item.remove("entry") {
+                Some(AttributeValue::S(json)) => entries.push(serde_json::from_str::<AuditEntry>(&json).map_err(|_| ConversionError::UnexpectedDataType("entry"))?),
+                _ => return Err(ConversionError::UnexpectedDataType("entry").into()),
+            }
+        }
+        entries.sort_by_key(|entry| entry.occurred_at);
+        Ok(entries)
+    }
+
+    #[instrument(skip_all, fields(table = %self.table, org_id = %org_id.to_hex()), err)]
+    async fn prune(&self, org_id: Id, before: DateTime<Utc>) -> Result<usize, Self::Error> {
+        let mut deleted = 0;
+        for mut item in self.query(org_id, "<", before).await? {
+            if let Some(id) = item.remove("id") {
+                self.client.delete_item().table_name(&self.table).key("id", id).send().await?;
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+}
@@ -0,0 +1,46 @@
+use crate::ports::outputs::audit_log::AuditLog;
+use crate::types::{AuditEntry, DatabaseError, Id};
+use std::sync::{PoisonError, RwLock};
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+
+
+/// Audit logs kept in memory, for tests and local development. They are lost on restart.
+#[derive(Default)]
+pub struct MemoryAuditLog {
+    entries: RwLock<HashMap<Id, Vec<AuditEntry>>>,
+}
+
+
+impl MemoryAuditLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+
+impl AuditLog for MemoryAuditLog {
+    type Error = DatabaseError;
+
+    async fn record(&self, entry: AuditEntry) -> Result<(), Self::Error> {
+        self.entries.write().unwrap_or_else(PoisonError::into_inner).entry(entry.org_id).or_default().push(entry);
+        Ok(())
+    }
+
+    async fn entries(&self, org_id: Id, since: DateTime<Utc>) -> Result<Vec<AuditEntry>, Self::Error> {
+        let entries = self.entries.read().unwrap_or_else(PoisonError::into_inner);
+        let mut kept = entries.get(&org_id).into_iter().flatten().filter(|entry| entry.occurred_at >= since).cloned().collect::<Vec<_>>();
+        kept.sort_by_key(|entry| entry.occurred_at);
+        Ok(kept)
+    }
+
+    async fn prune(&self, org_id: Id, before: DateTime<Utc>) -> Result<usize, Self::Error> {
+        let mut entries = self.entries.write().unwrap_or_else(PoisonError::into_inner);
+        let Some(log) = entries.get_mut(&org_id) else {
+            return Ok(0);
+        };
+        let count = log.len();
+        log.retain(|entry| entry.occurred_at >= before);
+        Ok(count - log.len())
+    }
+}
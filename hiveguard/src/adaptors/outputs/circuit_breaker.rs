@@ -0,0 +1,125 @@
+use crate::config::CircuitBreakerConfig;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+
+/// Tracks the failures of a provider and stops calling it while its failure rate is too high.
+///
+/// The circuit opens once at least `min_calls` calls were made in the current window and `failure_rate` of them failed.
+/// Calls are then refused for `cool_down_secs`, after which a single probe is let through:
+/// the circuit closes again if it succeeds and stays open for another cool-down otherwise.
+pub struct CircuitBreaker {
+    /// the provider reported in logs. eg `twilio`
+    name: &'static str,
+    config: CircuitBreakerConfig,
+    state: Mutex<State>,
+}
+
+
+struct State {
+    circuit: Circuit,
+    window_start: Instant,
+    calls: u32,
+    failures: u32,
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Circuit {
+    Closed,
+    Open { until: Instant },
+    /// a probe was let through at `since`, another one is allowed after a cool-down if it never reports back.
+    HalfOpen { since: Instant },
+}
+
+
+impl CircuitBreaker {
+    pub fn new(name: &'static str, config: &CircuitBreakerConfig) -> Self {
+        let state = State { circuit: Circuit::Closed, window_start: Instant::now(), calls: 0, failures: 0 };
+        Self { name, config: config.clone(), state: Mutex::new(state) }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Whether the provider may be called now. A call that is allowed must be followed by [`Self::record`].
+    pub fn allow(&self) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        match state.circuit {
+            Circuit::Closed => true,
+            Circuit::Open { until } | Circuit::HalfOpen { since: until } if now < until => false,
+            Circuit::Open { .. } | Circuit::HalfOpen { .. } => {
+                state.circuit = Circuit::HalfOpen { since: now + self.cool_down() };
+                true
+            },
+        }
+    }
+
+    /// Records the outcome of an allowed call.
+    pub fn record(&self, success: bool) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        match state.circuit {
+            Circuit::HalfOpen { .. } if success => {
+                tracing::info!(provider = self.name, "circuit closed");
+                *state = State { circuit: Circuit::Closed, window_start: now, calls: 0, failures: 0 };
+            },
+            Circuit::HalfOpen { .. } => state.circuit = Circuit::Open { until: now + self.cool_down() },
+            Circuit::Open { .. } => {},
+            Circuit::Closed => {
+                if now.duration_since(state.window_start) >= Duration::from_secs(self.config.window_secs) {
+                    *state = State { circuit: Circuit::Closed, window_start: now, calls: 0, failures: 0 };
+                }
+                state.calls += 1;
+                state.failures += u32::from(!success);
+                if state.calls >= self.config.min_calls && f64::from(state.failures) >= self.config.failure_rate * f64::from(state.calls) {
+                    tracing::warn!(provider = self.name, calls = state.calls, failures = state.failures, "circuit opened");
+                    state.circuit = Circuit::Open { until: now + self.cool_down() };
+                }
+            },
+        }
+    }
+
+    fn cool_down(&self) -> Duration {
+        Duration::from_secs(self.config.cool_down_secs)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(cool_down_secs: u64) -> CircuitBreaker {
+        let config = CircuitBreakerConfig { failure_rate: 0.5, min_calls: 4, window_secs: 60, cool_down_secs };
+        CircuitBreaker::new("test", &config)
+    }
+
+    #[test]
+    fn test_opens_once_the_failure_rate_is_reached() {
+        let breaker = breaker(60);
+        for success in [false, true, false] {
+            assert!(breaker.allow());
+            breaker.record(success);
+        }
+        assert!(breaker.allow());
+        breaker.record(false);
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn test_a_successful_probe_closes_the_circuit() {
+        let breaker = breaker(0);
+        for _ in 0..4 {
+            breaker.record(false);
+        }
+        assert!(breaker.allow());
+        breaker.record(false);
+        assert!(matches!(breaker.state.lock().unwrap().circuit, Circuit::Open { .. }));
+        assert!(breaker.allow());
+        breaker.record(true);
+        assert_eq!(breaker.state.lock().unwrap().circuit, Circuit::Closed);
+    }
+}
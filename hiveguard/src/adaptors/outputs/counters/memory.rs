@@ -0,0 +1,98 @@
+use crate::ports::outputs::counters::Counters;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+
+/// the number of keys past which every expired key is dropped on each call.
+const PRUNE_AT: usize = 1024;
+
+
+/// Counters and locks kept in the memory of the process, for deployments of a single instance.
+/// Every instance keeps its own, so limits are enforced per instance.
+#[derive(Debug, Default)]
+pub struct Memory {
+    /// the value of every key, with the time it expires at. locks are keys of value 1.
+    entries: Mutex<HashMap<String, (u64, Instant)>>,
+}
+
+
+impl Memory {
+    /// Calls `f` with the entries, `key` being dropped if it expired.
+    fn update<T>(&self, key: &str, f: impl FnOnce(&mut HashMap<String, (u64, Instant)>, Instant) -> T) -> T {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        if entries.len() >= PRUNE_AT {
+            entries.retain(|_, (_, expires_at)| *expires_at > now);
+        } else if entries.get(key).is_some_and(|(_, expires_at)| *expires_at <= now) {
+            entries.remove(key);
+        }
+        f(&mut entries, now)
+    }
+}
+
+
+impl Counters for Memory {
+    type Error = Infallible;
+
+    async fn increment(&self, key: &str, window: Duration) -> Result<u64, Self::Error> {
+        Ok(self.update(key, |entries, now| {
+            let entry = entries.entry(key.to_owned()).or_insert((0, now + window));
+            entry.0 += 1;
+            entry.0
+        }))
+    }
+
+    async fn count(&self, key: &str) -> Result<u64, Self::Error> {
+        Ok(self.update(key, |entries, _| entries.get(key).map_or(0, |(count, _)| *count)))
+    }
+
+    async fn acquire(&self, key: &str, ttl: Duration) -> Result<bool, Self::Error> {
+        Ok(self.update(key, |entries, now| {
+            if entries.contains_key(key) {
+                return false;
+            }
+            entries.insert(key.to_owned(), (1, now + ttl));
+            true
+        }))
+    }
+
+    async fn reset(&self, key: &str) -> Result<(), Self::Error> {
+        self.update(key, |entries, _| entries.remove(key));
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_counters_reset_once_their_window_is_over() {
+        let memory = Memory::default();
+        let window = Duration::from_millis(50);
+        assert_eq!(memory.increment("login:jane", window).await, Ok(1));
+        assert_eq!(memory.increment("login:jane", window).await, Ok(2));
+        assert_eq!(memory.count("login:jane").await, Ok(2));
+        assert_eq!(memory.count("login:john").await, Ok(0));
+        tokio::time::sleep(window).await;
+        assert_eq!(memory.count("login:jane").await, Ok(0));
+        assert_eq!(memory.increment("login:jane", window).await, Ok(1));
+        memory.reset("login:jane").await.unwrap();
+        assert_eq!(memory.count("login:jane").await, Ok(0));
+    }
+
+    #[tokio::test]
+    async fn test_locks_are_taken_once_until_they_expire() {
+        let memory = Memory::default();
+        let ttl = Duration::from_millis(50);
+        assert_eq!(memory.acquire("resend:jane", ttl).await, Ok(true));
+        assert_eq!(memory.acquire("resend:jane", ttl).await, Ok(false));
+        tokio::time::sleep(ttl).await;
+        assert_eq!(memory.acquire("resend:jane", ttl).await, Ok(true));
+        memory.reset("resend:jane").await.unwrap();
+        assert_eq!(memory.acquire("resend:jane", ttl).await, Ok(true));
+    }
+}
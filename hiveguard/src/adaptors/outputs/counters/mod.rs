@@ -0,0 +1,83 @@
+use crate::ports::outputs::counters::Counters;
+use crate::config::CountersConfig;
+use std::time::Duration;
+
+mod memory;
+#[cfg(feature = "redis")]
+mod redis;
+
+pub use memory::Memory;
+#[cfg(feature = "redis")]
+pub use self::redis::Redis;
+
+
+pub type CounterError = Box<dyn std::error::Error + Send + Sync>;
+
+
+/// The counter store selected in the configuration.
+pub enum CounterStore {
+    Memory(Memory),
+    #[cfg(feature = "redis")]
+    Redis(Redis),
+}
+
+
+impl CounterStore {
+    /// Connects to the configured store.
+    pub async fn new(config: &CountersConfig) -> Result<Self, CounterError> {
+        match config {
+            CountersConfig::Memory => Ok(Self::Memory(Memory::default())),
+            #[cfg(feature = "redis")]
+            CountersConfig::Redis { url, prefix } => Ok(Self::Redis(Redis::connect(url, prefix.clone()).await?)),
+        }
+    }
+}
+
+
+impl Counters for CounterStore {
+    type Error = CounterError;
+
+    async fn increment(&self, key: &str, window: Duration) -> Result<u64, Self::Error> {
+        match self {
+            Self::Memory(memory) => {
+                let Ok(count) = memory.increment(key, window).await;
+                Ok(count)
+            },
+            #[cfg(feature = "redis")]
+            Self::Redis(redis) => Ok(redis.increment(key, window).await?),
+        }
+    }
+
+    async fn count(&self, key: &str) -> Result<u64, Self::Error> {
+        match self {
+            Self::Memory(memory) => {
+                let Ok(count) = memory.count(key).await;
+                Ok(count)
+            },
+            #[cfg(feature = "redis")]
+            Self::Redis(redis) => Ok(redis.count(key).await?),
+        }
+    }
+
+    async fn acquire(&self, key: &str, ttl: Duration) -> Result<bool, Self::Error> {
+        match self {
+            Self::Memory(memory) => {
+                let Ok(acquired) = memory.acquire(key, ttl).await;
+                Ok(acquired)
+            },
+            #[cfg(feature = "redis")]
+            Self::Redis(redis) => Ok(redis.acquire(key, ttl).await?),
+        }
+    }
+
+    async fn reset(&self, key: &str) -> Result<(), Self::Error> {
+        match self {
+            Self::Memory(memory) => {
+                let Ok(()) = memory.reset(key).await;
+                Ok(())
+            },
+            #[cfg(feature = "redis")]
+            Self::Redis(redis) => Ok(redis.reset(key).await?),
+        }
+    }
+}
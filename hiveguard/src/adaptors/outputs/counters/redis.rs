@@ -0,0 +1,70 @@
+use crate::ports::outputs::counters::Counters;
+use redis::aio::ConnectionManager;
+use redis::{RedisError, Script};
+use tracing::instrument;
+use std::time::Duration;
+
+
+/// increments `KEYS[1]`, starting its window of `ARGV[1]` milliseconds on the first increment.
+const INCREMENT: &str = r"
+local count = redis.call('INCR', KEYS[1])
+if count == 1 then
+    redis.call('PEXPIRE', KEYS[1], ARGV[1])
+end
+return count
+";
+
+
+/// Counters and locks kept in Redis, under `<prefix>:<key>`, shared by every instance connected to it.
+///
+/// The connection is reestablished when it drops, the calls made meanwhile failing.
+#[derive(Clone)]
+pub struct Redis {
+    connection: ConnectionManager,
+    prefix: String,
+}
+
+
+impl Redis {
+    pub async fn connect(url: &str, prefix: String) -> Result<Self, RedisError> {
+        let client = redis::Client::open(url)?;
+        Ok(Self { connection: ConnectionManager::new(client).await?, prefix })
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}:{}", self.prefix, key)
+    }
+}
+
+
+impl Counters for Redis {
+    type Error = RedisError;
+
+    #[instrument(skip(self), err)]
+    async fn increment(&self, key: &str, window: Duration) -> Result<u64, Self::Error> {
+        Script::new(INCREMENT).key(self.key(key)).arg(millis(window)).invoke_async(&mut self.connection.clone()).await
+    }
+
+    #[instrument(skip(self), err)]
+    async fn count(&self, key: &str) -> Result<u64, Self::Error> {
+        let count: Option<u64> = redis::cmd("GET").arg(self.key(key)).query_async(&mut self.connection.clone()).await?;
+        Ok(count.unwrap_or(0))
+    }
+
+    #[instrument(skip(self), err)]
+    async fn acquire(&self, key: &str, ttl: Duration) -> Result<bool, Self::Error> {
+        let set: Option<String> = redis::cmd("SET").arg(self.key(key)).arg(1).arg("NX").arg("PX").arg(millis(ttl)).query_async(&mut self.connection.clone()).await?;
+        Ok(set.is_some())
+    }
+
+    #[instrument(skip(self), err)]
+    async fn reset(&self, key: &str) -> Result<(), Self::Error> {
+        redis::cmd("DEL").arg(self.key(key)).query_async(&mut self.connection.clone()).await
+    }
+}
+
+
+/// `duration` in milliseconds, at least 1 as Redis refuses to expire keys in 0 milliseconds.
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX).max(1)
+}
@@ -0,0 +1,564 @@
+//! The behaviour every `Database` is expected to share with `Memory` and `DynamoDB`.
+//!
+//! An adaptor proves parity by running the suite against a fresh database in its tests, with the `testing` feature:
+//!
+//! ```ignore
+//! hiveguard::database_tests!(MyBackend::connect("postgres://localhost/hiveguard_test").await);
+//! ```
+//!
+//! The expression is evaluated in every test, within an async context, so each check starts from an empty database.
+//! Timestamps are whole seconds, the precision of the coarsest adaptor.
+use crate::ports::outputs::database::{Database, tables::{UsersTable, SessionsTable, VerificationsTable, GrantsTable}};
+use crate::types::{Error, User, Session, Verification, GrantRecord, GrantKind, Email, Phone, Either, Device, Login, Status, Namespace, Document, Consent, DatabaseError, ConversionError, UserFilter};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde_json::{Map, Value, json};
+use crate::testing::{id, UserFixture};
+
+
+/// Generates a `#[tokio::test]` for every check of the conformance suite, each running against the database `$db` evaluates to.
+///
+/// Attributes given before the expression are added to every test, eg `#[ignore]` for a database that has to be running.
+#[macro_export]
+macro_rules! database_tests {
+    // expressions may start with an attribute too, so the attributes are taken one at a time before the expression is.
+    (#[$meta:meta] $($rest:tt)+) => {
+        $crate::database_tests!(@attributes [#[$meta]] $($rest)+);
+    };
+    (@attributes [$($attributes:tt)*] #[$meta:meta] $($rest:tt)+) => {
+        $crate::database_tests!(@attributes [$($attributes)* #[$meta]] $($rest)+);
+    };
+    (@attributes $attributes:tt $db:expr) => {
+        $crate::database_tests!(@all $attributes $db);
+    };
+    ($db:expr) => {
+        $crate::database_tests!(@all [] $db);
+    };
+    (@all $attributes:tt $db:expr) => {
+        $crate::database_tests!(@tests $attributes $db;
+            users_round_trip,
+            duplicate_keys_are_rejected,
+            unique_attributes_are_enforced,
+            missing_users_are_not_found,
+            updates_follow_patch_semantics,
+            conditional_updates_fail_once_users_changed,
+            indexes_follow_updates,
+            users_are_found_by_username,
+            guests_have_no_contacts,
+            users_are_listed_in_pages,
+            users_are_searched,
+            sessions_round_trip,
+            expired_sessions_are_purged,
+            verifications_round_trip,
+            expired_verifications_are_purged,
+            grants_round_trip,
+            grants_are_taken_once,
+            expired_grants_are_left_out
+        );
+    };
+    (@tests $attributes:tt $db:expr; $check:ident $(, $rest:ident)*) => {
+        $crate::database_tests!(@test $attributes $db; $check);
+        $crate::database_tests!(@tests $attributes $db; $($rest),*);
+    };
+    (@tests $attributes:tt $db:expr;) => {};
+    (@test [$(#[$meta:meta])*] $db:expr; $check:ident) => {
+        #[::tokio::test]
+        $(#[$meta])*
+        async fn $check() {
+            $crate::adaptors::outputs::databases::conformance::$check(&$db).await;
+        }
+    };
+}
+
+
+/// Users are stored and read back as they are, by their id and by their contacts, until they are deleted.
+pub async fn users_round_trip<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB)
+where
+    Error: From<DB::Error>
+{
+    let user = user(1);
+    ok(db.create_user(user.clone()).await);
+    assert_eq!(ok(db.get_user_by_id(user.id).await), Some(user.clone()));
+    #[cfg(feature = "email")]
+    assert_eq!(ok(db.get_user_by_email(user.email.clone().expect("the user has an email")).await), Some(user.clone()));
+    #[cfg(feature = "phone")]
+    assert_eq!(ok(db.get_user_by_phone(user.phone.clone().expect("the user has a phone")).await), Some(user.clone()));
+    ok(db.set_user_status(user.id, Status::Suspended, Some(String::from("chargeback"))).await);
+    let metadata = Map::from_iter([(String::from("plan"), json!({"tier": "pro"}))]);
+    ok(db.set_user_metadata(user.id, Namespace::Service, metadata.clone()).await);
+    let consent = Consent { version: String::from("2026-01"), accepted_at: now() };
+    ok(db.set_user_consent(user.id, Document::TermsOfService, consent.clone()).await);
+    ok(db.set_user_password(user.id, String::from("new hash")).await);
+    let updated = ok(db.get_user_by_id(user.id).await).expect("the user exists");
+    assert_eq!((updated.status, updated.status_reason.as_deref()), (Status::Suspended, Some("chargeback")));
+    assert_eq!(updated.metadata.service, metadata);
+    assert_eq!(updated.consents.terms_of_service, Some(consent));
+    assert_eq!(updated.login, Login::Password(String::from("new hash")));
+    assert!(updated.updated_at >= user.updated_at);
+    assert_eq!(updated.created_at, user.created_at);
+    ok(db.delete_user(user.id).await);
+    assert_eq!(ok(db.get_user_by_id(user.id).await), None);
+    #[cfg(feature = "email")]
+    assert_eq!(ok(db.get_user_by_email(user.email.clone().expect("the user has an email")).await), None);
+    // deleting a user twice is not an error.
+    ok(db.delete_user(user.id).await);
+}
+
+
+/// Creating an item whose key is taken fails with `AlreadyExists` and leaves the existing item alone.
+pub async fn duplicate_keys_are_rejected<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, SessionsTable: SessionsTable<DB::Client, Item = Session>, VerificationsTable: VerificationsTable<DB::Client, Item = Verification>>>(db: &DB)
+where
+    Error: From<DB::Error>
+{
+    let user = user(1);
+    ok(db.create_user(user.clone()).await);
+    let other = User { username: String::from("other"), ..user.clone() };
+    assert_eq!(err(db.create_user(other).await), Error::DatabaseError(DatabaseError::AlreadyExists));
+    assert_eq!(ok(db.get_user_by_id(user.id).await), Some(user));
+    let session = session(1, 1);
+    ok(db.create_session(session.clone()).await);
+    assert_eq!(err(db.create_session(session).await), Error::DatabaseError(DatabaseError::AlreadyExists));
+    let verification = verification(1, now() + TimeDelta::minutes(5));
+    ok(db.create_verification_code(verification.clone()).await);
+    assert_eq!(err(db.create_verification_code(verification).await), Error::DatabaseError(DatabaseError::AlreadyExists));
+}
+
+
+/// Usernames and contacts belong to a single user: taking one that another user holds fails with `AlreadyExists`,
+/// even in another case, and the values a user gives up, by an update or by being deleted, can be taken again.
+pub async fn unique_attributes_are_enforced<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB)
+where
+    Error: From<DB::Error>
+{
+    let (first, second) = (user(1), user(2));
+    ok(db.create_user(first.clone()).await);
+    ok(db.create_user(second.clone()).await);
+    let duplicate = User { username: first.username.clone(), ..user(3) };
+    assert_eq!(err(db.create_user(duplicate).await), Error::DatabaseError(DatabaseError::AlreadyExists));
+    let duplicate = User { username: first.username.to_uppercase(), ..user(3) };
+    assert_eq!(err(db.create_user(duplicate).await), Error::DatabaseError(DatabaseError::AlreadyExists));
+    #[cfg(feature = "email")]
+    {
+        let duplicate = User { email: first.email.clone(), ..user(3) };
+        assert_eq!(err(db.create_user(duplicate).await), Error::DatabaseError(DatabaseError::AlreadyExists));
+        let duplicate = User { email: Some(Email::try_from("USER1@example.com").expect("a valid email")), ..user(3) };
+        assert_eq!(err(db.create_user(duplicate).await), Error::DatabaseError(DatabaseError::AlreadyExists));
+    }
+    assert_eq!(ok(db.get_user_by_id(id(3)).await), None);
+    let taken = Map::from_iter([(String::from("username"), json!(first.username))]);
+    assert_eq!(err(db.update_user(second.id, taken).await), Error::DatabaseError(DatabaseError::AlreadyExists));
+    assert_eq!(ok(db.get_user_by_id(second.id).await), Some(second.clone()));
+    let renamed = Map::from_iter([(String::from("username"), json!("renamed"))]);
+    ok(db.update_user(first.id, renamed).await);
+    let freed = Map::from_iter([(String::from("username"), json!(first.username))]);
+    assert_eq!(ok(db.update_user(second.id, freed).await).username, first.username);
+    // a change of case keeps the value claimed.
+    let recased = Map::from_iter([(String::from("username"), json!(first.username.to_uppercase()))]);
+    assert_eq!(ok(db.update_user(second.id, recased).await).username, first.username.to_uppercase());
+    let duplicate = User { username: first.username.clone(), ..user(3) };
+    assert_eq!(err(db.create_user(duplicate).await), Error::DatabaseError(DatabaseError::AlreadyExists));
+    #[cfg(feature = "email")]
+    {
+        let email = Email::try_from("USER2@example.com").expect("a valid email");
+        let recased = Map::from_iter([(String::from("email"), serde_json::to_value(&email).expect("an email serializes"))]);
+        assert_eq!(ok(db.update_user(second.id, recased).await).email, Some(email));
+        let duplicate = User { email: second.email.clone(), ..user(3) };
+        assert_eq!(err(db.create_user(duplicate).await), Error::DatabaseError(DatabaseError::AlreadyExists));
+    }
+    ok(db.delete_user(second.id).await);
+    ok(db.create_user(user(2)).await);
+}
+
+
+/// Writes to a user that does not exist fail with `UserNotFound` rather than creating one.
+pub async fn missing_users_are_not_found<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB)
+where
+    Error: From<DB::Error>
+{
+    let id = user(1).id;
+    let not_found = Error::DatabaseError(DatabaseError::UserNotFound);
+    let update = Map::from_iter([(String::from("fullname"), json!("Jane Doe"))]);
+    assert_eq!(err(db.update_user(id, update).await), not_found);
+    assert_eq!(err(db.set_user_status(id, Status::Suspended, None).await), not_found);
+    assert_eq!(err(db.set_user_metadata(id, Namespace::User, Map::new()).await), not_found);
+    assert_eq!(err(db.set_user_consent(id, Document::PrivacyPolicy, Consent { version: String::from("1"), accepted_at: now() }).await), not_found);
+    assert_eq!(err(db.set_user_password(id, String::from("hash")).await), not_found);
+    assert_eq!(ok(db.get_user_by_id(id).await), None);
+}
+
+
+/// `update_user_if` updates a user as it was read, and fails with `Changed` once it was updated since, leaving it as it is.
+pub async fn conditional_updates_fail_once_users_changed<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB)
+where
+    Error: From<DB::Error>
+{
+    // read an hour ago, as the coarsest adaptor keeps the time of updates to the second.
+    let read = now() - TimeDelta::hours(1);
+    let user = User { updated_at: read, ..user(1) };
+    ok(db.create_user(user.clone()).await);
+    let rename = |fullname: &str| Map::from_iter([(String::from("fullname"), json!(fullname))]);
+    let renamed = ok(db.update_user_if(user.id, rename("First"), read).await);
+    assert_eq!(renamed.fullname, "First");
+    let changed = Error::DatabaseError(DatabaseError::Changed);
+    assert_eq!(err(db.update_user_if(user.id, rename("Second"), read).await), changed);
+    let username = Map::from_iter([(String::from("username"), json!("renamed"))]);
+    assert_eq!(err(db.update_user_if(user.id, username, read).await), changed);
+    assert_eq!(ok(db.get_user_by_id(user.id).await), Some(renamed.clone()));
+    assert_eq!(ok(db.update_user_if(user.id, rename("Second"), renamed.updated_at).await).fullname, "Second");
+    assert_eq!(err(db.update_user_if(id(2), rename("Second"), read).await), Error::DatabaseError(DatabaseError::UserNotFound));
+}
+
+
+/// `update_user` sets the fields of the patch, clears the ones set to `null`, leaves the others alone
+/// and rejects patches of immutable or unknown fields without writing anything.
+pub async fn updates_follow_patch_semantics<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB)
+where
+    Error: From<DB::Error>
+{
+    let user = User { profile: Some(String::from("profile")), ..user(1) };
+    ok(db.create_user(user.clone()).await);
+    let update = Map::from_iter([(String::from("fullname"), json!("Jane Doe")), (String::from("profile"), Value::Null)]);
+    let updated = ok(db.update_user(user.id, update).await);
+    assert_eq!((updated.fullname.as_str(), updated.profile.as_deref()), ("Jane Doe", None));
+    assert_eq!(updated.username, user.username);
+    assert!(updated.updated_at >= user.updated_at);
+    assert_eq!(ok(db.get_user_by_id(user.id).await), Some(updated.clone()));
+    let immutable = Map::from_iter([(String::from("status"), json!("suspended"))]);
+    assert_eq!(err(db.update_user(user.id, immutable).await), Error::DatabaseError(DatabaseError::ConversionError(ConversionError::ImmutableField("status"))));
+    let unknown = Map::from_iter([(String::from("role"), json!("admin")), (String::from("fullname"), json!("Mallory"))]);
+    assert_eq!(err(db.update_user(user.id, unknown).await), Error::DatabaseError(DatabaseError::ConversionError(ConversionError::UnknownField(String::from("role")))));
+    assert_eq!(ok(db.get_user_by_id(user.id).await), Some(updated));
+}
+
+
+/// Lookups by an index find a user by its current value only, once it is updated or the user is deleted.
+pub async fn indexes_follow_updates<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, SessionsTable: SessionsTable<DB::Client, Item = Session>>>(db: &DB)
+where
+    Error: From<DB::Error>
+{
+    let user = user(1);
+    ok(db.create_user(user.clone()).await);
+    #[cfg(feature = "email")]
+    {
+        let email = Email::try_from("jane@example.org").expect("a valid email");
+        let update = Map::from_iter([(String::from("email"), serde_json::to_value(&email).expect("an email serializes"))]);
+        let updated = ok(db.update_user(user.id, update).await);
+        assert_eq!(ok(db.get_user_by_email(user.email.clone().expect("the user has an email")).await), None);
+        assert_eq!(ok(db.get_user_by_email(email).await), Some(updated));
+    }
+    #[cfg(feature = "phone")]
+    {
+        let phone = Phone::try_from(String::from("+254798765432")).expect("a valid phone");
+        let update = Map::from_iter([(String::from("phone"), serde_json::to_value(&phone).expect("a phone serializes"))]);
+        let updated = ok(db.update_user(user.id, update).await);
+        assert_eq!(ok(db.get_user_by_phone(user.phone.clone().expect("the user has a phone")).await), None);
+        assert_eq!(ok(db.get_user_by_phone(phone).await), Some(updated));
+    }
+    let (first, second) = (session(1, 1), session(2, 1));
+    ok(db.create_session(first.clone()).await);
+    ok(db.create_session(second.clone()).await);
+    let mut sessions = ok(db.get_sessions_by_user_id(user.id).await);
+    sessions.sort_by_key(|session| session.id.to_hex());
+    assert_eq!(sessions, vec![first.clone(), second.clone()]);
+    ok(db.delete_session(first.id).await);
+    assert_eq!(ok(db.get_sessions_by_user_id(user.id).await), vec![second]);
+}
+
+
+/// Users are found by their username, and by the usernames they had once they change it.
+pub async fn users_are_found_by_username<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB)
+where
+    Error: From<DB::Error>
+{
+    let user = user(1);
+    ok(db.create_user(user.clone()).await);
+    assert_eq!(ok(db.get_user_by_username(user.username.clone()).await), Some(user.clone()));
+    assert_eq!(ok(db.get_users_by_previous_username(user.username.clone()).await), vec![]);
+    let update = Map::from_iter([
+        (String::from("username"), json!("renamed")),
+        (String::from("previous_username"), json!(user.username)),
+        (String::from("username_changed_at"), json!(now())),
+    ]);
+    let renamed = ok(db.update_user(user.id, update).await);
+    assert_eq!(renamed.previous_username.as_deref(), Some(user.username.as_str()));
+    assert_eq!(ok(db.get_user_by_username(user.username.clone()).await), None);
+    assert_eq!(ok(db.get_user_by_username(String::from("renamed")).await), Some(renamed.clone()));
+    assert_eq!(ok(db.get_users_by_previous_username(user.username.clone()).await), vec![renamed]);
+}
+
+
+/// Guests are stored without contacts, any number of them, and are given their contacts when they upgrade.
+pub async fn guests_have_no_contacts<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB)
+where
+    Error: From<DB::Error>
+{
+    let guest = |n: u8| User {
+        #[cfg(feature = "email")]
+        email: None,
+        #[cfg(feature = "phone")]
+        phone: None,
+        login: Login::Guest(now() + TimeDelta::days(30)),
+        ..user(n)
+    };
+    let (first, second) = (guest(1), guest(2));
+    ok(db.create_user(first.clone()).await);
+    ok(db.create_user(second.clone()).await);
+    assert_eq!(ok(db.get_user_by_id(first.id).await), Some(first.clone()));
+    assert_eq!(ok(db.get_user_by_id(second.id).await), Some(second));
+    #[cfg(feature = "email")]
+    {
+        let email = Email::try_from("jane@example.org").expect("a valid email");
+        let update = Map::from_iter([(String::from("email"), serde_json::to_value(&email).expect("an email serializes"))]);
+        let upgraded = ok(db.update_user(first.id, update).await);
+        assert_eq!(upgraded.email.as_ref(), Some(&email));
+        assert_eq!(ok(db.get_user_by_email(email).await), Some(upgraded));
+    }
+    ok(db.set_user_password(first.id, String::from("hash")).await);
+    let upgraded = ok(db.get_user_by_id(first.id).await).expect("the user exists");
+    assert_eq!(upgraded.login, Login::Password(String::from("hash")));
+}
+
+
+/// Following `next` lists every user exactly once, in pages of at most `limit` users.
+pub async fn users_are_listed_in_pages<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB)
+where
+    Error: From<DB::Error>
+{
+    let users = (1..=5).map(user).collect::<Vec<_>>();
+    for user in &users {
+        ok(db.create_user(user.clone()).await);
+    }
+    let (mut listed, mut after) = (Vec::new(), None);
+    loop {
+        let page = ok(db.list_users(after, 2).await);
+        assert!(page.items.len() <= 2, "a page holds at most `limit` users");
+        listed.extend(page.items);
+        match page.next {
+            Some(next) => after = Some(next),
+            None => break,
+        }
+    }
+    listed.sort_by_key(|user| user.id.to_hex());
+    assert_eq!(listed, users);
+}
+
+
+/// Following `next` finds every user matching a filter exactly once, and only them.
+pub async fn users_are_searched<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB)
+where
+    Error: From<DB::Error>
+{
+    let users = (1..=6).map(|n| User { status: if n % 2 == 0 { Status::Suspended } else { Status::Active }, ..user(n) }).collect::<Vec<_>>();
+    for user in &users {
+        ok(db.create_user(user.clone()).await);
+    }
+    let search = async |filter: UserFilter| {
+        let (mut found, mut after) = (Vec::new(), None);
+        loop {
+            let page = ok(db.search_users(filter.clone(), after, 2).await);
+            assert!(page.items.len() <= 2, "a page holds at most `limit` users");
+            found.extend(page.items);
+            match page.next {
+                Some(next) => after = Some(next),
+                None => break,
+            }
+        }
+        found.sort_by_key(|user| user.id.to_hex());
+        found
+    };
+    let suspended = users.iter().filter(|user| user.status == Status::Suspended).cloned().collect::<Vec<_>>();
+    assert_eq!(search(UserFilter { status: Some(Status::Suspended), ..Default::default() }).await, suspended);
+    assert_eq!(search(UserFilter { username: Some(String::from("user3")), ..Default::default() }).await, vec![users[2].clone()]);
+    assert_eq!(search(UserFilter { created_before: Some(users[0].created_at), ..Default::default() }).await, vec![]);
+    #[cfg(feature = "email")]
+    assert_eq!(search(UserFilter { email: Some(String::from("user5@")), ..Default::default() }).await, vec![users[4].clone()]);
+    assert_eq!(search(UserFilter::default()).await, users);
+}
+
+
+/// Sessions are stored and read back as they are, their refresh token rotates and their activity is tracked.
+pub async fn sessions_round_trip<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(db: &DB)
+where
+    Error: From<DB::Error>
+{
+    let session = session(1, 1);
+    ok(db.create_session(session.clone()).await);
+    assert_eq!(ok(db.get_session_by_id(session.id).await), Some(session.clone()));
+    let refresh_token_id = id(9);
+    ok(db.change_current_refresh_token(session.id, refresh_token_id).await);
+    let rotated = ok(db.get_session_by_id(session.id).await).expect("the session exists");
+    assert_eq!((rotated.refresh_token_id, rotated.previous_refresh_token_id), (refresh_token_id, Some(session.refresh_token_id)));
+    assert_eq!((rotated.created_at, &rotated.device), (session.created_at, &session.device));
+    ok(db.touch_session(session.id).await);
+    let touched = ok(db.get_session_by_id(session.id).await).expect("the session exists");
+    assert!(touched.last_active_at > session.last_active_at);
+    let not_found = Error::DatabaseError(DatabaseError::SessionNotFound);
+    assert_eq!(err(db.touch_session(id(8)).await), not_found);
+    assert_eq!(err(db.change_current_refresh_token(id(8), refresh_token_id).await), not_found);
+    assert_eq!(ok(db.get_session_by_id(id(8)).await), None);
+    ok(db.delete_session(session.id).await);
+    assert_eq!(ok(db.get_session_by_id(session.id).await), None);
+    // deleting a session twice is not an error.
+    ok(db.delete_session(session.id).await);
+}
+
+
+/// Sessions idle or created for too long are purged, the others are kept.
+pub async fn expired_sessions_are_purged<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(db: &DB)
+where
+    Error: From<DB::Error>
+{
+    let active = session(1, 1);
+    let idle = Session { last_active_at: now() - TimeDelta::days(2), ..session(2, 1) };
+    let old = Session { created_at: now() - TimeDelta::days(40), ..session(3, 1) };
+    for session in [&active, &idle, &old] {
+        ok(db.create_session(session.clone()).await);
+    }
+    let mut purged = ok(db.purge_expired_sessions(now() - TimeDelta::days(1), now() - TimeDelta::days(30)).await);
+    purged.sort_by_key(|id| id.to_hex());
+    assert_eq!(purged, vec![idle.id, old.id]);
+    assert_eq!(ok(db.get_sessions_by_user_id(active.user_id).await), vec![active]);
+}
+
+
+/// Verifications are found by the contact they were sent to until they are deleted.
+pub async fn verifications_round_trip<DB: Database<VerificationsTable: VerificationsTable<DB::Client, Item = Verification>>>(db: &DB)
+where
+    Error: From<DB::Error>
+{
+    let by_email = verification(1, now() + TimeDelta::minutes(5));
+    let phone = Phone::try_from(String::from("+254712345672")).expect("a valid phone");
+    let by_phone = Verification { owner_contact: Either::Left(phone.clone()), ..verification(2, now() + TimeDelta::minutes(5)) };
+    ok(db.create_verification_code(by_email.clone()).await);
+    ok(db.create_verification_code(by_phone.clone()).await);
+    let email = Email::try_from("user1@example.com").expect("a valid email");
+    assert_eq!(ok(db.get_verification_by_email(email.clone()).await), Some(by_email.clone()));
+    assert_eq!(ok(db.get_verification_by_phone(phone).await), Some(by_phone));
+    ok(db.delete_verification(by_email.id).await);
+    assert_eq!(ok(db.get_verification_by_email(email).await), None);
+}
+
+
+/// Verifications that expired are purged, the others are kept.
+pub async fn expired_verifications_are_purged<DB: Database<VerificationsTable: VerificationsTable<DB::Client, Item = Verification>>>(db: &DB)
+where
+    Error: From<DB::Error>
+{
+    let expired = verification(1, now() - TimeDelta::minutes(5));
+    let pending = verification(2, now() + TimeDelta::minutes(5));
+    ok(db.create_verification_code(expired).await);
+    ok(db.create_verification_code(pending.clone()).await);
+    assert_eq!(ok(db.purge_expired_verifications(now()).await), 1);
+    let email = Email::try_from("user2@example.com").expect("a valid email");
+    assert_eq!(ok(db.get_verification_by_email(email).await), Some(pending));
+}
+
+
+/// Grants are found by their id and by their user until they are deleted.
+pub async fn grants_round_trip<DB: Database<GrantsTable: GrantsTable<DB::Client, Item = GrantRecord>>>(db: &DB)
+where
+    Error: From<DB::Error>
+{
+    let code = grant("code", GrantKind::AuthorizationCode, Some(1), now() + TimeDelta::minutes(5));
+    let consent = grant("consent", GrantKind::Consent, Some(1), now() + TimeDelta::days(30));
+    let device = grant("device", GrantKind::DeviceCode, None, now() + TimeDelta::minutes(5));
+    for grant in [&code, &consent, &device] {
+        ok(db.create_grant(grant.clone()).await);
+    }
+    assert_eq!(err(db.create_grant(code.clone()).await), Error::DatabaseError(DatabaseError::AlreadyExists));
+    assert_eq!(ok(db.get_grant_by_id(code.id.clone()).await), Some(code.clone()));
+    assert_eq!(ok(db.get_grant_by_id(device.id.clone()).await), Some(device));
+    let mut grants = ok(db.get_grants_by_user_id(id(1)).await);
+    grants.sort_by(|a, b| a.id.cmp(&b.id));
+    assert_eq!(grants, vec![code.clone(), consent.clone()]);
+    ok(db.delete_grant(code.id.clone()).await);
+    assert_eq!(ok(db.get_grant_by_id(code.id).await), None);
+    assert_eq!(ok(db.get_grants_by_user_id(id(1)).await), vec![consent]);
+}
+
+
+/// Taking a grant deletes it, so that an authorization code is redeemed once.
+pub async fn grants_are_taken_once<DB: Database<GrantsTable: GrantsTable<DB::Client, Item = GrantRecord>>>(db: &DB)
+where
+    Error: From<DB::Error>
+{
+    let code = grant("code", GrantKind::AuthorizationCode, Some(1), now() + TimeDelta::minutes(5));
+    ok(db.create_grant(code.clone()).await);
+    assert_eq!(ok(db.take_grant(code.id.clone()).await), Some(code.clone()));
+    assert_eq!(ok(db.take_grant(code.id.clone()).await), None);
+    assert_eq!(ok(db.get_grant_by_id(code.id).await), None);
+}
+
+
+/// Expired grants are never read, and are purged with the others kept.
+pub async fn expired_grants_are_left_out<DB: Database<GrantsTable: GrantsTable<DB::Client, Item = GrantRecord>>>(db: &DB)
+where
+    Error: From<DB::Error>
+{
+    let expired = grant("expired", GrantKind::AuthorizationCode, Some(1), now() - TimeDelta::minutes(5));
+    let pending = grant("pending", GrantKind::AuthorizationCode, Some(1), now() + TimeDelta::minutes(5));
+    ok(db.create_grant(expired.clone()).await);
+    ok(db.create_grant(pending.clone()).await);
+    assert_eq!(ok(db.get_grant_by_id(expired.id.clone()).await), None);
+    assert_eq!(ok(db.get_grants_by_user_id(id(1)).await), vec![pending.clone()]);
+    assert!(ok(db.purge_expired_grants(now()).await) <= 1);
+    assert_eq!(ok(db.take_grant(expired.id).await), None);
+    assert_eq!(ok(db.get_grant_by_id(pending.id.clone()).await), Some(pending));
+}
+
+
+fn ok<T, E>(result: Result<T, E>) -> T
+where
+    Error: From<E>
+{
+    match result.map_err(Error::from) {
+        Ok(value) => value,
+        Err(err) => panic!("the database failed: {:?}", err),
+    }
+}
+
+
+fn err<T: std::fmt::Debug, E>(result: Result<T, E>) -> Error
+where
+    Error: From<E>
+{
+    match result.map_err(Error::from) {
+        Ok(value) => panic!("the database succeeded where it should have failed: {:?}", value),
+        Err(err) => err,
+    }
+}
+
+
+/// The current time, in whole seconds.
+fn now() -> DateTime<Utc> {
+    DateTime::from_timestamp(Utc::now().timestamp(), 0).expect("now is a valid timestamp")
+}
+
+
+fn user(n: u8) -> User {
+    let now = now();
+    let fixture = UserFixture::new().id(id(n)).username(&format!("user{}", n)).fullname("fullname");
+    #[cfg(feature = "email")]
+    let fixture = fixture.email(&format!("user{}@example.com", n));
+    #[cfg(feature = "phone")]
+    let fixture = fixture.phone(&format!("+25471234567{}", n));
+    User { created_at: now, updated_at: now, ..fixture.password("hash").build() }
+}
+
+
+fn session(n: u8, user: u8) -> Session {
+    let now = now() - TimeDelta::hours(1);
+    let device = Device::new(Some([192, 0, 2, 1].into()), Some(String::from("curl/8.0")));
+    Session { id: id(n), user_id: id(user), refresh_token_id: id(n + 100), previous_refresh_token_id: None, device, created_at: now, updated_at: now, last_active_at: now }
+}
+
+
+fn verification(n: u8, expires: DateTime<Utc>) -> Verification {
+    let email = Email::try_from(format!("user{}@example.com", n).as_str()).expect("a valid email");
+    Verification { owner_contact: Either::Right(email), id: id(n), code: 123456, expires, created_at: now(), updated_at: now() }
+}
+
+
+fn grant(key: &str, kind: GrantKind, user: Option<u8>, expires: DateTime<Utc>) -> GrantRecord {
+    let data = Map::from_iter([(String::from("redirect_uri"), json!("https://app.example.com/callback"))]);
+    GrantRecord { id: String::from(key), kind, client_id: String::from("web"), user_id: user.map(id), scope: String::from("openid profile"), data, expires, created_at: now() }
+}
@@ -1,23 +1,120 @@
-use crate::ports::outputs::database::Database;
+use crate::ports::outputs::database::{Database, tables::GrantsTable};
+use crate::config::DynamoDBConfig;
 use crate::types::DatabaseError;
+use aws_sdk_dynamodb::config::{retry::RetryConfig, timeout::TimeoutConfig};
+use aws_sdk_dynamodb::config::{Credentials, Region};
 use aws_sdk_dynamodb::Client;
+use retries::RetryCounter;
+use super::Instrumented;
+use std::time::Duration;
 
 pub mod tables;
+pub mod schema;
+mod retries;
 
 
-pub struct DynamoDB {
+/// The database of the DynamoDB tables of `DynamoDBConfig`. The grants may be kept elsewhere, see [`DynamoDB::with_grants`].
+pub struct DynamoDB<Grants = tables::GrantsTable> {
     client: Client,
-    users_table: tables::UsersTable,
-    sessions_table: tables::SessionsTable,
-    verifications_table: tables::VerificationsTable,
+    /// the client reads are sent to, when they don't go to `client`.
+    replica: Option<Client>,
+    users_table: Instrumented<tables::UsersTable>,
+    sessions_table: Instrumented<tables::SessionsTable>,
+    verifications_table: Instrumented<tables::VerificationsTable>,
+    grants_table: Instrumented<Grants>,
+    slow_query_threshold: Duration,
 }
 
-impl Database for DynamoDB {
+
+impl DynamoDB {
+    pub fn new(client: Client, config: &DynamoDBConfig, slow_query_threshold: Duration) -> Self {
+        let users_table = tables::UsersTable { name: config.users_table.clone(), unique_table: config.unique_table.clone(), outbox_table: config.outbox_table.clone() };
+        let sessions_table = tables::SessionsTable { name: config.sessions_table.clone() };
+        let verifications_table = tables::VerificationsTable { name: config.verifications_table.clone() };
+        let grants_table = tables::GrantsTable { name: config.grants_table.clone() };
+        Self {
+            client,
+            replica: None,
+            users_table: Instrumented::new(users_table, "users", slow_query_threshold),
+            sessions_table: Instrumented::new(sessions_table, "sessions", slow_query_threshold),
+            verifications_table: Instrumented::new(verifications_table, "verifications", slow_query_threshold),
+            grants_table: Instrumented::new(grants_table, "grants", slow_query_threshold),
+            slow_query_threshold,
+        }
+    }
+
+    /// Builds a client from the AWS environment, with the endpoint, region and credentials of `config` when they are set.
+    ///
+    /// Calls are retried in the adaptive mode of the SDK, which backs off exponentially and, once DynamoDB throttles,
+    /// rate limits the calls that follow too. Every retry is counted by [`RetryCounter`].
+    pub async fn client(config: &DynamoDBConfig) -> Client {
+        Self::build(config, config.endpoint.as_ref(), config.region.as_ref()).await
+    }
+
+    /// Builds the client of `read_endpoint` and `read_region` like [`DynamoDB::client`], if either is set.
+    pub async fn replica(config: &DynamoDBConfig) -> Option<Client> {
+        if config.read_endpoint.is_none() && config.read_region.is_none() {
+            return None;
+        }
+        let endpoint = config.read_endpoint.as_ref().or(config.endpoint.as_ref());
+        let region = config.read_region.as_ref().or(config.region.as_ref());
+        Some(Self::build(config, endpoint, region).await)
+    }
+
+    async fn build(config: &DynamoDBConfig, endpoint: Option<&String>, region: Option<&String>) -> Client {
+        let shared = aws_config::load_from_env().await;
+        let retry = RetryConfig::adaptive()
+            .with_max_attempts(config.max_attempts)
+            .with_initial_backoff(Duration::from_millis(config.initial_backoff_ms))
+            .with_max_backoff(Duration::from_millis(config.max_backoff_ms));
+        let timeout = TimeoutConfig::builder().operation_timeout(Duration::from_millis(config.operation_timeout_ms)).build();
+        let mut builder = aws_sdk_dynamodb::config::Builder::from(&shared)
+            .retry_config(retry)
+            .timeout_config(timeout)
+            .interceptor(RetryCounter::new());
+        if let Some(endpoint) = endpoint {
+            builder = builder.endpoint_url(endpoint);
+        }
+        if let Some(region) = region {
+            builder = builder.region(Region::new(region.clone()));
+        }
+        if let Some(credentials) = &config.credentials {
+            let credentials = Credentials::new(&credentials.access_key_id, &credentials.secret_access_key, credentials.session_token.clone(), None, "hiveguard");
+            builder = builder.credentials_provider(credentials);
+        }
+        Client::from_conf(builder.build())
+    }
+}
+
+
+impl<Grants> DynamoDB<Grants> {
+    /// Sends the reads that don't have to see the latest writes through `replica`. see [`Database::replica`].
+    pub fn with_replica(self, replica: Client) -> Self {
+        Self { replica: Some(replica), ..self }
+    }
+
+    /// Keeps the grants in `grants` instead of the grants table. eg in [`RedisGrants`](super::RedisGrants), which expires them on time.
+    pub fn with_grants<G>(self, grants: G) -> DynamoDB<G> {
+        DynamoDB {
+            client: self.client,
+            replica: self.replica,
+            users_table: self.users_table,
+            sessions_table: self.sessions_table,
+            verifications_table: self.verifications_table,
+            grants_table: Instrumented::new(grants, "grants", self.slow_query_threshold),
+            slow_query_threshold: self.slow_query_threshold,
+        }
+    }
+}
+
+
+impl<Grants: GrantsTable<Client, Error: Into<DatabaseError>>> Database for DynamoDB<Grants> {
     type Client = Client;
     type Error = DatabaseError;
-    type UsersTable = tables::UsersTable;
-    type SessionsTable = tables::SessionsTable;
-    type VerificationsTable = tables::VerificationsTable;
+    type UsersTable = Instrumented<tables::UsersTable>;
+    type SessionsTable = Instrumented<tables::SessionsTable>;
+    type VerificationsTable = Instrumented<tables::VerificationsTable>;
+    type GrantsTable = Instrumented<Grants>;
     
     fn users_table(&self) ->  &Self::UsersTable {
         &self.users_table
@@ -31,7 +128,98 @@ impl Database for DynamoDB {
         &self.verifications_table
     }
 
+    fn grants_table(&self) -> &Self::GrantsTable {
+        &self.grants_table
+    }
+
     fn client(&self) -> &Self::Client {
         &self.client
     }
-}
\ No newline at end of file
+
+    fn replica(&self) -> &Self::Client {
+        self.replica.as_ref().unwrap_or(&self.client)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::StaticCredentials;
+    use crate::types::{Id, TenantId, PiiCipher};
+    use std::collections::HashMap;
+
+    /// A database of new tables in the DynamoDB Local at `HIVEGUARD_TEST_DYNAMODB`, `http://localhost:8000` unless it is set.
+    /// eg `docker run -p 8000:8000 amazon/dynamodb-local`
+    async fn local() -> DynamoDB {
+        let config = DynamoDBConfig {
+            endpoint: Some(std::env::var("HIVEGUARD_TEST_DYNAMODB").unwrap_or_else(|_| String::from("http://localhost:8000"))),
+            region: Some(String::from("us-east-1")),
+            credentials: Some(StaticCredentials { access_key_id: "local".into(), secret_access_key: "local".into(), session_token: None }),
+            ..Default::default()
+        };
+        // every test gets tables of its own, named as those of a tenant.
+        let config = config.for_tenant(&TenantId::try_from(format!("test-{}", Id::new().to_hex())).unwrap());
+        let client = DynamoDB::client(&config).await;
+        schema::create_tables(&client, &schema::TableSchema::all(&config)).await.unwrap();
+        DynamoDB::new(client, &config, Duration::from_secs(1))
+    }
+
+    fn cipher() -> PiiCipher {
+        let keks = HashMap::from([(String::from("2026-10"), [1; 32])]);
+        PiiCipher::new(keks, String::from("2026-10"), [2; 32]).unwrap()
+    }
+
+    crate::database_tests!(#[ignore = "needs DynamoDB Local, see `local`"] local().await);
+
+    #[tokio::test]
+    #[ignore = "needs DynamoDB Local, see `local`"]
+    async fn unique_attributes_are_enforced_with_a_cipher() {
+        PiiCipher::install_for_test(cipher());
+        crate::adaptors::outputs::databases::conformance::unique_attributes_are_enforced(&local().await).await;
+    }
+
+    #[cfg(feature = "email")]
+    #[tokio::test]
+    #[ignore = "needs DynamoDB Local, see `local`"]
+    async fn blind_indexes_are_not_searched_as_text() {
+        use crate::testing::{id, UserFixture};
+        use crate::types::UserFilter;
+        let db = local().await;
+        let clear = UserFixture::new().id(id(1)).username("jane").email("jane@acme.com").build();
+        db.create_user(clear).await.unwrap();
+        PiiCipher::install_for_test(cipher());
+        let sealed = UserFixture::new().id(id(2)).username("john").email("john@acme.com").build();
+        db.create_user(sealed).await.unwrap();
+        let search = async |email: &str| {
+            let filter = UserFilter { email: Some(String::from(email)), ..Default::default() };
+            db.search_users(filter, None, 10).await.unwrap().items.into_iter().map(|user| user.id).collect::<Vec<_>>()
+        };
+        assert_eq!(search("hmac:").await, vec![]);
+        assert_eq!(search("jane@").await, vec![id(1)]);
+        assert_eq!(search("john@acme.com").await, vec![id(2)]);
+        assert_eq!(search("john@").await, vec![]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DynamoDB Local, see `local`"]
+    async fn users_are_written_along_with_their_event() {
+        use crate::adaptors::outputs::outbox::dynamodb::DynamoDBOutbox;
+        use crate::ports::outputs::outbox::Outbox;
+        use crate::testing::{id, UserFixture};
+        use crate::types::{DomainEvent, DomainEventKind, DatabaseError};
+        use serde_json::{Map, Value};
+        let db = local().await;
+        let outbox = DynamoDBOutbox::new(db.client.clone(), db.users_table.inner().outbox_table.clone());
+        let created = DomainEvent::new(DomainEventKind::UserCreated { user_id: id(1), username: String::from("jane") });
+        db.create_user_with_event(UserFixture::new().id(id(1)).username("jane").build(), created.clone()).await.unwrap();
+        let taken = DomainEvent::new(DomainEventKind::UserCreated { user_id: id(2), username: String::from("jane") });
+        assert_eq!(db.create_user_with_event(UserFixture::new().id(id(2)).username("jane").build(), taken).await, Err(DatabaseError::AlreadyExists));
+        let rename = |username: &str| Map::from_iter([(String::from("username"), Value::String(String::from(username)))]);
+        let renamed = DomainEvent::new(DomainEventKind::UserRenamed { user_id: id(1), from: String::from("jane"), to: String::from("janet") });
+        db.update_user_with_event(id(1), rename("janet"), renamed.clone()).await.unwrap();
+        let missing = DomainEvent::new(DomainEventKind::UserRenamed { user_id: id(3), from: String::from("john"), to: String::from("johnny") });
+        assert_eq!(db.update_user_with_event(id(3), rename("johnny"), missing).await, Err(DatabaseError::UserNotFound));
+        assert_eq!(outbox.pending(10).await.unwrap(), vec![created, renamed]);
+    }
+}
@@ -0,0 +1,45 @@
+use aws_sdk_dynamodb::config::{interceptors::BeforeTransmitInterceptorContextRef, ConfigBag, Intercept, RuntimeComponents};
+use aws_smithy_runtime_api::client::orchestrator::Metadata;
+use aws_smithy_runtime_api::client::retries::RequestAttempts;
+use aws_smithy_runtime_api::box_error::BoxError;
+
+
+/// Logs every retry of a DynamoDB call, throttled or otherwise failed.
+///
+/// With the `otlp` feature the retries are also counted in the `db.client.retries` counter, by operation.
+#[derive(Debug)]
+pub struct RetryCounter {
+    #[cfg(feature = "otlp")]
+    retries: opentelemetry::metrics::Counter<u64>,
+}
+
+
+impl RetryCounter {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "otlp")]
+            retries: opentelemetry::global::meter("hiveguard")
+                .u64_counter("db.client.retries")
+                .with_description("retries of database calls")
+                .build(),
+        }
+    }
+}
+
+
+impl Intercept for RetryCounter {
+    fn name(&self) -> &'static str {
+        "RetryCounter"
+    }
+
+    fn read_before_attempt(&self, _context: &BeforeTransmitInterceptorContextRef<'_>, _components: &RuntimeComponents, cfg: &mut ConfigBag) -> Result<(), BoxError> {
+        let attempt = cfg.load::<RequestAttempts>().map_or(1, RequestAttempts::attempts);
+        if attempt > 1 {
+            let operation = cfg.load::<Metadata>().map_or("unknown", Metadata::name);
+            tracing::warn!(operation, attempt, "retrying a DynamoDB call");
+            #[cfg(feature = "otlp")]
+            self.retries.add(1, &[opentelemetry::KeyValue::new("db.operation.name", operation.to_string())]);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+use aws_sdk_dynamodb::types::{AttributeDefinition, BillingMode, GlobalSecondaryIndex, KeySchemaElement, KeyType, Projection, ProjectionType};
+use aws_sdk_dynamodb::types::{ScalarAttributeType, TableDescription, TimeToLiveDescription, TimeToLiveSpecification, TimeToLiveStatus};
+use aws_sdk_dynamodb::operation::describe_table::DescribeTableError;
+use aws_sdk_dynamodb::error::SdkError;
+use crate::types::{ConfigIssue, DatabaseError};
+use aws_sdk_dynamodb::client::Waiters;
+use crate::config::DynamoDBConfig;
+use aws_sdk_dynamodb::Client;
+use std::time::Duration;
+
+
+/// how long a created table has to become active.
+const CREATE_TIMEOUT: Duration = Duration::from_secs(120);
+
+
+/// A table the DynamoDB adaptors expect, keyed by `id`, binary but for the lookup items of unique values, with a `<attribute>-index` global secondary index per looked up attribute.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableSchema {
+    /// the setting naming the table. eg `dynamodb.users_table`
+    pub setting: &'static str,
+    pub name: String,
+    pub key: (&'static str, ScalarAttributeType),
+    pub indexes: Vec<(&'static str, ScalarAttributeType)>,
+    /// the attribute holding the time items expire at, in seconds since the epoch.
+    pub ttl: Option<&'static str>,
+}
+
+
+impl TableSchema {
+    /// The tables of `config`, with the indexes of the contacts the build has.
+    pub fn all(config: &DynamoDBConfig) -> Vec<Self> {
+        let contacts = [("email", cfg!(feature = "email")), ("phone", cfg!(feature = "phone"))]
+            .into_iter()
+            .filter_map(|(contact, built)| built.then_some((contact, ScalarAttributeType::S)))
+            .collect::<Vec<_>>();
+        let table = |setting, name: &String, indexes, ttl| Self { setting, name: name.clone(), key: ("id", ScalarAttributeType::B), indexes, ttl };
+        let usernames = [("username", ScalarAttributeType::S), ("previous_username", ScalarAttributeType::S)];
+        vec![
+            table("dynamodb.users_table", &config.users_table, contacts.iter().cloned().chain(usernames).collect(), None),
+            table("dynamodb.sessions_table", &config.sessions_table, vec![("user_id", ScalarAttributeType::B)], None),
+            table("dynamodb.verifications_table", &config.verifications_table, contacts, Some("expires")),
+            Self { key: ("id", ScalarAttributeType::S), ..table("dynamodb.grants_table", &config.grants_table, vec![("user_id", ScalarAttributeType::B)], Some("expires")) },
+            table("dynamodb.mail_table", &config.mail_table, Vec::new(), None),
+            table("dynamodb.login_history_table", &config.login_history_table, vec![("user_id", ScalarAttributeType::B)], Some("expires")),
+            table("dynamodb.outbox_table", &config.outbox_table, Vec::new(), None),
+            table("dynamodb.organisations_table", &config.organisations_table, vec![("parent", ScalarAttributeType::B)], None),
+            Self { key: ("id", ScalarAttributeType::S), ..table("dynamodb.memberships_table", &config.memberships_table, vec![("org_id", ScalarAttributeType::B), ("user_id", ScalarAttributeType::B)], None) },
+            table("dynamodb.audit_table", &config.audit_table, vec![("org_id", ScalarAttributeType::B)], None),
+            Self { key: ("id", ScalarAttributeType::S), ..table("dynamodb.keys_table", &config.keys_table, Vec::new(), None) },
+            Self { key: ("id", ScalarAttributeType::S), ..table("dynamodb.unique_table", &config.unique_table, Vec::new(), None) },
+        ]
+    }
+
+    /// How `table` and its time to live differ from the schema.
+    pub fn differences(&self, table: &TableDescription, ttl: Option<&TimeToLiveDescription>) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+        let mut issue = |message: String| issues.push(ConfigIssue::new(self.setting, format!("table {}: {}", self.name, message)));
+        let attributes = table.attribute_definitions();
+        let typed = |(name, kind): &(&str, ScalarAttributeType)| attributes.iter().any(|attribute| attribute.attribute_name == *name && attribute.attribute_type == *kind);
+        if !is_hash_key(table.key_schema(), self.key.0) || !typed(&self.key) {
+            issue(format!("must be keyed by the {} attribute `{}` alone", self.key.1.as_str(), self.key.0));
+        }
+        for index in &self.indexes {
+            let name = format!("{}-index", index.0);
+            let found = table.global_secondary_indexes().iter().find(|found| found.index_name() == Some(name.as_str()));
+            if !found.is_some_and(|found| is_hash_key(found.key_schema(), index.0)) || !typed(index) {
+                issue(format!("must have the global secondary index `{}` keyed by the {} attribute `{}`", name, index.1.as_str(), index.0));
+            }
+        }
+        if let Some(attribute) = self.ttl {
+            let enabled = ttl.is_some_and(|ttl| ttl.attribute_name() == Some(attribute) && matches!(ttl.time_to_live_status(), Some(TimeToLiveStatus::Enabled | TimeToLiveStatus::Enabling)));
+            if !enabled {
+                issue(format!("must have its time to live on `{}`", attribute));
+            }
+        }
+        issues
+    }
+
+    async fn describe(&self, client: &Client) -> Result<Option<TableDescription>, DatabaseError> {
+        match client.describe_table().table_name(&self.name).send().await {
+            Ok(output) => Ok(output.table),
+            Err(SdkError::ServiceError(err)) if matches!(err.err(), DescribeTableError::ResourceNotFoundException(_)) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn create(&self, client: &Client) -> Result<(), DatabaseError> {
+        let mut attributes = vec![&self.key];
+        attributes.extend(&self.indexes);
+        let mut request = client.create_table()
+            .table_name(&self.name)
+            .billing_mode(BillingMode::PayPerRequest)
+            .key_schema(hash_key(self.key.0)?);
+        for (name, kind) in attributes {
+            request = request.attribute_definitions(AttributeDefinition::builder().attribute_name(*name).attribute_type(kind.clone()).build()?);
+        }
+        for (name, _) in &self.indexes {
+            let index = GlobalSecondaryIndex::builder()
+                .index_name(format!("{}-index", name))
+                .key_schema(hash_key(name)?)
+                .projection(Projection::builder().projection_type(ProjectionType::All).build())
+                .build()?;
+            request = request.global_secondary_indexes(index);
+        }
+        request.send().await?;
+        client.wait_until_table_exists().table_name(&self.name).wait(CREATE_TIMEOUT).await.map_err(|err| DatabaseError::Internal(Box::new(err)))?;
+        if let Some(attribute) = self.ttl {
+            let specification = TimeToLiveSpecification::builder().enabled(true).attribute_name(attribute).build()?;
+            client.update_time_to_live().table_name(&self.name).time_to_live_specification(specification).send().await?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Creates the tables of `schemas` that don't exist yet, leaving the others as they are. Returns the names of the tables created.
+pub async fn create_tables(client: &Client, schemas: &[TableSchema]) -> Result<Vec<String>, DatabaseError> {
+    let mut created = Vec::new();
+    for schema in schemas {
+        if schema.describe(client).await?.is_none() {
+            tracing::info!(table = %schema.name, "creating the table");
+            schema.create(client).await?;
+            created.push(schema.name.clone());
+        }
+    }
+    Ok(created)
+}
+
+
+/// How the existing tables differ from `schemas`, a missing table being an issue of its own.
+pub async fn check_schema(client: &Client, schemas: &[TableSchema]) -> Result<Vec<ConfigIssue>, DatabaseError> {
+    let mut issues = Vec::new();
+    for schema in schemas {
+        let Some(table) = schema.describe(client).await? else {
+            issues.push(ConfigIssue::new(schema.setting, format!("table {} does not exist", schema.name)));
+            continue;
+        };
+        let ttl = match schema.ttl {
+            Some(_) => client.describe_time_to_live().table_name(&schema.name).send().await?.time_to_live_description,
+            None => None,
+        };
+        issues.extend(schema.differences(&table, ttl.as_ref()));
+    }
+    Ok(issues)
+}
+
+
+fn hash_key(attribute: &str) -> Result<KeySchemaElement, DatabaseError> {
+    Ok(KeySchemaElement::builder().attribute_name(attribute).key_type(KeyType::Hash).build()?)
+}
+
+
+fn is_hash_key(key_schema: &[KeySchemaElement], attribute: &str) -> bool {
+    matches!(key_schema, [key] if key.attribute_name == attribute && key.key_type == KeyType::Hash)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_dynamodb::types::GlobalSecondaryIndexDescription;
+
+    fn sessions() -> TableSchema {
+        TableSchema::all(&DynamoDBConfig::default()).remove(1)
+    }
+
+    fn table(key: &str, index: Option<&str>) -> TableDescription {
+        let attribute = |name: &str| AttributeDefinition::builder().attribute_name(name).attribute_type(ScalarAttributeType::B).build().unwrap();
+        let mut table = TableDescription::builder().key_schema(hash_key(key).unwrap()).attribute_definitions(attribute("id")).attribute_definitions(attribute("user_id"));
+        if let Some(index) = index {
+            table = table.global_secondary_indexes(GlobalSecondaryIndexDescription::builder().index_name(index).key_schema(hash_key("user_id").unwrap()).build());
+        }
+        table.build()
+    }
+
+    #[test]
+    fn test_matching_tables_have_no_differences() {
+        assert_eq!(sessions().differences(&table("id", Some("user_id-index")), None), vec![]);
+    }
+
+    #[test]
+    fn test_differences_are_reported() {
+        let issues = sessions().differences(&table("user_id", Some("user-index")), None);
+        let messages = issues.iter().map(|issue| issue.message.as_str()).collect::<Vec<_>>();
+        assert_eq!(messages, vec![
+            "table sessions: must be keyed by the B attribute `id` alone",
+            "table sessions: must have the global secondary index `user_id-index` keyed by the B attribute `user_id`",
+        ]);
+        let verifications = TableSchema { indexes: Vec::new(), ..TableSchema::all(&DynamoDBConfig::default()).remove(2) };
+        let disabled = TimeToLiveDescription::builder().attribute_name("expires").time_to_live_status(TimeToLiveStatus::Disabled).build();
+        let issues = verifications.differences(&table("id", None), Some(&disabled));
+        assert_eq!(issues, vec![ConfigIssue::new("dynamodb.verifications_table", "table verifications: must have its time to live on `expires`")]);
+    }
+}
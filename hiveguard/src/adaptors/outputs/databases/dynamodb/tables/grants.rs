@@ -0,0 +1,90 @@
+use crate::ports::outputs::database::tables::GrantsTable as Table;
+use aws_sdk_dynamodb::types::{AttributeValue, ReturnValue};
+use crate::types::{GrantRecord, Id, DatabaseError};
+use std::collections::HashMap;
+use aws_sdk_dynamodb::Client;
+use chrono::{DateTime, Utc};
+use tracing::instrument;
+use super::delete_where;
+use macros::dynamodb;
+
+
+/// Grants keyed by the string `id`, expiring through the time to live of the table on `expires`.
+///
+/// DynamoDB deletes expired items within days rather than on time, so every read leaves them out.
+pub struct GrantsTable {
+    pub name: String,
+}
+
+
+/// Everything but the creation is implemented by hand, as the key is a string and expired grants are left out.
+#[dynamodb]
+impl Table<Client> for GrantsTable {
+    type Error = DatabaseError;
+    type Item = GrantRecord;
+
+    #[instrument(skip_all, fields(table = %self.name), err)]
+    async fn get_grant_by_id(&self, id: String, client: &Client) -> Result<Option<Self::Item>, Self::Error> {
+        let output = client.get_item().table_name(&self.name).key("id", AttributeValue::S(id)).send().await?;
+        unexpired(output.item)
+    }
+
+    #[instrument(skip_all, fields(table = %self.name), err)]
+    async fn get_grants_by_user_id(&self, user_id: Id, client: &Client) -> Result<Vec<Self::Item>, Self::Error> {
+        let mut grants = Vec::new();
+        let mut start = None;
+        loop {
+            let output = client.query()
+                .table_name(&self.name)
+                .index_name("user_id-index")
+                .key_condition_expression("user_id = :user_id")
+                .filter_expression("expires > :now")
+                .expression_attribute_values(":user_id", AttributeValue::from(user_id))
+                .expression_attribute_values(":now", AttributeValue::N(Utc::now().timestamp().to_string()))
+                .set_exclusive_start_key(start)
+                .send()
+                .await?;
+            for item in output.items.unwrap_or_default() {
+                grants.push(GrantRecord::try_from(item)?);
+            }
+            start = output.last_evaluated_key;
+            if start.is_none() {
+                break Ok(grants);
+            }
+        }
+    }
+
+    /// Deleting returns the deleted item to a single caller, so a grant is never taken twice.
+    #[instrument(skip_all, fields(table = %self.name), err)]
+    async fn take_grant(&self, id: String, client: &Client) -> Result<Option<Self::Item>, Self::Error> {
+        let output = client.delete_item()
+            .table_name(&self.name)
+            .key("id", AttributeValue::S(id))
+            .return_values(ReturnValue::AllOld)
+            .send()
+            .await?;
+        unexpired(output.attributes)
+    }
+
+    #[instrument(skip_all, fields(table = %self.name), err)]
+    async fn delete_grant(&self, id: String, client: &Client) -> Result<(), Self::Error> {
+        client.delete_item().table_name(&self.name).key("id", AttributeValue::S(id)).send().await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(table = %self.name), err)]
+    async fn purge_expired_grants(&self, before: DateTime<Utc>, client: &Client) -> Result<usize, Self::Error> {
+        let values = HashMap::from([(String::from(":before"), AttributeValue::N(before.timestamp().to_string()))]);
+        Ok(delete_where(client, &self.name, "expires < :before", values).await?.len())
+    }
+}
+
+
+/// The grant `item` holds, unless it expired.
+fn unexpired(item: Option<HashMap<String, AttributeValue>>) -> Result<Option<GrantRecord>, DatabaseError> {
+    let Some(item) = item else {
+        return Ok(None);
+    };
+    let grant = GrantRecord::try_from(item)?;
+    Ok((!grant.is_expired(Utc::now())).then_some(grant))
+}
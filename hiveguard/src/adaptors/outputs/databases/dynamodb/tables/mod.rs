@@ -1,71 +1,55 @@
 mod verifications;
+mod grants;
 mod sessions;
 mod users;
 
 
 pub use verifications::VerificationsTable;
+pub use grants::GrantsTable;
 pub use sessions::SessionsTable;
 pub use users::UsersTable;
 
 
+use crate::types::{ConversionError, DatabaseError, to_attribute_value};
 use aws_sdk_dynamodb::types::AttributeValue;
-use crate::types::ConversionError;
 use std::collections::HashMap;
 use serde_json::{Map, Value};
-
-
-fn strings(values: Vec<Value>) -> Result<Vec<String>, ConversionError> {
-    let mut strings = Vec::new();
-    for value in values {
-        match value {
-            Value::String(string) => strings.push(string),
-            _ => Err(ConversionError::UnexpectedDataType("Unknown. converting a Vec<Value> to a Vec<String> for function strings"))?
+use aws_sdk_dynamodb::Client;
+
+
+/// Deletes every item of `table` matching `filter`, scanning the table a page at a time.
+/// Returns the keys of the items deleted.
+async fn delete_where(client: &Client, table: &str, filter: &str, values: HashMap<String, AttributeValue>) -> Result<Vec<AttributeValue>, DatabaseError> {
+    let mut deleted = Vec::new();
+    let mut start = None;
+    loop {
+        let output = client.scan()
+            .table_name(table)
+            .projection_expression("id")
+            .filter_expression(filter)
+            .set_expression_attribute_values(Some(values.clone()))
+            .set_exclusive_start_key(start)
+            .send()
+            .await?;
+        for mut item in output.items.unwrap_or_default() {
+            if let Some(id) = item.remove("id") {
+                client.delete_item().table_name(table).key("id", id.clone()).send().await?;
+                deleted.push(id);
+            }
         }
-    }
-    Ok(strings)
-}
-
-
-fn numbers(values: &Vec<Value>) -> Result<Vec<String>, ConversionError> {
-    let mut numbers = Vec::new();
-    for value in values {
-        match value {
-            Value::Number(number) => numbers.push(number.to_string()),
-            _ => Err(ConversionError::UnexpectedDataType("Unknown. converting a Vec<Value> to a Vec<String> for function numbers"))?
+        start = output.last_evaluated_key;
+        if start.is_none() {
+            break Ok(deleted);
         }
     }
-    Ok(numbers)
 }
 
 
 fn map_to_hash_map(map: Map<String, Value>) -> Result<HashMap<String, AttributeValue>, ConversionError> {
     let mut hash_map = HashMap::new();
     for (key, value) in map {
-        let value = value_to_attribute_value(value)?;
+        let value = to_attribute_value(value)?;
         hash_map.insert(key, value);
     }
     Ok(hash_map)
 }
-
-
-fn value_to_attribute_value(value: Value) -> Result<AttributeValue, ConversionError> {
-    match value {
-        Value::String(string) => Ok(AttributeValue::S(string)),
-        Value::Number(number) => Ok(AttributeValue::N(number.to_string())),
-        Value::Bool(boolean) => Ok(AttributeValue::Bool(boolean)),
-        Value::Array(array) => {
-            match numbers(&array) {
-                Ok(numbers) => Ok(AttributeValue::Ns(numbers)),
-                Err(_) => {
-                    let strings = strings(array)?;
-                    Ok(AttributeValue::Ss(strings))
-                }
-            }
-        },
-        Value::Object(object) => {
-            let hash_map = map_to_hash_map(object)?;
-            Ok(AttributeValue::M(hash_map))
-        },
-        Value::Null => Ok(AttributeValue::Null(true))
-    }
-}
\ No newline at end of file
@@ -1,56 +1,25 @@
-use aws_sdk_dynamodb::{types::{AttributeValue, KeysAndAttributes}, Client, error::BuildError};
 use crate::ports::outputs::database::tables::SessionsTable as Table;
 use crate::types::{Session, Id, DatabaseError};
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+use aws_sdk_dynamodb::Client;
+use chrono::{DateTime, Utc};
+use tracing::instrument;
+use super::delete_where;
+use macros::dynamodb;
 
 pub struct SessionsTable{
     pub name: String,
 }
 
 
-impl SessionsTable {
-    fn keys_and_attributes(key: String, value: AttributeValue) -> Result<KeysAndAttributes, BuildError> {
-        let input = [(key, value)].into();
-        KeysAndAttributes::builder().keys(input).build()
-    }
-}
-
-
+/// Everything but the refresh token rotation, the activity tracking and the purge is generated from the schema of the table.
+#[dynamodb]
 impl Table<Client> for SessionsTable {
     type Error = DatabaseError;
     type Item = Session;
-    async fn create_session(&self, session: Self::Item, client: &Client) -> Result<(), Self::Error> {
-        let input = Some(session.into());
-        let _ = client.put_item().table_name(&self.name).set_item(input).send().await?;
-        Ok(())
-    }
-
-    async fn get_session_by_id(&self, id: Id, client: &Client) -> Result<Option<Self::Item>, Self::Error> {
-        let (k, v) = ("id", id.into());
-        let output = client.get_item().table_name(&self.name).key(k, v).send().await?;
-        match output.item {
-            Some(item) => Ok(Some(item.try_into()?)),
-            None => Ok(None)
-        }
-    }
-
-    async fn get_sessions_by_user_id(&self, user_id: Id, client: &Client) -> Result<Vec<Self::Item>, Self::Error> {
-        let (key, value) = ("user_id".into(), user_id.into());
-        let keys = Self::keys_and_attributes(key, value)?;
-        let output = client.batch_get_item().request_items(&self.name, keys).send().await?;
-        match output.responses {
-            Some(mut tables) => {
-                let mut sessions = vec![];
-                if let Some(items) = tables.remove(&self.name) {
-                    for item in items {
-                        sessions.push(item.try_into()?);
-                    }
-                }
-                Ok(sessions)
-            },
-            None => Ok(vec![])
-        }
-    }
 
+    #[instrument(skip_all, fields(table = %self.name, session_id = %id.to_hex()), err)]
     async fn change_current_refresh_token(
         &self,
         id: Id,
@@ -58,21 +27,54 @@ impl Table<Client> for SessionsTable {
         client: &Client
     ) -> Result<(), Self::Error> {
         let (k, v) = ("id", id.into());
-        let update_expression = "SET previous_refresh_token_id = refresh_token_id, refresh_token_id = :new_id";
+        let update_expression = "SET previous_refresh_token_id = refresh_token_id, refresh_token_id = :new_id, updated_at = :updated_at";
         let (key, value) = (":new_id", new_refresh_token_id.into());
-        let _ = client.update_item()
+        let result = client.update_item()
             .table_name(&self.name)
             .key(k, v)
+            .condition_expression("attribute_exists(id)")
             .update_expression(update_expression)
             .expression_attribute_values(key, value)
+            .expression_attribute_values(":updated_at", AttributeValue::N(Utc::now().timestamp().to_string()))
             .send()
-            .await?;
-        Ok(())
+            .await;
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => match DatabaseError::from(err) {
+                DatabaseError::AlreadyExists => Err(DatabaseError::SessionNotFound),
+                err => Err(err),
+            },
+        }
     }
 
-    async fn delete_session(&self, id: Id, client: &Client) -> Result<(), Self::Error> {
-        let (k, v) = ("id", id.into());
-        client.delete_item().table_name(&self.name).key(k, v).send().await?;
-        Ok(())
+    #[instrument(skip_all, fields(table = %self.name, session_id = %id.to_hex()), err)]
+    async fn touch_session(&self, id: Id, client: &Client) -> Result<(), Self::Error> {
+        let result = client.update_item()
+            .table_name(&self.name)
+            .key("id", id.into())
+            .condition_expression("attribute_exists(id)")
+            .update_expression("SET last_active_at = :last_active_at")
+            .expression_attribute_values(":last_active_at", AttributeValue::N(Utc::now().timestamp().to_string()))
+            .send()
+            .await;
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => match DatabaseError::from(err) {
+                DatabaseError::AlreadyExists => Err(DatabaseError::SessionNotFound),
+                err => Err(err),
+            },
+        }
     }
-}
\ No newline at end of file
+
+    #[instrument(skip_all, fields(table = %self.name), err)]
+    async fn purge_expired_sessions(&self, idle_before: DateTime<Utc>, created_before: DateTime<Utc>, client: &Client) -> Result<Vec<Id>, Self::Error> {
+        let values = HashMap::from([
+            (String::from(":idle_before"), AttributeValue::N(idle_before.timestamp().to_string())),
+            (String::from(":created_before"), AttributeValue::N(created_before.timestamp().to_string())),
+        ]);
+        // sessions written before activity was tracked have no last_active_at.
+        let filter = "last_active_at < :idle_before OR (attribute_not_exists(last_active_at) AND updated_at < :idle_before) OR created_at < :created_before";
+        let deleted = delete_where(client, &self.name, filter, values).await?;
+        Ok(deleted.into_iter().map(Id::try_from).collect::<Result<_, _>>()?)
+    }
+}
@@ -1,76 +1,539 @@
 use crate::ports::outputs::database::tables::UsersTable as Table;
-use aws_sdk_dynamodb::types::{AttributeValue, ReturnValue};
-use crate::types::{User, Id, DatabaseError, Phone, Email};
+use crate::types::{User, UserPatch, Id, DatabaseError, Email, Phone, Page, Namespace, Status, Document, Consent, UserFilter, DomainEvent, PiiCipher, PII_FIELDS};
+use crate::adaptors::outputs::outbox::dynamodb::DynamoDBOutbox;
+use aws_sdk_dynamodb::operation::transact_write_items::TransactWriteItemsError;
+use aws_sdk_dynamodb::types::{Put, Update, Delete, TransactWriteItem};
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::error::SdkError;
+use std::collections::HashMap;
+use aws_sdk_dynamodb::types::ReturnValue;
 use aws_sdk_dynamodb::Client;
 use serde_json::{Map, Value};
 use super::map_to_hash_map;
+use crate::adaptors::outputs::databases::claimed;
+use tracing::instrument;
+use chrono::{DateTime, Utc};
+use macros::dynamodb;
+
+
+/// the attributes claimed by lookup items of `unique_table`, as declared by the schema of the table.
+const UNIQUE: [&str; 3] = ["username", "email", "phone"];
 
 
 pub struct UsersTable{
-    pub name: String
+    pub name: String,
+    /// holds the lookup items claiming the usernames and contacts of users.
+    pub unique_table: String,
+    /// the outbox the events written along with users are put in. see [`DynamoDBOutbox`].
+    pub outbox_table: String,
 }
 
+/// The key and the `email-index` and `phone-index` lookups are generated from the schema of the table,
+/// as are `create_user` and `delete_user`, which claim and release the unique attributes of the user.
+/// Usernames are plain strings, so their lookups are written out.
+#[dynamodb]
 impl Table<Client> for UsersTable {
     type Error = DatabaseError;
     type Item = User;
-    async fn create_user(&self, user: Self::Item, client: &Client) -> Result<(), Self::Error> {
-        let input = Some(user.into());
-        let _ = client.put_item().table_name(&self.name).set_item(input).send().await?;
+
+    /// The user, its lookup items and the event are put in a single transaction.
+    #[instrument(skip_all, fields(table = %self.name, user_id = %user.id.to_hex(), event = event.name()), err)]
+    async fn create_user_with_event(&self, user: Self::Item, event: DomainEvent, client: &Client) -> Result<(), Self::Error> {
+        let id = user.id;
+        let item: HashMap<String, AttributeValue> = user.into();
+        let put = Put::builder().table_name(&self.name).set_item(Some(item.clone())).condition_expression("attribute_not_exists(id)").build()?;
+        let mut items = vec![TransactWriteItem::builder().put(put).build()];
+        for field in UNIQUE {
+            if let Some(AttributeValue::S(value)) = item.get(field) {
+                items.push(self.claim(field, value, id)?);
+            }
+        }
+        items.push(self.event(&event)?);
+        client.transact_write_items().set_transact_items(Some(items)).send().await?;
         Ok(())
     }
 
-    async fn get_user_by_id(&self, id: Id, client: &Client) -> Result<Option<Self::Item>, Self::Error> {
-        let (k, v) = ("id", id.into());
-        let output = client.get_item().table_name(&self.name).key(k, v).send().await?;
-        match output.item {
+    #[instrument(skip_all, fields(table = %self.name), err)]
+    async fn get_user_by_username(&self, username: String, client: &Client) -> Result<Option<Self::Item>, Self::Error> {
+        let output = client.query()
+            .table_name(&self.name)
+            .index_name("username-index")
+            .key_condition_expression("username = :username")
+            .expression_attribute_values(":username", AttributeValue::S(username))
+            .limit(1)
+            .send()
+            .await?;
+        match output.items.and_then(|items| items.into_iter().next()) {
             Some(item) => Ok(Some(item.try_into()?)),
-            None => Ok(None)
+            None => Ok(None),
         }
     }
 
-    async fn get_user_by_email(&self, email: Email, client: &Client) -> Result<Option<Self::Item>, Self::Error> {
-        let (k, v) = ("email", AttributeValue::S(email.to_string()));
-        let output = client.get_item().table_name(&self.name).key(k, v).send().await?;
-        match output.item {
-            Some(item) => Ok(Some(item.try_into()?)),
-            None => Ok(None)
+    #[instrument(skip_all, fields(table = %self.name), err)]
+    async fn get_users_by_previous_username(&self, previous_username: String, client: &Client) -> Result<Vec<Self::Item>, Self::Error> {
+        let mut users = Vec::new();
+        let mut start = None;
+        loop {
+            let output = client.query()
+                .table_name(&self.name)
+                .index_name("previous_username-index")
+                .key_condition_expression("previous_username = :previous_username")
+                .expression_attribute_values(":previous_username", AttributeValue::S(previous_username.clone()))
+                .set_exclusive_start_key(start)
+                .send()
+                .await?;
+            for item in output.items.unwrap_or_default() {
+                users.push(item.try_into()?);
+            }
+            start = output.last_evaluated_key;
+            if start.is_none() {
+                break Ok(users);
+            }
         }
     }
 
-    async fn get_user_by_phone(&self, phone: Phone, client: &Client) -> Result<Option<Self::Item>, Self::Error> {
-        let (k, v) = ("phone", AttributeValue::S(phone.to_string()));
-        let output = client.get_item().table_name(&self.name).key(k, v).send().await?;
-        match output.item {
-            Some(item) => Ok(Some(item.try_into()?)),
-            None => Ok(None)
+    /// Changing a unique attribute swaps its lookup items in the same transaction as the update,
+    /// which fails with `AlreadyExists` when the new value is taken.
+    #[instrument(skip_all, fields(table = %self.name, user_id = %id.to_hex()), err)]
+    async fn update_user(&self, id: Id, update: Map<String, Value>, client: &Client) -> Result<Self::Item, Self::Error> {
+        self.patch_user(id, update, None, None, client).await
+    }
+
+    /// The event is put in the transaction of the update.
+    #[instrument(skip_all, fields(table = %self.name, user_id = %id.to_hex(), event = event.name()), err)]
+    async fn update_user_with_event(&self, id: Id, update: Map<String, Value>, event: DomainEvent, client: &Client) -> Result<Self::Item, Self::Error> {
+        self.patch_user(id, update, None, Some(&event), client).await
+    }
+
+    /// The update is conditioned on the `updated_at` read, stored to the second like every timestamp of the table.
+    #[instrument(skip_all, fields(table = %self.name, user_id = %id.to_hex()), err)]
+    async fn update_user_if(&self, id: Id, update: Map<String, Value>, updated_at: DateTime<Utc>, client: &Client) -> Result<Self::Item, Self::Error> {
+        self.patch_user(id, update, Some(updated_at), None, client).await
+    }
+
+    /// The namespace is stored as a JSON string, removed when it is empty.
+    #[instrument(skip_all, fields(table = %self.name, user_id = %id.to_hex()), err)]
+    async fn set_user_metadata(&self, id: Id, namespace: Namespace, metadata: Map<String, Value>, client: &Client) -> Result<(), Self::Error> {
+        let builder = client.update_item()
+            .table_name(&self.name)
+            .key("id", id.into())
+            .condition_expression("attribute_exists(id)")
+            .expression_attribute_names("#metadata", namespace.attribute())
+            .expression_attribute_values(":updated_at", now());
+        let builder = if metadata.is_empty() {
+            builder.update_expression("SET updated_at = :updated_at REMOVE #metadata")
+        } else {
+            builder.update_expression("SET #metadata = :metadata, updated_at = :updated_at")
+                .expression_attribute_values(":metadata", AttributeValue::S(Value::Object(metadata).to_string()))
+        };
+        match builder.send().await {
+            Ok(_) => Ok(()),
+            Err(err) => match DatabaseError::from(err) {
+                DatabaseError::AlreadyExists => Err(DatabaseError::UserNotFound),
+                err => Err(err),
+            },
         }
     }
 
-    async fn update_user(&self, id: Id, update: Map<String, Value>, client: &Client) -> Result<Self::Item, Self::Error> {
-        let (k, v) = ("id", id.into());
-        if update.is_empty() {
+    #[instrument(skip_all, fields(table = %self.name, user_id = %id.to_hex(), %status), err)]
+    async fn set_user_status(&self, id: Id, status: Status, reason: Option<String>, client: &Client) -> Result<(), Self::Error> {
+        let builder = client.update_item()
+            .table_name(&self.name)
+            .key("id", id.into())
+            .condition_expression("attribute_exists(id)")
+            .expression_attribute_values(":status", AttributeValue::S(status.as_str().into()))
+            .expression_attribute_values(":updated_at", now());
+        let builder = match reason {
+            Some(reason) => builder.update_expression("SET #status = :status, status_reason = :reason, updated_at = :updated_at")
+                .expression_attribute_values(":reason", AttributeValue::S(reason)),
+            None => builder.update_expression("SET #status = :status, updated_at = :updated_at REMOVE status_reason"),
+        };
+        // `status` is a reserved word of DynamoDB.
+        match builder.expression_attribute_names("#status", "status").send().await {
+            Ok(_) => Ok(()),
+            Err(err) => match DatabaseError::from(err) {
+                DatabaseError::AlreadyExists => Err(DatabaseError::UserNotFound),
+                err => Err(err),
+            },
+        }
+    }
+
+    /// The consent is stored as a JSON string, in an attribute of its own per document.
+    #[instrument(skip_all, fields(table = %self.name, user_id = %id.to_hex(), %document), err)]
+    async fn set_user_consent(&self, id: Id, document: Document, consent: Consent, client: &Client) -> Result<(), Self::Error> {
+        let result = client.update_item()
+            .table_name(&self.name)
+            .key("id", id.into())
+            .condition_expression("attribute_exists(id)")
+            .update_expression("SET #consent = :consent, updated_at = :updated_at")
+            .expression_attribute_names("#consent", document.attribute())
+            .expression_attribute_values(":consent", AttributeValue::S(serde_json::json!(consent).to_string()))
+            .expression_attribute_values(":updated_at", now())
+            .send()
+            .await;
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => match DatabaseError::from(err) {
+                DatabaseError::AlreadyExists => Err(DatabaseError::UserNotFound),
+                err => Err(err),
+            },
+        }
+    }
+
+    /// The attributes of the other logins are removed, since a user has a single one.
+    #[instrument(skip_all, fields(table = %self.name, user_id = %id.to_hex()), err)]
+    async fn set_user_password(&self, id: Id, hash: String, client: &Client) -> Result<(), Self::Error> {
+        let result = client.update_item()
+            .table_name(&self.name)
+            .key("id", id.into())
+            .condition_expression("attribute_exists(id)")
+            .update_expression("SET #password = :password, updated_at = :updated_at REMOVE #oauth, #directory, #saml")
+            // `password` is a reserved word of DynamoDB, the others are named the same way for consistency.
+            .expression_attribute_names("#password", "password")
+            .expression_attribute_names("#oauth", "oauth")
+            .expression_attribute_names("#directory", "directory")
+            .expression_attribute_names("#saml", "saml")
+            .expression_attribute_values(":password", AttributeValue::S(hash))
+            .expression_attribute_values(":updated_at", now())
+            .send()
+            .await;
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => match DatabaseError::from(err) {
+                DatabaseError::AlreadyExists => Err(DatabaseError::UserNotFound),
+                err => Err(err),
+            },
+        }
+    }
+
+    /// Scans the table with the filter as a filter expression, a page of `limit` items at a time,
+    /// until `limit` users match or the table ends. DynamoDB filters a page once it is read, hence the loop.
+    #[instrument(skip_all, fields(table = %self.name, limit), err)]
+    async fn search_users(&self, filter: UserFilter, after: Option<Id>, limit: usize, client: &Client) -> Result<Page<Self::Item, Id>, Self::Error> {
+        let mut conditions = Vec::new();
+        let mut names = HashMap::new();
+        let mut values = HashMap::new();
+        #[cfg(feature = "email")]
+        if let Some(email) = filter.email {
+            names.insert(String::from("#email"), String::from("email"));
+            // a blind index only matches the whole address. those still stored in the clear match any part of it,
+            // which the blind indexes are kept out of, as their text has nothing to do with the address.
+            match PiiCipher::installed() {
+                Some(cipher) => {
+                    conditions.push("(#email = :email_blind OR (NOT begins_with(#email, :blind_prefix) AND contains(#email, :email)))");
+                    values.insert(String::from(":email_blind"), AttributeValue::S(cipher.blind("email", &email)));
+                    values.insert(String::from(":blind_prefix"), AttributeValue::S(String::from(crate::types::BLIND)));
+                },
+                None => conditions.push("contains(#email, :email)"),
+            }
+            values.insert(String::from(":email"), AttributeValue::S(email));
+        }
+        if let Some(username) = filter.username {
+            conditions.push("#username = :username");
+            names.insert(String::from("#username"), String::from("username"));
+            values.insert(String::from(":username"), AttributeValue::S(username));
+        }
+        if let Some(status) = filter.status {
+            conditions.push("#status = :status");
+            names.insert(String::from("#status"), String::from("status"));
+            values.insert(String::from(":status"), AttributeValue::S(status.as_str().into()));
+        }
+        if let Some(after) = filter.created_after {
+            conditions.push("#created_at >= :created_after");
+            values.insert(String::from(":created_after"), AttributeValue::N(after.timestamp().to_string()));
+        }
+        if let Some(before) = filter.created_before {
+            conditions.push("#created_at < :created_before");
+            values.insert(String::from(":created_before"), AttributeValue::N(before.timestamp().to_string()));
+        }
+        if values.contains_key(":created_after") || values.contains_key(":created_before") {
+            names.insert(String::from("#created_at"), String::from("created_at"));
+        }
+        let expression = (!conditions.is_empty()).then(|| conditions.join(" AND "));
+        let mut start = after.map(|after| HashMap::from([(String::from("id"), AttributeValue::from(after))]));
+        let mut items = Vec::new();
+        loop {
+            let output = client.scan()
+                .table_name(&self.name)
+                .limit(i32::try_from(limit).unwrap_or(i32::MAX))
+                .set_filter_expression(expression.clone())
+                .set_expression_attribute_names((!names.is_empty()).then(|| names.clone()))
+                .set_expression_attribute_values((!values.is_empty()).then(|| values.clone()))
+                .set_exclusive_start_key(start)
+                .send()
+                .await?;
+            for item in output.items.unwrap_or_default() {
+                items.push(User::try_from(item)?);
+            }
+            start = output.last_evaluated_key;
+            if items.len() >= limit || start.is_none() {
+                break;
+            }
+        }
+        // the last page read may hold more matches than fit, the next page then starts after the last user returned.
+        if items.len() > limit {
+            items.truncate(limit);
+            let next = items.last().map(|user| user.id);
+            return Ok(Page { items, next });
+        }
+        let next = match start.and_then(|mut key| key.remove("id")) {
+            Some(key) => Some(key.try_into()?),
+            None => None,
+        };
+        Ok(Page { items, next })
+    }
+
+    /// Envelopes under another KEK are rewrapped in place. Contacts stored in the clear are sealed in a transaction swapping
+    /// their lookup items for those of their blind indexes. Users changed meanwhile are left to the next run.
+    #[instrument(skip_all, fields(table = %self.name), err)]
+    async fn reencrypt_users(&self, client: &Client) -> Result<usize, Self::Error> {
+        let Some(cipher) = PiiCipher::installed() else {
+            return Ok(0);
+        };
+        let mut changed = 0;
+        let mut start = None;
+        loop {
+            let output = client.scan()
+                .table_name(&self.name)
+                .set_exclusive_start_key(start)
+                .send()
+                .await?;
+            for item in output.items.unwrap_or_default() {
+                if self.reencrypt_user(&cipher, item, client).await? {
+                    changed += 1;
+                }
+            }
+            start = output.last_evaluated_key;
+            if start.is_none() {
+                break Ok(changed);
+            }
+        }
+    }
+}
+
+
+impl UsersTable {
+    /// Applies `update` to the user, if it was last updated at `updated_at` when it is set, adding `event` to the outbox when it is set.
+    async fn patch_user(&self, id: Id, update: Map<String, Value>, updated_at: Option<DateTime<Utc>>, event: Option<&DomainEvent>, client: &Client) -> Result<User, DatabaseError> {
+        UserPatch::try_from(update.clone())?;
+        let (k, v) = ("id", AttributeValue::from(id));
+        if update.is_empty() && event.is_none() {
             let output = client.get_item().table_name(&self.name).key(k, v).send().await?;
-            match output.item {
-                Some(item) => return Ok(item.try_into()?),
-                None => return Err(DatabaseError::UserNotFound)
+            let user = User::try_from(output.item.ok_or(DatabaseError::UserNotFound)?)?;
+            if updated_at.is_some_and(|updated_at| updated_at.timestamp() != user.updated_at.timestamp()) {
+                return Err(DatabaseError::Changed);
             }
+            return Ok(user);
+        }
+        let mut map = map_to_hash_map(update)?;
+        if let Some(cipher) = PiiCipher::installed() {
+            cipher.seal_attributes(&mut map);
         }
-        let map = map_to_hash_map(update)?;
-        let mut builder = client.update_item().table_name(&self.name).key(k, v);
-        for (k, v) in map {
-            builder = builder.update_expression(format!("SET {} = :{}", k, k));
-            builder = builder.expression_attribute_values(format!(":{}", k), v);
+        map.insert("updated_at".into(), now());
+        if event.is_some() || UNIQUE.iter().any(|field| map.contains_key(*field)) {
+            return self.transact_update(id, map, updated_at, event, client).await;
         }
-        let output = builder.return_values(ReturnValue::AllNew).send().await?;
+        let (expression, names, mut values) = assignments(map);
+        let condition = condition(updated_at, &mut values);
+        let builder = client.update_item().table_name(&self.name).key(k, v).condition_expression(condition);
+        let builder = builder.update_expression(expression).set_expression_attribute_names(Some(names)).set_expression_attribute_values(Some(values));
+        let output = match builder.return_values(ReturnValue::AllNew).send().await {
+            Ok(output) => output,
+            Err(err) => match DatabaseError::from(err) {
+                DatabaseError::AlreadyExists => return Err(self.failed_condition(id, updated_at, client).await),
+                err => return Err(err),
+            },
+        };
         match output.attributes {
-            Some(item) => return Ok(item.try_into()?),
-            None => return Err(DatabaseError::UserNotFound)
+            Some(item) => Ok(item.try_into()?),
+            None => Err(DatabaseError::UserNotFound)
         }
     }
 
-    async fn delete_user(&self, id: Id, client: &Client) -> Result<(), Self::Error> {
-        let (k, v) = ("id", id.into());
-        client.delete_item().table_name(&self.name).key(k, v).send().await?;
-        Ok(())
+    /// Updates the user in a transaction, along with the lookup items of the unique attributes `map` changes and the outbox item of `event`.
+    ///
+    /// The update is conditioned on the values it replaces, so a concurrent change of the same attributes cancels it
+    /// rather than leaving a lookup item behind, and on `updated_at` when it is set.
+    async fn transact_update(&self, id: Id, map: HashMap<String, AttributeValue>, updated_at: Option<DateTime<Utc>>, event: Option<&DomainEvent>, client: &Client) -> Result<User, DatabaseError> {
+        let current = client.get_item().table_name(&self.name).key("id", id.into()).send().await?.item.ok_or(DatabaseError::UserNotFound)?;
+        let mut lookups = Vec::new();
+        let (expression, names, mut values) = assignments(map.clone());
+        let mut conditions = vec![condition(updated_at, &mut values)];
+        for field in UNIQUE {
+            let (Some(value), previous) = (map.get(field), current.get(field)) else {
+                continue;
+            };
+            if Some(value) == previous {
+                continue;
+            }
+            match previous {
+                Some(AttributeValue::S(previous)) => {
+                    conditions.push(format!("#{} = :previous_{}", field, field));
+                    values.insert(format!(":previous_{}", field), AttributeValue::S(previous.clone()));
+                    // a change of case only sets the attribute, the lookup item claims the value whatever its case.
+                    if let AttributeValue::S(value) = value && claimed(value) == claimed(previous) {
+                        continue;
+                    }
+                    let delete = Delete::builder().table_name(&self.unique_table).key("id", self.lookup(field, previous)).build()?;
+                    lookups.push(TransactWriteItem::builder().delete(delete).build());
+                },
+                _ => conditions.push(format!("attribute_not_exists(#{})", field)),
+            }
+            if let AttributeValue::S(value) = value {
+                lookups.push(self.claim(field, value, id)?);
+            }
+        }
+        if let Some(event) = event {
+            lookups.push(self.event(event)?);
+        }
+        let update = Update::builder()
+            .table_name(&self.name)
+            .key("id", id.into())
+            .condition_expression(conditions.join(" AND "))
+            .update_expression(expression)
+            .set_expression_attribute_names(Some(names))
+            .set_expression_attribute_values(Some(values))
+            .build()?;
+        let mut items = vec![TransactWriteItem::builder().update(update).build()];
+        items.extend(lookups);
+        match client.transact_write_items().set_transact_items(Some(items)).send().await {
+            Ok(_) => {},
+            // the update comes first: its condition failing means the user was deleted or changed meanwhile, any other means a value is taken.
+            Err(SdkError::ServiceError(err)) if matches!(err.err(), TransactWriteItemsError::TransactionCanceledException(canceled)
+                if canceled.cancellation_reasons().first().and_then(|reason| reason.code()) == Some("ConditionalCheckFailed")) => return Err(self.failed_condition(id, updated_at, client).await),
+            Err(err) => return Err(err.into()),
+        }
+        let item = client.get_item().table_name(&self.name).key("id", id.into()).consistent_read(true).send().await?.item.ok_or(DatabaseError::UserNotFound)?;
+        Ok(item.try_into()?)
+    }
+
+    /// The error of an update of the user whose condition failed: the user is gone, or changed since `updated_at` when it is set.
+    async fn failed_condition(&self, id: Id, updated_at: Option<DateTime<Utc>>, client: &Client) -> DatabaseError {
+        if updated_at.is_none() {
+            return DatabaseError::UserNotFound;
+        }
+        match client.get_item().table_name(&self.name).key("id", id.into()).consistent_read(true).send().await {
+            Ok(output) if output.item.is_some() => DatabaseError::Changed,
+            Ok(_) => DatabaseError::UserNotFound,
+            Err(err) => err.into(),
+        }
+    }
+
+    /// Moves the contacts of the user `item` onto the active KEK of `cipher`. Returns whether any of them changed.
+    async fn reencrypt_user(&self, cipher: &PiiCipher, item: HashMap<String, AttributeValue>, client: &Client) -> Result<bool, DatabaseError> {
+        let id = Id::try_from(item.get("id").cloned().ok_or(DatabaseError::UserNotFound)?)?;
+        let mut changed = false;
+        for field in PII_FIELDS {
+            let pii = format!("{}_pii", field);
+            let result = match (item.get(field), item.get(&pii)) {
+                (_, Some(AttributeValue::S(envelope))) => {
+                    let rewrapped = match cipher.rewrap(field, envelope) {
+                        Ok(Some(rewrapped)) => rewrapped,
+                        Ok(None) => continue,
+                        // the KEK of the envelope is no longer configured, there is nothing to rewrap it with.
+                        Err(err) => {
+                            tracing::error!(error = %err, user_id = %id.to_hex(), field, "could not rewrap the envelope");
+                            continue;
+                        },
+                    };
+                    client.update_item()
+                        .table_name(&self.name)
+                        .key("id", id.into())
+                        .condition_expression("#pii = :envelope")
+                        .update_expression("SET #pii = :rewrapped")
+                        .expression_attribute_names("#pii", &pii)
+                        .expression_attribute_values(":envelope", AttributeValue::S(envelope.clone()))
+                        .expression_attribute_values(":rewrapped", AttributeValue::S(rewrapped))
+                        .send()
+                        .await
+                        .map(|_| ())
+                        .map_err(DatabaseError::from)
+                },
+                (Some(AttributeValue::S(value)), _) if !PiiCipher::is_blind(value) => {
+                    let blind = cipher.blind(field, value);
+                    let update = Update::builder()
+                        .table_name(&self.name)
+                        .key("id", id.into())
+                        .condition_expression("#field = :value")
+                        .update_expression("SET #field = :blind, #pii = :envelope")
+                        .expression_attribute_names("#field", field)
+                        .expression_attribute_names("#pii", &pii)
+                        .expression_attribute_values(":value", AttributeValue::S(value.clone()))
+                        .expression_attribute_values(":blind", AttributeValue::S(blind.clone()))
+                        .expression_attribute_values(":envelope", AttributeValue::S(cipher.seal(field, value)))
+                        .build()?;
+                    let delete = Delete::builder().table_name(&self.unique_table).key("id", self.lookup(field, value)).build()?;
+                    let lookup = HashMap::from([(String::from("id"), self.lookup(field, &blind)), (String::from("owner"), AttributeValue::from(id))]);
+                    let put = Put::builder().table_name(&self.unique_table).set_item(Some(lookup)).condition_expression("attribute_not_exists(id)").build()?;
+                    client.transact_write_items()
+                        .transact_items(TransactWriteItem::builder().update(update).build())
+                        .transact_items(TransactWriteItem::builder().delete(delete).build())
+                        .transact_items(TransactWriteItem::builder().put(put).build())
+                        .send()
+                        .await
+                        .map(|_| ())
+                        .map_err(DatabaseError::from)
+                },
+                _ => continue,
+            };
+            match result {
+                Ok(()) => changed = true,
+                Err(DatabaseError::AlreadyExists) => tracing::warn!(user_id = %id.to_hex(), field, "the user changed while being re-encrypted"),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(changed)
+    }
+
+    /// The put of the lookup item claiming `value` for `field` for the user `owner`, unless it is claimed already.
+    fn claim(&self, field: &str, value: &str, owner: Id) -> Result<TransactWriteItem, DatabaseError> {
+        let lookup = HashMap::from([(String::from("id"), self.lookup(field, value)), (String::from("owner"), AttributeValue::from(owner))]);
+        let put = Put::builder().table_name(&self.unique_table).set_item(Some(lookup)).condition_expression("attribute_not_exists(id)").build()?;
+        Ok(TransactWriteItem::builder().put(put).build())
+    }
+
+    /// The put of the outbox item of `event`.
+    fn event(&self, event: &DomainEvent) -> Result<TransactWriteItem, DatabaseError> {
+        let put = Put::builder().table_name(&self.outbox_table).set_item(Some(DynamoDBOutbox::item(event)?)).condition_expression("attribute_not_exists(id)").build()?;
+        Ok(TransactWriteItem::builder().put(put).build())
+    }
+
+    /// The key of the lookup item claiming `value` for `field`, whatever its case. see [`claimed`].
+    ///
+    /// The lookup items `#[dynamodb]` generates for `create_user` and `delete_user` are keyed by it too.
+    fn lookup(&self, field: &str, value: &str) -> AttributeValue {
+        AttributeValue::S(format!("{}#{}#{}", self.name, field, claimed(value)))
+    }
+}
+
+
+/// The `SET` expression assigning every attribute of `map`, with its attribute names and values.
+fn assignments(map: HashMap<String, AttributeValue>) -> (String, HashMap<String, String>, HashMap<String, AttributeValue>) {
+    let mut assignments = Vec::new();
+    let mut names = HashMap::new();
+    let mut values = HashMap::new();
+    for (k, v) in map {
+        assignments.push(format!("#{} = :{}", k, k));
+        names.insert(format!("#{}", k), k.clone());
+        values.insert(format!(":{}", k), v);
+    }
+    (format!("SET {}", assignments.join(", ")), names, values)
+}
+
+
+/// The condition of an update of the user, that it exists and was last updated at `updated_at` when it is set.
+fn condition(updated_at: Option<DateTime<Utc>>, values: &mut HashMap<String, AttributeValue>) -> String {
+    match updated_at {
+        Some(updated_at) => {
+            values.insert(String::from(":read"), AttributeValue::N(updated_at.timestamp().to_string()));
+            String::from("attribute_exists(id) AND updated_at = :read")
+        },
+        None => String::from("attribute_exists(id)"),
     }
-}
\ No newline at end of file
+}
+
+
+/// The current time as the epoch seconds `updated_at` is stored as.
+fn now() -> AttributeValue {
+    AttributeValue::N(chrono::Utc::now().timestamp().to_string())
+}
@@ -0,0 +1,272 @@
+use crate::ports::outputs::database::tables::{UsersTable, SessionsTable, VerificationsTable, GrantsTable};
+use crate::types::{Id, Email, Phone, Page, Namespace, Status, Document, Consent, UserFilter, DomainEvent};
+use std::time::{Duration, Instant};
+use serde_json::{Map, Value};
+use chrono::{DateTime, Utc};
+use std::future::Future;
+
+
+/// Wraps a table of any database adaptor, recording the latency of every operation
+/// and logging the ones slower than `slow_threshold`.
+///
+/// With the `otlp` feature the latencies are also recorded in the `db.client.operation.duration` histogram.
+pub struct Instrumented<T> {
+    inner: T,
+    /// the table name reported in logs and metrics. eg `users`
+    table: &'static str,
+    slow_threshold: Duration,
+    #[cfg(feature = "otlp")]
+    duration: opentelemetry::metrics::Histogram<f64>,
+}
+
+
+impl<T> Instrumented<T> {
+    pub fn new(inner: T, table: &'static str, slow_threshold: Duration) -> Self {
+        Self {
+            inner,
+            table,
+            slow_threshold,
+            #[cfg(feature = "otlp")]
+            duration: opentelemetry::global::meter("hiveguard")
+                .f64_histogram("db.client.operation.duration")
+                .with_unit("s")
+                .with_description("duration of database operations")
+                .build(),
+        }
+    }
+
+    /// The wrapped table.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    async fn observe<O, E, F: Future<Output = Result<O, E>>>(&self, operation: &'static str, future: F) -> Result<O, E> {
+        let start = Instant::now();
+        let result = future.await;
+        let elapsed = start.elapsed();
+        let outcome = if result.is_ok() { "ok" } else { "error" };
+        if elapsed >= self.slow_threshold {
+            tracing::warn!(table = self.table, operation, outcome, elapsed_ms = elapsed.as_millis() as u64, "slow database operation");
+        }
+        #[cfg(feature = "otlp")]
+        {
+            use opentelemetry::KeyValue;
+            let attributes = [
+                KeyValue::new("db.collection.name", self.table),
+                KeyValue::new("db.operation.name", operation),
+                KeyValue::new("outcome", outcome),
+            ];
+            self.duration.record(elapsed.as_secs_f64(), &attributes);
+        }
+        result
+    }
+}
+
+
+impl<Client, T: UsersTable<Client>> UsersTable<Client> for Instrumented<T> {
+    type Error = T::Error;
+    type Item = T::Item;
+
+    async fn create_user(&self, user: Self::Item, client: &Client) -> Result<(), Self::Error> {
+        self.observe("create_user", self.inner.create_user(user, client)).await
+    }
+
+    async fn create_user_with_event(&self, user: Self::Item, event: DomainEvent, client: &Client) -> Result<(), Self::Error> {
+        self.observe("create_user_with_event", self.inner.create_user_with_event(user, event, client)).await
+    }
+
+    async fn get_user_by_id(&self, id: Id, client: &Client) -> Result<Option<Self::Item>, Self::Error> {
+        self.observe("get_user_by_id", self.inner.get_user_by_id(id, client)).await
+    }
+
+    async fn get_user_by_email(&self, email: Email, client: &Client) -> Result<Option<Self::Item>, Self::Error> {
+        self.observe("get_user_by_email", self.inner.get_user_by_email(email, client)).await
+    }
+
+    async fn get_user_by_phone(&self, phone: Phone, client: &Client) -> Result<Option<Self::Item>, Self::Error> {
+        self.observe("get_user_by_phone", self.inner.get_user_by_phone(phone, client)).await
+    }
+
+    async fn get_user_by_username(&self, username: String, client: &Client) -> Result<Option<Self::Item>, Self::Error> {
+        self.observe("get_user_by_username", self.inner.get_user_by_username(username, client)).await
+    }
+
+    async fn get_users_by_previous_username(&self, previous_username: String, client: &Client) -> Result<Vec<Self::Item>, Self::Error> {
+        self.observe("get_users_by_previous_username", self.inner.get_users_by_previous_username(previous_username, client)).await
+    }
+
+    async fn update_user(&self, id: Id, update: Map<String, Value>, client: &Client) -> Result<Self::Item, Self::Error> {
+        self.observe("update_user", self.inner.update_user(id, update, client)).await
+    }
+
+    async fn update_user_with_event(&self, id: Id, update: Map<String, Value>, event: DomainEvent, client: &Client) -> Result<Self::Item, Self::Error> {
+        self.observe("update_user_with_event", self.inner.update_user_with_event(id, update, event, client)).await
+    }
+
+    async fn update_user_if(&self, id: Id, update: Map<String, Value>, updated_at: DateTime<Utc>, client: &Client) -> Result<Self::Item, Self::Error> {
+        self.observe("update_user_if", self.inner.update_user_if(id, update, updated_at, client)).await
+    }
+
+    async fn set_user_metadata(&self, id: Id, namespace: Namespace, metadata: Map<String, Value>, client: &Client) -> Result<(), Self::Error> {
+        self.observe("set_user_metadata", self.inner.set_user_metadata(id, namespace, metadata, client)).await
+    }
+
+    async fn set_user_status(&self, id: Id, status: Status, reason: Option<String>, client: &Client) -> Result<(), Self::Error> {
+        self.observe("set_user_status", self.inner.set_user_status(id, status, reason, client)).await
+    }
+
+    async fn set_user_consent(&self, id: Id, document: Document, consent: Consent, client: &Client) -> Result<(), Self::Error> {
+        self.observe("set_user_consent", self.inner.set_user_consent(id, document, consent, client)).await
+    }
+
+    async fn set_user_password(&self, id: Id, hash: String, client: &Client) -> Result<(), Self::Error> {
+        self.observe("set_user_password", self.inner.set_user_password(id, hash, client)).await
+    }
+
+    async fn delete_user(&self, id: Id, client: &Client) -> Result<(), Self::Error> {
+        self.observe("delete_user", self.inner.delete_user(id, client)).await
+    }
+
+    async fn list_users(&self, after: Option<Id>, limit: usize, client: &Client) -> Result<Page<Self::Item, Id>, Self::Error> {
+        self.observe("list_users", self.inner.list_users(after, limit, client)).await
+    }
+
+    async fn search_users(&self, filter: UserFilter, after: Option<Id>, limit: usize, client: &Client) -> Result<Page<Self::Item, Id>, Self::Error> {
+        self.observe("search_users", self.inner.search_users(filter, after, limit, client)).await
+    }
+
+    async fn reencrypt_users(&self, client: &Client) -> Result<usize, Self::Error> {
+        self.observe("reencrypt_users", self.inner.reencrypt_users(client)).await
+    }
+}
+
+
+impl<Client, T: SessionsTable<Client>> SessionsTable<Client> for Instrumented<T> {
+    type Error = T::Error;
+    type Item = T::Item;
+
+    async fn create_session(&self, session: Self::Item, client: &Client) -> Result<(), Self::Error> {
+        self.observe("create_session", self.inner.create_session(session, client)).await
+    }
+
+    async fn get_session_by_id(&self, id: Id, client: &Client) -> Result<Option<Self::Item>, Self::Error> {
+        self.observe("get_session_by_id", self.inner.get_session_by_id(id, client)).await
+    }
+
+    async fn get_sessions_by_user_id(&self, user_id: Id, client: &Client) -> Result<Vec<Self::Item>, Self::Error> {
+        self.observe("get_sessions_by_user_id", self.inner.get_sessions_by_user_id(user_id, client)).await
+    }
+
+    async fn change_current_refresh_token(&self, id: Id, new_refresh_token_id: Id, client: &Client) -> Result<(), Self::Error> {
+        self.observe("change_current_refresh_token", self.inner.change_current_refresh_token(id, new_refresh_token_id, client)).await
+    }
+
+    async fn touch_session(&self, id: Id, client: &Client) -> Result<(), Self::Error> {
+        self.observe("touch_session", self.inner.touch_session(id, client)).await
+    }
+
+    async fn delete_session(&self, id: Id, client: &Client) -> Result<(), Self::Error> {
+        self.observe("delete_session", self.inner.delete_session(id, client)).await
+    }
+
+    async fn purge_expired_sessions(&self, idle_before: DateTime<Utc>, created_before: DateTime<Utc>, client: &Client) -> Result<Vec<Id>, Self::Error> {
+        self.observe("purge_expired_sessions", self.inner.purge_expired_sessions(idle_before, created_before, client)).await
+    }
+}
+
+
+impl<Client, T: VerificationsTable<Client>> VerificationsTable<Client> for Instrumented<T> {
+    type Error = T::Error;
+    type Item = T::Item;
+
+    async fn create_verification_code(&self, verification_code: Self::Item, client: &Client) -> Result<(), Self::Error> {
+        self.observe("create_verification_code", self.inner.create_verification_code(verification_code, client)).await
+    }
+
+    async fn get_verification_by_email(&self, email: Email, client: &Client) -> Result<Option<Self::Item>, Self::Error> {
+        self.observe("get_verification_by_email", self.inner.get_verification_by_email(email, client)).await
+    }
+
+    async fn get_verification_by_phone(&self, phone: Phone, client: &Client) -> Result<Option<Self::Item>, Self::Error> {
+        self.observe("get_verification_by_phone", self.inner.get_verification_by_phone(phone, client)).await
+    }
+
+    async fn delete_verification(&self, user_id: Id, client: &Client) -> Result<(), Self::Error> {
+        self.observe("delete_verification", self.inner.delete_verification(user_id, client)).await
+    }
+
+    async fn purge_expired_verifications(&self, before: DateTime<Utc>, client: &Client) -> Result<usize, Self::Error> {
+        self.observe("purge_expired_verifications", self.inner.purge_expired_verifications(before, client)).await
+    }
+}
+
+
+impl<Client, T: GrantsTable<Client>> GrantsTable<Client> for Instrumented<T> {
+    type Error = T::Error;
+    type Item = T::Item;
+
+    async fn create_grant(&self, grant: Self::Item, client: &Client) -> Result<(), Self::Error> {
+        self.observe("create_grant", self.inner.create_grant(grant, client)).await
+    }
+
+    async fn get_grant_by_id(&self, id: String, client: &Client) -> Result<Option<Self::Item>, Self::Error> {
+        self.observe("get_grant_by_id", self.inner.get_grant_by_id(id, client)).await
+    }
+
+    async fn get_grants_by_user_id(&self, user_id: Id, client: &Client) -> Result<Vec<Self::Item>, Self::Error> {
+        self.observe("get_grants_by_user_id", self.inner.get_grants_by_user_id(user_id, client)).await
+    }
+
+    async fn take_grant(&self, id: String, client: &Client) -> Result<Option<Self::Item>, Self::Error> {
+        self.observe("take_grant", self.inner.take_grant(id, client)).await
+    }
+
+    async fn delete_grant(&self, id: String, client: &Client) -> Result<(), Self::Error> {
+        self.observe("delete_grant", self.inner.delete_grant(id, client)).await
+    }
+
+    async fn purge_expired_grants(&self, before: DateTime<Utc>, client: &Client) -> Result<usize, Self::Error> {
+        self.observe("purge_expired_grants", self.inner.purge_expired_grants(before, client)).await
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Verifications;
+
+    impl VerificationsTable<()> for Verifications {
+        type Error = &'static str;
+        type Item = ();
+
+        async fn create_verification_code(&self, _: (), _: &()) -> Result<(), Self::Error> {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            Ok(())
+        }
+
+        async fn get_verification_by_email(&self, _: Email, _: &()) -> Result<Option<()>, Self::Error> {
+            Err("unavailable")
+        }
+
+        async fn get_verification_by_phone(&self, _: Phone, _: &()) -> Result<Option<()>, Self::Error> {
+            Ok(None)
+        }
+
+        async fn delete_verification(&self, _: Id, _: &()) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        async fn purge_expired_verifications(&self, _: DateTime<Utc>, _: &()) -> Result<usize, Self::Error> {
+            Ok(0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_results_pass_through() {
+        let table = Instrumented::new(Verifications, "verifications", Duration::ZERO);
+        assert_eq!(table.create_verification_code((), &()).await, Ok(()));
+        assert_eq!(table.get_verification_by_email(Email::try_from("a@example.com").unwrap(), &()).await, Err("unavailable"));
+    }
+}
@@ -0,0 +1,329 @@
+use crate::ports::outputs::database::Database;
+use crate::types::{User, Session, Verification, GrantRecord, DatabaseError};
+use serde::{Deserialize, Serialize};
+use crate::adaptors::outputs::outbox::memory::MemoryOutbox;
+use crate::config::MemoryConfig;
+use super::Instrumented;
+use std::time::Duration;
+
+mod tables;
+mod table;
+
+pub use table::MemoryTable;
+
+
+/// A database kept in memory, for tests and local development. Everything is lost when it is dropped,
+/// unless it is exported first.
+pub struct Memory {
+    users_table: Instrumented<MemoryTable<User>>,
+    sessions_table: Instrumented<MemoryTable<Session>>,
+    verifications_table: Instrumented<MemoryTable<Verification>>,
+    grants_table: Instrumented<MemoryTable<GrantRecord>>,
+}
+
+
+impl Memory {
+    pub fn new(slow_query_threshold: Duration) -> Self {
+        Self {
+            users_table: Instrumented::new(MemoryTable::new(), "users", slow_query_threshold),
+            sessions_table: Instrumented::new(MemoryTable::new(), "sessions", slow_query_threshold),
+            verifications_table: Instrumented::new(MemoryTable::new(), "verifications", slow_query_threshold),
+            grants_table: Instrumented::new(MemoryTable::new(), "grants", slow_query_threshold),
+        }
+    }
+
+    /// The outbox the users are written to along with their events, to be handed to the domain as its publisher
+    /// when the outbox is enabled, and dispatched.
+    pub fn outbox(&self) -> &MemoryOutbox {
+        self.users_table.inner().outbox()
+    }
+
+    /// A database whose tables hold at most the number of items `config` sets for them.
+    pub fn with_capacity(config: &MemoryConfig, slow_query_threshold: Duration) -> Self {
+        fn table<Item: serde::Serialize + serde::de::DeserializeOwned + Clone>(table: &'static str, max_items: Option<usize>, config: &MemoryConfig) -> MemoryTable<Item> {
+            max_items.map_or_else(MemoryTable::new, |max_items| MemoryTable::with_capacity(table, max_items, config.when_full))
+        }
+        Self {
+            users_table: Instrumented::new(table("users", config.max_users, config), "users", slow_query_threshold),
+            sessions_table: Instrumented::new(table("sessions", config.max_sessions, config), "sessions", slow_query_threshold),
+            verifications_table: Instrumented::new(table("verifications", config.max_verifications, config), "verifications", slow_query_threshold),
+            grants_table: Instrumented::new(table("grants", config.max_grants, config), "grants", slow_query_threshold),
+        }
+    }
+}
+
+
+/// Every item of a [`Memory`] database, as a single JSON document. eg to back a development database up,
+/// to clone it into another environment or to attach the state reproducing a bug to its report.
+///
+/// Password hashes are included, so snapshots of anything but test data are secrets.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct Snapshot {
+    pub users: Vec<User>,
+    pub sessions: Vec<Session>,
+    pub verifications: Vec<Verification>,
+    pub grants: Vec<GrantRecord>,
+}
+
+
+impl Memory {
+    /// The items of every table, ordered by key.
+    pub fn export(&self) -> Snapshot {
+        Snapshot {
+            users: self.users_table.inner().items(),
+            sessions: self.sessions_table.inner().items(),
+            verifications: self.verifications_table.inner().items(),
+            grants: self.grants_table.inner().items(),
+        }
+    }
+
+    /// Creates the items of `snapshot`, indexed like any other. Meant for an empty database:
+    /// an item whose key or unique attributes are taken fails the import with `AlreadyExists`,
+    /// leaving the items created before it.
+    pub async fn import(&self, snapshot: Snapshot) -> Result<(), DatabaseError> {
+        for user in snapshot.users {
+            self.create_user(user).await?;
+        }
+        for session in snapshot.sessions {
+            self.create_session(session).await?;
+        }
+        for verification in snapshot.verifications {
+            self.create_verification_code(verification).await?;
+        }
+        for grant in snapshot.grants {
+            self.create_grant(grant).await?;
+        }
+        Ok(())
+    }
+}
+
+
+impl Database for Memory {
+    type Client = ();
+    type Error = DatabaseError;
+    type UsersTable = Instrumented<MemoryTable<User>>;
+    type SessionsTable = Instrumented<MemoryTable<Session>>;
+    type VerificationsTable = Instrumented<MemoryTable<Verification>>;
+    type GrantsTable = Instrumented<MemoryTable<GrantRecord>>;
+
+    fn users_table(&self) -> &Self::UsersTable {
+        &self.users_table
+    }
+
+    fn sessions_table(&self) -> &Self::SessionsTable {
+        &self.sessions_table
+    }
+
+    fn verifications_table(&self) -> &Self::VerificationsTable {
+        &self.verifications_table
+    }
+
+    fn grants_table(&self) -> &Self::GrantsTable {
+        &self.grants_table
+    }
+
+    fn client(&self) -> &Self::Client {
+        &()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use macros::{memory, table};
+    use crate::types::{Id, Device};
+    use chrono::Utc;
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Event {
+        user_id: Id,
+        occurred_at: String,
+    }
+
+    #[table(key = "user_id", sort = "occurred_at")]
+    trait EventsTable<Client> {
+        type Error;
+        type Item;
+        async fn create_event(&self, event: Self::Item, client: &Client) -> Result<(), Self::Error>;
+        async fn get_event_by_user_id_and_occurred_at(&self, user_id: Id, occurred_at: &str, client: &Client) -> Result<Option<Self::Item>, Self::Error>;
+        async fn get_events_by_user_id_and_occurred_at_between(&self, user_id: Id, from: &str, to: &str, client: &Client) -> Result<Vec<Self::Item>, Self::Error>;
+        async fn get_events_by_user_id_and_occurred_at_starting_with(&self, user_id: Id, prefix: &str, client: &Client) -> Result<Vec<Self::Item>, Self::Error>;
+        async fn delete_event(&self, user_id: Id, occurred_at: &str, client: &Client) -> Result<(), Self::Error>;
+    }
+
+    #[memory]
+    impl EventsTable<()> for MemoryTable<Event> {
+        type Error = DatabaseError;
+        type Item = Event;
+    }
+
+    /// A database keeping its notes in the table its client is, so that reads sent to the replica can be told apart.
+    /// the `MockDatabase` generated alongside it is not used.
+    #[allow(dead_code)]
+    mod replicas {
+        use super::*;
+        use macros::{database, client, replica, primary, skip};
+
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+        pub struct Note {
+            pub id: Id,
+        }
+
+        #[table(key = "id")]
+        pub trait NotesTable<Client> {
+            type Error;
+            type Item;
+            #[skip(Error)]
+            async fn create_note(&self, note: Self::Item, client: &Client) -> Result<(), Self::Error>;
+            #[skip(Error)]
+            async fn get_note_by_id(&self, id: Id, client: &Client) -> Result<Option<Self::Item>, Self::Error>;
+            #[skip(Error)]
+            #[primary]
+            async fn get_latest_note_by_id(&self, id: Id, client: &Client) -> Result<Option<Self::Item>, Self::Error>;
+        }
+
+        #[memory]
+        impl NotesTable<()> for MemoryTable<Note> {
+            type Error = DatabaseError;
+            type Item = Note;
+
+            async fn get_latest_note_by_id(&self, id: Id, client: &()) -> Result<Option<Note>, DatabaseError> {
+                self.get_note_by_id(id, client).await
+            }
+        }
+
+        /// Notes stored in the table the client is.
+        pub struct Notes;
+
+        impl NotesTable<MemoryTable<Note>> for Notes {
+            type Error = DatabaseError;
+            type Item = Note;
+
+            async fn create_note(&self, note: Note, client: &MemoryTable<Note>) -> Result<(), DatabaseError> {
+                client.create_note(note, &()).await
+            }
+
+            async fn get_note_by_id(&self, id: Id, client: &MemoryTable<Note>) -> Result<Option<Note>, DatabaseError> {
+                client.get_note_by_id(id, &()).await
+            }
+
+            async fn get_latest_note_by_id(&self, id: Id, client: &MemoryTable<Note>) -> Result<Option<Note>, DatabaseError> {
+                client.get_latest_note_by_id(id, &()).await
+            }
+        }
+
+        #[database]
+        pub trait Replicated {
+            type Client;
+            type Error;
+            type NotesTable: NotesTable<Self::Client, Error: Into<Self::Error>>;
+
+            fn notes_table(&self) -> &Self::NotesTable;
+            #[client]
+            fn client(&self) -> &Self::Client;
+            #[replica]
+            fn replica(&self) -> &Self::Client;
+        }
+
+        pub struct Replicas {
+            pub primary: MemoryTable<Note>,
+            pub replica: MemoryTable<Note>,
+        }
+
+        impl Replicated for Replicas {
+            type Client = MemoryTable<Note>;
+            type Error = DatabaseError;
+            type NotesTable = Notes;
+
+            fn notes_table(&self) -> &Notes {
+                &Notes
+            }
+
+            fn client(&self) -> &MemoryTable<Note> {
+                &self.primary
+            }
+
+            fn replica(&self) -> &MemoryTable<Note> {
+                &self.replica
+            }
+        }
+    }
+
+    fn session() -> Session {
+        let id = Id::try_from(String::from("000000000000000000000001")).unwrap();
+        let user_id = Id::try_from(String::from("000000000000000000000002")).unwrap();
+        let refresh_token_id = Id::try_from(String::from("000000000000000000000003")).unwrap();
+        let now = Utc::now() - chrono::TimeDelta::hours(1);
+        let device = Device::new(Some([192, 0, 2, 1].into()), Some(String::from("curl/8.0")));
+        Session { id, user_id, refresh_token_id, previous_refresh_token_id: None, device, created_at: now, updated_at: now, last_active_at: now }
+    }
+
+    #[tokio::test]
+    async fn test_sessions_round_trip() {
+        let db = Memory::new(Duration::from_secs(1));
+        let session = session();
+        db.create_session(session.clone()).await.unwrap();
+        assert_eq!(db.create_session(session.clone()).await, Err(DatabaseError::AlreadyExists));
+        assert_eq!(db.get_session_by_id(session.id).await.unwrap(), Some(session.clone()));
+        assert_eq!(db.get_sessions_by_user_id(session.user_id).await.unwrap(), vec![session.clone()]);
+        let new_refresh_token_id = Id::try_from(String::from("000000000000000000000004")).unwrap();
+        db.change_current_refresh_token(session.id, new_refresh_token_id).await.unwrap();
+        let rotated = db.get_session_by_id(session.id).await.unwrap().unwrap();
+        assert_eq!(rotated.previous_refresh_token_id, Some(session.refresh_token_id));
+        assert!(rotated.updated_at > session.updated_at);
+        assert_eq!(rotated.created_at, session.created_at);
+        db.touch_session(session.id).await.unwrap();
+        let touched = db.get_session_by_id(session.id).await.unwrap().unwrap();
+        assert!(touched.last_active_at > session.last_active_at);
+        assert_eq!(touched.device, session.device);
+        assert_eq!(db.touch_session(new_refresh_token_id).await, Err(DatabaseError::SessionNotFound));
+        db.delete_session(session.id).await.unwrap();
+        assert_eq!(db.get_sessions_by_user_id(session.user_id).await.unwrap(), vec![]);
+    }
+
+    crate::database_tests!(Memory::new(Duration::from_secs(1)));
+
+    #[tokio::test]
+    async fn test_snapshots_round_trip() {
+        let db = Memory::new(Duration::from_secs(1));
+        let session = session();
+        db.create_session(session.clone()).await.unwrap();
+        let json = serde_json::to_string(&db.export()).unwrap();
+        let copy = Memory::new(Duration::from_secs(1));
+        copy.import(serde_json::from_str(&json).unwrap()).await.unwrap();
+        assert_eq!(copy.export(), Snapshot { sessions: vec![session.clone()], ..Default::default() });
+        assert_eq!(copy.get_sessions_by_user_id(session.user_id).await.unwrap(), vec![session]);
+        assert_eq!(copy.import(db.export()).await, Err(DatabaseError::AlreadyExists));
+    }
+
+    #[tokio::test]
+    async fn test_reads_are_sent_to_the_replica() {
+        use replicas::{Note, NotesTable, Replicas, Replicated};
+        let db = Replicas { primary: MemoryTable::new(), replica: MemoryTable::new() };
+        let note = Note { id: Id::default() };
+        db.create_note(note.clone()).await.unwrap();
+        assert_eq!(db.get_note_by_id(note.id).await, Ok(None));
+        assert_eq!(db.get_latest_note_by_id(note.id).await, Ok(Some(note.clone())));
+        db.replica.create_note(note.clone(), &()).await.unwrap();
+        assert_eq!(db.get_note_by_id(note.id).await, Ok(Some(note)));
+    }
+
+    #[tokio::test]
+    async fn test_sorted_table() {
+        let table = MemoryTable::new();
+        let user_id = Id::default();
+        let event = |occurred_at: &str| Event { user_id, occurred_at: occurred_at.into() };
+        for occurred_at in ["2026-10-03", "2026-09-30", "2026-10-01"] {
+            table.create_event(event(occurred_at), &()).await.unwrap();
+        }
+        assert_eq!(table.get_event_by_user_id_and_occurred_at(user_id, "2026-10-01", &()).await.unwrap(), Some(event("2026-10-01")));
+        let between = table.get_events_by_user_id_and_occurred_at_between(user_id, "2026-09-01", "2026-10-02", &()).await.unwrap();
+        assert_eq!(between, vec![event("2026-09-30"), event("2026-10-01")]);
+        table.delete_event(user_id, "2026-10-01", &()).await.unwrap();
+        let october = table.get_events_by_user_id_and_occurred_at_starting_with(user_id, "2026-10", &()).await.unwrap();
+        assert_eq!(october, vec![event("2026-10-03")]);
+    }
+}
@@ -0,0 +1,549 @@
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::collections::{BTreeSet, HashMap};
+use serde::{de::DeserializeOwned, Serialize};
+use crate::types::{DatabaseError, Coerce};
+use crate::adaptors::outputs::databases::claimed;
+use crate::adaptors::outputs::outbox::memory::MemoryOutbox;
+use crate::config::WhenFull;
+use std::cmp::Ordering;
+use serde_json::Value;
+use std::sync::RwLock;
+
+
+/// Joins the values of a composite key into the key of an item.
+const SEPARATOR: char = '\u{1f}';
+
+
+/// A thread-safe in-memory table, completed into an implementation of any `#[table]` trait with `#[memory]`.
+///
+/// Items are stored by the value of their key and every declared index is kept in a lookup map,
+/// so lookups never scan the whole table. With a composite key, range queries scan the items of a single partition.
+///
+/// A table built `with_capacity` holds a bounded number of items. Once full, an insert either fails
+/// or evicts the least recently used item, found by scanning the table.
+pub struct MemoryTable<Item> {
+    state: RwLock<State<Item>>,
+    capacity: Option<Capacity>,
+    /// ticks on every access, the items being stamped with the tick they were last used at.
+    clock: AtomicU64,
+    /// the events written along with the items. eg by `UsersTable::create_user_with_event`
+    outbox: MemoryOutbox,
+}
+
+
+struct Capacity {
+    /// the table name reported in logs and metrics. eg `users`
+    table: &'static str,
+    max_items: usize,
+    when_full: WhenFull,
+    #[cfg(feature = "otlp")]
+    evictions: opentelemetry::metrics::Counter<u64>,
+}
+
+
+struct State<Item> {
+    items: HashMap<String, Entry<Item>>,
+    /// the keys of the items by indexed attribute and value.
+    indexes: HashMap<(&'static str, String), BTreeSet<String>>,
+    /// the key of the item holding each value of a unique attribute, by attribute and [`claimed`] value.
+    claims: HashMap<(&'static str, String), String>,
+}
+
+
+struct Entry<Item> {
+    item: Item,
+    /// the indexed fields, key included, whether the item has a value for them or not.
+    fields: Vec<&'static str>,
+    /// the indexed attributes of the item, key included.
+    attributes: Vec<(&'static str, String)>,
+    /// the indexed attributes whose value no other item may share.
+    unique: Vec<&'static str>,
+    /// the tick of the clock of the table the item was last read or written at.
+    used: AtomicU64,
+}
+
+
+impl<Item> Default for MemoryTable<Item> {
+    fn default() -> Self {
+        let state = State { items: HashMap::new(), indexes: HashMap::new(), claims: HashMap::new() };
+        Self { state: RwLock::new(state), capacity: None, clock: AtomicU64::new(0), outbox: MemoryOutbox::new() }
+    }
+}
+
+
+impl<Item: Serialize + DeserializeOwned + Clone> MemoryTable<Item> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A table holding at most `max_items` items, `when_full` deciding what inserting another one does.
+    ///
+    /// With the `otlp` feature evictions are counted in the `db.client.evictions` counter.
+    pub fn with_capacity(table: &'static str, max_items: usize, when_full: WhenFull) -> Self {
+        let capacity = Capacity {
+            table,
+            max_items,
+            when_full,
+            #[cfg(feature = "otlp")]
+            evictions: opentelemetry::global::meter("hiveguard")
+                .u64_counter("db.client.evictions")
+                .with_description("items evicted from full memory tables")
+                .build(),
+        };
+        Self { capacity: Some(capacity), ..Self::default() }
+    }
+
+    /// The events written along with the items of the table, waiting to be published.
+    pub fn outbox(&self) -> &MemoryOutbox {
+        &self.outbox
+    }
+
+    /// Stores `item` unless an item with the same key, or the same value of one of the `unique` attributes, exists.
+    /// Unique values differing only in case are the same value.
+    /// The item is indexed by every attribute of `keys`, `indexes` and `unique`.
+    ///
+    /// `keys` holds the key, followed by the sort key of a composite key.
+    pub fn insert(&self, keys: &[&'static str], indexes: &[&'static str], unique: &[&'static str], item: Item) -> Result<(), DatabaseError> {
+        let json = serde_json::to_value(&item).map_err(internal)?;
+        let mut values = Vec::new();
+        for key in keys {
+            match attribute(&json, key) {
+                Some(value) => values.push(value),
+                None => return Err(DatabaseError::Internal(format!("the item has no `{}` attribute", key).into())),
+            }
+        }
+        let id = values.join(&SEPARATOR.to_string());
+        let mut state = self.state.write().unwrap_or_else(|err| err.into_inner());
+        if state.items.contains_key(&id) {
+            return Err(DatabaseError::AlreadyExists);
+        }
+        let fields = keys.iter().chain(indexes).chain(unique).copied().collect::<Vec<_>>();
+        let attributes = attributes(&json, fields.iter().copied());
+        state.check_unique(&id, unique, &attributes)?;
+        if let Some(capacity) = self.capacity.as_ref().filter(|capacity| state.items.len() >= capacity.max_items) {
+            capacity.make_room(&mut state)?;
+        }
+        state.index(&id, &attributes, unique);
+        state.items.insert(id, Entry { item, fields, attributes, unique: unique.to_vec(), used: AtomicU64::new(self.tick()) });
+        Ok(())
+    }
+
+    /// Every item whose `field`, the key or one of the indexes, equals `value`.
+    pub fn find<V: Serialize>(&self, field: &'static str, value: &V) -> Result<Vec<Item>, DatabaseError> {
+        let value = canonical(&serde_json::to_value(value).map_err(internal)?, field);
+        let state = self.state.read().unwrap_or_else(|err| err.into_inner());
+        let Some(keys) = state.indexes.get(&(field, value)) else {
+            return Ok(Vec::new());
+        };
+        Ok(keys.iter().filter_map(|key| state.items.get(key)).map(|entry| self.use_entry(entry)).collect())
+    }
+
+    /// The item whose composite key is `value` and `sort_value`.
+    pub fn get<K: Serialize, S: Serialize>(&self, key: &'static str, value: &K, sort: &'static str, sort_value: &S) -> Result<Option<Item>, DatabaseError> {
+        let id = composite(key, value, sort, sort_value)?;
+        let state = self.state.read().unwrap_or_else(|err| err.into_inner());
+        Ok(state.items.get(&id).map(|entry| self.use_entry(entry)))
+    }
+
+    /// The items whose `key` equals `value` and whose `sort` key is within `from..=to`, ordered by their sort key.
+    pub fn between<K: Serialize, S: Serialize>(&self, key: &'static str, value: &K, sort: &'static str, from: &S, to: &S) -> Result<Vec<Item>, DatabaseError> {
+        let from = serde_json::to_value(from).map_err(internal)?;
+        let to = serde_json::to_value(to).map_err(internal)?;
+        let (from, to) = (canonical_value(&from, sort), canonical_value(&to, sort));
+        self.range(key, value, sort, |sort_value| {
+            compare(sort_value, from).is_some_and(Ordering::is_ge) && compare(sort_value, to).is_some_and(Ordering::is_le)
+        })
+    }
+
+    /// The items whose `key` equals `value` and whose string `sort` key starts with `prefix`, ordered by their sort key.
+    pub fn starting_with<K: Serialize, S: Serialize>(&self, key: &'static str, value: &K, sort: &'static str, prefix: &S) -> Result<Vec<Item>, DatabaseError> {
+        let prefix = serde_json::to_value(prefix).map_err(internal)?;
+        let Value::String(prefix) = canonical_value(&prefix, sort) else {
+            return Ok(Vec::new());
+        };
+        self.range(key, value, sort, |sort_value| matches!(sort_value, Value::String(sort_value) if sort_value.starts_with(prefix.as_str())))
+    }
+
+    /// Removes the item whose `key` equals `value`. Removing a missing item is not an error.
+    pub fn remove<V: Serialize>(&self, key: &'static str, value: &V) -> Result<(), DatabaseError> {
+        let id = canonical(&serde_json::to_value(value).map_err(internal)?, key);
+        self.remove_id(&id);
+        Ok(())
+    }
+
+    /// Removes the item whose `key` equals `value` and returns it, if there was one.
+    pub fn take<V: Serialize>(&self, key: &'static str, value: &V) -> Result<Option<Item>, DatabaseError> {
+        let id = canonical(&serde_json::to_value(value).map_err(internal)?, key);
+        Ok(self.remove_id(&id))
+    }
+
+    /// Removes the item whose composite key is `value` and `sort_value`. Removing a missing item is not an error.
+    pub fn remove_sorted<K: Serialize, S: Serialize>(&self, key: &'static str, value: &K, sort: &'static str, sort_value: &S) -> Result<(), DatabaseError> {
+        let id = composite(key, value, sort, sort_value)?;
+        self.remove_id(&id);
+        Ok(())
+    }
+
+    /// Replaces the item whose `key` equals `value` with the result of `f`, re-indexing it.
+    ///
+    /// Returns the updated item, or `None` when there is no such item.
+    pub fn update<V: Serialize, F: FnOnce(Item) -> Result<Item, DatabaseError>>(&self, key: &'static str, value: &V, f: F) -> Result<Option<Item>, DatabaseError> {
+        let id = canonical(&serde_json::to_value(value).map_err(internal)?, key);
+        let mut state = self.state.write().unwrap_or_else(|err| err.into_inner());
+        let Some(entry) = state.items.get(&id) else {
+            return Ok(None);
+        };
+        let item = f(entry.item.clone())?;
+        let json = serde_json::to_value(&item).map_err(internal)?;
+        if attribute(&json, key).as_ref() != Some(&id) {
+            return Err(DatabaseError::Internal(format!("the `{}` of an item cannot be updated", key).into()));
+        }
+        let entry = &state.items[&id];
+        let (fields, unique) = (entry.fields.clone(), entry.unique.clone());
+        let attributes = attributes(&json, fields.iter().copied());
+        state.check_unique(&id, &unique, &attributes)?;
+        let previous = std::mem::take(&mut state.items.get_mut(&id).expect("the item exists").attributes);
+        state.unindex(&id, &previous, &unique);
+        state.index(&id, &attributes, &unique);
+        state.items.insert(id, Entry { item: item.clone(), fields, attributes, unique, used: AtomicU64::new(self.tick()) });
+        Ok(Some(item))
+    }
+
+    /// At most `limit` items ordered by their `key`, starting after `after` when it is set,
+    /// and the key of the last item when more items follow.
+    pub fn list<V: Serialize, K: DeserializeOwned>(&self, key: &'static str, after: Option<&V>, limit: usize) -> Result<(Vec<Item>, Option<K>), DatabaseError> {
+        self.list_where(key, after, limit, |_| true)
+    }
+
+    /// Lists the items matching `predicate` like `list` lists every item.
+    pub fn list_where<V: Serialize, K: DeserializeOwned, F: Fn(&Item) -> bool>(&self, key: &'static str, after: Option<&V>, limit: usize, predicate: F) -> Result<(Vec<Item>, Option<K>), DatabaseError> {
+        if limit == 0 {
+            return Err(DatabaseError::Internal("the limit of a listing must be positive".into()));
+        }
+        let after = match after {
+            Some(after) => Some(canonical(&serde_json::to_value(after).map_err(internal)?, key)),
+            None => None,
+        };
+        let state = self.state.read().unwrap_or_else(|err| err.into_inner());
+        let mut ids = state.items.iter()
+            .filter(|(id, entry)| after.as_ref().is_none_or(|after| *id > after) && predicate(&entry.item))
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        let items = ids.iter().take(limit).map(|id| state.items[*id].item.clone()).collect::<Vec<_>>();
+        if ids.len() <= limit {
+            return Ok((items, None));
+        }
+        let last = serde_json::to_value(items.last().expect("a page is not empty when more items follow")).map_err(internal)?;
+        let next = match attribute_value(&last, key) {
+            Some(value) => Some(serde_json::from_value(value.clone()).map_err(internal)?),
+            None => None,
+        };
+        Ok((items, next))
+    }
+
+    /// Every item, ordered by key.
+    pub fn items(&self) -> Vec<Item> {
+        let state = self.state.read().unwrap_or_else(|err| err.into_inner());
+        let mut entries = state.items.iter().collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|(id, _)| *id);
+        entries.into_iter().map(|(_, entry)| entry.item.clone()).collect()
+    }
+
+    /// Removes every item matching `predicate`, scanning the whole table. Returns the items removed.
+    pub fn remove_where<F: Fn(&Item) -> bool>(&self, predicate: F) -> Vec<Item> {
+        let mut state = self.state.write().unwrap_or_else(|err| err.into_inner());
+        let ids = state.items.iter().filter(|(_, entry)| predicate(&entry.item)).map(|(id, _)| id.clone()).collect::<Vec<_>>();
+        let mut removed = Vec::new();
+        for id in &ids {
+            if let Some(entry) = state.items.remove(id) {
+                state.unindex(id, &entry.attributes, &entry.unique);
+                removed.push(entry.item);
+            }
+        }
+        removed
+    }
+
+    /// The next tick of the clock.
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, AtomicOrdering::Relaxed) + 1
+    }
+
+    /// Stamps `entry` as used now and returns a copy of its item.
+    fn use_entry(&self, entry: &Entry<Item>) -> Item {
+        entry.used.store(self.tick(), AtomicOrdering::Relaxed);
+        entry.item.clone()
+    }
+
+    fn remove_id(&self, id: &str) -> Option<Item> {
+        let mut state = self.state.write().unwrap_or_else(|err| err.into_inner());
+        let entry = state.items.remove(id)?;
+        state.unindex(id, &entry.attributes, &entry.unique);
+        Some(entry.item)
+    }
+
+    /// The items of the partition `value` whose `sort` key satisfies `condition`, ordered by their sort key.
+    fn range<K: Serialize, F: Fn(&Value) -> bool>(&self, key: &'static str, value: &K, sort: &'static str, condition: F) -> Result<Vec<Item>, DatabaseError> {
+        let mut items = Vec::new();
+        for item in self.find(key, value)? {
+            let json = serde_json::to_value(&item).map_err(internal)?;
+            if let Some(sort_value) = attribute_value(&json, sort).filter(|sort_value| condition(sort_value)) {
+                items.push((sort_value.clone(), item));
+            }
+        }
+        items.sort_by(|(a, _), (b, _)| compare(a, b).unwrap_or(Ordering::Equal));
+        Ok(items.into_iter().map(|(_, item)| item).collect())
+    }
+}
+
+
+impl Capacity {
+    /// Frees the room of an item in the full table of `state`, or fails when the table rejects inserts once full.
+    fn make_room<Item>(&self, state: &mut State<Item>) -> Result<(), DatabaseError> {
+        if self.when_full == WhenFull::Reject {
+            tracing::warn!(table = self.table, max_items = self.max_items, "the memory table is full");
+            return Err(DatabaseError::Unavailable);
+        }
+        let least_recently_used = state.items.iter().min_by_key(|(_, entry)| entry.used.load(AtomicOrdering::Relaxed)).map(|(id, _)| id.clone());
+        if let Some(id) = least_recently_used && let Some(entry) = state.items.remove(&id) {
+            state.unindex(&id, &entry.attributes, &entry.unique);
+            tracing::debug!(table = self.table, "evicted the least recently used item");
+            #[cfg(feature = "otlp")]
+            self.evictions.add(1, &[opentelemetry::KeyValue::new("db.collection.name", self.table)]);
+        }
+        Ok(())
+    }
+}
+
+
+impl<Item> State<Item> {
+    /// Fails when another item than `id` holds the value of one of the `unique` attributes among `attributes`.
+    fn check_unique(&self, id: &str, unique: &[&'static str], attributes: &[(&'static str, String)]) -> Result<(), DatabaseError> {
+        for (field, value) in attributes.iter().filter(|(field, _)| unique.contains(field)) {
+            if self.claims.get(&(*field, claimed(value))).is_some_and(|key| key != id) {
+                return Err(DatabaseError::AlreadyExists);
+            }
+        }
+        Ok(())
+    }
+
+    fn index(&mut self, id: &str, attributes: &[(&'static str, String)], unique: &[&'static str]) {
+        for (field, value) in attributes {
+            self.indexes.entry((field, value.clone())).or_default().insert(id.to_string());
+            if unique.contains(field) {
+                self.claims.insert((field, claimed(value)), id.to_string());
+            }
+        }
+    }
+
+    fn unindex(&mut self, id: &str, attributes: &[(&'static str, String)], unique: &[&'static str]) {
+        for (field, value) in attributes {
+            let index = (*field, value.clone());
+            if let Some(keys) = self.indexes.get_mut(&index) {
+                keys.remove(id);
+                if keys.is_empty() {
+                    self.indexes.remove(&index);
+                }
+            }
+            let claim = (*field, claimed(value));
+            if unique.contains(field) && self.claims.get(&claim).is_some_and(|key| key == id) {
+                self.claims.remove(&claim);
+            }
+        }
+    }
+}
+
+
+/// The value of `field` in `item`, looked up at the top level or one level down. eg the `email` of a verification's `owner_contact`.
+fn attribute_value<'a>(item: &'a Value, field: &str) -> Option<&'a Value> {
+    let Value::Object(map) = item else {
+        return None;
+    };
+    let value = map.get(field).or_else(|| map.values().find_map(|value| match value {
+        Value::Object(map) => map.get(field),
+        _ => None,
+    }))?;
+    match canonical_value(value, field) {
+        Value::Null => None,
+        value => Some(value),
+    }
+}
+
+
+/// The string the value of `field` in `item` is indexed under.
+fn attribute(item: &Value, field: &str) -> Option<String> {
+    attribute_value(item, field).map(|value| canonical(value, field))
+}
+
+
+/// The indexed attributes of `item` among `fields`, each once.
+fn attributes(item: &Value, fields: impl IntoIterator<Item = &'static str>) -> Vec<(&'static str, String)> {
+    let mut attributes: Vec<(&'static str, String)> = Vec::new();
+    for field in fields {
+        if attributes.iter().all(|(indexed, _)| *indexed != field) && let Some(value) = attribute(item, field) {
+            attributes.push((field, value));
+        }
+    }
+    attributes
+}
+
+
+/// Values serialized as an object holding `field`, like an `Email` serialized as `{"email": .., "email_verified": ..}`,
+/// are indexed by that attribute alone so an item is found whether or not it is verified.
+fn canonical_value<'a>(value: &'a Value, field: &str) -> &'a Value {
+    match value {
+        Value::Object(map) if map.contains_key(field) => canonical_value(&map[field], field),
+        value => value,
+    }
+}
+
+
+/// The string `value` is indexed under.
+fn canonical(value: &Value, field: &str) -> String {
+    match canonical_value(value, field) {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+
+/// The key of the item whose composite key is `value` and `sort_value`.
+fn composite<K: Serialize, S: Serialize>(key: &str, value: &K, sort: &str, sort_value: &S) -> Result<String, DatabaseError> {
+    let value = canonical(&serde_json::to_value(value).map_err(internal)?, key);
+    let sort_value = canonical(&serde_json::to_value(sort_value).map_err(internal)?, sort);
+    Ok(format!("{}{}{}", value, SEPARATOR, sort_value))
+}
+
+
+/// Orders numbers by value and strings lexicographically, like the sort keys of DynamoDB.
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(_), Value::Number(_)) => match (a.to_decimal(), b.to_decimal()) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            // beyond the range of a decimal, where f64 is all there is.
+            _ => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        },
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+
+fn internal(err: serde_json::Error) -> DatabaseError {
+    DatabaseError::Internal(Box::new(err))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Item {
+        id: u32,
+        owner: String,
+        contact: Contact,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Contact {
+        email: String,
+        email_verified: bool,
+    }
+
+    fn item(id: u32, owner: &str, email: &str) -> Item {
+        Item { id, owner: owner.into(), contact: Contact { email: email.into(), email_verified: false } }
+    }
+
+    #[test]
+    fn test_insert_and_find() {
+        let table = MemoryTable::new();
+        table.insert(&["id"], &["owner", "email"], &[], item(1, "alice", "a@example.com")).unwrap();
+        table.insert(&["id"], &["owner", "email"], &[], item(2, "alice", "b@example.com")).unwrap();
+        assert_eq!(table.insert(&["id"], &["owner"], &[], item(1, "bob", "c@example.com")), Err(DatabaseError::AlreadyExists));
+        assert_eq!(table.find("id", &1).unwrap(), vec![item(1, "alice", "a@example.com")]);
+        assert_eq!(table.find("owner", &"alice").unwrap().len(), 2);
+        let contact = Contact { email: "b@example.com".into(), email_verified: true };
+        assert_eq!(table.find("email", &contact).unwrap(), vec![item(2, "alice", "b@example.com")]);
+        assert!(table.find("owner", &"bob").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_update_and_remove_reindex() {
+        let table = MemoryTable::new();
+        table.insert(&["id"], &["owner"], &[], item(1, "alice", "a@example.com")).unwrap();
+        let updated = table.update("id", &1, |item| Ok(Item { owner: "bob".into(), ..item })).unwrap();
+        assert_eq!(updated, Some(item(1, "bob", "a@example.com")));
+        assert!(table.find("owner", &"alice").unwrap().is_empty());
+        assert_eq!(table.find("owner", &"bob").unwrap().len(), 1);
+        assert_eq!(table.update("id", &2, Ok).unwrap(), None);
+        table.remove("id", &1).unwrap();
+        assert!(table.find("id", &1).unwrap().is_empty());
+        assert!(table.find("owner", &"bob").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unique_attributes() {
+        let table = MemoryTable::new();
+        table.insert(&["id"], &["owner"], &["email"], item(1, "alice", "a@example.com")).unwrap();
+        assert_eq!(table.insert(&["id"], &["owner"], &["email"], item(2, "bob", "a@example.com")), Err(DatabaseError::AlreadyExists));
+        assert_eq!(table.insert(&["id"], &["owner"], &["email"], item(2, "bob", "A@Example.com")), Err(DatabaseError::AlreadyExists));
+        table.insert(&["id"], &["owner"], &["email"], item(2, "bob", "b@example.com")).unwrap();
+        let taken = table.update("id", &2, |item| Ok(Item { contact: Contact { email: "a@example.com".into(), email_verified: true }, ..item }));
+        assert_eq!(taken, Err(DatabaseError::AlreadyExists));
+        table.update("id", &1, |item| Ok(Item { owner: "carol".into(), ..item })).unwrap();
+        table.remove("id", &1).unwrap();
+        table.update("id", &2, |item| Ok(Item { contact: Contact { email: "a@example.com".into(), email_verified: true }, ..item })).unwrap();
+        assert_eq!(table.find("email", &"a@example.com").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_full_tables() {
+        let rejecting = MemoryTable::with_capacity("items", 2, WhenFull::Reject);
+        for id in [1, 2] {
+            rejecting.insert(&["id"], &["owner"], &[], item(id, "alice", "a@example.com")).unwrap();
+        }
+        assert_eq!(rejecting.insert(&["id"], &["owner"], &[], item(3, "alice", "a@example.com")), Err(DatabaseError::Unavailable));
+        assert_eq!(rejecting.find("owner", &"alice").unwrap().len(), 2);
+        let evicting = MemoryTable::with_capacity("items", 2, WhenFull::EvictLru);
+        for id in [1, 2] {
+            evicting.insert(&["id"], &["owner"], &[], item(id, "alice", "a@example.com")).unwrap();
+        }
+        evicting.find("id", &1).unwrap();
+        evicting.insert(&["id"], &["owner"], &[], item(3, "alice", "a@example.com")).unwrap();
+        assert!(evicting.find("id", &2).unwrap().is_empty());
+        let ids = evicting.find("owner", &"alice").unwrap().into_iter().map(|item| item.id).collect::<BTreeSet<_>>();
+        assert_eq!(ids, BTreeSet::from([1, 3]));
+    }
+
+    #[test]
+    fn test_list_pages() {
+        let table = MemoryTable::new();
+        for id in [3, 1, 2] {
+            table.insert(&["id"], &[], &[], item(id, "alice", "a@example.com")).unwrap();
+        }
+        let ids = |items: Vec<Item>| items.into_iter().map(|item| item.id).collect::<Vec<_>>();
+        let (first, next) = table.list::<u32, u32>("id", None, 2).unwrap();
+        assert_eq!((ids(first), next), (vec![1, 2], Some(2)));
+        let (last, next) = table.list::<u32, u32>("id", next.as_ref(), 2).unwrap();
+        assert_eq!((ids(last), next), (vec![3], None));
+        assert!(table.list::<u32, u32>("id", None, 0).is_err());
+    }
+
+    #[test]
+    fn test_composite_key_ranges() {
+        let table = MemoryTable::new();
+        for (id, owner, email) in [(3, "alice", "c@example.com"), (1, "alice", "a@example.com"), (2, "alice", "b@example.com"), (1, "bob", "d@example.com")] {
+            table.insert(&["owner", "id"], &[], &[], item(id, owner, email)).unwrap();
+        }
+        assert_eq!(table.insert(&["owner", "id"], &[], &[], item(1, "alice", "e@example.com")), Err(DatabaseError::AlreadyExists));
+        assert_eq!(table.get("owner", &"alice", "id", &2).unwrap(), Some(item(2, "alice", "b@example.com")));
+        let ids = |items: Vec<Item>| items.into_iter().map(|item| item.id).collect::<Vec<_>>();
+        assert_eq!(ids(table.between("owner", &"alice", "id", &2, &3).unwrap()), vec![2, 3]);
+        assert_eq!(ids(table.find("owner", &"bob").unwrap()), vec![1]);
+        table.remove_sorted("owner", &"alice", "id", &1).unwrap();
+        assert_eq!(ids(table.between("owner", &"alice", "id", &0, &10).unwrap()), vec![2, 3]);
+        assert_eq!(table.get("owner", &"bob", "id", &1).unwrap(), Some(item(1, "bob", "d@example.com")));
+    }
+}
@@ -0,0 +1,155 @@
+use crate::ports::outputs::database::tables::{UsersTable, SessionsTable, VerificationsTable, GrantsTable};
+use crate::types::{User, UserPatch, Session, Verification, GrantRecord, Id, DatabaseError, Email, Phone, Page, Namespace, Status, Document, Consent, Login, UserFilter, DomainEvent};
+use serde_json::{Map, Value};
+use crate::ports::outputs::outbox::Outbox;
+use super::MemoryTable;
+use chrono::{DateTime, Utc};
+use macros::memory;
+
+
+/// The key and the `email` and `phone` lookups are generated from the schema of the table.
+#[memory]
+impl UsersTable<()> for MemoryTable<User> {
+    type Error = DatabaseError;
+    type Item = User;
+
+    /// The event is added once the user is stored. Only an event whose id is taken could fail it, which never happens to a new event.
+    async fn create_user_with_event(&self, user: Self::Item, event: DomainEvent, client: &()) -> Result<(), Self::Error> {
+        self.create_user(user, client).await?;
+        self.outbox().add(&event).await
+    }
+
+    async fn update_user(&self, id: Id, update: Map<String, Value>, _: &()) -> Result<Self::Item, Self::Error> {
+        self.patch_user(id, update, None)
+    }
+
+    async fn update_user_with_event(&self, id: Id, update: Map<String, Value>, event: DomainEvent, _: &()) -> Result<Self::Item, Self::Error> {
+        let user = self.patch_user(id, update, None)?;
+        self.outbox().add(&event).await?;
+        Ok(user)
+    }
+
+    /// The version is compared under the write lock of the update, so no other write comes in between.
+    async fn update_user_if(&self, id: Id, update: Map<String, Value>, updated_at: DateTime<Utc>, _: &()) -> Result<Self::Item, Self::Error> {
+        self.patch_user(id, update, Some(updated_at))
+    }
+
+    async fn set_user_metadata(&self, id: Id, namespace: Namespace, metadata: Map<String, Value>, _: &()) -> Result<(), Self::Error> {
+        let user = self.update("id", &id, |mut user| {
+            *user.metadata.get_mut(namespace) = metadata;
+            user.updated_at = Utc::now();
+            Ok(user)
+        })?;
+        user.map(|_| ()).ok_or(DatabaseError::UserNotFound)
+    }
+
+    async fn set_user_status(&self, id: Id, status: Status, reason: Option<String>, _: &()) -> Result<(), Self::Error> {
+        let user = self.update("id", &id, |user| Ok(User { status, status_reason: reason, updated_at: Utc::now(), ..user }))?;
+        user.map(|_| ()).ok_or(DatabaseError::UserNotFound)
+    }
+
+    async fn set_user_consent(&self, id: Id, document: Document, consent: Consent, _: &()) -> Result<(), Self::Error> {
+        let user = self.update("id", &id, |mut user| {
+            *user.consents.get_mut(document) = Some(consent);
+            user.updated_at = Utc::now();
+            Ok(user)
+        })?;
+        user.map(|_| ()).ok_or(DatabaseError::UserNotFound)
+    }
+
+    async fn set_user_password(&self, id: Id, hash: String, _: &()) -> Result<(), Self::Error> {
+        let user = self.update("id", &id, |user| Ok(User { login: Login::Password(hash), updated_at: Utc::now(), ..user }))?;
+        user.map(|_| ()).ok_or(DatabaseError::UserNotFound)
+    }
+
+    async fn search_users(&self, filter: UserFilter, after: Option<Id>, limit: usize, _: &()) -> Result<Page<Self::Item, Id>, Self::Error> {
+        self.list_where("id", after.as_ref(), limit, |user| filter.matches(user)).map(Into::into)
+    }
+
+    /// Users are held in memory only, never at rest.
+    async fn reencrypt_users(&self, _: &()) -> Result<usize, Self::Error> {
+        Ok(0)
+    }
+}
+
+
+impl MemoryTable<User> {
+    /// Applies `update` to the user, if it was last updated at `updated_at` when it is set.
+    fn patch_user(&self, id: Id, update: Map<String, Value>, updated_at: Option<DateTime<Utc>>) -> Result<User, DatabaseError> {
+        let patch = UserPatch::try_from(update)?;
+        let user = self.update("id", &id, |mut user| {
+            if updated_at.is_some_and(|updated_at| updated_at != user.updated_at) {
+                return Err(DatabaseError::Changed);
+            }
+            patch.apply(&mut user);
+            user.updated_at = Utc::now();
+            Ok(user)
+        })?;
+        user.ok_or(DatabaseError::UserNotFound)
+    }
+}
+
+
+/// Everything but the refresh token rotation, the activity tracking and the purge is generated from the schema of the table.
+#[memory]
+impl SessionsTable<()> for MemoryTable<Session> {
+    type Error = DatabaseError;
+    type Item = Session;
+
+    async fn change_current_refresh_token(&self, id: Id, new_refresh_token_id: Id, _: &()) -> Result<(), Self::Error> {
+        let session = self.update("id", &id, |session| Ok(Session {
+            previous_refresh_token_id: Some(session.refresh_token_id),
+            refresh_token_id: new_refresh_token_id,
+            updated_at: Utc::now(),
+            ..session
+        }))?;
+        session.map(|_| ()).ok_or(DatabaseError::SessionNotFound)
+    }
+
+    async fn touch_session(&self, id: Id, _: &()) -> Result<(), Self::Error> {
+        let session = self.update("id", &id, |session| Ok(Session { last_active_at: Utc::now(), ..session }))?;
+        session.map(|_| ()).ok_or(DatabaseError::SessionNotFound)
+    }
+
+    async fn purge_expired_sessions(&self, idle_before: DateTime<Utc>, created_before: DateTime<Utc>, _: &()) -> Result<Vec<Id>, Self::Error> {
+        let purged = self.remove_where(|session| session.last_active_at < idle_before || session.created_at < created_before);
+        Ok(purged.into_iter().map(|session| session.id).collect())
+    }
+}
+
+
+/// Everything but the purge is generated from the schema of the table.
+#[memory]
+impl VerificationsTable<()> for MemoryTable<Verification> {
+    type Error = DatabaseError;
+    type Item = Verification;
+
+    async fn purge_expired_verifications(&self, before: DateTime<Utc>, _: &()) -> Result<usize, Self::Error> {
+        Ok(self.remove_where(|verification| verification.expires < before).len())
+    }
+}
+
+
+/// The creation and deletion are generated from the schema of the table. The reads leave out the expired grants.
+#[memory]
+impl GrantsTable<()> for MemoryTable<GrantRecord> {
+    type Error = DatabaseError;
+    type Item = GrantRecord;
+
+    async fn get_grant_by_id(&self, id: String, _: &()) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(self.find("id", &id)?.into_iter().find(|grant| !grant.is_expired(Utc::now())))
+    }
+
+    async fn get_grants_by_user_id(&self, user_id: Id, _: &()) -> Result<Vec<Self::Item>, Self::Error> {
+        let now = Utc::now();
+        Ok(self.find("user_id", &user_id)?.into_iter().filter(|grant| !grant.is_expired(now)).collect())
+    }
+
+    async fn take_grant(&self, id: String, _: &()) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(self.take("id", &id)?.filter(|grant| !grant.is_expired(Utc::now())))
+    }
+
+    async fn purge_expired_grants(&self, before: DateTime<Utc>, _: &()) -> Result<usize, Self::Error> {
+        Ok(self.remove_where(|grant| grant.expires < before).len())
+    }
+}
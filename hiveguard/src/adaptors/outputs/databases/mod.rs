@@ -1,2 +1,24 @@
 #[cfg(feature = "dynamodb")]
-mod dynamodb;
\ No newline at end of file
+pub mod dynamodb;
+mod instrumented;
+#[cfg(feature = "redis")]
+mod redis;
+#[cfg(any(test, feature = "testing"))]
+pub mod conformance;
+pub mod memory;
+
+
+pub use instrumented::Instrumented;
+#[cfg(feature = "redis")]
+pub use self::redis::RedisGrants;
+
+
+/// The form a value of a unique attribute is claimed in, so that values differing only in case are the same value.
+/// eg `Jane@Example.com` claims `jane@example.com`. Blind indexes are opaque and claimed as they are, the values being lowercased
+/// before they are blinded. see [`PiiCipher::blind`](crate::types::PiiCipher::blind).
+pub(crate) fn claimed(value: &str) -> String {
+    match crate::types::PiiCipher::is_blind(value) {
+        true => value.to_string(),
+        false => value.to_lowercase(),
+    }
+}
@@ -0,0 +1,128 @@
+use crate::ports::outputs::database::tables::GrantsTable;
+use crate::types::{GrantRecord, Id, DatabaseError};
+use redis::aio::ConnectionManager;
+use redis::{RedisError, Script};
+use chrono::{DateTime, Utc};
+use tracing::instrument;
+
+
+/// sets `KEYS[1]` to the grant `ARGV[1]` for `ARGV[2]` milliseconds unless it is set,
+/// and adds its id `ARGV[3]` to the grants of its user `KEYS[2]`, if any, kept at least as long.
+const CREATE: &str = r"
+if not redis.call('SET', KEYS[1], ARGV[1], 'NX', 'PX', ARGV[2]) then
+    return 0
+end
+if #KEYS == 2 then
+    redis.call('SADD', KEYS[2], ARGV[3])
+    if redis.call('PTTL', KEYS[2]) < tonumber(ARGV[2]) then
+        redis.call('PEXPIRE', KEYS[2], ARGV[2])
+    end
+end
+return 1
+";
+
+
+/// Grants kept in Redis under `<prefix>:grant:<id>`, expiring on time rather than when they are purged.
+///
+/// The table of whatever database it is used with, whose client it ignores. eg `DynamoDB::with_grants`.
+/// The ids of the grants of a user are kept in the set `<prefix>:user-grants:<user id>`, pruned as the grants expire.
+#[derive(Clone)]
+pub struct RedisGrants {
+    connection: ConnectionManager,
+    prefix: String,
+}
+
+
+impl RedisGrants {
+    pub async fn connect(url: &str, prefix: String) -> Result<Self, RedisError> {
+        let client = redis::Client::open(url)?;
+        Ok(Self { connection: ConnectionManager::new(client).await?, prefix })
+    }
+
+    fn key(&self, id: &str) -> String {
+        format!("{}:grant:{}", self.prefix, id)
+    }
+
+    fn user_key(&self, user_id: Id) -> String {
+        format!("{}:user-grants:{}", self.prefix, user_id.to_hex())
+    }
+}
+
+
+impl<Client> GrantsTable<Client> for RedisGrants {
+    type Error = DatabaseError;
+    type Item = GrantRecord;
+
+    #[instrument(skip_all, err)]
+    async fn create_grant(&self, grant: Self::Item, _: &Client) -> Result<(), Self::Error> {
+        let ttl = (grant.expires - Utc::now()).num_milliseconds().max(1);
+        let create = Script::new(CREATE);
+        let mut script = create.prepare_invoke();
+        script.key(self.key(&grant.id));
+        if let Some(user_id) = grant.user_id {
+            script.key(self.user_key(user_id));
+        }
+        let json = serde_json::to_string(&grant).map_err(|err| DatabaseError::Internal(Box::new(err)))?;
+        let created: u8 = script.arg(json).arg(ttl).arg(&grant.id).invoke_async(&mut self.connection.clone()).await?;
+        match created {
+            1 => Ok(()),
+            _ => Err(DatabaseError::AlreadyExists),
+        }
+    }
+
+    #[instrument(skip_all, err)]
+    async fn get_grant_by_id(&self, id: String, _: &Client) -> Result<Option<Self::Item>, Self::Error> {
+        let json: Option<String> = redis::cmd("GET").arg(self.key(&id)).query_async(&mut self.connection.clone()).await?;
+        unexpired(json)
+    }
+
+    #[instrument(skip_all, err)]
+    async fn get_grants_by_user_id(&self, user_id: Id, _: &Client) -> Result<Vec<Self::Item>, Self::Error> {
+        let mut connection = self.connection.clone();
+        let ids: Vec<String> = redis::cmd("SMEMBERS").arg(self.user_key(user_id)).query_async(&mut connection).await?;
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let keys = ids.iter().map(|id| self.key(id)).collect::<Vec<_>>();
+        let found: Vec<Option<String>> = redis::cmd("MGET").arg(keys).query_async(&mut connection).await?;
+        let mut grants = Vec::new();
+        let mut gone = Vec::new();
+        for (id, json) in ids.into_iter().zip(found) {
+            match unexpired(json)? {
+                Some(grant) => grants.push(grant),
+                None => gone.push(id),
+            }
+        }
+        if !gone.is_empty() {
+            redis::cmd("SREM").arg(self.user_key(user_id)).arg(gone).query_async::<()>(&mut connection).await?;
+        }
+        Ok(grants)
+    }
+
+    /// `GETDEL` returns the grant to a single caller. It requires Redis 6.2.
+    #[instrument(skip_all, err)]
+    async fn take_grant(&self, id: String, _: &Client) -> Result<Option<Self::Item>, Self::Error> {
+        let json: Option<String> = redis::cmd("GETDEL").arg(self.key(&id)).query_async(&mut self.connection.clone()).await?;
+        unexpired(json)
+    }
+
+    #[instrument(skip_all, err)]
+    async fn delete_grant(&self, id: String, _: &Client) -> Result<(), Self::Error> {
+        Ok(redis::cmd("DEL").arg(self.key(&id)).query_async(&mut self.connection.clone()).await?)
+    }
+
+    /// Redis deletes the grants as they expire, so there is nothing left to purge.
+    async fn purge_expired_grants(&self, _: DateTime<Utc>, _: &Client) -> Result<usize, Self::Error> {
+        Ok(0)
+    }
+}
+
+
+/// The grant stored as `json`, unless it expired.
+fn unexpired(json: Option<String>) -> Result<Option<GrantRecord>, DatabaseError> {
+    let Some(json) = json else {
+        return Ok(None);
+    };
+    let grant = serde_json::from_str::<GrantRecord>(&json).map_err(|err| DatabaseError::Internal(Box::new(err)))?;
+    Ok((!grant.is_expired(Utc::now())).then_some(grant))
+}
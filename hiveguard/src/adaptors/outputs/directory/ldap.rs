@@ -0,0 +1,93 @@
+use ldap3::{dn_escape, LdapConnAsync, LdapConnSettings, LdapError, Scope, SearchEntry};
+use crate::ports::outputs::directory::Directory;
+use crate::config::LdapAttributes;
+use crate::types::DirectoryEntry;
+use tracing::instrument;
+use std::time::Duration;
+
+
+/// The LDAP result code of a bind with wrong credentials.
+const INVALID_CREDENTIALS: u32 = 49;
+
+
+/// Authenticates users by binding to an LDAP directory, eg Active Directory, as the user themselves.
+///
+/// No service account is needed: the entry of the user is read with their own bind once it succeeded.
+/// A connection is opened per authentication.
+pub struct Ldap {
+    url: String,
+    /// the DN template, with `{username}` in it.
+    bind_dn: String,
+    starttls: bool,
+    attributes: LdapAttributes,
+    timeout: Duration,
+}
+
+
+impl Ldap {
+    pub fn new(url: String, bind_dn: String, starttls: bool, attributes: LdapAttributes, timeout: Duration) -> Self {
+        Self { url, bind_dn, starttls, attributes, timeout }
+    }
+
+    /// Connects to the directory, and upgrades the connection with StartTLS when it is set, without binding.
+    pub async fn check(&self) -> Result<(), LdapError> {
+        let settings = LdapConnSettings::new().set_conn_timeout(self.timeout).set_starttls(self.starttls);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.url).await?;
+        ldap3::drive!(conn);
+        ldap.unbind().await
+    }
+
+    fn dn(&self, username: &str) -> String {
+        self.bind_dn.replace("{username}", &dn_escape(username))
+    }
+}
+
+
+impl Directory for Ldap {
+    type Error = LdapError;
+
+    #[instrument(skip(self, password), err)]
+    async fn authenticate(&self, username: &str, password: &str) -> Result<Option<DirectoryEntry>, Self::Error> {
+        // a bind without a password is an unauthenticated bind, which most servers accept whoever the DN names.
+        if password.is_empty() {
+            return Ok(None);
+        }
+        let settings = LdapConnSettings::new().set_conn_timeout(self.timeout).set_starttls(self.starttls);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.url).await?;
+        ldap3::drive!(conn);
+        let dn = self.dn(username);
+        let bind = ldap.with_timeout(self.timeout).simple_bind(&dn, password).await?;
+        if bind.rc == INVALID_CREDENTIALS {
+            return Ok(None);
+        }
+        bind.success()?;
+        let attributes = [&self.attributes.fullname, &self.attributes.email, &self.attributes.phone];
+        let (entries, _) = ldap.with_timeout(self.timeout).search(&dn, Scope::Base, "(objectClass=*)", attributes).await?.success()?;
+        let _ = ldap.unbind().await;
+        let Some(entry) = entries.into_iter().next() else {
+            return Ok(None);
+        };
+        let mut entry = SearchEntry::construct(entry);
+        let mut attribute = |name: &str| entry.attrs.remove(name).and_then(|values| values.into_iter().next());
+        Ok(Some(DirectoryEntry {
+            username: username.to_string(),
+            fullname: attribute(&self.attributes.fullname),
+            email: attribute(&self.attributes.email),
+            phone: attribute(&self.attributes.phone),
+            dn,
+        }))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usernames_are_escaped_in_the_dn() {
+        let ldap = Ldap::new("ldap://localhost".into(), "uid={username},ou=people,dc=example,dc=com".into(), false, LdapAttributes::default(), Duration::from_secs(1));
+        assert_eq!(ldap.dn("jane"), "uid=jane,ou=people,dc=example,dc=com");
+        assert_eq!(ldap.dn("jane,ou=admins"), "uid=jane\\2cou\\3dadmins,ou=people,dc=example,dc=com");
+    }
+}
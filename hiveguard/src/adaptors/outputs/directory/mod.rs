@@ -0,0 +1,60 @@
+use crate::ports::outputs::directory::Directory;
+use crate::config::DirectoryConfig;
+use crate::types::DirectoryEntry;
+
+#[cfg(feature = "ldap")]
+mod ldap;
+
+#[cfg(feature = "ldap")]
+pub use ldap::Ldap;
+
+
+pub type DirectoryError = Box<dyn std::error::Error + Send + Sync>;
+
+
+/// The external directory selected in the configuration.
+pub enum ExternalDirectory {
+    /// no one is authenticated by a directory.
+    Disabled,
+    #[cfg(feature = "ldap")]
+    Ldap(Ldap),
+}
+
+
+impl ExternalDirectory {
+    /// Connects to the directory. see [`Ldap::check`].
+    pub async fn check(&self) -> Result<(), DirectoryError> {
+        match self {
+            Self::Disabled => Ok(()),
+            #[cfg(feature = "ldap")]
+            Self::Ldap(ldap) => Ok(ldap.check().await?),
+        }
+    }
+}
+
+
+impl From<&DirectoryConfig> for ExternalDirectory {
+    fn from(config: &DirectoryConfig) -> Self {
+        match config {
+            DirectoryConfig::Disabled => Self::Disabled,
+            #[cfg(feature = "ldap")]
+            DirectoryConfig::Ldap { url, bind_dn, starttls, attributes, timeout_secs } => {
+                Self::Ldap(Ldap::new(url.clone(), bind_dn.clone(), *starttls, attributes.clone(), std::time::Duration::from_secs(*timeout_secs)))
+            },
+        }
+    }
+}
+
+
+impl Directory for ExternalDirectory {
+    type Error = DirectoryError;
+
+    #[cfg_attr(not(feature = "ldap"), allow(unused_variables))]
+    async fn authenticate(&self, username: &str, password: &str) -> Result<Option<DirectoryEntry>, Self::Error> {
+        match self {
+            Self::Disabled => Ok(None),
+            #[cfg(feature = "ldap")]
+            Self::Ldap(ldap) => Ok(ldap.authenticate(username, password).await?),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use crate::adaptors::outputs::{http::is_transient, retry::Retry};
+use crate::ports::outputs::error_reporter::ErrorReporter;
+use std::collections::HashMap;
+use crate::types::ErrorReport;
+use reqwest::Client;
+
+
+/// POSTs every [`ErrorReport`] as JSON to a generic HTTP sink, retrying transient failures.
+pub struct Http {
+    client: Client,
+    retry: Retry,
+    url: String,
+    headers: HashMap<String, String>,
+}
+
+
+impl Http {
+    pub fn new(client: Client, retry: Retry, url: String, headers: HashMap<String, String>) -> Self {
+        Self { client, retry, url, headers }
+    }
+}
+
+
+impl ErrorReporter for Http {
+    type Error = reqwest::Error;
+
+    async fn report(&self, report: ErrorReport) -> Result<(), Self::Error> {
+        let report = &report;
+        self.retry.run(|| async move {
+            let mut request = self.client.post(&self.url).json(report);
+            for (name, value) in &self.headers {
+                request = request.header(name, value);
+            }
+            request.send().await?.error_for_status()?;
+            Ok(())
+        }, is_transient).await
+    }
+}
@@ -0,0 +1,46 @@
+use crate::ports::outputs::error_reporter::ErrorReporter;
+use crate::config::ErrorReportingConfig;
+use crate::types::ErrorReport;
+use super::retry::Retry;
+use reqwest::Client;
+
+mod sentry;
+mod http;
+
+pub use sentry::{Sentry, parse_dsn};
+pub use http::Http;
+
+
+/// The error reporting service selected in the configuration.
+pub enum ErrorReporting {
+    Disabled,
+    Sentry(Sentry),
+    Http(Http),
+}
+
+
+impl ErrorReporting {
+    /// Reports are sent with `client`, the shared HTTP client, and retried with `retry`.
+    ///
+    /// `config` is expected to be validated. A malformed Sentry DSN disables reporting.
+    pub fn new(config: &ErrorReportingConfig, client: Client, retry: Retry) -> Self {
+        match config {
+            ErrorReportingConfig::Disabled => Self::Disabled,
+            ErrorReportingConfig::Sentry { dsn, environment } => Sentry::new(client, retry, dsn, environment.clone()).map_or(Self::Disabled, Self::Sentry),
+            ErrorReportingConfig::Http { url, headers } => Self::Http(Http::new(client, retry, url.clone(), headers.clone())),
+        }
+    }
+}
+
+
+impl ErrorReporter for ErrorReporting {
+    type Error = reqwest::Error;
+
+    async fn report(&self, report: ErrorReport) -> Result<(), Self::Error> {
+        match self {
+            Self::Disabled => Ok(()),
+            Self::Sentry(sentry) => sentry.report(report).await,
+            Self::Http(http) => http.report(report).await,
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use crate::adaptors::outputs::{http::is_transient, retry::Retry};
+use crate::ports::outputs::error_reporter::ErrorReporter;
+use crate::types::ErrorReport;
+use serde_json::{json, Value};
+use reqwest::Client;
+use url::Url;
+
+
+const CLIENT: &str = concat!("hiveguard/", env!("CARGO_PKG_VERSION"));
+
+
+/// Sends error reports to Sentry's store endpoint, retrying transient failures.
+pub struct Sentry {
+    client: Client,
+    retry: Retry,
+    store_url: String,
+    key: String,
+    environment: Option<String>,
+}
+
+
+impl Sentry {
+    /// Returns `None` when `dsn` is not a valid Sentry DSN. eg `https://<key>@o0.ingest.sentry.io/<project>`
+    pub fn new(client: Client, retry: Retry, dsn: &str, environment: Option<String>) -> Option<Self> {
+        let (store_url, key) = parse_dsn(dsn)?;
+        Some(Self { client, retry, store_url, key, environment })
+    }
+
+    fn event(&self, report: ErrorReport) -> Value {
+        json!({
+            "event_id": format!("{:032x}", rand::random::<u128>()),
+            "timestamp": report.occurred_at.to_rfc3339(),
+            "level": "error",
+            "platform": "other",
+            "release": CLIENT,
+            "environment": self.environment,
+            "message": { "formatted": report.message },
+            "tags": {
+                "request_id": report.context.request_id,
+                "method": report.context.method,
+                "route": report.context.route,
+                "job": report.context.job,
+            },
+        })
+    }
+}
+
+
+impl ErrorReporter for Sentry {
+    type Error = reqwest::Error;
+
+    async fn report(&self, report: ErrorReport) -> Result<(), Self::Error> {
+        let auth = format!("Sentry sentry_version=7, sentry_key={}, sentry_client={}", self.key, CLIENT);
+        let (auth, event) = (&auth, &self.event(report));
+        self.retry.run(|| async move {
+            self.client.post(&self.store_url)
+                .header("X-Sentry-Auth", auth)
+                .json(event)
+                .send().await?
+                .error_for_status()?;
+            Ok(())
+        }, is_transient).await
+    }
+}
+
+
+/// Splits a DSN into the url of its project's store endpoint and its public key.
+pub fn parse_dsn(dsn: &str) -> Option<(String, String)> {
+    let url = Url::parse(dsn).ok()?;
+    let key = url.username();
+    let host = url.host_str()?;
+    let (prefix, project) = url.path().trim_end_matches('/').rsplit_once('/')?;
+    if key.is_empty() || project.is_empty() || !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    let port = url.port().map(|port| format!(":{}", port)).unwrap_or_default();
+    let store_url = format!("{}://{}{}{}/api/{}/store/", url.scheme(), host, port, prefix, project);
+    Some((store_url, key.to_string()))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dsn() {
+        let (url, key) = parse_dsn("https://abc123@o1.ingest.sentry.io/42").unwrap();
+        assert_eq!(url, "https://o1.ingest.sentry.io/api/42/store/");
+        assert_eq!(key, "abc123");
+        let (url, _) = parse_dsn("http://key@localhost:9000/sentry/7").unwrap();
+        assert_eq!(url, "http://localhost:9000/sentry/api/7/store/");
+        assert!(parse_dsn("https://o1.ingest.sentry.io/42").is_none());
+        assert!(parse_dsn("https://key@o1.ingest.sentry.io/").is_none());
+    }
+}
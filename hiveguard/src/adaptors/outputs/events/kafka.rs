@@ -0,0 +1,53 @@
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::message::{Header, OwnedHeaders};
+use crate::ports::outputs::events::EventPublisher;
+use std::collections::HashMap;
+use rdkafka::ClientConfig;
+use crate::types::DomainEvent;
+use super::PublishError;
+use tracing::instrument;
+use std::time::Duration;
+
+
+/// How long a publish waits for room in the queue of the producer.
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+
+/// Produces domain events to a Kafka topic.
+///
+/// Events are keyed by the id of their user, so the events of a user land on the same partition in order.
+/// Their type and version are also sent as the `type` and `version` headers, for consumers routing on them.
+pub struct Kafka {
+    producer: FutureProducer,
+    topic: String,
+}
+
+
+impl Kafka {
+    pub fn new(brokers: &str, topic: String, properties: &HashMap<String, String>) -> Result<Self, PublishError> {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", brokers);
+        for (key, value) in properties {
+            config.set(key, value);
+        }
+        Ok(Self { producer: config.create()?, topic })
+    }
+}
+
+
+impl EventPublisher for Kafka {
+    type Error = PublishError;
+
+    #[instrument(skip_all, fields(event = event.name()), err)]
+    async fn publish(&self, event: &DomainEvent) -> Result<(), Self::Error> {
+        let payload = serde_json::to_vec(event)?;
+        let key = event.kind.user_id().to_hex();
+        let version = event.version.to_string();
+        let headers = OwnedHeaders::new()
+            .insert(Header { key: "type", value: Some(event.name()) })
+            .insert(Header { key: "version", value: Some(&version) });
+        let record = FutureRecord::to(&self.topic).key(&key).payload(&payload).headers(headers);
+        self.producer.send(record, QUEUE_TIMEOUT).await.map_err(|(err, _)| err)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+use crate::ports::outputs::events::EventPublisher;
+use crate::config::EventsConfig;
+use crate::types::DomainEvent;
+
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "nats")]
+mod nats;
+
+#[cfg(feature = "kafka")]
+pub use kafka::Kafka;
+#[cfg(feature = "nats")]
+pub use nats::Nats;
+
+
+pub type PublishError = Box<dyn std::error::Error + Send + Sync>;
+
+
+/// The event bus selected in the configuration.
+pub enum EventSink {
+    /// events are dropped.
+    Disabled,
+    #[cfg(feature = "nats")]
+    Nats(Nats),
+    #[cfg(feature = "kafka")]
+    Kafka(Kafka),
+}
+
+
+impl EventSink {
+    /// Connects to the configured bus.
+    pub async fn new(config: &EventsConfig) -> Result<Self, PublishError> {
+        match config {
+            EventsConfig::Disabled => Ok(Self::Disabled),
+            #[cfg(feature = "nats")]
+            EventsConfig::Nats { url, subject_prefix } => Ok(Self::Nats(Nats::connect(url, subject_prefix.clone()).await?)),
+            #[cfg(feature = "kafka")]
+            EventsConfig::Kafka { brokers, topic, properties } => Ok(Self::Kafka(Kafka::new(brokers, topic.clone(), properties)?)),
+        }
+    }
+}
+
+
+impl EventPublisher for EventSink {
+    type Error = PublishError;
+
+    #[cfg_attr(not(any(feature = "nats", feature = "kafka")), allow(unused_variables))]
+    async fn publish(&self, event: &DomainEvent) -> Result<(), Self::Error> {
+        match self {
+            Self::Disabled => Ok(()),
+            #[cfg(feature = "nats")]
+            Self::Nats(nats) => nats.publish(event).await,
+            #[cfg(feature = "kafka")]
+            Self::Kafka(kafka) => kafka.publish(event).await,
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use crate::ports::outputs::events::EventPublisher;
+use async_nats::{Client, HeaderMap};
+use crate::types::DomainEvent;
+use super::PublishError;
+use tracing::instrument;
+
+
+/// Publishes domain events to NATS, on the subject `<prefix>.<type>`. eg `hiveguard.user.created`
+///
+/// The id of every event is sent as the `Nats-Msg-Id` header, so a JetStream stream drops the duplicates of retried publishes.
+pub struct Nats {
+    client: Client,
+    prefix: String,
+}
+
+
+impl Nats {
+    pub async fn connect(url: &str, prefix: String) -> Result<Self, PublishError> {
+        Ok(Self { client: async_nats::connect(url).await?, prefix })
+    }
+}
+
+
+impl EventPublisher for Nats {
+    type Error = PublishError;
+
+    #[instrument(skip_all, fields(event = event.name()), err)]
+    async fn publish(&self, event: &DomainEvent) -> Result<(), Self::Error> {
+        let payload = serde_json::to_vec(event)?;
+        let mut headers = HeaderMap::new();
+        headers.insert("Nats-Msg-Id", event.id.to_hex().as_str());
+        let subject = format!("{}.{}", self.prefix, event.name());
+        self.client.publish_with_headers(subject, headers, payload.into()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use crate::adaptors::outputs::{http::is_transient, retry::Retry};
+use crate::ports::outputs::geoip::GeoIp;
+use crate::config::LocationFields;
+use reqwest::{Client, StatusCode};
+use std::collections::HashMap;
+use crate::types::Location;
+use serde_json::Value;
+use tracing::instrument;
+use std::net::IpAddr;
+
+
+/// Locates addresses with an HTTP lookup service answering JSON, retrying transient failures. eg `https://ipapi.co/{ip}/json/`
+///
+/// The country, the city and the coordinates are read from the top level fields named in the configuration.
+/// Addresses the service answers `404 Not Found` for, or without a country nor a city, are not located.
+pub struct HttpGeoIp {
+    client: Client,
+    retry: Retry,
+    /// the URL of the lookups, where `{ip}` is replaced by the address.
+    url: String,
+    fields: LocationFields,
+    /// sent with every request, typically the credentials of the service.
+    headers: HashMap<String, String>,
+}
+
+
+impl HttpGeoIp {
+    pub fn new(client: Client, retry: Retry, url: String, fields: LocationFields, headers: HashMap<String, String>) -> Self {
+        Self { client, retry, url, fields, headers }
+    }
+
+    fn location(&self, body: &Value) -> Option<Location> {
+        let field = |name: &str| body.get(name).and_then(Value::as_str).filter(|value| !value.is_empty()).map(str::to_string);
+        let coordinate = |name: &str| body.get(name).and_then(Value::as_f64);
+        let location = Location {
+            country: field(&self.fields.country).map(|country| country.to_uppercase()),
+            city: field(&self.fields.city),
+            latitude: coordinate(&self.fields.latitude),
+            longitude: coordinate(&self.fields.longitude),
+        };
+        (location.country.is_some() || location.city.is_some()).then_some(location)
+    }
+}
+
+
+impl GeoIp for HttpGeoIp {
+    type Error = reqwest::Error;
+
+    #[instrument(skip(self), err)]
+    async fn locate(&self, ip: IpAddr) -> Result<Option<Location>, Self::Error> {
+        let url = self.url.replace("{ip}", &ip.to_string());
+        let url = &url;
+        let body = self.retry.run(|| async move {
+            let mut request = self.client.get(url);
+            for (name, value) in &self.headers {
+                request = request.header(name, value);
+            }
+            let response = request.send().await?;
+            if response.status() == StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            Ok(Some(response.error_for_status()?.json::<Value>().await?))
+        }, is_transient).await?;
+        Ok(body.and_then(|body| self.location(&body)))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{HttpConfig, RetryConfig};
+    use serde_json::json;
+
+    #[test]
+    fn test_locations_are_read_from_the_configured_fields() {
+        let client = crate::adaptors::outputs::http::client(&HttpConfig::default()).unwrap();
+        let fields = LocationFields { country: "countryCode".into(), latitude: "lat".into(), longitude: "lon".into(), ..Default::default() };
+        let geoip = HttpGeoIp::new(client, Retry::new(&RetryConfig::default()), "http://ip-api.com/json/{ip}".into(), fields, HashMap::new());
+        let nairobi = Location { country: Some("KE".into()), city: Some("Nairobi".into()), latitude: Some(-1.28), longitude: Some(36.82) };
+        assert_eq!(geoip.location(&json!({"countryCode": "ke", "city": "Nairobi", "lat": -1.28, "lon": 36.82})), Some(nairobi));
+        assert_eq!(geoip.location(&json!({"country_code": "KE", "city": ""})), None);
+    }
+}
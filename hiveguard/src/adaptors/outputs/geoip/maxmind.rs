@@ -0,0 +1,37 @@
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+use crate::ports::outputs::geoip::GeoIp;
+use crate::types::Location;
+use std::net::IpAddr;
+use std::path::Path;
+
+
+/// Locates addresses in a MaxMind GeoIP2 or GeoLite2 City database, read in memory when it is opened.
+///
+/// The database is not reloaded, so an updated file is only picked up on restart.
+pub struct MaxMind {
+    reader: Reader<Vec<u8>>,
+}
+
+
+impl MaxMind {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MaxMindDBError> {
+        Ok(Self { reader: Reader::open_readfile(path)? })
+    }
+}
+
+
+impl GeoIp for MaxMind {
+    type Error = MaxMindDBError;
+
+    async fn locate(&self, ip: IpAddr) -> Result<Option<Location>, Self::Error> {
+        let city = match self.reader.lookup::<geoip2::City>(ip) {
+            Ok(city) => city,
+            Err(MaxMindDBError::AddressNotFoundError(_)) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let country = city.country.and_then(|country| country.iso_code).map(str::to_string);
+        let (latitude, longitude) = city.location.map_or((None, None), |location| (location.latitude, location.longitude));
+        let city = city.city.and_then(|city| city.names).and_then(|names| names.get("en").map(|name| name.to_string()));
+        Ok(Some(Location { country, city, latitude, longitude }))
+    }
+}
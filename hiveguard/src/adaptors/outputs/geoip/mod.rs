@@ -0,0 +1,54 @@
+use crate::ports::outputs::geoip::GeoIp;
+use crate::config::GeoIpConfig;
+use crate::types::Location;
+use super::retry::Retry;
+use std::net::IpAddr;
+use reqwest::Client;
+
+#[cfg(feature = "maxmind")]
+mod maxmind;
+mod http;
+
+#[cfg(feature = "maxmind")]
+pub use maxmind::MaxMind;
+pub use self::http::HttpGeoIp;
+
+
+pub type GeoIpError = Box<dyn std::error::Error + Send + Sync>;
+
+
+/// The GeoIP database selected in the configuration.
+pub enum GeoIpSource {
+    /// nothing is located.
+    Disabled,
+    #[cfg(feature = "maxmind")]
+    MaxMind(MaxMind),
+    Http(Box<HttpGeoIp>),
+}
+
+
+impl GeoIpSource {
+    /// Lookup services are called with `client`, the shared HTTP client, and retried with `retry`.
+    pub fn new(config: &GeoIpConfig, client: Client, retry: Retry) -> Result<Self, GeoIpError> {
+        match config {
+            GeoIpConfig::Disabled => Ok(Self::Disabled),
+            #[cfg(feature = "maxmind")]
+            GeoIpConfig::MaxMind { path } => Ok(Self::MaxMind(MaxMind::open(path)?)),
+            GeoIpConfig::Http { url, fields, headers } => Ok(Self::Http(Box::new(HttpGeoIp::new(client, retry, url.clone(), fields.clone(), headers.clone())))),
+        }
+    }
+}
+
+
+impl GeoIp for GeoIpSource {
+    type Error = GeoIpError;
+
+    async fn locate(&self, ip: IpAddr) -> Result<Option<Location>, Self::Error> {
+        match self {
+            Self::Disabled => Ok(None),
+            #[cfg(feature = "maxmind")]
+            Self::MaxMind(maxmind) => Ok(maxmind.locate(ip).await?),
+            Self::Http(http) => Ok(http.locate(ip).await?),
+        }
+    }
+}
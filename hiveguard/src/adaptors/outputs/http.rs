@@ -0,0 +1,43 @@
+use reqwest::{Client, Proxy, StatusCode};
+use crate::config::HttpConfig;
+use std::time::Duration;
+
+
+const USER_AGENT: &str = concat!("hiveguard/", env!("CARGO_PKG_VERSION"));
+
+
+/// Builds the HTTP client shared by every outbound adaptor.
+///
+/// The client pools its connections and is cheap to clone, so the adaptors clone this one rather than building their own.
+/// `config` is expected to be validated.
+pub fn client(config: &HttpConfig) -> Result<Client, reqwest::Error> {
+    let mut builder = Client::builder()
+        .user_agent(USER_AGENT)
+        .pool_max_idle_per_host(config.pool_max_idle_per_host)
+        .pool_idle_timeout(Duration::from_secs(config.pool_idle_timeout_secs))
+        .connect_timeout(Duration::from_millis(config.connect_timeout_ms))
+        .timeout(Duration::from_millis(config.timeout_ms));
+    if let Some(proxy) = &config.proxy {
+        builder = builder.proxy(Proxy::all(proxy)?);
+    }
+    builder.build()
+}
+
+
+/// Whether `err` may not happen again on retry: a timeout, a connection failure, `429 Too Many Requests` or a server error.
+pub fn is_transient(err: &reqwest::Error) -> bool {
+    err.is_timeout() || err.is_connect() || err.status().is_some_and(|status| status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_is_built_from_the_config() {
+        assert!(client(&HttpConfig::default()).is_ok());
+        let config = HttpConfig { proxy: Some("http://proxy.internal:3128".into()), ..Default::default() };
+        assert!(client(&config).is_ok());
+    }
+}
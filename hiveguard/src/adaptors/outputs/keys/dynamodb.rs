@@ -0,0 +1,90 @@
+use crate::types::{ConversionError, DatabaseError, SigningKey};
+use crate::ports::outputs::keys::KeyStore;
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+use aws_sdk_dynamodb::Client;
+use tracing::instrument;
+
+
+/// Signing keys in a DynamoDB table keyed by `id`, the kid, and stored as JSON in the `key` attribute.
+///
+/// The key pairs are stored as they are, so access to the table has to be restricted like access to the keys.
+pub struct DynamoDBKeys {
+    client: Client,
+    table: String,
+}
+
+
+impl DynamoDBKeys {
+    pub fn new(client: Client, table: String) -> Self {
+        Self { client, table }
+    }
+
+    fn item(key: &SigningKey) -> Result<HashMap<String, AttributeValue>, DatabaseError> {
+        let json = serde_json::to_string(key).map_err(|_| ConversionError::UnexpectedDataType("key"))?;
+        Ok(HashMap::from([
+            (String::from("id"), AttributeValue::S(key.kid.clone())),
+            (String::from("key"), AttributeValue::S(json)),
+        ]))
+    }
+
+    fn decode(mut item: HashMap<String, AttributeValue>) -> Result<SigningKey, DatabaseError> {
+        match item.remove("key") {
+            Some(AttributeValue::S(json)) => Ok(serde_json::from_str(&json).map_err(|_| ConversionError::UnexpectedDataType("key"))?),
+            _ => Err(ConversionError::UnexpectedDataType("key").into()),
+        }
+    }
+}
+
+
+impl KeyStore for DynamoDBKeys {
+    type Error = DatabaseError;
+
+    #[instrument(skip_all, fields(table = %self.table, kid = %key.kid), err)]
+    async fn create(&self, key: SigningKey) -> Result<(), Self::Error> {
+        self.client.put_item()
+            .table_name(&self.table)
+            .set_item(Some(Self::item(&key)?))
+            .condition_expression("attribute_not_exists(id)")
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(table = %self.table, kid), err)]
+    async fn get(&self, kid: &str) -> Result<Option<SigningKey>, Self::Error> {
+        let output = self.client.get_item().table_name(&self.table).key("id", AttributeValue::S(kid.to_string())).send().await?;
+        output.item.map(Self::decode).transpose()
+    }
+
+    #[instrument(skip_all, fields(table = %self.table), err)]
+    async fn list(&self) -> Result<Vec<SigningKey>, Self::Error> {
+        let mut keys = Vec::new();
+        let mut start = None;
+        loop {
+            let output = self.client.scan().table_name(&self.table).set_exclusive_start_key(start).send().await?;
+            for item in output.items.unwrap_or_default() {
+                keys.push(Self::decode(item)?);
+            }
+            start = output.last_evaluated_key;
+            if start.is_none() {
+                break Ok(keys);
+            }
+        }
+    }
+
+    #[instrument(skip_all, fields(table = %self.table, kid = %key.kid), err)]
+    async fn update(&self, key: SigningKey) -> Result<(), Self::Error> {
+        let result = self.client.put_item()
+            .table_name(&self.table)
+            .set_item(Some(Self::item(&key)?))
+            .condition_expression("attribute_exists(id)")
+            .send()
+            .await;
+        match result.map_err(DatabaseError::from) {
+            // the condition failing means there is nothing to update.
+            Err(DatabaseError::AlreadyExists) => Err(DatabaseError::KeyNotFound),
+            result => result.map(|_| ()),
+        }
+    }
+}
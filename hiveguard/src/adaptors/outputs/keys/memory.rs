@@ -0,0 +1,50 @@
+use crate::types::{DatabaseError, SigningKey};
+use crate::ports::outputs::keys::KeyStore;
+use std::sync::{PoisonError, RwLock};
+use std::collections::HashMap;
+
+
+/// Signing keys kept in memory, for tests and local development. They are lost on restart,
+/// and tokens signed with them can no longer be verified.
+#[derive(Default)]
+pub struct MemoryKeys {
+    keys: RwLock<HashMap<String, SigningKey>>,
+}
+
+
+impl MemoryKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+
+impl KeyStore for MemoryKeys {
+    type Error = DatabaseError;
+
+    async fn create(&self, key: SigningKey) -> Result<(), Self::Error> {
+        let mut keys = self.keys.write().unwrap_or_else(PoisonError::into_inner);
+        if keys.contains_key(&key.kid) {
+            return Err(DatabaseError::AlreadyExists);
+        }
+        keys.insert(key.kid.clone(), key);
+        Ok(())
+    }
+
+    async fn get(&self, kid: &str) -> Result<Option<SigningKey>, Self::Error> {
+        Ok(self.keys.read().unwrap_or_else(PoisonError::into_inner).get(kid).cloned())
+    }
+
+    async fn list(&self) -> Result<Vec<SigningKey>, Self::Error> {
+        Ok(self.keys.read().unwrap_or_else(PoisonError::into_inner).values().cloned().collect())
+    }
+
+    async fn update(&self, key: SigningKey) -> Result<(), Self::Error> {
+        let mut keys = self.keys.write().unwrap_or_else(PoisonError::into_inner);
+        match keys.get_mut(&key.kid) {
+            Some(stored) => *stored = key,
+            None => return Err(DatabaseError::KeyNotFound),
+        }
+        Ok(())
+    }
+}
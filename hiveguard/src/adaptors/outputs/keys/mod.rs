@@ -0,0 +1,3 @@
+#[cfg(feature = "dynamodb")]
+pub mod dynamodb;
+pub mod memory;
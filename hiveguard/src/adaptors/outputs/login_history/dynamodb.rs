@@ -0,0 +1,76 @@
+use crate::types::{ConversionError, DatabaseError, Id, LoginAttempt};
+use crate::ports::outputs::login_history::LoginHistory;
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+use aws_sdk_dynamodb::Client;
+use chrono::TimeDelta;
+use tracing::instrument;
+
+
+/// A login history in a DynamoDB table keyed by `id`, with a `user_id-index` to find the attempts of a user.
+///
+/// Every attempt is stored as JSON in the `attempt` attribute, and expires `retention` after it occurred
+/// through the time to live of the table, on `expires`.
+pub struct DynamoDBHistory {
+    client: Client,
+    table: String,
+    retention: TimeDelta,
+}
+
+
+impl DynamoDBHistory {
+    pub fn new(client: Client, table: String, retention: TimeDelta) -> Self {
+        Self { client, table, retention }
+    }
+}
+
+
+impl LoginHistory for DynamoDBHistory {
+    type Error = DatabaseError;
+
+    #[instrument(skip_all, fields(table = %self.table, user_id = %attempt.user_id.to_hex()), err)]
+    async fn record(&self, attempt: LoginAttempt) -> Result<(), Self::Error> {
+        let json = serde_json::to_string(&attempt).map_err(|_| ConversionError::UnexpectedDataType("attempt"))?;
+        let expires = attempt.occurred_at + self.retention;
+        let item = HashMap::from([
+            (String::from("id"), attempt.id.into()),
+            (String::from("user_id"), attempt.user_id.into()),
+            (String::from("expires"), AttributeValue::N(expires.timestamp().to_string())),
+            (String::from("attempt"), AttributeValue::S(json)),
+        ]);
+        self.client.put_item().table_name(&self.table).set_item(Some(item)).send().await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(table = %self.table, user_id = %user_id.to_hex()), err)]
+    async fn recent(&self, user_id: Id, limit: usize) -> Result<Vec<LoginAttempt>, Self::Error> {
+        let mut attempts = Vec::new();
+        let mut start = None;
+        loop {
+            let output = self.client.query()
+                .table_name(&self.table)
+                .index_name("user_id-index")
+                .key_condition_expression("user_id = :user_id")
+                .expression_attribute_values(":user_id", user_id.into())
+                .set_exclusive_start_key(start)
+                .send()
+                .await?;
+            for mut item in output.items.unwrap_or_default() {
+                match item.remove("attempt") {
+                    Some(AttributeValue::S(json)) => attempts.push(serde_json::from_str::<LoginAttempt>(&json).map_err(|_| ConversionError::UnexpectedDataType("attempt"))?),
+                    _ => return Err(ConversionError::UnexpectedDataType("attempt").into()),
+                }
+            }
+            start = output.last_evaluated_key;
+            if start.is_none() {
+                break;
+            }
+        }
+        // the time to live deletes expired items lazily.
+        let since = chrono::Utc::now() - self.retention;
+        attempts.retain(|attempt| attempt.occurred_at > since);
+        attempts.sort_by_key(|attempt| std::cmp::Reverse(attempt.occurred_at));
+        attempts.truncate(limit);
+        Ok(attempts)
+    }
+}
@@ -0,0 +1,44 @@
+use std::sync::{PoisonError, RwLock};
+use crate::ports::outputs::login_history::LoginHistory;
+use crate::types::{DatabaseError, Id, LoginAttempt};
+use std::collections::HashMap;
+use chrono::{TimeDelta, Utc};
+
+
+/// A login history kept in memory, for tests and local development. It is lost on restart.
+///
+/// The attempts of a user older than `retention` are dropped whenever they log in again.
+pub struct MemoryHistory {
+    attempts: RwLock<HashMap<Id, Vec<LoginAttempt>>>,
+    retention: TimeDelta,
+}
+
+
+impl MemoryHistory {
+    pub fn new(retention: TimeDelta) -> Self {
+        Self { attempts: Default::default(), retention }
+    }
+}
+
+
+impl LoginHistory for MemoryHistory {
+    type Error = DatabaseError;
+
+    async fn record(&self, attempt: LoginAttempt) -> Result<(), Self::Error> {
+        let since = Utc::now() - self.retention;
+        let mut attempts = self.attempts.write().unwrap_or_else(PoisonError::into_inner);
+        let history = attempts.entry(attempt.user_id).or_default();
+        history.retain(|kept| kept.occurred_at > since);
+        history.push(attempt);
+        Ok(())
+    }
+
+    async fn recent(&self, user_id: Id, limit: usize) -> Result<Vec<LoginAttempt>, Self::Error> {
+        let since = Utc::now() - self.retention;
+        let attempts = self.attempts.read().unwrap_or_else(PoisonError::into_inner);
+        let mut recent = attempts.get(&user_id).into_iter().flatten().filter(|attempt| attempt.occurred_at > since).cloned().collect::<Vec<_>>();
+        recent.sort_by_key(|attempt| std::cmp::Reverse(attempt.occurred_at));
+        recent.truncate(limit);
+        Ok(recent)
+    }
+}
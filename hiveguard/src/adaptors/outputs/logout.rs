@@ -0,0 +1,32 @@
+use crate::adaptors::outputs::{http::is_transient, retry::Retry};
+use crate::ports::outputs::logout::LogoutNotifier;
+use tracing::instrument;
+use reqwest::Client;
+use url::Url;
+
+
+/// POSTs logout tokens as forms to the back-channel logout uris of services, retrying transient failures.
+pub struct BackChannel {
+    client: Client,
+    retry: Retry,
+}
+
+
+impl BackChannel {
+    pub fn new(client: Client, retry: Retry) -> Self {
+        Self { client, retry }
+    }
+}
+
+
+impl LogoutNotifier for BackChannel {
+    type Error = reqwest::Error;
+
+    #[instrument(skip_all, fields(uri = %uri), err)]
+    async fn notify(&self, uri: &Url, logout_token: &str) -> Result<(), Self::Error> {
+        self.retry.run(|| async move {
+            self.client.post(uri.clone()).form(&[("logout_token", logout_token)]).send().await?.error_for_status()?;
+            Ok(())
+        }, is_transient).await
+    }
+}
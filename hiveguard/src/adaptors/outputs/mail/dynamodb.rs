@@ -0,0 +1,87 @@
+use crate::types::{ConversionError, DatabaseError, Mail};
+use aws_sdk_dynamodb::types::AttributeValue;
+use crate::ports::outputs::mail::MailQueue;
+use std::collections::HashMap;
+use aws_sdk_dynamodb::Client;
+use chrono::{DateTime, Utc};
+use tracing::instrument;
+
+
+/// A mail queue in a DynamoDB table keyed by `id`.
+///
+/// Every mail is stored as JSON in the `mail` attribute, next to the `status` and `next_attempt_at` it is looked up by.
+/// Due mails are found with a scan, which is fine while the table only holds a few days of mails.
+pub struct DynamoDBQueue {
+    client: Client,
+    table: String,
+}
+
+
+impl DynamoDBQueue {
+    pub fn new(client: Client, table: String) -> Self {
+        Self { client, table }
+    }
+}
+
+
+impl MailQueue for DynamoDBQueue {
+    type Error = DatabaseError;
+
+    #[instrument(skip_all, fields(table = %self.table, mail_id = %mail.id), err)]
+    async fn enqueue(&self, mail: Mail) -> Result<(), Self::Error> {
+        self.client.put_item()
+            .table_name(&self.table)
+            .set_item(Some(item(mail)?))
+            .condition_expression("attribute_not_exists(id)")
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(table = %self.table), err)]
+    async fn due(&self, now: DateTime<Utc>, limit: usize) -> Result<Vec<Mail>, Self::Error> {
+        let mut mails = Vec::new();
+        let mut start = None;
+        loop {
+            let output = self.client.scan()
+                .table_name(&self.table)
+                .filter_expression("#status = :pending AND next_attempt_at <= :now")
+                .expression_attribute_names("#status", "status")
+                .expression_attribute_values(":pending", AttributeValue::S("pending".into()))
+                .expression_attribute_values(":now", AttributeValue::N(now.timestamp().to_string()))
+                .set_exclusive_start_key(start)
+                .send()
+                .await?;
+            for mut item in output.items.unwrap_or_default() {
+                match item.remove("mail") {
+                    Some(AttributeValue::S(json)) => mails.push(serde_json::from_str::<Mail>(&json).map_err(|_| ConversionError::UnexpectedDataType("mail"))?),
+                    _ => return Err(ConversionError::UnexpectedDataType("mail").into()),
+                }
+            }
+            start = output.last_evaluated_key;
+            if start.is_none() {
+                break;
+            }
+        }
+        mails.sort_by_key(|mail| mail.next_attempt_at);
+        mails.truncate(limit);
+        Ok(mails)
+    }
+
+    #[instrument(skip_all, fields(table = %self.table, mail_id = %mail.id, status = mail.status.as_str()), err)]
+    async fn update(&self, mail: Mail) -> Result<(), Self::Error> {
+        self.client.put_item().table_name(&self.table).set_item(Some(item(mail)?)).send().await?;
+        Ok(())
+    }
+}
+
+
+fn item(mail: Mail) -> Result<HashMap<String, AttributeValue>, ConversionError> {
+    let json = serde_json::to_string(&mail).map_err(|_| ConversionError::UnexpectedDataType("mail"))?;
+    Ok(HashMap::from([
+        (String::from("id"), mail.id.into()),
+        (String::from("status"), AttributeValue::S(mail.status.as_str().into())),
+        (String::from("next_attempt_at"), AttributeValue::N(mail.next_attempt_at.timestamp().to_string())),
+        (String::from("mail"), AttributeValue::S(json)),
+    ]))
+}
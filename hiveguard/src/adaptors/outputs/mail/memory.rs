@@ -0,0 +1,50 @@
+use std::sync::{PoisonError, RwLock};
+use crate::ports::outputs::mail::MailQueue;
+use crate::types::{DatabaseError, Id, Mail, MailStatus};
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+
+
+/// A mail queue kept in memory, for tests and local development. Mails not yet delivered are lost on restart.
+#[derive(Default)]
+pub struct MemoryQueue {
+    mails: RwLock<HashMap<Id, Mail>>,
+}
+
+
+impl MemoryQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, id: Id) -> Option<Mail> {
+        self.mails.read().unwrap_or_else(PoisonError::into_inner).get(&id).cloned()
+    }
+}
+
+
+impl MailQueue for MemoryQueue {
+    type Error = DatabaseError;
+
+    async fn enqueue(&self, mail: Mail) -> Result<(), Self::Error> {
+        let mut mails = self.mails.write().unwrap_or_else(PoisonError::into_inner);
+        if mails.contains_key(&mail.id) {
+            return Err(DatabaseError::AlreadyExists);
+        }
+        mails.insert(mail.id, mail);
+        Ok(())
+    }
+
+    async fn due(&self, now: DateTime<Utc>, limit: usize) -> Result<Vec<Mail>, Self::Error> {
+        let mails = self.mails.read().unwrap_or_else(PoisonError::into_inner);
+        let mut due = mails.values().filter(|mail| mail.status == MailStatus::Pending && mail.next_attempt_at <= now).cloned().collect::<Vec<_>>();
+        due.sort_by_key(|mail| mail.next_attempt_at);
+        due.truncate(limit);
+        Ok(due)
+    }
+
+    async fn update(&self, mail: Mail) -> Result<(), Self::Error> {
+        self.mails.write().unwrap_or_else(PoisonError::into_inner).insert(mail.id, mail);
+        Ok(())
+    }
+}
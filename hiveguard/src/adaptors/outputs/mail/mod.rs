@@ -0,0 +1,5 @@
+#[cfg(feature = "dynamodb")]
+pub mod dynamodb;
+#[cfg(feature = "email")]
+pub mod smtp;
+pub mod memory;
@@ -0,0 +1,48 @@
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use lettre::message::{Mailbox, MultiPart};
+use crate::ports::outputs::mail::Mailer;
+use crate::config::SmtpConfig;
+use crate::types::Mail;
+use tracing::instrument;
+
+
+/// Sends mails through the SMTP server of `SmtpConfig`.
+pub struct Smtp {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+
+impl Smtp {
+    pub fn new(config: &SmtpConfig) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::from_url(&config.url)?.build();
+        Ok(Self { transport, from: config.from.parse()? })
+    }
+
+    /// Connects to the server and says hello, without sending anything.
+    pub async fn check(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self.transport.test_connection().await? {
+            true => Ok(()),
+            false => Err("the server did not accept the connection".into()),
+        }
+    }
+}
+
+
+impl Mailer for Smtp {
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    #[instrument(skip_all, fields(mail_id = %mail.id), err)]
+    async fn send(&self, mail: &Mail) -> Result<(), Self::Error> {
+        let builder = Message::builder()
+            .from(self.from.clone())
+            .to(Mailbox::new(None, mail.to.clone()))
+            .subject(&mail.subject);
+        let message = match &mail.html {
+            Some(html) => builder.multipart(MultiPart::alternative_plain_html(mail.text.clone(), html.clone()))?,
+            None => builder.body(mail.text.clone())?,
+        };
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
@@ -1 +1,22 @@
-mod databases;
\ No newline at end of file
+pub mod databases;
+pub mod counters;
+pub mod mail;
+pub mod outbox;
+pub mod login_history;
+pub mod audit_log;
+pub mod logout;
+pub mod organisations;
+pub mod events;
+pub mod geoip;
+pub mod keys;
+pub mod signers;
+pub mod directory;
+pub mod error_reporting;
+pub mod secrets;
+pub mod circuit_breaker;
+pub mod verification;
+pub mod retry;
+pub mod http;
+pub mod security_events;
+#[cfg(feature = "s3")]
+pub mod s3;
\ No newline at end of file
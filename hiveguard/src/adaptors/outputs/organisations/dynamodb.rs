@@ -0,0 +1,173 @@
+use crate::types::{ConversionError, DatabaseError, Id, Organisation, Membership};
+use crate::ports::outputs::organisations::OrganisationStore;
+use aws_sdk_dynamodb::types::AttributeValue;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use aws_sdk_dynamodb::Client;
+use tracing::instrument;
+
+
+/// Organisations and memberships in two DynamoDB tables.
+///
+/// Organisations are keyed by `id`, with a `parent-index` to find the ones under an organisation.
+/// Memberships are keyed by `id`, the hex ids of the organisation and the user joined by `:`, with an `org_id-index`
+/// and a `user_id-index`. Both are stored as JSON, in the `organisation` and `membership` attributes.
+pub struct DynamoDBOrganisations {
+    client: Client,
+    organisations_table: String,
+    memberships_table: String,
+}
+
+
+impl DynamoDBOrganisations {
+    pub fn new(client: Client, organisations_table: String, memberships_table: String) -> Self {
+        Self { client, organisations_table, memberships_table }
+    }
+
+    /// The items of `table` whose `field` is `id`, through the `<field>-index`.
+    async fn query(&self, table: &str, field: &str, id: Id) -> Result<Vec<HashMap<String, AttributeValue>>, DatabaseError> {
+        let mut items = Vec::new();
+        let mut start = None;
+        loop {
+            let output = self.client.query()
+                .table_name(table)
+                .index_name(format!("{}-index", field))
+                .key_condition_expression("#field = :id")
+                .expression_attribute_names("#field", field)
+                .expression_attribute_values(":id", id.into())
+                .set_exclusive_start_key(start)
+                .send()
+                .await?;
+            items.extend(output.items.unwrap_or_default());
+            start = output.last_evaluated_key;
+            if start.is_none() {
+                break Ok(items);
+            }
+        }
+    }
+}
+
+
+fn membership_key(org_id: Id, user_id: Id) -> AttributeValue {
+    AttributeValue::S(format!("{}:{}", org_id.to_hex(), user_id.to_hex()))
+}
+
+
+fn encode<T: serde::Serialize>(value: &T, attribute: &'static str) -> Result<AttributeValue, DatabaseError> {
+    let json = serde_json::to_string(value).map_err(|_| ConversionError::UnexpectedDataType(attribute))?;
+    Ok(AttributeValue::S(json))
+}
+
+
+fn decode<T: DeserializeOwned>(mut item: HashMap<String, AttributeValue>, attribute: &'static str) -> Result<T, DatabaseError> {
+    match item.remove(attribute) {
+        Some(AttributeValue::S(json)) => Ok(serde_json::from_str(&json).map_err(|_| ConversionError::UnexpectedDataType(attribute))?),
+        _ => Err(ConversionError::UnexpectedDataType(attribute).into()),
+    }
+}
+
+
+impl DynamoDBOrganisations {
+    fn organisation_item(organisation: &Organisation) -> Result<HashMap<String, AttributeValue>, DatabaseError> {
+        let mut item = HashMap::from([
+            (String::from("id"), organisation.id.into()),
+            (String::from("organisation"), encode(organisation, "organisation")?),
+        ]);
+        if let Some(parent) = organisation.parent {
+            item.insert(String::from("parent"), parent.into());
+        }
+        Ok(item)
+    }
+}
+
+
+impl OrganisationStore for DynamoDBOrganisations {
+    type Error = DatabaseError;
+
+    #[instrument(skip_all, fields(table = %self.organisations_table, org_id = %organisation.id.to_hex()), err)]
+    async fn create(&self, organisation: Organisation) -> Result<(), Self::Error> {
+        self.client.put_item()
+            .table_name(&self.organisations_table)
+            .set_item(Some(Self::organisation_item(&organisation)?))
+            .condition_expression("attribute_not_exists(id)")
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(table = %self.organisations_table, org_id = %id.to_hex()), err)]
+    async fn get(&self, id: Id) -> Result<Option<Organisation>, Self::Error> {
+        let output = self.client.get_item().table_name(&self.organisations_table).key("id", id.into()).send().await?;
+        output.item.map(|item| decode(item, "organisation")).transpose()
+    }
+
+    #[instrument(skip_all, fields(table = %self.organisations_table), err)]
+    async fn all(&self) -> Result<Vec<Organisation>, Self::Error> {
+        let mut organisations = Vec::new();
+        let mut start = None;
+        loop {
+            let output = self.client.scan().table_name(&self.organisations_table).set_exclusive_start_key(start).send().await?;
+            for item in output.items.unwrap_or_default() {
+                organisations.push(decode(item, "organisation")?);
+            }
+            start = output.last_evaluated_key;
+            if start.is_none() {
+                break Ok(organisations);
+            }
+        }
+    }
+
+    #[instrument(skip_all, fields(table = %self.organisations_table, org_id = %parent.to_hex()), err)]
+    async fn children(&self, parent: Id) -> Result<Vec<Organisation>, Self::Error> {
+        self.query(&self.organisations_table, "parent", parent).await?.into_iter().map(|item| decode(item, "organisation")).collect()
+    }
+
+    #[instrument(skip_all, fields(table = %self.organisations_table, org_id = %organisation.id.to_hex()), err)]
+    async fn update(&self, organisation: Organisation) -> Result<(), Self::Error> {
+        let result = self.client.put_item()
+            .table_name(&self.organisations_table)
+            .set_item(Some(Self::organisation_item(&organisation)?))
+            .condition_expression("attribute_exists(id)")
+            .send()
+            .await;
+        match result.map_err(DatabaseError::from) {
+            // the condition failing means there is nothing to update.
+            Err(DatabaseError::AlreadyExists) => Err(DatabaseError::OrganisationNotFound),
+            result => result.map(|_| ()),
+        }
+    }
+
+    #[instrument(skip_all, fields(table = %self.organisations_table, org_id = %id.to_hex()), err)]
+    async fn delete(&self, id: Id) -> Result<(), Self::Error> {
+        self.client.delete_item().table_name(&self.organisations_table).key("id", id.into()).send().await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(table = %self.memberships_table, org_id = %membership.org_id.to_hex(), user_id = %membership.user_id.to_hex()), err)]
+    async fn set_membership(&self, membership: Membership) -> Result<(), Self::Error> {
+        let item = HashMap::from([
+            (String::from("id"), membership_key(membership.org_id, membership.user_id)),
+            (String::from("org_id"), membership.org_id.into()),
+            (String::from("user_id"), membership.user_id.into()),
+            (String::from("membership"), encode(&membership, "membership")?),
+        ]);
+        self.client.put_item().table_name(&self.memberships_table).set_item(Some(item)).send().await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(table = %self.memberships_table, org_id = %org_id.to_hex(), user_id = %user_id.to_hex()), err)]
+    async fn remove_membership(&self, org_id: Id, user_id: Id) -> Result<(), Self::Error> {
+        self.client.delete_item().table_name(&self.memberships_table).key("id", membership_key(org_id, user_id)).send().await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(table = %self.memberships_table, org_id = %org_id.to_hex()), err)]
+    async fn members(&self, org_id: Id) -> Result<Vec<Membership>, Self::Error> {
+        self.query(&self.memberships_table, "org_id", org_id).await?.into_iter().map(|item| decode(item, "membership")).collect()
+    }
+
+    #[instrument(skip_all, fields(table = %self.memberships_table, user_id = %user_id.to_hex()), err)]
+    async fn memberships(&self, user_id: Id) -> Result<Vec<Membership>, Self::Error> {
+        self.query(&self.memberships_table, "user_id", user_id).await?.into_iter().map(|item| decode(item, "membership")).collect()
+    }
+}
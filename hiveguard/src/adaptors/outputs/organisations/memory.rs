@@ -0,0 +1,82 @@
+use crate::ports::outputs::organisations::OrganisationStore;
+use crate::types::{DatabaseError, Id, Organisation, Membership};
+use std::sync::{PoisonError, RwLock};
+use std::collections::HashMap;
+
+
+/// Organisations kept in memory, for tests and local development. They are lost on restart.
+#[derive(Default)]
+pub struct MemoryOrganisations {
+    organisations: RwLock<HashMap<Id, Organisation>>,
+    /// by organisation, then user.
+    memberships: RwLock<HashMap<(Id, Id), Membership>>,
+}
+
+
+impl MemoryOrganisations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+
+impl OrganisationStore for MemoryOrganisations {
+    type Error = DatabaseError;
+
+    async fn create(&self, organisation: Organisation) -> Result<(), Self::Error> {
+        let mut organisations = self.organisations.write().unwrap_or_else(PoisonError::into_inner);
+        if organisations.contains_key(&organisation.id) {
+            return Err(DatabaseError::AlreadyExists);
+        }
+        organisations.insert(organisation.id, organisation);
+        Ok(())
+    }
+
+    async fn get(&self, id: Id) -> Result<Option<Organisation>, Self::Error> {
+        Ok(self.organisations.read().unwrap_or_else(PoisonError::into_inner).get(&id).cloned())
+    }
+
+    async fn all(&self) -> Result<Vec<Organisation>, Self::Error> {
+        Ok(self.organisations.read().unwrap_or_else(PoisonError::into_inner).values().cloned().collect())
+    }
+
+    async fn children(&self, parent: Id) -> Result<Vec<Organisation>, Self::Error> {
+        let organisations = self.organisations.read().unwrap_or_else(PoisonError::into_inner);
+        Ok(organisations.values().filter(|organisation| organisation.parent == Some(parent)).cloned().collect())
+    }
+
+    async fn update(&self, organisation: Organisation) -> Result<(), Self::Error> {
+        let mut organisations = self.organisations.write().unwrap_or_else(PoisonError::into_inner);
+        match organisations.get_mut(&organisation.id) {
+            Some(stored) => *stored = organisation,
+            None => return Err(DatabaseError::OrganisationNotFound),
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, id: Id) -> Result<(), Self::Error> {
+        self.organisations.write().unwrap_or_else(PoisonError::into_inner).remove(&id);
+        Ok(())
+    }
+
+    async fn set_membership(&self, membership: Membership) -> Result<(), Self::Error> {
+        let mut memberships = self.memberships.write().unwrap_or_else(PoisonError::into_inner);
+        memberships.insert((membership.org_id, membership.user_id), membership);
+        Ok(())
+    }
+
+    async fn remove_membership(&self, org_id: Id, user_id: Id) -> Result<(), Self::Error> {
+        self.memberships.write().unwrap_or_else(PoisonError::into_inner).remove(&(org_id, user_id));
+        Ok(())
+    }
+
+    async fn members(&self, org_id: Id) -> Result<Vec<Membership>, Self::Error> {
+        let memberships = self.memberships.read().unwrap_or_else(PoisonError::into_inner);
+        Ok(memberships.values().filter(|membership| membership.org_id == org_id).cloned().collect())
+    }
+
+    async fn memberships(&self, user_id: Id) -> Result<Vec<Membership>, Self::Error> {
+        let memberships = self.memberships.read().unwrap_or_else(PoisonError::into_inner);
+        Ok(memberships.values().filter(|membership| membership.user_id == user_id).cloned().collect())
+    }
+}
@@ -0,0 +1,94 @@
+use crate::types::{ConversionError, DatabaseError, DomainEvent, Id};
+use crate::ports::outputs::events::EventPublisher;
+use aws_sdk_dynamodb::types::AttributeValue;
+use crate::ports::outputs::outbox::Outbox;
+use std::collections::HashMap;
+use aws_sdk_dynamodb::Client;
+use tracing::instrument;
+
+
+/// An outbox in a DynamoDB table keyed by `id`.
+///
+/// Every event is stored as JSON in the `event` attribute. Pending events are found with a scan,
+/// which is fine as long as the `dispatch_events` job keeps the table close to empty.
+pub struct DynamoDBOutbox {
+    client: Client,
+    table: String,
+}
+
+
+impl DynamoDBOutbox {
+    pub fn new(client: Client, table: String) -> Self {
+        Self { client, table }
+    }
+
+    /// The item storing `event`, also put by the writes the event is added along with. eg `UsersTable::create_user_with_event`
+    pub(crate) fn item(event: &DomainEvent) -> Result<HashMap<String, AttributeValue>, DatabaseError> {
+        let json = serde_json::to_string(event).map_err(|_| ConversionError::UnexpectedDataType("event"))?;
+        Ok(HashMap::from([
+            (String::from("id"), event.id.into()),
+            (String::from("event"), AttributeValue::S(json)),
+        ]))
+    }
+}
+
+
+impl Outbox for DynamoDBOutbox {
+    type Error = DatabaseError;
+
+    #[instrument(skip_all, fields(table = %self.table, event = event.name(), event_id = %event.id.to_hex()), err)]
+    async fn add(&self, event: &DomainEvent) -> Result<(), Self::Error> {
+        self.client.put_item()
+            .table_name(&self.table)
+            .set_item(Some(Self::item(event)?))
+            .condition_expression("attribute_not_exists(id)")
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(table = %self.table), err)]
+    async fn pending(&self, limit: usize) -> Result<Vec<DomainEvent>, Self::Error> {
+        let mut events = Vec::new();
+        let mut start = None;
+        loop {
+            let output = self.client.scan()
+                .table_name(&self.table)
+                .set_exclusive_start_key(start)
+                .send()
+                .await?;
+            for mut item in output.items.unwrap_or_default() {
+                match item.remove("event") {
+                    Some(AttributeValue::S(json)) => events.push(serde_json::from_str::<DomainEvent>(&json).map_err(|_| ConversionError::UnexpectedDataType("event"))?),
+                    _ => return Err(ConversionError::UnexpectedDataType("event").into()),
+                }
+            }
+            start = output.last_evaluated_key;
+            if start.is_none() {
+                break;
+            }
+        }
+        events.sort_by_key(|event| event.occurred_at);
+        events.truncate(limit);
+        Ok(events)
+    }
+
+    #[instrument(skip_all, fields(table = %self.table, event_id = %id.to_hex()), err)]
+    async fn remove(&self, id: Id) -> Result<(), Self::Error> {
+        self.client.delete_item().table_name(&self.table).key("id", id.into()).send().await?;
+        Ok(())
+    }
+}
+
+
+impl EventPublisher for DynamoDBOutbox {
+    type Error = DatabaseError;
+
+    async fn publish(&self, event: &DomainEvent) -> Result<(), Self::Error> {
+        self.add(event).await
+    }
+
+    fn durable(&self) -> bool {
+        true
+    }
+}
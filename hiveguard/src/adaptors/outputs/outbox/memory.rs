@@ -0,0 +1,59 @@
+use std::sync::{PoisonError, RwLock};
+use crate::ports::outputs::events::EventPublisher;
+use crate::ports::outputs::outbox::Outbox;
+use crate::types::{DatabaseError, DomainEvent, Id};
+use std::collections::HashMap;
+
+
+/// An outbox kept in memory, for tests and local development. Events not yet published are lost on restart.
+#[derive(Default)]
+pub struct MemoryOutbox {
+    events: RwLock<HashMap<Id, DomainEvent>>,
+}
+
+
+impl MemoryOutbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+
+impl Outbox for MemoryOutbox {
+    type Error = DatabaseError;
+
+    async fn add(&self, event: &DomainEvent) -> Result<(), Self::Error> {
+        let mut events = self.events.write().unwrap_or_else(PoisonError::into_inner);
+        if events.contains_key(&event.id) {
+            return Err(DatabaseError::AlreadyExists);
+        }
+        events.insert(event.id, event.clone());
+        Ok(())
+    }
+
+    async fn pending(&self, limit: usize) -> Result<Vec<DomainEvent>, Self::Error> {
+        let events = self.events.read().unwrap_or_else(PoisonError::into_inner);
+        let mut pending = events.values().cloned().collect::<Vec<_>>();
+        pending.sort_by_key(|event| event.occurred_at);
+        pending.truncate(limit);
+        Ok(pending)
+    }
+
+    async fn remove(&self, id: Id) -> Result<(), Self::Error> {
+        self.events.write().unwrap_or_else(PoisonError::into_inner).remove(&id);
+        Ok(())
+    }
+}
+
+
+impl EventPublisher for MemoryOutbox {
+    type Error = DatabaseError;
+
+    async fn publish(&self, event: &DomainEvent) -> Result<(), Self::Error> {
+        self.add(event).await
+    }
+
+    fn durable(&self) -> bool {
+        true
+    }
+}
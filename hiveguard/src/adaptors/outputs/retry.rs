@@ -0,0 +1,108 @@
+use std::sync::{Arc, Mutex, PoisonError};
+use crate::config::RetryConfig;
+use std::time::Duration;
+use std::future::Future;
+use rand::random_range;
+
+
+/// Retries failed outbound calls with exponential backoff and full jitter, within a retry budget.
+///
+/// The budget caps retries to a fraction of the calls made, so a provider that is down is not hammered
+/// with `max_attempts` times its usual traffic. Clones share the same budget.
+#[derive(Clone)]
+pub struct Retry {
+    config: RetryConfig,
+    budget: Arc<Mutex<f64>>,
+}
+
+
+impl Retry {
+    /// The budget starts full, with room for `config.budget_capacity` retries.
+    pub fn new(config: &RetryConfig) -> Self {
+        Self { config: config.clone(), budget: Arc::new(Mutex::new(config.budget_capacity as f64)) }
+    }
+
+    /// Calls `operation` until it succeeds, fails with an error `retryable` rejects,
+    /// makes `max_attempts` attempts or the budget runs out.
+    pub async fn run<T, E, F: FnMut() -> Fut, Fut: Future<Output = Result<T, E>>, R: Fn(&E) -> bool>(&self, mut operation: F, retryable: R) -> Result<T, E> {
+        self.deposit();
+        let mut attempt = 1;
+        loop {
+            match operation().await {
+                Err(err) if attempt < self.config.max_attempts && retryable(&err) && self.withdraw() => {
+                    tokio::time::sleep(self.backoff(attempt)).await;
+                    attempt += 1;
+                },
+                result => break result,
+            }
+        }
+    }
+
+    /// A random delay up to `initial_backoff_ms * 2^(attempt - 1)`, capped at `max_backoff_ms`.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self.config.initial_backoff_ms.saturating_mul(1 << (attempt - 1).min(32)).min(self.config.max_backoff_ms);
+        Duration::from_millis(random_range(0..=ceiling))
+    }
+
+    /// Every call earns `budget_ratio` of a retry.
+    fn deposit(&self) {
+        let mut budget = self.budget.lock().unwrap_or_else(PoisonError::into_inner);
+        *budget = (*budget + self.config.budget_ratio).min(self.config.budget_capacity as f64);
+    }
+
+    /// Spends a retry, if there is one left.
+    fn withdraw(&self) -> bool {
+        let mut budget = self.budget.lock().unwrap_or_else(PoisonError::into_inner);
+        if *budget < 1.0 {
+            tracing::warn!("retry budget exhausted");
+            return false;
+        }
+        *budget -= 1.0;
+        true
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn retry(max_attempts: u32, budget_capacity: u32) -> Retry {
+        Retry::new(&RetryConfig { max_attempts, initial_backoff_ms: 1, max_backoff_ms: 2, budget_ratio: 0.0, budget_capacity })
+    }
+
+    async fn failing(retry: &Retry, calls: &AtomicU32, retryable: bool) -> Result<(), ()> {
+        retry.run(|| async {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Err(())
+        }, |_| retryable).await
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_max_attempts() {
+        let calls = AtomicU32::new(0);
+        assert_eq!(failing(&retry(3, 10), &calls, true).await, Err(()));
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+        calls.store(0, Ordering::Relaxed);
+        assert_eq!(failing(&retry(3, 10), &calls, false).await, Err(()));
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_budget_limits_retries() {
+        let retry = retry(3, 3);
+        let calls = AtomicU32::new(0);
+        for _ in 0..3 {
+            let _ = failing(&retry, &calls, true).await;
+        }
+        assert_eq!(calls.load(Ordering::Relaxed), 3 + 3);
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let retry = Retry::new(&RetryConfig { initial_backoff_ms: 100, max_backoff_ms: 1_000, ..Default::default() });
+        assert!(retry.backoff(1) <= Duration::from_millis(100));
+        assert!(retry.backoff(40) <= Duration::from_millis(1_000));
+    }
+}
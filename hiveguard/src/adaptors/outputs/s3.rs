@@ -0,0 +1,106 @@
+use aws_sdk_s3::{Client, config::Region, operation::head_object::HeadObjectError, presigning::PresigningConfig};
+use crate::types::{Upload, StoredObject, StorageError};
+use crate::ports::outputs::storage::Storage;
+use std::time::{Duration, SystemTime};
+use crate::config::S3Config;
+use tracing::instrument;
+
+
+/// Stores files in an S3 bucket, or a bucket of any S3 compatible store.
+pub struct S3 {
+    client: Client,
+    bucket: String,
+    /// the base URL of stored files, without a trailing slash.
+    public_url: String,
+}
+
+
+impl S3 {
+    pub fn new(client: Client, config: &S3Config) -> Self {
+        Self { client, bucket: config.bucket.clone(), public_url: public_url(config) }
+    }
+
+    /// Builds the client from the AWS environment, with the endpoint and region of `config` when they are set.
+    pub async fn from_env(config: &S3Config) -> Self {
+        let shared = aws_config::load_from_env().await;
+        let mut builder = aws_sdk_s3::config::Builder::from(&shared).force_path_style(config.force_path_style);
+        if let Some(endpoint) = &config.endpoint {
+            builder = builder.endpoint_url(endpoint);
+        }
+        if let Some(region) = &config.region {
+            builder = builder.region(Region::new(region.clone()));
+        }
+        Self::new(Client::from_conf(builder.build()), config)
+    }
+}
+
+
+impl Storage for S3 {
+    type Error = StorageError;
+
+    #[instrument(skip(self), fields(bucket = %self.bucket), err)]
+    async fn presign_upload(&self, key: &str, content_type: &str, content_length: u64, expires_in: Duration) -> Result<Upload, Self::Error> {
+        let presigning = PresigningConfig::builder().start_time(SystemTime::now()).expires_in(expires_in).build()?;
+        let request = self.client.put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(content_type)
+            .content_length(content_length as i64)
+            .presigned(presigning)
+            .await?;
+        Ok(Upload {
+            key: key.to_string(),
+            url: request.uri().to_string(),
+            headers: request.headers().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+            expires_at: chrono::Utc::now() + expires_in,
+        })
+    }
+
+    #[instrument(skip(self), fields(bucket = %self.bucket), err)]
+    async fn head(&self, key: &str) -> Result<Option<StoredObject>, Self::Error> {
+        match self.client.head_object().bucket(&self.bucket).key(key).send().await {
+            Ok(output) => Ok(Some(StoredObject {
+                size: output.content_length().unwrap_or_default().max(0) as u64,
+                content_type: output.content_type().map(String::from),
+            })),
+            Err(err) if matches!(err.as_service_error(), Some(HeadObjectError::NotFound(_))) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn public_url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_url, key)
+    }
+}
+
+
+/// `public_url` when it is set, the URL of the bucket otherwise.
+fn public_url(config: &S3Config) -> String {
+    match (&config.public_url, &config.endpoint) {
+        (Some(url), _) => url.trim_end_matches('/').to_string(),
+        (None, Some(endpoint)) if config.force_path_style => format!("{}/{}", endpoint.trim_end_matches('/'), config.bucket),
+        (None, Some(endpoint)) => match endpoint.split_once("://") {
+            Some((scheme, host)) => format!("{}://{}.{}", scheme, config.bucket, host.trim_end_matches('/')),
+            None => format!("{}.{}", config.bucket, endpoint),
+        },
+        (None, None) => format!("https://{}.s3.amazonaws.com", config.bucket),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_url() {
+        let mut config = S3Config { bucket: "avatars".into(), ..Default::default() };
+        assert_eq!(public_url(&config), "https://avatars.s3.amazonaws.com");
+        config.endpoint = Some("http://localhost:9000/".into());
+        assert_eq!(public_url(&config), "http://avatars.localhost:9000");
+        config.force_path_style = true;
+        assert_eq!(public_url(&config), "http://localhost:9000/avatars");
+        config.public_url = Some("https://cdn.example.com/".into());
+        assert_eq!(public_url(&config), "https://cdn.example.com");
+    }
+}
@@ -0,0 +1,46 @@
+use aws_sdk_secretsmanager::{Client, operation::get_secret_value::GetSecretValueError};
+use crate::types::SecretError;
+use tracing::instrument;
+use serde_json::Value;
+
+
+/// Reads secrets from AWS Secrets Manager.
+///
+/// Without a key the whole secret string is returned. With a key the secret string is read as a JSON object.
+pub struct SecretsManager {
+    client: Client,
+}
+
+
+impl SecretsManager {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    pub async fn from_env() -> Self {
+        let config = aws_config::load_from_env().await;
+        Self::new(Client::new(&config))
+    }
+
+    #[instrument(skip(self), err)]
+    pub async fn secret(&self, id: &str, key: Option<&str>) -> Result<String, SecretError> {
+        let reference = format!("aws-sm:{}", id);
+        let output = match self.client.get_secret_value().secret_id(id).send().await {
+            Ok(output) => output,
+            Err(err) => match err.as_service_error() {
+                Some(GetSecretValueError::ResourceNotFoundException(_)) => return Err(SecretError::NotFound(reference)),
+                _ => return Err(err.into()),
+            },
+        };
+        let secret = output.secret_string.ok_or_else(|| SecretError::NotFound(reference.clone()))?;
+        let Some(key) = key else {
+            return Ok(secret);
+        };
+        let secret: Value = serde_json::from_str(&secret).map_err(|_| SecretError::MissingKey(reference.clone()))?;
+        match secret.get(key) {
+            Some(Value::String(secret)) => Ok(secret.clone()),
+            Some(value) => Ok(value.to_string()),
+            None => Err(SecretError::MissingKey(reference)),
+        }
+    }
+}
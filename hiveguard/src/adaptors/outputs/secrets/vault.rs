@@ -0,0 +1,47 @@
+use crate::types::SecretError;
+use reqwest::{Client, StatusCode};
+use tracing::instrument;
+use serde_json::Value;
+
+
+/// Reads secrets from a HashiCorp Vault KV version 2 secrets engine.
+///
+/// A reference path starts with the mount of the engine. eg `secret/hiveguard` reads `hiveguard` from the `secret` mount.
+pub struct Vault {
+    client: Client,
+    address: String,
+    token: String,
+}
+
+
+impl Vault {
+    pub fn new(address: String, token: String) -> Self {
+        let address = address.trim_end_matches('/').to_string();
+        Self { client: Client::new(), address, token }
+    }
+
+    /// Reads the address and token from `VAULT_ADDR` and `VAULT_TOKEN`.
+    pub fn from_env() -> Option<Self> {
+        let address = std::env::var("VAULT_ADDR").ok()?;
+        let token = std::env::var("VAULT_TOKEN").ok()?;
+        Some(Self::new(address, token))
+    }
+
+    #[instrument(skip(self), err)]
+    pub async fn secret(&self, path: &str, key: Option<&str>) -> Result<String, SecretError> {
+        let reference = format!("vault:{}", path);
+        let (mount, name) = path.split_once('/').ok_or_else(|| SecretError::NotFound(reference.clone()))?;
+        let key = key.ok_or_else(|| SecretError::MissingKey(reference.clone()))?;
+        let url = format!("{}/v1/{}/data/{}", self.address, mount, name);
+        let response = self.client.get(url).header("X-Vault-Token", &self.token).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(SecretError::NotFound(reference));
+        }
+        let body: Value = response.error_for_status()?.json().await?;
+        match body["data"]["data"].get(key) {
+            Some(Value::String(secret)) => Ok(secret.clone()),
+            Some(value) => Ok(value.to_string()),
+            None => Err(SecretError::MissingKey(reference)),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use crate::ports::outputs::security_events::SecurityEvents;
+use crate::types::SecurityEvent;
+use super::SecurityEventSink;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+
+/// Emits the security events of requests on tasks of their own, so that a failed login does not wait on the sink.
+/// eg a webhook retrying a collector that is down.
+///
+/// The request has moved on by the time an event fails, so failures are only logged.
+/// Jobs and admin commands, which should not end before their events are delivered, use the sink itself.
+#[derive(Clone)]
+pub struct Background(Arc<SecurityEventSink>);
+
+
+impl Background {
+    pub fn new(sink: SecurityEventSink) -> Self {
+        Self(Arc::new(sink))
+    }
+}
+
+
+impl SecurityEvents for Background {
+    type Error = Infallible;
+
+    async fn emit(&self, event: SecurityEvent) -> Result<(), Self::Error> {
+        let sink = Arc::clone(&self.0);
+        tokio::spawn(async move {
+            let name = event.name();
+            if let Err(err) = sink.emit(event).await {
+                tracing::error!(error = %err, event = name, "could not emit the security event");
+            }
+        });
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SecurityEventKind;
+    use crate::config::SecurityEventsConfig;
+    use crate::adaptors::outputs::retry::Retry;
+
+    #[tokio::test]
+    async fn test_events_are_emitted_in_the_background() {
+        let sink = SecurityEventSink::new(&SecurityEventsConfig::Bus { capacity: 8 }, reqwest::Client::new(), Retry::new(&Default::default()));
+        let mut receiver = sink.subscribe().unwrap();
+        let event = SecurityEvent::new(SecurityEventKind::MfaDisabled, None);
+        Background::new(sink).emit(event.clone()).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), event);
+    }
+}
@@ -0,0 +1,49 @@
+use crate::ports::outputs::security_events::SecurityEvents;
+use tokio::sync::broadcast::{self, Receiver, Sender};
+use crate::types::SecurityEvent;
+use std::convert::Infallible;
+
+
+/// Publishes security events on an in-process broadcast channel so that other components can forward them to a message bus.
+///
+/// Subscribers that fall more than `capacity` events behind miss the oldest ones.
+#[derive(Clone)]
+pub struct Bus(Sender<SecurityEvent>);
+
+
+impl Bus {
+    pub fn new(capacity: usize) -> Self {
+        Self(broadcast::channel(capacity).0)
+    }
+
+    pub fn subscribe(&self) -> Receiver<SecurityEvent> {
+        self.0.subscribe()
+    }
+}
+
+
+impl SecurityEvents for Bus {
+    type Error = Infallible;
+
+    /// Events published while nobody is subscribed are dropped.
+    async fn emit(&self, event: SecurityEvent) -> Result<(), Self::Error> {
+        let _ = self.0.send(event);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SecurityEventKind;
+
+    #[tokio::test]
+    async fn test_subscribers_receive_events() {
+        let bus = Bus::new(8);
+        let mut receiver = bus.subscribe();
+        let event = SecurityEvent::new(SecurityEventKind::MfaDisabled, None);
+        bus.emit(event.clone()).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), event);
+    }
+}
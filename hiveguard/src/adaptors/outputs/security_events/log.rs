@@ -0,0 +1,18 @@
+use crate::ports::outputs::security_events::SecurityEvents;
+use crate::types::SecurityEvent;
+use std::convert::Infallible;
+
+
+/// Writes security events to the `security` log target so they travel with the rest of the logs.
+pub struct Log;
+
+
+impl SecurityEvents for Log {
+    type Error = Infallible;
+
+    async fn emit(&self, event: SecurityEvent) -> Result<(), Self::Error> {
+        let payload = serde_json::to_string(&event).unwrap_or_default();
+        tracing::warn!(target: "security", event = event.name(), payload = %payload, "security event");
+        Ok(())
+    }
+}
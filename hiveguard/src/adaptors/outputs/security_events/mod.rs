@@ -0,0 +1,63 @@
+use crate::ports::outputs::security_events::SecurityEvents;
+use crate::config::SecurityEventsConfig;
+use tokio::sync::broadcast::Receiver;
+use crate::types::SecurityEvent;
+use super::retry::Retry;
+use reqwest::Client;
+
+mod webhook;
+mod bus;
+mod log;
+mod background;
+
+pub use webhook::Webhook;
+pub use bus::Bus;
+pub use log::Log;
+pub use background::Background;
+
+
+/// The security event destination selected in the configuration.
+pub enum SecurityEventSink {
+    Log(Log),
+    Webhook(Webhook),
+    Bus(Bus),
+}
+
+
+impl SecurityEventSink {
+    /// Webhooks are called with `client`, the shared HTTP client, and retried with `retry`.
+    pub fn new(config: &SecurityEventsConfig, client: Client, retry: Retry) -> Self {
+        match config {
+            SecurityEventsConfig::Log => Self::Log(Log),
+            SecurityEventsConfig::Webhook { url, headers } => Self::Webhook(Webhook::new(client, retry, url.clone(), headers.clone())),
+            SecurityEventsConfig::Bus { capacity } => Self::Bus(Bus::new(*capacity)),
+        }
+    }
+
+    /// A receiver of the events emitted from now on, when the sink is a bus.
+    pub fn subscribe(&self) -> Option<Receiver<SecurityEvent>> {
+        match self {
+            Self::Bus(bus) => Some(bus.subscribe()),
+            Self::Log(_) | Self::Webhook(_) => None,
+        }
+    }
+}
+
+
+impl SecurityEvents for SecurityEventSink {
+    type Error = reqwest::Error;
+
+    async fn emit(&self, event: SecurityEvent) -> Result<(), Self::Error> {
+        match self {
+            Self::Log(log) => {
+                let Ok(()) = log.emit(event).await;
+                Ok(())
+            },
+            Self::Webhook(webhook) => webhook.emit(event).await,
+            Self::Bus(bus) => {
+                let Ok(()) = bus.emit(event).await;
+                Ok(())
+            },
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use crate::adaptors::outputs::{http::is_transient, retry::Retry};
+use crate::ports::outputs::security_events::SecurityEvents;
+use crate::types::SecurityEvent;
+use std::collections::HashMap;
+use tracing::instrument;
+use reqwest::Client;
+
+
+/// POSTs every security event as JSON to a SIEM or any other HTTP collector, retrying transient failures.
+pub struct Webhook {
+    client: Client,
+    retry: Retry,
+    url: String,
+    /// sent with every request, typically the collector's credentials.
+    headers: HashMap<String, String>,
+}
+
+
+impl Webhook {
+    pub fn new(client: Client, retry: Retry, url: String, headers: HashMap<String, String>) -> Self {
+        Self { client, retry, url, headers }
+    }
+}
+
+
+impl SecurityEvents for Webhook {
+    type Error = reqwest::Error;
+
+    #[instrument(skip_all, fields(event = event.name()), err)]
+    async fn emit(&self, event: SecurityEvent) -> Result<(), Self::Error> {
+        let event = &event;
+        self.retry.run(|| async move {
+            let mut request = self.client.post(&self.url).json(event);
+            for (name, value) in &self.headers {
+                request = request.header(name, value);
+            }
+            request.send().await?.error_for_status()?;
+            Ok(())
+        }, is_transient).await
+    }
+}
@@ -0,0 +1,34 @@
+use crate::ports::outputs::signer::Signer;
+use ed25519_dalek::{Signer as _, SigningKey};
+use std::convert::Infallible;
+
+
+/// Signs with a key pair held in memory, that of `tokens.signing_key`.
+pub struct KeySigner {
+    key: SigningKey,
+}
+
+
+impl KeySigner {
+    /// The signer of a hex encoded 64 byte key pair, if its public key is that of its secret key.
+    pub fn new(key_pair: &str) -> Option<Self> {
+        let bytes = (0..key_pair.len()).step_by(2)
+            .map(|i| key_pair.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+            .collect::<Option<Vec<u8>>>()?;
+        let key = SigningKey::from_keypair_bytes(&bytes.try_into().ok()?).ok()?;
+        Some(Self { key })
+    }
+}
+
+
+impl Signer for KeySigner {
+    type Error = Infallible;
+
+    async fn public_key(&self) -> Result<[u8; 32], Self::Error> {
+        Ok(self.key.verifying_key().to_bytes())
+    }
+
+    async fn sign(&self, message: &[u8]) -> Result<[u8; 64], Self::Error> {
+        Ok(self.key.sign(message).to_bytes())
+    }
+}
@@ -0,0 +1,67 @@
+use aws_sdk_kms::types::{MessageType, SigningAlgorithmSpec};
+use crate::ports::outputs::signer::Signer;
+use aws_sdk_kms::primitives::Blob;
+use aws_sdk_kms::config::Region;
+use aws_sdk_kms::Client;
+use tracing::instrument;
+use super::SignerError;
+
+
+/// the DER prefix of the SubjectPublicKeyInfo of Ed25519 keys, followed by the 32 byte key.
+const ED25519_SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+
+/// Signs with an `ECC_NIST_EDWARDS25519` key of AWS KMS. The key never leaves KMS, every signature is a request.
+pub struct KmsSigner {
+    client: Client,
+    key_id: String,
+}
+
+
+impl KmsSigner {
+    pub fn new(client: Client, key_id: String) -> Self {
+        Self { client, key_id }
+    }
+
+    /// Builds the client from the default credential chain, in `region` and at `endpoint` when set.
+    pub async fn from_env(key_id: String, region: Option<&String>, endpoint: Option<&String>) -> Self {
+        let shared = aws_config::load_from_env().await;
+        let mut builder = aws_sdk_kms::config::Builder::from(&shared);
+        if let Some(endpoint) = endpoint {
+            builder = builder.endpoint_url(endpoint);
+        }
+        if let Some(region) = region {
+            builder = builder.region(Region::new(region.clone()));
+        }
+        Self::new(Client::from_conf(builder.build()), key_id)
+    }
+}
+
+
+impl Signer for KmsSigner {
+    type Error = SignerError;
+
+    #[instrument(skip(self), fields(key_id = self.key_id), err)]
+    async fn public_key(&self) -> Result<[u8; 32], Self::Error> {
+        let output = self.client.get_public_key().key_id(&self.key_id).send().await?;
+        let der = output.public_key.map(Blob::into_inner).unwrap_or_default();
+        match der.strip_prefix(&ED25519_SPKI_PREFIX).map(<[u8; 32]>::try_from) {
+            Some(Ok(key)) => Ok(key),
+            _ => Err(format!("the KMS key {} is not an Ed25519 key", self.key_id).into()),
+        }
+    }
+
+    #[instrument(skip_all, fields(key_id = self.key_id), err)]
+    async fn sign(&self, message: &[u8]) -> Result<[u8; 64], Self::Error> {
+        let output = self.client.sign()
+            .key_id(&self.key_id)
+            .message(Blob::new(message))
+            // pure Ed25519, KMS hashing the message as the algorithm does.
+            .message_type(MessageType::Raw)
+            .signing_algorithm(SigningAlgorithmSpec::Ed25519Sha512)
+            .send()
+            .await?;
+        let signature = output.signature.map(Blob::into_inner).unwrap_or_default();
+        Ok(signature.try_into().map_err(|_| "KMS returned a signature of another length")?)
+    }
+}
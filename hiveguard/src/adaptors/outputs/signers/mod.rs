@@ -0,0 +1,69 @@
+use crate::config::{SignerConfig, TokensConfig};
+use crate::ports::outputs::signer::Signer;
+
+#[cfg(feature = "kms")]
+mod kms;
+#[cfg(feature = "pkcs11")]
+mod pkcs11;
+mod key;
+
+#[cfg(feature = "kms")]
+pub use kms::KmsSigner;
+#[cfg(feature = "pkcs11")]
+pub use pkcs11::Pkcs11Signer;
+pub use key::KeySigner;
+
+
+pub type SignerError = Box<dyn std::error::Error + Send + Sync>;
+
+
+/// The signer selected by `tokens.signer`.
+pub enum SignerBackend {
+    Key(KeySigner),
+    #[cfg(feature = "kms")]
+    Kms(KmsSigner),
+    #[cfg(feature = "pkcs11")]
+    Pkcs11(Pkcs11Signer),
+}
+
+
+impl SignerBackend {
+    /// Fails when the key cannot be found, so that a misconfigured signer stops hiveguard from starting.
+    pub async fn new(config: &TokensConfig) -> Result<Self, SignerError> {
+        let signer = match &config.signer {
+            SignerConfig::Key => Self::Key(KeySigner::new(&config.signing_key).ok_or("tokens.signing_key is not a hex encoded 64 byte key pair")?),
+            #[cfg(feature = "kms")]
+            SignerConfig::Kms { key_id, region, endpoint } => Self::Kms(KmsSigner::from_env(key_id.clone(), region.as_ref(), endpoint.as_ref()).await),
+            #[cfg(feature = "pkcs11")]
+            SignerConfig::Pkcs11 { module, token, key, pin } => Self::Pkcs11(Pkcs11Signer::open(module, token, key, pin)?),
+        };
+        // checks the key exists and is an Ed25519 key.
+        signer.public_key().await?;
+        Ok(signer)
+    }
+}
+
+
+impl Signer for SignerBackend {
+    type Error = SignerError;
+
+    async fn public_key(&self) -> Result<[u8; 32], Self::Error> {
+        match self {
+            Self::Key(key) => Ok(key.public_key().await?),
+            #[cfg(feature = "kms")]
+            Self::Kms(kms) => Ok(kms.public_key().await?),
+            #[cfg(feature = "pkcs11")]
+            Self::Pkcs11(hsm) => Ok(hsm.public_key().await?),
+        }
+    }
+
+    async fn sign(&self, message: &[u8]) -> Result<[u8; 64], Self::Error> {
+        match self {
+            Self::Key(key) => Ok(key.sign(message).await?),
+            #[cfg(feature = "kms")]
+            Self::Kms(kms) => Ok(kms.sign(message).await?),
+            #[cfg(feature = "pkcs11")]
+            Self::Pkcs11(hsm) => Ok(hsm.sign(message).await?),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use cryptoki::mechanism::{Mechanism, eddsa::{EddsaParams, EddsaSignatureScheme}};
+use cryptoki::object::{Attribute, AttributeType, ObjectClass, ObjectHandle};
+use cryptoki::context::{CInitializeArgs, Pkcs11};
+use cryptoki::session::{Session, UserType};
+use crate::ports::outputs::signer::Signer;
+use std::sync::{Arc, Mutex, PoisonError};
+use cryptoki::types::AuthPin;
+use super::SignerError;
+
+
+/// Signs with an Ed25519 key of an HSM, through the PKCS#11 module of its vendor. The key never leaves the HSM.
+///
+/// A single session is opened and logged in, and signatures are made one at a time on a blocking thread.
+pub struct Pkcs11Signer {
+    session: Arc<Mutex<Session>>,
+    key: ObjectHandle,
+    public_key: [u8; 32],
+}
+
+
+impl Pkcs11Signer {
+    /// Loads `module`, logs in to the token labelled `token` with `pin`, and finds the key pair labelled `key`.
+    pub fn open(module: &str, token: &str, key: &str, pin: &str) -> Result<Self, SignerError> {
+        let pkcs11 = Pkcs11::new(module)?;
+        pkcs11.initialize(CInitializeArgs::OsThreads)?;
+        let mut slot = None;
+        for candidate in pkcs11.get_slots_with_token()? {
+            if pkcs11.get_token_info(candidate)?.label() == token {
+                slot = Some(candidate);
+                break;
+            }
+        }
+        let slot = slot.ok_or_else(|| format!("no PKCS#11 token is labelled {}", token))?;
+        let session = pkcs11.open_ro_session(slot)?;
+        session.login(UserType::User, Some(&AuthPin::new(pin.to_string())))?;
+        let find = |class: ObjectClass| -> Result<ObjectHandle, SignerError> {
+            let objects = session.find_objects(&[Attribute::Class(class), Attribute::Label(key.as_bytes().to_vec())])?;
+            objects.first().copied().ok_or_else(|| format!("the PKCS#11 token {} has no {} labelled {}", token, class, key).into())
+        };
+        let private = find(ObjectClass::PRIVATE_KEY)?;
+        let public = find(ObjectClass::PUBLIC_KEY)?;
+        let public_key = match session.get_attributes(public, &[AttributeType::EcPoint])?.pop() {
+            Some(Attribute::EcPoint(point)) => Self::point(&point),
+            _ => None,
+        };
+        let public_key = public_key.ok_or_else(|| format!("the PKCS#11 key {} is not an Ed25519 key", key))?;
+        Ok(Self { session: Arc::new(Mutex::new(session)), key: private, public_key })
+    }
+
+    /// The key of an Ed25519 `CKA_EC_POINT`, DER encoded as an octet string or, by some modules, raw.
+    fn point(point: &[u8]) -> Option<[u8; 32]> {
+        let key = match point {
+            [0x04, 0x20, key @ ..] if key.len() == 32 => key,
+            key => key,
+        };
+        key.try_into().ok()
+    }
+}
+
+
+impl Signer for Pkcs11Signer {
+    type Error = SignerError;
+
+    async fn public_key(&self) -> Result<[u8; 32], Self::Error> {
+        Ok(self.public_key)
+    }
+
+    async fn sign(&self, message: &[u8]) -> Result<[u8; 64], Self::Error> {
+        let (session, key, message) = (self.session.clone(), self.key, message.to_vec());
+        let signature = tokio::task::spawn_blocking(move || {
+            let session = session.lock().unwrap_or_else(PoisonError::into_inner);
+            session.sign(&Mechanism::Eddsa(EddsaParams::new(EddsaSignatureScheme::Pure)), key, &message)
+        }).await??;
+        Ok(signature.try_into().map_err(|_| "the HSM returned a signature of another length")?)
+    }
+}
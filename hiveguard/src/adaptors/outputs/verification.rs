@@ -0,0 +1,138 @@
+use crate::ports::outputs::database::{Database, tables::VerificationsTable};
+use super::circuit_breaker::CircuitBreaker;
+use crate::ports::outputs::verify::Verify;
+use crate::types::ProviderUnavailable;
+
+
+/// Sends verification codes through `primary` behind a circuit breaker, falling back to `secondary` when there is one.
+///
+/// While the circuit of `primary` is open codes go straight to `secondary`, or fail fast with [`ProviderUnavailable`].
+/// A code `primary` fails to send is also sent through `secondary`.
+/// Codes are checked by `primary` whichever provider sent them, so both have to share the verifications table.
+pub struct Guarded<P, S = P> {
+    primary: P,
+    breaker: CircuitBreaker,
+    secondary: Option<S>,
+}
+
+
+impl<P, S> Guarded<P, S> {
+    pub fn new(primary: P, breaker: CircuitBreaker, secondary: Option<S>) -> Self {
+        Self { primary, breaker, secondary }
+    }
+}
+
+
+impl<Contact, const SIZE: usize, P, S> Verify<Contact, SIZE> for Guarded<P, S>
+where
+    Contact: Clone,
+    P: Verify<Contact, SIZE, Error: From<ProviderUnavailable>, Channel: Clone>,
+    S: Verify<Contact, SIZE, VerificationCode = P::VerificationCode, Error = P::Error, Channel = P::Channel>,
+{
+    type VerificationCode = P::VerificationCode;
+    type Error = P::Error;
+    type Channel = P::Channel;
+
+    async fn initiate<DB: Database<VerificationsTable: VerificationsTable<DB::Client, Item = Self::VerificationCode>>>(&self, contact: &Contact, channel: Self::Channel, magic_link_base_uri: Option<&str>, db: &DB) -> Result<Self::VerificationCode, Self::Error> {
+        if self.breaker.allow() {
+            let result = self.primary.initiate(contact, channel.clone(), magic_link_base_uri, db).await;
+            self.breaker.record(result.is_ok());
+            if result.is_ok() || self.secondary.is_none() {
+                return result;
+            }
+            tracing::warn!(provider = self.breaker.name(), "falling back to the secondary verification provider");
+        }
+        match &self.secondary {
+            Some(secondary) => secondary.initiate(contact, channel, magic_link_base_uri, db).await,
+            None => Err(ProviderUnavailable(self.breaker.name()).into()),
+        }
+    }
+
+    async fn verify<DB: Database<VerificationsTable: VerificationsTable<DB::Client, Item = Self::VerificationCode>>>(&self, contact: &Contact, code_or_id: &str, db: &DB) -> Result<(), Self::Error> {
+        self.primary.verify(contact, code_or_id, db).await
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DatabaseError, Error, GrantRecord, Session, User};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use crate::ports::outputs::database::MockDatabase;
+    use crate::ports::outputs::verify::Code;
+    use crate::config::CircuitBreakerConfig;
+
+    type Mock = MockDatabase<User, Session, Sent, GrantRecord, DatabaseError>;
+
+    #[derive(Debug)]
+    struct Sent([u8; 6]);
+
+    impl Code<String> for Sent {
+        type Error = Error;
+
+        fn new(_: String, _: Option<i64>) -> Self {
+            Self(Self::generate())
+        }
+
+        fn code(&self) -> &[u8; 6] {
+            &self.0
+        }
+
+        fn magic_link(base_uri: &str) -> String {
+            base_uri.to_string()
+        }
+    }
+
+    struct Provider {
+        fails: bool,
+        calls: AtomicU32,
+    }
+
+    impl Provider {
+        fn new(fails: bool) -> Self {
+            Self { fails, calls: AtomicU32::new(0) }
+        }
+    }
+
+    impl Verify<String> for Provider {
+        type VerificationCode = Sent;
+        type Error = Error;
+        type Channel = ();
+
+        async fn initiate<DB: Database<VerificationsTable: VerificationsTable<DB::Client, Item = Self::VerificationCode>>>(&self, contact: &String, _: (), _: Option<&str>, _: &DB) -> Result<Sent, Error> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            if self.fails { Err(Error::InvalidCredentials) } else { Ok(Sent::new(contact.clone(), None)) }
+        }
+
+        async fn verify<DB: Database<VerificationsTable: VerificationsTable<DB::Client, Item = Self::VerificationCode>>>(&self, _: &String, _: &str, _: &DB) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new("sms", &CircuitBreakerConfig { min_calls: 2, ..Default::default() })
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_and_stops_calling_a_failing_provider() {
+        let guarded = Guarded::new(Provider::new(true), breaker(), Some(Provider::new(false)));
+        let contact = String::from("+254700000000");
+        for _ in 0..3 {
+            assert!(guarded.initiate(&contact, (), None, &Mock::default()).await.is_ok());
+        }
+        assert_eq!(guarded.primary.calls.load(Ordering::Relaxed), 2);
+        assert_eq!(guarded.secondary.as_ref().unwrap().calls.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fails_fast_without_a_secondary() {
+        let guarded: Guarded<Provider> = Guarded::new(Provider::new(true), breaker(), None);
+        let contact = String::from("+254700000000");
+        for _ in 0..2 {
+            assert_eq!(guarded.initiate(&contact, (), None, &Mock::default()).await.err(), Some(Error::InvalidCredentials));
+        }
+        assert_eq!(guarded.initiate(&contact, (), None, &Mock::default()).await.err(), Some(Error::ProviderUnavailable("sms")));
+        assert_eq!(guarded.primary.calls.load(Ordering::Relaxed), 2);
+    }
+}
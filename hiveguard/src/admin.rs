@@ -0,0 +1,253 @@
+use hiveguard::ports::outputs::database::{Database, tables::{UsersTable, SessionsTable}};
+use hiveguard::adaptors::outputs::security_events::SecurityEventSink;
+use hiveguard::adaptors::outputs::{http, retry::Retry};
+use hiveguard::domain::{Admin, Accounts, Export, Keyring};
+use hiveguard::ports::outputs::keys::KeyStore;
+#[cfg(feature = "argon2")]
+use hiveguard::domain::Seed;
+use tokio::io::{AsyncWrite, BufReader};
+use hiveguard::types::{Error, Id, User, Session, TenantId};
+use crate::cli::{AdminCommand, KeysCommand};
+use hiveguard::config::Config;
+use rand::RngCore;
+use tokio::fs::File;
+
+
+/// how many users are read at a time by `dump`.
+const PAGE_SIZE: usize = 100;
+
+
+/// Runs an `admin` command against the database of `config`, or of `tenant` when the deployment has tenants.
+pub async fn run(command: AdminCommand, config: &Config, tenant: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "dynamodb")]
+    {
+        use hiveguard::adaptors::outputs::{databases::dynamodb::DynamoDB, keys::dynamodb::DynamoDBKeys};
+        let db = dynamodb(config, tenant).await?;
+        // the signing keys are shared by every tenant.
+        let keys = DynamoDBKeys::new(DynamoDB::client(&config.dynamodb).await, config.dynamodb.keys_table.clone());
+        execute(&db, &keys, command, config).await
+    }
+    #[cfg(not(feature = "dynamodb"))]
+    {
+        let _ = (command, config, tenant);
+        Err("the admin commands need a database, build hiveguard with the dynamodb feature".into())
+    }
+}
+
+
+/// Connects to the DynamoDB tables of `config`, or of `tenant` when the deployment has tenants,
+/// creating the missing tables first when `dynamodb.create_tables` is set.
+#[cfg(feature = "dynamodb")]
+async fn dynamodb(config: &Config, tenant: Option<&str>) -> Result<hiveguard::adaptors::outputs::databases::dynamodb::DynamoDB, Box<dyn std::error::Error>> {
+    use hiveguard::adaptors::outputs::databases::dynamodb::DynamoDB;
+    use std::time::Duration;
+    let tables = match (tenant, config.tenancy.tenants.iter().any(|known| Some(known.id.as_str()) == tenant)) {
+        (None, _) if config.tenancy.tenants.is_empty() => config.dynamodb.clone(),
+        (None, _) => return Err("the deployment has tenants, pick one with --tenant".into()),
+        (Some(tenant), true) => config.dynamodb.for_tenant(&TenantId::try_from(tenant)?),
+        (Some(tenant), false) => return Err(format!("`{}` is not one of tenancy.tenants", tenant).into()),
+    };
+    if config.dynamodb.create_tables {
+        create_tables(config).await?;
+    }
+    let client = DynamoDB::client(&config.dynamodb).await;
+    let db = DynamoDB::new(client, &tables, Duration::from_millis(config.database.slow_query_threshold_ms));
+    Ok(match DynamoDB::replica(&config.dynamodb).await {
+        Some(replica) => db.with_replica(replica),
+        None => db,
+    })
+}
+
+
+/// Creates the DynamoDB tables of `config` that don't exist yet, those of every tenant when the deployment has tenants.
+#[cfg(feature = "dynamodb")]
+pub async fn create_tables(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    use hiveguard::adaptors::outputs::databases::dynamodb::{DynamoDB, schema};
+    let client = DynamoDB::client(&config.dynamodb).await;
+    for table in schema::create_tables(&client, &schemas(config)?).await? {
+        println!("created the table {}", table);
+    }
+    Ok(())
+}
+
+
+/// The tables of `config`, those of every tenant when the deployment has tenants.
+#[cfg(feature = "dynamodb")]
+fn schemas(config: &Config) -> Result<Vec<hiveguard::adaptors::outputs::databases::dynamodb::schema::TableSchema>, Box<dyn std::error::Error>> {
+    use hiveguard::adaptors::outputs::databases::dynamodb::schema::TableSchema;
+    if config.tenancy.tenants.is_empty() {
+        return Ok(TableSchema::all(&config.dynamodb));
+    }
+    let mut schemas = Vec::<TableSchema>::new();
+    for tenant in &config.tenancy.tenants {
+        for schema in TableSchema::all(&config.dynamodb.for_tenant(&TenantId::try_from(tenant.id.as_str())?)) {
+            // tables shared by the tenants, eg the signing keys, are listed once.
+            if !schemas.iter().any(|known| known.name == schema.name) {
+                schemas.push(schema);
+            }
+        }
+    }
+    Ok(schemas)
+}
+
+
+/// Checks the tables of `config` against the keys, indexes and time to live the adaptors expect, failing with every difference.
+pub async fn check_schema(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "dynamodb")]
+    {
+        use hiveguard::adaptors::outputs::databases::dynamodb::{DynamoDB, schema};
+        let client = DynamoDB::client(&config.dynamodb).await;
+        let issues = schema::check_schema(&client, &schemas(config)?).await?;
+        if !issues.is_empty() {
+            let issues = issues.iter().map(|issue| format!("\n  {}", issue)).collect::<String>();
+            return Err(format!("the database schema does not match:{}", issues).into());
+        }
+        Ok(())
+    }
+    #[cfg(not(feature = "dynamodb"))]
+    {
+        let _ = config;
+        Err("checking the schema needs a database, build hiveguard with the dynamodb feature".into())
+    }
+}
+
+
+async fn execute<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, SessionsTable: SessionsTable<DB::Client, Item = Session>>, S: KeyStore>(db: &DB, store: &S, command: AdminCommand, config: &Config) -> Result<(), Box<dyn std::error::Error>>
+where
+    Error: From<DB::Error> + From<S::Error>
+{
+    let events = SecurityEventSink::new(&config.security_events, http::client(&config.http)?, Retry::new(&config.retry));
+    match command {
+        #[cfg(feature = "argon2")]
+        AdminCommand::CreateAdmin { username, fullname, #[cfg(feature = "email")] email, #[cfg(feature = "phone")] phone, password } => {
+            let now = chrono::Utc::now();
+            let user = User {
+                id: Id::default(),
+                username,
+                fullname,
+                #[cfg(feature = "email")]
+                email: Some(hiveguard::types::Email::try_from(email.as_str())?),
+                #[cfg(feature = "phone")]
+                phone: Some(hiveguard::types::Phone::try_from(phone)?),
+                login: hiveguard::types::Login::Password(String::new()),
+                profile: None,
+                avatar: None,
+                status: hiveguard::types::Status::Active,
+                status_reason: None,
+                previous_username: None,
+                username_changed_at: None,
+                metadata: Default::default(),
+                consents: Default::default(),
+                created_at: now,
+                updated_at: now,
+            };
+            let admin = Admin::create(db, &passwords(config), user, password).await?;
+            println!("created the admin {}", admin.id);
+        },
+        #[cfg(feature = "argon2")]
+        AdminCommand::ResetPassword { user, password } => {
+            let revoked = Admin::reset_password(db, &passwords(config), &events, Id::try_from(user)?, password).await?;
+            println!("the password is reset, {} sessions were revoked", revoked);
+        },
+        AdminCommand::Unlock { user, reason } => {
+            Accounts::reactivate(db, &events, Id::try_from(user)?, reason, None).await?;
+            println!("the account is active");
+        },
+        AdminCommand::Merge { primary, duplicate } => {
+            let moved = Accounts::merge(db, &events, Id::try_from(primary)?, Id::try_from(duplicate)?, None).await?;
+            println!("the accounts are merged, {} sessions were moved", moved);
+        },
+        AdminCommand::GenerateKey => println!("{}", generate_key()),
+        AdminCommand::Keys(command) => keys(store, command, config).await?,
+        AdminCommand::Dump { output } => {
+            let written = match output {
+                Some(path) => dump(db, &mut File::create(path).await?).await?,
+                None => dump(db, &mut tokio::io::stdout()).await?,
+            };
+            eprintln!("{} users written", written);
+        },
+        AdminCommand::Load { input } => {
+            let created = Admin::load(db, BufReader::new(File::open(input).await?)).await?;
+            println!("{} users created", created);
+        },
+        #[cfg(feature = "argon2")]
+        AdminCommand::Seed { file } => {
+            let created = Seed::read(&file).await?.load(db, &passwords(config)).await?;
+            println!("{} users created", created);
+        },
+    }
+    Ok(())
+}
+
+
+async fn dump<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>, W: AsyncWrite + Unpin>(db: &DB, writer: &mut W) -> Result<usize, Error>
+where
+    Error: From<DB::Error>
+{
+    Export::fixtures(db, PAGE_SIZE, writer).await
+}
+
+
+async fn keys<S: KeyStore>(store: &S, command: KeysCommand, config: &Config) -> Result<(), Box<dyn std::error::Error>>
+where
+    Error: From<S::Error>
+{
+    let keyring = Keyring::from(config);
+    let now = chrono::Utc::now();
+    match command {
+        KeysCommand::List => {
+            for (key, status) in keyring.list(store, now).await? {
+                let status = serde_json::to_value(status).ok().and_then(|status| status.as_str().map(String::from)).unwrap_or_default();
+                println!("{}\t{:?}\t{}\t{}\t{}", key.kid, key.algorithm, key.created_at.to_rfc3339(), key.activates_at.to_rfc3339(), status);
+            }
+        },
+        KeysCommand::Generate { activate_at } => {
+            let key = keyring.generate(store, activate_at).await?;
+            println!("generated the key {}, signing from {}", key.kid, key.activates_at.to_rfc3339());
+        },
+        KeysCommand::Activate { kid, at } => {
+            let key = keyring.activate(store, &kid, at.unwrap_or(now)).await?;
+            println!("the key {} signs from {}", key.kid, key.activates_at.to_rfc3339());
+        },
+        KeysCommand::Retire { kid } => {
+            let key = keyring.retire(store, &kid).await?;
+            println!("the key {} is retired", key.kid);
+        },
+        KeysCommand::Signer => {
+            use hiveguard::adaptors::outputs::signers::SignerBackend;
+            use hiveguard::ports::outputs::signer::Signer;
+            let signer = SignerBackend::new(&config.tokens).await.map_err(|err| err.to_string())?;
+            let public_key = signer.public_key().await.map_err(|err| err.to_string())?;
+            println!("{}", public_key.iter().map(|byte| format!("{:02x}", byte)).collect::<String>());
+        },
+    }
+    Ok(())
+}
+
+
+#[cfg(feature = "argon2")]
+fn passwords(config: &Config) -> hiveguard::domain::PasswordService<argon2::Argon2<'static>> {
+    hiveguard::domain::PasswordService::new(argon2::Argon2::default(), config.passwords.max_concurrent_hashes)
+}
+
+
+/// A random hex encoded 32 byte key, as `tokens.key` expects.
+pub fn generate_key() -> String {
+    let mut key = [0u8; 32];
+    rand::rng().fill_bytes(&mut key);
+    key.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_keys_are_hex_encoded_32_byte_keys() {
+        let key = generate_key();
+        assert_eq!(key.len(), 64);
+        assert!(key.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(key, generate_key());
+    }
+}
@@ -0,0 +1,184 @@
+use hiveguard::config::{Config, LogLevel, CONFIG_PATH, PROFILE};
+use clap::{Parser, Subcommand};
+use chrono::{DateTime, Utc};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+
+/// Hiveguard authentication server.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    /// Path of the configuration file (.json, .toml, .yaml or .yml).
+    #[arg(short, long, env = CONFIG_PATH)]
+    pub config: Option<PathBuf>,
+    /// Profile of the configuration file to apply on top of its base settings. eg `dev` or `prod`
+    #[arg(short, long, env = PROFILE)]
+    pub profile: Option<String>,
+    /// Address the server listens on. Overrides `bind` in the configuration.
+    #[arg(short, long)]
+    pub bind: Option<SocketAddr>,
+    /// Log level. Overrides `log_level` in the configuration.
+    #[arg(short, long, value_enum)]
+    pub log_level: Option<LogLevel>,
+    /// Load and validate the configuration, then exit.
+    #[arg(long)]
+    pub check_config: bool,
+    /// Check that the database tables exist with the keys, indexes and time to live hiveguard expects, then exit.
+    #[arg(long)]
+    pub check_schema: bool,
+    /// Check every configured dependency (database, SMTP, event bus, directory, GeoIP database and keys), print a report, then exit.
+    /// Exits with an error when a check fails.
+    #[arg(long)]
+    pub doctor: bool,
+    /// The tenant the admin commands run against, one of `tenancy.tenants`. Required when the deployment has tenants.
+    #[arg(long, global = true)]
+    pub tenant: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Break-glass operations, run against the configured database directly.
+    #[command(subcommand)]
+    Admin(AdminCommand),
+}
+
+
+#[derive(Subcommand, Debug)]
+pub enum AdminCommand {
+    /// Create an active user with the admin role, eg the first one of a deployment.
+    #[cfg(feature = "argon2")]
+    CreateAdmin {
+        #[arg(long)]
+        username: String,
+        #[arg(long)]
+        fullname: String,
+        #[cfg(feature = "email")]
+        #[arg(long)]
+        email: String,
+        #[cfg(feature = "phone")]
+        #[arg(long)]
+        phone: String,
+        /// Read from the environment so that it stays out of the shell history.
+        #[arg(long, env = hiveguard::config::ADMIN_PASSWORD, hide_env_values = true)]
+        password: String,
+    },
+    /// Set a new password for a user and log them out everywhere.
+    #[cfg(feature = "argon2")]
+    ResetPassword {
+        /// The id of the user.
+        user: String,
+        #[arg(long, env = hiveguard::config::ADMIN_PASSWORD, hide_env_values = true)]
+        password: String,
+    },
+    /// Reactivate a suspended or deactivated account.
+    Unlock {
+        /// The id of the user.
+        user: String,
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Merge a duplicate account into another one, which takes over its sessions. The duplicate is deactivated.
+    Merge {
+        /// The id of the account kept.
+        primary: String,
+        /// The id of the duplicate.
+        duplicate: String,
+    },
+    /// Print a new random key for `tokens.key`. Tokens issued with the current key stop being valid once it is replaced.
+    GenerateKey,
+    /// Manage the keys tokens are signed with under `v4.public`.
+    #[command(subcommand)]
+    Keys(KeysCommand),
+    /// Write every user, password hashes included, as newline-delimited JSON.
+    Dump {
+        /// Where to write the users. The standard output when missing.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Create the users of a dump. Users that already exist are skipped.
+    Load {
+        input: PathBuf,
+    },
+    /// Create the users declared in a seed file (.json, .toml, .yaml or .yml). Users that already exist are skipped.
+    #[cfg(feature = "argon2")]
+    Seed {
+        file: PathBuf,
+    },
+}
+
+
+#[derive(Subcommand, Debug)]
+pub enum KeysCommand {
+    /// List the signing keys with their status, the key of `tokens.signing_key` included.
+    List,
+    /// Generate a signing key. It is published right away, and signs tokens once it activates.
+    Generate {
+        /// When the key starts signing tokens, eg `2026-11-01T00:00:00Z`. Right away when missing.
+        #[arg(long)]
+        activate_at: Option<DateTime<Utc>>,
+    },
+    /// Move the activation of a key, eg to roll back to a superseded key.
+    Activate {
+        kid: String,
+        /// When the key starts signing tokens. Right away when missing.
+        #[arg(long)]
+        at: Option<DateTime<Utc>>,
+    },
+    /// Retire a key, which stops publishing it. The tokens it signed stop being valid.
+    Retire {
+        kid: String,
+    },
+    /// Print the public key of `tokens.signer`, eg a KMS or HSM key, for the `public_keys` of resource servers.
+    Signer,
+}
+
+
+impl Cli {
+    /// The options that override configuration values, to apply to every configuration loaded.
+    pub fn overrides(&self) -> impl Fn(&mut Config) + Send + Sync + 'static {
+        let (bind, log_level) = (self.bind, self.log_level);
+        move |config| {
+            if let Some(bind) = bind {
+                config.bind = bind;
+            }
+            if let Some(log_level) = log_level {
+                config.log_level = log_level;
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_admin_commands() {
+        let cli = Cli::parse_from(["hiveguard", "admin", "unlock", "000000000000000000000001", "--reason", "false positive"]);
+        assert!(matches!(cli.command, Some(Command::Admin(AdminCommand::Unlock { user, reason: Some(_) })) if user == "000000000000000000000001"));
+        let cli = Cli::parse_from(["hiveguard", "--config", "hiveguard.toml", "admin", "dump"]);
+        assert!(matches!(cli.command, Some(Command::Admin(AdminCommand::Dump { output: None }))));
+        let cli = Cli::parse_from(["hiveguard", "admin", "keys", "generate", "--activate-at", "2026-11-01T00:00:00Z"]);
+        assert!(matches!(cli.command, Some(Command::Admin(AdminCommand::Keys(KeysCommand::Generate { activate_at: Some(_) })))));
+    }
+
+    #[test]
+    fn test_overrides() {
+        let cli = Cli::parse_from(["hiveguard", "--bind", "127.0.0.1:3000", "--log-level", "debug"]);
+        let mut config = Config::default();
+        cli.overrides()(&mut config);
+        assert_eq!(config.bind, "127.0.0.1:3000".parse().unwrap());
+        assert_eq!(config.log_level, LogLevel::Debug);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::types::ConfigError;
+use serde_json::{Map, Value};
+
+
+const PREFIX: &str = "HIVEGUARD_";
+const SEPARATOR: &str = "__";
+/// The variable used to point hiveguard at its configuration file. It is not a configuration field itself.
+pub const CONFIG_PATH: &str = "HIVEGUARD_CONFIG";
+/// The variable selecting the configuration profile. It is not a configuration field itself.
+pub const PROFILE: &str = "HIVEGUARD_PROFILE";
+/// The variable the password of the `admin` commands is read from. It is not a configuration field itself.
+pub const ADMIN_PASSWORD: &str = "HIVEGUARD_ADMIN_PASSWORD";
+
+
+/// Applies every `HIVEGUARD_*` variable in `vars` on top of `config`.
+///
+/// Variables are applied in lexicographic order so that a more specific variable (`HIVEGUARD_TOKENS__KEY`)
+/// always wins over a less specific one (`HIVEGUARD_TOKENS`).
+/// A value is typed after the value it replaces, or after the value of the same field in `defaults` when `config` has none:
+/// it is kept as a string in place of a string, and parsed as JSON, falling back to a string, in place of anything else.
+/// Fields with neither, such as optional ones, get JSON objects and arrays parsed and anything else kept as a string.
+///
+/// Returns the dotted paths of the fields kept as strings for want of a type. see [`retype`].
+pub fn apply_overrides<I: IntoIterator<Item = (String, String)>>(config: &mut Value, defaults: &Value, vars: I) -> Result<Vec<String>, ConfigError> {
+    let mut vars = vars.into_iter()
+        .filter(|(name, _)| name.starts_with(PREFIX) && ![CONFIG_PATH, PROFILE, ADMIN_PASSWORD].contains(&name.as_str()))
+        .collect::<Vec<_>>();
+    vars.sort();
+    let mut untyped = Vec::new();
+    for (name, raw) in vars {
+        let keys = name[PREFIX.len()..].split(SEPARATOR).map(str::to_lowercase).collect::<Vec<_>>();
+        if keys.iter().any(String::is_empty) {
+            return Err(ConfigError::InvalidOverride(name));
+        }
+        let Some((last, parents)) = keys.split_last() else {
+            return Err(ConfigError::InvalidOverride(name));
+        };
+        let mut current = &mut *config;
+        let mut default = Some(defaults);
+        for key in parents {
+            let Value::Object(map) = current else {
+                return Err(ConfigError::InvalidOverride(name));
+            };
+            current = map.entry(key.clone()).or_insert_with(|| Value::Object(Map::new()));
+            default = default.and_then(|default| default.get(key));
+        }
+        let Value::Object(map) = current else {
+            return Err(ConfigError::InvalidOverride(name));
+        };
+        let value = match map.get(last).or(default.and_then(|default| default.get(last))) {
+            Some(Value::String(_)) => Value::String(raw),
+            None | Some(Value::Null) => match serde_json::from_str(&raw) {
+                Ok(value @ (Value::Object(_) | Value::Array(_))) => value,
+                _ => {
+                    untyped.push(keys.join("."));
+                    Value::String(raw)
+                },
+            },
+            Some(_) => serde_json::from_str(&raw).unwrap_or(Value::String(raw)),
+        };
+        map.insert(last.clone(), value);
+    }
+    Ok(untyped)
+}
+
+
+/// Parses the string at the dotted `path` of `config` as JSON, for a field kept as a string by [`apply_overrides`]
+/// that turned out not to be one. eg `HIVEGUARD_MEMORY__MAX_USERS=1000`.
+///
+/// Returns whether the value changed.
+pub fn retype(config: &mut Value, path: &str) -> bool {
+    let Some(value) = path.split('.').try_fold(config, |value, key| value.get_mut(key)) else {
+        return false;
+    };
+    let Some(parsed) = value.as_str().and_then(|raw| serde_json::from_str::<Value>(raw).ok()).filter(|parsed| !parsed.is_string()) else {
+        return false;
+    };
+    *value = parsed;
+    true
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_nested_overrides() {
+        let mut config = json!({"issuer": "file", "tokens": {"key": "abc", "access_token_ttl": 60}});
+        let vars = vars(&[
+            ("HIVEGUARD_ISSUER", "env"),
+            ("HIVEGUARD_TOKENS__ACCESS_TOKEN_TTL", "120"),
+            ("HIVEGUARD_DYNAMODB__USERS_TABLE", "people"),
+            ("PATH", "/usr/bin"),
+        ]);
+        apply_overrides(&mut config, &json!({}), vars).unwrap();
+        assert_eq!(config, json!({
+            "issuer": "env",
+            "tokens": {"key": "abc", "access_token_ttl": 120},
+            "dynamodb": {"users_table": "people"}
+        }));
+    }
+
+    #[test]
+    fn test_existing_strings_are_not_reinterpreted() {
+        let mut config = json!({"tokens": {"key": "abc"}});
+        apply_overrides(&mut config, &json!({}), vars(&[("HIVEGUARD_TOKENS__KEY", "1234")])).unwrap();
+        assert_eq!(config, json!({"tokens": {"key": "1234"}}));
+    }
+
+    #[test]
+    fn test_overrides_are_typed_after_the_defaults() {
+        let mut config = json!({});
+        let defaults = json!({"tokens": {"key": "", "access_token_ttl": 60}, "memory": {"max_users": null}});
+        let vars = vars(&[
+            ("HIVEGUARD_TOKENS__KEY", "123456"),
+            ("HIVEGUARD_TOKENS__ACCESS_TOKEN_TTL", "120"),
+            ("HIVEGUARD_MEMORY__MAX_USERS", "1000"),
+        ]);
+        assert_eq!(apply_overrides(&mut config, &defaults, vars).unwrap(), vec![String::from("memory.max_users")]);
+        assert_eq!(config, json!({"tokens": {"key": "123456", "access_token_ttl": 120}, "memory": {"max_users": "1000"}}));
+        assert!(retype(&mut config, "memory.max_users"));
+        assert!(!retype(&mut config, "memory.max_users"));
+        assert_eq!(config["memory"]["max_users"], json!(1000));
+    }
+
+    #[test]
+    fn test_specific_overrides_win() {
+        let mut config = json!({});
+        let vars = vars(&[
+            ("HIVEGUARD_TOKENS__KEY", "specific"),
+            ("HIVEGUARD_TOKENS", r#"{"key": "general", "access_token_ttl": 60}"#),
+        ]);
+        apply_overrides(&mut config, &json!({}), vars).unwrap();
+        assert_eq!(config, json!({"tokens": {"key": "specific", "access_token_ttl": 60}}));
+    }
+
+    #[test]
+    fn test_override_through_a_scalar_is_rejected() {
+        let mut config = json!({"issuer": "file"});
+        let result = apply_overrides(&mut config, &json!({}), vars(&[("HIVEGUARD_ISSUER__NAME", "env")]));
+        assert!(matches!(result, Err(ConfigError::InvalidOverride(name)) if name == "HIVEGUARD_ISSUER__NAME"));
+    }
+}
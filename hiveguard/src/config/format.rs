@@ -0,0 +1,68 @@
+use crate::types::ConfigError;
+use serde_json::Value;
+use std::path::Path;
+
+
+/// The file formats a configuration can be written in.
+///
+/// Every format is parsed into the same intermediate tree before being deserialized into [`super::Config`],
+/// so the field names and nesting are identical regardless of the format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+
+impl Format {
+    pub fn parse(&self, contents: &str) -> Result<Value, ConfigError> {
+        match self {
+            Format::Json => Ok(serde_json::from_str(contents)?),
+            Format::Toml => Ok(toml::from_str(contents)?),
+            Format::Yaml => Ok(serde_yaml::from_str(contents)?),
+        }
+    }
+}
+
+
+impl TryFrom<&Path> for Format {
+    type Error = ConfigError;
+
+    fn try_from(path: &Path) -> Result<Self, Self::Error> {
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+        match extension.to_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "toml" => Ok(Format::Toml),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            _ => Err(ConfigError::UnsupportedFormat(path.display().to_string())),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(Format::try_from(Path::new("hiveguard.json")).unwrap(), Format::Json);
+        assert_eq!(Format::try_from(Path::new("hiveguard.TOML")).unwrap(), Format::Toml);
+        assert_eq!(Format::try_from(Path::new("config/hiveguard.yml")).unwrap(), Format::Yaml);
+        assert!(Format::try_from(Path::new("hiveguard.ini")).is_err());
+        assert!(Format::try_from(Path::new("hiveguard")).is_err());
+    }
+
+    #[test]
+    fn test_formats_produce_the_same_tree() {
+        let expected = json!({"issuer": "hiveguard", "tokens": {"key": "abc", "access_token_ttl": 60}});
+        let json = r#"{"issuer": "hiveguard", "tokens": {"key": "abc", "access_token_ttl": 60}}"#;
+        let toml = "issuer = \"hiveguard\"\n\n[tokens]\nkey = \"abc\"\naccess_token_ttl = 60\n";
+        let yaml = "issuer: hiveguard\ntokens:\n  key: abc\n  access_token_ttl: 60\n";
+        assert_eq!(Format::Json.parse(json).unwrap(), expected);
+        assert_eq!(Format::Toml.parse(toml).unwrap(), expected);
+        assert_eq!(Format::Yaml.parse(yaml).unwrap(), expected);
+    }
+}
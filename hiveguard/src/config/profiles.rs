@@ -0,0 +1,85 @@
+use crate::types::ConfigError;
+use serde_json::Value;
+
+
+/// The section of the configuration holding the profiles.
+const PROFILES: &str = "profiles";
+
+
+/// Merges the overrides of `profile` onto the base configuration and drops the `profiles` section.
+///
+/// Objects are merged key by key while any other value replaces the base value.
+pub fn apply_profile(config: &mut Value, profile: Option<&str>) -> Result<(), ConfigError> {
+    let mut profiles = match config {
+        Value::Object(map) => map.remove(PROFILES),
+        _ => None,
+    };
+    let Some(profile) = profile else {
+        return Ok(());
+    };
+    let overrides = profiles.as_mut()
+        .and_then(|profiles| profiles.get_mut(profile))
+        .map(Value::take)
+        .ok_or_else(|| ConfigError::UnknownProfile(profile.to_string()))?;
+    merge(config, overrides);
+    Ok(())
+}
+
+
+fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    },
+                }
+            }
+        },
+        (base, overrides) => *base = overrides,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config() -> Value {
+        json!({
+            "issuer": "hiveguard",
+            "tokens": {"key": "dev", "access_token_ttl": 60},
+            "profiles": {
+                "prod": {"tokens": {"key": "prod"}, "dynamodb": {"users_table": "prod-users"}}
+            }
+        })
+    }
+
+    #[test]
+    fn test_profile_is_merged_onto_the_base() {
+        let mut config = config();
+        apply_profile(&mut config, Some("prod")).unwrap();
+        assert_eq!(config, json!({
+            "issuer": "hiveguard",
+            "tokens": {"key": "prod", "access_token_ttl": 60},
+            "dynamodb": {"users_table": "prod-users"}
+        }));
+    }
+
+    #[test]
+    fn test_profiles_are_dropped_without_a_selection() {
+        let mut config = config();
+        apply_profile(&mut config, None).unwrap();
+        assert_eq!(config, json!({"issuer": "hiveguard", "tokens": {"key": "dev", "access_token_ttl": 60}}));
+    }
+
+    #[test]
+    fn test_unknown_profile() {
+        let mut config = config();
+        let result = apply_profile(&mut config, Some("staging"));
+        assert!(matches!(result, Err(ConfigError::UnknownProfile(profile)) if profile == "staging"));
+    }
+}
@@ -0,0 +1,253 @@
+use crate::ports::outputs::secrets::Secrets;
+use arc_swap::{ArcSwap, Guard};
+use std::time::{Duration, SystemTime};
+use std::path::{Path, PathBuf};
+use std::fmt::Display;
+use std::sync::Arc;
+use super::{Config, TokensConfig, OutboxConfig, DisposableEmailsConfig};
+
+
+/// A [`Config`] shared by the running services whose runtime tunable settings can be swapped atomically without a restart.
+///
+/// Readers take a cheap snapshot with [`SharedConfig::load`] and never block a reload.
+#[derive(Clone)]
+pub struct SharedConfig(Arc<ArcSwap<Config>>);
+
+
+impl SharedConfig {
+    pub fn new(config: Config) -> Self {
+        Self(Arc::new(ArcSwap::from_pointee(config)))
+    }
+
+    pub fn load(&self) -> Guard<Arc<Config>> {
+        self.0.load()
+    }
+
+    /// Swaps in the runtime tunable settings of `config`.
+    ///
+    /// The returned fields changed in `config` but only take effect after a restart.
+    pub fn reload(&self, config: Config) -> Vec<&'static str> {
+        let mut ignored = Vec::new();
+        self.0.rcu(|current| {
+            let mut next = Config::clone(current);
+            ignored = next.apply_tunables(config.clone());
+            next
+        });
+        ignored
+    }
+
+    /// Polls the configuration file at `path` every `period` and reloads it, with the same `profile`, whenever it is modified.
+    ///
+    /// `overrides` is applied to every configuration loaded, like it was to the first one, eg the command line options,
+    /// so that the values it sets are not taken for changes.
+    /// A configuration that fails to load or validate is reported and the current one is kept.
+    /// The returned future runs forever and is meant to be spawned.
+    pub async fn watch<S: Secrets, O: Fn(&mut Config)>(self, path: PathBuf, profile: Option<String>, period: Duration, secrets: S, overrides: O)
+    where
+        S::Error: Display
+    {
+        let mut last_modified = modified(&path);
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            let current = modified(&path);
+            if current == last_modified {
+                continue;
+            }
+            last_modified = current;
+            match Config::load(Some(&path), profile.as_deref(), &secrets).await {
+                Ok(mut config) => {
+                    overrides(&mut config);
+                    let ignored = self.reload(config);
+                    tracing::info!("configuration reloaded");
+                    if !ignored.is_empty() {
+                        tracing::warn!(fields = ?ignored, "configuration changes that require a restart were ignored");
+                    }
+                },
+                Err(err) => tracing::error!(error = %err, "could not reload the configuration"),
+            }
+        }
+    }
+}
+
+
+impl Config {
+    /// Copies the settings that are safe to change while running from `other`.
+    /// Returns the fields that differ but can only change on restart.
+    ///
+    /// `other` is destructured without a rest pattern, so that a new field does not build until it is sorted into one or
+    /// the other.
+    fn apply_tunables(&mut self, other: Config) -> Vec<&'static str> {
+        let Config {
+            issuer,
+            bind,
+            log_level,
+            id_strategy,
+            tokens,
+            pii,
+            sessions,
+            cookies,
+            database,
+            memory,
+            passwords,
+            usernames,
+            signup,
+            disposable_emails,
+            guests,
+            unverified,
+            profile,
+            avatars,
+            http,
+            limits,
+            retry,
+            circuit_breaker,
+            security_events,
+            events,
+            outbox,
+            counters,
+            error_reporting,
+            jobs,
+            mail_queue,
+            erasure,
+            consent,
+            directory,
+            tenancy,
+            proxies,
+            geoip,
+            geo_blocking,
+            anomalies,
+            login_history,
+            organisations,
+            audit,
+            redirects,
+            services,
+            acl,
+            #[cfg(feature = "dynamodb")]
+            dynamodb,
+            #[cfg(feature = "s3")]
+            s3,
+            #[cfg(feature = "email")]
+            smtp,
+            #[cfg(feature = "otlp")]
+            telemetry,
+            #[cfg(feature = "saml")]
+            saml,
+        } = other;
+        let TokensConfig { key, access_token_ttl, refresh_token_ttl, cache_ttl_secs, cache_capacity, algorithm, signing_key, signer } = tokens;
+        let OutboxConfig { enabled, batch_size } = outbox;
+        let DisposableEmailsConfig { action, list } = disposable_emails;
+        let mut ignored = Vec::new();
+        let mut restart = |field: &'static str, changed: bool| {
+            if changed {
+                ignored.push(field);
+            }
+        };
+        restart("issuer", self.issuer != issuer);
+        restart("bind", self.bind != bind);
+        restart("log_level", self.log_level != log_level);
+        restart("id_strategy", self.id_strategy != id_strategy);
+        restart("tokens.key", self.tokens.key != key);
+        restart("tokens.signing", (self.tokens.algorithm, &self.tokens.signing_key, &self.tokens.signer) != (algorithm, &signing_key, &signer));
+        restart("pii", self.pii != pii);
+        restart("tokens.cache", (self.tokens.cache_ttl_secs, self.tokens.cache_capacity) != (cache_ttl_secs, cache_capacity));
+        restart("cookies", self.cookies != cookies);
+        restart("database", self.database != database);
+        restart("memory", self.memory != memory);
+        // the password service is built with its bound on startup.
+        restart("passwords", self.passwords != passwords);
+        restart("usernames", self.usernames != usernames);
+        // the bundled domains and those of the list are read once, on startup.
+        restart("disposable_emails.list", self.disposable_emails.list != list);
+        restart("avatars", self.avatars != avatars);
+        restart("http", self.http != http);
+        restart("retry", self.retry != retry);
+        restart("circuit_breaker", self.circuit_breaker != circuit_breaker);
+        restart("security_events", self.security_events != security_events);
+        restart("events", self.events != events);
+        restart("outbox.enabled", self.outbox.enabled != enabled);
+        restart("counters", self.counters != counters);
+        restart("directory", self.directory != directory);
+        restart("error_reporting", self.error_reporting != error_reporting);
+        restart("jobs", self.jobs != jobs);
+        restart("tenancy", self.tenancy != tenancy);
+        restart("geoip", self.geoip != geoip);
+        #[cfg(feature = "dynamodb")]
+        restart("dynamodb", self.dynamodb != dynamodb);
+        #[cfg(feature = "s3")]
+        restart("s3", self.s3 != s3);
+        #[cfg(feature = "otlp")]
+        restart("telemetry", self.telemetry != telemetry);
+        #[cfg(feature = "saml")]
+        restart("saml", self.saml != saml);
+        self.tokens.access_token_ttl = access_token_ttl;
+        self.tokens.refresh_token_ttl = refresh_token_ttl;
+        self.sessions = sessions;
+        self.signup = signup;
+        self.disposable_emails.action = action;
+        self.guests = guests;
+        self.unverified = unverified;
+        self.profile = profile;
+        self.limits = limits;
+        self.mail_queue = mail_queue;
+        self.outbox.batch_size = batch_size;
+        self.erasure = erasure;
+        self.consent = consent;
+        self.proxies = proxies;
+        self.geo_blocking = geo_blocking;
+        self.anomalies = anomalies;
+        self.login_history = login_history;
+        self.organisations = organisations;
+        self.audit = audit;
+        self.redirects = redirects;
+        self.services = services;
+        self.acl = acl;
+        #[cfg(feature = "email")]
+        {
+            self.smtp = smtp;
+        }
+        ignored
+    }
+}
+
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{PasswordsConfig, AccessList};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_reload_only_swaps_tunables() {
+        let shared = SharedConfig::new(Config::default());
+        let config = Config {
+            issuer: "elsewhere".into(),
+            tokens: TokensConfig { access_token_ttl: 60, ..Default::default() },
+            ..Default::default()
+        };
+        let ignored = shared.reload(config);
+        assert_eq!(ignored, vec!["issuer"]);
+        let current = shared.load();
+        assert_eq!(current.issuer, Config::default().issuer);
+        assert_eq!(current.tokens.access_token_ttl, 60);
+    }
+
+    #[test]
+    fn test_reload_swaps_network_acls_but_not_password_policy() {
+        let shared = SharedConfig::new(Config::default());
+        let admin = AccessList { allow: vec!["10.0.0.0/8".parse().unwrap()], deny: Vec::new() };
+        let config = Config {
+            acl: HashMap::from([(String::from("admin"), admin.clone())]),
+            passwords: PasswordsConfig { max_concurrent_hashes: 3 },
+            ..Default::default()
+        };
+        assert_eq!(shared.reload(config), vec!["passwords"]);
+        let current = shared.load();
+        assert_eq!(current.acl.get("admin"), Some(&admin));
+        assert_eq!(current.passwords, Config::default().passwords);
+    }
+}
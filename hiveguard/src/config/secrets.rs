@@ -0,0 +1,85 @@
+use crate::ports::outputs::secrets::Secrets;
+use crate::types::{ConfigError, ConfigIssue};
+use std::fmt::Display;
+use serde_json::Value;
+
+
+/// Replaces every secret reference in `config` with the secret it points to.
+///
+/// Every reference that fails to resolve is reported with the path of its field.
+pub async fn resolve<S: Secrets>(config: &mut Value, secrets: &S) -> Result<(), ConfigError>
+where
+    S::Error: Display
+{
+    let mut strings = Vec::new();
+    collect(config, String::new(), &mut strings);
+    let mut issues = Vec::new();
+    for (field, string) in strings {
+        match secrets.resolve(string).await {
+            Ok(Some(secret)) => *string = secret,
+            Ok(None) => {},
+            Err(err) => issues.push(ConfigIssue::new(field, err.to_string())),
+        }
+    }
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(ConfigError::Invalid(issues))
+    }
+}
+
+
+fn collect<'a>(value: &'a mut Value, field: String, strings: &mut Vec<(String, &'a mut String)>) {
+    match value {
+        Value::String(string) => strings.push((field, string)),
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let field = if field.is_empty() { key.clone() } else { format!("{}.{}", field, key) };
+                collect(value, field, strings);
+            }
+        },
+        Value::Array(values) => {
+            for (index, value) in values.iter_mut().enumerate() {
+                collect(value, format!("{}[{}]", field, index), strings);
+            }
+        },
+        _ => {},
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Store;
+
+    impl Secrets for Store {
+        type Error = String;
+
+        async fn resolve(&self, value: &str) -> Result<Option<String>, Self::Error> {
+            match value {
+                "vault:secret/hiveguard#key" => Ok(Some("secret".into())),
+                "vault:secret/hiveguard#missing" => Err("secret not found".into()),
+                _ => Ok(None),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_references_are_resolved() {
+        let mut config = json!({"issuer": "hiveguard", "tokens": {"key": "vault:secret/hiveguard#key", "access_token_ttl": 60}});
+        resolve(&mut config, &Store).await.unwrap();
+        assert_eq!(config, json!({"issuer": "hiveguard", "tokens": {"key": "secret", "access_token_ttl": 60}}));
+    }
+
+    #[tokio::test]
+    async fn test_failures_carry_the_field_path() {
+        let mut config = json!({"smtp": {"url": "vault:secret/hiveguard#missing"}});
+        match resolve(&mut config, &Store).await {
+            Err(ConfigError::Invalid(issues)) => assert_eq!(issues, vec![ConfigIssue::new("smtp.url", "secret not found")]),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
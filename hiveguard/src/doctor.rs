@@ -0,0 +1,179 @@
+use hiveguard::adaptors::outputs::directory::ExternalDirectory;
+use hiveguard::adaptors::outputs::geoip::GeoIpSource;
+use hiveguard::adaptors::outputs::events::EventSink;
+use hiveguard::adaptors::outputs::{http, retry::Retry};
+use hiveguard::config::{Config, DirectoryConfig, EventsConfig, GeoIpConfig};
+use std::fmt::{self, Display};
+
+
+/// The outcome of one check of `--doctor`.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Pass(String),
+    Fail(String),
+    /// the dependency is not configured.
+    Skipped,
+}
+
+
+#[derive(Debug, PartialEq)]
+struct Check {
+    /// the dependency checked. eg `database`
+    name: &'static str,
+    outcome: Outcome,
+}
+
+
+impl Check {
+    fn new<E: Display>(name: &'static str, result: Result<String, E>) -> Self {
+        let outcome = match result {
+            Ok(detail) => Outcome::Pass(detail),
+            Err(err) => Outcome::Fail(err.to_string()),
+        };
+        Self { name, outcome }
+    }
+
+    fn skipped(name: &'static str) -> Self {
+        Self { name, outcome: Outcome::Skipped }
+    }
+}
+
+
+impl Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.outcome {
+            Outcome::Pass(detail) => write!(f, "pass  {:<12}{}", self.name, detail),
+            Outcome::Fail(error) => write!(f, "FAIL  {:<12}{}", self.name, error),
+            Outcome::Skipped => write!(f, "skip  {:<12}not configured", self.name),
+        }
+    }
+}
+
+
+/// Checks every dependency `config` sets up, prints a line per dependency and fails when one of them failed.
+///
+/// The configuration itself is valid by then, as it was loaded.
+pub async fn run(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let checks = checks(config).await;
+    for check in &checks {
+        println!("{}", check);
+    }
+    match checks.iter().filter(|check| matches!(check.outcome, Outcome::Fail(_))).count() {
+        0 => Ok(()),
+        failed => Err(format!("{} of {} checks failed", failed, checks.len()).into()),
+    }
+}
+
+
+async fn checks(config: &Config) -> Vec<Check> {
+    let mut checks = vec![database(config).await];
+    #[cfg(feature = "email")]
+    checks.push(smtp(config).await);
+    checks.push(events(config).await);
+    checks.push(directory(config).await);
+    checks.push(geoip(config));
+    // the key was validated with the rest of the configuration.
+    checks.push(Check::new::<String>("tokens.key", Ok(String::from("a 32 byte key"))));
+    #[cfg(feature = "saml")]
+    checks.extend(saml(config));
+    checks
+}
+
+
+/// Connects to the database and checks its schema, like `--check-schema`.
+async fn database(config: &Config) -> Check {
+    #[cfg(feature = "dynamodb")]
+    {
+        let result = crate::admin::check_schema(config).await.map(|()| String::from("the tables match the schema"));
+        Check::new("database", result)
+    }
+    #[cfg(not(feature = "dynamodb"))]
+    {
+        let _ = config;
+        Check::skipped("database")
+    }
+}
+
+
+#[cfg(feature = "email")]
+async fn smtp(config: &Config) -> Check {
+    use hiveguard::adaptors::outputs::mail::smtp::Smtp;
+    if config.smtp.url.is_empty() {
+        return Check::skipped("smtp");
+    }
+    let result = match Smtp::new(&config.smtp) {
+        Ok(smtp) => smtp.check().await.map(|()| String::from("the server accepted the connection")),
+        Err(err) => Err(err),
+    };
+    Check::new("smtp", result)
+}
+
+
+async fn events(config: &Config) -> Check {
+    if config.events == EventsConfig::Disabled {
+        return Check::skipped("events");
+    }
+    Check::new("events", EventSink::new(&config.events).await.map(|_| String::from("connected to the bus")))
+}
+
+
+async fn directory(config: &Config) -> Check {
+    if config.directory == DirectoryConfig::Disabled {
+        return Check::skipped("directory");
+    }
+    Check::new("directory", ExternalDirectory::from(&config.directory).check().await.map(|()| String::from("connected to the directory")))
+}
+
+
+/// Opens the GeoIP database. Lookup services are not called, as every call may be billed.
+fn geoip(config: &Config) -> Check {
+    if config.geoip == GeoIpConfig::Disabled {
+        return Check::skipped("geoip");
+    }
+    let result = http::client(&config.http)
+        .map_err(|err| err.to_string())
+        .and_then(|client| GeoIpSource::new(&config.geoip, client, Retry::new(&config.retry)).map_err(|err| err.to_string()))
+        .map(|_| String::from("the database is readable"));
+    Check::new("geoip", result)
+}
+
+
+/// The key assertions are signed with and the certificates of the identity providers, when they are used.
+#[cfg(feature = "saml")]
+fn saml(config: &Config) -> Vec<Check> {
+    use hiveguard::domain::saml::{idp::IdentityProvider, sp::ServiceProvider};
+    let mut checks = Vec::new();
+    if !config.saml.enabled {
+        return checks;
+    }
+    if !config.saml.service_providers.is_empty() {
+        let result = IdentityProvider::new(&config.saml).map(|_| String::from("the key and its certificate are readable"));
+        checks.push(Check::new("saml.idp", result));
+    }
+    if !config.saml.identity_providers.is_empty() {
+        let result = ServiceProvider::new(&config.saml).map(|_| format!("the certificates of {} identity providers are readable", config.saml.identity_providers.len()));
+        checks.push(Check::new("saml.sp", result));
+    }
+    checks
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks_print_as_a_report() {
+        assert_eq!(Check::new::<String>("database", Ok("the tables match the schema".into())).to_string(), "pass  database    the tables match the schema");
+        assert_eq!(Check::new("smtp", Err("connection refused")).to_string(), "FAIL  smtp        connection refused");
+        assert_eq!(Check::skipped("events").to_string(), "skip  events      not configured");
+    }
+
+    #[tokio::test]
+    async fn test_unconfigured_dependencies_are_skipped() {
+        let config = Config::default();
+        assert_eq!(events(&config).await, Check::skipped("events"));
+        assert_eq!(directory(&config).await, Check::skipped("directory"));
+        assert_eq!(geoip(&config), Check::skipped("geoip"));
+    }
+}
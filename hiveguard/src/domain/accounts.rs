@@ -0,0 +1,255 @@
+use crate::ports::outputs::database::{Database, tables::{UsersTable, SessionsTable}};
+use crate::types::{Error, User, Id, Session, Status, DatabaseError, SecurityEvent, SecurityEventKind, UserFilter, Page, Namespace};
+use crate::ports::outputs::security_events::SecurityEvents;
+use super::metadata::UserMetadata;
+use super::sessions::Sessions;
+use serde_json::{Map, Value};
+use tracing::instrument;
+use std::fmt::Display;
+
+
+/// the `service` metadata field holding the id of the account a merged account was merged into.
+pub const MERGED_INTO: &str = "merged_into";
+
+
+/// Admin operations on the lifecycle of accounts.
+///
+/// Every change records its reason on the user and emits a `status_changed` security event naming the admin who made it.
+/// Suspending or deactivating an account deletes its sessions so that it cannot renew its tokens.
+pub struct Accounts;
+
+
+impl Accounts {
+    /// Locks an active or pending account until it is reactivated.
+    pub async fn suspend<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, SessionsTable: SessionsTable<DB::Client, Item = Session>>, E: SecurityEvents>(db: &DB, events: &E, id: Id, reason: String, changed_by: Option<Id>) -> Result<(), Error>
+    where
+        Error: From<DB::Error>,
+        E::Error: Display
+    {
+        Self::change_status(db, events, id, Status::Suspended, Some(reason), changed_by).await
+    }
+
+    /// Reopens a suspended or deactivated account.
+    pub async fn reactivate<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, SessionsTable: SessionsTable<DB::Client, Item = Session>>, E: SecurityEvents>(db: &DB, events: &E, id: Id, reason: Option<String>, changed_by: Option<Id>) -> Result<(), Error>
+    where
+        Error: From<DB::Error>,
+        E::Error: Display
+    {
+        Self::change_status(db, events, id, Status::Active, reason, changed_by).await
+    }
+
+    pub async fn deactivate<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, SessionsTable: SessionsTable<DB::Client, Item = Session>>, E: SecurityEvents>(db: &DB, events: &E, id: Id, reason: Option<String>, changed_by: Option<Id>) -> Result<(), Error>
+    where
+        Error: From<DB::Error>,
+        E::Error: Display
+    {
+        Self::change_status(db, events, id, Status::Deactivated, reason, changed_by).await
+    }
+
+    /// Merges the account `duplicate` into `primary`, eg a password user and a SAML user who turn out to be the same person.
+    ///
+    /// The sessions of the duplicate move to the primary, so its devices stay logged in, as the primary.
+    /// The duplicate is kept as a tombstone: it is deactivated with the reason `merged into <primary>` and names the primary
+    /// under [`MERGED_INTO`] in its `service` metadata, which logins through its directory or identity provider follow. see [`Accounts::follow`].
+    /// An `account_merged` security event names the admin who merged them. Returns the number of sessions moved.
+    /// A merge that failed halfway, leaving the duplicate marked but not deactivated, is finished by merging the same accounts again.
+    #[instrument(skip(db, events), fields(primary = %primary.to_hex(), duplicate = %duplicate.to_hex()), err)]
+    pub async fn merge<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, SessionsTable: SessionsTable<DB::Client, Item = Session>>, E: SecurityEvents>(db: &DB, events: &E, primary: Id, duplicate: Id, merged_by: Option<Id>) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>,
+        E::Error: Display
+    {
+        if primary == duplicate {
+            return Err(Error::InvalidMerge(String::from("an account cannot be merged into itself")));
+        }
+        let into = db.get_user_by_id(primary).await?.ok_or(DatabaseError::UserNotFound)?;
+        let user = db.get_user_by_id(duplicate).await?.ok_or(DatabaseError::UserNotFound)?;
+        // marked first, so that a merge that fails halfway can be told apart and finished by merging again.
+        match (merged_into(&into), merged_into(&user)) {
+            (None, None) => {
+                UserMetadata::update(db, duplicate, Namespace::Service, Map::from_iter([(String::from(MERGED_INTO), Value::String(primary.to_hex()))])).await?;
+            },
+            (None, Some(into)) if into == primary && user.status != Status::Deactivated => {},
+            _ => return Err(Error::InvalidMerge(String::from("the account was already merged"))),
+        }
+        let sessions = db.get_sessions_by_user_id(duplicate).await?;
+        let moved = sessions.len();
+        for session in sessions {
+            Sessions::delete(db, session.id).await?;
+            db.create_session(Session { user_id: primary, ..session }).await?;
+        }
+        db.set_user_status(duplicate, Status::Deactivated, Some(format!("merged into {}", primary.to_hex()))).await?;
+        let event = SecurityEvent::new(SecurityEventKind::AccountMerged { into: primary, sessions: moved, merged_by }, Some(duplicate));
+        if let Err(err) = events.emit(event).await {
+            tracing::error!(error = %err, "could not emit the security event");
+        }
+        Ok(moved)
+    }
+
+    /// The account `user` was merged into, or `user` itself when it was never merged.
+    pub async fn follow<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB, user: User) -> Result<User, Error>
+    where
+        Error: From<DB::Error>
+    {
+        match merged_into(&user) {
+            Some(primary) => Ok(db.get_user_by_id(primary).await?.ok_or(DatabaseError::UserNotFound)?),
+            None => Ok(user),
+        }
+    }
+
+    /// A page of at most `limit` users matching `filter`, for support tooling. Password hashes are left out.
+    ///
+    /// Pages may be short, so the search is over only once `next` is `None`.
+    #[instrument(skip(db), err)]
+    pub async fn search<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB, filter: UserFilter, after: Option<Id>, limit: usize) -> Result<Page<User, Id>, Error>
+    where
+        Error: From<DB::Error>
+    {
+        let mut page = db.search_users(filter, after, limit.max(1)).await?;
+        for user in &mut page.items {
+            user.login.set_hash(String::new());
+        }
+        Ok(page)
+    }
+}
+
+
+impl Accounts {
+    #[instrument(skip(db, events, reason), fields(user_id = %id.to_hex()), err)]
+    async fn change_status<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, SessionsTable: SessionsTable<DB::Client, Item = Session>>, E: SecurityEvents>(db: &DB, events: &E, id: Id, status: Status, reason: Option<String>, changed_by: Option<Id>) -> Result<(), Error>
+    where
+        Error: From<DB::Error>,
+        E::Error: Display
+    {
+        let user = db.get_user_by_id(id).await?.ok_or(DatabaseError::UserNotFound)?;
+        if !can_change(user.status, status) {
+            return Err(Error::InvalidStatusTransition(user.status, status));
+        }
+        db.set_user_status(id, status, reason.clone()).await?;
+        if !status.can_login() {
+            Sessions::delete_all(db, id, None).await?;
+        }
+        let event = SecurityEvent::new(SecurityEventKind::StatusChanged { status, reason, changed_by }, Some(id));
+        if let Err(err) = events.emit(event).await {
+            tracing::error!(error = %err, "could not emit the security event");
+        }
+        Ok(())
+    }
+}
+
+
+fn merged_into(user: &User) -> Option<Id> {
+    user.metadata.service.get(MERGED_INTO).and_then(Value::as_str).and_then(|id| Id::try_from(id.to_string()).ok())
+}
+
+
+/// Whether an admin can move an account from `from` to `to`.
+///
+/// Verification is what activates a pending account, an admin can only suspend or deactivate it.
+/// Reactivating an account pending erasure cancels the erasure, which is requested through `Erasure` only.
+fn can_change(from: Status, to: Status) -> bool {
+    match to {
+        Status::PendingVerification | Status::PendingErasure => false,
+        Status::Active => matches!(from, Status::Suspended | Status::Deactivated | Status::PendingErasure),
+        Status::Suspended => matches!(from, Status::PendingVerification | Status::Active),
+        Status::Deactivated => !matches!(from, Status::Deactivated | Status::PendingErasure),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{id, Harness, UserFixture};
+    use crate::adaptors::outputs::security_events::Bus;
+    use crate::ports::outputs::database::MockDatabase;
+    use crate::types::{GrantRecord, Login, Verification};
+    use chrono::Utc;
+
+    type Mock = MockDatabase<User, Session, Verification<Id>, GrantRecord, DatabaseError>;
+
+    fn user(status: Status) -> User {
+        UserFixture::new().id(id(1)).status(status).build()
+    }
+
+    fn session(n: u8) -> Session {
+        Session { id: id(n), user_id: id(1), refresh_token_id: id(n + 10), previous_refresh_token_id: None, device: Default::default(), created_at: Utc::now(), updated_at: Utc::now(), last_active_at: Utc::now() }
+    }
+
+    #[tokio::test]
+    async fn test_suspension_deletes_sessions_and_is_reported() {
+        let db = Mock::default();
+        db.users_table().get_user_by_id_returns(Ok(Some(user(Status::Active)))).set_user_status_returns(Ok(()));
+        db.sessions_table()
+            .get_sessions_by_user_id_returns(Ok(vec![session(2), session(3)]))
+            .delete_session_returns(Ok(()))
+            .delete_session_returns(Ok(()));
+        let events = Bus::new(1);
+        let mut subscriber = events.subscribe();
+        Accounts::suspend(&db, &events, id(1), "chargeback".into(), Some(id(9))).await.unwrap();
+        let calls = db.users_table().calls();
+        assert_eq!(calls[1].args, vec![format!("{:?}", id(1)), format!("{:?}", Status::Suspended), format!("{:?}", Some("chargeback"))]);
+        let deleted = db.sessions_table().calls().into_iter().filter(|call| call.method == "delete_session").count();
+        assert_eq!(deleted, 2);
+        let event = subscriber.recv().await.unwrap();
+        assert_eq!(event.kind, SecurityEventKind::StatusChanged { status: Status::Suspended, reason: Some("chargeback".into()), changed_by: Some(id(9)) });
+    }
+
+    #[tokio::test]
+    async fn test_only_suspended_or_deactivated_accounts_are_reactivated() {
+        let db = Mock::default();
+        db.users_table().get_user_by_id_returns(Ok(Some(user(Status::PendingVerification))));
+        let result = Accounts::reactivate(&db, &Bus::new(1), id(1), None, None).await;
+        assert_eq!(result, Err(Error::InvalidStatusTransition(Status::PendingVerification, Status::Active)));
+        db.users_table().get_user_by_id_returns(Ok(Some(user(Status::Suspended)))).set_user_status_returns(Ok(()));
+        assert_eq!(Accounts::reactivate(&db, &Bus::new(1), id(1), None, None).await, Ok(()));
+        assert!(db.sessions_table().calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_merged_accounts_hand_over_their_sessions() {
+        let harness = Harness::new();
+        let primary = harness.create(UserFixture::new()).await;
+        let duplicate = harness.create(UserFixture::new()).await;
+        let session = Session { user_id: duplicate.id, ..session(2) };
+        harness.db.create_session(session.clone()).await.unwrap();
+        let events = Bus::new(1);
+        let mut subscriber = events.subscribe();
+        assert_eq!(Accounts::merge(&harness.db, &events, primary.id, duplicate.id, Some(id(9))).await, Ok(1));
+        assert_eq!(harness.db.get_sessions_by_user_id(primary.id).await.unwrap(), vec![Session { user_id: primary.id, ..session }]);
+        let tombstone = harness.db.get_user_by_id(duplicate.id).await.unwrap().unwrap();
+        assert_eq!(tombstone.status, Status::Deactivated);
+        assert_eq!(Accounts::follow(&harness.db, tombstone).await.unwrap().id, primary.id);
+        let event = subscriber.recv().await.unwrap();
+        assert_eq!((event.kind, event.user_id), (SecurityEventKind::AccountMerged { into: primary.id, sessions: 1, merged_by: Some(id(9)) }, Some(duplicate.id)));
+        let again = Accounts::merge(&harness.db, &events, primary.id, duplicate.id, None).await;
+        assert_eq!(again, Err(Error::InvalidMerge(String::from("the account was already merged"))));
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_merges_are_finished_by_merging_again() {
+        let harness = Harness::new();
+        let primary = harness.create(UserFixture::new()).await;
+        let duplicate = harness.create(UserFixture::new()).await;
+        let other = harness.create(UserFixture::new()).await;
+        let session = Session { user_id: duplicate.id, ..session(2) };
+        harness.db.create_session(session.clone()).await.unwrap();
+        // the merge stopped right after marking the duplicate.
+        UserMetadata::update(&harness.db, duplicate.id, Namespace::Service, Map::from_iter([(String::from(MERGED_INTO), Value::String(primary.id.to_hex()))])).await.unwrap();
+        let elsewhere = Accounts::merge(&harness.db, &Bus::new(1), other.id, duplicate.id, None).await;
+        assert_eq!(elsewhere, Err(Error::InvalidMerge(String::from("the account was already merged"))));
+        assert_eq!(Accounts::merge(&harness.db, &Bus::new(1), primary.id, duplicate.id, None).await, Ok(1));
+        assert_eq!(harness.db.get_sessions_by_user_id(primary.id).await.unwrap(), vec![Session { user_id: primary.id, ..session }]);
+        assert_eq!(harness.db.get_user_by_id(duplicate.id).await.unwrap().unwrap().status, Status::Deactivated);
+    }
+
+    #[tokio::test]
+    async fn test_search_results_have_no_password_hashes() {
+        let harness = Harness::new();
+        let jane = harness.create(UserFixture::new().username("jane").password("secret")).await;
+        harness.create(UserFixture::new()).await;
+        let filter = UserFilter { username: Some(String::from("jane")), ..Default::default() };
+        let page = Accounts::search(&harness.db, filter, None, 0).await.unwrap();
+        assert_eq!(page.items, vec![User { login: Login::Password(String::new()), ..jane }]);
+    }
+}
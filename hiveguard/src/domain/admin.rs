@@ -0,0 +1,241 @@
+use crate::ports::outputs::database::{Database, tables::{UsersTable, SessionsTable}};
+use crate::types::{Error, User, Id, Session, Login, Status, DatabaseError, Namespace};
+#[cfg(feature = "email")]
+use crate::ports::outputs::{database::tables::VerificationsTable, verify::Verify};
+#[cfg(feature = "email")]
+use crate::types::{Email, ConversionError};
+use crate::ports::outputs::security_events::SecurityEvents;
+use super::metadata::UserMetadata;
+use super::password::{Password, PasswordService};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+use super::sessions::Sessions;
+use serde_json::{Map, Value};
+use tracing::instrument;
+use std::fmt::Display;
+use chrono::Utc;
+
+
+/// the `service` metadata field holding the role of a user.
+pub const ROLE: &str = "role";
+/// the `service` metadata field of users who cannot log in with their password until they reset it. see [`Admin::force_password_reset`].
+pub const PASSWORD_RESET_REQUIRED: &str = "password_reset_required";
+
+
+/// Break-glass operations of `hiveguard admin`, run against the database directly rather than through the API.
+///
+/// There are no roles in hiveguard itself: an admin is a user whose `service` metadata has `"role": "admin"`,
+/// which the services built on hiveguard decide what to make of.
+pub struct Admin;
+
+
+impl Admin {
+    /// Creates an active admin that logs in with `password`, eg the very first one of a deployment.
+    #[instrument(skip(db, passwords, user, password), fields(user_id = %user.id.to_hex()), err)]
+    pub async fn create<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>, P: Password + Send + Sync + 'static>(db: &DB, passwords: &PasswordService<P>, mut user: User, password: String) -> Result<User, Error>
+    where
+        Error: From<DB::Error>
+    {
+        let now = Utc::now();
+        user.login = Login::Password(passwords.hash_password(password).await?);
+        user.status = Status::Active;
+        user.metadata.service = Map::from_iter([(String::from(ROLE), Value::String(String::from("admin")))]);
+        user.created_at = now;
+        user.updated_at = now;
+        db.create_user(user.clone()).await?;
+        Ok(user)
+    }
+
+    /// Sets a new password for the user and logs them out everywhere.
+    #[instrument(skip(db, passwords, events, password), fields(user_id = %id.to_hex()), err)]
+    pub async fn reset_password<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, SessionsTable: SessionsTable<DB::Client, Item = Session>>, P: Password + Send + Sync + 'static, E: SecurityEvents>(db: &DB, passwords: &PasswordService<P>, events: &E, id: Id, password: String) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>,
+        E::Error: Display
+    {
+        let user = db.get_user_by_id(id).await?.ok_or(DatabaseError::UserNotFound)?;
+        db.set_user_password(id, passwords.hash_password(password).await?).await?;
+        Self::clear_password_reset(db, &user).await?;
+        Sessions::revoke_all(db, events, id, None).await
+    }
+
+    /// Makes the user reset their password before logging in with it again, eg when it is known to have leaked.
+    ///
+    /// The user is logged out everywhere and sent a code to reset their password with, through `verifier`,
+    /// along with a magic link to `reset_uri` when there is one. Until they do with [`Authentication::reset_password`](super::Authentication::reset_password),
+    /// logins with their password are refused with `Error::PasswordResetRequired`. Returns the number of sessions revoked.
+    #[cfg(feature = "email")]
+    #[instrument(skip(db, events, verifier, channel), fields(user_id = %id.to_hex()), err)]
+    pub async fn force_password_reset<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, SessionsTable: SessionsTable<DB::Client, Item = Session>, VerificationsTable: VerificationsTable<DB::Client, Item = V::VerificationCode>>, E: SecurityEvents, V: Verify<Email>>(db: &DB, events: &E, verifier: &V, channel: V::Channel, id: Id, reset_uri: Option<&str>) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>,
+        Error: From<V::Error>,
+        E::Error: Display
+    {
+        let user = db.get_user_by_id(id).await?.ok_or(DatabaseError::UserNotFound)?;
+        let email = user.email.ok_or(ConversionError::MissingField("email"))?;
+        user.login.password()?;
+        UserMetadata::update(db, id, Namespace::Service, Map::from_iter([(String::from(PASSWORD_RESET_REQUIRED), Value::Bool(true))])).await?;
+        let revoked = Sessions::revoke_all(db, events, id, None).await?;
+        verifier.initiate(&email, channel, reset_uri, db).await?;
+        Ok(revoked)
+    }
+
+    /// Whether the user has to reset their password before logging in with it. see [`Admin::force_password_reset`].
+    pub fn must_reset_password(user: &User) -> bool {
+        user.metadata.service.get(PASSWORD_RESET_REQUIRED) == Some(&Value::Bool(true))
+    }
+
+    pub(super) async fn clear_password_reset<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB, user: &User) -> Result<(), Error>
+    where
+        Error: From<DB::Error>
+    {
+        if user.metadata.service.contains_key(PASSWORD_RESET_REQUIRED) {
+            UserMetadata::update(db, user.id, Namespace::Service, Map::from_iter([(String::from(PASSWORD_RESET_REQUIRED), Value::Null)])).await?;
+        }
+        Ok(())
+    }
+
+    /// Creates the users of fixtures written by `Export::fixtures`, one JSON user per line.
+    ///
+    /// Users that already exist are left as they are, so loading the same fixtures twice is harmless.
+    /// Returns the number of users created.
+    #[instrument(skip_all, err)]
+    pub async fn load<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>, R: AsyncBufRead + Unpin>(db: &DB, reader: R) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>
+    {
+        let mut lines = reader.lines();
+        let mut created = 0;
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let user = serde_json::from_str::<User>(&line).map_err(std::io::Error::from)?;
+            let id = user.id;
+            match db.create_user(user).await.map_err(Error::from) {
+                Ok(()) => created += 1,
+                Err(Error::DatabaseError(DatabaseError::AlreadyExists)) => tracing::warn!(user_id = %id.to_hex(), "the user already exists"),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(created)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptors::outputs::databases::memory::Memory;
+    use crate::adaptors::outputs::security_events::Bus;
+    use crate::types::Device;
+    use crate::testing::{id, Plain, UserFixture};
+    use super::super::export::Export;
+    use std::time::Duration;
+
+    fn user(n: u8) -> User {
+        let user = UserFixture::new().id(id(n)).username(&format!("user{}", n)).status(Status::PendingVerification);
+        #[cfg(feature = "email")]
+        let user = user.email(&format!("user{}@example.com", n));
+        user.build()
+    }
+
+    #[tokio::test]
+    async fn test_admins_are_active_and_have_the_admin_role() {
+        let db = Memory::new(Duration::from_secs(1));
+        let passwords = PasswordService::new(Plain, 1);
+        Admin::create(&db, &passwords, user(1), String::from("secret")).await.unwrap();
+        let admin = db.get_user_by_id(user(1).id).await.unwrap().unwrap();
+        assert_eq!((admin.status, admin.login), (Status::Active, Login::Password(String::from("secret"))));
+        assert_eq!(admin.metadata.service[ROLE], "admin");
+        let again = Admin::create(&db, &passwords, user(1), String::from("secret")).await;
+        assert_eq!(again, Err(Error::DatabaseError(DatabaseError::AlreadyExists)));
+    }
+
+    #[tokio::test]
+    async fn test_password_resets_log_the_user_out() {
+        let db = Memory::new(Duration::from_secs(1));
+        db.create_user(user(1)).await.unwrap();
+        let session = Session { id: Id::default(), user_id: user(1).id, refresh_token_id: Id::default(), previous_refresh_token_id: None, device: Device::default(), created_at: Utc::now(), updated_at: Utc::now(), last_active_at: Utc::now() };
+        db.create_session(session).await.unwrap();
+        let passwords = PasswordService::new(Plain, 1);
+        UserMetadata::update(&db, user(1).id, Namespace::Service, Map::from_iter([(String::from(PASSWORD_RESET_REQUIRED), Value::Bool(true))])).await.unwrap();
+        let revoked = Admin::reset_password(&db, &passwords, &Bus::new(1), user(1).id, String::from("new")).await;
+        assert_eq!(revoked, Ok(1));
+        let reset = db.get_user_by_id(user(1).id).await.unwrap().unwrap();
+        assert_eq!(reset.login, Login::Password(String::from("new")));
+        assert!(!Admin::must_reset_password(&reset));
+        assert_eq!(Admin::reset_password(&db, &passwords, &Bus::new(1), user(2).id, String::from("new")).await, Err(Error::DatabaseError(DatabaseError::UserNotFound)));
+    }
+
+    #[cfg(feature = "email")]
+    #[tokio::test]
+    async fn test_forced_password_resets_send_a_code_and_log_the_user_out() {
+        use crate::ports::outputs::database::{MockDatabase, tables::VerificationsTable};
+        use crate::ports::outputs::verify::Code;
+        use crate::types::{Email, GrantRecord};
+        use std::sync::Mutex;
+
+        #[derive(Debug)]
+        struct Sent([u8; 6]);
+
+        impl Code<Email> for Sent {
+            type Error = Error;
+
+            fn new(_: Email, _: Option<i64>) -> Self {
+                Self(Self::generate())
+            }
+
+            fn code(&self) -> &[u8; 6] {
+                &self.0
+            }
+
+            fn magic_link(base_uri: &str) -> String {
+                base_uri.to_string()
+            }
+        }
+
+        #[derive(Default)]
+        struct Provider(Mutex<Vec<(Email, Option<String>)>>);
+
+        impl Verify<Email> for Provider {
+            type VerificationCode = Sent;
+            type Error = Error;
+            type Channel = ();
+
+            async fn initiate<DB: Database<VerificationsTable: VerificationsTable<DB::Client, Item = Self::VerificationCode>>>(&self, contact: &Email, _: (), magic_link_base_uri: Option<&str>, _: &DB) -> Result<Sent, Error> {
+                self.0.lock().unwrap().push((contact.clone(), magic_link_base_uri.map(String::from)));
+                Ok(Sent::new(contact.clone(), None))
+            }
+
+            async fn verify<DB: Database<VerificationsTable: VerificationsTable<DB::Client, Item = Self::VerificationCode>>>(&self, _: &Email, _: &str, _: &DB) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+
+        let db = MockDatabase::<User, Session, Sent, GrantRecord, DatabaseError>::default();
+        let session = Session { id: Id::default(), user_id: user(1).id, refresh_token_id: Id::default(), previous_refresh_token_id: None, device: Device::default(), created_at: Utc::now(), updated_at: Utc::now(), last_active_at: Utc::now() };
+        db.users_table().get_user_by_id_returns(Ok(Some(user(1)))).get_user_by_id_returns(Ok(Some(user(1)))).set_user_metadata_returns(Ok(()));
+        db.sessions_table().get_sessions_by_user_id_returns(Ok(vec![session])).delete_session_returns(Ok(()));
+        let provider = Provider::default();
+        let revoked = Admin::force_password_reset(&db, &Bus::new(1), &provider, (), user(1).id, Some("https://example.com/reset")).await;
+        assert_eq!(revoked, Ok(1));
+        let calls = db.users_table().calls();
+        assert_eq!(calls[2].method, "set_user_metadata");
+        assert!(calls[2].args[2].contains(PASSWORD_RESET_REQUIRED));
+        assert_eq!(*provider.0.lock().unwrap(), vec![(user(1).email.unwrap(), Some(String::from("https://example.com/reset")))]);
+    }
+
+    #[tokio::test]
+    async fn test_fixtures_are_loaded_back() {
+        let db = Memory::new(Duration::from_secs(1));
+        db.create_user(user(1)).await.unwrap();
+        db.create_user(user(2)).await.unwrap();
+        let mut fixtures = Vec::new();
+        assert_eq!(Export::fixtures(&db, 1, &mut fixtures).await, Ok(2));
+        let copy = Memory::new(Duration::from_secs(1));
+        copy.create_user(user(2)).await.unwrap();
+        assert_eq!(Admin::load(&copy, fixtures.as_slice()).await, Ok(1));
+        assert_eq!(copy.get_user_by_id(user(1).id).await.unwrap(), db.get_user_by_id(user(1).id).await.unwrap());
+    }
+}
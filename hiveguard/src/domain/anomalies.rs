@@ -0,0 +1,162 @@
+use crate::ports::outputs::database::{Database, tables::SessionsTable};
+use crate::types::{Error, Id, Session, Device, Anomaly, SecurityEvent, SecurityEventKind};
+use crate::ports::outputs::security_events::SecurityEvents;
+use crate::config::{AnomaliesConfig, AnomalyAction};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::fmt::Display;
+
+
+/// Compares logins with the sessions the user started recently. see [`AnomaliesConfig`].
+///
+/// Anomalies are reported as `anomalous_login` security events. Logins are only compared once located,
+/// see `Geolocation::locate`, and the first login of a user has nothing to be compared to.
+#[derive(Debug, Clone)]
+pub struct AnomalyDetection {
+    enabled: bool,
+    action: AnomalyAction,
+    max_speed_kmh: f64,
+    min_distance_km: f64,
+    new_country: bool,
+    history: TimeDelta,
+}
+
+
+impl AnomalyDetection {
+    /// Checks a login of `user_id` from `device`, reporting its anomalies to `events`.
+    /// Anomalous logins are refused with `Error::StepUpRequired` when they are challenged.
+    pub async fn check<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>, E: SecurityEvents>(&self, db: &DB, events: &E, user_id: Id, device: &Device) -> Result<Vec<Anomaly>, Error>
+    where
+        Error: From<DB::Error>,
+        E::Error: Display
+    {
+        if !self.enabled || device.location.is_none() {
+            return Ok(Vec::new());
+        }
+        let sessions = db.get_sessions_by_user_id(user_id).await?;
+        let anomalies = self.detect(&sessions, device, Utc::now());
+        if anomalies.is_empty() {
+            return Ok(anomalies);
+        }
+        let challenged = self.action == AnomalyAction::Challenge;
+        tracing::warn!(user_id = %user_id.to_hex(), ?anomalies, challenged, "anomalous login");
+        let event = SecurityEvent::new(SecurityEventKind::AnomalousLogin { anomalies: anomalies.clone(), challenged }, Some(user_id)).located(device.location.clone());
+        if let Err(err) = events.emit(event).await {
+            tracing::error!(error = %err, "could not emit the security event");
+        }
+        if challenged {
+            return Err(Error::StepUpRequired);
+        }
+        Ok(anomalies)
+    }
+
+    /// How a login from `device` at `now` differs from the located `sessions` started within the history.
+    pub fn detect(&self, sessions: &[Session], device: &Device, now: DateTime<Utc>) -> Vec<Anomaly> {
+        let Some(location) = &device.location else {
+            return Vec::new();
+        };
+        let history = sessions.iter()
+            .filter(|session| session.created_at >= now - self.history)
+            .filter_map(|session| Some((session.last_active_at, session.device.location.as_ref()?)))
+            .collect::<Vec<_>>();
+        let mut anomalies = Vec::new();
+        if let Some(country) = &location.country {
+            let known = history.iter().any(|(_, from)| from.country.as_ref() == Some(country));
+            if self.new_country && !history.is_empty() && !known {
+                anomalies.push(Anomaly::NewCountry { country: country.clone() });
+            }
+        }
+        if let Some((at, from)) = history.iter().max_by_key(|(at, _)| *at) {
+            let distance = from.distance_km(location).unwrap_or_default();
+            // a minute at least, for logins from two places at once.
+            let hours = ((now - *at).num_seconds().max(60) as f64) / 3600.0;
+            let speed = distance / hours;
+            if distance >= self.min_distance_km && speed > self.max_speed_kmh {
+                anomalies.push(Anomaly::ImpossibleTravel { from: (*from).clone(), speed_kmh: speed.round() as u32 });
+            }
+        }
+        anomalies
+    }
+}
+
+
+impl From<&AnomaliesConfig> for AnomalyDetection {
+    fn from(config: &AnomaliesConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            action: config.action,
+            max_speed_kmh: config.max_speed_kmh.into(),
+            min_distance_km: config.min_distance_km.into(),
+            new_country: config.new_country,
+            history: TimeDelta::days(config.history_days.into()),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptors::outputs::security_events::Bus;
+    use crate::types::Location;
+    use crate::testing::{Harness, UserFixture};
+    use crate::domain::Tokenizer;
+
+    fn at(country: &str, latitude: f64, longitude: f64) -> Location {
+        Location { country: Some(country.into()), city: None, latitude: Some(latitude), longitude: Some(longitude) }
+    }
+
+    fn nairobi() -> Location {
+        at("KE", -1.2864, 36.8172)
+    }
+
+    fn detection(config: serde_json::Value) -> AnomalyDetection {
+        AnomalyDetection::from(&serde_json::from_value::<AnomaliesConfig>(config).unwrap())
+    }
+
+    fn located(location: Location) -> Device {
+        Device { location: Some(location), ..Device::default() }
+    }
+
+    #[test]
+    fn test_travelling_too_fast_to_a_new_country_is_anomalous() {
+        let now = Utc::now();
+        let session = |location: Location, active: DateTime<Utc>| Session {
+            id: Id::default(),
+            user_id: Id::default(),
+            refresh_token_id: Id::default(),
+            previous_refresh_token_id: None,
+            device: located(location),
+            created_at: active - TimeDelta::hours(1),
+            updated_at: active,
+            last_active_at: active,
+        };
+        let detection = detection(serde_json::json!({"enabled": true}));
+        let sessions = [session(nairobi(), now - TimeDelta::hours(1))];
+        let london = at("GB", 51.5072, -0.1276);
+        let anomalies = detection.detect(&sessions, &located(london.clone()), now);
+        assert_eq!(anomalies.len(), 2);
+        assert_eq!(anomalies[0], Anomaly::NewCountry { country: "GB".into() });
+        assert!(matches!(&anomalies[1], Anomaly::ImpossibleTravel { from, speed_kmh } if *from == nairobi() && *speed_kmh > 6000));
+        let mombasa = at("KE", -4.0435, 39.6682);
+        assert_eq!(detection.detect(&sessions, &located(mombasa), now), vec![]);
+        let a_day_later = now + TimeDelta::hours(23);
+        assert_eq!(detection.detect(&sessions, &located(london), a_day_later), vec![Anomaly::NewCountry { country: "GB".into() }]);
+        assert_eq!(detection.detect(&[], &located(nairobi()), now), vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_challenged_logins_are_refused_and_reported() {
+        let harness = Harness::new();
+        let user = harness.create(UserFixture::new()).await;
+        harness.tokens.generate_token(&harness.db, user.id, located(nairobi()), None).await.unwrap();
+        let events = Bus::new(1);
+        let mut subscriber = events.subscribe();
+        let challenge = detection(serde_json::json!({"enabled": true, "action": "challenge"}));
+        let refused = challenge.check(&harness.db, &events, user.id, &located(at("GB", 51.5072, -0.1276))).await;
+        assert_eq!(refused, Err(Error::StepUpRequired));
+        let event = subscriber.recv().await.unwrap();
+        assert!(matches!(event.kind, SecurityEventKind::AnomalousLogin { challenged: true, .. }));
+        assert_eq!(event.location.and_then(|location| location.country).as_deref(), Some("GB"));
+        assert_eq!(challenge.check(&harness.db, &events, user.id, &located(nairobi())).await, Ok(vec![]));
+    }
+}
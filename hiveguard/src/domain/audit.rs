@@ -0,0 +1,178 @@
+use crate::types::{Error, Id, AuditEntry, AuditFormat, Organisation, OrgRole, ConversionError, FieldError, DatabaseError};
+use crate::ports::outputs::organisations::OrganisationStore;
+use crate::ports::outputs::audit_log::AuditLog;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use chrono::{DateTime, TimeDelta, Utc};
+use super::Organisations;
+use crate::config::AuditConfig;
+use tracing::instrument;
+use std::fmt::Display;
+
+
+/// the columns of CSV exports, in order.
+const CSV_HEADER: &str = "id,org_id,actor_id,action,target,occurred_at\r\n";
+
+
+/// The audit logs of organisations, which their owners export and keep for as long as they choose. see [`AuditConfig`].
+///
+/// Entries older than the retention of their organisation are left out of exports, and deleted by `Maintenance::prune_audit_log`.
+/// Losing an entry is logged rather than failing the change it records.
+#[derive(Debug, Clone)]
+pub struct OrgAudit {
+    retention_days: u32,
+    max_retention_days: u32,
+}
+
+
+impl OrgAudit {
+    /// Records `entry` in the audit log of its organisation.
+    pub async fn record<L: AuditLog>(log: &L, entry: AuditEntry)
+    where
+        L::Error: Display
+    {
+        if let Err(err) = log.record(entry).await {
+            tracing::error!(error = %err, "could not record the audit entry");
+        }
+    }
+
+    /// Writes the entries of the organisation within its retention to `writer` in `format`, oldest first.
+    /// Only its owners, and the owners of the organisations above it, may export it.
+    ///
+    /// Returns the number of entries written.
+    #[instrument(skip(self, organisations, store, log, writer), err)]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn export<S: OrganisationStore, L: AuditLog, W: AsyncWrite + Unpin>(&self, organisations: &Organisations, store: &S, log: &L, org_id: Id, user_id: Id, format: AuditFormat, writer: &mut W) -> Result<usize, Error>
+    where
+        Error: From<S::Error>,
+        Error: From<L::Error>
+    {
+        let organisation = Self::owned(organisations, store, org_id, user_id).await?;
+        let entries = log.entries(org_id, self.cutoff(&organisation, Utc::now())).await?;
+        let mut lines = Vec::new();
+        match format {
+            AuditFormat::Csv => {
+                lines.extend(CSV_HEADER.as_bytes());
+                for entry in &entries {
+                    lines.extend(csv_line(entry).as_bytes());
+                }
+            },
+            AuditFormat::Ndjson => for entry in &entries {
+                serde_json::to_writer(&mut lines, entry).map_err(std::io::Error::from)?;
+                lines.push(b'\n');
+            },
+        }
+        writer.write_all(&lines).await?;
+        writer.flush().await?;
+        Ok(entries.len())
+    }
+
+    /// Keeps the entries of the organisation for `days`, or for `audit.retention_days` when it is `None`. Owners only.
+    #[instrument(skip(self, organisations, store, log), err)]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn set_retention<S: OrganisationStore, L: AuditLog>(&self, organisations: &Organisations, store: &S, log: &L, org_id: Id, user_id: Id, days: Option<u32>) -> Result<Organisation, Error>
+    where
+        Error: From<S::Error>,
+        L::Error: Display
+    {
+        if days.is_some_and(|days| days == 0 || days > self.max_retention_days) {
+            let detail = format!("must be between 1 and {}", self.max_retention_days);
+            return Err(ConversionError::InvalidFields(vec![FieldError { field: String::from("retention_days"), detail }]).into());
+        }
+        let organisation = Self::owned(organisations, store, org_id, user_id).await?;
+        let changed = Organisation { audit_retention_days: days, updated_at: Utc::now(), ..organisation };
+        store.update(changed.clone()).await?;
+        let retention = days.map_or_else(|| String::from("default"), |days| days.to_string());
+        Self::record(log, AuditEntry::new(org_id, Some(user_id), "audit.retention_changed", Some(retention))).await;
+        Ok(changed)
+    }
+
+    /// How long the entries of `organisation` are kept for.
+    pub fn retention(&self, organisation: &Organisation) -> TimeDelta {
+        TimeDelta::days(organisation.audit_retention_days.unwrap_or(self.retention_days).min(self.max_retention_days).into())
+    }
+
+    /// The time before which the entries of `organisation` are past its retention at `now`.
+    pub fn cutoff(&self, organisation: &Organisation, now: DateTime<Utc>) -> DateTime<Utc> {
+        now - self.retention(organisation)
+    }
+
+    /// The organisation, if the user is one of its owners.
+    async fn owned<S: OrganisationStore>(organisations: &Organisations, store: &S, org_id: Id, user_id: Id) -> Result<Organisation, Error>
+    where
+        Error: From<S::Error>
+    {
+        if organisations.role(store, org_id, user_id).await? != Some(OrgRole::Owner) {
+            return Err(Error::OwnerRequired);
+        }
+        Ok(store.get(org_id).await?.ok_or(DatabaseError::OrganisationNotFound)?)
+    }
+}
+
+
+impl From<&AuditConfig> for OrgAudit {
+    fn from(config: &AuditConfig) -> Self {
+        Self { retention_days: config.retention_days, max_retention_days: config.max_retention_days }
+    }
+}
+
+
+/// The CSV line of `entry`, as RFC 4180 has it.
+fn csv_line(entry: &AuditEntry) -> String {
+    let fields = [
+        entry.id.to_hex(),
+        entry.org_id.to_hex(),
+        entry.actor_id.map(Id::to_hex).unwrap_or_default(),
+        csv_field(&entry.action),
+        entry.target.as_deref().map(csv_field).unwrap_or_default(),
+        entry.occurred_at.to_rfc3339(),
+    ];
+    format!("{}\r\n", fields.join(","))
+}
+
+
+/// `value` quoted when it has to be. Values a spreadsheet would take for a formula are prefixed with `'`, so that opening
+/// an export never runs one.
+fn csv_field(value: &str) -> String {
+    let value = match value.starts_with(['=', '+', '-', '@']) {
+        true => format!("'{}", value),
+        false => String::from(value),
+    };
+    match value.contains([',', '"', '\r', '\n']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptors::outputs::organisations::memory::MemoryOrganisations;
+    use crate::adaptors::outputs::audit_log::memory::MemoryAuditLog;
+
+    #[tokio::test]
+    async fn test_owners_export_the_audit_log_within_its_retention() {
+        let (organisations, store, log) = (Organisations::default(), MemoryOrganisations::new(), MemoryAuditLog::new());
+        let audit = OrgAudit::from(&AuditConfig { retention_days: 30, max_retention_days: 90 });
+        let holding = organisations.create(&store, String::from("Holding"), None).await.unwrap();
+        let (owner, admin) = (Id::new(), Id::new());
+        organisations.add_member(&store, holding.id, owner, OrgRole::Owner).await.unwrap();
+        organisations.add_member(&store, holding.id, admin, OrgRole::Admin).await.unwrap();
+        let old = AuditEntry { occurred_at: Utc::now() - TimeDelta::days(31), ..AuditEntry::new(holding.id, Some(owner), "membership.added", None) };
+        OrgAudit::record(&log, old).await;
+        OrgAudit::record(&log, AuditEntry::new(holding.id, Some(owner), "organisation.renamed", Some(String::from("=HYPERLINK(\"x\"), Inc")))).await;
+        let mut csv = Vec::new();
+        assert_eq!(audit.export(&organisations, &store, &log, holding.id, owner, AuditFormat::Csv, &mut csv).await, Ok(1));
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.starts_with(CSV_HEADER));
+        assert!(csv.contains(",organisation.renamed,\"'=HYPERLINK(\"\"x\"\"), Inc\","));
+        let mut ndjson = Vec::new();
+        assert_eq!(audit.export(&organisations, &store, &log, holding.id, admin, AuditFormat::Ndjson, &mut ndjson).await, Err(Error::OwnerRequired));
+        assert!(matches!(audit.set_retention(&organisations, &store, &log, holding.id, owner, Some(91)).await, Err(Error::ConversionError(ConversionError::InvalidFields(_)))));
+        let changed = audit.set_retention(&organisations, &store, &log, holding.id, owner, Some(60)).await.unwrap();
+        assert_eq!(audit.retention(&changed), TimeDelta::days(60));
+        assert_eq!(audit.export(&organisations, &store, &log, holding.id, owner, AuditFormat::Ndjson, &mut ndjson).await, Ok(3));
+        let actions = String::from_utf8(ndjson).unwrap().lines().map(|line| serde_json::from_str::<AuditEntry>(line).unwrap().action).collect::<Vec<_>>();
+        assert_eq!(actions, vec!["membership.added", "organisation.renamed", "audit.retention_changed"]);
+    }
+}
@@ -1,39 +1,389 @@
 use crate::ports::outputs::database::{Database, tables::{UsersTable, VerificationsTable, SessionsTable}};
-use crate::types::{Error, User, TokenBundle, Email, DatabaseError, Verification, Id, Session};
-use super::{Password, Tokenizer};
+use crate::types::{Error, User, TokenBundle, Email, DatabaseError, Verification, Id, Session, Device, SecurityEvent, SecurityEventKind, LoginFailure, DomainEventKind, Document, Consent, LoginAttempt, LoginOutcome};
+use crate::ports::outputs::security_events::SecurityEvents;
+use crate::ports::outputs::events::EventPublisher;
+use crate::ports::outputs::login_history::LoginHistory;
+use crate::ports::outputs::verify::Verify;
+use super::sessions::Sessions;
+use super::{Password, PasswordService, Tokenizer, UsernameRules, SignupRules, Profiles, AnomalyDetection, UnverifiedAccounts, Admin, LoginAttempts, Events};
+use crate::config::ConsentConfig;
+use tracing::{instrument, Span};
+use std::fmt::Display;
+use chrono::Utc;
 
 
 pub struct Authentication;
 
 
 impl Authentication {
-    pub async fn signup<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, VerificationsTable: VerificationsTable<DB::Client, Item = Verification<Id>>, SessionsTable: SessionsTable<DB::Client, Item = Session>>, T: Tokenizer, Hasher: Password>(db: &DB, mut user: User, tokenizer: &T, hasher: Hasher) -> Result<TokenBundle, Error>
+    /// The user must be allowed to sign up by `signup`, and the username must follow `usernames` and not be held by a user who changed it,
+    /// all checked before the password is hashed. A user signing up without a username is given a placeholder, until they choose theirs.
+    /// The new user is logged in, with a session started from `device`, and `user.created` is published to `publisher`.
+    /// Signing up accepts the current versions of the documents of `consent`.
+    /// The new user is held to `unverified` like any login: it may get limited tokens, or none until it verifies a contact.
+    #[instrument(skip_all, fields(user_id = %user.id.to_hex()), err)]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn signup<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, VerificationsTable: VerificationsTable<DB::Client, Item = Verification<Id>>, SessionsTable: SessionsTable<DB::Client, Item = Session>>, T: Tokenizer, P: Password + Send + Sync + 'static, B: EventPublisher>(db: &DB, mut user: User, tokenizer: &T, passwords: &PasswordService<P>, usernames: &UsernameRules, signup: &SignupRules, consent: &ConsentConfig, unverified: &UnverifiedAccounts, publisher: &B, device: Device) -> Result<TokenBundle, Error>
     where
         Error: From<DB::Error>,
         Error: From<T::Error>,
-        T::Error: From<DB::Error>
+        T::Error: From<DB::Error>,
+        B::Error: Display
     {
-        let password = user.login.password()?;
-        let hash = hasher.hash_password(password)?;
+        signup.check(&mut user)?;
+        let now = Utc::now();
+        if user.username.is_empty() {
+            user.username = UsernameRules::placeholder(user.id);
+        } else {
+            Profiles::check_username(db, &user.username, usernames, now).await?;
+        }
+        user.created_at = now;
+        user.updated_at = now;
+        let password = user.login.password()?.clone();
+        let hash = passwords.hash_password(password).await?;
         user.login.set_hash(hash);
+        for document in Document::ALL {
+            *user.consents.get_mut(document) = current_version(consent, document).map(Consent::new);
+        }
         let subject = user.id;
-        db.create_user(user).await?;
-        Ok(tokenizer.generate_token(db, subject).await?)
+        let access = unverified.check(&user, now);
+        let created = DomainEventKind::UserCreated { user_id: user.id, username: user.username.clone() };
+        Events::create_user(db, user, publisher, created).await?;
+        let scope = access?;
+        Ok(tokenizer.generate_token(db, subject, device, scope).await?)
     }
 
-    pub async fn login<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, VerificationsTable: VerificationsTable<DB::Client, Item = Verification<Id>>, SessionsTable: SessionsTable<DB::Client, Item = Session>>, T: Tokenizer, Verifyer: Password>(db: &DB, email: Email, password: String, tokenizer: &T, verifyer: Verifyer) -> Result<TokenBundle, Error> 
+    /// Suspended and deactivated accounts are refused once their password is verified,
+    /// and so are users who did not accept the current version of a document of `consent`. see [`Authentication::accept`].
+    /// Accounts that did not verify a contact are held to `unverified`, which may refuse them or limit the scope of their tokens.
+    /// Failed attempts are reported to `events` and successful ones published to `publisher` as `login.succeeded`.
+    /// A failure to deliver either event is logged and does not fail the login. Pass the sink wrapped in
+    /// `security_events::Background` so that a failed login does not wait on the delivery of its event either.
+    /// The login is then checked against the recent sessions of the user by `anomalies`, which may refuse it with `Error::StepUpRequired`.
+    #[instrument(skip_all, fields(user_id), err)]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn login<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, VerificationsTable: VerificationsTable<DB::Client, Item = Verification<Id>>, SessionsTable: SessionsTable<DB::Client, Item = Session>>, T: Tokenizer, P: Password + Send + Sync + 'static, E: SecurityEvents, B: EventPublisher, H: LoginHistory>(db: &DB, email: Email, password: String, tokenizer: &T, passwords: &PasswordService<P>, consent: &ConsentConfig, anomalies: &AnomalyDetection, unverified: &UnverifiedAccounts, events: &E, publisher: &B, history: &H, device: Device) -> Result<TokenBundle, Error> 
     where
         Error: From<DB::Error>,
         Error: From<T::Error>,
-        T::Error: From<DB::Error>
+        T::Error: From<DB::Error>,
+        E::Error: Display,
+        B::Error: Display,
+        H::Error: Display
+    {
+        Self::login_with_scope(db, email, password, None, tokenizer, passwords, consent, anomalies, unverified, events, publisher, history, device).await
+    }
+
+    /// [`Authentication::login`], with tokens of `scope` unless `unverified` limits the scope of the user.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) async fn login_with_scope<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, VerificationsTable: VerificationsTable<DB::Client, Item = Verification<Id>>, SessionsTable: SessionsTable<DB::Client, Item = Session>>, T: Tokenizer, P: Password + Send + Sync + 'static, E: SecurityEvents, B: EventPublisher, H: LoginHistory>(db: &DB, email: Email, password: String, scope: Option<&str>, tokenizer: &T, passwords: &PasswordService<P>, consent: &ConsentConfig, anomalies: &AnomalyDetection, unverified: &UnverifiedAccounts, events: &E, publisher: &B, history: &H, device: Device) -> Result<TokenBundle, Error>
+    where
+        Error: From<DB::Error>,
+        Error: From<T::Error>,
+        T::Error: From<DB::Error>,
+        E::Error: Display,
+        B::Error: Display,
+        H::Error: Display
+    {
+        let mut failed = None;
+        let user = match Self::authenticate(db, email, password, passwords, events, &mut failed).await {
+            Ok(user) => user,
+            Err(err) => {
+                if let Some((user_id, reason)) = failed {
+                    LoginAttempts::record(history, LoginAttempt::new(user_id, device, LoginOutcome::Failed { reason })).await;
+                }
+                return Err(err);
+            },
+        };
+        let scope = unverified.check(&user, Utc::now())?.or(scope);
+        let outdated = user.consents.outdated(|document| current_version(consent, document));
+        if !outdated.is_empty() {
+            return Err(Error::ConsentRequired(outdated));
+        }
+        anomalies.check(db, events, user.id, &device).await?;
+        let subject = user.id;
+        let bundle = tokenizer.generate_token(db, subject, device.clone(), scope).await?;
+        LoginAttempts::record(history, LoginAttempt::new(subject, device, LoginOutcome::Succeeded)).await;
+        Events::publish(publisher, DomainEventKind::LoginSucceeded { user_id: subject }).await?;
+        Ok(bundle)
+    }
+
+    /// Records that the user accepted the current version of `documents`, once their password is verified.
+    ///
+    /// It is how users refused at login with `Error::ConsentRequired` accept the new versions before logging in again.
+    /// Documents that do not have to be accepted are skipped.
+    #[instrument(skip_all, fields(user_id), err)]
+    pub async fn accept<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>, P: Password + Send + Sync + 'static, E: SecurityEvents>(db: &DB, email: Email, password: String, passwords: &PasswordService<P>, consent: &ConsentConfig, events: &E, documents: &[Document]) -> Result<(), Error>
+    where
+        Error: From<DB::Error>,
+        E::Error: Display
+    {
+        let user = Self::authenticate(db, email, password, passwords, events, &mut None).await?;
+        for document in documents {
+            if let Some(version) = current_version(consent, *document) {
+                db.set_user_consent(user.id, *document, Consent::new(version)).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets a new password for the user of `email` with `code_or_id`, the code or the magic link id `verifier` sent them,
+    /// and logs them out everywhere. It is how users whose password reset was forced by [`Admin::force_password_reset`] log in again.
+    /// Returns the number of sessions revoked.
+    #[instrument(skip_all, fields(user_id), err)]
+    pub async fn reset_password<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, SessionsTable: SessionsTable<DB::Client, Item = Session>, VerificationsTable: VerificationsTable<DB::Client, Item = V::VerificationCode>>, V: Verify<Email>, P: Password + Send + Sync + 'static, E: SecurityEvents>(db: &DB, verifier: &V, passwords: &PasswordService<P>, events: &E, email: Email, code_or_id: &str, password: String) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>,
+        Error: From<V::Error>,
+        E::Error: Display
+    {
+        let user = db.get_user_by_email(email.clone()).await?.ok_or(DatabaseError::UserNotFound)?;
+        Span::current().record("user_id", user.id.to_hex());
+        user.login.password()?;
+        verifier.verify(&email, code_or_id, db).await?;
+        db.set_user_password(user.id, passwords.hash_password(password).await?).await?;
+        Admin::clear_password_reset(db, &user).await?;
+        Sessions::revoke_all(db, events, user.id, None).await
+    }
+}
+
+impl Authentication {
+    /// Verifies the password of the user of `email`, reporting failures to `events`.
+    /// Failures of a known user are also left in `failed`, for the login history.
+    async fn authenticate<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>, P: Password + Send + Sync + 'static, E: SecurityEvents>(db: &DB, email: Email, password: String, passwords: &PasswordService<P>, events: &E, failed: &mut Option<(Id, LoginFailure)>) -> Result<User, Error>
+    where
+        Error: From<DB::Error>,
+        E::Error: Display
     {
         let user = match db.get_user_by_email(email).await?{
             Some(user) => user,
-            None => return Err(Error::DatabaseError(DatabaseError::UserNotFound)),
+            None => {
+                Self::failed_login(events, LoginFailure::UnknownUser, None).await;
+                return Err(Error::DatabaseError(DatabaseError::UserNotFound))
+            },
         };
-        let hash = user.login.password()?;
-        verifyer.verify_password(&password, hash)?;
-        let subject = user.id;
-        Ok(tokenizer.generate_token(db, subject).await?)
+        Span::current().record("user_id", user.id.to_hex());
+        let hash = user.login.password()?.clone();
+        if let Err(err) = passwords.verify_password(password, hash).await {
+            if err == Error::WrongPassword {
+                *failed = Some((user.id, LoginFailure::WrongPassword));
+                Self::failed_login(events, LoginFailure::WrongPassword, Some(user.id)).await;
+            }
+            return Err(err);
+        }
+        if !user.status.can_login() {
+            *failed = Some((user.id, LoginFailure::AccountDisabled));
+            Self::failed_login(events, LoginFailure::AccountDisabled, Some(user.id)).await;
+            return Err(Error::AccountDisabled(user.status));
+        }
+        if Admin::must_reset_password(&user) {
+            *failed = Some((user.id, LoginFailure::PasswordResetRequired));
+            Self::failed_login(events, LoginFailure::PasswordResetRequired, Some(user.id)).await;
+            return Err(Error::PasswordResetRequired);
+        }
+        Ok(user)
+    }
+
+    /// Reports a failed login to `events`, logging a failure to deliver it rather than failing the login a second time.
+    pub(super) async fn failed_login<E: SecurityEvents>(events: &E, reason: LoginFailure, user_id: Option<Id>)
+    where
+        E::Error: Display
+    {
+        let event = SecurityEvent::new(SecurityEventKind::FailedLogin { reason }, user_id);
+        if let Err(err) = events.emit(event).await {
+            tracing::error!(error = %err, "could not emit the security event");
+        }
+    }
+}
+
+
+pub(super) fn current_version(consent: &ConsentConfig, document: Document) -> Option<String> {
+    match document {
+        Document::TermsOfService => consent.terms_of_service.clone(),
+        Document::PrivacyPolicy => consent.privacy_policy.clone(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptors::outputs::security_events::Bus;
+    use crate::adaptors::outputs::events::EventSink;
+    use crate::ports::outputs::database::MockDatabase;
+    use crate::types::{Token, UsernameError, Status, GrantRecord};
+    use crate::config::{UsernamesConfig, AnomaliesConfig};
+    use crate::adaptors::outputs::login_history::memory::MemoryHistory;
+    use crate::testing::{Plain, Harness, UserFixture};
+
+    type Mock = MockDatabase<User, Session, Verification<Id>, GrantRecord, DatabaseError>;
+
+    fn history() -> MemoryHistory {
+        MemoryHistory::new(chrono::TimeDelta::days(1))
+    }
+
+    struct Tokens;
+
+    impl Tokenizer for Tokens {
+        type Error = Error;
+
+        async fn generate_token<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, _: &DB, _: Id, _: Device, _: Option<&str>) -> Result<TokenBundle, Self::Error> where Self::Error: From<DB::Error> {
+            unreachable!("no token is issued for a failed login")
+        }
+
+        async fn renew_token<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, _: &DB, _: &Token) -> Result<Token, Self::Error> where Self::Error: From<DB::Error> {
+            unreachable!()
+        }
+
+        async fn renew_refresh_token<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, _: &DB, _: &Token) -> Result<Token, Self::Error> where Self::Error: From<DB::Error> {
+            unreachable!()
+        }
+
+        async fn renew_bundle<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, _: &DB, _: &Token) -> Result<TokenBundle, Self::Error> where Self::Error: From<DB::Error> {
+            unreachable!()
+        }
+
+        async fn invalidate_token<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, _: &DB, _: &Token) -> Result<(), Self::Error> where Self::Error: From<DB::Error> {
+            unreachable!()
+        }
+
+        async fn validate_token(&self, _: &Token) -> Result<(), Self::Error> {
+            unreachable!()
+        }
+
+        async fn decode_token(&self, _: &str) -> Result<Token, Self::Error> {
+            unreachable!()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_login_of_unknown_user_is_reported() {
+        let db = Mock::default();
+        db.users_table().get_user_by_email_returns(Ok(None));
+        let events = Bus::new(1);
+        let mut subscriber = events.subscribe();
+        let email = Email::try_from("unknown@example.com").unwrap();
+        let result = Authentication::login(&db, email.clone(), "password".into(), &Tokens, &PasswordService::new(Plain, 1), &ConsentConfig::default(), &AnomalyDetection::from(&AnomaliesConfig::default()), &UnverifiedAccounts::default(), &events, &EventSink::Disabled, &history(), Device::default()).await;
+        assert_eq!(result, Err(Error::DatabaseError(DatabaseError::UserNotFound)));
+        let calls = db.users_table().calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].method, "get_user_by_email");
+        assert_eq!(calls[0].args, vec![format!("{:?}", email)]);
+        let event = subscriber.recv().await.unwrap();
+        assert!(matches!(event.kind, SecurityEventKind::FailedLogin { reason: LoginFailure::UnknownUser }));
+    }
+
+    #[tokio::test]
+    async fn test_login_of_suspended_user_is_refused() {
+        let db = Mock::default();
+        let user = UserFixture::new().status(Status::Suspended).build();
+        db.users_table().get_user_by_email_returns(Ok(Some(user.clone())));
+        let events = Bus::new(1);
+        let mut subscriber = events.subscribe();
+        let email = Email::try_from("jane@example.com").unwrap();
+        let history = history();
+        let result = Authentication::login(&db, email, "password".into(), &Tokens, &PasswordService::new(Plain, 1), &ConsentConfig::default(), &AnomalyDetection::from(&AnomaliesConfig::default()), &UnverifiedAccounts::default(), &events, &EventSink::Disabled, &history, Device::default()).await;
+        assert_eq!(result, Err(Error::AccountDisabled(Status::Suspended)));
+        let event = subscriber.recv().await.unwrap();
+        assert!(matches!(event.kind, SecurityEventKind::FailedLogin { reason: LoginFailure::AccountDisabled }));
+        let recorded = history.recent(user.id, 10).await.unwrap();
+        assert_eq!(recorded.iter().map(|attempt| attempt.outcome).collect::<Vec<_>>(), vec![LoginOutcome::Failed { reason: LoginFailure::AccountDisabled }]);
+    }
+
+    #[tokio::test]
+    async fn test_login_is_refused_until_a_forced_reset_completes() {
+        let db = Mock::default();
+        let mut flagged = UserFixture::new().build();
+        flagged.metadata.service.insert(String::from(crate::domain::PASSWORD_RESET_REQUIRED), serde_json::Value::Bool(true));
+        db.users_table().get_user_by_email_returns(Ok(Some(flagged)));
+        let events = Bus::new(1);
+        let mut subscriber = events.subscribe();
+        let email = Email::try_from("jane@example.com").unwrap();
+        let result = Authentication::login(&db, email, "password".into(), &Tokens, &PasswordService::new(Plain, 1), &ConsentConfig::default(), &AnomalyDetection::from(&AnomaliesConfig::default()), &UnverifiedAccounts::default(), &events, &EventSink::Disabled, &history(), Device::default()).await;
+        assert_eq!(result, Err(Error::PasswordResetRequired));
+        let event = subscriber.recv().await.unwrap();
+        assert!(matches!(event.kind, SecurityEventKind::FailedLogin { reason: LoginFailure::PasswordResetRequired }));
+    }
+
+    #[tokio::test]
+    async fn test_signup_with_a_reserved_username_is_rejected() {
+        let db = Mock::default();
+        let user = UserFixture::new().username("Admin").build();
+        let usernames = UsernameRules::from(&UsernamesConfig::default());
+        let result = Authentication::signup(&db, user, &Tokens, &PasswordService::new(Plain, 1), &usernames, &SignupRules::default(), &ConsentConfig::default(), &UnverifiedAccounts::default(), &EventSink::Disabled, Device::default()).await;
+        assert_eq!(result, Err(Error::InvalidUsername(UsernameError::Reserved("admin".into()))));
+        assert!(db.users_table().calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_signup_needs_no_more_than_an_email_and_a_password() {
+        let harness = Harness::new();
+        let user = User {
+            #[cfg(feature = "phone")]
+            phone: None,
+            ..UserFixture::new().username("").fullname("").password("secret").build()
+        };
+        let id = user.id;
+        let usernames = UsernameRules::from(&UsernamesConfig::default());
+        Authentication::signup(&harness.db, user, &harness.tokens, &harness.passwords, &usernames, &SignupRules::default(), &ConsentConfig::default(), &UnverifiedAccounts::default(), &EventSink::Disabled, Device::default()).await.unwrap();
+        let created = harness.db.get_user_by_id(id).await.unwrap().unwrap();
+        assert!(UsernameRules::has_placeholder(&created));
+    }
+
+    #[tokio::test]
+    async fn test_signups_are_written_along_with_their_event() {
+        use crate::ports::outputs::outbox::Outbox;
+        let harness = &Harness::new();
+        let usernames = &UsernameRules::from(&UsernamesConfig::default());
+        let signup = |user: User| async move {
+            Authentication::signup(&harness.db, user, &harness.tokens, &harness.passwords, usernames, &SignupRules::default(), &ConsentConfig::default(), &UnverifiedAccounts::default(), harness.db.outbox(), Device::default()).await
+        };
+        let jane = UserFixture::new().username("jane").password("secret").build();
+        let id = jane.id;
+        signup(jane).await.unwrap();
+        let taken = UserFixture::new().id(id).password("secret").build();
+        assert_eq!(signup(taken).await, Err(DatabaseError::AlreadyExists.into()));
+        let pending = harness.db.outbox().pending(10).await.unwrap();
+        assert_eq!(pending.into_iter().map(|event| event.kind).collect::<Vec<_>>(), vec![DomainEventKind::UserCreated { user_id: id, username: String::from("jane") }]);
+    }
+
+    #[cfg(any(feature = "email", feature = "phone"))]
+    #[tokio::test]
+    async fn test_unverified_signups_get_limited_tokens_or_none() {
+        use crate::config::UnverifiedConfig;
+        use crate::domain::UNVERIFIED_SCOPE;
+        let harness = &Harness::new();
+        let usernames = &UsernameRules::from(&UsernamesConfig::default());
+        let unverified = |access: &str| UnverifiedAccounts::from(&serde_json::from_value::<UnverifiedConfig>(serde_json::json!({"access": access})).unwrap());
+        let signup = |user: User, access| async move {
+            Authentication::signup(&harness.db, user, &harness.tokens, &harness.passwords, usernames, &SignupRules::default(), &ConsentConfig::default(), &unverified(access), &EventSink::Disabled, Device::default()).await
+        };
+        let limited = signup(UserFixture::new().username("jane").password("secret").build(), "limited").await.unwrap();
+        assert_eq!(limited.scope.as_deref(), Some(UNVERIFIED_SCOPE));
+        let denied = UserFixture::new().username("john").password("secret").build();
+        let id = denied.id;
+        assert_eq!(signup(denied, "deny").await, Err(Error::VerificationRequired));
+        assert!(harness.db.get_user_by_id(id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_login_is_refused_until_the_current_terms_are_accepted() {
+        let db = Mock::default();
+        let user = UserFixture::new().build();
+        db.users_table()
+            .get_user_by_email_returns(Ok(Some(user.clone())))
+            .get_user_by_email_returns(Ok(Some(user)))
+            .set_user_consent_returns(Ok(()));
+        let events = Bus::new(1);
+        let consent = ConsentConfig { terms_of_service: Some("2025-01".into()), privacy_policy: None };
+        let email = Email::try_from("jane@example.com").unwrap();
+        let passwords = PasswordService::new(Plain, 1);
+        let result = Authentication::login(&db, email.clone(), "password".into(), &Tokens, &passwords, &consent, &AnomalyDetection::from(&AnomaliesConfig::default()), &UnverifiedAccounts::default(), &events, &EventSink::Disabled, &history(), Device::default()).await;
+        assert_eq!(result, Err(Error::ConsentRequired(vec![Document::TermsOfService])));
+        let documents = [Document::TermsOfService, Document::PrivacyPolicy];
+        Authentication::accept(&db, email, "password".into(), &passwords, &consent, &events, &documents).await.unwrap();
+        let calls = db.users_table().calls().into_iter().filter(|call| call.method == "set_user_consent").collect::<Vec<_>>();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].args[1], format!("{:?}", Document::TermsOfService));
     }
-}
\ No newline at end of file
+}
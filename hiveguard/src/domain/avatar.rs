@@ -0,0 +1,151 @@
+use crate::ports::outputs::database::{Database, tables::UsersTable};
+use crate::types::{Error, User, Id, Upload, StorageError};
+use crate::ports::outputs::storage::Storage;
+use crate::config::AvatarsConfig;
+use serde_json::{Map, Value};
+use tracing::instrument;
+use std::time::Duration;
+
+
+/// Uploads of the pictures users set as their avatar.
+///
+/// The picture is uploaded by the client straight to the storage through a presigned URL. Once it is uploaded,
+/// [`Avatars::confirm`] checks what was stored and sets its URL as the avatar of the user.
+#[derive(Debug, Clone)]
+pub struct Avatars {
+    max_bytes: u64,
+    content_types: Vec<String>,
+    upload_ttl: Duration,
+}
+
+
+impl Avatars {
+    /// A URL the user can upload a `content_length` bytes picture of `content_type` to.
+    #[instrument(skip(self, storage), fields(user_id = %user_id.to_hex()), err)]
+    pub async fn upload<S: Storage>(&self, storage: &S, user_id: Id, content_type: &str, content_length: u64) -> Result<Upload, Error>
+    where
+        Error: From<S::Error>
+    {
+        self.check(content_type, content_length)?;
+        let key = format!("{}{}", prefix(user_id), Id::default().to_hex());
+        Ok(storage.presign_upload(&key, content_type, content_length, self.upload_ttl).await?)
+    }
+
+    /// Sets the picture uploaded under `key` as the avatar of the user, and returns the updated user.
+    ///
+    /// Only keys handed to the same user by [`Avatars::upload`] are accepted.
+    #[instrument(skip(self, db, storage), fields(user_id = %user_id.to_hex()), err)]
+    pub async fn confirm<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>, S: Storage>(&self, db: &DB, storage: &S, user_id: Id, key: &str) -> Result<User, Error>
+    where
+        Error: From<DB::Error>,
+        Error: From<S::Error>
+    {
+        if !key.starts_with(&prefix(user_id)) {
+            return Err(StorageError::NotFound(key.to_string()).into());
+        }
+        let object = storage.head(key).await?.ok_or_else(|| StorageError::NotFound(key.to_string()))?;
+        self.check(object.content_type.as_deref().unwrap_or_default(), object.size)?;
+        let mut update = Map::new();
+        update.insert("avatar".into(), Value::String(storage.public_url(key)));
+        Ok(db.update_user(user_id, update).await?)
+    }
+
+    fn check(&self, content_type: &str, size: u64) -> Result<(), StorageError> {
+        if !self.content_types.iter().any(|accepted| accepted.eq_ignore_ascii_case(content_type)) {
+            return Err(StorageError::UnsupportedContentType(content_type.to_string()));
+        }
+        if size > self.max_bytes {
+            return Err(StorageError::TooLarge(self.max_bytes));
+        }
+        Ok(())
+    }
+}
+
+
+impl From<&AvatarsConfig> for Avatars {
+    fn from(config: &AvatarsConfig) -> Self {
+        Self {
+            max_bytes: config.max_bytes,
+            content_types: config.content_types.clone(),
+            upload_ttl: Duration::from_secs(config.upload_ttl_secs),
+        }
+    }
+}
+
+
+/// The keys of the avatars of a user start with it.
+fn prefix(user_id: Id) -> String {
+    format!("avatars/{}/", user_id.to_hex())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DatabaseError, GrantRecord, Session, Verification, StoredObject};
+    use crate::ports::outputs::database::MockDatabase;
+    use std::collections::HashMap;
+
+    type Mock = MockDatabase<User, Session, Verification<Id>, GrantRecord, DatabaseError>;
+
+    /// Holds a single object.
+    struct Stored(Option<StoredObject>);
+
+    impl Storage for Stored {
+        type Error = StorageError;
+
+        async fn presign_upload(&self, key: &str, _: &str, _: u64, expires_in: Duration) -> Result<Upload, Self::Error> {
+            Ok(Upload { key: key.into(), url: format!("https://bucket.example.com/{}?signed", key), headers: HashMap::new(), expires_at: chrono::Utc::now() + expires_in })
+        }
+
+        async fn head(&self, _: &str) -> Result<Option<StoredObject>, Self::Error> {
+            Ok(self.0.clone())
+        }
+
+        fn public_url(&self, key: &str) -> String {
+            format!("https://cdn.example.com/{}", key)
+        }
+    }
+
+    fn id() -> Id {
+        Id::try_from(String::from("000000000000000000000001")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_upload_is_validated() {
+        let avatars = Avatars::from(&AvatarsConfig::default());
+        let storage = Stored(None);
+        let upload = avatars.upload(&storage, id(), "image/png", 1024).await.unwrap();
+        assert!(upload.key.starts_with("avatars/000000000000000000000001/"));
+        let result = avatars.upload(&storage, id(), "image/svg+xml", 1024).await;
+        assert_eq!(result.unwrap_err(), Error::StorageError(StorageError::UnsupportedContentType("image/svg+xml".into())));
+        let result = avatars.upload(&storage, id(), "image/png", 3 * 1024 * 1024).await;
+        assert_eq!(result.unwrap_err(), Error::StorageError(StorageError::TooLarge(2 * 1024 * 1024)));
+    }
+
+    #[tokio::test]
+    async fn test_confirm_sets_the_avatar() {
+        let avatars = Avatars::from(&AvatarsConfig::default());
+        let storage = Stored(Some(StoredObject { size: 1024, content_type: Some("image/png".into()) }));
+        let db = Mock::default();
+        let key = "avatars/000000000000000000000001/abc";
+        db.users_table().update_user_returns(Err(DatabaseError::UserNotFound));
+        assert_eq!(avatars.confirm(&db, &storage, id(), key).await, Err(Error::DatabaseError(DatabaseError::UserNotFound)));
+        let calls = db.users_table().calls();
+        assert_eq!(calls[0].method, "update_user");
+        assert!(calls[0].args[1].contains("https://cdn.example.com/avatars/000000000000000000000001/abc"));
+    }
+
+    #[tokio::test]
+    async fn test_confirm_rejects_what_the_user_did_not_upload() {
+        let avatars = Avatars::from(&AvatarsConfig::default());
+        let db = Mock::default();
+        let key = "avatars/000000000000000000000002/abc";
+        let storage = Stored(Some(StoredObject { size: 1024, content_type: Some("image/png".into()) }));
+        assert_eq!(avatars.confirm(&db, &storage, id(), key).await, Err(Error::StorageError(StorageError::NotFound(key.into()))));
+        let key = "avatars/000000000000000000000001/abc";
+        let storage = Stored(None);
+        assert_eq!(avatars.confirm(&db, &storage, id(), key).await, Err(Error::StorageError(StorageError::NotFound(key.into()))));
+        assert!(db.users_table().calls().is_empty());
+    }
+}
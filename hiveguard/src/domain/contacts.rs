@@ -0,0 +1,191 @@
+use crate::ports::outputs::database::{Database, tables::UsersTable};
+use crate::types::{Error, User, Token, Status, DatabaseError};
+#[cfg(any(feature = "email", feature = "phone"))]
+use crate::types::{Id, ConversionError};
+#[cfg(any(feature = "email", feature = "phone"))]
+use crate::ports::outputs::{database::tables::VerificationsTable, verify::Verify};
+#[cfg(feature = "email")]
+use crate::types::Email;
+#[cfg(feature = "phone")]
+use crate::types::Phone;
+#[cfg(any(feature = "email", feature = "phone"))]
+use serde_json::{Map, Value};
+#[cfg(any(feature = "email", feature = "phone"))]
+use tracing::instrument;
+
+
+/// the claim of tokens telling whether the email address of their user is verified, as OpenID Connect names it.
+pub const EMAIL_VERIFIED_CLAIM: &str = "email_verified";
+/// the claim of tokens telling whether the phone number of their user is verified, as OpenID Connect names it.
+pub const PHONE_VERIFIED_CLAIM: &str = "phone_verified";
+
+
+/// The verification of the contacts of users, with the codes a [`Verify`] sent them.
+///
+/// A contact is only verified here: a user changing it through `Profiles::patch` gets an unverified one,
+/// whatever the patch says. Users pending verification become active once a contact is verified.
+pub struct Contacts;
+
+
+impl Contacts {
+    /// Verifies the email address of the user `id` with `code_or_id`, the code or the magic link id `verifier` sent it.
+    #[cfg(feature = "email")]
+    #[instrument(skip(db, verifier, code_or_id), fields(user_id = %id.to_hex()), err)]
+    pub async fn confirm_email<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, VerificationsTable: VerificationsTable<DB::Client, Item = V::VerificationCode>>, V: Verify<Email>>(db: &DB, verifier: &V, id: Id, code_or_id: &str) -> Result<User, Error>
+    where
+        Error: From<DB::Error>,
+        Error: From<V::Error>
+    {
+        let user = db.get_user_by_id(id).await?.ok_or(DatabaseError::UserNotFound)?;
+        let email = user.email.clone().ok_or(ConversionError::MissingField("email"))?;
+        verifier.verify(&email, code_or_id, db).await?;
+        if !email.is_verified() {
+            Self::update(db, id, "email", serde_json::to_value(email.verified()).map_err(|err| DatabaseError::Internal(Box::new(err)))?).await?;
+        }
+        Self::activate(db, &user).await
+    }
+
+    /// Verifies the phone number of the user `id` with `code_or_id`, the code `verifier` sent it.
+    #[cfg(feature = "phone")]
+    #[instrument(skip(db, verifier, code_or_id), fields(user_id = %id.to_hex()), err)]
+    pub async fn confirm_phone<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, VerificationsTable: VerificationsTable<DB::Client, Item = V::VerificationCode>>, V: Verify<Phone>>(db: &DB, verifier: &V, id: Id, code_or_id: &str) -> Result<User, Error>
+    where
+        Error: From<DB::Error>,
+        Error: From<V::Error>
+    {
+        let user = db.get_user_by_id(id).await?.ok_or(DatabaseError::UserNotFound)?;
+        let phone = user.phone.clone().ok_or(ConversionError::MissingField("phone"))?;
+        verifier.verify(&phone, code_or_id, db).await?;
+        if !phone.is_verified() {
+            Self::update(db, id, "phone", serde_json::to_value(phone.verified()).map_err(|err| DatabaseError::Internal(Box::new(err)))?).await?;
+        }
+        Self::activate(db, &user).await
+    }
+
+    /// Sets the [`EMAIL_VERIFIED_CLAIM`] and [`PHONE_VERIFIED_CLAIM`] of `token`, issued to `user`, for the contacts they have.
+    #[cfg_attr(not(any(feature = "email", feature = "phone")), allow(unused_variables))]
+    pub fn tag(user: &User, token: &mut Token) {
+        #[cfg(feature = "email")]
+        if let Some(email) = &user.email {
+            token.claims.insert(String::from(EMAIL_VERIFIED_CLAIM), Value::Bool(email.is_verified()));
+        }
+        #[cfg(feature = "phone")]
+        if let Some(phone) = &user.phone {
+            token.claims.insert(String::from(PHONE_VERIFIED_CLAIM), Value::Bool(phone.is_verified()));
+        }
+    }
+}
+
+
+impl Contacts {
+    #[cfg(any(feature = "email", feature = "phone"))]
+    async fn update<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB, id: Id, field: &str, contact: Value) -> Result<(), Error>
+    where
+        Error: From<DB::Error>
+    {
+        db.update_user(id, Map::from_iter([(field.to_string(), contact)])).await?;
+        Ok(())
+    }
+
+    #[cfg_attr(not(any(feature = "email", feature = "phone")), allow(dead_code))]
+    async fn activate<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB, user: &User) -> Result<User, Error>
+    where
+        Error: From<DB::Error>
+    {
+        if user.status == Status::PendingVerification {
+            db.set_user_status(user.id, Status::Active, None).await?;
+        }
+        Ok(db.get_user_by_id(user.id).await?.ok_or(DatabaseError::UserNotFound)?)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::UserFixture;
+
+    #[test]
+    fn test_tokens_tell_whether_contacts_are_verified() {
+        let mut token = Token::default();
+        Contacts::tag(&UserFixture::new().build(), &mut token);
+        #[cfg(feature = "email")]
+        assert_eq!(token.claims.get(EMAIL_VERIFIED_CLAIM), Some(&Value::Bool(false)));
+        let mut token = Token::default();
+        Contacts::tag(&UserFixture::new().verified().build(), &mut token);
+        #[cfg(feature = "email")]
+        assert_eq!(token.claims.get(EMAIL_VERIFIED_CLAIM), Some(&Value::Bool(true)));
+        #[cfg(feature = "phone")]
+        assert_eq!(token.claims.get(PHONE_VERIFIED_CLAIM), Some(&Value::Bool(true)));
+    }
+
+    #[cfg(feature = "email")]
+    mod confirm {
+        use super::*;
+        use crate::types::{GrantRecord, Session};
+        use crate::ports::outputs::database::MockDatabase;
+        use crate::ports::outputs::verify::Code;
+
+        type Mock = MockDatabase<User, Session, Sent, GrantRecord, DatabaseError>;
+
+        #[derive(Debug)]
+        struct Sent([u8; 6]);
+
+        impl Code<Email> for Sent {
+            type Error = Error;
+
+            fn new(_: Email, _: Option<i64>) -> Self {
+                Self(Self::generate())
+            }
+
+            fn code(&self) -> &[u8; 6] {
+                &self.0
+            }
+
+            fn magic_link(base_uri: &str) -> String {
+                base_uri.to_string()
+            }
+        }
+
+        struct Provider(&'static str);
+
+        impl Verify<Email> for Provider {
+            type VerificationCode = Sent;
+            type Error = Error;
+            type Channel = ();
+
+            async fn initiate<DB: Database<VerificationsTable: VerificationsTable<DB::Client, Item = Self::VerificationCode>>>(&self, contact: &Email, _: (), _: Option<&str>, _: &DB) -> Result<Sent, Error> {
+                Ok(Sent::new(contact.clone(), None))
+            }
+
+            async fn verify<DB: Database<VerificationsTable: VerificationsTable<DB::Client, Item = Self::VerificationCode>>>(&self, _: &Email, code_or_id: &str, _: &DB) -> Result<(), Error> {
+                if code_or_id == self.0 { Ok(()) } else { Err(Error::InvalidCredentials) }
+            }
+        }
+
+        #[tokio::test]
+        async fn test_confirmed_emails_are_verified_and_their_users_activated() {
+            let db = Mock::default();
+            let user = UserFixture::new().email("jane@acme.com").status(Status::PendingVerification).build();
+            let verified = User { email: user.email.clone().map(Email::verified), status: Status::Active, ..user.clone() };
+            db.users_table()
+                .get_user_by_id_returns(Ok(Some(user.clone())))
+                .update_user_returns(Ok(verified.clone()))
+                .set_user_status_returns(Ok(()))
+                .get_user_by_id_returns(Ok(Some(verified.clone())));
+            assert_eq!(Contacts::confirm_email(&db, &Provider("123456"), user.id, "123456").await, Ok(verified));
+            let calls = db.users_table().calls();
+            let methods = calls.iter().map(|call| call.method).collect::<Vec<_>>();
+            assert_eq!(methods, vec!["get_user_by_id", "update_user", "set_user_status", "get_user_by_id"]);
+        }
+
+        #[tokio::test]
+        async fn test_wrong_codes_verify_nothing() {
+            let db = Mock::default();
+            let user = UserFixture::new().email("jane@acme.com").build();
+            db.users_table().get_user_by_id_returns(Ok(Some(user.clone())));
+            assert_eq!(Contacts::confirm_email(&db, &Provider("123456"), user.id, "654321").await, Err(Error::InvalidCredentials));
+            assert_eq!(db.users_table().calls().len(), 1);
+        }
+    }
+}
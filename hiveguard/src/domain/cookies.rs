@@ -0,0 +1,310 @@
+use crate::ports::outputs::database::{Database, tables::SessionsTable};
+use crate::types::{Error, Id, Session, Token, TokenBundle};
+use crate::config::{CookiesConfig, SameSite};
+use chrono::{DateTime, TimeDelta, Utc};
+use hmac::{Hmac, Mac};
+use super::Tokenizer;
+use sha2::Sha256;
+
+
+/// The cookie session mode. see `CookiesConfig`.
+///
+/// Requests are authenticated by a bearer token when they have one, and by the access token cookie otherwise.
+/// Cookies are sent by the browser whoever made the request, so a request authenticated by them
+/// must also repeat the CSRF cookie in a header unless its method is safe (signed double submit).
+/// The CSRF token is an HMAC of the session id, so a token planted in the cookie jar by a sibling subdomain
+/// only passes for the session it was issued to, see [`Cookies::credentials`].
+/// Access token cookies about to expire are renewed on the fly, under the same check, see [`Cookies::renew`].
+pub struct Cookies {
+    config: CookiesConfig,
+    /// the lifetime of refresh tokens in seconds.
+    refresh_token_ttl: i64,
+    /// the key the CSRF tokens are signed with.
+    key: Vec<u8>,
+}
+
+
+/// How a request is authenticated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Credentials {
+    Bearer(String),
+    Cookie(String),
+}
+
+
+/// The headers of a request that carry credentials.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestHeaders<'a> {
+    pub method: &'a str,
+    pub authorization: Option<&'a str>,
+    pub cookie: Option<&'a str>,
+    /// the value of `CookiesConfig::csrf_header`.
+    pub csrf: Option<&'a str>,
+}
+
+
+impl Cookies {
+    /// `key` signs the CSRF tokens. eg the decoded `tokens.key`, which is never used as is.
+    pub fn new(config: &CookiesConfig, refresh_token_ttl: i64, key: &[u8]) -> Self {
+        Self { config: config.clone(), refresh_token_ttl, key: key.to_vec() }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// The `Set-Cookie` headers logging the browser in with `bundle` of the session `session_id`, along with its CSRF token.
+    pub fn set(&self, bundle: &TokenBundle, session_id: Id, now: DateTime<Utc>) -> Vec<String> {
+        let csrf = self.csrf(session_id);
+        let mut cookies = self.tokens(bundle, now);
+        cookies.push(self.cookie(&self.config.csrf_token, &csrf, self.refresh_token_ttl, false));
+        cookies
+    }
+
+    /// Renews the token cookies of a request whose access token is missing, expired or about to expire.
+    ///
+    /// `access` is the access token of the request if it has a valid one.
+    /// Returns the `Set-Cookie` headers to add to the response, or `None` when nothing had to be renewed or nothing could be.
+    /// Unsafe requests have to present the CSRF token of the session of the refresh token like any cookie request,
+    /// and fail with `Error::CsrfMismatch` otherwise. The CSRF token is kept so that requests already in flight still pass.
+    pub async fn renew<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>, T: Tokenizer>(&self, db: &DB, tokenizer: &T, access: Option<&Token>, headers: RequestHeaders<'_>, now: DateTime<Utc>) -> Result<Option<Vec<String>>, Error>
+    where
+        Error: From<T::Error>,
+        T::Error: From<DB::Error>
+    {
+        if !self.config.enabled {
+            return Ok(None);
+        }
+        if access.is_some_and(|token| token.expiration - now > TimeDelta::seconds(self.config.renew_within_secs)) {
+            return Ok(None);
+        }
+        let Some(refresh_token) = self.refresh_token(headers.cookie.unwrap_or_default()) else {
+            return Ok(None);
+        };
+        let refresh_token = tokenizer.decode_token(refresh_token).await?;
+        // a refresh token of another session was planted next to the access token.
+        if access.is_some_and(|token| token.session_id != refresh_token.session_id) {
+            return Ok(None);
+        }
+        self.check_csrf(headers, refresh_token.session_id)?;
+        let bundle = tokenizer.renew_bundle(db, &refresh_token).await?;
+        Ok(Some(self.tokens(&bundle, now)))
+    }
+
+    /// The `Set-Cookie` headers logging the browser out.
+    pub fn clear(&self) -> Vec<String> {
+        [&self.config.access_token, &self.config.refresh_token, &self.config.csrf_token]
+            .into_iter()
+            .map(|name| self.cookie(name, "", 0, name != &self.config.csrf_token))
+            .collect()
+    }
+
+    /// The credentials of a request, if it has any, with its access token decoded by `tokenizer`.
+    ///
+    /// Cookies are ignored while the cookie mode is disabled. An unsafe request authenticated by them has to repeat
+    /// the CSRF token of the session of its access token in the CSRF cookie and header, or fails with `Error::CsrfMismatch`.
+    /// Bearer tokens and safe methods need none.
+    pub async fn credentials<T: Tokenizer>(&self, tokenizer: &T, headers: RequestHeaders<'_>) -> Result<Option<(Credentials, Token)>, Error>
+    where
+        Error: From<T::Error>
+    {
+        if let Some(token) = headers.authorization.and_then(|value| value.strip_prefix("Bearer ")) {
+            let token = token.trim();
+            let decoded = tokenizer.decode_token(token).await?;
+            return Ok(Some((Credentials::Bearer(token.to_string()), decoded)));
+        }
+        if !self.config.enabled {
+            return Ok(None);
+        }
+        let Some(token) = find(headers.cookie.unwrap_or_default(), &self.config.access_token) else {
+            return Ok(None);
+        };
+        let decoded = tokenizer.decode_token(token).await?;
+        self.check_csrf(headers, decoded.session_id)?;
+        Ok(Some((Credentials::Cookie(token.to_string()), decoded)))
+    }
+
+    /// Checks that a cookie request presents the CSRF token of the session `session_id` in both its CSRF cookie and
+    /// its CSRF header, unless its method is safe.
+    fn check_csrf(&self, headers: RequestHeaders, session_id: Id) -> Result<(), Error> {
+        if matches!(headers.method, "GET" | "HEAD" | "OPTIONS") {
+            return Ok(());
+        }
+        let expected = self.csrf(session_id);
+        let cookie = find(headers.cookie.unwrap_or_default(), &self.config.csrf_token).unwrap_or_default();
+        let presented = headers.csrf.unwrap_or_default();
+        match constant_time_eq(expected.as_bytes(), cookie.as_bytes()) && constant_time_eq(expected.as_bytes(), presented.as_bytes()) {
+            true => Ok(()),
+            false => Err(Error::CsrfMismatch),
+        }
+    }
+
+    /// The CSRF token of the session `session_id`.
+    fn csrf(&self, session_id: Id) -> String {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.key).expect("an HMAC key of any length");
+        mac.update(b"hiveguard-csrf");
+        mac.update(&[0]);
+        mac.update(session_id.to_hex().as_bytes());
+        mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// The refresh token cookie of a request.
+    pub fn refresh_token<'a>(&self, cookie: &'a str) -> Option<&'a str> {
+        find(cookie, &self.config.refresh_token)
+    }
+
+    fn tokens(&self, bundle: &TokenBundle, now: DateTime<Utc>) -> Vec<String> {
+        let access = bundle.expires_in(now);
+        vec![
+            self.cookie(&self.config.access_token, &bundle.access_token, access, true),
+            self.cookie(&self.config.refresh_token, &bundle.refresh_token, self.refresh_token_ttl, true),
+        ]
+    }
+
+    fn cookie(&self, name: &str, value: &str, max_age: i64, http_only: bool) -> String {
+        let mut cookie = format!("{}={}; Path=/; Max-Age={}", name, value, max_age);
+        if let Some(domain) = &self.config.domain {
+            cookie.push_str("; Domain=");
+            cookie.push_str(domain);
+        }
+        if self.config.secure {
+            cookie.push_str("; Secure");
+        }
+        if http_only {
+            cookie.push_str("; HttpOnly");
+        }
+        cookie.push_str(match self.config.same_site {
+            SameSite::Strict => "; SameSite=Strict",
+            SameSite::Lax => "; SameSite=Lax",
+            SameSite::None => "; SameSite=None",
+        });
+        cookie
+    }
+}
+
+
+/// the value of the cookie `name` in a `Cookie` header.
+fn find<'a>(header: &'a str, name: &str) -> Option<&'a str> {
+    header.split(';')
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+        .filter(|value| !value.is_empty())
+}
+
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Harness, Tokens, UserFixture};
+    use crate::types::Device;
+
+    const SESSION: &str = "000000000000000000000001";
+
+    const KEY: &[u8] = &[7; 32];
+
+    fn cookies() -> Cookies {
+        Cookies::new(&CookiesConfig { enabled: true, ..Default::default() }, 3600, KEY)
+    }
+
+    fn bundle(now: DateTime<Utc>) -> TokenBundle {
+        TokenBundle {
+            access_token: "access".into(),
+            refresh_token: "refresh".into(),
+            token_type: "Bearer".into(),
+            scope: None,
+            id_token: None,
+            expires_at: now + chrono::TimeDelta::minutes(15),
+        }
+    }
+
+    #[test]
+    fn test_login_sets_http_only_token_cookies() {
+        let now = Utc::now();
+        let headers = cookies().set(&bundle(now), Id::try_from(SESSION.to_string()).unwrap(), now);
+        assert_eq!(headers[0], "hg_access=access; Path=/; Max-Age=900; Secure; HttpOnly; SameSite=Lax");
+        assert_eq!(headers[1], "hg_refresh=refresh; Path=/; Max-Age=3600; Secure; HttpOnly; SameSite=Lax");
+        assert!(headers[2].starts_with("hg_csrf=") && !headers[2].contains("HttpOnly"));
+        assert!(cookies().clear().iter().all(|cookie| cookie.contains("=; Path=/; Max-Age=0")));
+    }
+
+    /// The CSRF token `cookies` sets for the session `session_id`.
+    fn csrf(cookies: &Cookies, session_id: Id) -> String {
+        let now = Utc::now();
+        let set = cookies.set(&bundle(now), session_id, now);
+        set[2].strip_prefix("hg_csrf=").and_then(|cookie| cookie.split(';').next()).unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_bearer_tokens_win_over_cookies() {
+        let headers = RequestHeaders { method: "POST", authorization: Some("Bearer 000000000000000000000002"), cookie: Some("hg_access=000000000000000000000001"), csrf: None };
+        let (credentials, token) = cookies().credentials(&Tokens, headers).await.unwrap().unwrap();
+        assert_eq!(credentials, Credentials::Bearer("000000000000000000000002".into()));
+        assert_eq!(token.session_id.to_hex(), "000000000000000000000002");
+    }
+
+    #[tokio::test]
+    async fn test_unsafe_cookie_requests_need_the_csrf_token_of_their_session() {
+        let cookies = cookies();
+        let csrf = csrf(&cookies, Id::try_from(SESSION.to_string()).unwrap());
+        let cookie = format!("theme=dark; hg_access={}; hg_csrf={}", SESSION, csrf);
+        let get = RequestHeaders { method: "GET", cookie: Some(&cookie), ..Default::default() };
+        assert_eq!(cookies.credentials(&Tokens, get).await.unwrap().map(|(credentials, _)| credentials), Some(Credentials::Cookie(SESSION.into())));
+        let forged = RequestHeaders { method: "POST", cookie: Some(&cookie), csrf: Some("9999"), ..Default::default() };
+        assert_eq!(cookies.credentials(&Tokens, forged).await, Err(Error::CsrfMismatch));
+        let missing = RequestHeaders { method: "DELETE", cookie: Some(&cookie), ..Default::default() };
+        assert_eq!(cookies.credentials(&Tokens, missing).await, Err(Error::CsrfMismatch));
+        let post = RequestHeaders { method: "POST", cookie: Some(&cookie), csrf: Some(&csrf), ..Default::default() };
+        let (credentials, token) = cookies.credentials(&Tokens, post).await.unwrap().unwrap();
+        assert_eq!((credentials, token.session_id.to_hex()), (Credentials::Cookie(SESSION.into()), SESSION.to_string()));
+        // a pair planted by a sibling subdomain passes the double submit, but not for the session of the victim.
+        let planted = RequestHeaders { method: "POST", cookie: Some("hg_access=000000000000000000000001; hg_csrf=0123"), csrf: Some("0123"), ..Default::default() };
+        assert_eq!(cookies.credentials(&Tokens, planted).await, Err(Error::CsrfMismatch));
+        let other = format!("hg_access=000000000000000000000002; hg_csrf={}", csrf);
+        let other = RequestHeaders { method: "POST", cookie: Some(&other), csrf: Some(&csrf), ..Default::default() };
+        assert_eq!(cookies.credentials(&Tokens, other).await, Err(Error::CsrfMismatch));
+    }
+
+    #[tokio::test]
+    async fn test_expiring_access_tokens_are_renewed() {
+        let now = Utc::now();
+        let harness = Harness::new();
+        let user = harness.create(UserFixture::new()).await;
+        let bundle = harness.tokens.generate_token(&harness.db, user.id, Device::default(), None).await.unwrap();
+        let session_id = harness.tokens.decode_token(&bundle.access_token).await.unwrap().session_id;
+        let csrf = csrf(&cookies(), session_id);
+        let cookie = format!("hg_refresh={}; hg_csrf={}", bundle.refresh_token, csrf);
+        let access = |expires_in: TimeDelta| Token { session_id, expiration: now + expires_in, ..Default::default() };
+        let renew = |access: Option<Token>, method: &'static str, presented: Option<String>| {
+            let (harness, cookie) = (&harness, cookie.clone());
+            async move {
+                let headers = RequestHeaders { method, cookie: Some(&cookie), csrf: presented.as_deref(), ..Default::default() };
+                cookies().renew(&harness.db, &harness.tokens, access.as_ref(), headers, now).await
+            }
+        };
+        assert_eq!(renew(Some(access(TimeDelta::minutes(10))), "GET", None).await, Ok(None));
+        let renewed = renew(Some(access(TimeDelta::seconds(30))), "GET", None).await.unwrap().unwrap();
+        assert_eq!(renewed.len(), 2);
+        assert!(renewed[0].starts_with(&format!("hg_access={};", bundle.access_token)));
+        assert!(renewed[1].starts_with(&format!("hg_refresh={}.", session_id.to_hex())) && !renewed[1].contains(&bundle.refresh_token));
+        assert!(renew(None, "GET", None).await.unwrap().is_some());
+        let planted = Token { session_id: Id::try_from(SESSION.to_string()).unwrap(), ..access(TimeDelta::zero()) };
+        assert_eq!(renew(Some(planted), "GET", None).await, Ok(None));
+        // an unsafe request renewing its tokens is held to the CSRF token of its session like any other.
+        assert_eq!(renew(None, "POST", None).await, Err(Error::CsrfMismatch));
+        assert_eq!(renew(None, "POST", Some(String::from("0123"))).await, Err(Error::CsrfMismatch));
+        assert!(renew(None, "POST", Some(csrf.clone())).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_cookies_are_ignored_while_disabled() {
+        let cookies = Cookies::new(&CookiesConfig::default(), 3600, KEY);
+        let headers = RequestHeaders { method: "GET", cookie: Some("hg_access=000000000000000000000001"), ..Default::default() };
+        assert_eq!(cookies.credentials(&Tokens, headers).await, Ok(None));
+    }
+}
@@ -0,0 +1,115 @@
+use crate::ports::outputs::database::{Database, tables::{UsersTable, SessionsTable}};
+use crate::types::{Error, User, TokenBundle, Email, Session, Device, Login, ConversionError, LoginFailure};
+use crate::ports::outputs::security_events::SecurityEvents;
+use crate::ports::outputs::directory::Directory;
+use super::{Authentication, Tokenizer};
+use super::external::ExternalUsers;
+use tracing::instrument;
+use std::fmt::Display;
+
+
+/// Logs in the users of an external directory, eg LDAP, which stays the source of truth for their passwords.
+///
+/// A user is created the first time they log in, from their entry in the directory, and their name is kept in sync on every login after that.
+/// Users are matched by email, so the entries of the directory need one.
+/// An account is only ever logged in by the entry of the directory that created it, so an account that logs in with a password of its own,
+/// or the account of another entry that shares its email, is never taken over.
+pub struct DirectoryLogin;
+
+
+impl DirectoryLogin {
+    #[instrument(skip(db, directory, tokenizer, events, password, device), fields(user_id), err)]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn login<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, SessionsTable: SessionsTable<DB::Client, Item = Session>>, D: Directory, T: Tokenizer, E: SecurityEvents>(db: &DB, directory: &D, tokenizer: &T, events: &E, username: &str, password: &str, device: Device) -> Result<TokenBundle, Error>
+    where
+        Error: From<DB::Error>,
+        Error: From<T::Error>,
+        T::Error: From<DB::Error>,
+        D::Error: Display,
+        E::Error: Display
+    {
+        let entry = match directory.authenticate(username, password).await {
+            Ok(Some(entry)) => entry,
+            Ok(None) => {
+                Authentication::failed_login(events, LoginFailure::WrongPassword, None).await;
+                return Err(Error::InvalidCredentials);
+            },
+            Err(err) => {
+                tracing::error!(error = %err, "could not reach the directory");
+                return Err(Error::ProviderUnavailable("directory"));
+            },
+        };
+        let email = Email::try_from(entry.email.as_deref().ok_or(ConversionError::MissingFields(&["email"]))?)?;
+        let fullname = entry.fullname.unwrap_or_else(|| entry.username.clone());
+        let user = ExternalUsers::provision(db, events, Login::Directory(entry.dn), email, entry.username, fullname, entry.phone).await?;
+        Ok(tokenizer.generate_token(db, user.id, device, None).await?)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptors::outputs::databases::memory::Memory;
+    use crate::adaptors::outputs::security_events::Bus;
+    use crate::types::{DirectoryEntry, SecurityEventKind};
+    use crate::testing::{Tokens, UserFixture};
+    use std::time::Duration;
+
+    struct Entries;
+
+    impl Directory for Entries {
+        type Error = &'static str;
+
+        async fn authenticate(&self, username: &str, password: &str) -> Result<Option<DirectoryEntry>, Self::Error> {
+            match (username, password) {
+                ("jane", "secret") => Ok(Some(DirectoryEntry {
+                    dn: "uid=jane,dc=example,dc=com".into(),
+                    username: "jane".into(),
+                    fullname: Some("Jane Doe".into()),
+                    email: Some("jane@example.com".into()),
+                    phone: Some("+254712345678".into()),
+                })),
+                ("down", _) => Err("connection refused"),
+                _ => Ok(None),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_directory_users_are_created_on_their_first_login() {
+        let db = Memory::new(Duration::from_secs(1));
+        let events = Bus::new(1);
+        DirectoryLogin::login(&db, &Entries, &Tokens, &events, "jane", "secret", Device::default()).await.unwrap();
+        let email = Email::try_from("jane@example.com").unwrap();
+        let user = db.get_user_by_email(email).await.unwrap().unwrap();
+        assert_eq!((user.username.as_str(), user.fullname.as_str()), ("jane", "Jane Doe"));
+        assert_eq!(user.login, Login::Directory("uid=jane,dc=example,dc=com".into()));
+        DirectoryLogin::login(&db, &Entries, &Tokens, &events, "jane", "secret", Device::default()).await.unwrap();
+        assert_eq!(db.list_users(None, 10).await.unwrap().items.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_credentials_and_outages_are_refused() {
+        let db = Memory::new(Duration::from_secs(1));
+        let events = Bus::new(1);
+        let mut subscriber = events.subscribe();
+        let result = DirectoryLogin::login(&db, &Entries, &Tokens, &events, "jane", "wrong", Device::default()).await;
+        assert_eq!(result, Err(Error::InvalidCredentials));
+        assert!(matches!(subscriber.recv().await.unwrap().kind, SecurityEventKind::FailedLogin { reason: LoginFailure::WrongPassword }));
+        let result = DirectoryLogin::login(&db, &Entries, &Tokens, &events, "down", "secret", Device::default()).await;
+        assert_eq!(result, Err(Error::ProviderUnavailable("directory")));
+    }
+
+    #[tokio::test]
+    async fn test_accounts_of_other_entries_are_not_taken_over() {
+        let db = Memory::new(Duration::from_secs(1));
+        let other = UserFixture::new().username("jane").email("jane@example.com").login(Login::Directory("uid=jane,ou=former,dc=example,dc=com".into())).build();
+        db.create_user(other).await.unwrap();
+        let events = Bus::new(1);
+        let mut subscriber = events.subscribe();
+        let result = DirectoryLogin::login(&db, &Entries, &Tokens, &events, "jane", "secret", Device::default()).await;
+        assert_eq!(result, Err(Error::InvalidCredentials));
+        assert!(matches!(subscriber.recv().await.unwrap().kind, SecurityEventKind::FailedLogin { reason: LoginFailure::WrongPassword }));
+    }
+}
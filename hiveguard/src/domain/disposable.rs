@@ -0,0 +1,95 @@
+use crate::config::{DisposableEmailsConfig, DisposableEmailAction};
+use crate::types::{Email, Error};
+use std::collections::HashSet;
+
+
+/// the `service` metadata field set on users whose email address is disposable, when they are flagged.
+pub const DISPOSABLE_EMAIL: &str = "disposable_email";
+
+
+/// the domains of the disposable email providers bundled with hiveguard.
+const BUNDLED: &str = include_str!("disposable_domains.txt");
+
+
+/// The disposable email providers users sign up or change their address with. see [`DisposableEmailsConfig`].
+#[derive(Debug, Clone, Default)]
+pub struct DisposableEmails {
+    action: DisposableEmailAction,
+    /// the lowercased domains.
+    domains: HashSet<String>,
+}
+
+
+impl DisposableEmails {
+    /// The bundled providers, with those of the file `config.list` names.
+    pub fn load(config: &DisposableEmailsConfig) -> std::io::Result<Self> {
+        let list = match &config.list {
+            Some(path) => std::fs::read_to_string(path)?,
+            None => String::new(),
+        };
+        Ok(Self::new(config.action, [BUNDLED, list.as_str()]))
+    }
+
+    fn new<'a>(action: DisposableEmailAction, lists: impl IntoIterator<Item = &'a str>) -> Self {
+        let domains = lists.into_iter()
+            .flat_map(str::lines)
+            .map(|line| line.split('#').next().unwrap_or_default().trim())
+            .filter(|domain| !domain.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        Self { action, domains }
+    }
+
+    /// Whether `email` is at a disposable provider or one of its subdomains.
+    pub fn is_disposable(&self, email: &Email) -> bool {
+        let domain = email.domain().to_lowercase();
+        let mut parent = domain.as_str();
+        loop {
+            if self.domains.contains(parent) {
+                return true;
+            }
+            match parent.split_once('.') {
+                Some((_, rest)) => parent = rest,
+                None => return false,
+            }
+        }
+    }
+
+    /// Checks that `email` can be used, returning whether its user has to be flagged with [`DISPOSABLE_EMAIL`].
+    pub fn check(&self, email: &Email) -> Result<bool, Error> {
+        match self.action {
+            DisposableEmailAction::Allow => Ok(false),
+            DisposableEmailAction::Flag => Ok(self.is_disposable(email)),
+            DisposableEmailAction::Block if self.is_disposable(email) => Err(Error::DisposableEmail(email.domain().to_lowercase())),
+            DisposableEmailAction::Block => Ok(false),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(email: &str) -> Email {
+        Email::try_from(email).unwrap()
+    }
+
+    #[test]
+    fn test_providers_are_bundled_and_extended() {
+        let disposable = DisposableEmails::new(DisposableEmailAction::Block, [BUNDLED, "# ours\nThrowaway.example # a comment\n"]);
+        assert!(disposable.is_disposable(&email("jane@mailinator.com")));
+        assert!(disposable.is_disposable(&email("jane@eu.throwaway.example")));
+        assert!(!disposable.is_disposable(&email("jane@acme.com")));
+        assert!(!disposable.is_disposable(&email("jane@notmailinator.example")));
+    }
+
+    #[test]
+    fn test_disposable_emails_are_blocked_or_flagged() {
+        let disposable = |action| DisposableEmails::new(action, [BUNDLED]);
+        assert_eq!(disposable(DisposableEmailAction::Allow).check(&email("jane@yopmail.com")), Ok(false));
+        assert_eq!(disposable(DisposableEmailAction::Flag).check(&email("jane@yopmail.com")), Ok(true));
+        assert_eq!(disposable(DisposableEmailAction::Flag).check(&email("jane@acme.com")), Ok(false));
+        assert_eq!(disposable(DisposableEmailAction::Block).check(&email("jane@YOPmail.com")), Err(Error::DisposableEmail("yopmail.com".into())));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::ports::outputs::database::{Database, tables::{UsersTable, SessionsTable}};
+use crate::types::{Error, User, Id, Session, Status, DatabaseError, SecurityEvent, SecurityEventKind};
+use crate::ports::outputs::security_events::SecurityEvents;
+use crate::config::ErasureConfig;
+use chrono::{DateTime, TimeDelta, Utc};
+use super::sessions::Sessions;
+use tracing::instrument;
+use std::fmt::Display;
+
+
+/// Erases accounts at the request of their owner or an admin, eg for a GDPR right to erasure request.
+///
+/// A request moves the account to `Status::PendingErasure`, which logs it out everywhere and keeps it from logging in.
+/// The `erase_accounts` job then deletes the user and their sessions once the grace period ended.
+/// Reactivating the account before that cancels the erasure.
+///
+/// Both the request and the erasure emit security events, so the audit log and the webhooks keep a proof of the erasure
+/// that holds no personal data but the id of the user.
+pub struct Erasure;
+
+
+impl Erasure {
+    /// Schedules the erasure of the account `id`. Returns the time it will be erased at.
+    #[instrument(skip(db, events, config), fields(user_id = %id.to_hex()), err)]
+    pub async fn request<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, SessionsTable: SessionsTable<DB::Client, Item = Session>>, E: SecurityEvents>(db: &DB, events: &E, config: &ErasureConfig, id: Id, requested_by: Option<Id>) -> Result<DateTime<Utc>, Error>
+    where
+        Error: From<DB::Error>,
+        E::Error: Display
+    {
+        let user = db.get_user_by_id(id).await?.ok_or(DatabaseError::UserNotFound)?;
+        if user.status == Status::PendingErasure {
+            return Err(Error::InvalidStatusTransition(user.status, Status::PendingErasure));
+        }
+        db.set_user_status(id, Status::PendingErasure, Some(String::from("erasure requested"))).await?;
+        Sessions::delete_all(db, id, None).await?;
+        let erase_after = Utc::now() + grace_period(config);
+        Self::emit(events, SecurityEventKind::ErasureRequested { requested_by, erase_after }, id).await;
+        Ok(erase_after)
+    }
+
+    /// Erases every account whose grace period ended. Returns the number of accounts erased.
+    ///
+    /// The grace period runs from the last update of the account, which is when its status changed.
+    #[instrument(skip_all, err)]
+    pub async fn erase_due<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, SessionsTable: SessionsTable<DB::Client, Item = Session>>, E: SecurityEvents>(db: &DB, events: &E, config: &ErasureConfig) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>,
+        E::Error: Display
+    {
+        let due = Utc::now() - grace_period(config);
+        let mut after = None;
+        let mut erased = 0;
+        loop {
+            let page = db.list_users(after, config.page_size.max(1)).await?;
+            for user in page.items.iter().filter(|user| user.status == Status::PendingErasure && user.updated_at <= due) {
+                let sessions = Sessions::delete_all(db, user.id, None).await?;
+                db.delete_user(user.id).await?;
+                Self::emit(events, SecurityEventKind::AccountErased { sessions }, user.id).await;
+                erased += 1;
+            }
+            match page.next {
+                Some(next) => after = Some(next),
+                None => break Ok(erased),
+            }
+        }
+    }
+}
+
+
+impl Erasure {
+    async fn emit<E: SecurityEvents>(events: &E, kind: SecurityEventKind, user_id: Id)
+    where
+        E::Error: Display
+    {
+        if let Err(err) = events.emit(SecurityEvent::new(kind, Some(user_id))).await {
+            tracing::error!(error = %err, "could not emit the security event");
+        }
+    }
+}
+
+
+fn grace_period(config: &ErasureConfig) -> TimeDelta {
+    TimeDelta::seconds(config.grace_period_secs.min(i64::MAX as u64 / 1000) as i64)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{id, UserFixture};
+    use crate::adaptors::outputs::databases::memory::Memory;
+    use crate::adaptors::outputs::security_events::Bus;
+    use crate::types::Device;
+    use super::super::accounts::Accounts;
+    use std::time::Duration;
+
+    fn user(n: u8) -> User {
+        UserFixture::new().id(id(n)).build()
+    }
+
+    fn session(n: u8, user_id: Id) -> Session {
+        Session { id: id(n), user_id, refresh_token_id: id(n + 10), previous_refresh_token_id: None, device: Device::default(), created_at: Utc::now(), updated_at: Utc::now(), last_active_at: Utc::now() }
+    }
+
+    #[tokio::test]
+    async fn test_accounts_are_erased_after_the_grace_period() {
+        let db = Memory::new(Duration::from_secs(1));
+        db.create_user(user(1)).await.unwrap();
+        db.create_user(user(2)).await.unwrap();
+        db.create_session(session(3, id(1))).await.unwrap();
+        let events = Bus::new(4);
+        let mut subscriber = events.subscribe();
+        let config = ErasureConfig { grace_period_secs: 0, ..Default::default() };
+        Erasure::request(&db, &events, &config, id(1), Some(id(9))).await.unwrap();
+        assert_eq!(db.get_user_by_id(id(1)).await.unwrap().unwrap().status, Status::PendingErasure);
+        assert!(db.get_sessions_by_user_id(id(1)).await.unwrap().is_empty());
+        assert!(matches!(subscriber.recv().await.unwrap().kind, SecurityEventKind::ErasureRequested { requested_by: Some(requested_by), .. } if requested_by == id(9)));
+        let later = ErasureConfig { grace_period_secs: 60, ..Default::default() };
+        assert_eq!(Erasure::erase_due(&db, &events, &later).await, Ok(0));
+        assert_eq!(Erasure::erase_due(&db, &events, &config).await, Ok(1));
+        assert_eq!(db.get_user_by_id(id(1)).await.unwrap(), None);
+        assert!(db.get_user_by_id(id(2)).await.unwrap().is_some());
+        let event = subscriber.recv().await.unwrap();
+        assert_eq!((event.kind, event.user_id), (SecurityEventKind::AccountErased { sessions: 0 }, Some(id(1))));
+    }
+
+    #[tokio::test]
+    async fn test_reactivation_cancels_the_erasure() {
+        let db = Memory::new(Duration::from_secs(1));
+        db.create_user(user(1)).await.unwrap();
+        let events = Bus::new(4);
+        let config = ErasureConfig { grace_period_secs: 0, ..Default::default() };
+        Erasure::request(&db, &events, &config, id(1), None).await.unwrap();
+        let again = Erasure::request(&db, &events, &config, id(1), None).await;
+        assert_eq!(again, Err(Error::InvalidStatusTransition(Status::PendingErasure, Status::PendingErasure)));
+        Accounts::reactivate(&db, &events, id(1), None, Some(id(9))).await.unwrap();
+        assert_eq!(Erasure::erase_due(&db, &events, &config).await, Ok(0));
+        assert_eq!(db.get_user_by_id(id(1)).await.unwrap().unwrap().status, Status::Active);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::ports::outputs::database::{Database, tables::UsersTable};
+use crate::ports::outputs::events::EventPublisher;
+use crate::ports::outputs::outbox::Outbox;
+use crate::types::{Error, DomainEvent, DomainEventKind, User, Id};
+use serde_json::{Map, Value};
+use crate::config::OutboxConfig;
+use tracing::instrument;
+use std::fmt::Display;
+
+
+/// Publishes the domain events written to the outbox. see [`OutboxConfig`].
+///
+/// With the outbox enabled, the domain is handed the outbox of the database as its publisher, so an event is stored
+/// rather than lost when the bus is down: along with the user it follows when it is created or updated, or right after the write otherwise.
+/// The `dispatch_events` job then publishes them to the bus the way they would have been published right away.
+pub struct Events;
+
+
+impl Events {
+    /// Publishes the event of `kind` to `publisher`, once the write it follows is done.
+    ///
+    /// An event the bus did not take is logged and lost. An event a durable publisher, ie the outbox, could not store fails
+    /// the call with `Error::ProviderUnavailable`, so that it is never lost silently, even though the write went through.
+    pub(super) async fn publish<B: EventPublisher>(publisher: &B, kind: DomainEventKind) -> Result<(), Error>
+    where
+        B::Error: Display
+    {
+        let event = DomainEvent::new(kind);
+        match publisher.publish(&event).await {
+            Ok(()) => Ok(()),
+            Err(err) if publisher.durable() => {
+                tracing::error!(error = %err, event = event.name(), event_id = %event.id.to_hex(), "could not store the event in the outbox");
+                Err(Error::ProviderUnavailable("the outbox"))
+            },
+            Err(err) => {
+                tracing::error!(error = %err, event = event.name(), "could not publish the event");
+                Ok(())
+            },
+        }
+    }
+
+    /// Creates `user` and publishes the event of `kind` to `publisher`.
+    ///
+    /// A durable publisher is not handed the event: the database adds it to its outbox in the same write as the user,
+    /// so that neither is stored without the other. see [`UsersTable::create_user_with_event`].
+    pub(super) async fn create_user<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>, B: EventPublisher>(db: &DB, user: User, publisher: &B, kind: DomainEventKind) -> Result<(), Error>
+    where
+        Error: From<DB::Error>,
+        B::Error: Display
+    {
+        if publisher.durable() {
+            return Ok(db.create_user_with_event(user, DomainEvent::new(kind)).await?);
+        }
+        db.create_user(user).await?;
+        Self::publish(publisher, kind).await
+    }
+
+    /// Applies `update` to the user `id` and publishes the event of `kind` to `publisher`, like [`Events::create_user`].
+    pub(super) async fn update_user<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>, B: EventPublisher>(db: &DB, id: Id, update: Map<String, Value>, publisher: &B, kind: DomainEventKind) -> Result<User, Error>
+    where
+        Error: From<DB::Error>,
+        B::Error: Display
+    {
+        if publisher.durable() {
+            return Ok(db.update_user_with_event(id, update, DomainEvent::new(kind)).await?);
+        }
+        let user = db.update_user(id, update).await?;
+        Self::publish(publisher, kind).await?;
+        Ok(user)
+    }
+
+    /// Publishes a batch of the pending events, the oldest first. Returns the number of events published.
+    ///
+    /// A failure ends the batch, so that no event is published ahead of an older one. it is retried on the next run.
+    #[instrument(skip_all, err)]
+    pub async fn dispatch<O: Outbox, P: EventPublisher>(outbox: &O, publisher: &P, config: &OutboxConfig) -> Result<usize, Error>
+    where
+        Error: From<O::Error>,
+        P::Error: Display
+    {
+        let mut published = 0;
+        for event in outbox.pending(config.batch_size).await? {
+            if let Err(err) = publisher.publish(&event).await {
+                tracing::warn!(event = event.name(), event_id = %event.id.to_hex(), error = %err, "could not publish an event");
+                break;
+            }
+            outbox.remove(event.id).await?;
+            published += 1;
+        }
+        Ok(published)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptors::outputs::outbox::memory::MemoryOutbox;
+    use crate::types::{DomainEvent, DomainEventKind, Id};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Mutex;
+
+    /// Records the events it publishes, after failing the first `failures`.
+    #[derive(Default)]
+    struct Bus {
+        failures: AtomicU32,
+        published: Mutex<Vec<DomainEvent>>,
+    }
+
+    impl EventPublisher for Bus {
+        type Error = &'static str;
+
+        async fn publish(&self, event: &DomainEvent) -> Result<(), Self::Error> {
+            if self.failures.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |failures| failures.checked_sub(1)).is_ok() {
+                return Err("connection refused");
+            }
+            self.published.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pending_events_are_published_in_order_once() {
+        let outbox = MemoryOutbox::new();
+        let user_id = Id::default();
+        let created = DomainEvent::new(DomainEventKind::UserCreated { user_id, username: String::from("jane") });
+        let renamed = DomainEvent::new(DomainEventKind::UserRenamed { user_id, from: String::from("jane"), to: String::from("janet") });
+        outbox.publish(&created).await.unwrap();
+        outbox.publish(&renamed).await.unwrap();
+        let bus = Bus { failures: AtomicU32::new(1), ..Default::default() };
+        let config = OutboxConfig::default();
+        assert_eq!(Events::dispatch(&outbox, &bus, &config).await, Ok(0));
+        assert_eq!(outbox.pending(10).await.unwrap().len(), 2);
+        assert_eq!(Events::dispatch(&outbox, &bus, &OutboxConfig { batch_size: 1, ..config.clone() }).await, Ok(1));
+        assert_eq!(Events::dispatch(&outbox, &bus, &config).await, Ok(1));
+        assert_eq!(Events::dispatch(&outbox, &bus, &config).await, Ok(0));
+        assert_eq!(*bus.published.lock().unwrap(), vec![created, renamed]);
+    }
+
+    /// An outbox whose table is unreachable.
+    struct Unreachable;
+
+    impl EventPublisher for Unreachable {
+        type Error = &'static str;
+
+        async fn publish(&self, _: &DomainEvent) -> Result<(), Self::Error> {
+            Err("connection refused")
+        }
+
+        fn durable(&self) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn test_events_the_outbox_could_not_store_fail_the_call() {
+        let kind = || DomainEventKind::LoginSucceeded { user_id: Id::default() };
+        let bus = Bus { failures: AtomicU32::new(1), ..Default::default() };
+        assert_eq!(Events::publish(&bus, kind()).await, Ok(()));
+        assert_eq!(Events::publish(&Unreachable, kind()).await, Err(Error::ProviderUnavailable("the outbox")));
+    }
+}
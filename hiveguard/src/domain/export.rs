@@ -0,0 +1,93 @@
+use crate::ports::outputs::database::{Database, tables::UsersTable};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use crate::types::{Error, User};
+use tracing::instrument;
+
+
+/// Streams listings as newline-delimited JSON, one item per line.
+///
+/// Items are read a page at a time and every page is flushed before the next one is read,
+/// so an export holds at most one page in memory however large the listing is.
+pub struct Export;
+
+
+impl Export {
+    /// Writes every user to `writer`, reading `page_size` users at a time. Password hashes are left out.
+    ///
+    /// Returns the number of users written.
+    #[instrument(skip_all, fields(page_size), err)]
+    pub async fn users<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>, W: AsyncWrite + Unpin>(db: &DB, page_size: usize, writer: &mut W) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>
+    {
+        Self::write_users(db, page_size, writer, false).await
+    }
+
+    /// Writes every user to `writer` as fixtures, password hashes included, for `Admin::load` to read back.
+    ///
+    /// Returns the number of users written.
+    #[instrument(skip_all, fields(page_size), err)]
+    pub async fn fixtures<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>, W: AsyncWrite + Unpin>(db: &DB, page_size: usize, writer: &mut W) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>
+    {
+        Self::write_users(db, page_size, writer, true).await
+    }
+}
+
+
+impl Export {
+    async fn write_users<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>, W: AsyncWrite + Unpin>(db: &DB, page_size: usize, writer: &mut W, hashes: bool) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>
+    {
+        let mut after = None;
+        let mut written = 0;
+        loop {
+            let page = db.list_users(after, page_size.max(1)).await?;
+            let mut lines = Vec::new();
+            for mut user in page.items {
+                if !hashes {
+                    user.login.set_hash(String::new());
+                }
+                serde_json::to_writer(&mut lines, &user).map_err(std::io::Error::from)?;
+                lines.push(b'\n');
+                written += 1;
+            }
+            writer.write_all(&lines).await?;
+            writer.flush().await?;
+            match page.next {
+                Some(next) => after = Some(next),
+                None => break Ok(written),
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DatabaseError, GrantRecord, Id, Page, Session, Verification};
+    use crate::ports::outputs::database::MockDatabase;
+    use crate::testing::UserFixture;
+
+    type Mock = MockDatabase<User, Session, Verification<Id>, GrantRecord, DatabaseError>;
+
+    #[tokio::test]
+    async fn test_users_are_exported_page_by_page() {
+        let db = Mock::default();
+        let (first, second, third) = (UserFixture::new().build(), UserFixture::new().build(), UserFixture::new().build());
+        db.users_table()
+            .list_users_returns(Ok(Page { items: vec![first.clone(), second.clone()], next: Some(second.id) }))
+            .list_users_returns(Ok(Page { items: vec![third.clone()], next: None }));
+        let mut output = Vec::new();
+        assert_eq!(Export::users(&db, 2, &mut output).await, Ok(3));
+        let lines = String::from_utf8(output).unwrap().lines().map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()).collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[2]["id"], third.id.to_hex());
+        assert!(lines.iter().all(|line| line.get("password").is_none()));
+        let calls = db.users_table().calls();
+        assert_eq!(calls[1].args, vec![format!("{:?}", Some(second.id)), String::from("2")]);
+    }
+}
@@ -0,0 +1,75 @@
+use crate::ports::outputs::database::{Database, tables::UsersTable};
+use crate::types::{Error, User, Email, Id, Login, Status, LoginFailure};
+use crate::ports::outputs::security_events::SecurityEvents;
+use super::{Accounts, Authentication};
+use serde_json::{Map, Value};
+use tracing::Span;
+use std::fmt::Display;
+use chrono::Utc;
+
+
+/// The users of external providers, eg a directory or a SAML identity provider, which stay the source of truth for them.
+///
+/// A user is created the first time they log in, from what the provider knows of them, matched by email,
+/// and their name is kept in sync on every login after that.
+pub(super) struct ExternalUsers;
+
+
+impl ExternalUsers {
+    /// The user of `email` logging in with `login`, created or synced from `username`, `fullname` and `phone`, and followed to the account it was merged into.
+    ///
+    /// An account is only ever logged in by the provider that created it: the account of `email` is refused when it logs in some other way.
+    /// So are disabled accounts. Both are reported to `events`, and the user is recorded in the `user_id` of the current span.
+    #[cfg_attr(not(feature = "phone"), allow(unused_variables))]
+    #[allow(clippy::too_many_arguments)]
+    pub(super) async fn provision<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>, E: SecurityEvents>(db: &DB, events: &E, login: Login, email: Email, username: String, fullname: String, phone: Option<String>) -> Result<User, Error>
+    where
+        Error: From<DB::Error>,
+        E::Error: Display
+    {
+        let user = match db.get_user_by_email(email.clone()).await? {
+            Some(user) => {
+                if user.login != login {
+                    Authentication::failed_login(events, LoginFailure::WrongPassword, Some(user.id)).await;
+                    return Err(Error::InvalidCredentials);
+                }
+                if user.fullname != fullname {
+                    let update = Map::from_iter([(String::from("fullname"), Value::String(fullname))]);
+                    db.update_user(user.id, update).await?;
+                }
+                user
+            },
+            None => {
+                let now = Utc::now();
+                let user = User {
+                    id: Id::default(),
+                    username,
+                    fullname,
+                    email: Some(email),
+                    #[cfg(feature = "phone")]
+                    phone: Some(crate::types::Phone::try_from(phone.ok_or(crate::types::ConversionError::MissingFields(&["phone"]))?)?),
+                    login,
+                    profile: None,
+                    avatar: None,
+                    status: Status::Active,
+                    status_reason: None,
+                    previous_username: None,
+                    username_changed_at: None,
+                    metadata: Default::default(),
+                    consents: Default::default(),
+                    created_at: now,
+                    updated_at: now,
+                };
+                db.create_user(user.clone()).await?;
+                user
+            },
+        };
+        let user = Accounts::follow(db, user).await?;
+        Span::current().record("user_id", user.id.to_hex());
+        if !user.status.can_login() {
+            Authentication::failed_login(events, LoginFailure::AccountDisabled, Some(user.id)).await;
+            return Err(Error::AccountDisabled(user.status));
+        }
+        Ok(user)
+    }
+}
@@ -0,0 +1,95 @@
+use crate::types::{Error, Device, Location};
+use crate::ports::outputs::geoip::GeoIp;
+use crate::config::GeoBlockingConfig;
+use std::collections::HashSet;
+use std::fmt::Display;
+
+
+/// Where requests come from, and the countries they are taken from. see [`GeoBlockingConfig`].
+///
+/// The location of a device is recorded on the session it starts, and can be added to the security events of its
+/// requests with `SecurityEvent::located`.
+#[derive(Debug, Clone)]
+pub struct Geolocation {
+    allow: HashSet<String>,
+    deny: HashSet<String>,
+    allow_unknown: bool,
+}
+
+
+impl Geolocation {
+    /// Records where `device` is, as told by `geoip`, then fails with `Error::CountryNotAllowed` if its country is refused.
+    /// A failed lookup is logged and leaves the device unlocated.
+    pub async fn locate<G: GeoIp>(&self, geoip: &G, device: &mut Device) -> Result<(), Error>
+    where
+        G::Error: Display
+    {
+        if let Some(ip) = device.ip {
+            match geoip.locate(ip).await {
+                Ok(location) => device.location = location,
+                Err(err) => tracing::warn!(error = %err, %ip, "could not locate the address"),
+            }
+        }
+        self.check(device.location.as_ref())
+    }
+
+    /// Fails with `Error::CountryNotAllowed` unless requests are taken from `location`.
+    pub fn check(&self, location: Option<&Location>) -> Result<(), Error> {
+        match location.and_then(|location| location.country.as_deref()) {
+            Some(country) if self.deny.contains(country) || (!self.allow.is_empty() && !self.allow.contains(country)) => {
+                tracing::warn!(country, "request refused by geo-blocking");
+                Err(Error::CountryNotAllowed(Some(country.to_string())))
+            },
+            None if !self.allow.is_empty() && !self.allow_unknown => {
+                tracing::warn!("request from an unknown country refused by geo-blocking");
+                Err(Error::CountryNotAllowed(None))
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+
+impl From<&GeoBlockingConfig> for Geolocation {
+    fn from(config: &GeoBlockingConfig) -> Self {
+        Self {
+            allow: config.allow.iter().cloned().collect(),
+            deny: config.deny.iter().cloned().collect(),
+            allow_unknown: config.allow_unknown,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use std::net::IpAddr;
+
+    struct Nairobi;
+
+    impl GeoIp for Nairobi {
+        type Error = Infallible;
+
+        async fn locate(&self, _: IpAddr) -> Result<Option<Location>, Self::Error> {
+            Ok(Some(Location { country: Some("KE".into()), city: Some("Nairobi".into()), ..Default::default() }))
+        }
+    }
+
+    fn geolocation(config: serde_json::Value) -> Geolocation {
+        Geolocation::from(&serde_json::from_value::<GeoBlockingConfig>(config).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_devices_are_located_and_blocked_by_country() {
+        let mut device = Device::new(Some([192, 0, 2, 1].into()), None);
+        geolocation(serde_json::json!({})).locate(&Nairobi, &mut device).await.unwrap();
+        assert_eq!(device.location.as_ref().map(Location::to_string).as_deref(), Some("Nairobi, KE"));
+        let denied = geolocation(serde_json::json!({"deny": ["KE"]})).locate(&Nairobi, &mut device).await;
+        assert_eq!(denied, Err(Error::CountryNotAllowed(Some("KE".into()))));
+        assert_eq!(geolocation(serde_json::json!({"allow": ["UG"]})).check(device.location.as_ref()), Err(Error::CountryNotAllowed(Some("KE".into()))));
+        assert_eq!(geolocation(serde_json::json!({"allow": ["UG"]})).check(None), Ok(()));
+        assert_eq!(geolocation(serde_json::json!({"allow": ["UG"], "allow_unknown": false})).check(None), Err(Error::CountryNotAllowed(None)));
+    }
+}
@@ -0,0 +1,212 @@
+use crate::ports::outputs::database::{Database, tables::{UsersTable, SessionsTable}};
+use crate::types::{Error, User, Id, Session, Status, Login, Device, TokenBundle, DatabaseError, DomainEventKind, Document, Consent, Namespace};
+use super::{Password, PasswordService, Tokenizer, UsernameRules, SignupRules, Profiles, UserMetadata, Events};
+use crate::ports::outputs::events::EventPublisher;
+use super::authentication::current_version;
+use crate::config::{GuestsConfig, ConsentConfig};
+use chrono::{TimeDelta, Utc};
+use super::sessions::Sessions;
+use serde_json::{Map, Value};
+use tracing::instrument;
+use std::fmt::Display;
+
+
+/// the scope of the tokens issued to guests, which resource servers hold to what guests may do.
+pub const GUEST_SCOPE: &str = "guest";
+
+
+/// Guest accounts, for products letting people try them before signing up. see [`GuestsConfig`].
+///
+/// A guest is a user without contacts that logs in with [`Login::Guest`], ie with nothing but the tokens it was issued,
+/// so it never logs in again once they are lost. It upgrades to a full account by signing up under its own id,
+/// which keeps whatever the product stored for it. Guests that did not upgrade in time are deleted by the
+/// `purge_guests` job, along with their sessions.
+#[derive(Debug, Clone)]
+pub struct Guests {
+    enabled: bool,
+    ttl: TimeDelta,
+    page_size: usize,
+}
+
+
+impl Guests {
+    /// Starts a guest from `device`, and issues its first tokens, of the [`GUEST_SCOPE`] scope.
+    #[instrument(skip_all, err)]
+    pub async fn start<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, SessionsTable: SessionsTable<DB::Client, Item = Session>>, T: Tokenizer, B: EventPublisher>(&self, db: &DB, tokenizer: &T, publisher: &B, device: Device) -> Result<TokenBundle, Error>
+    where
+        Error: From<DB::Error>,
+        Error: From<T::Error>,
+        T::Error: From<DB::Error>,
+        B::Error: Display
+    {
+        if !self.enabled {
+            return Err(Error::GuestsDisabled);
+        }
+        let now = Utc::now();
+        let id = Id::default();
+        let expires_at = now + self.ttl;
+        let guest = User {
+            id,
+            username: UsernameRules::placeholder(id),
+            fullname: String::new(),
+            #[cfg(feature = "email")]
+            email: None,
+            #[cfg(feature = "phone")]
+            phone: None,
+            login: Login::Guest(expires_at),
+            profile: None,
+            avatar: None,
+            status: Status::Active,
+            status_reason: None,
+            previous_username: None,
+            username_changed_at: None,
+            metadata: Default::default(),
+            consents: Default::default(),
+            created_at: now,
+            updated_at: now,
+        };
+        Events::create_user(db, guest, publisher, DomainEventKind::GuestCreated { user_id: id, expires_at }).await?;
+        Ok(tokenizer.generate_token(db, id, device, Some(GUEST_SCOPE)).await?)
+    }
+
+    /// Turns the guest `id` into a full account, with the username, name, contacts and password of `upgrade`.
+    ///
+    /// `upgrade` is checked like a user signing up, by `signup` and `usernames`, and accepts the current versions of the
+    /// documents of `consent`. Without a username, the guest keeps its placeholder.
+    /// The guest keeps its id, metadata and sessions, and `guest.upgraded` is published to `publisher`.
+    /// The tokens it holds, and the ones renewed from them, keep the guest scope: it logs in again for tokens of its account.
+    #[instrument(skip(self, db, upgrade, passwords, usernames, signup, consent, publisher), fields(user_id = %id.to_hex()), err)]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn upgrade<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>, P: Password + Send + Sync + 'static, B: EventPublisher>(&self, db: &DB, id: Id, mut upgrade: User, passwords: &PasswordService<P>, usernames: &UsernameRules, signup: &SignupRules, consent: &ConsentConfig, publisher: &B) -> Result<User, Error>
+    where
+        Error: From<DB::Error>,
+        B::Error: Display
+    {
+        let guest = db.get_user_by_id(id).await?.ok_or(DatabaseError::UserNotFound)?;
+        let now = Utc::now();
+        match guest.login.guest_expires_at() {
+            None => return Err(Error::NotAGuest),
+            // it is only waiting to be purged.
+            Some(expires_at) if expires_at <= now => return Err(DatabaseError::UserNotFound.into()),
+            Some(_) => {},
+        }
+        signup.check(&mut upgrade)?;
+        if upgrade.username.is_empty() {
+            upgrade.username = UsernameRules::placeholder(id);
+        } else {
+            Profiles::check_username(db, &upgrade.username, usernames, now).await?;
+        }
+        let hash = passwords.hash_password(upgrade.login.password()?.clone()).await?;
+        #[cfg_attr(not(any(feature = "email", feature = "phone")), allow(unused_mut))]
+        let mut update = Map::from_iter([
+            (String::from("username"), Value::String(upgrade.username.clone())),
+            (String::from("fullname"), Value::String(upgrade.fullname)),
+        ]);
+        #[cfg(feature = "email")]
+        update.insert(String::from("email"), serde_json::to_value(&upgrade.email).map_err(|err| DatabaseError::Internal(Box::new(err)))?);
+        #[cfg(feature = "phone")]
+        update.insert(String::from("phone"), serde_json::to_value(&upgrade.phone).map_err(|err| DatabaseError::Internal(Box::new(err)))?);
+        // the contacts go first, so that an upgrade refused for a taken contact leaves a guest that can try again.
+        db.update_user(id, update).await?;
+        db.set_user_password(id, hash).await?;
+        if !upgrade.metadata.service.is_empty() {
+            UserMetadata::update(db, id, Namespace::Service, std::mem::take(&mut upgrade.metadata.service)).await?;
+        }
+        for document in Document::ALL {
+            if let Some(version) = current_version(consent, document) {
+                db.set_user_consent(id, document, Consent::new(version)).await?;
+            }
+        }
+        Events::publish(publisher, DomainEventKind::GuestUpgraded { user_id: id, username: upgrade.username }).await?;
+        Ok(db.get_user_by_id(id).await?.ok_or(DatabaseError::UserNotFound)?)
+    }
+
+    /// Deletes every guest that expired, with its sessions. Returns the number of guests deleted.
+    #[instrument(skip_all, err)]
+    pub async fn purge_expired<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, db: &DB) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>
+    {
+        let now = Utc::now();
+        let mut after = None;
+        let mut purged = 0;
+        loop {
+            let page = db.list_users(after, self.page_size.max(1)).await?;
+            for guest in page.items.iter().filter(|user| user.login.guest_expires_at().is_some_and(|expires_at| expires_at <= now)) {
+                Sessions::delete_all(db, guest.id, None).await?;
+                db.delete_user(guest.id).await?;
+                purged += 1;
+            }
+            match page.next {
+                Some(next) => after = Some(next),
+                None => break Ok(purged),
+            }
+        }
+    }
+}
+
+
+impl From<&GuestsConfig> for Guests {
+    fn from(config: &GuestsConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            ttl: TimeDelta::days(config.ttl_days.into()),
+            page_size: config.page_size,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptors::outputs::events::EventSink;
+    use crate::config::UsernamesConfig;
+    use crate::testing::{Harness, UserFixture};
+
+    fn guests(enabled: bool) -> Guests {
+        Guests::from(&serde_json::from_value::<GuestsConfig>(serde_json::json!({"enabled": enabled})).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_guests_start_without_contacts() {
+        let harness = Harness::new();
+        assert_eq!(guests(false).start(&harness.db, &harness.tokens, &EventSink::Disabled, Device::default()).await, Err(Error::GuestsDisabled));
+        let bundle = guests(true).start(&harness.db, &harness.tokens, &EventSink::Disabled, Device::default()).await.unwrap();
+        assert_eq!(bundle.scope.as_deref(), Some(GUEST_SCOPE));
+        let session = harness.db.get_session_by_id(Id::try_from(bundle.access_token).unwrap()).await.unwrap().unwrap();
+        let guest = harness.db.get_user_by_id(session.user_id).await.unwrap().unwrap();
+        assert!(guest.login.guest_expires_at().is_some_and(|expires_at| expires_at > Utc::now() + TimeDelta::days(29)));
+        #[cfg(feature = "email")]
+        assert_eq!(guest.email, None);
+    }
+
+    #[tokio::test]
+    async fn test_guests_upgrade_keeping_their_id() {
+        let harness = Harness::new();
+        let bundle = guests(true).start(&harness.db, &harness.tokens, &EventSink::Disabled, Device::default()).await.unwrap();
+        let id = harness.db.get_session_by_id(Id::try_from(bundle.access_token).unwrap()).await.unwrap().unwrap().user_id;
+        let usernames = UsernameRules::from(&UsernamesConfig::default());
+        let upgrade = |username: &str| UserFixture::new().username(username).password("secret").build();
+        let upgraded = guests(true).upgrade(&harness.db, id, upgrade("jane"), &harness.passwords, &usernames, &SignupRules::default(), &ConsentConfig::default(), &EventSink::Disabled).await.unwrap();
+        assert_eq!((upgraded.id, upgraded.username.as_str()), (id, "jane"));
+        assert_eq!(upgraded.login, Login::Password(String::from("secret")));
+        assert_eq!(harness.db.get_sessions_by_user_id(id).await.unwrap().len(), 1);
+        let again = guests(true).upgrade(&harness.db, id, upgrade("janet"), &harness.passwords, &usernames, &SignupRules::default(), &ConsentConfig::default(), &EventSink::Disabled).await;
+        assert_eq!(again, Err(Error::NotAGuest));
+    }
+
+    #[tokio::test]
+    async fn test_expired_guests_are_purged() {
+        let harness = Harness::new();
+        let expired = harness.create(UserFixture::new().login(Login::Guest(Utc::now() - TimeDelta::minutes(1)))).await;
+        let current = harness.create(UserFixture::new().login(Login::Guest(Utc::now() + TimeDelta::days(1)))).await;
+        let user = harness.create(UserFixture::new()).await;
+        harness.tokens.generate_token(&harness.db, expired.id, Device::default(), None).await.unwrap();
+        assert_eq!(guests(true).purge_expired(&harness.db).await, Ok(1));
+        assert_eq!(harness.db.get_user_by_id(expired.id).await.unwrap(), None);
+        assert_eq!(harness.db.get_sessions_by_user_id(expired.id).await.unwrap(), vec![]);
+        assert!(harness.db.get_user_by_id(current.id).await.unwrap().is_some());
+        assert!(harness.db.get_user_by_id(user.id).await.unwrap().is_some());
+    }
+}
@@ -0,0 +1,226 @@
+use crate::types::{Error, Id, SigningKey, KeyAlgorithm, KeyStatus, DatabaseError};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use crate::ports::outputs::keys::KeyStore;
+use serde_json::{Value, json};
+use super::tokenization::paseto;
+use rusty_paseto::core::Key;
+use chrono::{DateTime, Utc};
+use crate::config::Config;
+use tracing::instrument;
+use base64::Engine;
+
+
+/// the kid of the key of `tokens.signing_key`.
+pub const CONFIGURED_KID: &str = "config";
+
+
+/// The keys tokens are signed with under `v4.public`, rotated without a restart.
+///
+/// Keys are generated ahead of their activation so that resource servers fetch them from the key set before they sign
+/// anything. The active key is the non-retired key activated last, the key of `tokens.signing_key` when no stored key is
+/// active yet. Superseded keys stay in the key set until they are retired, so that the tokens they signed still verify.
+pub struct Keyring {
+    configured: Option<SigningKey>,
+}
+
+
+impl Keyring {
+    /// Generates a new key, signing from `activates_at` on, or right away when it is missing.
+    #[instrument(skip_all, err)]
+    pub async fn generate<S: KeyStore>(&self, store: &S, activates_at: Option<DateTime<Utc>>) -> Result<SigningKey, Error>
+    where
+        Error: From<S::Error>
+    {
+        let mut seed = [0u8; 32];
+        rand::fill(&mut seed);
+        let pair = ed25519_dalek::SigningKey::from_bytes(&seed);
+        let now = Utc::now();
+        let key = SigningKey {
+            kid: Id::default().to_hex(),
+            algorithm: KeyAlgorithm::Ed25519,
+            key_pair: hex(&pair.to_keypair_bytes()),
+            public_key: hex(pair.verifying_key().as_bytes()),
+            created_at: now,
+            activates_at: activates_at.unwrap_or(now),
+            retired_at: None,
+        };
+        store.create(key.clone()).await?;
+        tracing::info!(kid = key.kid, activates_at = %key.activates_at, "generated a signing key");
+        Ok(key)
+    }
+
+    /// Moves the activation of a key to `at`. Retired keys are never activated again.
+    #[instrument(skip(self, store), err)]
+    pub async fn activate<S: KeyStore>(&self, store: &S, kid: &str, at: DateTime<Utc>) -> Result<SigningKey, Error>
+    where
+        Error: From<S::Error>
+    {
+        let mut key = self.stored(store, kid).await?;
+        if key.retired_at.is_some() {
+            return Err(Error::InvalidKeyChange("the key is retired"));
+        }
+        key.activates_at = at;
+        store.update(key.clone()).await?;
+        Ok(key)
+    }
+
+    /// Retires a key, taking it out of the key set. The tokens it signed stop verifying, so the active key is never retired.
+    #[instrument(skip(self, store), err)]
+    pub async fn retire<S: KeyStore>(&self, store: &S, kid: &str) -> Result<SigningKey, Error>
+    where
+        Error: From<S::Error>
+    {
+        let mut key = self.stored(store, kid).await?;
+        let now = Utc::now();
+        match Self::status(&key, &self.keys(store).await?, now) {
+            KeyStatus::Active => return Err(Error::InvalidKeyChange("the key is active, activate another key first")),
+            KeyStatus::Retired => return Ok(key),
+            KeyStatus::Scheduled | KeyStatus::Superseded => {},
+        }
+        key.retired_at = Some(now);
+        store.update(key.clone()).await?;
+        Ok(key)
+    }
+
+    /// Every key with its status at `now`, oldest first, the key of `tokens.signing_key` included.
+    pub async fn list<S: KeyStore>(&self, store: &S, now: DateTime<Utc>) -> Result<Vec<(SigningKey, KeyStatus)>, Error>
+    where
+        Error: From<S::Error>
+    {
+        let keys = self.keys(store).await?;
+        let mut statuses = keys.iter().map(|key| (key.clone(), Self::status(key, &keys, now))).collect::<Vec<_>>();
+        statuses.sort_by_key(|(key, _)| key.created_at);
+        Ok(statuses)
+    }
+
+    /// The kid and key pair new tokens are signed with at `now`, if there is any.
+    pub async fn signing_key<S: KeyStore>(&self, store: &S, now: DateTime<Utc>) -> Result<Option<(String, Key<64>)>, Error>
+    where
+        Error: From<S::Error>
+    {
+        let keys = self.keys(store).await?;
+        Ok(keys.iter()
+            .find(|key| Self::status(key, &keys, now) == KeyStatus::Active)
+            .and_then(|key| Some((key.kid.clone(), paseto::signing_key(&key.key_pair)?))))
+    }
+
+    /// The JSON Web Key Set of the keys that are not retired at `now`, scheduled ones included.
+    pub async fn jwks<S: KeyStore>(&self, store: &S, now: DateTime<Utc>) -> Result<Value, Error>
+    where
+        Error: From<S::Error>
+    {
+        let keys = self.list(store, now).await?.into_iter()
+            .filter(|(_, status)| *status != KeyStatus::Retired)
+            // `Key` panics on keys of another length.
+            .filter_map(|(key, _)| Some((Some(&key.public_key).filter(|public| public.len() == 64).and_then(|public| Key::<32>::try_from(public.as_str()).ok())?, key.kid)))
+            .map(|(public, kid)| json!({"kty": "OKP", "crv": "Ed25519", "use": "sig", "kid": kid, "x": URL_SAFE_NO_PAD.encode(*public)}))
+            .collect::<Vec<_>>();
+        Ok(json!({"keys": keys}))
+    }
+
+    /// The status of `key` among `keys` at `now`.
+    fn status(key: &SigningKey, keys: &[SigningKey], now: DateTime<Utc>) -> KeyStatus {
+        let live = |key: &SigningKey| key.retired_at.is_none_or(|retired_at| retired_at > now);
+        if !live(key) {
+            return KeyStatus::Retired;
+        }
+        if key.activates_at > now {
+            return KeyStatus::Scheduled;
+        }
+        let active = keys.iter()
+            .filter(|key| live(key) && key.activates_at <= now)
+            .max_by_key(|key| (key.activates_at, key.created_at));
+        match active {
+            Some(active) if active.kid == key.kid => KeyStatus::Active,
+            _ => KeyStatus::Superseded,
+        }
+    }
+
+    async fn keys<S: KeyStore>(&self, store: &S) -> Result<Vec<SigningKey>, Error>
+    where
+        Error: From<S::Error>
+    {
+        let mut keys = store.list().await?;
+        keys.extend(self.configured.clone());
+        Ok(keys)
+    }
+
+    /// The stored key `kid`. The key of `tokens.signing_key` is changed in the configuration only.
+    async fn stored<S: KeyStore>(&self, store: &S, kid: &str) -> Result<SigningKey, Error>
+    where
+        Error: From<S::Error>
+    {
+        if self.configured.as_ref().is_some_and(|key| key.kid == kid) {
+            return Err(Error::InvalidKeyChange("the key is configured by tokens.signing_key"));
+        }
+        Ok(store.get(kid).await?.ok_or(DatabaseError::KeyNotFound)?)
+    }
+}
+
+
+impl From<&Config> for Keyring {
+    fn from(config: &Config) -> Self {
+        // the configured key is older than any stored key, so that it only signs until one is activated.
+        let configured = paseto::signing_key(&config.tokens.signing_key).map(|pair| SigningKey {
+            kid: String::from(CONFIGURED_KID),
+            algorithm: KeyAlgorithm::Ed25519,
+            key_pair: config.tokens.signing_key.clone(),
+            public_key: hex(&pair[32..]),
+            created_at: DateTime::UNIX_EPOCH,
+            activates_at: DateTime::UNIX_EPOCH,
+            retired_at: None,
+        });
+        Self { configured }
+    }
+}
+
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptors::outputs::keys::memory::MemoryKeys;
+    use rusty_paseto::core::{Paseto, PasetoAsymmetricPublicKey, Public, V4};
+    use chrono::TimeDelta;
+
+    const SIGNING_KEY: &str = "b4cbfb43df4ce210727d953e4a713307fa19bb7d9f85041438d9e11b942a37741eb9dbbbbc047c03fd70604e0071f0987e16b28b757225c11f00415d0e20b1a2";
+
+    #[tokio::test]
+    async fn test_keys_rotate_from_the_configured_key() {
+        let mut config = Config::default();
+        config.tokens.signing_key = String::from(SIGNING_KEY);
+        let (keyring, store, now) = (Keyring::from(&config), MemoryKeys::new(), Utc::now());
+        assert_eq!(keyring.signing_key(&store, now).await.unwrap().map(|(kid, _)| kid).as_deref(), Some(CONFIGURED_KID));
+        let next = keyring.generate(&store, Some(now + TimeDelta::hours(1))).await.unwrap();
+        let statuses = keyring.list(&store, now).await.unwrap().into_iter().map(|(key, status)| (key.kid, status)).collect::<Vec<_>>();
+        assert_eq!(statuses, vec![(String::from(CONFIGURED_KID), KeyStatus::Active), (next.kid.clone(), KeyStatus::Scheduled)]);
+        let jwks = keyring.jwks(&store, now).await.unwrap();
+        assert_eq!(jwks["keys"].as_array().map(Vec::len), Some(2));
+        assert_eq!(jwks["keys"][0]["x"], Value::from("Hrnbu7wEfAP9cGBOAHHwmH4Wsot1ciXBHwBBXQ4gsaI"));
+        let later = now + TimeDelta::hours(2);
+        let (kid, key) = keyring.signing_key(&store, later).await.unwrap().unwrap();
+        assert_eq!(kid, next.kid);
+        let token = paseto::sign("{}", &key).unwrap();
+        let public = Key::<32>::try_from(next.public_key.as_str()).unwrap();
+        assert!(Paseto::<V4, Public>::try_verify(&token, &PasetoAsymmetricPublicKey::from(&public), None, None).is_ok());
+        assert_eq!(keyring.retire(&store, CONFIGURED_KID).await, Err(Error::InvalidKeyChange("the key is configured by tokens.signing_key")));
+        assert_eq!(keyring.retire(&store, "unknown").await, Err(Error::DatabaseError(DatabaseError::KeyNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_the_active_key_is_never_retired() {
+        let (keyring, store) = (Keyring::from(&Config::default()), MemoryKeys::new());
+        let first = keyring.generate(&store, None).await.unwrap();
+        assert!(matches!(keyring.retire(&store, &first.kid).await, Err(Error::InvalidKeyChange(_))));
+        let second = keyring.generate(&store, None).await.unwrap();
+        let retired = keyring.retire(&store, &first.kid).await.unwrap();
+        assert!(retired.retired_at.is_some());
+        assert!(matches!(keyring.activate(&store, &first.kid, Utc::now()).await, Err(Error::InvalidKeyChange(_))));
+        let jwks = keyring.jwks(&store, Utc::now()).await.unwrap();
+        assert_eq!(jwks["keys"].as_array().map(|keys| keys.iter().map(|key| key["kid"].clone()).collect::<Vec<_>>()), Some(vec![Value::from(second.kid)]));
+    }
+}
@@ -0,0 +1,81 @@
+use crate::types::{Error, ConversionError, FieldError};
+use crate::config::LimitsConfig;
+use serde_json::Value;
+
+
+/// The limits of `limits` on the bodies of requests.
+///
+/// The HTTP layer names the group of every route, as it does for `NetworkAccess`, stops reading a body past
+/// [`RequestLimits::max_body_bytes`] and parses it with [`RequestLimits::json`]. Nothing too deep or too long then reaches
+/// the `Payload`s, the `Map` updates of the tables or the memory database, which all walk values recursively.
+pub struct RequestLimits {
+    config: LimitsConfig,
+}
+
+
+impl RequestLimits {
+    /// The most bytes of the bodies of the routes of `group`.
+    pub fn max_body_bytes(&self, group: &str) -> usize {
+        self.config.routes.get(group).copied().unwrap_or(self.config.max_body_bytes)
+    }
+
+    /// The JSON body of a request to a route of `group`, once it is known to be within every limit.
+    pub fn json(&self, group: &str, body: &[u8]) -> Result<Value, Error> {
+        let limit = self.max_body_bytes(group);
+        if body.len() > limit {
+            return Err(Error::PayloadTooLarge(limit));
+        }
+        let value = serde_json::from_slice(body).map_err(|err| ConversionError::InvalidFields(vec![FieldError { field: String::new(), detail: err.to_string() }]))?;
+        self.check(&value)?;
+        Ok(value)
+    }
+
+    /// Fails unless the arrays and objects of `value` are nested and sized within the limits.
+    ///
+    /// The value is walked with a stack of its own, so that checking a deep value is not what overflows.
+    pub fn check(&self, value: &Value) -> Result<(), ConversionError> {
+        let mut values = vec![(value, 1)];
+        while let Some((value, depth)) = values.pop() {
+            let children: Box<dyn Iterator<Item = &Value>> = match value {
+                Value::Array(items) if items.len() > self.config.max_array_length => return Err(ConversionError::ArrayTooLong(self.config.max_array_length)),
+                Value::Array(items) => Box::new(items.iter()),
+                Value::Object(fields) => Box::new(fields.values()),
+                _ => continue,
+            };
+            if depth > self.config.max_json_depth {
+                return Err(ConversionError::TooDeep(self.config.max_json_depth));
+            }
+            values.extend(children.map(|child| (child, depth + 1)));
+        }
+        Ok(())
+    }
+}
+
+
+impl From<&LimitsConfig> for RequestLimits {
+    fn from(config: &LimitsConfig) -> Self {
+        Self { config: config.clone() }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_bodies_are_checked_against_the_limits_of_their_route() {
+        let config = LimitsConfig { max_body_bytes: 32, routes: HashMap::from([(String::from("admin"), 64)]), max_json_depth: 2, max_array_length: 3 };
+        let limits = RequestLimits::from(&config);
+        let body = br#"{"fullname": "Jane", "profile": "hello"}"#;
+        assert_eq!(limits.json("users", body), Err(Error::PayloadTooLarge(32)));
+        assert_eq!(limits.json("admin", body), Ok(json!({"fullname": "Jane", "profile": "hello"})));
+        assert!(matches!(limits.json("admin", b"{\"fullname\":"), Err(Error::ConversionError(ConversionError::InvalidFields(_)))));
+        assert_eq!(limits.check(&json!({"tags": ["a", "b"]})), Ok(()));
+        assert_eq!(limits.check(&json!({"tags": [["a"]]})), Err(ConversionError::TooDeep(2)));
+        assert_eq!(limits.check(&json!({"tags": [1, 2, 3, 4]})), Err(ConversionError::ArrayTooLong(3)));
+        assert_eq!(limits.check(&json!("scalar")), Ok(()));
+    }
+}
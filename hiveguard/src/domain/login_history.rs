@@ -0,0 +1,71 @@
+use crate::ports::outputs::login_history::LoginHistory;
+use crate::types::{Error, Id, LoginAttempt};
+use crate::config::LoginHistoryConfig;
+use tracing::instrument;
+use std::fmt::Display;
+
+
+/// The login history users review their account with. see [`LoginHistoryConfig`].
+///
+/// `Authentication::login` records every attempt naming a user, be it successful or refused for a wrong password or the
+/// state of the account. Losing an attempt is logged rather than failing the login.
+#[derive(Debug, Clone)]
+pub struct LoginAttempts {
+    limit: usize,
+}
+
+
+impl LoginAttempts {
+    /// The most recent attempts of the user, at most `limit` of them, and never more than `login_history.limit`.
+    #[instrument(skip(self, history), fields(user_id = %user_id.to_hex()), err)]
+    pub async fn recent<H: LoginHistory>(&self, history: &H, user_id: Id, limit: Option<usize>) -> Result<Vec<LoginAttempt>, Error>
+    where
+        Error: From<H::Error>
+    {
+        let limit = limit.unwrap_or(self.limit).clamp(1, self.limit.max(1));
+        Ok(history.recent(user_id, limit).await?)
+    }
+
+    pub(super) async fn record<H: LoginHistory>(history: &H, attempt: LoginAttempt)
+    where
+        H::Error: Display
+    {
+        if let Err(err) = history.record(attempt).await {
+            tracing::error!(error = %err, "could not record the login attempt");
+        }
+    }
+}
+
+
+impl From<&LoginHistoryConfig> for LoginAttempts {
+    fn from(config: &LoginHistoryConfig) -> Self {
+        Self { limit: config.limit }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptors::outputs::login_history::memory::MemoryHistory;
+    use crate::types::{Device, LoginOutcome, LoginFailure};
+    use chrono::{TimeDelta, Utc};
+
+    #[tokio::test]
+    async fn test_recent_attempts_come_first_up_to_the_limit() {
+        let history = MemoryHistory::new(TimeDelta::days(30));
+        let user_id = Id::default();
+        let attempt = |days: i64, outcome| LoginAttempt { occurred_at: Utc::now() - TimeDelta::days(days), ..LoginAttempt::new(user_id, Device::default(), outcome) };
+        let failed = LoginOutcome::Failed { reason: LoginFailure::WrongPassword };
+        for recorded in [attempt(40, LoginOutcome::Succeeded), attempt(2, failed), attempt(1, LoginOutcome::Succeeded), attempt(3, LoginOutcome::Succeeded)] {
+            LoginAttempts::record(&history, recorded).await;
+        }
+        LoginAttempts::record(&history, LoginAttempt::new(Id::default(), Device::default(), LoginOutcome::Succeeded)).await;
+        let attempts = LoginAttempts::from(&LoginHistoryConfig { limit: 2, ..Default::default() });
+        let recent = attempts.recent(&history, user_id, None).await.unwrap();
+        assert_eq!(recent.iter().map(|attempt| attempt.outcome).collect::<Vec<_>>(), vec![LoginOutcome::Succeeded, failed]);
+        assert_eq!(attempts.recent(&history, user_id, Some(100)).await.unwrap().len(), 2);
+        let everything = LoginAttempts::from(&LoginHistoryConfig::default()).recent(&history, user_id, None).await.unwrap();
+        assert_eq!(everything.len(), 3);
+    }
+}
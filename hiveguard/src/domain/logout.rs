@@ -0,0 +1,155 @@
+use crate::ports::outputs::database::{Database, tables::SessionsTable};
+use crate::types::{Error, Id, Session, LogoutToken};
+use crate::ports::outputs::logout::LogoutNotifier;
+use super::tokenization::paseto;
+use super::sessions::Sessions;
+use super::redirects::RedirectUris;
+use rusty_paseto::core::Key;
+use super::services::Services;
+use crate::config::Config;
+use tracing::instrument;
+use std::fmt::Display;
+use url::Url;
+
+
+/// Logging out of hiveguard and of the services the user logged in to, as of OpenID Connect RP-Initiated
+/// and Back-Channel Logout.
+///
+/// Every service with a `backchannel_logout_uri` is sent a [`LogoutToken`] when a session ends, whether it
+/// took part in the session or not, as sessions do not record the services they were used by.
+pub struct Logout {
+    issuer: String,
+    signing_key: Option<Key<64>>,
+    services: Services,
+    redirects: RedirectUris,
+    /// the client id of every service notified of ended sessions, with where it is notified.
+    back_channels: Vec<(String, Url)>,
+}
+
+
+impl Logout {
+    /// Ends the session at the request of a service, and returns where to redirect the user to afterwards, if anywhere.
+    ///
+    /// `post_logout_redirect_uri` has to be registered by the service `client_id`, and is given back `state` when set.
+    /// An unknown or already ended session is not an error, so that logging out twice redirects the same way.
+    #[instrument(skip(self, db, notifier, state), fields(session_id = %session_id.to_hex()), err)]
+    pub async fn end_session<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>, N: LogoutNotifier>(&self, db: &DB, notifier: &N, session_id: Id, client_id: Option<&str>, post_logout_redirect_uri: Option<&str>, state: Option<&str>) -> Result<Option<Url>, Error>
+    where
+        Error: From<DB::Error>,
+        N::Error: Display
+    {
+        let redirect = match post_logout_redirect_uri {
+            Some(uri) => {
+                let client_id = client_id.ok_or_else(|| Error::InvalidRedirectUri(String::from("a post logout redirect uri needs the client id of its service")))?;
+                let mut url = self.redirects.check(&self.services.get(client_id)?.post_logout_redirect_uris, uri)?;
+                if let Some(state) = state {
+                    url.query_pairs_mut().append_pair("state", state);
+                }
+                Some(url)
+            },
+            None => None,
+        };
+        if let Some(session) = db.get_session_by_id(session_id).await? {
+            Sessions::delete(db, session.id).await?;
+            self.sessions_ended(notifier, session.user_id, &[session.id]).await;
+        }
+        Ok(redirect)
+    }
+
+    /// Sends a logout token for every session of `sessions`, ended by `user_id`, to every service with a back-channel.
+    ///
+    /// To be called wherever sessions end. Services that could not be notified are logged and skipped.
+    /// Returns the number of logout tokens delivered.
+    pub async fn sessions_ended<N: LogoutNotifier>(&self, notifier: &N, user_id: Id, sessions: &[Id]) -> usize
+    where
+        N::Error: Display
+    {
+        let Some(key) = &self.signing_key else {
+            return 0;
+        };
+        let mut delivered = 0;
+        for (client_id, uri) in &self.back_channels {
+            for session_id in sessions {
+                let token = LogoutToken::new(self.issuer.clone(), client_id.clone(), user_id, *session_id);
+                let Some(token) = serde_json::to_string(&token).ok().and_then(|payload| paseto::sign(&payload, key)) else {
+                    tracing::error!(client_id, "could not sign the logout token");
+                    continue;
+                };
+                match notifier.notify(uri, &token).await {
+                    Ok(()) => delivered += 1,
+                    Err(err) => tracing::warn!(error = %err, client_id, "could not notify the service of the logout"),
+                }
+            }
+        }
+        delivered
+    }
+}
+
+
+impl From<&Config> for Logout {
+    fn from(config: &Config) -> Self {
+        let back_channels = config.services.iter()
+            .filter_map(|service| Some((service.client_id.clone(), Url::parse(service.backchannel_logout_uri.as_deref()?).ok()?)))
+            .collect();
+        Self {
+            issuer: config.issuer.clone(),
+            signing_key: paseto::signing_key(&config.tokens.signing_key),
+            services: Services::from(config),
+            redirects: RedirectUris::from(&config.redirects),
+            back_channels,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Device, BACKCHANNEL_LOGOUT_EVENT};
+    use crate::testing::Harness;
+    use rusty_paseto::core::{Paseto, PasetoAsymmetricPublicKey, Public, V4};
+    use std::sync::Mutex;
+
+    const SIGNING_KEY: &str = "b4cbfb43df4ce210727d953e4a713307fa19bb7d9f85041438d9e11b942a37741eb9dbbbbc047c03fd70604e0071f0987e16b28b757225c11f00415d0e20b1a2";
+    const PUBLIC_KEY: &str = "1eb9dbbbbc047c03fd70604e0071f0987e16b28b757225c11f00415d0e20b1a2";
+
+    /// records the logout tokens instead of posting them.
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(Url, String)>>);
+
+    impl LogoutNotifier for Recorder {
+        type Error = String;
+
+        async fn notify(&self, uri: &Url, logout_token: &str) -> Result<(), Self::Error> {
+            self.0.lock().unwrap().push((uri.clone(), String::from(logout_token)));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ending_a_session_notifies_the_services() {
+        let mut config = Config::default();
+        config.tokens.signing_key = String::from(SIGNING_KEY);
+        config.services = serde_json::from_value(serde_json::json!([
+            {"client_id": "web", "post_logout_redirect_uris": ["https://app.example.com/bye"], "backchannel_logout_uri": "https://app.example.com/logout"},
+            {"client_id": "partner"},
+        ])).unwrap();
+        let (logout, harness, recorder) = (Logout::from(&config), Harness::new(), Recorder::default());
+        let now = chrono::Utc::now();
+        let session = Session { id: Id::default(), user_id: Id::default(), refresh_token_id: Id::default(), previous_refresh_token_id: None, device: Device::default(), created_at: now, updated_at: now, last_active_at: now };
+        harness.db.create_session(session.clone()).await.unwrap();
+        let redirect = logout.end_session(&harness.db, &recorder, session.id, Some("web"), Some("https://app.example.com/bye"), Some("xyz")).await.unwrap();
+        assert_eq!(redirect.map(String::from).as_deref(), Some("https://app.example.com/bye?state=xyz"));
+        assert_eq!(harness.db.get_session_by_id(session.id).await.unwrap(), None);
+        let sent = recorder.0.lock().unwrap().clone();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0.as_str(), "https://app.example.com/logout");
+        let key = Key::<32>::try_from(PUBLIC_KEY).unwrap();
+        let payload = Paseto::<V4, Public>::try_verify(&sent[0].1, &PasetoAsymmetricPublicKey::from(&key), None, None).unwrap();
+        let token = serde_json::from_str::<LogoutToken>(&payload).unwrap();
+        assert_eq!((token.audience.as_str(), token.subject, token.session_id), ("web", session.user_id, session.id));
+        assert!(token.events.contains_key(BACKCHANNEL_LOGOUT_EVENT));
+        assert!(matches!(logout.end_session(&harness.db, &recorder, session.id, Some("partner"), Some("https://app.example.com/bye"), None).await, Err(Error::InvalidRedirectUri(_))));
+        assert_eq!(logout.end_session(&harness.db, &recorder, session.id, None, None, None).await, Ok(None));
+    }
+}
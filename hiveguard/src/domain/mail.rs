@@ -0,0 +1,129 @@
+use crate::ports::outputs::mail::{MailQueue, Mailer};
+use crate::types::{Error, Mail, MailStatus};
+use crate::config::MailQueueConfig;
+use chrono::{TimeDelta, Utc};
+use tracing::instrument;
+use std::fmt::Display;
+
+
+/// Delivers transactional emails through the mail queue.
+///
+/// Requests only enqueue their mails, so an SMTP server that is down delays them rather than failing the request.
+/// The `deliver_mail` job then sends them, retrying failures with exponential backoff until `max_attempts`
+/// after which the mail is marked dead and kept for inspection.
+pub struct Mails;
+
+
+impl Mails {
+    pub async fn enqueue<Q: MailQueue>(queue: &Q, mail: Mail) -> Result<(), Error>
+    where
+        Error: From<Q::Error>
+    {
+        Ok(queue.enqueue(mail).await?)
+    }
+
+    /// Sends a batch of the due mails. Returns the number of mails sent.
+    #[instrument(skip_all, err)]
+    pub async fn deliver<Q: MailQueue, M: Mailer>(queue: &Q, mailer: &M, config: &MailQueueConfig) -> Result<usize, Error>
+    where
+        Error: From<Q::Error>,
+        M::Error: Display
+    {
+        let mut sent = 0;
+        for mut mail in queue.due(Utc::now(), config.batch_size).await? {
+            match mailer.send(&mail).await {
+                Ok(()) => {
+                    mail.status = MailStatus::Sent;
+                    mail.last_error = None;
+                    sent += 1;
+                },
+                Err(err) => {
+                    mail.attempts += 1;
+                    mail.last_error = Some(err.to_string());
+                    if mail.attempts >= config.max_attempts {
+                        mail.status = MailStatus::Dead;
+                        tracing::error!(mail_id = %mail.id, attempts = mail.attempts, error = %err, "giving up on a mail");
+                    } else {
+                        mail.next_attempt_at = Utc::now() + backoff(config, mail.attempts);
+                        tracing::warn!(mail_id = %mail.id, attempts = mail.attempts, error = %err, "could not send a mail");
+                    }
+                },
+            }
+            mail.updated_at = Utc::now();
+            queue.update(mail).await?;
+        }
+        Ok(sent)
+    }
+}
+
+
+/// `initial_backoff_secs * 2^(attempts - 1)`, capped at `max_backoff_secs`.
+fn backoff(config: &MailQueueConfig, attempts: u32) -> TimeDelta {
+    let secs = config.initial_backoff_secs.saturating_mul(1 << (attempts - 1).min(32)).min(config.max_backoff_secs);
+    TimeDelta::seconds(secs.min(i64::MAX as u64 / 1000) as i64)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptors::outputs::mail::memory::MemoryQueue;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails the first `failures` sends.
+    struct Flaky {
+        failures: AtomicU32,
+    }
+
+    impl Mailer for Flaky {
+        type Error = &'static str;
+
+        async fn send(&self, _: &Mail) -> Result<(), Self::Error> {
+            match self.failures.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |failures| failures.checked_sub(1)) {
+                Ok(_) => Err("connection refused"),
+                Err(_) => Ok(()),
+            }
+        }
+    }
+
+    fn mail() -> Mail {
+        Mail::new("user@example.com".parse().unwrap(), "Verify your email", "123456", None)
+    }
+
+    #[tokio::test]
+    async fn test_failed_mails_are_retried_later() {
+        let queue = MemoryQueue::new();
+        let mail = mail();
+        Mails::enqueue(&queue, mail.clone()).await.unwrap();
+        let mailer = Flaky { failures: AtomicU32::new(1) };
+        let config = MailQueueConfig::default();
+        assert_eq!(Mails::deliver(&queue, &mailer, &config).await, Ok(0));
+        let retried = queue.get(mail.id).unwrap();
+        assert_eq!((retried.status, retried.attempts), (MailStatus::Pending, 1));
+        assert_eq!(retried.last_error.as_deref(), Some("connection refused"));
+        assert!(retried.next_attempt_at > Utc::now() + TimeDelta::seconds(20));
+        assert_eq!(Mails::deliver(&queue, &mailer, &config).await, Ok(0));
+        queue.update(Mail { next_attempt_at: Utc::now(), ..retried }).await.unwrap();
+        assert_eq!(Mails::deliver(&queue, &mailer, &config).await, Ok(1));
+        assert_eq!(queue.get(mail.id).unwrap().status, MailStatus::Sent);
+    }
+
+    #[tokio::test]
+    async fn test_mails_are_given_up_on_after_max_attempts() {
+        let queue = MemoryQueue::new();
+        let mail = mail();
+        queue.enqueue(Mail { attempts: 2, ..mail.clone() }).await.unwrap();
+        let mailer = Flaky { failures: AtomicU32::new(1) };
+        let config = MailQueueConfig { max_attempts: 3, ..Default::default() };
+        assert_eq!(Mails::deliver(&queue, &mailer, &config).await, Ok(0));
+        assert_eq!(queue.get(mail.id).unwrap().status, MailStatus::Dead);
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let config = MailQueueConfig { initial_backoff_secs: 30, max_backoff_secs: 100, ..Default::default() };
+        assert_eq!(backoff(&config, 1), TimeDelta::seconds(30));
+        assert_eq!(backoff(&config, 2), TimeDelta::seconds(60));
+        assert_eq!(backoff(&config, 40), TimeDelta::seconds(100));
+    }
+}
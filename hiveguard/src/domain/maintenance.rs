@@ -0,0 +1,138 @@
+use crate::ports::outputs::database::{Database, tables::{UsersTable, SessionsTable, VerificationsTable, GrantsTable}};
+use crate::types::{Error, Id, User, Session, Verification, GrantRecord};
+use crate::ports::outputs::{organisations::OrganisationStore, audit_log::AuditLog};
+use super::sessions::{Sessions, SessionPolicy};
+use super::OrgAudit;
+use tracing::instrument;
+use chrono::Utc;
+
+
+/// The clean ups run by the maintenance jobs. see `JobsConfig`.
+///
+/// Every operation returns the number of items it removed or changed.
+pub struct Maintenance;
+
+
+impl Maintenance {
+    #[instrument(skip_all, err)]
+    pub async fn purge_verifications<DB: Database<VerificationsTable: VerificationsTable<DB::Client, Item = Verification<Id>>>>(db: &DB) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>
+    {
+        Ok(db.purge_expired_verifications(Utc::now()).await?)
+    }
+
+    /// Deletes the grants that expired. Reads leave them out already, this only frees their storage.
+    #[instrument(skip_all, err)]
+    pub async fn purge_grants<DB: Database<GrantsTable: GrantsTable<DB::Client, Item = GrantRecord>>>(db: &DB) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>
+    {
+        Ok(db.purge_expired_grants(Utc::now()).await?)
+    }
+
+    /// Deletes the sessions that went unused for longer than the idle timeout of `policy` or outlived its maximum lifetime.
+    #[instrument(skip_all, err)]
+    pub async fn purge_sessions<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(db: &DB, policy: &SessionPolicy) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>
+    {
+        Sessions::purge_expired(db, policy).await
+    }
+
+    /// Moves the emails and phone numbers of users onto the active key of `pii`, encrypting those still stored in the clear.
+    #[instrument(skip_all, err)]
+    pub async fn reencrypt_pii<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>
+    {
+        Ok(db.reencrypt_users().await?)
+    }
+
+    /// Deletes the audit entries of every organisation that are past its retention. see `OrgAudit::retention`.
+    #[instrument(skip_all, err)]
+    pub async fn prune_audit_log<S: OrganisationStore, L: AuditLog>(store: &S, log: &L, audit: &OrgAudit) -> Result<usize, Error>
+    where
+        Error: From<S::Error>,
+        Error: From<L::Error>
+    {
+        let now = Utc::now();
+        let mut pruned = 0;
+        for organisation in store.all().await? {
+            pruned += log.prune(organisation.id, audit.cutoff(&organisation, now)).await?;
+        }
+        Ok(pruned)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{id, Tokens};
+    use crate::domain::{Cached, Tokenizer};
+    use crate::adaptors::outputs::databases::memory::Memory;
+    use crate::adaptors::outputs::organisations::memory::MemoryOrganisations;
+    use crate::adaptors::outputs::audit_log::memory::MemoryAuditLog;
+    use crate::types::{Device, Either, Email, AuditEntry};
+    use crate::scheduler::Scheduler;
+    use chrono::{DateTime, TimeDelta};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_expired_items_are_purged() {
+        let db = Memory::new(Duration::from_secs(1));
+        let now = Utc::now();
+        let verification = |n: u8, expires: DateTime<Utc>| Verification { owner_contact: Either::Right(Email::try_from(format!("user{}@example.com", n).as_str()).unwrap()), id: id(n), code: 123456, expires, created_at: now, updated_at: now };
+        db.create_verification_code(verification(1, now - TimeDelta::minutes(1))).await.unwrap();
+        db.create_verification_code(verification(2, now + TimeDelta::minutes(1))).await.unwrap();
+        assert_eq!(Maintenance::purge_verifications(&db).await, Ok(1));
+        let session = |n: u8, created_at: DateTime<Utc>, last_active_at: DateTime<Utc>| Session { id: id(n), user_id: id(1), refresh_token_id: id(n + 10), previous_refresh_token_id: None, device: Device::default(), created_at, updated_at: created_at, last_active_at };
+        db.create_session(session(2, now - TimeDelta::hours(3), now - TimeDelta::hours(2))).await.unwrap();
+        db.create_session(session(3, now - TimeDelta::days(2), now)).await.unwrap();
+        db.create_session(session(4, now - TimeDelta::hours(3), now - TimeDelta::minutes(5))).await.unwrap();
+        let policy = SessionPolicy { idle_timeout: TimeDelta::hours(1), max_lifetime: TimeDelta::days(1) };
+        assert_eq!(Maintenance::purge_sessions(&db, &policy).await, Ok(2));
+        let left = db.get_sessions_by_user_id(id(1)).await.unwrap();
+        assert_eq!(left.into_iter().map(|session| session.id).collect::<Vec<_>>(), vec![id(4)]);
+    }
+
+    #[tokio::test]
+    async fn test_purged_sessions_are_revoked() {
+        let db = Memory::new(Duration::from_secs(1));
+        let now = Utc::now();
+        // revocations are process wide, so the sessions are not ones other tests use.
+        let (idle, active) = (Id::new(), Id::new());
+        for (id, last_active_at) in [(idle, now - TimeDelta::hours(2)), (active, now)] {
+            db.create_session(Session { id, user_id: Id::new(), refresh_token_id: Id::new(), previous_refresh_token_id: None, device: Device::default(), created_at: now, updated_at: now, last_active_at }).await.unwrap();
+        }
+        let cached = Cached::new(Tokens, Duration::from_secs(60), 10);
+        let (idle_token, active_token) = (cached.decode_token(&idle.to_hex()).await.unwrap(), cached.decode_token(&active.to_hex()).await.unwrap());
+        let policy = SessionPolicy { idle_timeout: TimeDelta::hours(1), max_lifetime: TimeDelta::days(1) };
+        assert_eq!(Maintenance::purge_sessions(&db, &policy).await, Ok(1));
+        // a token used from the cache is the one decoded before, decoding again gives it a new id.
+        assert_ne!(cached.decode_token(&idle.to_hex()).await.unwrap().id, idle_token.id);
+        assert_eq!(cached.decode_token(&active.to_hex()).await.unwrap().id, active_token.id);
+    }
+
+    #[tokio::test]
+    async fn test_purges_can_be_scheduled() {
+        let db = Memory::new(Duration::from_secs(1));
+        let config = crate::config::JobsConfig::default();
+        let (organisations, store, log) = (crate::domain::Organisations::default(), MemoryOrganisations::new(), MemoryAuditLog::new());
+        let audit = OrgAudit::from(&crate::config::AuditConfig::default());
+        let mut scheduler = Scheduler::new();
+        scheduler.add("purge_verifications", config.purge_verifications.schedule.parse().unwrap(), || Maintenance::purge_verifications(&db));
+        assert_eq!(scheduler.run_now("purge_verifications").await, Some(Ok(0)));
+        scheduler.add("purge_grants", config.purge_grants.schedule.parse().unwrap(), || Maintenance::purge_grants(&db));
+        assert_eq!(scheduler.run_now("purge_grants").await, Some(Ok(0)));
+        scheduler.add("reencrypt_pii", config.reencrypt_pii.schedule.parse().unwrap(), || Maintenance::reencrypt_pii(&db));
+        assert_eq!(scheduler.run_now("reencrypt_pii").await, Some(Ok(0)));
+        let organisation = organisations.create(&store, String::from("Acme"), None).await.unwrap();
+        log.record(AuditEntry { occurred_at: Utc::now() - TimeDelta::days(366), ..AuditEntry::new(organisation.id, None, "membership.added", None) }).await.unwrap();
+        log.record(AuditEntry::new(organisation.id, None, "membership.removed", None)).await.unwrap();
+        scheduler.add("prune_audit_log", config.prune_audit_log.schedule.parse().unwrap(), || Maintenance::prune_audit_log(&store, &log, &audit));
+        assert_eq!(scheduler.run_now("prune_audit_log").await, Some(Ok(1)));
+        assert_eq!(log.entries(organisation.id, DateTime::<Utc>::MIN_UTC).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,82 @@
+use crate::ports::outputs::database::{Database, tables::UsersTable};
+use crate::types::{Error, User, Id, Namespace, DatabaseError};
+use serde_json::{Map, Value};
+use tracing::instrument;
+
+
+/// Reads and writes the metadata of users.
+///
+/// Users read both namespaces but only ever write [`Namespace::User`], the [`Namespace::Service`] one is written by services.
+pub struct UserMetadata;
+
+
+impl UserMetadata {
+    pub async fn get<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB, id: Id, namespace: Namespace) -> Result<Map<String, Value>, Error>
+    where
+        Error: From<DB::Error>
+    {
+        let mut user = db.get_user_by_id(id).await?.ok_or(DatabaseError::UserNotFound)?;
+        Ok(std::mem::take(user.metadata.get_mut(namespace)))
+    }
+
+    /// Merges `changes` into the namespace, a `null` value removing its key, and returns the updated namespace.
+    ///
+    /// Invalid keys and namespaces that would outgrow `metadata::MAX_SIZE` are rejected before anything is written.
+    #[instrument(skip_all, fields(user_id = %id.to_hex(), ?namespace), err)]
+    pub async fn update<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB, id: Id, namespace: Namespace, changes: Map<String, Value>) -> Result<Map<String, Value>, Error>
+    where
+        Error: From<DB::Error>
+    {
+        let mut user = db.get_user_by_id(id).await?.ok_or(DatabaseError::UserNotFound)?;
+        user.metadata.merge(namespace, changes)?;
+        let metadata = std::mem::take(user.metadata.get_mut(namespace));
+        db.set_user_metadata(id, namespace, metadata.clone()).await?;
+        Ok(metadata)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ConversionError, GrantRecord, Session, Verification};
+    use crate::ports::outputs::database::MockDatabase;
+    use crate::testing::UserFixture;
+    use serde_json::json;
+
+    type Mock = MockDatabase<User, Session, Verification<Id>, GrantRecord, DatabaseError>;
+
+    fn user(id: Id) -> User {
+        UserFixture::new()
+            .id(id)
+            .metadata(Namespace::User, map(json!({"theme": "dark"})))
+            .metadata(Namespace::Service, map(json!({"plan": "pro"})))
+            .build()
+    }
+
+    fn map(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn test_update_merges_into_the_namespace() {
+        let db = Mock::default();
+        let id = Id::try_from(String::from("000000000000000000000001")).unwrap();
+        db.users_table().get_user_by_id_returns(Ok(Some(user(id)))).set_user_metadata_returns(Ok(()));
+        let metadata = UserMetadata::update(&db, id, Namespace::User, map(json!({"theme": null, "locale": "sw"}))).await.unwrap();
+        assert_eq!(metadata, map(json!({"locale": "sw"})));
+        let calls = db.users_table().calls();
+        assert_eq!(calls[1].method, "set_user_metadata");
+        assert_eq!(calls[1].args[1], format!("{:?}", Namespace::User));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_changes_are_not_written() {
+        let db = Mock::default();
+        let id = Id::try_from(String::from("000000000000000000000001")).unwrap();
+        db.users_table().get_user_by_id_returns(Ok(Some(user(id))));
+        let result = UserMetadata::update(&db, id, Namespace::Service, map(json!({"a.b": 1}))).await;
+        assert_eq!(result, Err(Error::ConversionError(ConversionError::InvalidMetadataKey("a.b".into()))));
+        assert_eq!(db.users_table().calls().len(), 1);
+    }
+}
@@ -1,7 +1,86 @@
 mod authentication;
 mod tokenization;
+mod maintenance;
+mod metadata;
+mod sessions;
+mod erasure;
+#[cfg(feature = "email")]
+mod directory;
+#[cfg(feature = "email")]
+mod external;
+#[cfg(feature = "saml")]
+pub mod saml;
+mod cookies;
+mod accounts;
+mod username;
+mod signup;
+mod disposable;
+mod guests;
+mod unverified;
+mod requirements;
+mod avatar;
+mod profile;
+mod mail;
+mod events;
+mod export;
+mod admin;
+mod seed;
 mod password;
+mod tenancy;
+mod network;
+mod limits;
+mod geolocation;
+mod anomalies;
+mod login_history;
+mod contacts;
+mod organisations;
+mod redirects;
+mod services;
+mod userinfo;
+mod logout;
+mod keys;
+mod token_exchange;
+mod password_grant;
+mod audit;
 
 
-pub use tokenization::Tokenizer;
-pub use password::Password;
\ No newline at end of file
+pub use cookies::{Cookies, Credentials, RequestHeaders};
+pub use password::{Password, PasswordService};
+pub use tokenization::{Tokenizer, sign_token, cache::Cached, policy::{TokenPolicy, TokenPolicies}};
+pub use sessions::{Sessions, SessionPolicy};
+pub use authentication::Authentication;
+#[cfg(feature = "email")]
+pub use directory::DirectoryLogin;
+pub use maintenance::Maintenance;
+pub use metadata::UserMetadata;
+pub use username::UsernameRules;
+pub use signup::SignupRules;
+pub use disposable::{DisposableEmails, DISPOSABLE_EMAIL};
+pub use guests::{Guests, GUEST_SCOPE};
+pub use unverified::{UnverifiedAccounts, UNVERIFIED_SCOPE};
+pub use requirements::{ProfileRequirements, PROFILE_INCOMPLETE_CLAIM};
+pub use admin::{Admin, ROLE, PASSWORD_RESET_REQUIRED};
+pub use accounts::{Accounts, MERGED_INTO};
+pub use erasure::Erasure;
+pub use avatar::Avatars;
+pub use profile::Profiles;
+pub use seed::{Seed, SeedUser};
+pub use export::Export;
+pub use mail::Mails;
+pub use events::Events;
+pub use tenancy::{Tenancy, TENANT_CLAIM};
+pub use network::{NetworkAccess, ForwardingHeaders};
+pub use limits::RequestLimits;
+pub use geolocation::Geolocation;
+pub use anomalies::AnomalyDetection;
+pub use login_history::LoginAttempts;
+pub use contacts::{Contacts, EMAIL_VERIFIED_CLAIM, PHONE_VERIFIED_CLAIM};
+pub use organisations::Organisations;
+pub use redirects::RedirectUris;
+pub use services::{Services, Grant};
+pub use userinfo::{UserInfo, OPENID_SCOPE};
+pub use logout::Logout;
+pub use keys::{Keyring, CONFIGURED_KID};
+pub use password_grant::PasswordGrant;
+pub use token_exchange::TokenExchange;
+pub use audit::OrgAudit;
@@ -0,0 +1,146 @@
+use crate::config::{AccessList, ProxiesConfig, ForwardedHeader};
+use crate::types::{Error, Device};
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+
+/// The network side of the requests: the address of the client behind the proxies, and the groups of routes it may reach.
+///
+/// The HTTP layer names the group of every route, eg `admin` for the admin API, and checks the client against it
+/// before handling the request.
+pub struct NetworkAccess {
+    proxies: ProxiesConfig,
+    acl: HashMap<String, AccessList>,
+}
+
+
+/// The headers of a request proxies forward the address of their client in.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ForwardingHeaders<'a> {
+    pub forwarded: Option<&'a str>,
+    pub x_forwarded_for: Option<&'a str>,
+}
+
+
+impl NetworkAccess {
+    pub fn new(proxies: &ProxiesConfig, acl: &HashMap<String, AccessList>) -> Self {
+        Self { proxies: proxies.clone(), acl: acl.clone() }
+    }
+
+    /// The address of the client of a request from `peer`, the other end of the connection.
+    ///
+    /// The forwarding header is read from the right, one hop at a time, for as long as the hop was added by a trusted proxy,
+    /// one of the first `proxies.hops` or one of `proxies.trusted`, since anything left of that is whatever the client claimed.
+    /// A hop that isn't an address, eg `unknown`, ends the walk at the proxy that added it.
+    pub fn client_ip(&self, peer: IpAddr, headers: ForwardingHeaders) -> IpAddr {
+        let hops = match self.proxies.header {
+            ForwardedHeader::XForwardedFor => headers.x_forwarded_for.into_iter().flat_map(|header| header.split(',')).map(|hop| hop.trim().parse().ok()).collect::<Vec<_>>(),
+            ForwardedHeader::Forwarded => headers.forwarded.into_iter().flat_map(|header| header.split(',')).map(forwarded_for).collect(),
+        };
+        let mut client = peer;
+        for (proxy, hop) in hops.into_iter().rev().enumerate() {
+            if proxy >= self.proxies.hops && !self.is_trusted(client) {
+                break;
+            }
+            match hop {
+                Some(ip) => client = ip,
+                None => break,
+            }
+        }
+        client
+    }
+
+    /// The device of a request, with the address of its client and what its user agent tells about it.
+    pub fn device(&self, peer: IpAddr, headers: ForwardingHeaders, user_agent: Option<&str>) -> Device {
+        Device::new(Some(self.client_ip(peer, headers)), user_agent.map(str::to_string))
+    }
+
+    /// Fails with `Error::AddressNotAllowed` unless `group` takes requests from `client`.
+    pub fn authorize(&self, group: &str, client: IpAddr) -> Result<(), Error> {
+        let Some(list) = self.acl.get(group) else {
+            return Ok(());
+        };
+        let denied = list.deny.iter().any(|range| range.contains(client));
+        let allowed = list.allow.is_empty() || list.allow.iter().any(|range| range.contains(client));
+        if denied || !allowed {
+            tracing::warn!(group, %client, "request refused by the network ACL");
+            return Err(Error::AddressNotAllowed(client));
+        }
+        Ok(())
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.proxies.trusted.iter().any(|range| range.contains(ip))
+    }
+}
+
+
+/// The address of the `for` parameter of an element of a `Forwarded` header. eg `for=192.0.2.60;proto=https`
+///
+/// The port is dropped, and obfuscated identifiers such as `_hidden` or `unknown` are no address.
+fn forwarded_for(element: &str) -> Option<IpAddr> {
+    let value = element.split(';').filter_map(|pair| pair.split_once('=')).find(|(name, _)| name.trim().eq_ignore_ascii_case("for"))?.1;
+    let value = value.trim().trim_matches('"');
+    match value.strip_prefix('[') {
+        // `[2001:db8::1]:4711`
+        Some(address) => address.split(']').next()?.parse().ok(),
+        None => value.split(':').next()?.parse().ok(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Cidr;
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    fn cidrs(ranges: &[&str]) -> Vec<Cidr> {
+        ranges.iter().map(|range| range.parse().unwrap()).collect()
+    }
+
+    fn x_forwarded_for(header: &str) -> ForwardingHeaders<'_> {
+        ForwardingHeaders { x_forwarded_for: Some(header), ..Default::default() }
+    }
+
+    fn network() -> NetworkAccess {
+        let proxies = ProxiesConfig { trusted: cidrs(&["10.0.0.0/8"]), ..Default::default() };
+        let acl = HashMap::from([(String::from("admin"), AccessList { allow: cidrs(&["192.0.2.0/24"]), deny: cidrs(&["192.0.2.13"]) })]);
+        NetworkAccess::new(&proxies, &acl)
+    }
+
+    #[test]
+    fn test_forwarded_addresses_are_believed_from_trusted_proxies_only() {
+        let network = network();
+        assert_eq!(network.client_ip(ip("10.0.0.5"), x_forwarded_for("203.0.113.9, 192.0.2.1, 10.0.0.7")), ip("192.0.2.1"));
+        assert_eq!(network.client_ip(ip("198.51.100.1"), x_forwarded_for("192.0.2.1")), ip("198.51.100.1"));
+        assert_eq!(network.client_ip(ip("10.0.0.5"), x_forwarded_for("unknown, 10.0.0.6")), ip("10.0.0.6"));
+        assert_eq!(network.client_ip(ip("10.0.0.5"), ForwardingHeaders::default()), ip("10.0.0.5"));
+    }
+
+    #[test]
+    fn test_hops_are_believed_whatever_their_address() {
+        let proxies = ProxiesConfig { hops: 2, header: ForwardedHeader::Forwarded, ..Default::default() };
+        let network = NetworkAccess::new(&proxies, &HashMap::new());
+        let forwarded = ForwardingHeaders {
+            forwarded: Some(r#"for=203.0.113.9, for="[2001:db8::1]:4711";proto=https, For=198.51.100.7:443"#),
+            x_forwarded_for: Some("192.0.2.66"),
+        };
+        assert_eq!(network.client_ip(ip("198.51.100.1"), forwarded), ip("2001:db8::1"));
+        let hidden = ForwardingHeaders { forwarded: Some("for=_gateway;by=10.0.0.1"), ..Default::default() };
+        assert_eq!(network.client_ip(ip("198.51.100.1"), hidden), ip("198.51.100.1"));
+        assert_eq!(network.device(ip("198.51.100.1"), ForwardingHeaders::default(), Some("curl/8.0")).ip, Some(ip("198.51.100.1")));
+    }
+
+    #[test]
+    fn test_groups_take_requests_from_their_ranges() {
+        let network = network();
+        assert_eq!(network.authorize("admin", ip("192.0.2.1")), Ok(()));
+        assert_eq!(network.authorize("admin", ip("192.0.2.13")), Err(Error::AddressNotAllowed(ip("192.0.2.13"))));
+        assert_eq!(network.authorize("admin", ip("203.0.113.9")), Err(Error::AddressNotAllowed(ip("203.0.113.9"))));
+        assert_eq!(network.authorize("public", ip("203.0.113.9")), Ok(()));
+    }
+}
@@ -0,0 +1,261 @@
+use crate::ports::outputs::organisations::OrganisationStore;
+use crate::types::{Error, Id, Organisation, OrgRole, Membership, DatabaseError};
+use crate::config::OrganisationsConfig;
+use std::collections::HashMap;
+use tracing::instrument;
+use chrono::Utc;
+
+
+/// Organisations nested under one another, eg the subsidiaries of a holding. see [`OrganisationsConfig`].
+///
+/// Memberships flow downward: a member of an organisation is a member of every organisation under it, with the
+/// highest role they were given in it or above it. No organisation can end up under itself, and no tree gets deeper than `max_depth`.
+#[derive(Debug, Clone)]
+pub struct Organisations {
+    max_depth: usize,
+}
+
+
+impl Organisations {
+    /// Creates an organisation, under `parent` when it is set.
+    #[instrument(skip(self, store, name), err)]
+    pub async fn create<S: OrganisationStore>(&self, store: &S, name: String, parent: Option<Id>) -> Result<Organisation, Error>
+    where
+        Error: From<S::Error>
+    {
+        if let Some(parent) = parent {
+            self.check_depth(self.path(store, parent).await?.len() + 1)?;
+        }
+        let organisation = Organisation::new(name, parent);
+        store.create(organisation.clone()).await?;
+        Ok(organisation)
+    }
+
+    /// Moves the organisation, and everything under it, under `parent`, or to the top without one.
+    #[instrument(skip(self, store), err)]
+    pub async fn move_to<S: OrganisationStore>(&self, store: &S, id: Id, parent: Option<Id>) -> Result<Organisation, Error>
+    where
+        Error: From<S::Error>
+    {
+        let organisation = store.get(id).await?.ok_or(DatabaseError::OrganisationNotFound)?;
+        let above = match parent {
+            Some(parent) => self.path(store, parent).await?,
+            None => Vec::new(),
+        };
+        if above.iter().any(|ancestor| ancestor.id == id) {
+            return Err(Error::InvalidHierarchy(String::from("an organisation cannot be moved under itself or one of the organisations under it")));
+        }
+        self.check_depth(above.len() + self.height(store, id).await?)?;
+        let moved = Organisation { parent, updated_at: Utc::now(), ..organisation };
+        store.update(moved.clone()).await?;
+        Ok(moved)
+    }
+
+    /// Deletes an organisation with nothing under it, along with the memberships given in it.
+    #[instrument(skip(self, store), err)]
+    pub async fn delete<S: OrganisationStore>(&self, store: &S, id: Id) -> Result<(), Error>
+    where
+        Error: From<S::Error>
+    {
+        if !store.children(id).await?.is_empty() {
+            return Err(Error::InvalidHierarchy(String::from("the organisations under it have to be moved or deleted first")));
+        }
+        for membership in store.members(id).await? {
+            store.remove_membership(id, membership.user_id).await?;
+        }
+        Ok(store.delete(id).await?)
+    }
+
+    /// The organisations above the organisation, its parent first.
+    pub async fn ancestors<S: OrganisationStore>(&self, store: &S, id: Id) -> Result<Vec<Organisation>, Error>
+    where
+        Error: From<S::Error>
+    {
+        let mut path = self.path(store, id).await?;
+        path.remove(0);
+        Ok(path)
+    }
+
+    /// Every organisation under the organisation, however deep, level by level.
+    pub async fn descendants<S: OrganisationStore>(&self, store: &S, id: Id) -> Result<Vec<Organisation>, Error>
+    where
+        Error: From<S::Error>
+    {
+        let mut descendants = Vec::new();
+        let mut level = vec![id];
+        // trees are never deeper than `max_depth`, unless the store was edited by hand.
+        for _ in 0..self.max_depth {
+            let mut next = Vec::new();
+            for parent in level {
+                next.extend(store.children(parent).await?);
+            }
+            level = next.iter().map(|organisation| organisation.id).collect();
+            descendants.extend(next);
+            if level.is_empty() {
+                break;
+            }
+        }
+        Ok(descendants)
+    }
+
+    /// Gives the user `role` in the organisation and every organisation under it, replacing the role they were given in it.
+    #[instrument(skip(self, store), err)]
+    pub async fn add_member<S: OrganisationStore>(&self, store: &S, org_id: Id, user_id: Id, role: OrgRole) -> Result<Membership, Error>
+    where
+        Error: From<S::Error>
+    {
+        store.get(org_id).await?.ok_or(DatabaseError::OrganisationNotFound)?;
+        let membership = Membership::new(org_id, user_id, role);
+        store.set_membership(membership.clone()).await?;
+        Ok(membership)
+    }
+
+    /// Removes the membership given in the organisation. The user keeps the roles they inherit from above it.
+    pub async fn remove_member<S: OrganisationStore>(&self, store: &S, org_id: Id, user_id: Id) -> Result<(), Error>
+    where
+        Error: From<S::Error>
+    {
+        Ok(store.remove_membership(org_id, user_id).await?)
+    }
+
+    /// The role of the user in the organisation: the highest one given to them in it or above it, if any.
+    pub async fn role<S: OrganisationStore>(&self, store: &S, org_id: Id, user_id: Id) -> Result<Option<OrgRole>, Error>
+    where
+        Error: From<S::Error>
+    {
+        let roles = store.memberships(user_id).await?.into_iter().map(|membership| (membership.org_id, membership.role)).collect::<HashMap<_, _>>();
+        if roles.is_empty() {
+            return Ok(None);
+        }
+        let path = self.path(store, org_id).await?;
+        Ok(path.iter().filter_map(|organisation| roles.get(&organisation.id).copied()).max())
+    }
+
+    /// Every member of the organisation, those of the organisations above it included, each with the membership giving them their role.
+    pub async fn members<S: OrganisationStore>(&self, store: &S, org_id: Id) -> Result<Vec<Membership>, Error>
+    where
+        Error: From<S::Error>
+    {
+        let mut members = HashMap::<Id, Membership>::new();
+        for organisation in self.path(store, org_id).await? {
+            for membership in store.members(organisation.id).await? {
+                match members.get(&membership.user_id) {
+                    Some(kept) if kept.role >= membership.role => {},
+                    _ => {
+                        members.insert(membership.user_id, membership);
+                    },
+                }
+            }
+        }
+        let mut members = members.into_values().collect::<Vec<_>>();
+        members.sort_by_key(|membership| membership.created_at);
+        Ok(members)
+    }
+
+    /// The organisation followed by the ones above it, up to the top.
+    async fn path<S: OrganisationStore>(&self, store: &S, id: Id) -> Result<Vec<Organisation>, Error>
+    where
+        Error: From<S::Error>
+    {
+        let mut path = vec![store.get(id).await?.ok_or(DatabaseError::OrganisationNotFound)?];
+        while let Some(parent) = path.last().and_then(|organisation| organisation.parent) {
+            if path.len() >= self.max_depth || path.iter().any(|organisation| organisation.id == parent) {
+                return Err(Error::InvalidHierarchy(String::from("the organisations above it loop or are nested deeper than organisations.max_depth")));
+            }
+            path.push(store.get(parent).await?.ok_or(DatabaseError::OrganisationNotFound)?);
+        }
+        Ok(path)
+    }
+
+    /// The levels of the tree under the organisation, the organisation included.
+    async fn height<S: OrganisationStore>(&self, store: &S, id: Id) -> Result<usize, Error>
+    where
+        Error: From<S::Error>
+    {
+        let mut height = 1;
+        let mut level = vec![id];
+        while height <= self.max_depth {
+            let mut next = Vec::new();
+            for parent in level {
+                next.extend(store.children(parent).await?.into_iter().map(|organisation| organisation.id));
+            }
+            if next.is_empty() {
+                break;
+            }
+            level = next;
+            height += 1;
+        }
+        Ok(height)
+    }
+
+    fn check_depth(&self, depth: usize) -> Result<(), Error> {
+        if depth > self.max_depth {
+            return Err(Error::InvalidHierarchy(format!("organisations cannot be nested more than {} levels deep", self.max_depth)));
+        }
+        Ok(())
+    }
+}
+
+
+impl Default for Organisations {
+    fn default() -> Self {
+        Self::from(&OrganisationsConfig::default())
+    }
+}
+
+
+impl From<&OrganisationsConfig> for Organisations {
+    fn from(config: &OrganisationsConfig) -> Self {
+        Self { max_depth: config.max_depth }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptors::outputs::organisations::memory::MemoryOrganisations;
+
+    /// a holding with a subsidiary, which has a branch of its own.
+    async fn tree(organisations: &Organisations, store: &MemoryOrganisations) -> (Organisation, Organisation, Organisation) {
+        let holding = organisations.create(store, String::from("Holding"), None).await.unwrap();
+        let subsidiary = organisations.create(store, String::from("Subsidiary"), Some(holding.id)).await.unwrap();
+        let branch = organisations.create(store, String::from("Branch"), Some(subsidiary.id)).await.unwrap();
+        (holding, subsidiary, branch)
+    }
+
+    #[tokio::test]
+    async fn test_roles_flow_downward() {
+        let (organisations, store) = (Organisations::default(), MemoryOrganisations::new());
+        let (holding, subsidiary, branch) = tree(&organisations, &store).await;
+        let (jane, john) = (Id::default(), Id::default());
+        organisations.add_member(&store, holding.id, jane, OrgRole::Admin).await.unwrap();
+        organisations.add_member(&store, subsidiary.id, john, OrgRole::Member).await.unwrap();
+        organisations.add_member(&store, branch.id, jane, OrgRole::Member).await.unwrap();
+        assert_eq!(organisations.role(&store, branch.id, jane).await, Ok(Some(OrgRole::Admin)));
+        assert_eq!(organisations.role(&store, branch.id, john).await, Ok(Some(OrgRole::Member)));
+        assert_eq!(organisations.role(&store, holding.id, john).await, Ok(None));
+        let members = organisations.members(&store, branch.id).await.unwrap();
+        assert_eq!(members.iter().map(|membership| (membership.user_id, membership.org_id)).collect::<Vec<_>>(), vec![(jane, holding.id), (john, subsidiary.id)]);
+        let descendants = organisations.descendants(&store, holding.id).await.unwrap();
+        assert_eq!(descendants.iter().map(|organisation| organisation.id).collect::<Vec<_>>(), vec![subsidiary.id, branch.id]);
+        let ancestors = organisations.ancestors(&store, branch.id).await.unwrap();
+        assert_eq!(ancestors.iter().map(|organisation| organisation.id).collect::<Vec<_>>(), vec![subsidiary.id, holding.id]);
+    }
+
+    #[tokio::test]
+    async fn test_organisations_cannot_loop_or_nest_too_deep() {
+        let organisations = Organisations::from(&OrganisationsConfig { max_depth: 3 });
+        let store = MemoryOrganisations::new();
+        let (holding, subsidiary, branch) = tree(&organisations, &store).await;
+        assert!(matches!(organisations.move_to(&store, holding.id, Some(branch.id)).await, Err(Error::InvalidHierarchy(_))));
+        assert!(matches!(organisations.move_to(&store, subsidiary.id, Some(subsidiary.id)).await, Err(Error::InvalidHierarchy(_))));
+        assert!(matches!(organisations.create(&store, String::from("Desk"), Some(branch.id)).await, Err(Error::InvalidHierarchy(_))));
+        let other = organisations.create(&store, String::from("Other"), None).await.unwrap();
+        assert!(matches!(organisations.move_to(&store, other.id, Some(branch.id)).await, Err(Error::InvalidHierarchy(_))));
+        assert_eq!(organisations.move_to(&store, branch.id, Some(holding.id)).await.unwrap().parent, Some(holding.id));
+        assert!(matches!(organisations.delete(&store, holding.id).await, Err(Error::InvalidHierarchy(_))));
+        assert_eq!(organisations.delete(&store, branch.id).await, Ok(()));
+        assert_eq!(organisations.move_to(&store, branch.id, None).await, Err(DatabaseError::OrganisationNotFound.into()));
+    }
+}
@@ -1,5 +1,8 @@
 use password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
 use crate::types::Error;
+use tokio::sync::Semaphore;
+use arc_swap::ArcSwap;
+use std::sync::Arc;
 
 
 pub trait Password {
@@ -20,4 +23,140 @@ impl<T: PasswordHasher + PasswordVerifier> Password for T {
         self.verify_password(password.as_bytes(), &parsed_hash)?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Runs a [`Password`] implementation on the blocking thread pool so hashing never stalls the async executor.
+///
+/// At most `max_concurrent` hashes are computed at once. Further calls wait for a permit,
+/// which bounds the memory and CPU spent on hashing during a burst of signups or logins.
+/// A permit is held until the hash is computed, even when the caller gave up on it.
+/// The server reads the bound on startup; an application reloading its own configuration can change it with [`PasswordService::resize`].
+pub struct PasswordService<P> {
+    password: Arc<P>,
+    permits: Arc<ArcSwap<Permits>>,
+}
+
+
+struct Permits {
+    max_concurrent: usize,
+    semaphore: Arc<Semaphore>,
+}
+
+
+impl Permits {
+    fn new(max_concurrent: usize) -> Self {
+        Self { max_concurrent, semaphore: Arc::new(Semaphore::new(max_concurrent)) }
+    }
+}
+
+
+impl<P> Clone for PasswordService<P> {
+    fn clone(&self) -> Self {
+        Self { password: self.password.clone(), permits: self.permits.clone() }
+    }
+}
+
+
+impl<P: Password + Send + Sync + 'static> PasswordService<P> {
+    pub fn new(password: P, max_concurrent: usize) -> Self {
+        Self { password: Arc::new(password), permits: Arc::new(ArcSwap::from_pointee(Permits::new(max_concurrent))) }
+    }
+
+    /// Bounds the hashes computed at once to `max_concurrent`, for this service and its clones.
+    ///
+    /// The hashes already running finish under the previous bound, so both may be running for a moment.
+    pub fn resize(&self, max_concurrent: usize) {
+        if self.permits.load().max_concurrent != max_concurrent {
+            self.permits.store(Arc::new(Permits::new(max_concurrent)));
+        }
+    }
+
+    pub async fn hash_password(&self, password: String) -> Result<String, Error> {
+        self.blocking(move |hasher| hasher.hash_password(&password)).await
+    }
+
+    pub async fn verify_password(&self, password: String, hash: String) -> Result<(), Error> {
+        self.blocking(move |hasher| hasher.verify_password(&password, &hash)).await
+    }
+
+    async fn blocking<T: Send + 'static, F: FnOnce(&P) -> Result<T, Error> + Send + 'static>(&self, f: F) -> Result<T, Error> {
+        let semaphore = self.permits.load().semaphore.clone();
+        let permit = semaphore.acquire_owned().await.expect("the semaphore is never closed");
+        let password = self.password.clone();
+        // the permit goes with the task, as a dropped caller does not stop a hash already running.
+        let task = tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            f(&password)
+        });
+        match task.await {
+            Ok(result) => result,
+            Err(err) => std::panic::resume_unwind(err.into_panic()),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Records the highest number of hashes computed at once.
+    #[derive(Default)]
+    struct Slow {
+        running: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    impl Password for Slow {
+        fn hash_password(&self, password: &str) -> Result<String, Error> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(running, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(20));
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            Ok(password.to_string())
+        }
+
+        fn verify_password(&self, password: &str, hash: &str) -> Result<(), Error> {
+            if password == hash { Ok(()) } else { Err(Error::WrongPassword) }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_is_bounded() {
+        let service = PasswordService::new(Slow::default(), 2);
+        let hashes = (0..6).map(|_| {
+            let service = service.clone();
+            tokio::spawn(async move { service.hash_password("password".into()).await })
+        }).collect::<Vec<_>>();
+        for hash in hashes {
+            assert_eq!(hash.await.unwrap(), Ok("password".to_string()));
+        }
+        assert_eq!(service.password.peak.load(Ordering::SeqCst), 2);
+        assert_eq!(service.verify_password("a".into(), "b".into()).await, Err(Error::WrongPassword));
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_hashes_keep_their_permit() {
+        let service = PasswordService::new(Slow::default(), 1);
+        let abandoned = tokio::time::timeout(Duration::from_millis(5), service.hash_password("password".into())).await;
+        assert!(abandoned.is_err());
+        assert_eq!(service.hash_password("password".into()).await, Ok("password".to_string()));
+        assert_eq!(service.password.peak.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_follows_resizes() {
+        let service = PasswordService::new(Slow::default(), 1);
+        service.clone().resize(3);
+        let hashes = (0..6).map(|_| {
+            let service = service.clone();
+            tokio::spawn(async move { service.hash_password("password".into()).await })
+        }).collect::<Vec<_>>();
+        for hash in hashes {
+            assert_eq!(hash.await.unwrap(), Ok("password".to_string()));
+        }
+        assert_eq!(service.password.peak.load(Ordering::SeqCst), 3);
+    }
+}
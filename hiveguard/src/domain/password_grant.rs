@@ -0,0 +1,81 @@
+use crate::ports::outputs::database::{Database, tables::{UsersTable, VerificationsTable, SessionsTable}};
+use crate::types::{Error, User, TokenBundle, Email, Verification, Id, Session, Device};
+use crate::ports::outputs::security_events::SecurityEvents;
+use crate::ports::outputs::events::EventPublisher;
+use crate::ports::outputs::login_history::LoginHistory;
+use super::{Authentication, Services, Grant, Tokenizer, TokenPolicies, Password, PasswordService, AnomalyDetection, UnverifiedAccounts};
+use crate::config::{Config, ConsentConfig};
+use tracing::instrument;
+use std::fmt::Display;
+
+
+/// The resource owner password grant, for first-party services: the password of a user is traded for tokens of the
+/// service directly, without the consent screen, while its native apps move to the code flow.
+pub struct PasswordGrant {
+    services: Services,
+    policies: TokenPolicies,
+}
+
+
+impl PasswordGrant {
+    /// Logs the user of `email` in for `client_id` like [`Authentication::login`], once the service is found to be
+    /// first-party. The tokens get the `scope` requested, and fail with `Error::InvalidScope` on any the token policy
+    /// of the service does not allow.
+    #[instrument(skip_all, fields(client_id = client_id, user_id), err)]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn login<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, VerificationsTable: VerificationsTable<DB::Client, Item = Verification<Id>>, SessionsTable: SessionsTable<DB::Client, Item = Session>>, T: Tokenizer, P: Password + Send + Sync + 'static, E: SecurityEvents, B: EventPublisher, H: LoginHistory>(&self, client_id: &str, scope: Option<&str>, db: &DB, email: Email, password: String, tokenizer: &T, passwords: &PasswordService<P>, consent: &ConsentConfig, anomalies: &AnomalyDetection, unverified: &UnverifiedAccounts, events: &E, publisher: &B, history: &H, device: Device) -> Result<TokenBundle, Error>
+    where
+        Error: From<DB::Error>,
+        Error: From<T::Error>,
+        T::Error: From<DB::Error>,
+        E::Error: Display,
+        B::Error: Display,
+        H::Error: Display
+    {
+        self.services.check_grant(client_id, Grant::Password)?;
+        let scope = self.policies.for_service(client_id).scope(scope)?;
+        Authentication::login_with_scope(db, email, password, scope.as_deref(), tokenizer, passwords, consent, anomalies, unverified, events, publisher, history, device).await
+    }
+}
+
+
+impl From<&Config> for PasswordGrant {
+    fn from(config: &Config) -> Self {
+        Self { services: Services::from(config), policies: TokenPolicies::from(config) }
+    }
+}
+
+
+#[cfg(all(test, feature = "email"))]
+mod tests {
+    use super::*;
+    use crate::adaptors::outputs::events::EventSink;
+    use crate::adaptors::outputs::login_history::memory::MemoryHistory;
+    use crate::config::AnomaliesConfig;
+    use crate::testing::{Harness, UserFixture};
+
+    #[tokio::test]
+    async fn test_only_first_party_services_log_users_in_with_their_password() {
+        let harness = Harness::new();
+        let user = harness.create(UserFixture::new().password("secret")).await;
+        let config = Config {
+            services: serde_json::from_value(serde_json::json!([
+                {"client_id": "mobile", "first_party": true, "tokens": {"scopes": ["profile"]}},
+                {"client_id": "partner"},
+            ])).unwrap(),
+            ..Default::default()
+        };
+        let grant = PasswordGrant::from(&config);
+        let (anomalies, history) = (AnomalyDetection::from(&AnomaliesConfig::default()), MemoryHistory::new(chrono::TimeDelta::days(1)));
+        let (consent, unverified) = (ConsentConfig::default(), UnverifiedAccounts::default());
+        let login = |client_id: &'static str, scope: Option<&'static str>| {
+            grant.login(client_id, scope, &harness.db, user.email.clone().unwrap(), String::from("secret"), &harness.tokens, &harness.passwords, &consent, &anomalies, &unverified, &harness.events, &EventSink::Disabled, &history, Device::default())
+        };
+        assert_eq!(login("partner", None).await, Err(Error::ServiceNotTrusted("the password grant")));
+        assert_eq!(login("mobile", Some("profile email")).await, Err(Error::InvalidScope(String::from("email"))));
+        assert!(harness.db.get_sessions_by_user_id(user.id).await.unwrap().is_empty());
+        let bundle = login("mobile", Some("profile")).await.unwrap();
+        assert_eq!(bundle.scope.as_deref(), Some("profile"));
+        assert_eq!(harness.db.get_sessions_by_user_id(user.id).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,318 @@
+use crate::ports::outputs::database::{Database, tables::UsersTable};
+use crate::types::{Error, User, Id, ETag, JsonPatch, DatabaseError, Email, Namespace, ConversionError, DomainEventKind, Validator};
+use super::disposable::{DisposableEmails, DISPOSABLE_EMAIL};
+use crate::ports::outputs::events::EventPublisher;
+use super::metadata::UserMetadata;
+use super::events::Events;
+use super::username::UsernameRules;
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+use tracing::instrument;
+use std::fmt::Display;
+
+
+/// the fields of a user written by [`Profiles::rename`] alone.
+const USERNAME_FIELDS: [&str; 3] = ["username", "previous_username", "username_changed_at"];
+
+
+/// Updates of users through JSON Patches (RFC 6902), next to the `Map` based updates of `update_user`.
+///
+/// Users are read with their [`ETag`] and only patched while it is still theirs, so that two clients editing the same
+/// user don't silently overwrite each other: the second one gets `Error::PreconditionFailed` and reads the user again.
+/// The patch is written on the condition that the user was not updated since it was read, so a write landing between the
+/// check of the tag and the patch fails it too.
+///
+/// Deleting a user takes no `If-Match`: users are erased through [`Erasure::request`](super::Erasure::request), which
+/// schedules the erasure rather than writing over an edit.
+pub struct Profiles;
+
+
+impl Profiles {
+    /// The user and its tag.
+    pub async fn get<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB, id: Id) -> Result<(User, ETag), Error>
+    where
+        Error: From<DB::Error>
+    {
+        let user = db.get_user_by_id(id).await?.ok_or(DatabaseError::UserNotFound)?;
+        let etag = ETag::of(&user);
+        Ok((user, etag))
+    }
+
+    /// Applies `patch` to the user if `if_match` names its current tag, and returns the updated user and its new tag.
+    ///
+    /// The operations run against the user as it is serialized, without its login so hashes can neither be tested nor copied.
+    /// The fields they change then go through `update_user`, so the same fields are writable and a removed field is cleared.
+    /// Every field it changes is checked first, all invalid fields failing the patch together.
+    /// A new email address is checked against `disposable` first, and the flag of the user follows it.
+    /// Contacts are only verified by [`Contacts`](super::Contacts): a changed contact is unverified, whatever the patch says.
+    /// Usernames are changed with [`Profiles::rename`] instead.
+    #[instrument(skip_all, fields(user_id = %id.to_hex(), operations = patch.0.len()), err)]
+    pub async fn patch<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB, id: Id, if_match: &str, patch: JsonPatch, disposable: &DisposableEmails) -> Result<(User, ETag), Error>
+    where
+        Error: From<DB::Error>
+    {
+        let (user, etag) = Self::get(db, id).await?;
+        if !etag.matches(if_match) {
+            return Err(Error::PreconditionFailed);
+        }
+        let Ok(Value::Object(mut document)) = serde_json::to_value(&user) else {
+            unreachable!("users are serialized as objects");
+        };
+        if let Ok(Value::Object(login)) = serde_json::to_value(&user.login) {
+            document.retain(|field, _| !login.contains_key(field));
+        }
+        let mut update = patch.apply(&document)?;
+        if let Some(field) = USERNAME_FIELDS.into_iter().find(|field| update.contains_key(*field)) {
+            return Err(ConversionError::ImmutableField(field).into());
+        }
+        for (contact, verified) in [("email", "email_verified"), ("phone", "phone_verified")] {
+            if let Some(Value::Object(patched)) = update.get_mut(contact) {
+                let unchanged = document.get(contact).and_then(|current| current.get(contact)) == patched.get(contact);
+                let kept = unchanged && document.get(contact).and_then(|current| current.get(verified)) == Some(&Value::Bool(true));
+                patched.insert(String::from(verified), Value::Bool(kept));
+            }
+        }
+        update.retain(|field, value| !matches!(field.as_str(), "email" | "phone") || document.get(field) != Some(value));
+        if update.is_empty() {
+            return Ok((user, etag));
+        }
+        User::rules(Validator::new(&update)).check()?;
+        let email = update.get("email").and_then(|email| serde_json::from_value::<Email>(email.clone()).ok());
+        let flagged = email.map(|email| disposable.check(&email)).transpose()?;
+        let mut user = match db.update_user_if(id, update, user.updated_at).await.map_err(Error::from) {
+            Err(Error::DatabaseError(DatabaseError::Changed)) => return Err(Error::PreconditionFailed),
+            result => result?,
+        };
+        if let Some(flagged) = flagged.filter(|flagged| *flagged != user.metadata.service.contains_key(DISPOSABLE_EMAIL)) {
+            let flag = if flagged { Value::Bool(true) } else { Value::Null };
+            UserMetadata::update(db, id, Namespace::Service, Map::from_iter([(String::from(DISPOSABLE_EMAIL), flag)])).await?;
+            user = db.get_user_by_id(id).await?.ok_or(DatabaseError::UserNotFound)?;
+        }
+        let etag = ETag::of(&user);
+        Ok((user, etag))
+    }
+
+    /// Changes the username of the user, once `usernames.change_cooldown_days` passed since their last change.
+    ///
+    /// The new username has to follow `rules`, and be neither the username of another user nor held by one.
+    /// The previous username is then held for the user for `usernames.hold_days`, and `user.renamed` is published to `publisher`.
+    /// Choosing a username in place of the placeholder given at signup is not a change: nothing is held and no cooldown starts.
+    #[instrument(skip(db, rules, publisher), fields(user_id = %id.to_hex()), err)]
+    pub async fn rename<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>, B: EventPublisher>(db: &DB, id: Id, username: String, rules: &UsernameRules, publisher: &B) -> Result<User, Error>
+    where
+        Error: From<DB::Error>,
+        B::Error: Display
+    {
+        let user = db.get_user_by_id(id).await?.ok_or(DatabaseError::UserNotFound)?;
+        if user.username == username {
+            return Ok(user);
+        }
+        rules.check(&username)?;
+        let now = Utc::now();
+        if let Some(next) = rules.next_change(&user, now) {
+            return Err(Error::UsernameChangeTooSoon(next));
+        }
+        if Self::holder(db, &username, rules, now).await?.is_some_and(|holder| holder.id != id) {
+            return Err(DatabaseError::AlreadyExists.into());
+        }
+        let mut update = Map::from_iter([(String::from("username"), Value::String(username.clone()))]);
+        if !UsernameRules::has_placeholder(&user) {
+            update.insert(String::from("previous_username"), Value::String(user.username.clone()));
+            update.insert(String::from("username_changed_at"), serde_json::json!(now));
+        }
+        Events::update_user(db, id, update, publisher, DomainEventKind::UserRenamed { user_id: id, from: user.username, to: username }).await
+    }
+
+    /// The user going by `username`, or still holding it after changing it when `usernames.resolve_previous` is set.
+    pub async fn find_by_username<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB, username: &str, rules: &UsernameRules) -> Result<Option<User>, Error>
+    where
+        Error: From<DB::Error>
+    {
+        if let Some(user) = db.get_user_by_username(username.to_string()).await? {
+            return Ok(Some(user));
+        }
+        if !rules.resolves_previous() {
+            return Ok(None);
+        }
+        Self::holder(db, username, rules, Utc::now()).await
+    }
+
+    /// Checks that `username` follows `rules` and is not held by a user who changed it.
+    pub(super) async fn check_username<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB, username: &str, rules: &UsernameRules, now: DateTime<Utc>) -> Result<(), Error>
+    where
+        Error: From<DB::Error>
+    {
+        rules.check(username)?;
+        match Self::holder(db, username, rules, now).await? {
+            Some(_) => Err(DatabaseError::AlreadyExists.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// The user holding `username` since they changed it, if any.
+    pub(super) async fn holder<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB, username: &str, rules: &UsernameRules, now: DateTime<Utc>) -> Result<Option<User>, Error>
+    where
+        Error: From<DB::Error>
+    {
+        let users = db.get_users_by_previous_username(username.to_string()).await?;
+        Ok(users.into_iter().find(|user| rules.holds(user, username, now)))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ConversionError;
+    use crate::testing::{Harness, UserFixture};
+    use crate::adaptors::outputs::events::EventSink;
+    use crate::ports::outputs::database::MockDatabase;
+    use crate::types::{Session, Verification, GrantRecord};
+    use crate::config::UsernamesConfig;
+    use serde_json::json;
+
+    type Mock = MockDatabase<User, Session, Verification<Id>, GrantRecord, DatabaseError>;
+
+    fn patch(operations: Value) -> JsonPatch {
+        serde_json::from_value(operations).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_users_are_patched() {
+        let harness = Harness::new();
+        let user = harness.create(UserFixture::new().fullname("Jane")).await;
+        let operations = patch(json!([
+            {"op": "test", "path": "/fullname", "value": "Jane"},
+            {"op": "add", "path": "/profile", "value": "hello"},
+            {"op": "copy", "from": "/profile", "path": "/fullname"},
+        ]));
+        let (patched, etag) = Profiles::patch(&harness.db, user.id, "*", operations, &DisposableEmails::default()).await.unwrap();
+        assert_eq!((patched.fullname.as_str(), patched.profile.as_deref()), ("hello", Some("hello")));
+        let (patched, _) = Profiles::patch(&harness.db, user.id, &etag.to_string(), patch(json!([{"op": "remove", "path": "/profile"}])), &DisposableEmails::default()).await.unwrap();
+        assert_eq!(patched.profile, None);
+        assert_eq!(harness.db.get_user_by_id(user.id).await.unwrap(), Some(patched));
+    }
+
+    #[tokio::test]
+    async fn test_stale_tags_are_refused() {
+        let harness = Harness::new();
+        let user = harness.create(UserFixture::new()).await;
+        let (_, etag) = Profiles::get(&harness.db, user.id).await.unwrap();
+        let rename = |fullname: &str| patch(json!([{"op": "replace", "path": "/fullname", "value": fullname}]));
+        let (first, _) = Profiles::patch(&harness.db, user.id, &etag.to_string(), rename("First"), &DisposableEmails::default()).await.unwrap();
+        assert_eq!(Profiles::patch(&harness.db, user.id, &etag.to_string(), rename("Second"), &DisposableEmails::default()).await, Err(Error::PreconditionFailed));
+        assert_eq!(Profiles::get(&harness.db, user.id).await.unwrap().0, first);
+    }
+
+    #[tokio::test]
+    async fn test_writes_between_the_check_and_the_patch_fail_it() {
+        let user = UserFixture::new().build();
+        let db = Mock::default();
+        db.users_table().get_user_by_id_returns(Ok(Some(user.clone()))).update_user_if_returns(Err(DatabaseError::Changed));
+        let rename = patch(json!([{"op": "replace", "path": "/fullname", "value": "Jane"}]));
+        let result = Profiles::patch(&db, user.id, &ETag::of(&user).to_string(), rename, &DisposableEmails::default()).await;
+        assert_eq!(result, Err(Error::PreconditionFailed));
+        let calls = db.users_table().calls();
+        assert_eq!(calls[1].args[2], format!("{:?}", user.updated_at));
+    }
+
+    #[tokio::test]
+    async fn test_logins_and_immutable_fields_are_out_of_reach() {
+        let harness = Harness::new();
+        let user = harness.create(UserFixture::new().password("secret")).await;
+        let leak = patch(json!([{"op": "copy", "from": "/password", "path": "/profile"}]));
+        assert_eq!(Profiles::patch(&harness.db, user.id, "*", leak, &DisposableEmails::default()).await, Err(ConversionError::InvalidPatch(String::from("`/password` does not exist")).into()));
+        let status = patch(json!([{"op": "replace", "path": "/status", "value": "active"}]));
+        assert_eq!(Profiles::patch(&harness.db, user.id, "*", status, &DisposableEmails::default()).await, Err(DatabaseError::ConversionError(ConversionError::ImmutableField("status")).into()));
+        assert_eq!(harness.db.get_user_by_id(user.id).await.unwrap(), Some(user.clone()));
+        let username = patch(json!([{"op": "replace", "path": "/username", "value": "renamed"}]));
+        assert_eq!(Profiles::patch(&harness.db, user.id, "*", username, &DisposableEmails::default()).await, Err(ConversionError::ImmutableField("username").into()));
+    }
+
+    #[tokio::test]
+    async fn test_every_invalid_field_fails_the_patch() {
+        let harness = Harness::new();
+        let user = harness.create(UserFixture::new()).await;
+        let operations = patch(json!([
+            {"op": "replace", "path": "/fullname", "value": "x".repeat(300)},
+            {"op": "add", "path": "/avatar", "value": "not a url"},
+        ]));
+        let Err(Error::ConversionError(ConversionError::InvalidFields(errors))) = Profiles::patch(&harness.db, user.id, "*", operations, &DisposableEmails::default()).await else {
+            panic!("the patch is invalid");
+        };
+        let mut fields = errors.iter().map(|error| error.field.as_str()).collect::<Vec<_>>();
+        fields.sort();
+        assert_eq!(fields, vec!["avatar", "fullname"]);
+        assert_eq!(harness.db.get_user_by_id(user.id).await.unwrap(), Some(user));
+    }
+
+    #[tokio::test]
+    async fn test_renamed_users_hold_their_previous_username() {
+        let harness = Harness::new();
+        let rules = UsernameRules::from(&UsernamesConfig::default());
+        let jane = harness.create(UserFixture::new().username("jane")).await;
+        let john = harness.create(UserFixture::new().username("john")).await;
+        let renamed = Profiles::rename(&harness.db, jane.id, String::from("janet"), &rules, &EventSink::Disabled).await.unwrap();
+        assert_eq!((renamed.username.as_str(), renamed.previous_username.as_deref()), ("janet", Some("jane")));
+        assert_eq!(Profiles::find_by_username(&harness.db, "jane", &rules).await.unwrap(), Some(renamed.clone()));
+        assert_eq!(Profiles::rename(&harness.db, john.id, String::from("jane"), &rules, &EventSink::Disabled).await, Err(DatabaseError::AlreadyExists.into()));
+        assert!(matches!(Profiles::rename(&harness.db, jane.id, String::from("jay"), &rules, &EventSink::Disabled).await, Err(Error::UsernameChangeTooSoon(_))));
+        let config: UsernamesConfig = serde_json::from_value(json!({"resolve_previous": false})).unwrap();
+        assert_eq!(Profiles::find_by_username(&harness.db, "jane", &UsernameRules::from(&config)).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_renames_are_written_along_with_their_event() {
+        use crate::ports::outputs::outbox::Outbox;
+        let harness = Harness::new();
+        let rules = UsernameRules::from(&UsernamesConfig::default());
+        let jane = harness.create(UserFixture::new().username("jane")).await;
+        let renamed = Profiles::rename(&harness.db, jane.id, String::from("janet"), &rules, harness.db.outbox()).await.unwrap();
+        assert_eq!(harness.db.get_user_by_id(jane.id).await.unwrap(), Some(renamed));
+        let pending = harness.db.outbox().pending(10).await.unwrap();
+        assert_eq!(pending.into_iter().map(|event| event.kind).collect::<Vec<_>>(), vec![DomainEventKind::UserRenamed { user_id: jane.id, from: String::from("jane"), to: String::from("janet") }]);
+    }
+
+    #[tokio::test]
+    async fn test_placeholder_usernames_are_replaced_freely() {
+        let harness = Harness::new();
+        let rules = UsernameRules::from(&UsernamesConfig::default());
+        let user = harness.create(UserFixture::new()).await;
+        let placeholder = UsernameRules::placeholder(user.id);
+        harness.db.update_user(user.id, Map::from_iter([(String::from("username"), Value::String(placeholder.clone()))])).await.unwrap();
+        let renamed = Profiles::rename(&harness.db, user.id, String::from("jane"), &rules, &EventSink::Disabled).await.unwrap();
+        assert_eq!((renamed.previous_username, renamed.username_changed_at), (None, None));
+        assert_eq!(Profiles::find_by_username(&harness.db, &placeholder, &rules).await.unwrap(), None);
+        assert_eq!(Profiles::rename(&harness.db, user.id, String::from("janet"), &rules, &EventSink::Disabled).await.unwrap().username, "janet");
+    }
+
+    #[cfg(feature = "email")]
+    #[tokio::test]
+    async fn test_disposable_email_changes_are_flagged_or_refused() {
+        use crate::config::{DisposableEmailsConfig, DisposableEmailAction};
+        let harness = Harness::new();
+        let user = harness.create(UserFixture::new()).await;
+        let disposable = |action| DisposableEmails::load(&DisposableEmailsConfig { action, list: None }).unwrap();
+        let change = |email: &str| patch(json!([{"op": "replace", "path": "/email", "value": {"email": email}}]));
+        let (flagged, _) = Profiles::patch(&harness.db, user.id, "*", change("jane@yopmail.com"), &disposable(DisposableEmailAction::Flag)).await.unwrap();
+        assert_eq!(flagged.metadata.service.get(DISPOSABLE_EMAIL), Some(&Value::Bool(true)));
+        let (cleared, _) = Profiles::patch(&harness.db, user.id, "*", change("jane@acme.com"), &disposable(DisposableEmailAction::Flag)).await.unwrap();
+        assert_eq!(cleared.metadata.service.get(DISPOSABLE_EMAIL), None);
+        let refused = Profiles::patch(&harness.db, user.id, "*", change("jane@yopmail.com"), &disposable(DisposableEmailAction::Block)).await;
+        assert_eq!(refused, Err(Error::DisposableEmail("yopmail.com".into())));
+        assert_eq!(harness.db.get_user_by_id(user.id).await.unwrap(), Some(cleared));
+    }
+
+    #[cfg(feature = "email")]
+    #[tokio::test]
+    async fn test_contacts_are_not_verified_by_patches() {
+        let harness = Harness::new();
+        let user = harness.create(UserFixture::new().email("jane@acme.com").verified()).await;
+        let verify = patch(json!([{"op": "replace", "path": "/email", "value": {"email": "jane@example.com", "email_verified": true}}]));
+        let (patched, _) = Profiles::patch(&harness.db, user.id, "*", verify, &DisposableEmails::default()).await.unwrap();
+        assert!(!patched.email.unwrap().is_verified());
+        let verify = patch(json!([{"op": "replace", "path": "/email", "value": {"email": "jane@example.com", "email_verified": true}}]));
+        let (patched, _) = Profiles::patch(&harness.db, user.id, "*", verify, &DisposableEmails::default()).await.unwrap();
+        assert!(!patched.email.unwrap().is_verified());
+    }
+}
@@ -0,0 +1,78 @@
+use crate::config::{ProfileConfig, ProfileField};
+use crate::types::{Token, User};
+use super::UsernameRules;
+use serde_json::Value;
+
+
+/// the claim of the tokens of users whose profile misses a required field.
+pub const PROFILE_INCOMPLETE_CLAIM: &str = "profile_incomplete";
+
+
+/// The fields users fill in after signing up. see [`ProfileConfig`].
+#[derive(Debug, Clone, Default)]
+pub struct ProfileRequirements {
+    fields: Vec<ProfileField>,
+}
+
+
+impl ProfileRequirements {
+    /// The required fields `user` has yet to fill in, in the order they are configured.
+    pub fn missing(&self, user: &User) -> Vec<ProfileField> {
+        self.fields.iter().copied().filter(|field| !is_filled(user, *field)).collect()
+    }
+
+    pub fn is_complete(&self, user: &User) -> bool {
+        self.fields.iter().all(|field| is_filled(user, *field))
+    }
+
+    /// Flags `token`, issued to `user`, with [`PROFILE_INCOMPLETE_CLAIM`] when their profile is incomplete.
+    pub fn tag(&self, user: &User, token: &mut Token) {
+        if !self.is_complete(user) {
+            token.claims.insert(String::from(PROFILE_INCOMPLETE_CLAIM), Value::Bool(true));
+        }
+    }
+}
+
+
+impl From<&ProfileConfig> for ProfileRequirements {
+    fn from(config: &ProfileConfig) -> Self {
+        Self { fields: config.required_fields.clone() }
+    }
+}
+
+
+fn is_filled(user: &User, field: ProfileField) -> bool {
+    match field {
+        ProfileField::Username => !UsernameRules::has_placeholder(user),
+        ProfileField::Fullname => !user.fullname.trim().is_empty(),
+        #[cfg(feature = "phone")]
+        ProfileField::Phone => user.phone.is_some(),
+        ProfileField::Profile => user.profile.as_deref().is_some_and(|profile| !profile.trim().is_empty()),
+        ProfileField::Avatar => user.avatar.is_some(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::UserFixture;
+
+    #[test]
+    fn test_missing_fields_are_listed_and_flagged() {
+        let config: ProfileConfig = serde_json::from_value(serde_json::json!({"required_fields": ["username", "fullname", "avatar"]})).unwrap();
+        let requirements = ProfileRequirements::from(&config);
+        let mut user = UserFixture::new().fullname(" ").build();
+        user.username = UsernameRules::placeholder(user.id);
+        assert_eq!(requirements.missing(&user), vec![ProfileField::Username, ProfileField::Fullname, ProfileField::Avatar]);
+        let mut token = Token::default();
+        requirements.tag(&user, &mut token);
+        assert_eq!(token.claims.get(PROFILE_INCOMPLETE_CLAIM), Some(&Value::Bool(true)));
+        let user = UserFixture::new().fullname("Jane").build();
+        let user = User { avatar: Some("https://cdn.example.com/jane.png".parse().unwrap()), ..user };
+        assert!(requirements.is_complete(&user));
+        let mut token = Token::default();
+        requirements.tag(&user, &mut token);
+        assert_eq!(token.claims.get(PROFILE_INCOMPLETE_CLAIM), None);
+    }
+}
@@ -0,0 +1,309 @@
+use super::{decode, certificate_body, new_id, Binding, PROTOCOL, ASSERTION, METADATA, DSIG, EXC_C14N, ENVELOPED_SIGNATURE, RSA_SHA256, SHA256, HTTP_REDIRECT, HTTP_POST};
+use crate::config::{SamlConfig, ServiceProviderConfig, NameIdFormat};
+use rsa::signature::{SignatureEncoding, Signer};
+use super::xml::{escape_attribute, escape_text, Element};
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use base64::engine::general_purpose::STANDARD;
+use crate::types::{SamlError, User};
+use rsa::pkcs1v15::SigningKey;
+use chrono::{DateTime, TimeDelta, Utc};
+use rsa::RsaPrivateKey;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+
+const PERSISTENT: &str = "urn:oasis:names:tc:SAML:2.0:nameid-format:persistent";
+#[cfg(feature = "email")]
+const EMAIL_ADDRESS: &str = "urn:oasis:names:tc:SAML:1.1:nameid-format:emailAddress";
+const PASSWORD_PROTECTED_TRANSPORT: &str = "urn:oasis:names:tc:SAML:2.0:ac:classes:PasswordProtectedTransport";
+
+
+/// hiveguard as a SAML 2.0 identity provider, so that applications that only speak SAML can sign their users in with it.
+///
+/// Service providers send an `AuthnRequest` with the HTTP-Redirect or HTTP-POST binding, and once the user is logged in
+/// a signed assertion is posted back to the assertion consumer service configured for them.
+/// Requests are not required to be signed: assertions only ever go to the configured assertion consumer services.
+pub struct IdentityProvider {
+    entity_id: String,
+    sso_url: String,
+    /// the base64 DER of the certificate.
+    certificate: String,
+    key: SigningKey<Sha256>,
+    assertion_ttl: TimeDelta,
+    service_providers: Vec<ServiceProviderConfig>,
+}
+
+
+/// An authentication request of a service provider.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthnRequest {
+    pub id: String,
+    /// the entity id of the service provider.
+    pub issuer: String,
+    /// where the response is posted to.
+    pub acs_url: String,
+}
+
+
+/// A response to post to the assertion consumer service of a service provider.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamlResponse {
+    pub acs_url: String,
+    /// the base64 encoded `Response`, for the `SAMLResponse` form field.
+    pub saml_response: String,
+    pub relay_state: Option<String>,
+}
+
+
+impl IdentityProvider {
+    pub fn new(config: &SamlConfig) -> Result<Self, SamlError> {
+        let key = RsaPrivateKey::from_pkcs8_pem(&config.private_key)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_pem(&config.private_key))
+            .map_err(|err| SamlError::InvalidKey(err.to_string()))?;
+        Ok(Self {
+            entity_id: config.entity_id.clone(),
+            sso_url: config.sso_url.clone(),
+            certificate: certificate_body(&config.certificate)?,
+            key: SigningKey::new(key),
+            assertion_ttl: TimeDelta::seconds(config.assertion_ttl_secs.min(i64::MAX as u64 / 1000) as i64),
+            service_providers: config.service_providers.clone(),
+        })
+    }
+
+    /// The metadata service providers are configured with.
+    pub fn metadata(&self) -> String {
+        #[cfg_attr(not(feature = "email"), allow(unused_mut))]
+        let mut name_id_formats = format!("<md:NameIDFormat>{}</md:NameIDFormat>", PERSISTENT);
+        #[cfg(feature = "email")]
+        name_id_formats.push_str(&format!("<md:NameIDFormat>{}</md:NameIDFormat>", EMAIL_ADDRESS));
+        format!(
+            concat!(
+                r#"<md:EntityDescriptor xmlns:md="{}" entityID="{}">"#,
+                r#"<md:IDPSSODescriptor WantAuthnRequestsSigned="false" protocolSupportEnumeration="{}">"#,
+                r#"<md:KeyDescriptor use="signing"><ds:KeyInfo xmlns:ds="{}"><ds:X509Data><ds:X509Certificate>{}</ds:X509Certificate></ds:X509Data></ds:KeyInfo></md:KeyDescriptor>"#,
+                "{}",
+                r#"<md:SingleSignOnService Binding="{}" Location="{}"></md:SingleSignOnService>"#,
+                r#"<md:SingleSignOnService Binding="{}" Location="{}"></md:SingleSignOnService>"#,
+                "</md:IDPSSODescriptor></md:EntityDescriptor>",
+            ),
+            METADATA, escape_attribute(&self.entity_id), PROTOCOL, DSIG, self.certificate, name_id_formats,
+            HTTP_REDIRECT, escape_attribute(&self.sso_url), HTTP_POST, escape_attribute(&self.sso_url),
+        )
+    }
+
+    /// Reads the `SAMLRequest` of a service provider, received with `binding`.
+    ///
+    /// The service provider must be configured, and the assertion consumer service it asks for, if any, must be its configured one.
+    pub fn parse_request(&self, saml_request: &str, binding: Binding) -> Result<AuthnRequest, SamlError> {
+        let root = Element::parse(&decode(saml_request, binding)?)?;
+        if root.namespace != PROTOCOL || root.name != "AuthnRequest" {
+            return Err(SamlError::Malformed(format!("expected an AuthnRequest, not {}", root.name)));
+        }
+        let id = root.attribute("ID").ok_or_else(|| SamlError::Malformed("the request has no ID".into()))?;
+        let issuer = root.child(ASSERTION, "Issuer").map(Element::text).ok_or_else(|| SamlError::Malformed("the request has no Issuer".into()))?;
+        let provider = self.service_provider(&issuer)?;
+        if let Some(acs_url) = root.attribute("AssertionConsumerServiceURL") && acs_url != provider.acs_url {
+            return Err(SamlError::AcsUrlMismatch(acs_url.to_string()));
+        }
+        if let Some(binding) = root.attribute("ProtocolBinding") && binding != HTTP_POST {
+            return Err(SamlError::Malformed(format!("unsupported protocol binding {}", binding)));
+        }
+        Ok(AuthnRequest { id: id.to_string(), issuer, acs_url: provider.acs_url.clone() })
+    }
+
+    /// A successful response to `request`, asserting that `user` is logged in.
+    pub fn respond(&self, request: &AuthnRequest, user: &User, relay_state: Option<String>) -> Result<SamlResponse, SamlError> {
+        let provider = self.service_provider(&request.issuer)?;
+        let now = Utc::now();
+        let assertion = self.assertion(request, provider, user, now)?;
+        let response = format!(
+            concat!(
+                r#"<samlp:Response xmlns:samlp="{}" xmlns:saml="{}" Destination="{}" ID="{}" InResponseTo="{}" IssueInstant="{}" Version="2.0">"#,
+                "<saml:Issuer>{}</saml:Issuer>",
+                r#"<samlp:Status><samlp:StatusCode Value="urn:oasis:names:tc:SAML:2.0:status:Success"></samlp:StatusCode></samlp:Status>"#,
+                "{}</samlp:Response>",
+            ),
+            PROTOCOL, ASSERTION, escape_attribute(&provider.acs_url), new_id(), escape_attribute(&request.id), instant(now),
+            escape_text(&self.entity_id), assertion,
+        );
+        Ok(SamlResponse { acs_url: provider.acs_url.clone(), saml_response: STANDARD.encode(response), relay_state })
+    }
+
+    fn service_provider(&self, entity_id: &str) -> Result<&ServiceProviderConfig, SamlError> {
+        self.service_providers.iter()
+            .find(|provider| provider.entity_id == entity_id)
+            .ok_or_else(|| SamlError::UnknownServiceProvider(entity_id.to_string()))
+    }
+
+    /// The signed assertion. The signature is enveloped, right after the issuer as the schema requires.
+    /// Users without the contact the service provider names them by, ie guests, cannot be asserted.
+    fn assertion(&self, request: &AuthnRequest, provider: &ServiceProviderConfig, user: &User, now: DateTime<Utc>) -> Result<String, SamlError> {
+        let id = new_id();
+        let expires = instant(now + self.assertion_ttl);
+        let (format, name_id) = match provider.name_id_format {
+            NameIdFormat::Persistent => (PERSISTENT, user.id.to_hex()),
+            #[cfg(feature = "email")]
+            NameIdFormat::Email => (EMAIL_ADDRESS, user.email.as_ref().ok_or_else(|| SamlError::InvalidAssertion(String::from("the user has no email address")))?.to_string()),
+        };
+        #[cfg_attr(not(any(feature = "email", feature = "phone")), allow(unused_mut))]
+        let mut attributes = vec![("username", user.username.as_str()), ("fullname", user.fullname.as_str())];
+        #[cfg(feature = "email")]
+        attributes.extend(user.email.as_ref().map(|email| ("email", email.as_ref())));
+        #[cfg(feature = "phone")]
+        attributes.extend(user.phone.as_ref().map(|phone| ("phone", phone.as_ref())));
+        let attributes = attributes.into_iter().map(|(name, value)| format!(
+            r#"<saml:Attribute Name="{}" NameFormat="urn:oasis:names:tc:SAML:2.0:attrname-format:basic"><saml:AttributeValue>{}</saml:AttributeValue></saml:Attribute>"#,
+            name, escape_text(value),
+        )).collect::<String>();
+        let issuer = format!("<saml:Issuer>{}</saml:Issuer>", escape_text(&self.entity_id));
+        let statements = format!(
+            concat!(
+                r#"<saml:Subject><saml:NameID Format="{}">{}</saml:NameID>"#,
+                r#"<saml:SubjectConfirmation Method="urn:oasis:names:tc:SAML:2.0:cm:bearer"><saml:SubjectConfirmationData InResponseTo="{}" NotOnOrAfter="{}" Recipient="{}"></saml:SubjectConfirmationData></saml:SubjectConfirmation></saml:Subject>"#,
+                r#"<saml:Conditions NotBefore="{}" NotOnOrAfter="{}"><saml:AudienceRestriction><saml:Audience>{}</saml:Audience></saml:AudienceRestriction></saml:Conditions>"#,
+                r#"<saml:AuthnStatement AuthnInstant="{}" SessionIndex="{}"><saml:AuthnContext><saml:AuthnContextClassRef>{}</saml:AuthnContextClassRef></saml:AuthnContext></saml:AuthnStatement>"#,
+                "<saml:AttributeStatement>{}</saml:AttributeStatement>",
+            ),
+            format, escape_text(&name_id),
+            escape_attribute(&request.id), expires, escape_attribute(&provider.acs_url),
+            instant(now), expires, escape_text(&provider.entity_id),
+            instant(now), id, PASSWORD_PROTECTED_TRANSPORT,
+            attributes,
+        );
+        let open = format!(r#"<saml:Assertion xmlns:saml="{}" ID="{}" IssueInstant="{}" Version="2.0">"#, ASSERTION, id, instant(now));
+        let unsigned = format!("{}{}{}</saml:Assertion>", open, issuer, statements);
+        let signature = self.signature(&id, &unsigned);
+        Ok(format!("{}{}{}{}</saml:Assertion>", open, issuer, signature, statements))
+    }
+
+    fn signature(&self, id: &str, unsigned: &str) -> String {
+        // the assertion is parsed back so that what is digested is the canonical form the service provider computes.
+        let canonical = Element::parse(unsigned).map(|element| element.canonicalize(&|_| false)).unwrap_or_else(|_| unsigned.to_string());
+        let digest = STANDARD.encode(Sha256::digest(canonical.as_bytes()));
+        let signed_info = format!(
+            concat!(
+                r#"<ds:SignedInfo xmlns:ds="{}"><ds:CanonicalizationMethod Algorithm="{}"></ds:CanonicalizationMethod><ds:SignatureMethod Algorithm="{}"></ds:SignatureMethod>"#,
+                r##"<ds:Reference URI="#{}"><ds:Transforms><ds:Transform Algorithm="{}"></ds:Transform><ds:Transform Algorithm="{}"></ds:Transform></ds:Transforms>"##,
+                r#"<ds:DigestMethod Algorithm="{}"></ds:DigestMethod><ds:DigestValue>{}</ds:DigestValue></ds:Reference></ds:SignedInfo>"#,
+            ),
+            DSIG, EXC_C14N, RSA_SHA256, id, ENVELOPED_SIGNATURE, EXC_C14N, SHA256, digest,
+        );
+        let value = STANDARD.encode(self.key.sign(signed_info.as_bytes()).to_bytes());
+        // inside the signature, the SignedInfo inherits the namespace declaration of its parent.
+        let signed_info = signed_info.replacen(&format!(r#" xmlns:ds="{}""#, DSIG), "", 1);
+        format!(
+            r#"<ds:Signature xmlns:ds="{}">{}<ds:SignatureValue>{}</ds:SignatureValue><ds:KeyInfo><ds:X509Data><ds:X509Certificate>{}</ds:X509Certificate></ds:X509Data></ds:KeyInfo></ds:Signature>"#,
+            DSIG, signed_info, value, self.certificate,
+        )
+    }
+}
+
+
+/// A SAML `dateTime`, in UTC to the second.
+fn instant(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+
+impl SamlResponse {
+    /// An HTML page that posts the response to the assertion consumer service as soon as it loads, as the HTTP-POST binding does.
+    pub fn form(&self) -> String {
+        let relay_state = self.relay_state.as_deref()
+            .map(|relay_state| format!(r#"<input type="hidden" name="RelayState" value="{}">"#, escape_attribute(relay_state)))
+            .unwrap_or_default();
+        format!(
+            concat!(
+                r#"<!DOCTYPE html><html><body onload="document.forms[0].submit()"><form method="post" action="{}">"#,
+                r#"<input type="hidden" name="SAMLResponse" value="{}">{}<noscript><button type="submit">Continue</button></noscript></form></body></html>"#,
+            ),
+            escape_attribute(&self.acs_url), self.saml_response, relay_state,
+        )
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::fixtures::{KEY, CERTIFICATE};
+    use rsa::pkcs1v15::{Signature, VerifyingKey};
+    use rsa::signature::Verifier;
+    use crate::testing::{id, UserFixture};
+    use flate2::write::DeflateEncoder;
+    use std::io::Write;
+
+    fn config() -> SamlConfig {
+        SamlConfig {
+            enabled: true,
+            entity_id: "https://auth.example.com/saml/metadata".into(),
+            sso_url: "https://auth.example.com/saml/sso".into(),
+            certificate: CERTIFICATE.into(),
+            private_key: KEY.into(),
+            assertion_ttl_secs: 300,
+            service_providers: vec![ServiceProviderConfig {
+                entity_id: "https://app.example.com".into(),
+                acs_url: "https://app.example.com/saml/acs".into(),
+                name_id_format: NameIdFormat::Persistent,
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn user() -> User {
+        let user = UserFixture::new().id(id(1)).username("jane").fullname("Jane <Doe>");
+        #[cfg(feature = "email")]
+        let user = user.email("jane@example.com");
+        user.build()
+    }
+
+    fn redirect(xml: &str) -> String {
+        let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(xml.as_bytes()).unwrap();
+        STANDARD.encode(encoder.finish().unwrap())
+    }
+
+    #[test]
+    fn test_requests_of_configured_service_providers_are_accepted() {
+        let idp = IdentityProvider::new(&config()).unwrap();
+        let request = r#"<samlp:AuthnRequest xmlns:samlp="urn:oasis:names:tc:SAML:2.0:protocol" xmlns:saml="urn:oasis:names:tc:SAML:2.0:assertion" ID="_42" Version="2.0" AssertionConsumerServiceURL="https://app.example.com/saml/acs"><saml:Issuer>https://app.example.com</saml:Issuer></samlp:AuthnRequest>"#;
+        let parsed = idp.parse_request(&redirect(request), Binding::Redirect).unwrap();
+        assert_eq!(parsed, AuthnRequest { id: "_42".into(), issuer: "https://app.example.com".into(), acs_url: "https://app.example.com/saml/acs".into() });
+        let elsewhere = request.replace("https://app.example.com/saml/acs", "https://evil.example.com/acs");
+        assert_eq!(idp.parse_request(&STANDARD.encode(&elsewhere), Binding::Post), Err(SamlError::AcsUrlMismatch("https://evil.example.com/acs".into())));
+        let unknown = request.replace("<saml:Issuer>https://app.example.com", "<saml:Issuer>https://unknown.example.com");
+        assert_eq!(idp.parse_request(&STANDARD.encode(&unknown), Binding::Post), Err(SamlError::UnknownServiceProvider("https://unknown.example.com".into())));
+    }
+
+    #[test]
+    fn test_assertions_are_signed() {
+        let idp = IdentityProvider::new(&config()).unwrap();
+        let request = AuthnRequest { id: "_42".into(), issuer: "https://app.example.com".into(), acs_url: "https://app.example.com/saml/acs".into() };
+        let response = idp.respond(&request, &user(), Some("state".into())).unwrap();
+        let xml = String::from_utf8(STANDARD.decode(&response.saml_response).unwrap()).unwrap();
+        let root = Element::parse(&xml).unwrap();
+        assert_eq!(root.attribute("InResponseTo"), Some("_42"));
+        let assertion = root.child(ASSERTION, "Assertion").unwrap();
+        assert_eq!(assertion.find(ASSERTION, "NameID").unwrap().text(), user().id.to_hex());
+        assert_eq!(assertion.find(ASSERTION, "AttributeValue").unwrap().text(), "jane");
+        assert_eq!(assertion.find(ASSERTION, "Audience").unwrap().text(), "https://app.example.com");
+        let signature = assertion.child(DSIG, "Signature").unwrap();
+        let canonical = assertion.canonicalize(&|element| element.namespace == DSIG && element.name == "Signature");
+        let digest = STANDARD.encode(Sha256::digest(canonical.as_bytes()));
+        assert_eq!(signature.find(DSIG, "DigestValue").unwrap().text(), digest);
+        let signed_info = signature.child(DSIG, "SignedInfo").unwrap().canonicalize(&|_| false);
+        let value = STANDARD.decode(signature.child(DSIG, "SignatureValue").unwrap().text()).unwrap();
+        let key = VerifyingKey::<Sha256>::new(RsaPrivateKey::from_pkcs8_pem(KEY).unwrap().to_public_key());
+        assert!(key.verify(signed_info.as_bytes(), &Signature::try_from(value.as_slice()).unwrap()).is_ok());
+        assert!(response.form().contains(r#"name="RelayState" value="state""#));
+    }
+
+    #[test]
+    fn test_metadata_publishes_the_certificate() {
+        let idp = IdentityProvider::new(&config()).unwrap();
+        let metadata = Element::parse(&idp.metadata()).unwrap();
+        assert_eq!(metadata.attribute("entityID"), Some("https://auth.example.com/saml/metadata"));
+        assert_eq!(metadata.find(DSIG, "X509Certificate").unwrap().text(), certificate_body(CERTIFICATE).unwrap());
+        assert_eq!(metadata.find(METADATA, "SingleSignOnService").unwrap().attribute("Location"), Some("https://auth.example.com/saml/sso"));
+    }
+}
@@ -0,0 +1,109 @@
+use crate::ports::outputs::database::{Database, tables::{UsersTable, SessionsTable}};
+use crate::types::{Error, User, TokenBundle, Email, Session, Device, Login, ConversionError, SamlError};
+use crate::ports::outputs::security_events::SecurityEvents;
+use crate::ports::outputs::counters::Counters;
+use super::super::external::ExternalUsers;
+use super::super::Tokenizer;
+use super::sp::{ServiceProvider, Assertion};
+use tracing::instrument;
+use std::time::Duration;
+use std::fmt::Display;
+use chrono::Utc;
+
+
+/// Logs in the users of upstream SAML identity providers.
+///
+/// Like directory users, a user is created the first time they log in, from the attributes of the assertion,
+/// matched by email, and their name is kept in sync on every login after that.
+/// An account is only ever logged in by the identity provider that created it.
+/// An assertion logs in once: its id is kept in the counters until it expires, so a captured response can't be posted again.
+pub struct SamlLogin;
+
+
+impl SamlLogin {
+    /// Logs in the user of the `SAMLResponse` posted to the assertion consumer service.
+    ///
+    /// `request_id` is the id of the `AuthnRedirect` the login started with, `None` when it was started by the identity provider.
+    /// The login fails closed when `counters` can't tell whether the assertion was already used.
+    #[instrument(skip(db, sp, tokenizer, events, counters, saml_response, device), fields(user_id), err)]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn login<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, SessionsTable: SessionsTable<DB::Client, Item = Session>>, T: Tokenizer, E: SecurityEvents, C: Counters>(db: &DB, sp: &ServiceProvider, tokenizer: &T, events: &E, counters: &C, saml_response: &str, request_id: Option<&str>, device: Device) -> Result<TokenBundle, Error>
+    where
+        Error: From<DB::Error>,
+        Error: From<T::Error>,
+        T::Error: From<DB::Error>,
+        E::Error: Display,
+        C::Error: Display
+    {
+        let assertion = sp.consume(saml_response, request_id)?;
+        Self::claim(counters, &assertion).await?;
+        let email = Email::try_from(assertion.email.as_deref().ok_or(ConversionError::MissingFields(&["email"]))?)?;
+        let username = assertion.username.unwrap_or_else(|| email.as_ref().split('@').next().unwrap_or_default().to_string());
+        let fullname = assertion.fullname.unwrap_or_else(|| username.clone());
+        let user = ExternalUsers::provision(db, events, Login::Saml(assertion.issuer), email, username, fullname, assertion.phone).await?;
+        Ok(tokenizer.generate_token(db, user.id, device, None).await?)
+    }
+}
+
+
+impl SamlLogin {
+    /// Records the assertion as used until it expires, refusing it if it already was.
+    async fn claim<C: Counters>(counters: &C, assertion: &Assertion) -> Result<(), Error>
+    where
+        C::Error: Display
+    {
+        let key = format!("saml:assertion:{}:{}", assertion.issuer, assertion.id);
+        let ttl = (assertion.expires_at - Utc::now()).to_std().unwrap_or_default().max(Duration::from_secs(1));
+        match counters.acquire(&key, ttl).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(SamlError::InvalidAssertion("the assertion was already used".into()).into()),
+            Err(err) => {
+                tracing::error!(error = %err, "could not record the SAML assertion");
+                Err(Error::ProviderUnavailable("counters"))
+            },
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptors::outputs::databases::memory::Memory;
+    use crate::adaptors::outputs::security_events::Bus;
+    use super::super::fixtures::{service_provider, response, user};
+    use crate::adaptors::outputs::counters;
+    use crate::types::{SecurityEventKind, LoginFailure};
+    use crate::testing::Tokens;
+
+    #[tokio::test]
+    async fn test_users_are_created_on_their_first_login() {
+        let db = Memory::new(Duration::from_secs(1));
+        let (events, counters) = (Bus::new(1), counters::Memory::default());
+        let sp = ServiceProvider::new(&service_provider()).unwrap();
+        let (id, saml_response) = response(&sp, &user());
+        SamlLogin::login(&db, &sp, &Tokens, &events, &counters, &saml_response, Some(&id), Device::default()).await.unwrap();
+        let created = db.get_user_by_email(user().email.unwrap()).await.unwrap().unwrap();
+        assert_eq!((created.username.as_str(), created.fullname.as_str()), ("jane", "Jane Doe"));
+        assert_eq!(created.login, Login::Saml("https://idp.acme.com".into()));
+        let (id, saml_response) = response(&sp, &user());
+        SamlLogin::login(&db, &sp, &Tokens, &events, &counters, &saml_response, Some(&id), Device::default()).await.unwrap();
+        assert_eq!(db.list_users(None, 10).await.unwrap().items.len(), 1);
+        let replayed = SamlLogin::login(&db, &sp, &Tokens, &events, &counters, &saml_response, Some(&id), Device::default()).await;
+        assert_eq!(replayed, Err(Error::SamlError(SamlError::InvalidAssertion("the assertion was already used".into()))));
+    }
+
+    #[tokio::test]
+    async fn test_accounts_of_other_logins_are_not_taken_over() {
+        let db = Memory::new(Duration::from_secs(1));
+        db.create_user(user()).await.unwrap();
+        let events = Bus::new(1);
+        let mut subscriber = events.subscribe();
+        let counters = counters::Memory::default();
+        let sp = ServiceProvider::new(&service_provider()).unwrap();
+        let (id, saml_response) = response(&sp, &user());
+        let result = SamlLogin::login(&db, &sp, &Tokens, &events, &counters, &saml_response, Some(&id), Device::default()).await;
+        assert_eq!(result, Err(Error::InvalidCredentials));
+        assert!(matches!(subscriber.recv().await.unwrap().kind, SecurityEventKind::FailedLogin { reason: LoginFailure::WrongPassword }));
+    }
+}
@@ -0,0 +1,337 @@
+use super::{decode, certificate_body, new_id, Binding, PROTOCOL, ASSERTION, METADATA, DSIG, EXC_C14N, ENVELOPED_SIGNATURE, RSA_SHA256, SHA256, HTTP_POST};
+use crate::config::{SamlConfig, IdentityProviderConfig};
+use x509_cert::der::{Decode, Encode};
+use super::xml::{escape_attribute, escape_text, Element};
+use base64::engine::general_purpose::STANDARD;
+use rsa::pkcs1v15::{Signature, VerifyingKey};
+use chrono::{DateTime, TimeDelta, Utc};
+use rsa::pkcs8::DecodePublicKey;
+use flate2::write::DeflateEncoder;
+use rsa::signature::Verifier;
+use x509_cert::Certificate;
+use crate::types::SamlError;
+use sha2::{Digest, Sha256};
+use rsa::RsaPublicKey;
+use base64::Engine;
+use std::io::Write;
+
+
+const SUCCESS: &str = "urn:oasis:names:tc:SAML:2.0:status:Success";
+const BEARER: &str = "urn:oasis:names:tc:SAML:2.0:cm:bearer";
+
+
+/// hiveguard as a SAML 2.0 service provider of upstream identity providers, eg the one of a company,
+/// so that its employees log in with their corporate accounts.
+///
+/// Only signed assertions are accepted, either directly or through a signed response, and they must be meant for us:
+/// the audience, the recipient and, for the logins we started, the request they answer are all checked.
+/// Encrypted assertions are not supported.
+pub struct ServiceProvider {
+    entity_id: String,
+    acs_url: String,
+    clock_skew: TimeDelta,
+    identity_providers: Vec<(IdentityProviderConfig, VerifyingKey<Sha256>)>,
+}
+
+
+/// An authentication request to send the user to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthnRedirect {
+    /// the id of the request, to keep until the response comes back, eg in a cookie.
+    pub id: String,
+    /// the single sign on URL of the identity provider, with the request in its query.
+    pub url: String,
+}
+
+
+/// What an identity provider asserted about a user, with the attributes mapped as configured for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assertion {
+    /// the id of the assertion, unique for its identity provider.
+    pub id: String,
+    /// when the assertion stops being accepted, clock skew included. see `SamlLogin::login`, which refuses to consume it twice.
+    pub expires_at: DateTime<Utc>,
+    /// the entity id of the identity provider.
+    pub issuer: String,
+    pub name_id: String,
+    pub username: Option<String>,
+    pub fullname: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+}
+
+
+impl ServiceProvider {
+    pub fn new(config: &SamlConfig) -> Result<Self, SamlError> {
+        let identity_providers = config.identity_providers.iter()
+            .map(|provider| Ok((provider.clone(), verifying_key(&provider.certificate)?)))
+            .collect::<Result<_, SamlError>>()?;
+        Ok(Self {
+            entity_id: config.entity_id.clone(),
+            acs_url: config.acs_url.clone(),
+            clock_skew: TimeDelta::seconds(config.clock_skew_secs.min(i64::MAX as u64 / 1000) as i64),
+            identity_providers,
+        })
+    }
+
+    /// The metadata identity providers are configured with.
+    pub fn metadata(&self) -> String {
+        format!(
+            concat!(
+                r#"<md:EntityDescriptor xmlns:md="{}" entityID="{}">"#,
+                r#"<md:SPSSODescriptor AuthnRequestsSigned="false" WantAssertionsSigned="true" protocolSupportEnumeration="{}">"#,
+                r#"<md:AssertionConsumerService Binding="{}" Location="{}" index="0" isDefault="true"></md:AssertionConsumerService>"#,
+                "</md:SPSSODescriptor></md:EntityDescriptor>",
+            ),
+            METADATA, escape_attribute(&self.entity_id), PROTOCOL, HTTP_POST, escape_attribute(&self.acs_url),
+        )
+    }
+
+    /// An authentication request to the identity provider `name`, sent with the HTTP-Redirect binding.
+    pub fn authn_request(&self, name: &str, relay_state: Option<&str>) -> Result<AuthnRedirect, SamlError> {
+        let (provider, _) = self.identity_providers.iter()
+            .find(|(provider, _)| provider.name == name)
+            .ok_or_else(|| SamlError::UnknownIdentityProvider(name.to_string()))?;
+        let id = new_id();
+        let request = format!(
+            concat!(
+                r#"<samlp:AuthnRequest xmlns:samlp="{}" xmlns:saml="{}" AssertionConsumerServiceURL="{}" Destination="{}" ID="{}" IssueInstant="{}" ProtocolBinding="{}" Version="2.0">"#,
+                "<saml:Issuer>{}</saml:Issuer></samlp:AuthnRequest>",
+            ),
+            PROTOCOL, ASSERTION, escape_attribute(&self.acs_url), escape_attribute(&provider.sso_url), id,
+            Utc::now().format("%Y-%m-%dT%H:%M:%SZ"), HTTP_POST, escape_text(&self.entity_id),
+        );
+        let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(request.as_bytes()).and_then(|_| encoder.flush()).map_err(|err| SamlError::Malformed(err.to_string()))?;
+        let deflated = encoder.finish().map_err(|err| SamlError::Malformed(err.to_string()))?;
+        let mut url = url::Url::parse(&provider.sso_url).map_err(|err| SamlError::Malformed(err.to_string()))?;
+        url.query_pairs_mut().append_pair("SAMLRequest", &STANDARD.encode(deflated));
+        if let Some(relay_state) = relay_state {
+            url.query_pairs_mut().append_pair("RelayState", relay_state);
+        }
+        Ok(AuthnRedirect { id, url: url.into() })
+    }
+
+    /// Checks the `SAMLResponse` posted to the assertion consumer service, and reads its assertion.
+    ///
+    /// `request_id` is the id of the request the response answers, `None` when the login was started by the identity provider.
+    pub fn consume(&self, saml_response: &str, request_id: Option<&str>) -> Result<Assertion, SamlError> {
+        let root = Element::parse(&decode(saml_response, Binding::Post)?)?;
+        if root.namespace != PROTOCOL || root.name != "Response" {
+            return Err(SamlError::Malformed(format!("expected a Response, not {}", root.name)));
+        }
+        let status = root.child(PROTOCOL, "Status").and_then(|status| status.child(PROTOCOL, "StatusCode")).and_then(|code| code.attribute("Value"));
+        if status != Some(SUCCESS) {
+            return Err(SamlError::Unsuccessful(status.unwrap_or("none").to_string()));
+        }
+        if root.child(ASSERTION, "EncryptedAssertion").is_some() {
+            return Err(SamlError::Malformed("encrypted assertions are not supported".into()));
+        }
+        let mut assertions = root.elements().filter(|element| element.namespace == ASSERTION && element.name == "Assertion");
+        let (Some(assertion), None) = (assertions.next(), assertions.next()) else {
+            return Err(SamlError::Malformed("the response must have exactly one assertion".into()));
+        };
+        let id = assertion.attribute("ID").filter(|id| !id.is_empty()).map(String::from).ok_or_else(|| SamlError::Malformed("the assertion has no ID".into()))?;
+        let issuer = assertion.child(ASSERTION, "Issuer").map(Element::text).ok_or_else(|| SamlError::Malformed("the assertion has no Issuer".into()))?;
+        let (provider, key) = self.identity_providers.iter()
+            .find(|(provider, _)| provider.entity_id == issuer)
+            .ok_or_else(|| SamlError::UnknownIdentityProvider(issuer.clone()))?;
+        // the assertion read is the one the signature covers, never one found elsewhere by its id.
+        match (assertion.child(DSIG, "Signature"), root.child(DSIG, "Signature")) {
+            (Some(_), _) => verify_signature(assertion, key)?,
+            (None, Some(_)) => verify_signature(&root, key)?,
+            (None, None) => return Err(SamlError::InvalidSignature("neither the response nor the assertion is signed".into())),
+        }
+        let now = Utc::now();
+        let valid_until = self.check_conditions(assertion, now)?;
+        let subject = assertion.child(ASSERTION, "Subject").ok_or_else(|| SamlError::Malformed("the assertion has no Subject".into()))?;
+        let confirmed_until = self.check_subject_confirmation(subject, request_id, now)?;
+        let name_id = subject.child(ASSERTION, "NameID").map(Element::text).filter(|name_id| !name_id.is_empty())
+            .ok_or_else(|| SamlError::Malformed("the assertion has no NameID".into()))?;
+        let attribute = |name: &str| {
+            assertion.child(ASSERTION, "AttributeStatement")?
+                .elements()
+                .find(|attribute| attribute.name == "Attribute" && attribute.attribute("Name") == Some(name))?
+                .child(ASSERTION, "AttributeValue")
+                .map(Element::text)
+        };
+        let email = attribute(&provider.attributes.email).or_else(|| name_id.contains('@').then(|| name_id.clone()));
+        if let Some(email) = &email && !provider.domains.is_empty() {
+            let domain = email.rsplit_once('@').map(|(_, domain)| domain.to_lowercase()).unwrap_or_default();
+            if !provider.domains.iter().any(|allowed| allowed.eq_ignore_ascii_case(&domain)) {
+                return Err(SamlError::InvalidAssertion(format!("the identity provider may not log in users of {}", domain)));
+            }
+        }
+        Ok(Assertion {
+            id,
+            expires_at: valid_until.map_or(confirmed_until, |valid_until| valid_until.min(confirmed_until)) + self.clock_skew,
+            issuer,
+            username: attribute(&provider.attributes.username),
+            fullname: attribute(&provider.attributes.fullname),
+            email,
+            phone: attribute(&provider.attributes.phone),
+            name_id,
+        })
+    }
+
+    /// Returns the `NotOnOrAfter` of the conditions, if they have one.
+    fn check_conditions(&self, assertion: &Element, now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>, SamlError> {
+        let Some(conditions) = assertion.child(ASSERTION, "Conditions") else {
+            return Err(SamlError::InvalidAssertion("the assertion has no conditions".into()));
+        };
+        if let Some(not_before) = conditions.attribute("NotBefore") && instant(not_before)? > now + self.clock_skew {
+            return Err(SamlError::InvalidAssertion("the assertion is not valid yet".into()));
+        }
+        let not_on_or_after = conditions.attribute("NotOnOrAfter").map(instant).transpose()?;
+        if not_on_or_after.is_some_and(|not_on_or_after| not_on_or_after <= now - self.clock_skew) {
+            return Err(SamlError::InvalidAssertion("the assertion has expired".into()));
+        }
+        let mut restrictions = conditions.elements().filter(|element| element.namespace == ASSERTION && element.name == "AudienceRestriction").peekable();
+        if restrictions.peek().is_none() {
+            return Err(SamlError::InvalidAssertion("the assertion has no audience".into()));
+        }
+        for restriction in restrictions {
+            if !restriction.elements().any(|audience| audience.name == "Audience" && audience.text() == self.entity_id) {
+                return Err(SamlError::InvalidAssertion("the assertion is meant for another service provider".into()));
+            }
+        }
+        Ok(not_on_or_after)
+    }
+
+    /// Returns the `NotOnOrAfter` of the subject confirmation.
+    fn check_subject_confirmation(&self, subject: &Element, request_id: Option<&str>, now: DateTime<Utc>) -> Result<DateTime<Utc>, SamlError> {
+        let data = subject.elements()
+            .filter(|confirmation| confirmation.name == "SubjectConfirmation" && confirmation.attribute("Method") == Some(BEARER))
+            .find_map(|confirmation| confirmation.child(ASSERTION, "SubjectConfirmationData"))
+            .ok_or_else(|| SamlError::InvalidAssertion("the assertion has no bearer subject confirmation".into()))?;
+        if data.attribute("Recipient") != Some(self.acs_url.as_str()) {
+            return Err(SamlError::InvalidAssertion("the assertion is meant for another recipient".into()));
+        }
+        let not_on_or_after = match data.attribute("NotOnOrAfter").map(instant).transpose()? {
+            Some(not_on_or_after) if not_on_or_after > now - self.clock_skew => not_on_or_after,
+            _ => return Err(SamlError::InvalidAssertion("the subject confirmation has expired".into())),
+        };
+        if data.attribute("InResponseTo") != request_id {
+            return Err(SamlError::InvalidAssertion("the assertion does not answer the request".into()));
+        }
+        Ok(not_on_or_after)
+    }
+}
+
+
+/// Checks the enveloped signature of `element`.
+///
+/// Only exclusive canonicalization, without inclusive namespace prefixes, and RSA-SHA256 are supported.
+fn verify_signature(element: &Element, key: &VerifyingKey<Sha256>) -> Result<(), SamlError> {
+    let invalid = |reason: &str| SamlError::InvalidSignature(reason.to_string());
+    let signature = element.child(DSIG, "Signature").ok_or_else(|| invalid("the element is not signed"))?;
+    let signed_info = signature.child(DSIG, "SignedInfo").ok_or_else(|| invalid("the signature has no SignedInfo"))?;
+    let algorithm = |parent: &Element, name: &str| parent.child(DSIG, name).and_then(|element| element.attribute("Algorithm")).map(str::to_string);
+    if algorithm(signed_info, "CanonicalizationMethod").as_deref() != Some(EXC_C14N) {
+        return Err(invalid("unsupported canonicalization method"));
+    }
+    if algorithm(signed_info, "SignatureMethod").as_deref() != Some(RSA_SHA256) {
+        return Err(invalid("unsupported signature method"));
+    }
+    let mut references = signed_info.elements().filter(|element| element.namespace == DSIG && element.name == "Reference");
+    let (Some(reference), None) = (references.next(), references.next()) else {
+        return Err(invalid("the signature must have exactly one reference"));
+    };
+    let id = element.attribute("ID").ok_or_else(|| invalid("the signed element has no ID"))?;
+    if reference.attribute("URI") != Some(format!("#{}", id).as_str()) {
+        return Err(invalid("the signature does not reference the signed element"));
+    }
+    let transforms = reference.child(DSIG, "Transforms").map(|transforms| transforms.elements().collect::<Vec<_>>()).unwrap_or_default();
+    for transform in transforms {
+        if !matches!(transform.attribute("Algorithm"), Some(ENVELOPED_SIGNATURE | EXC_C14N)) || transform.elements().next().is_some() {
+            return Err(invalid("unsupported transform"));
+        }
+    }
+    if algorithm(reference, "DigestMethod").as_deref() != Some(SHA256) {
+        return Err(invalid("unsupported digest method"));
+    }
+    let canonical = element.canonicalize(&|child| std::ptr::eq(child, signature));
+    let digest = STANDARD.encode(Sha256::digest(canonical.as_bytes()));
+    if reference.child(DSIG, "DigestValue").map(Element::text) != Some(digest) {
+        return Err(invalid("the digest does not match"));
+    }
+    let value = signature.child(DSIG, "SignatureValue").map(Element::text).ok_or_else(|| invalid("the signature has no value"))?;
+    let value = STANDARD.decode(value.split_whitespace().collect::<String>()).map_err(|err| SamlError::InvalidSignature(err.to_string()))?;
+    let value = Signature::try_from(value.as_slice()).map_err(|err| SamlError::InvalidSignature(err.to_string()))?;
+    key.verify(signed_info.canonicalize(&|_| false).as_bytes(), &value).map_err(|_| invalid("the signature does not match"))
+}
+
+
+/// The RSA key of a PEM certificate.
+fn verifying_key(pem: &str) -> Result<VerifyingKey<Sha256>, SamlError> {
+    let der = STANDARD.decode(certificate_body(pem)?).map_err(|err| SamlError::InvalidKey(err.to_string()))?;
+    let certificate = Certificate::from_der(&der).map_err(|err| SamlError::InvalidKey(err.to_string()))?;
+    let spki = certificate.tbs_certificate.subject_public_key_info.to_der().map_err(|err| SamlError::InvalidKey(err.to_string()))?;
+    let key = RsaPublicKey::from_public_key_der(&spki).map_err(|err| SamlError::InvalidKey(err.to_string()))?;
+    Ok(VerifyingKey::new(key))
+}
+
+
+fn instant(value: &str) -> Result<DateTime<Utc>, SamlError> {
+    DateTime::parse_from_rfc3339(value).map(|time| time.to_utc()).map_err(|err| SamlError::Malformed(format!("{}: {}", value, err)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::fixtures::{service_provider, response, user};
+
+    fn tampered(saml_response: &str, from: &str, to: &str) -> String {
+        let xml = String::from_utf8(STANDARD.decode(saml_response).unwrap()).unwrap();
+        STANDARD.encode(xml.replacen(from, to, 1))
+    }
+
+    #[test]
+    fn test_signed_assertions_are_consumed() {
+        let sp = ServiceProvider::new(&service_provider()).unwrap();
+        let (id, saml_response) = response(&sp, &user());
+        let assertion = sp.consume(&saml_response, Some(&id)).unwrap();
+        assert_eq!(assertion.issuer, "https://idp.acme.com");
+        assert!(assertion.id.starts_with('_') && assertion.expires_at > Utc::now());
+        assert_eq!(assertion.name_id, user().id.to_hex());
+        assert_eq!((assertion.username.as_deref(), assertion.fullname.as_deref()), (Some("jane"), Some("Jane Doe")));
+        #[cfg(feature = "email")]
+        assert_eq!(assertion.email.as_deref(), Some("jane@acme.com"));
+    }
+
+    #[test]
+    fn test_tampered_responses_are_refused() {
+        let sp = ServiceProvider::new(&service_provider()).unwrap();
+        let (id, saml_response) = response(&sp, &user());
+        let forged = tampered(&saml_response, "<saml:AttributeValue>jane<", "<saml:AttributeValue>admin<");
+        assert_eq!(sp.consume(&forged, Some(&id)), Err(SamlError::InvalidSignature("the digest does not match".into())));
+        let xml = String::from_utf8(STANDARD.decode(&saml_response).unwrap()).unwrap();
+        let (start, end) = (xml.find("<ds:Signature ").unwrap(), xml.find("</ds:Signature>").unwrap() + "</ds:Signature>".len());
+        let unsigned = STANDARD.encode(format!("{}{}", &xml[..start], &xml[end..]));
+        assert_eq!(sp.consume(&unsigned, Some(&id)), Err(SamlError::InvalidSignature("neither the response nor the assertion is signed".into())));
+    }
+
+    #[test]
+    fn test_assertions_must_answer_our_request() {
+        let sp = ServiceProvider::new(&service_provider()).unwrap();
+        let (id, saml_response) = response(&sp, &user());
+        assert_eq!(sp.consume(&saml_response, Some("_other")), Err(SamlError::InvalidAssertion("the assertion does not answer the request".into())));
+        assert_eq!(sp.consume(&saml_response, None), Err(SamlError::InvalidAssertion("the assertion does not answer the request".into())));
+        let mut elsewhere = service_provider();
+        elsewhere.entity_id = "https://other.example.com".into();
+        let sp = ServiceProvider::new(&elsewhere).unwrap();
+        assert_eq!(sp.consume(&saml_response, Some(&id)), Err(SamlError::InvalidAssertion("the assertion is meant for another service provider".into())));
+        assert_eq!(sp.authn_request("unknown", None), Err(SamlError::UnknownIdentityProvider("unknown".into())));
+    }
+
+    #[cfg(feature = "email")]
+    #[test]
+    fn test_identity_providers_are_limited_to_their_domains() {
+        let mut config = service_provider();
+        config.identity_providers[0].domains = vec!["example.org".into()];
+        let sp = ServiceProvider::new(&config).unwrap();
+        let (id, saml_response) = response(&sp, &user());
+        assert_eq!(sp.consume(&saml_response, Some(&id)), Err(SamlError::InvalidAssertion("the identity provider may not log in users of acme.com".into())));
+    }
+}
@@ -0,0 +1,302 @@
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::escape::{resolve_predefined_entity, unescape};
+use std::collections::BTreeMap;
+use crate::types::SamlError;
+use quick_xml::Reader;
+
+
+const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+
+/// An element of a parsed SAML message, with the namespaces of its name and attributes resolved.
+///
+/// Comments and processing instructions are dropped, and documents with a DOCTYPE are refused.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    pub prefix: Option<String>,
+    pub name: String,
+    /// the URI of the namespace of the element, empty when it has none.
+    pub namespace: String,
+    pub attributes: Vec<Attribute>,
+    pub children: Vec<Node>,
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+    pub prefix: Option<String>,
+    pub name: String,
+    pub namespace: String,
+    pub value: String,
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
+
+
+impl Element {
+    pub fn parse(xml: &str) -> Result<Element, SamlError> {
+        let xml = xml.replace("\r\n", "\n");
+        let mut reader = Reader::from_str(&xml);
+        let mut scopes: Vec<BTreeMap<String, String>> = vec![BTreeMap::from([(String::from("xml"), String::from(XML_NAMESPACE))])];
+        let mut open: Vec<Element> = Vec::new();
+        loop {
+            let event = reader.read_event().map_err(malformed)?;
+            match event {
+                Event::Start(start) => {
+                    let element = Self::start(&start, &mut scopes)?;
+                    open.push(element);
+                },
+                Event::Empty(start) => {
+                    let element = Self::start(&start, &mut scopes)?;
+                    scopes.pop();
+                    match open.last_mut() {
+                        Some(parent) => parent.children.push(Node::Element(element)),
+                        None => return Ok(element),
+                    }
+                },
+                Event::End(_) => {
+                    scopes.pop();
+                    let element = open.pop().ok_or_else(|| SamlError::Malformed("unexpected end tag".into()))?;
+                    match open.last_mut() {
+                        Some(parent) => parent.children.push(Node::Element(element)),
+                        None => return Ok(element),
+                    }
+                },
+                Event::Text(text) => {
+                    let text = text.decode().map_err(malformed)?;
+                    push_text(&mut open, &unescape(&text).map_err(malformed)?);
+                },
+                Event::CData(data) => push_text(&mut open, &data.decode().map_err(malformed)?),
+                Event::GeneralRef(reference) => {
+                    let resolved = match reference.resolve_char_ref().map_err(malformed)? {
+                        Some(char) => char.to_string(),
+                        None => {
+                            let name = reference.decode().map_err(malformed)?;
+                            resolve_predefined_entity(&name).ok_or_else(|| SamlError::Malformed(format!("unknown entity &{};", name)))?.to_string()
+                        },
+                    };
+                    push_text(&mut open, &resolved);
+                },
+                Event::DocType(_) => return Err(SamlError::Malformed("a DOCTYPE is not allowed".into())),
+                Event::Decl(_) | Event::PI(_) | Event::Comment(_) => {},
+                Event::Eof => return Err(SamlError::Malformed("the document has no root element".into())),
+            }
+        }
+    }
+
+    fn start(start: &BytesStart, scopes: &mut Vec<BTreeMap<String, String>>) -> Result<Element, SamlError> {
+        let mut scope = scopes.last().cloned().unwrap_or_default();
+        let mut attributes = Vec::new();
+        for attribute in start.attributes() {
+            let attribute = attribute.map_err(malformed)?;
+            let key = std::str::from_utf8(attribute.key.as_ref()).map_err(malformed)?.to_string();
+            let raw = std::str::from_utf8(&attribute.value).map_err(malformed)?.replace(['\t', '\n', '\r'], " ");
+            let value = unescape(&raw).map_err(malformed)?.into_owned();
+            match key.split_once(':') {
+                None if key == "xmlns" => { scope.insert(String::new(), value); },
+                Some(("xmlns", prefix)) => { scope.insert(prefix.to_string(), value); },
+                None => attributes.push(Attribute { prefix: None, name: key, namespace: String::new(), value }),
+                Some((prefix, name)) => attributes.push(Attribute { prefix: Some(prefix.to_string()), name: name.to_string(), namespace: prefix.to_string(), value }),
+            }
+        }
+        for attribute in attributes.iter_mut().filter(|attribute| attribute.prefix.is_some()) {
+            attribute.namespace = scope.get(&attribute.namespace).cloned().ok_or_else(|| SamlError::Malformed(format!("undeclared prefix {}", attribute.namespace)))?;
+        }
+        let name = std::str::from_utf8(start.name().as_ref()).map_err(malformed)?.to_string();
+        let (prefix, name) = match name.split_once(':') {
+            Some((prefix, name)) => (Some(prefix.to_string()), name.to_string()),
+            None => (None, name),
+        };
+        let namespace = scope.get(prefix.as_deref().unwrap_or_default()).cloned();
+        let namespace = match (&prefix, namespace) {
+            (Some(prefix), None) => return Err(SamlError::Malformed(format!("undeclared prefix {}", prefix))),
+            (_, namespace) => namespace.unwrap_or_default(),
+        };
+        scopes.push(scope);
+        Ok(Element { prefix, name, namespace, attributes, children: Vec::new() })
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|attribute| attribute.prefix.is_none() && attribute.name == name).map(|attribute| attribute.value.as_str())
+    }
+
+    /// The first child element named `name` in `namespace`.
+    pub fn child(&self, namespace: &str, name: &str) -> Option<&Element> {
+        self.elements().find(|element| element.namespace == namespace && element.name == name)
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    /// The first element named `name` in `namespace`, this one or any descendant, depth first.
+    #[cfg(test)]
+    pub fn find(&self, namespace: &str, name: &str) -> Option<&Element> {
+        if self.namespace == namespace && self.name == name {
+            return Some(self);
+        }
+        self.elements().find_map(|element| element.find(namespace, name))
+    }
+
+    /// The element whose `ID` attribute is `id`.
+    #[cfg(test)]
+    pub fn find_by_id(&self, id: &str) -> Option<&Element> {
+        if self.attribute("ID") == Some(id) {
+            return Some(self);
+        }
+        self.elements().find_map(|element| element.find_by_id(id))
+    }
+
+    /// The text of the element, trimmed.
+    pub fn text(&self) -> String {
+        let text = self.children.iter().filter_map(|node| match node {
+            Node::Text(text) => Some(text.as_str()),
+            Node::Element(_) => None,
+        });
+        text.collect::<String>().trim().to_string()
+    }
+
+    /// The element in the Exclusive XML Canonicalization form, without comments.
+    ///
+    /// Children for which `exclude` returns true are left out, eg the signature of an enveloped signature.
+    pub fn canonicalize(&self, exclude: &dyn Fn(&Element) -> bool) -> String {
+        let mut out = String::new();
+        self.write_canonical(&BTreeMap::new(), exclude, &mut out);
+        out
+    }
+
+    fn write_canonical(&self, rendered: &BTreeMap<String, String>, exclude: &dyn Fn(&Element) -> bool, out: &mut String) {
+        let mut utilized = BTreeMap::new();
+        utilized.insert(self.prefix.clone().unwrap_or_default(), self.namespace.clone());
+        for attribute in &self.attributes {
+            if let Some(prefix) = &attribute.prefix {
+                utilized.insert(prefix.clone(), attribute.namespace.clone());
+            }
+        }
+        let mut scope = rendered.clone();
+        let mut declarations = Vec::new();
+        for (prefix, namespace) in utilized {
+            if prefix == "xml" {
+                continue;
+            }
+            let inherited = rendered.get(&prefix).map(String::as_str).unwrap_or_default();
+            if inherited != namespace {
+                declarations.push((prefix.clone(), namespace.clone()));
+                scope.insert(prefix, namespace);
+            }
+        }
+        let name = self.qualified_name();
+        out.push('<');
+        out.push_str(&name);
+        for (prefix, namespace) in &declarations {
+            match prefix.as_str() {
+                "" => out.push_str(" xmlns=\""),
+                prefix => {
+                    out.push_str(" xmlns:");
+                    out.push_str(prefix);
+                    out.push_str("=\"");
+                },
+            }
+            out.push_str(&escape_attribute(namespace));
+            out.push('"');
+        }
+        let mut attributes = self.attributes.iter().collect::<Vec<_>>();
+        attributes.sort_by(|a, b| (&a.namespace, &a.name).cmp(&(&b.namespace, &b.name)));
+        for attribute in attributes {
+            out.push(' ');
+            if let Some(prefix) = &attribute.prefix {
+                out.push_str(prefix);
+                out.push(':');
+            }
+            out.push_str(&attribute.name);
+            out.push_str("=\"");
+            out.push_str(&escape_attribute(&attribute.value));
+            out.push('"');
+        }
+        out.push('>');
+        for child in &self.children {
+            match child {
+                Node::Text(text) => out.push_str(&escape_text(text)),
+                Node::Element(element) if exclude(element) => {},
+                Node::Element(element) => element.write_canonical(&scope, &|_| false, out),
+            }
+        }
+        out.push_str("</");
+        out.push_str(&name);
+        out.push('>');
+    }
+
+    fn qualified_name(&self) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}:{}", prefix, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+
+fn push_text(open: &mut [Element], text: &str) {
+    // text outside of the root element is only whitespace.
+    let Some(element) = open.last_mut() else { return };
+    match element.children.last_mut() {
+        Some(Node::Text(previous)) => previous.push_str(text),
+        _ => element.children.push(Node::Text(text.to_string())),
+    }
+}
+
+
+fn malformed(err: impl std::fmt::Display) -> SamlError {
+    SamlError::Malformed(err.to_string())
+}
+
+
+/// Escapes text the way canonical XML does.
+pub fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('\r', "&#xD;")
+}
+
+
+/// Escapes an attribute value the way canonical XML does. The value is meant to be double quoted.
+pub fn escape_attribute(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('"', "&quot;").replace('\t', "&#x9;").replace('\n', "&#xA;").replace('\r', "&#xD;")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_form() {
+        let xml = r#"<?xml version="1.0"?>
+<a:Root xmlns:a="urn:a" xmlns:b="urn:b" xmlns:unused="urn:unused" z="1" b:y="2" a="&quot;&#x41;"><!-- comment --><Child
+   attribute='x&#10;y'/>a &amp; b<a:Empty/><![CDATA[<>]]></a:Root>"#;
+        let root = Element::parse(xml).unwrap();
+        assert_eq!(root.find("urn:a", "Empty").map(|element| element.name.as_str()), Some("Empty"));
+        let canonical = root.canonicalize(&|_| false);
+        assert_eq!(canonical, r#"<a:Root xmlns:a="urn:a" xmlns:b="urn:b" a="&quot;A" z="1" b:y="2"><Child attribute="x&#xA;y"></Child>a &amp; b<a:Empty></a:Empty>&lt;&gt;</a:Root>"#);
+    }
+
+    #[test]
+    fn test_namespaces_of_ancestors_are_rendered_where_used() {
+        let xml = r#"<p:Outer xmlns:p="urn:p" xmlns:q="urn:q"><q:Inner ID="_1"><p:Leaf/><q:Skipped/></q:Inner></p:Outer>"#;
+        let root = Element::parse(xml).unwrap();
+        let inner = root.find_by_id("_1").unwrap();
+        let canonical = inner.canonicalize(&|element| element.name == "Skipped");
+        assert_eq!(canonical, r#"<q:Inner xmlns:q="urn:q" ID="_1"><p:Leaf xmlns:p="urn:p"></p:Leaf></q:Inner>"#);
+    }
+
+    #[test]
+    fn test_doctypes_are_refused() {
+        let xml = r#"<!DOCTYPE a [<!ENTITY x "y">]><a>&x;</a>"#;
+        assert!(matches!(Element::parse(xml), Err(SamlError::Malformed(_))));
+    }
+}
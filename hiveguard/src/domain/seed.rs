@@ -0,0 +1,224 @@
+use crate::ports::outputs::database::{Database, tables::UsersTable};
+use crate::types::{Error, User, Id, Login, Status, Metadata, DatabaseError, ConfigError, ConfigIssue};
+use super::password::{Password, PasswordService};
+use std::collections::HashSet;
+use serde_json::Value;
+use crate::config::Format;
+use tracing::instrument;
+use serde::Deserialize;
+use std::path::Path;
+use super::admin::ROLE;
+use chrono::Utc;
+
+
+/// The users a demo, a development machine or a staging environment starts with, as declared in a JSON, TOML or YAML file.
+///
+/// ```yaml
+/// users:
+///   - username: admin
+///     email: admin@example.com
+///     password: correct horse battery staple
+///     role: admin
+/// ```
+///
+/// Loading a seed is idempotent: users that already exist, by id or by email, are left as they are.
+/// Without the `email` feature only the id matches, so users meant to survive a reload need a fixed one.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Seed {
+    #[serde(default)]
+    pub users: Vec<SeedUser>,
+}
+
+
+/// A user of a [`Seed`].
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SeedUser {
+    /// a fixed id, so that the user keeps it across resets. generated when missing.
+    #[serde(default)]
+    pub id: Option<Id>,
+    pub username: String,
+    /// the username when missing.
+    #[serde(default)]
+    pub fullname: Option<String>,
+    #[cfg(feature = "email")]
+    pub email: String,
+    #[cfg(feature = "phone")]
+    pub phone: String,
+    /// whether the contacts of the user are already verified.
+    #[serde(default)]
+    pub verified: bool,
+    /// in plain text, hashed when the user is created. users without one cannot log in with a password.
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub status: Status,
+    /// stored as the `role` field of the `service` metadata, like the role of `hiveguard admin create-admin`.
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub metadata: Metadata,
+}
+
+
+impl Seed {
+    /// Reads the seed file at `path`, its format following its extension.
+    pub async fn read(path: &Path) -> Result<Self, ConfigError> {
+        let format = Format::try_from(path)?;
+        let contents = tokio::fs::read_to_string(path).await?;
+        Self::parse(&contents, format)
+    }
+
+    /// Parses a seed, reporting every invalid user at once.
+    pub fn parse(contents: &str, format: Format) -> Result<Self, ConfigError> {
+        let seed = serde_json::from_value::<Seed>(format.parse(contents)?)?;
+        let mut issues = Vec::new();
+        let mut usernames = HashSet::new();
+        for (i, user) in seed.users.iter().enumerate() {
+            if user.username.trim().is_empty() {
+                issues.push(ConfigIssue::new(format!("users[{}].username", i), "must not be empty"));
+            } else if !usernames.insert(user.username.as_str()) {
+                issues.push(ConfigIssue::new(format!("users[{}].username", i), format!("`{}` is seeded more than once", user.username)));
+            }
+            #[cfg(feature = "email")]
+            if crate::types::Email::try_from(user.email.as_str()).is_err() {
+                issues.push(ConfigIssue::new(format!("users[{}].email", i), "must be a valid email address"));
+            }
+            #[cfg(feature = "phone")]
+            if crate::types::Phone::try_from(user.phone.clone()).is_err() {
+                issues.push(ConfigIssue::new(format!("users[{}].phone", i), "must be a valid phone number"));
+            }
+        }
+        if issues.is_empty() { Ok(seed) } else { Err(ConfigError::Invalid(issues)) }
+    }
+
+    /// Creates the users of the seed that do not exist yet. Returns the number of users created.
+    #[instrument(skip_all, fields(users = self.users.len()), err)]
+    pub async fn load<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>, P: Password + Send + Sync + 'static>(self, db: &DB, passwords: &PasswordService<P>) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>
+    {
+        let mut created = 0;
+        for seed in self.users {
+            let username = seed.username.clone();
+            #[cfg(feature = "email")]
+            if db.get_user_by_email(crate::types::Email::try_from(seed.email.as_str())?).await?.is_some() {
+                tracing::info!(%username, "the seeded user already exists");
+                continue;
+            }
+            let user = Self::user(passwords, seed).await?;
+            match db.create_user(user).await.map_err(Error::from) {
+                Ok(()) => created += 1,
+                Err(Error::DatabaseError(DatabaseError::AlreadyExists)) => tracing::info!(%username, "the seeded user already exists"),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(created)
+    }
+}
+
+
+impl Seed {
+    /// The user `seed` declares, its password hashed.
+    #[cfg_attr(not(any(feature = "email", feature = "phone")), allow(unused_mut))]
+    async fn user<P: Password + Send + Sync + 'static>(passwords: &PasswordService<P>, seed: SeedUser) -> Result<User, Error> {
+        #[cfg(feature = "email")]
+        let mut email = crate::types::Email::try_from(seed.email.as_str())?;
+        #[cfg(feature = "phone")]
+        let mut phone = crate::types::Phone::try_from(seed.phone)?;
+        if seed.verified {
+            #[cfg(feature = "email")]
+            if let crate::types::Email::New(address) = email {
+                email = crate::types::Email::Verified(address);
+            }
+            #[cfg(feature = "phone")]
+            if let crate::types::Phone::New(number) = phone {
+                phone = crate::types::Phone::Verified(number);
+            }
+        }
+        let hash = match seed.password {
+            Some(password) => passwords.hash_password(password).await?,
+            None => String::new(),
+        };
+        let mut metadata = seed.metadata;
+        if let Some(role) = seed.role {
+            metadata.service.insert(String::from(ROLE), Value::String(role));
+        }
+        let now = Utc::now();
+        Ok(User {
+            id: seed.id.unwrap_or_default(),
+            fullname: seed.fullname.unwrap_or_else(|| seed.username.clone()),
+            username: seed.username,
+            #[cfg(feature = "email")]
+            email: Some(email),
+            #[cfg(feature = "phone")]
+            phone: Some(phone),
+            login: Login::Password(hash),
+            profile: None,
+            avatar: None,
+            status: seed.status,
+            status_reason: None,
+            previous_username: None,
+            username_changed_at: None,
+            metadata,
+            consents: Default::default(),
+            created_at: now,
+            updated_at: now,
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Harness;
+
+    /// A seeded user in YAML, with the contacts the build has.
+    fn user(n: u8, fields: &str) -> String {
+        let mut user = format!("  - id: '{:024}'\n    username: user{}\n", n, n);
+        if cfg!(feature = "email") {
+            user.push_str(&format!("    email: user{}@example.com\n", n));
+        }
+        if cfg!(feature = "phone") {
+            user.push_str(&format!("    phone: '+25471234567{}'\n", n));
+        }
+        user + fields
+    }
+
+    #[tokio::test]
+    async fn test_seeds_are_loaded_once() {
+        let harness = Harness::new();
+        let contents = format!("users:\n{}{}", user(1, "    password: secret\n    role: admin\n"), user(2, "    status: suspended\n"));
+        let seed = Seed::parse(&contents, Format::Yaml).unwrap();
+        assert_eq!(seed.clone().load(&harness.db, &harness.passwords).await, Ok(2));
+        let admin = harness.db.get_user_by_id(Id::try_from(format!("{:024}", 1)).unwrap()).await.unwrap().unwrap();
+        assert_eq!((admin.fullname.as_str(), admin.login, admin.status), ("user1", Login::Password(String::from("secret")), Status::Active));
+        assert_eq!(admin.metadata.service[ROLE], "admin");
+        let suspended = harness.db.get_user_by_id(Id::try_from(format!("{:024}", 2)).unwrap()).await.unwrap().unwrap();
+        assert_eq!(suspended.status, Status::Suspended);
+        assert_eq!(seed.load(&harness.db, &harness.passwords).await, Ok(0));
+    }
+
+    #[test]
+    fn test_invalid_users_are_reported() {
+        let contents = format!("users:\n{}{}{}", user(1, ""), user(2, "").replace("username: user2", "username: ' '"), user(3, "").replace("username: user3", "username: user1"));
+        let Err(ConfigError::Invalid(issues)) = Seed::parse(&contents, Format::Yaml) else {
+            panic!("the seed is invalid");
+        };
+        let fields = issues.iter().map(|issue| issue.field.as_str()).collect::<Vec<_>>();
+        assert_eq!(fields, vec!["users[1].username", "users[2].username"]);
+        assert!(matches!(Seed::parse("users:\n  - username: jane\n    role: admin\n    admin: true\n", Format::Yaml), Err(ConfigError::Parse(_))));
+    }
+
+    #[cfg(feature = "email")]
+    #[test]
+    fn test_invalid_emails_are_reported() {
+        let contents = user(1, "").replace("user1@example.com", "user1");
+        let Err(ConfigError::Invalid(issues)) = Seed::parse(&format!("users:\n{}", contents), Format::Yaml) else {
+            panic!("the email is invalid");
+        };
+        assert_eq!(issues, vec![ConfigIssue::new("users[0].email", "must be a valid email address")]);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::config::{Config, ServiceConfig};
+use super::redirects::RedirectUris;
+use crate::types::{Error, TOKEN_EXCHANGE_GRANT};
+use std::collections::HashMap;
+use std::str::FromStr;
+use url::Url;
+
+
+/// The ways a service gets tokens from the token endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grant {
+    AuthorizationCode,
+    RefreshToken,
+    /// the user gives the service their password. first-party services only.
+    Password,
+    /// the service trades a token of the user for another (RFC 8693). first-party services only.
+    TokenExchange,
+}
+
+
+impl FromStr for Grant {
+    type Err = Error;
+
+    /// The grant of the `grant_type` of a request to the token endpoint.
+    fn from_str(grant_type: &str) -> Result<Self, Self::Err> {
+        match grant_type {
+            "authorization_code" => Ok(Grant::AuthorizationCode),
+            "refresh_token" => Ok(Grant::RefreshToken),
+            "password" => Ok(Grant::Password),
+            TOKEN_EXCHANGE_GRANT => Ok(Grant::TokenExchange),
+            _ => Err(Error::UnsupportedGrantType(String::from(grant_type))),
+        }
+    }
+}
+
+
+/// The services registered under `services`, and what each one may do depending on whether it is first-party.
+///
+/// Third-party services always show the consent screen, and only get tokens through the authorization code flow.
+/// First-party apps may also use the password grant and token exchange, while they move to the code flow.
+#[derive(Debug, Clone)]
+pub struct Services {
+    services: HashMap<String, ServiceConfig>,
+    redirects: RedirectUris,
+}
+
+
+impl Services {
+    /// The service registered as `client_id`, or `Error::UnknownService`.
+    pub fn get(&self, client_id: &str) -> Result<&ServiceConfig, Error> {
+        self.services.get(client_id).ok_or_else(|| Error::UnknownService(String::from(client_id)))
+    }
+
+    /// Whether users are asked to consent before the service gets tokens on their behalf.
+    pub fn requires_consent(&self, client_id: &str) -> Result<bool, Error> {
+        Ok(!self.get(client_id)?.first_party)
+    }
+
+    /// Checks the service may get tokens through `grant`.
+    pub fn check_grant(&self, client_id: &str, grant: Grant) -> Result<(), Error> {
+        let service = self.get(client_id)?;
+        match grant {
+            Grant::AuthorizationCode | Grant::RefreshToken => Ok(()),
+            Grant::Password if service.first_party => Ok(()),
+            Grant::Password => Err(Error::ServiceNotTrusted("the password grant")),
+            Grant::TokenExchange if service.first_party => Ok(()),
+            Grant::TokenExchange => Err(Error::ServiceNotTrusted("token exchange")),
+        }
+    }
+
+    /// Checks the service may log users in with cookie sessions rather than bearer tokens.
+    pub fn check_cookies(&self, client_id: &str) -> Result<(), Error> {
+        match self.get(client_id)?.first_party {
+            true => Ok(()),
+            false => Err(Error::ServiceNotTrusted("cookie sessions")),
+        }
+    }
+
+    /// The uri the authorization endpoint redirects to: `requested`, when it is registered for the service.
+    pub fn redirect_uri(&self, client_id: &str, requested: &str) -> Result<Url, Error> {
+        self.redirects.check(&self.get(client_id)?.redirect_uris, requested)
+    }
+}
+
+
+impl From<&Config> for Services {
+    fn from(config: &Config) -> Self {
+        Self {
+            services: config.services.iter().map(|service| (service.client_id.clone(), service.clone())).collect(),
+            redirects: RedirectUris::from(&config.redirects),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_first_party_services_are_trusted() {
+        let config = Config {
+            services: serde_json::from_value(serde_json::json!([
+                {"client_id": "web", "first_party": true, "redirect_uris": ["https://app.example.com/callback"]},
+                {"client_id": "partner", "redirect_uris": ["https://partner.example.org/callback"]},
+            ])).unwrap(),
+            ..Default::default()
+        };
+        let services = Services::from(&config);
+        assert_eq!(services.requires_consent("web"), Ok(false));
+        assert_eq!(services.check_grant("web", Grant::Password), Ok(()));
+        assert_eq!(services.check_cookies("web"), Ok(()));
+        assert_eq!(services.requires_consent("partner"), Ok(true));
+        assert_eq!(services.check_grant("partner", Grant::AuthorizationCode), Ok(()));
+        assert_eq!(services.check_grant("partner", Grant::Password), Err(Error::ServiceNotTrusted("the password grant")));
+        assert_eq!(services.check_grant("web", Grant::TokenExchange), Ok(()));
+        assert_eq!(services.check_grant("partner", Grant::TokenExchange), Err(Error::ServiceNotTrusted("token exchange")));
+        assert_eq!("urn:ietf:params:oauth:grant-type:token-exchange".parse(), Ok(Grant::TokenExchange));
+        assert_eq!("client_credentials".parse::<Grant>(), Err(Error::UnsupportedGrantType(String::from("client_credentials"))));
+        assert_eq!(services.check_cookies("partner"), Err(Error::ServiceNotTrusted("cookie sessions")));
+        assert!(services.redirect_uri("partner", "https://partner.example.org/callback").is_ok());
+        assert!(matches!(services.redirect_uri("partner", "https://app.example.com/callback"), Err(Error::InvalidRedirectUri(_))));
+        assert_eq!(services.requires_consent("unknown"), Err(Error::UnknownService(String::from("unknown"))));
+    }
+}
@@ -0,0 +1,221 @@
+use crate::ports::outputs::database::{Database, tables::SessionsTable};
+use crate::types::{Error, Id, Session, DatabaseError, SecurityEvent, SecurityEventKind, Agent};
+use crate::ports::outputs::security_events::SecurityEvents;
+use chrono::{DateTime, TimeDelta, Utc};
+use super::tokenization::cache::revoke_session;
+use crate::config::SessionsConfig;
+use tracing::instrument;
+use std::fmt::Display;
+
+
+/// Operations a user runs on their own sessions.
+pub struct Sessions;
+
+
+/// When sessions expire. Built from `SessionsConfig`, a stricter policy can be used for some users.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SessionPolicy {
+    pub idle_timeout: TimeDelta,
+    pub max_lifetime: TimeDelta,
+}
+
+
+impl From<&SessionsConfig> for SessionPolicy {
+    fn from(config: &SessionsConfig) -> Self {
+        let seconds = |secs: u64| TimeDelta::seconds(secs.min(i64::MAX as u64 / 1000) as i64);
+        Self { idle_timeout: seconds(config.idle_timeout_secs), max_lifetime: seconds(config.max_lifetime_secs) }
+    }
+}
+
+
+impl SessionPolicy {
+    /// the idle timeout slides with the activity of the session but never past its maximum lifetime.
+    pub fn expires_at(&self, session: &Session) -> DateTime<Utc> {
+        (session.last_active_at + self.idle_timeout).min(session.created_at + self.max_lifetime)
+    }
+}
+
+
+impl Sessions {
+    /// Logs `user_id` out everywhere, eg after a password change or a suspected compromise.
+    ///
+    /// Every session but `except` is deleted along with its refresh tokens, so none of them can be renewed.
+    /// Access tokens already issued stay valid until they expire.
+    /// Returns the number of sessions revoked.
+    #[instrument(skip(db, events), fields(user_id = %user_id.to_hex()), err)]
+    pub async fn revoke_all<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>, E: SecurityEvents>(db: &DB, events: &E, user_id: Id, except: Option<Id>) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>,
+        E::Error: Display
+    {
+        let revoked = Self::delete_all(db, user_id, except).await?;
+        let event = SecurityEvent::new(SecurityEventKind::SessionsRevoked { count: revoked, kept: except }, Some(user_id));
+        if let Err(err) = events.emit(event).await {
+            tracing::error!(error = %err, "could not emit the security event");
+        }
+        Ok(revoked)
+    }
+}
+
+
+impl Sessions {
+    /// The sessions of `user_id`, the most recently active first, each with what its user agent tells about its device.
+    /// Sessions started before devices were described are described from their user agent.
+    #[instrument(skip(db), fields(user_id = %user_id.to_hex()), err)]
+    pub async fn list<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(db: &DB, user_id: Id) -> Result<Vec<Session>, Error>
+    where
+        Error: From<DB::Error>
+    {
+        let mut sessions = db.get_sessions_by_user_id(user_id).await?;
+        for session in &mut sessions {
+            if session.device.agent.is_none() {
+                session.device.agent = session.device.user_agent.as_deref().map(Agent::parse);
+            }
+        }
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.last_active_at));
+        Ok(sessions)
+    }
+
+    /// Extends the session `id` for an authenticated request.
+    ///
+    /// A session that has expired under `policy` is deleted and refused with `Error::SessionExpired`.
+    #[instrument(skip(db), fields(session_id = %id.to_hex()), err)]
+    pub async fn extend<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(db: &DB, policy: &SessionPolicy, id: Id) -> Result<Session, Error>
+    where
+        Error: From<DB::Error>
+    {
+        let mut session = db.get_session_by_id(id).await?.ok_or(DatabaseError::SessionNotFound)?;
+        let now = Utc::now();
+        if policy.expires_at(&session) <= now {
+            Self::delete(db, id).await?;
+            return Err(Error::SessionExpired);
+        }
+        db.touch_session(id).await?;
+        session.last_active_at = now;
+        Ok(session)
+    }
+}
+
+
+impl Sessions {
+    /// Deletes the session `id` and revokes its tokens in the token caches of the process. see [`revoke_session`].
+    ///
+    /// Every session is deleted through it, or through [`Sessions::purge_expired`], so that no cached token outlives its session.
+    pub(super) async fn delete<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(db: &DB, id: Id) -> Result<(), Error>
+    where
+        Error: From<DB::Error>
+    {
+        db.delete_session(id).await?;
+        revoke_session(id);
+        Ok(())
+    }
+
+    /// Deletes the sessions that went unused for longer than the idle timeout of `policy` or outlived its maximum lifetime,
+    /// revoking their tokens like [`Sessions::delete`]. Returns the number of sessions deleted.
+    pub(super) async fn purge_expired<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(db: &DB, policy: &SessionPolicy) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>
+    {
+        let now = Utc::now();
+        let purged = db.purge_expired_sessions(now - policy.idle_timeout, now - policy.max_lifetime).await?;
+        for id in &purged {
+            revoke_session(*id);
+        }
+        Ok(purged.len())
+    }
+
+    pub(super) async fn delete_all<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(db: &DB, user_id: Id, except: Option<Id>) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>
+    {
+        let mut deleted = 0;
+        for session in db.get_sessions_by_user_id(user_id).await? {
+            if Some(session.id) != except {
+                Self::delete(db, session.id).await?;
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::id;
+    use crate::adaptors::outputs::security_events::Bus;
+    use crate::ports::outputs::database::MockDatabase;
+    use crate::types::{DatabaseError, GrantRecord, User, Verification};
+    use chrono::Utc;
+
+    type Mock = MockDatabase<User, Session, Verification<Id>, GrantRecord, DatabaseError>;
+
+    fn session(n: u8) -> Session {
+        Session { id: id(n), user_id: id(1), refresh_token_id: id(n + 10), previous_refresh_token_id: None, device: Default::default(), created_at: Utc::now(), updated_at: Utc::now(), last_active_at: Utc::now() }
+    }
+
+    fn policy() -> SessionPolicy {
+        SessionPolicy { idle_timeout: TimeDelta::hours(1), max_lifetime: TimeDelta::days(1) }
+    }
+
+    #[test]
+    fn test_expiry_slides_up_to_the_maximum_lifetime() {
+        let mut session = session(2);
+        session.created_at = Utc::now() - TimeDelta::hours(2);
+        session.last_active_at = Utc::now() - TimeDelta::minutes(30);
+        assert_eq!(policy().expires_at(&session), session.last_active_at + TimeDelta::hours(1));
+        session.created_at = Utc::now() - TimeDelta::hours(24);
+        assert_eq!(policy().expires_at(&session), session.created_at + TimeDelta::days(1));
+    }
+
+    #[tokio::test]
+    async fn test_active_sessions_are_extended() {
+        let db = Mock::default();
+        let mut active = session(2);
+        active.last_active_at = Utc::now() - TimeDelta::minutes(30);
+        db.sessions_table().get_session_by_id_returns(Ok(Some(active.clone()))).touch_session_returns(Ok(()));
+        let extended = Sessions::extend(&db, &policy(), active.id).await.unwrap();
+        assert!(extended.last_active_at > active.last_active_at);
+        assert_eq!(db.sessions_table().calls()[1].method, "touch_session");
+    }
+
+    #[tokio::test]
+    async fn test_idle_sessions_expire() {
+        let db = Mock::default();
+        let mut idle = session(2);
+        idle.last_active_at = Utc::now() - TimeDelta::hours(2);
+        db.sessions_table().get_session_by_id_returns(Ok(Some(idle.clone()))).delete_session_returns(Ok(()));
+        assert_eq!(Sessions::extend(&db, &policy(), idle.id).await, Err(Error::SessionExpired));
+        assert_eq!(db.sessions_table().calls()[1].method, "delete_session");
+    }
+
+    #[tokio::test]
+    async fn test_sessions_are_listed_with_their_device() {
+        let db = Mock::default();
+        let mut old = session(2);
+        old.last_active_at = Utc::now() - TimeDelta::days(1);
+        old.device.user_agent = Some(String::from("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36"));
+        db.sessions_table().get_sessions_by_user_id_returns(Ok(vec![old, session(3)]));
+        let sessions = Sessions::list(&db, id(1)).await.unwrap();
+        assert_eq!(sessions.iter().map(|session| session.id).collect::<Vec<_>>(), vec![id(3), id(2)]);
+        assert_eq!(sessions[1].device.agent.as_ref().map(Agent::to_string).as_deref(), Some("Chrome on Windows 10"));
+        assert_eq!(sessions[0].device.agent, None);
+    }
+
+    #[tokio::test]
+    async fn test_revoke_all_keeps_the_current_session() {
+        let db = Mock::default();
+        db.sessions_table()
+            .get_sessions_by_user_id_returns(Ok(vec![session(2), session(3), session(4)]))
+            .delete_session_returns(Ok(()))
+            .delete_session_returns(Ok(()));
+        let events = Bus::new(1);
+        let mut subscriber = events.subscribe();
+        assert_eq!(Sessions::revoke_all(&db, &events, id(1), Some(id(3))).await, Ok(2));
+        let deleted = db.sessions_table().calls().into_iter().filter(|call| call.method == "delete_session").map(|call| call.args).collect::<Vec<_>>();
+        assert_eq!(deleted, vec![vec![format!("{:?}", id(2))], vec![format!("{:?}", id(4))]]);
+        let event = subscriber.recv().await.unwrap();
+        assert_eq!(event.kind, SecurityEventKind::SessionsRevoked { count: 2, kept: Some(id(3)) });
+    }
+}
@@ -0,0 +1,125 @@
+#[cfg(feature = "email")]
+use super::disposable::{DisposableEmails, DISPOSABLE_EMAIL};
+use crate::config::SignupConfig;
+#[cfg(any(feature = "email", feature = "phone"))]
+use crate::types::ConversionError;
+use crate::types::{Error, User};
+
+
+/// Who can sign up on their own. see [`SignupConfig`].
+#[derive(Debug, Clone, Default)]
+pub struct SignupRules {
+    invite_only: bool,
+    /// the lowercased domains.
+    #[cfg(feature = "email")]
+    allowed_domains: Vec<String>,
+    #[cfg(feature = "email")]
+    denied_domains: Vec<String>,
+    #[cfg(feature = "email")]
+    disposable: DisposableEmails,
+}
+
+
+impl SignupRules {
+    /// The rules, with the disposable email providers refused or flagged at signup.
+    #[cfg(feature = "email")]
+    pub fn with_disposable_emails(self, disposable: DisposableEmails) -> Self {
+        Self { disposable, ..self }
+    }
+
+    /// Checks that `user` can sign up, before anything else is done with it, and flags it when its email address is disposable.
+    ///
+    /// The user needs the contact they log in with, their email address, or their phone number in builds without email.
+    /// The other fields can be filled in later. see `ProfileRequirements`. A phone number has to be valid, as the numbers read back are not checked as strictly.
+    pub fn check(&self, user: &mut User) -> Result<(), Error> {
+        if self.invite_only {
+            return Err(Error::SignupClosed);
+        }
+        #[cfg(feature = "phone")]
+        if let Some(phone) = &user.phone {
+            phone.check()?;
+        }
+        #[cfg(all(feature = "phone", not(feature = "email")))]
+        if user.phone.is_none() {
+            return Err(ConversionError::MissingField("phone").into());
+        }
+        #[cfg(feature = "email")]
+        {
+            let email = user.email.as_ref().ok_or(ConversionError::MissingField("email"))?;
+            let domain = email.domain().to_lowercase();
+            let listed = |domains: &[String]| domains.iter().any(|listed| domain == *listed || domain.strip_suffix(listed.as_str()).is_some_and(|sub| sub.ends_with('.')));
+            if listed(&self.denied_domains) || (!self.allowed_domains.is_empty() && !listed(&self.allowed_domains)) {
+                return Err(Error::EmailDomainNotAllowed(domain));
+            }
+            if self.disposable.check(email)? {
+                user.metadata.service.insert(String::from(DISPOSABLE_EMAIL), serde_json::Value::Bool(true));
+            }
+        }
+        #[cfg(not(feature = "email"))]
+        let _ = user;
+        Ok(())
+    }
+}
+
+
+impl From<&SignupConfig> for SignupRules {
+    fn from(config: &SignupConfig) -> Self {
+        #[cfg(feature = "email")]
+        let lowercase = |domains: &[String]| domains.iter().map(|domain| domain.to_lowercase()).collect();
+        Self {
+            invite_only: config.invite_only,
+            #[cfg(feature = "email")]
+            allowed_domains: lowercase(&config.allowed_domains),
+            #[cfg(feature = "email")]
+            denied_domains: lowercase(&config.denied_domains),
+            #[cfg(feature = "email")]
+            disposable: DisposableEmails::default(),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::UserFixture;
+    #[cfg(feature = "email")]
+    use crate::config::{DisposableEmailsConfig, DisposableEmailAction};
+
+    #[test]
+    fn test_invite_only_signup_is_closed() {
+        let config: SignupConfig = serde_json::from_value(serde_json::json!({"invite_only": true})).unwrap();
+        assert_eq!(SignupRules::from(&config).check(&mut UserFixture::new().build()), Err(Error::SignupClosed));
+        assert_eq!(SignupRules::default().check(&mut UserFixture::new().build()), Ok(()));
+    }
+
+    #[cfg(feature = "email")]
+    #[test]
+    fn test_email_domains_are_listed_with_their_subdomains() {
+        let config = SignupConfig { allowed_domains: vec!["Acme.com".into()], denied_domains: vec!["contractors.acme.com".into()], ..Default::default() };
+        let rules = SignupRules::from(&config);
+        let check = |email: &str| rules.check(&mut UserFixture::new().email(email).build());
+        assert_eq!(check("jane@ACME.com"), Ok(()));
+        assert_eq!(check("jane@eu.acme.com"), Ok(()));
+        assert_eq!(check("jane@notacme.com"), Err(Error::EmailDomainNotAllowed("notacme.com".into())));
+        assert_eq!(check("jane@contractors.acme.com"), Err(Error::EmailDomainNotAllowed("contractors.acme.com".into())));
+    }
+
+    #[cfg(feature = "email")]
+    #[test]
+    fn test_disposable_emails_are_flagged_at_signup() {
+        let config = DisposableEmailsConfig { action: DisposableEmailAction::Flag, list: None };
+        let rules = SignupRules::default().with_disposable_emails(DisposableEmails::load(&config).unwrap());
+        let mut user = UserFixture::new().email("jane@mailinator.com").build();
+        rules.check(&mut user).unwrap();
+        assert_eq!(user.metadata.service.get(DISPOSABLE_EMAIL), Some(&serde_json::Value::Bool(true)));
+    }
+
+    #[cfg(feature = "phone")]
+    #[test]
+    fn test_legacy_phone_numbers_are_refused_at_signup() {
+        let mut user = UserFixture::new().build();
+        user.phone = Some(crate::types::Phone::New(String::from("1234567890")));
+        assert_eq!(SignupRules::default().check(&mut user), Err(ConversionError::InvalidPhoneNumber.into()));
+    }
+}
@@ -0,0 +1,133 @@
+use crate::types::{Error, Token, TenantId, ConversionError};
+use crate::config::TenancyConfig;
+use std::collections::HashMap;
+use serde_json::Value;
+
+
+/// the claim of tokens naming the tenant they were issued for.
+pub const TENANT_CLAIM: &str = "tid";
+
+
+/// The tenants of a multi-tenant deployment, each with a database of its own.
+///
+/// Isolation comes from the databases rather than from every query: the domain services are handed the database
+/// of the tenant of a request and cannot reach the items of any other. The tenant of a request is the one served at
+/// its host, or, on a host shared by the tenants, the one its token was issued for. Tokens carry their tenant in the
+/// `tid` claim and are refused by the other tenants, even before their session is looked up.
+pub struct Tenancy<DB> {
+    hosts: HashMap<String, TenantId>,
+    databases: HashMap<TenantId, DB>,
+}
+
+
+impl<DB> Tenancy<DB> {
+    /// The tenants of `config`, `database` building the database of each of them. eg `Memory::new`,
+    /// or `DynamoDB::new` with the tables of `DynamoDBConfig::for_tenant`.
+    pub fn new<F: FnMut(&TenantId) -> DB>(config: &TenancyConfig, mut database: F) -> Result<Self, ConversionError> {
+        let mut hosts = HashMap::new();
+        let mut databases = HashMap::new();
+        for tenant in &config.tenants {
+            let id = TenantId::try_from(tenant.id.as_str())?;
+            hosts.extend(tenant.hosts.iter().map(|host| (normalize(host), id.clone())));
+            let db = database(&id);
+            databases.insert(id, db);
+        }
+        Ok(Self { hosts, databases })
+    }
+
+    /// The tenants, in no particular order.
+    pub fn tenants(&self) -> impl Iterator<Item = &TenantId> {
+        self.databases.keys()
+    }
+
+    /// The database of `tenant`.
+    pub fn database(&self, tenant: &TenantId) -> Result<&DB, Error> {
+        self.databases.get(tenant).ok_or_else(|| Error::UnknownTenant(tenant.to_string()))
+    }
+
+    /// The tenant of a request to `host`, the value of its `Host` header, presenting `token`, and the database of that tenant.
+    ///
+    /// A token issued for another tenant than the one of the host, or without a tenant, is refused as invalid credentials.
+    pub fn resolve(&self, host: &str, token: Option<&Token>) -> Result<(&TenantId, &DB), Error> {
+        let tenant = match (self.hosts.get(&normalize(host)), token) {
+            (Some(tenant), Some(token)) if self.tenant_of(token)? != tenant => return Err(Error::InvalidCredentials),
+            (Some(tenant), _) => tenant,
+            (None, Some(token)) => self.tenant_of(token)?,
+            (None, None) => return Err(Error::UnknownTenant(host.to_string())),
+        };
+        let db = self.database(tenant)?;
+        Ok((tenant, db))
+    }
+
+    /// Issues `token` for `tenant`.
+    pub fn tag(tenant: &TenantId, token: &mut Token) {
+        token.claims.insert(String::from(TENANT_CLAIM), Value::String(tenant.to_string()));
+    }
+
+    /// The tenant `token` was issued for, which has to be one of the deployment.
+    fn tenant_of(&self, token: &Token) -> Result<&TenantId, Error> {
+        let Some(Ok(tenant)) = token.claims.get(TENANT_CLAIM).and_then(Value::as_str).map(TenantId::try_from) else {
+            return Err(Error::InvalidCredentials);
+        };
+        self.databases.get_key_value(&tenant).map(|(tenant, _)| tenant).ok_or(Error::InvalidCredentials)
+    }
+}
+
+
+/// A host as it is looked up: lowercase, without its port or the trailing dot of a fully qualified name.
+fn normalize(host: &str) -> String {
+    let host = host.trim();
+    let host = match host.strip_prefix('[') {
+        // an IPv6 address, eg `[::1]:8080`
+        Some(address) => address.split(']').next().unwrap_or(address),
+        None => host.rsplit_once(':').map_or(host, |(host, _)| host),
+    };
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptors::outputs::databases::memory::Memory;
+    use crate::ports::outputs::database::Database;
+    use crate::config::TenantConfig;
+    use crate::testing::UserFixture;
+    use std::time::Duration;
+
+    fn tenancy() -> Tenancy<Memory> {
+        let tenant = |id: &str, host: &str| TenantConfig { id: id.into(), hosts: vec![host.into()] };
+        let config = TenancyConfig { tenants: vec![tenant("acme", "auth.acme.com"), tenant("globex", "login.globex.io")] };
+        Tenancy::new(&config, |_| Memory::new(Duration::from_secs(1))).unwrap()
+    }
+
+    fn token(tenant: &str) -> Token {
+        let mut token = Token::default();
+        Tenancy::<Memory>::tag(&TenantId::try_from(tenant).unwrap(), &mut token);
+        token
+    }
+
+    #[tokio::test]
+    async fn test_tenants_do_not_share_users() {
+        let tenancy = tenancy();
+        let user = UserFixture::new().build();
+        let (acme, db) = tenancy.resolve("Auth.Acme.com:443", None).unwrap();
+        assert_eq!(acme.as_str(), "acme");
+        db.create_user(user.clone()).await.unwrap();
+        let (_, globex) = tenancy.resolve("login.globex.io.", None).unwrap();
+        assert_eq!(globex.get_user_by_id(user.id).await.unwrap(), None);
+        globex.create_user(user).await.unwrap();
+        assert_eq!(tenancy.tenants().count(), 2);
+    }
+
+    #[test]
+    fn test_tokens_are_bound_to_their_tenant() {
+        let tenancy = tenancy();
+        assert_eq!(tenancy.resolve("auth.acme.com", Some(&token("acme"))).unwrap().0.as_str(), "acme");
+        assert_eq!(tenancy.resolve("api.shared.com", Some(&token("globex"))).unwrap().0.as_str(), "globex");
+        assert_eq!(tenancy.resolve("auth.acme.com", Some(&token("globex"))).err(), Some(Error::InvalidCredentials));
+        assert_eq!(tenancy.resolve("api.shared.com", Some(&token("initech"))).err(), Some(Error::InvalidCredentials));
+        assert_eq!(tenancy.resolve("api.shared.com", Some(&Token::default())).err(), Some(Error::InvalidCredentials));
+        assert_eq!(tenancy.resolve("[::1]:8080", None).err(), Some(Error::UnknownTenant(String::from("[::1]:8080"))));
+    }
+}
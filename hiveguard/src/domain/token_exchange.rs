@@ -0,0 +1,169 @@
+use crate::types::{Error, Id, Token, Audience, TokenExchangeRequest, ACCESS_TOKEN_TYPE};
+use super::{Services, Grant, Tokenizer, TokenPolicies, TokenPolicy};
+use chrono::{DateTime, Utc};
+use crate::config::Config;
+use tracing::instrument;
+use serde_json::Value;
+
+
+/// Token exchange (RFC 8693) for first-party services: an access token of a user is traded for another, of another
+/// audience or of a narrower scope, in the same session.
+///
+/// It lets native apps holding tokens of the password grant get the tokens of other services while they move to the
+/// code flow. A service only exchanges tokens issued to itself or to one of its `exchange_audiences`, and only for
+/// tokens of those. The new token never outlives the subject token nor gets a scope the subject token lacks.
+pub struct TokenExchange {
+    services: Services,
+    policies: TokenPolicies,
+}
+
+
+impl TokenExchange {
+    /// The token to issue to `client_id` for `request`, once signed or encrypted by the caller like any access token.
+    #[instrument(skip(self, tokenizer, request), err)]
+    pub async fn exchange<T: Tokenizer>(&self, tokenizer: &T, client_id: &str, request: &TokenExchangeRequest, now: DateTime<Utc>) -> Result<Token, Error>
+    where
+        Error: From<T::Error>
+    {
+        self.services.check_grant(client_id, Grant::TokenExchange)?;
+        for token_type in [Some(&request.subject_token_type), request.requested_token_type.as_ref()].into_iter().flatten() {
+            if token_type != ACCESS_TOKEN_TYPE {
+                return Err(Error::UnsupportedTokenType(token_type.clone()));
+            }
+        }
+        let audience = self.audience(client_id, request.audience.as_deref())?;
+        let subject = tokenizer.decode_token(&request.subject_token).await?;
+        tokenizer.validate_token(&subject).await?;
+        self.issue(client_id, audience, subject, request.scope.as_deref(), now)
+    }
+
+    /// The audience of the new token: `requested`, when it is the service itself or one of its `exchange_audiences`,
+    /// or else the service.
+    fn audience<'a>(&self, client_id: &'a str, requested: Option<&'a str>) -> Result<&'a str, Error> {
+        let Some(audience) = requested.filter(|audience| *audience != client_id) else {
+            return Ok(client_id);
+        };
+        if !self.services.get(client_id)?.exchange_audiences.iter().any(|allowed| allowed == audience) {
+            return Err(Error::InvalidTarget(String::from(audience)));
+        }
+        self.services.get(audience).map_err(|_| Error::InvalidTarget(String::from(audience)))?;
+        Ok(audience)
+    }
+
+    /// The token of `audience` traded for `subject`, once `subject` is found to be issued to `client_id` or to one of
+    /// its `exchange_audiences`. Its lifetime and scopes follow the token policy of `audience`.
+    fn issue(&self, client_id: &str, audience: &str, subject: Token, scope: Option<&str>, now: DateTime<Utc>) -> Result<Token, Error> {
+        let exchange_audiences = &self.services.get(client_id)?.exchange_audiences;
+        let mut owners = std::iter::once(client_id).chain(exchange_audiences.iter().map(String::as_str));
+        if !owners.any(|owner| subject.audience.contains(owner) || subject.claims.get("client_id").and_then(Value::as_str) == Some(owner)) {
+            return Err(Error::InvalidCredentials);
+        }
+        let policy = self.policies.for_service(audience);
+        let granted = subject.claims.get("scope").and_then(Value::as_str).unwrap_or_default();
+        let scope = Self::scope(policy, granted, scope)?;
+        let mut claims = subject.claims;
+        match scope {
+            Some(scope) => claims.insert(String::from("scope"), Value::String(scope)),
+            None => claims.remove("scope"),
+        };
+        claims.insert(String::from("client_id"), Value::from(client_id));
+        Ok(Token {
+            session_id: subject.session_id,
+            id: Id::new(),
+            issuer: subject.issuer,
+            subject: subject.subject,
+            audience: Audience::One(String::from(audience)),
+            expiration: subject.expiration.min(now + policy.access_token_ttl()),
+            not_before: None,
+            issued_at: now,
+            claims,
+        })
+    }
+
+    /// The scope of the new token: `requested`, if the subject token was `granted` all of it and the audience may be
+    /// issued all of it, or else the scopes of `granted` the audience may be issued.
+    fn scope(policy: &TokenPolicy, granted: &str, requested: Option<&str>) -> Result<Option<String>, Error> {
+        let Some(scope) = policy.scope(requested)? else {
+            let allowed = granted.split_whitespace().filter(|scope| policy.scope(Some(scope)).is_ok()).collect::<Vec<_>>();
+            return policy.scope(Some(&allowed.join(" ")));
+        };
+        match scope.split_whitespace().find(|scope| !granted.split_whitespace().any(|granted| granted == *scope)) {
+            Some(missing) => Err(Error::InvalidScope(String::from(missing))),
+            None => Ok(Some(scope)),
+        }
+    }
+}
+
+
+impl From<&Config> for TokenExchange {
+    fn from(config: &Config) -> Self {
+        Self { services: Services::from(config), policies: TokenPolicies::from(config) }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Tokens;
+    use chrono::TimeDelta;
+
+    fn request(audience: Option<&str>, scope: Option<&str>) -> TokenExchangeRequest {
+        let body = serde_json::json!({"subject_token": Id::new().to_hex(), "subject_token_type": ACCESS_TOKEN_TYPE, "audience": audience, "scope": scope});
+        serde_json::from_value(body).unwrap()
+    }
+
+    fn config() -> Config {
+        Config {
+            services: serde_json::from_value(serde_json::json!([
+                {"client_id": "mobile", "first_party": true, "exchange_audiences": ["reports"], "tokens": {"scopes": ["profile", "reports:read"]}},
+                {"client_id": "reports", "first_party": true, "tokens": {"access_token_ttl": 300, "scopes": ["reports:read"]}},
+                {"client_id": "billing", "first_party": true},
+                {"client_id": "partner"},
+            ])).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    /// A token of the user issued to `client_id`, granted `scope`.
+    fn subject(client_id: &str, scope: &str, now: DateTime<Utc>) -> Token {
+        let claims = serde_json::json!({"client_id": client_id, "scope": scope});
+        Token { subject: Id::new(), expiration: now + TimeDelta::hours(1), claims: claims.as_object().unwrap().clone(), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn test_first_party_services_exchange_tokens_within_their_scope() {
+        let config = config();
+        let (exchange, now) = (TokenExchange::from(&config), Utc::now());
+        assert_eq!(exchange.exchange(&Tokens, "partner", &request(None, None), now).await, Err(Error::ServiceNotTrusted("token exchange")));
+        let token = exchange.issue("mobile", "reports", subject("mobile", "profile reports:read", now), None, now).unwrap();
+        assert_eq!(token.audience, Audience::One(String::from("reports")));
+        assert_eq!((token.claims["scope"].as_str(), token.claims["client_id"].as_str()), (Some("reports:read"), Some("mobile")));
+        // the lifetime is the one of the audience, not of the service asking.
+        assert_eq!(token.expiration, now + TimeDelta::seconds(300));
+        let policies = TokenPolicies::from(&config);
+        let mobile = policies.for_service("mobile");
+        assert_eq!(TokenExchange::scope(mobile, "profile reports:read", Some("reports:read")), Ok(Some(String::from("reports:read"))));
+        assert_eq!(TokenExchange::scope(mobile, "profile reports:read", None), Ok(Some(String::from("profile reports:read"))));
+        assert_eq!(TokenExchange::scope(mobile, "profile email", None), Ok(Some(String::from("profile"))));
+        assert_eq!(TokenExchange::scope(mobile, "email", None), Ok(None));
+        assert_eq!(exchange.issue("mobile", "mobile", subject("mobile", "profile", now), Some("reports:read"), now), Err(Error::InvalidScope(String::from("reports:read"))));
+        assert_eq!(exchange.issue("mobile", "reports", subject("mobile", "profile", now), Some("profile"), now), Err(Error::InvalidScope(String::from("profile"))));
+        let refresh = TokenExchangeRequest { requested_token_type: Some(String::from("urn:ietf:params:oauth:token-type:refresh_token")), ..request(None, None) };
+        assert_eq!(exchange.exchange(&Tokens, "mobile", &refresh, now).await, Err(Error::UnsupportedTokenType(String::from("urn:ietf:params:oauth:token-type:refresh_token"))));
+    }
+
+    #[tokio::test]
+    async fn test_tokens_are_only_exchanged_between_the_services_allowed() {
+        let (exchange, now) = (TokenExchange::from(&config()), Utc::now());
+        assert_eq!(exchange.audience("mobile", None), Ok("mobile"));
+        assert_eq!(exchange.audience("mobile", Some("reports")), Ok("reports"));
+        assert_eq!(exchange.exchange(&Tokens, "mobile", &request(Some("billing"), None), now).await, Err(Error::InvalidTarget(String::from("billing"))));
+        assert_eq!(exchange.exchange(&Tokens, "mobile", &request(Some("https://api.example.com"), None), now).await, Err(Error::InvalidTarget(String::from("https://api.example.com"))));
+        // a token issued to another service, or to no service at all, is not the caller's to exchange.
+        assert_eq!(exchange.issue("mobile", "reports", subject("billing", "", now), None, now), Err(Error::InvalidCredentials));
+        assert_eq!(exchange.exchange(&Tokens, "mobile", &request(Some("reports"), None), now).await, Err(Error::InvalidCredentials));
+        let issued_to_reports = Token { audience: Audience::One(String::from("reports")), claims: Default::default(), ..subject("reports", "", now) };
+        assert!(exchange.issue("mobile", "mobile", issued_to_reports, None, now).is_ok());
+    }
+}
@@ -0,0 +1,239 @@
+use crate::ports::outputs::database::{Database, tables::SessionsTable};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, PoisonError, RwLock};
+use crate::types::{Id, Session, Token, Device, TokenBundle};
+use std::time::{Duration, Instant};
+use sha2::{Digest, Sha256};
+use super::Tokenizer;
+use chrono::Utc;
+
+
+/// Remembers the tokens `inner` recently decoded, so requests presenting the same token skip its cryptography.
+///
+/// Tokens are keyed by their SHA-256 hash, so the raw tokens are never kept in memory.
+/// A cached token is used until it expires or for at most `ttl`, whichever comes first,
+/// and a token is no longer used once its session is revoked, see [`revoke_session`].
+/// `ttl` bounds how long a token revoked by another instance can still be accepted here.
+pub struct Cached<T> {
+    inner: T,
+    ttl: Duration,
+    capacity: usize,
+    state: RwLock<State>,
+}
+
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<[u8; 32], Entry>,
+    /// the hashes of the cached tokens of every session.
+    sessions: HashMap<Id, HashSet<[u8; 32]>>,
+}
+
+
+struct Entry {
+    token: Token,
+    /// when the token was looked up, before it was decoded.
+    cached_at: Instant,
+}
+
+
+/// When the sessions ended in this process were revoked, kept for as long as a token cached before then could still be used.
+static REVOKED: LazyLock<RwLock<HashMap<Id, Instant>>> = LazyLock::new(Default::default);
+
+/// The longest `ttl` of the caches of the process, in milliseconds.
+static LONGEST_TTL: AtomicU64 = AtomicU64::new(0);
+
+
+/// Stops every [`Cached`] tokenizer of the process from using the tokens of `session_id` it already decoded,
+/// including the ones being decoded right now.
+///
+/// Called by `Sessions` for every session it deletes, and by [`Cached`] for the sessions it ends.
+pub fn revoke_session(session_id: Id) {
+    let now = Instant::now();
+    let ttl = Duration::from_millis(LONGEST_TTL.load(Ordering::Relaxed));
+    let mut revoked = REVOKED.write().unwrap_or_else(PoisonError::into_inner);
+    revoked.retain(|_, at| now.duration_since(*at) < ttl);
+    revoked.insert(session_id, now);
+}
+
+
+/// Whether `session_id` was revoked at or after `since`.
+fn revoked_since(session_id: Id, since: Instant) -> bool {
+    let revoked = REVOKED.read().unwrap_or_else(PoisonError::into_inner);
+    revoked.get(&session_id).is_some_and(|at| *at >= since)
+}
+
+
+impl<T> Cached<T> {
+    /// A `capacity` of 0 disables the cache.
+    pub fn new(inner: T, ttl: Duration, capacity: usize) -> Self {
+        LONGEST_TTL.fetch_max(u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX), Ordering::Relaxed);
+        Self { inner, ttl, capacity, state: RwLock::new(State::default()) }
+    }
+
+    fn get(&self, hash: &[u8; 32]) -> Option<Token> {
+        let state = self.state.read().unwrap_or_else(PoisonError::into_inner);
+        let entry = state.entries.get(hash)?;
+        let now = Utc::now();
+        let fresh = entry.cached_at.elapsed() < self.ttl
+            && entry.token.expiration > now
+            && entry.token.not_before.is_none_or(|not_before| not_before <= now)
+            && !revoked_since(entry.token.session_id, entry.cached_at);
+        fresh.then(|| entry.token.clone())
+    }
+
+    /// Caches `token`, looked up at `looked_up_at`, unless its session was revoked since.
+    fn insert(&self, hash: [u8; 32], token: Token, looked_up_at: Instant) {
+        if self.capacity == 0 || revoked_since(token.session_id, looked_up_at) {
+            return;
+        }
+        let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+        if state.entries.len() >= self.capacity {
+            let now = Utc::now();
+            let stale = state.entries.iter()
+                .filter(|(_, entry)| entry.cached_at.elapsed() >= self.ttl || entry.token.expiration <= now)
+                .map(|(hash, _)| *hash)
+                .collect::<Vec<_>>();
+            for hash in stale {
+                state.remove(&hash);
+            }
+        }
+        if state.entries.len() >= self.capacity {
+            let oldest = state.entries.iter().min_by_key(|(_, entry)| entry.cached_at).map(|(hash, _)| *hash);
+            if let Some(oldest) = oldest {
+                state.remove(&oldest);
+            }
+        }
+        state.sessions.entry(token.session_id).or_default().insert(hash);
+        state.entries.insert(hash, Entry { token, cached_at: looked_up_at });
+    }
+
+    /// Forgets every cached token of `session_id`, and revokes it for the other caches of the process.
+    pub fn invalidate_session(&self, session_id: Id) {
+        revoke_session(session_id);
+        let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+        for hash in state.sessions.remove(&session_id).unwrap_or_default() {
+            state.entries.remove(&hash);
+        }
+    }
+}
+
+
+impl State {
+    fn remove(&mut self, hash: &[u8; 32]) {
+        let Some(entry) = self.entries.remove(hash) else {
+            return;
+        };
+        if let Some(hashes) = self.sessions.get_mut(&entry.token.session_id) {
+            hashes.remove(hash);
+            if hashes.is_empty() {
+                self.sessions.remove(&entry.token.session_id);
+            }
+        }
+    }
+}
+
+
+impl<T: Tokenizer> Tokenizer for Cached<T> {
+    type Error = T::Error;
+
+    async fn generate_token<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, db: &DB, subject: Id, device: Device, scope: Option<&str>) -> Result<TokenBundle, Self::Error> where Self::Error: From<DB::Error> {
+        self.inner.generate_token(db, subject, device, scope).await
+    }
+
+    async fn renew_token<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, db: &DB, token: &Token) -> Result<Token, Self::Error> where Self::Error: From<DB::Error> {
+        self.inner.renew_token(db, token).await
+    }
+
+    async fn renew_refresh_token<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, db: &DB, token: &Token) -> Result<Token, Self::Error> where Self::Error: From<DB::Error> {
+        self.inner.renew_refresh_token(db, token).await
+    }
+
+    async fn renew_bundle<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, db: &DB, refresh_token: &Token) -> Result<TokenBundle, Self::Error> where Self::Error: From<DB::Error> {
+        self.inner.renew_bundle(db, refresh_token).await
+    }
+
+    async fn invalidate_token<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, db: &DB, token: &Token) -> Result<(), Self::Error> where Self::Error: From<DB::Error> {
+        self.invalidate_session(token.session_id);
+        self.inner.invalidate_token(db, token).await
+    }
+
+    async fn validate_token(&self, token: &Token) -> Result<(), Self::Error> {
+        self.inner.validate_token(token).await
+    }
+
+    async fn decode_token(&self, token: &str) -> Result<Token, Self::Error> {
+        let hash = Sha256::digest(token.as_bytes()).into();
+        let looked_up_at = Instant::now();
+        if let Some(token) = self.get(&hash) {
+            return Ok(token);
+        }
+        let decoded = self.inner.decode_token(token).await?;
+        // a session revoked while the token was being decoded is not cached, nor used from the cache later.
+        self.insert(hash, decoded.clone(), looked_up_at);
+        Ok(decoded)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Tokens;
+
+    // revocations are process wide, so these are not sessions the other tests end.
+    const TOKEN: &str = "ca0000000000000000000001";
+
+    /// Every decoding by [`Tokens`] gives its token a new id, so a token that was reused keeps the id it had.
+    fn cached(capacity: usize) -> Cached<Tokens> {
+        Cached::new(Tokens, Duration::from_secs(60), capacity)
+    }
+
+    #[tokio::test]
+    async fn test_decoded_tokens_are_reused() {
+        let cached = cached(10);
+        let first = cached.decode_token(TOKEN).await.unwrap();
+        assert_eq!(cached.decode_token(TOKEN).await.unwrap(), first);
+        let second = cached.decode_token("ca0000000000000000000002").await.unwrap();
+        assert_ne!(second.id, first.id);
+        assert_eq!(cached.state.read().unwrap().entries.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_invalidated_sessions_are_forgotten() {
+        let cached = cached(10);
+        let token = cached.decode_token(TOKEN).await.unwrap();
+        cached.invalidate_session(token.session_id);
+        assert_ne!(cached.decode_token(TOKEN).await.unwrap().id, token.id);
+    }
+
+    #[tokio::test]
+    async fn test_revoked_sessions_are_not_reused() {
+        let cached = cached(10);
+        let token = cached.decode_token("ca0000000000000000000003").await.unwrap();
+        revoke_session(token.session_id);
+        assert_ne!(cached.decode_token("ca0000000000000000000003").await.unwrap().id, token.id);
+        assert_eq!(cached.get(&Sha256::digest(b"ca0000000000000000000003").into()).map(|token| token.session_id), Some(token.session_id));
+    }
+
+    #[tokio::test]
+    async fn test_tokens_revoked_while_decoding_are_not_cached() {
+        let cached = cached(10);
+        let looked_up_at = Instant::now();
+        let token = Token { session_id: Id::try_from(String::from("ca0000000000000000000004")).unwrap(), expiration: Utc::now() + chrono::Duration::minutes(5), ..Default::default() };
+        revoke_session(token.session_id);
+        cached.insert([4; 32], token, looked_up_at);
+        assert!(cached.get(&[4; 32]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_capacity_is_respected() {
+        let cached = cached(1);
+        cached.decode_token(TOKEN).await.unwrap();
+        cached.decode_token("ca0000000000000000000002").await.unwrap();
+        let state = cached.state.read().unwrap();
+        assert_eq!(state.entries.len(), 1);
+        assert_eq!(state.sessions.len(), 1);
+    }
+}
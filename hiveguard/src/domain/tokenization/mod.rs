@@ -1,15 +1,40 @@
 use crate::ports::outputs::database::{Database, tables::SessionsTable};
-use crate::types::{Token, TokenBundle, Id, Session};
+use crate::types::{Error, Token, TokenBundle, Id, Session, Device};
+use crate::ports::outputs::signer::Signer;
+use std::fmt::Display;
 
 
-mod paseto;
+pub mod cache;
+pub mod policy;
+pub(crate) mod paseto;
 
 
 pub trait Tokenizer {
     type Error;
-    async fn generate_token<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, db: &DB, subject: Id) -> Result<TokenBundle, Self::Error> where Self::Error: From<DB::Error>;
+    /// Starts a session of `subject` from `device` and issues its first tokens.
+    ///
+    /// A `scope` limits the session: it is held in the `scope` claim of its tokens and of the tokens renewed from them,
+    /// and returned in the bundle.
+    async fn generate_token<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, db: &DB, subject: Id, device: Device, scope: Option<&str>) -> Result<TokenBundle, Self::Error> where Self::Error: From<DB::Error>;
     async fn renew_token<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, db: &DB, token: &Token) -> Result<Token, Self::Error> where Self::Error: From<DB::Error>;
     async fn renew_refresh_token<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, db: &DB, token: &Token) -> Result<Token, Self::Error> where Self::Error: From<DB::Error>;
+    /// Rotates the refresh token of the session of `refresh_token` and issues a new bundle of tokens for it.
+    async fn renew_bundle<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, db: &DB, refresh_token: &Token) -> Result<TokenBundle, Self::Error> where Self::Error: From<DB::Error>;
     async fn invalidate_token<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, db: &DB, token: &Token) -> Result<(), Self::Error> where Self::Error: From<DB::Error>;
     async fn validate_token(&self, token: &Token) -> Result<(), Self::Error>;
-}
\ No newline at end of file
+    /// Decrypts or verifies the signature of `token`, as presented by a client, and checks its claims.
+    async fn decode_token(&self, token: &str) -> Result<Token, Self::Error>;
+}
+
+
+/// Signs `token` under `v4.public` with `signer`, the backend `tokens.signer` selects.
+pub async fn sign_token<S: Signer>(signer: &S, token: &Token) -> Result<String, Error>
+where
+    S::Error: Display
+{
+    let payload = serde_json::to_string(token).map_err(|_| Error::ProviderUnavailable("the token signer"))?;
+    paseto::sign_with(&payload, signer).await.map_err(|err| {
+        tracing::error!(error = %err, "could not sign the token");
+        Error::ProviderUnavailable("the token signer")
+    })
+}
@@ -0,0 +1,64 @@
+use rusty_paseto::core::{Key, Paseto, PasetoAsymmetricPrivateKey, Payload, Public, V4};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use crate::ports::outputs::signer::Signer;
+use base64::Engine;
+
+
+/// the length of the hex encoded key pair of `tokens.signing_key`.
+const SIGNING_KEY_LENGTH: usize = 128;
+/// the header of the tokens signed under `v4.public`.
+const PUBLIC_HEADER: &str = "v4.public.";
+
+
+/// The key pair of `tokens.signing_key`, if it is a hex encoded 64 byte key pair.
+pub(crate) fn signing_key(key: &str) -> Option<Key<64>> {
+    // `Key` panics on keys of another length.
+    Some(key).filter(|key| key.len() == SIGNING_KEY_LENGTH).and_then(|key| Key::<64>::try_from(key).ok())
+}
+
+
+/// Signs `payload` under `v4.public`.
+pub(crate) fn sign(payload: &str, key: &Key<64>) -> Option<String> {
+    Paseto::<V4, Public>::builder().set_payload(Payload::from(payload)).try_sign(&PasetoAsymmetricPrivateKey::from(key)).ok()
+}
+
+
+/// Signs `payload` under `v4.public` with `signer`, whose key may never leave it. eg a key of AWS KMS or of an HSM.
+///
+/// The token has neither footer nor implicit assertion, like those of [`sign`].
+pub(crate) async fn sign_with<S: Signer>(payload: &str, signer: &S) -> Result<String, S::Error> {
+    let signature = signer.sign(&pae(&[PUBLIC_HEADER.as_bytes(), payload.as_bytes(), b"", b""])).await?;
+    let mut body = payload.as_bytes().to_vec();
+    body.extend_from_slice(&signature);
+    Ok(format!("{}{}", PUBLIC_HEADER, URL_SAFE_NO_PAD.encode(body)))
+}
+
+
+/// The pre-authentication encoding of `pieces`, what `v4.public` signs.
+fn pae(pieces: &[&[u8]]) -> Vec<u8> {
+    let mut encoded = (pieces.len() as u64).to_le_bytes().to_vec();
+    for piece in pieces {
+        // the most significant bit is cleared, as the specification requires.
+        encoded.extend_from_slice(&(piece.len() as u64 & (u64::MAX >> 1)).to_le_bytes());
+        encoded.extend_from_slice(piece);
+    }
+    encoded
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptors::outputs::signers::KeySigner;
+
+    const SIGNING_KEY: &str = "b4cbfb43df4ce210727d953e4a713307fa19bb7d9f85041438d9e11b942a37741eb9dbbbbc047c03fd70604e0071f0987e16b28b757225c11f00415d0e20b1a2";
+
+    #[tokio::test]
+    async fn test_signers_sign_like_the_key_they_hold() {
+        let signer = KeySigner::new(SIGNING_KEY).unwrap();
+        let payload = r#"{"sub":"000000000000000000000001"}"#;
+        // Ed25519 signatures are deterministic, so both tokens are the same.
+        assert_eq!(sign_with(payload, &signer).await.ok(), sign(payload, &signing_key(SIGNING_KEY).unwrap()));
+        assert!(KeySigner::new(&SIGNING_KEY.replace("1eb9", "1eba")).is_none());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::config::{Config, TokenAlgorithm, TokensConfig, ServiceConfig};
+use std::collections::{HashMap, HashSet};
+use crate::types::Error;
+use chrono::TimeDelta;
+
+
+/// How the tokens issued to a service are made: their lifetimes, the scopes it may request and their algorithm.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenPolicy {
+    access_token_ttl: TimeDelta,
+    refresh_token_ttl: TimeDelta,
+    algorithm: TokenAlgorithm,
+    /// any scope when `None`.
+    scopes: Option<HashSet<String>>,
+}
+
+
+/// The token policy of every service, falling back to `tokens` for what a service does not override.
+#[derive(Debug, Clone)]
+pub struct TokenPolicies {
+    default: TokenPolicy,
+    services: HashMap<String, TokenPolicy>,
+}
+
+
+impl TokenPolicy {
+    pub fn access_token_ttl(&self) -> TimeDelta {
+        self.access_token_ttl
+    }
+
+    pub fn refresh_token_ttl(&self) -> TimeDelta {
+        self.refresh_token_ttl
+    }
+
+    pub fn algorithm(&self) -> TokenAlgorithm {
+        self.algorithm
+    }
+
+    /// The scope of the tokens issued for a request of `requested`, space separated as in OAuth requests.
+    ///
+    /// Fails with `Error::InvalidScope` on the first scope the service may not request.
+    pub fn scope(&self, requested: Option<&str>) -> Result<Option<String>, Error> {
+        let Some(requested) = requested.map(str::trim).filter(|requested| !requested.is_empty()) else {
+            return Ok(None);
+        };
+        let mut scopes = Vec::new();
+        for scope in requested.split_whitespace() {
+            if self.scopes.as_ref().is_some_and(|allowed| !allowed.contains(scope)) {
+                return Err(Error::InvalidScope(String::from(scope)));
+            }
+            if !scopes.contains(&scope) {
+                scopes.push(scope);
+            }
+        }
+        Ok(Some(scopes.join(" ")))
+    }
+
+    fn new(tokens: &TokensConfig, service: Option<&ServiceConfig>) -> Self {
+        let overrides = service.map(|service| &service.tokens);
+        Self {
+            access_token_ttl: TimeDelta::seconds(overrides.and_then(|overrides| overrides.access_token_ttl).unwrap_or(tokens.access_token_ttl)),
+            refresh_token_ttl: TimeDelta::seconds(overrides.and_then(|overrides| overrides.refresh_token_ttl).unwrap_or(tokens.refresh_token_ttl)),
+            algorithm: overrides.and_then(|overrides| overrides.algorithm).unwrap_or(tokens.algorithm),
+            scopes: overrides.and_then(|overrides| overrides.scopes.as_ref()).map(|scopes| scopes.iter().cloned().collect()),
+        }
+    }
+}
+
+
+impl TokenPolicies {
+    /// The policy of the tokens issued to `client_id`. Unknown services get the one of `tokens`,
+    /// as refusing them is up to the endpoint authenticating the service.
+    pub fn for_service(&self, client_id: &str) -> &TokenPolicy {
+        self.services.get(client_id).unwrap_or(&self.default)
+    }
+
+    /// The policy of the tokens issued to users directly, rather than to a service.
+    pub fn default_policy(&self) -> &TokenPolicy {
+        &self.default
+    }
+}
+
+
+impl From<&Config> for TokenPolicies {
+    fn from(config: &Config) -> Self {
+        Self {
+            default: TokenPolicy::new(&config.tokens, None),
+            services: config.services.iter().map(|service| (service.client_id.clone(), TokenPolicy::new(&config.tokens, Some(service)))).collect(),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_services_override_the_token_settings() {
+        let config = Config {
+            services: serde_json::from_value(serde_json::json!([
+                {"client_id": "mobile", "tokens": {"refresh_token_ttl": 7776000, "algorithm": "v4.public"}},
+                {"client_id": "reports", "tokens": {"access_token_ttl": 60, "scopes": ["reports:read", "profile"]}},
+            ])).unwrap(),
+            ..Default::default()
+        };
+        let policies = TokenPolicies::from(&config);
+        let mobile = policies.for_service("mobile");
+        assert_eq!((mobile.access_token_ttl(), mobile.refresh_token_ttl()), (TimeDelta::minutes(15), TimeDelta::days(90)));
+        assert_eq!(mobile.algorithm(), TokenAlgorithm::Public);
+        assert_eq!(mobile.scope(Some("anything at all")), Ok(Some(String::from("anything at all"))));
+        let reports = policies.for_service("reports");
+        assert_eq!((reports.access_token_ttl(), reports.algorithm()), (TimeDelta::seconds(60), TokenAlgorithm::Local));
+        assert_eq!(reports.scope(Some(" profile reports:read profile ")), Ok(Some(String::from("profile reports:read"))));
+        assert_eq!(reports.scope(Some("profile reports:write")), Err(Error::InvalidScope(String::from("reports:write"))));
+        assert_eq!(reports.scope(None), Ok(None));
+        assert_eq!(policies.for_service("unknown"), policies.default_policy());
+    }
+}
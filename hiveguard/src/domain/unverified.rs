@@ -0,0 +1,150 @@
+use crate::ports::outputs::database::{Database, tables::{UsersTable, SessionsTable}};
+use crate::types::{Error, User, Session, Login, DatabaseError};
+use crate::config::{UnverifiedConfig, UnverifiedAccess};
+use chrono::{DateTime, TimeDelta, Utc};
+use super::sessions::Sessions;
+use tracing::instrument;
+
+
+/// the scope of the tokens issued to unverified accounts under [`UnverifiedAccess::Limited`].
+pub const UNVERIFIED_SCOPE: &str = "unverified";
+
+
+/// The policy of the accounts that did not verify a contact yet. see [`UnverifiedConfig`].
+///
+/// Only users logging in with a password are held to it. An account verifying a contact through [`Contacts`](super::Contacts)
+/// is no longer unverified, and its next tokens are no longer limited.
+#[derive(Debug, Clone)]
+pub struct UnverifiedAccounts {
+    access: UnverifiedAccess,
+    expire_after: Option<TimeDelta>,
+    page_size: usize,
+}
+
+
+impl UnverifiedAccounts {
+    /// Whether `user` logs in with a password without having verified any of their contacts.
+    pub fn is_unverified(user: &User) -> bool {
+        if !matches!(user.login, Login::Password(_)) {
+            return false;
+        }
+        #[cfg(feature = "email")]
+        if user.email.as_ref().is_some_and(|email| email.is_verified()) {
+            return false;
+        }
+        #[cfg(feature = "phone")]
+        if user.phone.as_ref().is_some_and(|phone| phone.is_verified()) {
+            return false;
+        }
+        // without contacts, there is nothing to verify.
+        cfg!(any(feature = "email", feature = "phone"))
+    }
+
+    /// Whether `user` stayed unverified for longer than `unverified.expire_after_days`.
+    pub fn is_expired(&self, user: &User, now: DateTime<Utc>) -> bool {
+        self.expire_after.is_some_and(|ttl| user.created_at + ttl <= now) && Self::is_unverified(user)
+    }
+
+    /// The scope `user` logs in with, if it is limited to one.
+    ///
+    /// An unverified account is refused with `Error::VerificationRequired` under [`UnverifiedAccess::Deny`],
+    /// and an expired one reads as an unknown user, as it is only waiting to be purged.
+    pub fn check(&self, user: &User, now: DateTime<Utc>) -> Result<Option<&'static str>, Error> {
+        if !Self::is_unverified(user) {
+            return Ok(None);
+        }
+        if self.is_expired(user, now) {
+            return Err(DatabaseError::UserNotFound.into());
+        }
+        match self.access {
+            UnverifiedAccess::Allow => Ok(None),
+            UnverifiedAccess::Limited => Ok(Some(UNVERIFIED_SCOPE)),
+            UnverifiedAccess::Deny => Err(Error::VerificationRequired),
+        }
+    }
+
+    /// Deletes every account that expired unverified, with its sessions. Returns the number of accounts deleted.
+    #[instrument(skip_all, err)]
+    pub async fn purge_expired<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>, SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, db: &DB) -> Result<usize, Error>
+    where
+        Error: From<DB::Error>
+    {
+        if self.expire_after.is_none() {
+            return Ok(0);
+        }
+        let now = Utc::now();
+        let mut after = None;
+        let mut purged = 0;
+        loop {
+            let page = db.list_users(after, self.page_size.max(1)).await?;
+            for user in page.items.iter().filter(|user| self.is_expired(user, now)) {
+                Sessions::delete_all(db, user.id, None).await?;
+                db.delete_user(user.id).await?;
+                purged += 1;
+            }
+            match page.next {
+                Some(next) => after = Some(next),
+                None => break Ok(purged),
+            }
+        }
+    }
+}
+
+
+impl Default for UnverifiedAccounts {
+    fn default() -> Self {
+        Self::from(&UnverifiedConfig::default())
+    }
+}
+
+
+impl From<&UnverifiedConfig> for UnverifiedAccounts {
+    fn from(config: &UnverifiedConfig) -> Self {
+        Self {
+            access: config.access,
+            expire_after: config.expire_after_days.map(|days| TimeDelta::days(days.into())),
+            page_size: config.page_size,
+        }
+    }
+}
+
+
+#[cfg(all(test, feature = "email"))]
+mod tests {
+    use super::*;
+    use crate::testing::{Harness, UserFixture};
+
+    fn unverified(config: serde_json::Value) -> UnverifiedAccounts {
+        UnverifiedAccounts::from(&serde_json::from_value::<UnverifiedConfig>(config).unwrap())
+    }
+
+    #[test]
+    fn test_unverified_accounts_are_held_to_the_policy() {
+        let now = Utc::now();
+        let user = UserFixture::new().email("jane@acme.com").password("secret").build();
+        assert_eq!(unverified(serde_json::json!({})).check(&user, now), Ok(None));
+        assert_eq!(unverified(serde_json::json!({"access": "limited"})).check(&user, now), Ok(Some(UNVERIFIED_SCOPE)));
+        assert_eq!(unverified(serde_json::json!({"access": "deny"})).check(&user, now), Err(Error::VerificationRequired));
+        let verified = UserFixture::new().email("jane@acme.com").password("secret").verified().build();
+        assert_eq!(unverified(serde_json::json!({"access": "deny"})).check(&verified, now), Ok(None));
+        let expiring = unverified(serde_json::json!({"expire_after_days": 7}));
+        assert_eq!(expiring.check(&user, now + TimeDelta::days(8)), Err(DatabaseError::UserNotFound.into()));
+        assert_eq!(expiring.check(&verified, now + TimeDelta::days(8)), Ok(None));
+    }
+
+    #[tokio::test]
+    async fn test_expired_unverified_accounts_are_purged() {
+        let harness = Harness::new();
+        let aged = |fixture: UserFixture| User { created_at: Utc::now() - TimeDelta::days(8), ..fixture.password("secret").build() };
+        let expired = aged(UserFixture::new().email("old@acme.com"));
+        let verified = aged(UserFixture::new().email("jane@acme.com").verified());
+        harness.db.create_user(expired.clone()).await.unwrap();
+        harness.db.create_user(verified.clone()).await.unwrap();
+        let recent = harness.create(UserFixture::new().email("new@acme.com").password("secret")).await;
+        assert_eq!(unverified(serde_json::json!({})).purge_expired(&harness.db).await, Ok(0));
+        assert_eq!(unverified(serde_json::json!({"expire_after_days": 7})).purge_expired(&harness.db).await, Ok(1));
+        assert_eq!(harness.db.get_user_by_id(expired.id).await.unwrap(), None);
+        assert!(harness.db.get_user_by_id(verified.id).await.unwrap().is_some());
+        assert!(harness.db.get_user_by_id(recent.id).await.unwrap().is_some());
+    }
+}
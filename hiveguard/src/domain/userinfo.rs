@@ -0,0 +1,87 @@
+use crate::ports::outputs::database::{Database, tables::UsersTable};
+use crate::types::{Error, User, Token, DatabaseError};
+use serde_json::{Map, Value};
+use tracing::instrument;
+
+
+/// the scope of the tokens of OpenID Connect requests, without which there is no userinfo.
+pub const OPENID_SCOPE: &str = "openid";
+
+
+/// The standard OpenID Connect claims of users, as returned by the userinfo endpoint.
+///
+/// Each claim is returned for the scope that requests it: `profile`, `email` or `phone`. `sub` is always returned.
+pub struct UserInfo;
+
+
+impl UserInfo {
+    /// The claims of the subject of `token`, an access token of the `openid` scope.
+    #[instrument(skip_all, fields(user_id = %token.subject.to_hex()), err)]
+    pub async fn get<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(db: &DB, token: &Token) -> Result<Map<String, Value>, Error>
+    where
+        Error: From<DB::Error>
+    {
+        let scope = token.claims.get("scope").and_then(Value::as_str).unwrap_or_default();
+        if !scope.split_whitespace().any(|scope| scope == OPENID_SCOPE) {
+            return Err(Error::InsufficientScope(String::from(OPENID_SCOPE)));
+        }
+        let user = db.get_user_by_id(token.subject).await?.ok_or(DatabaseError::UserNotFound)?;
+        if !user.status.can_login() {
+            return Err(Error::AccountDisabled(user.status));
+        }
+        Ok(Self::claims(&user, scope))
+    }
+
+    /// The claims of `user` the space separated `scope` grants.
+    pub fn claims(user: &User, scope: &str) -> Map<String, Value> {
+        let mut claims = Map::new();
+        claims.insert(String::from("sub"), Value::String(user.id.to_hex()));
+        for scope in scope.split_whitespace() {
+            match scope {
+                "profile" => {
+                    if !user.fullname.is_empty() {
+                        claims.insert(String::from("name"), Value::String(user.fullname.clone()));
+                    }
+                    claims.insert(String::from("preferred_username"), Value::String(user.username.clone()));
+                    if let Some(avatar) = &user.avatar {
+                        claims.insert(String::from("picture"), Value::String(avatar.to_string()));
+                    }
+                    claims.insert(String::from("updated_at"), Value::from(user.updated_at.timestamp()));
+                },
+                #[cfg(feature = "email")]
+                "email" => if let Some(email) = &user.email {
+                    claims.insert(String::from("email"), Value::String(email.to_string()));
+                    claims.insert(String::from("email_verified"), Value::Bool(email.is_verified()));
+                },
+                #[cfg(feature = "phone")]
+                "phone" => if let Some(phone) = &user.phone {
+                    claims.insert(String::from("phone_number"), Value::String(phone.to_string()));
+                    claims.insert(String::from("phone_number_verified"), Value::Bool(phone.is_verified()));
+                },
+                _ => {},
+            }
+        }
+        claims
+    }
+}
+
+
+#[cfg(all(test, feature = "email"))]
+mod tests {
+    use super::*;
+    use crate::testing::{Harness, UserFixture};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_claims_follow_the_scopes_of_the_token() {
+        let harness = Harness::new();
+        let user = harness.create(UserFixture::new().email("jane@acme.com").password("secret").verified()).await;
+        let token = |scope: &str| Token { subject: user.id, claims: Map::from_iter([(String::from("scope"), Value::from(scope))]), ..Default::default() };
+        let claims = UserInfo::get(&harness.db, &token("openid email")).await.unwrap();
+        assert_eq!(Value::Object(claims), json!({"sub": user.id.to_hex(), "email": "jane@acme.com", "email_verified": true}));
+        let claims = UserInfo::get(&harness.db, &token("openid profile")).await.unwrap();
+        assert_eq!(claims.get("preferred_username"), Some(&Value::String(user.username.clone())));
+        assert!(!claims.contains_key("email"));
+        assert_eq!(UserInfo::get(&harness.db, &token("email profile")).await, Err(Error::InsufficientScope(String::from(OPENID_SCOPE))));
+    }
+}
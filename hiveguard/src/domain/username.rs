@@ -0,0 +1,155 @@
+use unicode_security::{skeleton, GeneralSecurityProfile, MixedScript};
+use crate::types::{UsernameError, User, Id};
+use crate::config::UsernamesConfig;
+use chrono::{DateTime, Duration, Utc};
+
+
+/// The rules a username must follow to sign up. see [`UsernamesConfig`].
+#[derive(Debug, Clone)]
+pub struct UsernameRules {
+    min_length: usize,
+    max_length: usize,
+    symbols: Vec<char>,
+    allow_unicode: bool,
+    /// the lowercased reserved names with their confusable skeleton.
+    reserved: Vec<(String, String)>,
+    change_cooldown: Duration,
+    hold: Duration,
+    resolve_previous: bool,
+}
+
+
+impl UsernameRules {
+    /// Checks `username` against the rules, returning the first one it breaks.
+    ///
+    /// A username is rejected when it is a reserved name or when its confusable skeleton, as defined by
+    /// [UTS #39](https://www.unicode.org/reports/tr39/#Confusable_Detection), is the skeleton of a reserved name.
+    pub fn check(&self, username: &str) -> Result<(), UsernameError> {
+        let length = username.chars().count();
+        if length < self.min_length {
+            return Err(UsernameError::TooShort(self.min_length));
+        }
+        if length > self.max_length {
+            return Err(UsernameError::TooLong(self.max_length));
+        }
+        if let Some(c) = username.chars().find(|c| !self.allows(*c)) {
+            return Err(UsernameError::InvalidCharacter(c));
+        }
+        if !username.is_ascii() && !username.is_single_script() {
+            return Err(UsernameError::MixedScripts);
+        }
+        let lowercase = username.to_lowercase();
+        let username_skeleton = skeleton(&lowercase).collect::<String>();
+        for (name, name_skeleton) in &self.reserved {
+            if lowercase == *name {
+                return Err(UsernameError::Reserved(name.clone()));
+            }
+            if username_skeleton == *name_skeleton {
+                return Err(UsernameError::Confusable(name.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// When `user` can change their username again, `None` when they can right away.
+    pub fn next_change(&self, user: &User, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        user.username_changed_at.map(|changed_at| changed_at + self.change_cooldown).filter(|next| *next > now)
+    }
+
+    /// Whether `user` still holds `username`, the username they had before their last change.
+    pub fn holds(&self, user: &User, username: &str, now: DateTime<Utc>) -> bool {
+        user.previous_username.as_deref() == Some(username) && user.username_changed_at.is_some_and(|changed_at| changed_at + self.hold > now)
+    }
+
+    /// Whether held usernames find the user holding them.
+    pub fn resolves_previous(&self) -> bool {
+        self.resolve_previous
+    }
+
+    /// The username given to the user `id` when they sign up without one, until they choose theirs.
+    pub fn placeholder(id: Id) -> String {
+        format!("user-{}", id.to_hex())
+    }
+
+    /// Whether `user` still goes by the username they were given.
+    pub fn has_placeholder(user: &User) -> bool {
+        user.username == Self::placeholder(user.id)
+    }
+
+    fn allows(&self, c: char) -> bool {
+        c.is_ascii_alphanumeric()
+            || self.symbols.contains(&c)
+            || (self.allow_unicode && c.is_alphanumeric() && c.identifier_allowed())
+    }
+}
+
+
+impl From<&UsernamesConfig> for UsernameRules {
+    fn from(config: &UsernamesConfig) -> Self {
+        let reserved = config.reserved.iter().map(|name| {
+            let name = name.to_lowercase();
+            let name_skeleton = skeleton(&name).collect();
+            (name, name_skeleton)
+        }).collect();
+        Self {
+            min_length: config.min_length,
+            max_length: config.max_length,
+            symbols: config.symbols.chars().collect(),
+            allow_unicode: config.allow_unicode,
+            reserved,
+            change_cooldown: Duration::days(config.change_cooldown_days.into()),
+            hold: Duration::days(config.hold_days.into()),
+            resolve_previous: config.resolve_previous,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(allow_unicode: bool) -> UsernameRules {
+        UsernameRules::from(&UsernamesConfig { allow_unicode, ..Default::default() })
+    }
+
+    #[test]
+    fn test_length() {
+        assert_eq!(rules(false).check("ab"), Err(UsernameError::TooShort(3)));
+        assert_eq!(rules(false).check(&"a".repeat(33)), Err(UsernameError::TooLong(32)));
+        assert_eq!(rules(false).check("abc"), Ok(()));
+        // the length is counted in characters, not bytes.
+        assert_eq!(rules(true).check("jörg"), Ok(()));
+    }
+
+    #[test]
+    fn test_characters() {
+        assert_eq!(rules(false).check("jane.doe_1-2"), Ok(()));
+        assert_eq!(rules(false).check("jane doe"), Err(UsernameError::InvalidCharacter(' ')));
+        assert_eq!(rules(false).check("jane@doe"), Err(UsernameError::InvalidCharacter('@')));
+        assert_eq!(rules(false).check("jörg"), Err(UsernameError::InvalidCharacter('ö')));
+        assert_eq!(rules(true).check("jane\u{200b}doe"), Err(UsernameError::InvalidCharacter('\u{200b}')));
+    }
+
+    #[test]
+    fn test_reserved_names() {
+        assert_eq!(rules(false).check("Admin"), Err(UsernameError::Reserved("admin".into())));
+        assert_eq!(rules(false).check("api"), Err(UsernameError::Reserved("api".into())));
+        assert_eq!(rules(false).check("admins"), Ok(()));
+    }
+
+    #[test]
+    fn test_confusables() {
+        // written in cyrillic.
+        assert_eq!(rules(true).check("арі"), Err(UsernameError::Confusable("api".into())));
+        assert_eq!(rules(false).check("systern"), Err(UsernameError::Confusable("system".into())));
+        assert_eq!(rules(false).check("r00t"), Ok(()));
+    }
+
+    #[test]
+    fn test_mixed_scripts() {
+        // a latin `j` followed by cyrillic letters.
+        assert_eq!(rules(true).check("jаnе"), Err(UsernameError::MixedScripts));
+        assert_eq!(rules(true).check("иван"), Ok(()));
+    }
+}
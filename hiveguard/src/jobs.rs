@@ -0,0 +1,160 @@
+use hiveguard::adaptors::outputs::error_reporting::ErrorReporting;
+use hiveguard::ports::outputs::error_reporter::ErrorReporter;
+use hiveguard::types::{Error, RequestContext};
+use hiveguard::config::{JobConfig, SharedConfig};
+use hiveguard::scheduler::Scheduler;
+use std::future::Future;
+
+
+/// Runs the maintenance jobs enabled in `config.jobs` on their schedules, against the tables of every tenant. It never returns
+/// unless a job can't be set up.
+///
+/// The settings the jobs read, eg `mail_queue.batch_size`, are taken from `config` on every run so that they follow reloads.
+/// Internal errors failing a run are reported to `reporter`.
+pub async fn run(config: &SharedConfig, reporter: &ErrorReporting) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "dynamodb")]
+    {
+        use hiveguard::adaptors::outputs::{events::EventSink, http, retry::Retry, security_events::SecurityEventSink};
+        use hiveguard::domain::{Maintenance, Events, Erasure, Guests, UnverifiedAccounts, OrgAudit, SessionPolicy};
+        #[cfg(feature = "email")]
+        use hiveguard::domain::Mails;
+        let snapshot = config.load();
+        let tenants = dynamodb::tenants(&snapshot).await?;
+        let tenants = tenants.as_slice();
+        #[cfg(feature = "email")]
+        let mailer = hiveguard::adaptors::outputs::mail::smtp::Smtp::new(&snapshot.smtp).map_err(|err| err.to_string())?;
+        let events = SecurityEventSink::new(&snapshot.security_events, http::client(&snapshot.http)?, Retry::new(&snapshot.retry));
+        let publisher = EventSink::new(&snapshot.events).await.map_err(|err| err.to_string())?;
+        let jobs = &snapshot.jobs;
+        let mut scheduler = Scheduler::new();
+        add(&mut scheduler, reporter, "purge_verifications", &jobs.purge_verifications, || each(tenants, |tenant| Maintenance::purge_verifications(&tenant.db)))?;
+        add(&mut scheduler, reporter, "purge_grants", &jobs.purge_grants, || each(tenants, |tenant| Maintenance::purge_grants(&tenant.db)))?;
+        add(&mut scheduler, reporter, "purge_sessions", &jobs.purge_sessions, || async {
+            let policy = SessionPolicy::from(&config.load().sessions);
+            each(tenants, |tenant| Maintenance::purge_sessions(&tenant.db, &policy)).await
+        })?;
+        add(&mut scheduler, reporter, "reencrypt_pii", &jobs.reencrypt_pii, || each(tenants, |tenant| Maintenance::reencrypt_pii(&tenant.db)))?;
+        #[cfg(feature = "email")]
+        add(&mut scheduler, reporter, "deliver_mail", &jobs.deliver_mail, || async {
+            let settings = config.load().mail_queue.clone();
+            each(tenants, |tenant| Mails::deliver(&tenant.mail, &mailer, &settings)).await
+        })?;
+        add(&mut scheduler, reporter, "erase_accounts", &jobs.erase_accounts, || async {
+            let settings = config.load().erasure.clone();
+            each(tenants, |tenant| Erasure::erase_due(&tenant.db, &events, &settings)).await
+        })?;
+        add(&mut scheduler, reporter, "purge_guests", &jobs.purge_guests, || async {
+            let guests = Guests::from(&config.load().guests);
+            each(tenants, |tenant| guests.purge_expired(&tenant.db)).await
+        })?;
+        add(&mut scheduler, reporter, "purge_unverified", &jobs.purge_unverified, || async {
+            let unverified = UnverifiedAccounts::from(&config.load().unverified);
+            each(tenants, |tenant| unverified.purge_expired(&tenant.db)).await
+        })?;
+        // without the outbox, events are published right away and there is nothing to dispatch.
+        if snapshot.outbox.enabled {
+            add(&mut scheduler, reporter, "dispatch_events", &jobs.dispatch_events, || async {
+                let settings = config.load().outbox.clone();
+                each(tenants, |tenant| Events::dispatch(&tenant.outbox, &publisher, &settings)).await
+            })?;
+        }
+        add(&mut scheduler, reporter, "prune_audit_log", &jobs.prune_audit_log, || async {
+            let audit = OrgAudit::from(&config.load().audit);
+            each(tenants, |tenant| Maintenance::prune_audit_log(&tenant.organisations, &tenant.audit_log, &audit)).await
+        })?;
+        scheduler.run().await;
+        Ok(())
+    }
+    #[cfg(not(feature = "dynamodb"))]
+    {
+        let _ = (config, reporter);
+        tracing::warn!("the maintenance jobs need a database, build hiveguard with the dynamodb feature to run them");
+        std::future::pending().await
+    }
+}
+
+
+/// Adds the job `name` to `scheduler` when it is enabled, reporting the errors failing its runs to `reporter`.
+#[cfg_attr(not(feature = "dynamodb"), allow(dead_code))]
+fn add<'a, F, Fut>(scheduler: &mut Scheduler<'a>, reporter: &'a ErrorReporting, name: &'static str, job: &JobConfig, task: F) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: Fn() -> Fut + Send + Sync + 'a,
+    Fut: Future<Output = Result<usize, Error>> + Send + 'a,
+{
+    if job.enabled {
+        scheduler.add(name, job.schedule.parse()?, move || {
+            let run = task();
+            async move {
+                let result = run.await;
+                if let Err(err) = &result {
+                    reporter.capture(err, RequestContext { job: Some(String::from(name)), ..Default::default() }).await;
+                }
+                result
+            }
+        });
+    }
+    Ok(())
+}
+
+
+/// Runs `task` for every tenant, one after the other, and adds up the items they processed.
+/// A failure ends the run. the tenants after it are processed on the next one.
+#[cfg_attr(not(feature = "dynamodb"), allow(dead_code))]
+async fn each<'a, T, F, Fut>(tenants: &'a [T], task: F) -> Result<usize, Error>
+where
+    F: Fn(&'a T) -> Fut,
+    Fut: Future<Output = Result<usize, Error>>,
+{
+    let mut processed = 0;
+    for tenant in tenants {
+        processed += task(tenant).await?;
+    }
+    Ok(processed)
+}
+
+
+#[cfg(feature = "dynamodb")]
+mod dynamodb {
+    use hiveguard::adaptors::outputs::databases::dynamodb::DynamoDB;
+    use hiveguard::adaptors::outputs::{mail::dynamodb::DynamoDBQueue, outbox::dynamodb::DynamoDBOutbox};
+    use hiveguard::adaptors::outputs::{organisations::dynamodb::DynamoDBOrganisations, audit_log::dynamodb::DynamoDBAuditLog};
+    use hiveguard::types::TenantId;
+    use hiveguard::config::Config;
+    use std::time::Duration;
+
+    /// The tables of a tenant, or of the deployment when it has no tenants.
+    pub struct Tenant {
+        pub db: DynamoDB,
+        #[cfg_attr(not(feature = "email"), allow(dead_code))]
+        pub mail: DynamoDBQueue,
+        pub outbox: DynamoDBOutbox,
+        pub organisations: DynamoDBOrganisations,
+        pub audit_log: DynamoDBAuditLog,
+    }
+
+    pub async fn tenants(config: &Config) -> Result<Vec<Tenant>, Box<dyn std::error::Error>> {
+        let mut tables = Vec::new();
+        for tenant in &config.tenancy.tenants {
+            tables.push(config.dynamodb.for_tenant(&TenantId::try_from(tenant.id.as_str())?));
+        }
+        if tables.is_empty() {
+            tables.push(config.dynamodb.clone());
+        }
+        let client = DynamoDB::client(&config.dynamodb).await;
+        let replica = DynamoDB::replica(&config.dynamodb).await;
+        let threshold = Duration::from_millis(config.database.slow_query_threshold_ms);
+        Ok(tables.into_iter().map(|tables| {
+            let db = DynamoDB::new(client.clone(), &tables, threshold);
+            Tenant {
+                db: match &replica {
+                    Some(replica) => db.with_replica(replica.clone()),
+                    None => db,
+                },
+                mail: DynamoDBQueue::new(client.clone(), tables.mail_table),
+                outbox: DynamoDBOutbox::new(client.clone(), tables.outbox_table),
+                organisations: DynamoDBOrganisations::new(client.clone(), tables.organisations_table, tables.memberships_table),
+                audit_log: DynamoDBAuditLog::new(client.clone(), tables.audit_table),
+            }
+        }).collect())
+    }
+}
@@ -0,0 +1,20 @@
+//! The authentication engine of hiveguard, for embedding it in other Rust applications.
+//!
+//! The [`domain`] services are generic over the [`ports`], so an application brings its own HTTP framework
+//! and, if the adaptors of [`adaptors`] do not fit, its own [`ports::outputs::database::Database`].
+//! The `hiveguard` binary is a thin layer over this crate.
+
+// the ports are implemented within the workspace, where the futures are known to be `Send` where they need to be.
+#![allow(async_fn_in_trait)]
+
+pub mod adaptors;
+pub mod config;
+pub mod domain;
+pub mod ports;
+pub mod logging;
+pub mod scheduler;
+#[cfg(feature = "resource-server")]
+pub mod resource;
+pub mod types;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
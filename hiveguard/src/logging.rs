@@ -0,0 +1,110 @@
+use tracing_subscriber::{fmt::format::FmtSpan, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use crate::config::{Config, LogLevel};
+use std::error::Error as StdError;
+
+
+/// Keeps the telemetry pipelines alive. Buffered spans and metrics are flushed when it is dropped.
+#[derive(Default)]
+pub struct Telemetry {
+    #[cfg(feature = "otlp")]
+    tracer_provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+    #[cfg(feature = "otlp")]
+    meter_provider: Option<opentelemetry_sdk::metrics::SdkMeterProvider>,
+}
+
+
+/// Installs the global subscriber emitting one JSON object per line.
+///
+/// `RUST_LOG` takes precedence over the configured `log_level` when it is set.
+/// Closing spans are logged with their `time.busy` and `time.idle` fields so every instrumented call carries its latency.
+/// When `telemetry.endpoint` is set, spans and metrics are also exported to that OTLP collector.
+pub fn init(config: &Config) -> Result<Telemetry, Box<dyn StdError>> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(config.log_level.as_str()));
+    let fmt = tracing_subscriber::fmt::layer()
+        .json()
+        .with_current_span(true)
+        .with_span_list(true)
+        .with_span_events(FmtSpan::CLOSE);
+    let registry = tracing_subscriber::registry().with(filter).with(fmt);
+    #[cfg(feature = "otlp")]
+    {
+        let telemetry = otlp::init(&config.telemetry)?;
+        let layer = telemetry.tracer_provider.as_ref().map(|provider| {
+            use opentelemetry::trace::TracerProvider;
+            tracing_opentelemetry::layer().with_tracer(provider.tracer("hiveguard"))
+        });
+        registry.with(layer).init();
+        Ok(telemetry)
+    }
+    #[cfg(not(feature = "otlp"))]
+    {
+        registry.init();
+        Ok(Telemetry::default())
+    }
+}
+
+
+#[cfg(feature = "otlp")]
+mod otlp {
+    use opentelemetry_otlp::{MetricExporter, SpanExporter, WithExportConfig, WithHttpConfig};
+    use opentelemetry_sdk::{metrics::SdkMeterProvider, trace::SdkTracerProvider, Resource};
+    use opentelemetry_otlp::ExporterBuildError;
+    use crate::config::TelemetryConfig;
+    use super::Telemetry;
+
+    /// Builds the span and metric pipelines exporting to `config.endpoint` over OTLP/HTTP.
+    ///
+    /// The meter provider is installed globally so instruments can be created anywhere through `opentelemetry::global::meter`.
+    pub fn init(config: &TelemetryConfig) -> Result<Telemetry, ExporterBuildError> {
+        let Some(endpoint) = config.endpoint.as_deref().map(|endpoint| endpoint.trim_end_matches('/')) else {
+            return Ok(Telemetry::default());
+        };
+        let resource = Resource::builder().with_service_name(config.service_name.clone()).build();
+        let spans = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{}/v1/traces", endpoint))
+            .with_headers(config.headers.clone())
+            .build()?;
+        let metrics = MetricExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{}/v1/metrics", endpoint))
+            .with_headers(config.headers.clone())
+            .build()?;
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_batch_exporter(spans)
+            .with_resource(resource.clone())
+            .build();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_periodic_exporter(metrics)
+            .with_resource(resource)
+            .build();
+        opentelemetry::global::set_meter_provider(meter_provider.clone());
+        Ok(Telemetry { tracer_provider: Some(tracer_provider), meter_provider: Some(meter_provider) })
+    }
+}
+
+
+#[cfg(feature = "otlp")]
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Some(provider) = self.tracer_provider.take() && let Err(err) = provider.shutdown() {
+            eprintln!("could not flush the exported spans: {}", err);
+        }
+        if let Some(provider) = self.meter_provider.take() && let Err(err) = provider.shutdown() {
+            eprintln!("could not flush the exported metrics: {}", err);
+        }
+    }
+}
+
+
+impl LogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+}
@@ -1,9 +1,107 @@
-mod adaptors;
-mod domain;
-mod ports;
-mod types;
+#![recursion_limit = "256"]
+mod cli;
+mod admin;
+mod doctor;
+mod jobs;
+
+use hiveguard::adaptors::outputs::secrets::SecretStores;
+use hiveguard::adaptors::outputs::{error_reporting::ErrorReporting, http, retry::Retry};
+use hiveguard::ports::outputs::error_reporter::ErrorReporter;
+use hiveguard::types::{ErrorReport, RequestContext};
+use hiveguard::config::{Config, SharedConfig};
+use hiveguard::{logging, types::{Id, PiiCipher}};
+use std::time::Duration;
+use std::path::PathBuf;
+use clap::Parser;
+use cli::{Cli, Command, AdminCommand};
+
+/// how often the configuration file is checked for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    // a new key is needed before there is a valid configuration to load.
+    if let Some(Command::Admin(AdminCommand::GenerateKey)) = cli.command {
+        println!("{}", admin::generate_key());
+        return Ok(());
+    }
+    let secrets = SecretStores::from_env().await;
+    let overrides = cli.overrides();
+    let config = match Config::load(cli.config.as_deref(), cli.profile.as_deref(), &secrets).await {
+        Ok(mut config) => {
+            overrides(&mut config);
+            Id::set_strategy(config.id_strategy);
+            PiiCipher::install(config.pii.cipher());
+            SharedConfig::new(config)
+        },
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    if cli.check_config {
+        println!("configuration is valid");
+        return Ok(());
+    }
+    if cli.check_schema {
+        admin::check_schema(&config.load()).await?;
+        println!("database schema is valid");
+        return Ok(());
+    }
+    if cli.doctor {
+        return doctor::run(&config.load()).await;
+    }
+    if let Some(Command::Admin(command)) = cli.command {
+        return admin::run(command, &config.load(), cli.tenant.as_deref()).await;
+    }
+    let _telemetry = logging::init(&config.load())?;
+    let reporter = ErrorReporting::new(&config.load().error_reporting, http::client(&config.load().http)?, Retry::new(&config.load().retry));
+    let result = serve(&config, &reporter, cli.config, cli.profile, secrets, overrides).await;
+    if let Err(err) = &result {
+        tracing::error!(error = %err, "hiveguard stopped");
+        if let Err(err) = reporter.report(ErrorReport::new(err, RequestContext::default())).await {
+            tracing::error!(error = %err, "could not report the error");
+        }
+    }
+    result
+}
+
+
+/// Runs the configuration watcher, when there is a file to watch, and the maintenance jobs until hiveguard is shut down.
+async fn serve(config: &SharedConfig, reporter: &ErrorReporting, path: Option<PathBuf>, profile: Option<String>, secrets: SecretStores, overrides: impl Fn(&mut Config)) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "dynamodb")]
+    if config.load().dynamodb.create_tables {
+        admin::create_tables(&config.load()).await?;
+    }
+    let reload = async {
+        match path {
+            Some(path) => config.clone().watch(path, profile, RELOAD_INTERVAL, secrets, overrides).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        _ = reload => {},
+        result = jobs::run(config, reporter) => result?,
+        _ = shutdown() => tracing::info!("shutting down"),
+    }
     Ok(())
 }
+
+
+/// Resolves on Ctrl+C, or on SIGTERM where there are signals, eg when the container is stopped.
+async fn shutdown() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => {},
+                _ = terminate.recv() => {},
+            },
+            Err(_) => { let _ = tokio::signal::ctrl_c().await; },
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
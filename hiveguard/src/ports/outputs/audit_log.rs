@@ -0,0 +1,14 @@
+use crate::types::{Id, AuditEntry};
+use chrono::{DateTime, Utc};
+
+
+/// Keeps the audit logs of organisations, which their owners export. see `OrgAudit`.
+pub trait AuditLog {
+    type Error;
+
+    async fn record(&self, entry: AuditEntry) -> Result<(), Self::Error>;
+    /// The entries of the organisation that occurred at or after `since`, oldest first.
+    async fn entries(&self, org_id: Id, since: DateTime<Utc>) -> Result<Vec<AuditEntry>, Self::Error>;
+    /// Deletes the entries of the organisation that occurred before `before`. Returns the number of entries deleted.
+    async fn prune(&self, org_id: Id, before: DateTime<Utc>) -> Result<usize, Self::Error>;
+}
@@ -0,0 +1,20 @@
+use std::time::Duration;
+
+
+/// Counters and locks shared by every instance, so that limits hold across a cluster rather than per process.
+/// eg the failed logins counted towards a lockout, or the cooldown between two resent codes.
+pub trait Counters {
+    type Error;
+
+    /// Increments the counter `key`, which starts at 0 and is reset `window` after its first increment. Returns its new value.
+    async fn increment(&self, key: &str, window: Duration) -> Result<u64, Self::Error>;
+
+    /// The value of the counter `key`, 0 once its window is over.
+    async fn count(&self, key: &str) -> Result<u64, Self::Error>;
+
+    /// Takes the lock `key` for `ttl`. Returns false, without changing it, if the lock is already taken.
+    async fn acquire(&self, key: &str, ttl: Duration) -> Result<bool, Self::Error>;
+
+    /// Resets the counter, or releases the lock, `key`.
+    async fn reset(&self, key: &str) -> Result<(), Self::Error>;
+}
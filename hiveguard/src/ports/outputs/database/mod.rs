@@ -1,7 +1,8 @@
 pub mod tables;
 
-use crate::types::{Email, Id, Phone};
-use macros::{client, database};
+use crate::types::{Email, Id, Phone, Page, Namespace, Status, Document, Consent, UserFilter, DomainEvent};
+use macros::{client, replica, database};
+use chrono::{DateTime, Utc};
 use serde_json::{Map, Value};
 use tables::*;
 
@@ -12,10 +13,19 @@ pub trait Database {
     type UsersTable: UsersTable<Self::Client, Error: Into<Self::Error>>;
     type SessionsTable: SessionsTable<Self::Client, Error: Into<Self::Error>>;
     type VerificationsTable: VerificationsTable<Self::Client, Error: Into<Self::Error>>;
+    type GrantsTable: GrantsTable<Self::Client, Error: Into<Self::Error>>;
 
     fn users_table(&self) -> &Self::UsersTable;
     fn sessions_table(&self) -> &Self::SessionsTable;
     fn verifications_table(&self) -> &Self::VerificationsTable;
+    fn grants_table(&self) -> &Self::GrantsTable;
     #[client]
     fn client(&self) -> &Self::Client;
+    /// The client reads are sent to. eg the client of a read replica.
+    ///
+    /// Replicas may lag behind the primary, so the reads that have to see the latest writes are marked `#[primary]` on their table.
+    #[replica]
+    fn replica(&self) -> &Self::Client {
+        self.client()
+    }
 }
@@ -0,0 +1,28 @@
+use macros::{table, skip, primary};
+use chrono::{DateTime, Utc};
+use crate::types::Id;
+
+
+/// The short-lived grants of the authorization flows: authorization codes, device codes and consents. see `GrantRecord`.
+///
+/// Grants expire: reads never return an expired grant, even while it lingers until it is purged.
+#[table(key = "id", indexes("user_id"))]
+pub trait GrantsTable<Client> {
+    type Error;
+    type Item;
+    #[skip(Error)]
+    async fn create_grant(&self, grant: Self::Item, client: &Client) -> Result<(), Self::Error>;
+    #[skip(Error)]
+    #[primary]
+    async fn get_grant_by_id(&self, id: String, client: &Client) -> Result<Option<Self::Item>, Self::Error>;
+    #[skip(Error)]
+    async fn get_grants_by_user_id(&self, user_id: Id, client: &Client) -> Result<Vec<Self::Item>, Self::Error>;
+    /// Deletes the grant and returns it, unless it expired. Of concurrent calls, only one gets the grant, so that it is redeemed once.
+    #[skip(Error)]
+    async fn take_grant(&self, id: String, client: &Client) -> Result<Option<Self::Item>, Self::Error>;
+    #[skip(Error)]
+    async fn delete_grant(&self, id: String, client: &Client) -> Result<(), Self::Error>;
+    /// Deletes the grants that expired before `before`. Returns the number of grants deleted.
+    #[skip(Error)]
+    async fn purge_expired_grants(&self, before: DateTime<Utc>, client: &Client) -> Result<usize, Self::Error>;
+}
@@ -1,8 +1,10 @@
 mod verifications;
+mod grants;
 mod sessions;
 mod users;
 
 
 pub use verifications::VerificationsTable;
+pub use grants::GrantsTable;
 pub use sessions::SessionsTable;
 pub use users::UsersTable;
\ No newline at end of file
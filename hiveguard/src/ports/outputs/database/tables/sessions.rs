@@ -1,16 +1,19 @@
-use macros::{table, skip};
+use macros::{table, skip, primary};
+use chrono::{DateTime, Utc};
 use crate::types::Id;
 
 
-#[table]
+#[table(key = "id", indexes("user_id"))]
 pub trait SessionsTable<Client> {
     type Error;
     type Item;
     #[skip(Error)]
     async fn create_session(&self, session: Self::Item, client: &Client) -> Result<(), Self::Error>;
     #[skip(Error)]
+    #[primary]
     async fn get_session_by_id(&self, id: Id, client: &Client) -> Result<Option<Self::Item>, Self::Error>;
     #[skip(Error)]
+    #[primary]
     async fn get_sessions_by_user_id(&self, user_id: Id, client: &Client) -> Result<Vec<Self::Item>, Self::Error>;
     #[skip(Error)]
     async fn change_current_refresh_token(
@@ -19,6 +22,13 @@ pub trait SessionsTable<Client> {
         new_refresh_token_id: Id,
         client: &Client
     ) -> Result<(), Self::Error>;
+    /// Records that a token of the session was just used.
+    #[skip(Error)]
+    async fn touch_session(&self, id: Id, client: &Client) -> Result<(), Self::Error>;
     #[skip(Error)]
     async fn delete_session(&self, id: Id, client: &Client) -> Result<(), Self::Error>;
+    /// Deletes the sessions last active before `idle_before` or created before `created_before`.
+    /// Returns the ids of the sessions deleted, for their tokens to be revoked.
+    #[skip(Error)]
+    async fn purge_expired_sessions(&self, idle_before: DateTime<Utc>, created_before: DateTime<Utc>, client: &Client) -> Result<Vec<Id>, Self::Error>;
 }
@@ -1,13 +1,18 @@
-use crate::types::{Id, Email, Phone};
+use crate::types::{Id, Email, Phone, Page, Namespace, Status, Document, Consent, UserFilter, DomainEvent};
 use serde_json::{Map, Value};
+use chrono::{DateTime, Utc};
 use macros::{table, skip};
 
-#[table]
+#[table(key = "id", indexes("email", "phone", "username", "previous_username"), unique("username", "email", "phone"))]
 pub trait UsersTable<Client> {
     type Error;
     type Item;
     #[skip(Error)]
     async fn create_user(&self, user: Self::Item, client: &Client) -> Result<(), Self::Error>;
+    /// Creates the user like `create_user`, adding `event` to the outbox of the database in the same write,
+    /// so that neither is stored without the other.
+    #[skip(Error)]
+    async fn create_user_with_event(&self, user: Self::Item, event: DomainEvent, client: &Client) -> Result<(), Self::Error>;
     #[skip(Error)]
     async fn get_user_by_id(&self, id: Id, client: &Client) -> Result<Option<Self::Item>, Self::Error>;
     #[skip(Error)]
@@ -15,7 +20,45 @@ pub trait UsersTable<Client> {
     #[skip(Error)]
     async fn get_user_by_phone(&self, phone: Phone, client: &Client) -> Result<Option<Self::Item>, Self::Error>;
     #[skip(Error)]
+    async fn get_user_by_username(&self, username: String, client: &Client) -> Result<Option<Self::Item>, Self::Error>;
+    /// The users who went by `previous_username` before their last change of username.
+    #[skip(Error)]
+    async fn get_users_by_previous_username(&self, previous_username: String, client: &Client) -> Result<Vec<Self::Item>, Self::Error>;
+    #[skip(Error)]
     async fn update_user(&self, id: Id, update: Map<String, Value>, client: &Client) -> Result<Self::Item, Self::Error>;
+    /// Updates the user like `update_user`, adding `event` to the outbox of the database in the same write.
+    #[skip(Error)]
+    async fn update_user_with_event(&self, id: Id, update: Map<String, Value>, event: DomainEvent, client: &Client) -> Result<Self::Item, Self::Error>;
+    /// Updates the user like `update_user`, only while it was last updated at `updated_at`, as it was read.
+    ///
+    /// A user changed since is left as it is, and the update fails with `DatabaseError::Changed`.
+    #[skip(Error)]
+    async fn update_user_if(&self, id: Id, update: Map<String, Value>, updated_at: DateTime<Utc>, client: &Client) -> Result<Self::Item, Self::Error>;
+    /// Replaces the fields of one namespace of the metadata of the user.
+    #[skip(Error)]
+    async fn set_user_metadata(&self, id: Id, namespace: Namespace, metadata: Map<String, Value>, client: &Client) -> Result<(), Self::Error>;
+    /// Sets the status of the user along with why it changed.
+    #[skip(Error)]
+    async fn set_user_status(&self, id: Id, status: Status, reason: Option<String>, client: &Client) -> Result<(), Self::Error>;
+    /// Records that the user accepted a version of `document`, replacing what they accepted before.
+    #[skip(Error)]
+    async fn set_user_consent(&self, id: Id, document: Document, consent: Consent, client: &Client) -> Result<(), Self::Error>;
+    /// Makes the user log in with the password `hash`, whatever they logged in with before.
+    #[skip(Error)]
+    async fn set_user_password(&self, id: Id, hash: String, client: &Client) -> Result<(), Self::Error>;
     #[skip(Error)]
     async fn delete_user(&self, id: Id, client: &Client) -> Result<(), Self::Error>;
+    #[skip(Error)]
+    async fn list_users(&self, after: Option<Id>, limit: usize, client: &Client) -> Result<Page<Self::Item, Id>, Self::Error>;
+    /// Lists the users matching `filter` like `list_users` lists every user.
+    ///
+    /// A page holds at most `limit` users. It may hold fewer, even none, while `next` is set.
+    #[skip(Error)]
+    async fn search_users(&self, filter: UserFilter, after: Option<Id>, limit: usize, client: &Client) -> Result<Page<Self::Item, Id>, Self::Error>;
+    /// Moves the encrypted emails and phone numbers of every user onto the active key of the installed `PiiCipher`,
+    /// encrypting those still stored in the clear. Returns the number of users changed.
+    ///
+    /// Adaptors that do not store them at rest have nothing to do.
+    #[skip(Error)]
+    async fn reencrypt_users(&self, client: &Client) -> Result<usize, Self::Error>;
 }
\ No newline at end of file
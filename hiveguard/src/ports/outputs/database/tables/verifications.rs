@@ -1,17 +1,23 @@
 use crate::types::{Id, Email, Phone};
-use macros::{table, skip};
+use chrono::{DateTime, Utc};
+use macros::{table, skip, primary};
 
 
-#[table]
+#[table(key = "id", indexes("email", "phone"))]
 pub trait VerificationsTable<Client> {
     type Error;
     type Item;
     #[skip(Error)]
     async fn create_verification_code(&self, verification_code: Self::Item, client: &Client) -> Result<(), Self::Error>;
     #[skip(Error)]
+    #[primary]
     async fn get_verification_by_email(&self, email: Email, client: &Client) -> Result<Option<Self::Item>, Self::Error>;
     #[skip(Error)]
+    #[primary]
     async fn get_verification_by_phone(&self, phone: Phone, client: &Client) -> Result<Option<Self::Item>, Self::Error>;
     #[skip(Error)]
     async fn delete_verification(&self, user_id: Id, client: &Client) -> Result<(), Self::Error>;
+    /// Deletes the verifications that expired before `before`. Returns the number of verifications deleted.
+    #[skip(Error)]
+    async fn purge_expired_verifications(&self, before: DateTime<Utc>, client: &Client) -> Result<usize, Self::Error>;
 }
\ No newline at end of file
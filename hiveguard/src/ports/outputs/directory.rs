@@ -0,0 +1,12 @@
+use crate::types::DirectoryEntry;
+
+
+/// An external directory the passwords of users are checked against. eg LDAP or Active Directory.
+///
+/// The directory stays the source of truth for passwords, hiveguard never stores them.
+pub trait Directory {
+    type Error;
+
+    /// The entry of `username` when `password` is theirs, `None` when the credentials are wrong.
+    async fn authenticate(&self, username: &str, password: &str) -> Result<Option<DirectoryEntry>, Self::Error>;
+}
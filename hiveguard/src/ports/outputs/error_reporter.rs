@@ -0,0 +1,25 @@
+use crate::types::{Error, ErrorReport, RequestContext};
+use std::fmt::Display;
+
+
+/// An error tracking service internal errors are forwarded to.
+pub trait ErrorReporter {
+    type Error;
+
+    async fn report(&self, report: ErrorReport) -> Result<(), Self::Error>;
+
+    /// Reports `error` if it is internal. see [`Error::is_internal`].
+    ///
+    /// A failure to deliver the report is logged rather than returned so it never masks the original error.
+    async fn capture(&self, error: &Error, context: RequestContext)
+    where
+        Self::Error: Display
+    {
+        if !error.is_internal() {
+            return;
+        }
+        if let Err(err) = self.report(ErrorReport::new(error, context)).await {
+            tracing::error!(error = %err, "could not report the error");
+        }
+    }
+}
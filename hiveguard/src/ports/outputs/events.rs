@@ -0,0 +1,17 @@
+use crate::types::DomainEvent;
+
+
+/// A message bus [`DomainEvent`]s are published to. eg Kafka or NATS.
+pub trait EventPublisher {
+    type Error;
+
+    async fn publish(&self, event: &DomainEvent) -> Result<(), Self::Error>;
+
+    /// Whether the events are stored to be published later, eg in an outbox, in which case an event that could not be
+    /// stored fails the write it follows instead of being lost.
+    ///
+    /// The outbox is the one of the database: the events of the writes of users are added by the database, in the same write.
+    fn durable(&self) -> bool {
+        false
+    }
+}
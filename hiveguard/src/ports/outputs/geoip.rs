@@ -0,0 +1,11 @@
+use crate::types::Location;
+use std::net::IpAddr;
+
+
+/// A GeoIP database locating IP addresses. eg a MaxMind database file or an HTTP lookup service.
+pub trait GeoIp {
+    type Error;
+
+    /// The location of `ip`, `None` when the database does not know it. eg private addresses
+    async fn locate(&self, ip: IpAddr) -> Result<Option<Location>, Self::Error>;
+}
@@ -0,0 +1,15 @@
+use crate::types::SigningKey;
+
+
+/// Stores the signing keys of the keyring. Which key signs is decided by the domain, not the store.
+pub trait KeyStore {
+    type Error;
+
+    /// Fails with `AlreadyExists` when the kid is taken.
+    async fn create(&self, key: SigningKey) -> Result<(), Self::Error>;
+    async fn get(&self, kid: &str) -> Result<Option<SigningKey>, Self::Error>;
+    /// Every key, retired ones included. Keyrings hold a handful of keys, so they are read at once.
+    async fn list(&self) -> Result<Vec<SigningKey>, Self::Error>;
+    /// Replaces the key. Fails with `KeyNotFound` when it does not exist.
+    async fn update(&self, key: SigningKey) -> Result<(), Self::Error>;
+}
@@ -0,0 +1,11 @@
+use crate::types::{Id, LoginAttempt};
+
+
+/// Keeps the recent login attempts of users, so they can review who accessed their account.
+pub trait LoginHistory {
+    type Error;
+
+    async fn record(&self, attempt: LoginAttempt) -> Result<(), Self::Error>;
+    /// At most `limit` attempts of the user, the most recent first.
+    async fn recent(&self, user_id: Id, limit: usize) -> Result<Vec<LoginAttempt>, Self::Error>;
+}
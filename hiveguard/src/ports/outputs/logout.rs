@@ -0,0 +1,9 @@
+use url::Url;
+
+
+/// Delivers logout tokens to the back-channel logout uris of services. see [`Logout`](crate::domain::Logout).
+pub trait LogoutNotifier {
+    type Error;
+
+    async fn notify(&self, uri: &Url, logout_token: &str) -> Result<(), Self::Error>;
+}
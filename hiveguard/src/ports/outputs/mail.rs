@@ -0,0 +1,22 @@
+use crate::types::Mail;
+use chrono::{DateTime, Utc};
+
+
+/// Delivers emails. eg through SMTP.
+pub trait Mailer {
+    type Error;
+
+    async fn send(&self, mail: &Mail) -> Result<(), Self::Error>;
+}
+
+
+/// Persists the emails waiting to be delivered, so that delivering them is decoupled from the requests sending them.
+pub trait MailQueue {
+    type Error;
+
+    async fn enqueue(&self, mail: Mail) -> Result<(), Self::Error>;
+    /// At most `limit` pending mails due at `now`, the longest due first.
+    async fn due(&self, now: DateTime<Utc>, limit: usize) -> Result<Vec<Mail>, Self::Error>;
+    /// Replaces the stored mail with the same id, eg once it is sent.
+    async fn update(&self, mail: Mail) -> Result<(), Self::Error>;
+}
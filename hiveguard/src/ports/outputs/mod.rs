@@ -1,2 +1,18 @@
+pub mod audit_log;
+pub mod counters;
 pub mod database;
+pub mod directory;
+pub mod error_reporter;
+pub mod events;
+pub mod geoip;
+pub mod keys;
+pub mod signer;
+pub mod secrets;
+pub mod mail;
+pub mod outbox;
+pub mod login_history;
+pub mod logout;
+pub mod organisations;
+pub mod security_events;
+pub mod storage;
 pub mod verify;
\ No newline at end of file
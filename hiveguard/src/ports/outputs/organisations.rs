@@ -0,0 +1,25 @@
+use crate::types::{Id, Organisation, Membership};
+
+
+/// Stores organisations and the memberships of their users. The hierarchy is enforced by the domain, not the store.
+pub trait OrganisationStore {
+    type Error;
+
+    /// Fails with `AlreadyExists` when the id is taken.
+    async fn create(&self, organisation: Organisation) -> Result<(), Self::Error>;
+    async fn get(&self, id: Id) -> Result<Option<Organisation>, Self::Error>;
+    /// Every organisation, for the maintenance jobs.
+    async fn all(&self) -> Result<Vec<Organisation>, Self::Error>;
+    /// The organisations right under `parent`.
+    async fn children(&self, parent: Id) -> Result<Vec<Organisation>, Self::Error>;
+    /// Replaces the organisation. Fails with `OrganisationNotFound` when it does not exist.
+    async fn update(&self, organisation: Organisation) -> Result<(), Self::Error>;
+    async fn delete(&self, id: Id) -> Result<(), Self::Error>;
+    /// Adds the membership, or replaces the role the user had in the organisation.
+    async fn set_membership(&self, membership: Membership) -> Result<(), Self::Error>;
+    async fn remove_membership(&self, org_id: Id, user_id: Id) -> Result<(), Self::Error>;
+    /// The memberships given in the organisation itself, not those inherited from above it.
+    async fn members(&self, org_id: Id) -> Result<Vec<Membership>, Self::Error>;
+    /// The memberships given to the user, in any organisation.
+    async fn memberships(&self, user_id: Id) -> Result<Vec<Membership>, Self::Error>;
+}
@@ -0,0 +1,15 @@
+use crate::types::{DomainEvent, Id};
+
+
+/// Persists the domain events waiting to be published, so that an unreachable bus delays them rather than losing them.
+///
+/// Adaptors also implement `EventPublisher` by adding the event, so the domain publishes to the outbox like to any bus.
+pub trait Outbox {
+    type Error;
+
+    async fn add(&self, event: &DomainEvent) -> Result<(), Self::Error>;
+    /// At most `limit` events waiting to be published, the oldest first.
+    async fn pending(&self, limit: usize) -> Result<Vec<DomainEvent>, Self::Error>;
+    /// Removes the event once it is published.
+    async fn remove(&self, id: Id) -> Result<(), Self::Error>;
+}
@@ -0,0 +1,9 @@
+/// A store that configuration values can reference instead of holding credentials in plaintext.
+pub trait Secrets {
+    type Error;
+
+    /// Returns the secret `value` refers to. eg `vault:secret/hiveguard#smtp_password` or `aws-sm:hiveguard/prod`
+    ///
+    /// `Ok(None)` means `value` is not a secret reference and should be used as it is.
+    async fn resolve(&self, value: &str) -> Result<Option<String>, Self::Error>;
+}
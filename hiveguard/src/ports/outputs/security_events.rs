@@ -0,0 +1,9 @@
+use crate::types::SecurityEvent;
+
+
+/// A destination for [`SecurityEvent`]s. eg a log stream, a SIEM webhook or a message bus.
+pub trait SecurityEvents {
+    type Error;
+
+    async fn emit(&self, event: SecurityEvent) -> Result<(), Self::Error>;
+}
@@ -0,0 +1,9 @@
+/// Signs with an Ed25519 key it may never reveal. eg a key of AWS KMS or of an HSM.
+pub trait Signer {
+    type Error;
+
+    /// The public key verifying the signatures, as resource servers are configured with.
+    async fn public_key(&self) -> Result<[u8; 32], Self::Error>;
+    /// The pure Ed25519 signature of `message`.
+    async fn sign(&self, message: &[u8]) -> Result<[u8; 64], Self::Error>;
+}
@@ -0,0 +1,19 @@
+use crate::types::{Upload, StoredObject};
+use std::time::Duration;
+
+
+/// An object store for the files users upload. eg S3 or any S3 compatible store.
+pub trait Storage {
+    type Error;
+
+    /// A URL `content_length` bytes of `content_type` can be uploaded to under `key` for the next `expires_in`.
+    ///
+    /// The content type and length are part of the signature so the client cannot upload anything else.
+    async fn presign_upload(&self, key: &str, content_type: &str, content_length: u64, expires_in: Duration) -> Result<Upload, Self::Error>;
+
+    /// `None` when nothing is stored under `key`.
+    async fn head(&self, key: &str) -> Result<Option<StoredObject>, Self::Error>;
+
+    /// The URL the file stored under `key` is served from.
+    fn public_url(&self, key: &str) -> String;
+}
@@ -0,0 +1,149 @@
+use http::{HeaderValue, Request, Response, StatusCode, header::{AUTHORIZATION, WWW_AUTHENTICATE}};
+use std::task::{Context, Poll};
+use crate::types::{Error, Token};
+use tower_service::Service;
+use super::ResourceServer;
+use tower_layer::Layer;
+use std::future::Future;
+use std::sync::Arc;
+use std::pin::Pin;
+
+
+/// A tower layer refusing the requests without a valid token of hiveguard holding every scope it requires.
+///
+/// The token of an accepted request is added to its extensions, where handlers read it from.
+/// A refused request gets an empty response, with the status of its error and a `WWW-Authenticate` header.
+#[derive(Clone)]
+pub struct RequireToken {
+    server: Arc<ResourceServer>,
+    scopes: Arc<[String]>,
+}
+
+
+#[derive(Clone)]
+pub struct RequireTokenService<S> {
+    inner: S,
+    server: Arc<ResourceServer>,
+    scopes: Arc<[String]>,
+}
+
+
+impl RequireToken {
+    pub fn new(server: Arc<ResourceServer>) -> Self {
+        Self { server, scopes: Arc::new([]) }
+    }
+
+    /// Requires every scope of `scopes`, space separated as in tokens. eg `reports:read profile`
+    pub fn scopes(self, scopes: &str) -> Self {
+        Self { scopes: scopes.split_whitespace().map(String::from).collect(), ..self }
+    }
+}
+
+
+impl<S> Layer<S> for RequireToken {
+    type Service = RequireTokenService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequireTokenService { inner, server: self.server.clone(), scopes: self.scopes.clone() }
+    }
+}
+
+
+impl<S, B, R> Service<Request<B>> for RequireTokenService<S>
+where
+    S: Service<Request<B>, Response = Response<R>> + Clone + Send + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+    R: Default,
+{
+    type Response = Response<R>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        // the clone may not be ready, unlike `inner`.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let (server, scopes) = (self.server.clone(), self.scopes.clone());
+        Box::pin(async move {
+            let authorization = request.headers().get(AUTHORIZATION).and_then(|header| header.to_str().ok());
+            let scopes = scopes.iter().map(String::as_str).collect::<Vec<_>>();
+            match server.authorize(authorization, &scopes).await {
+                Ok(token) => {
+                    request.extensions_mut().insert::<Token>(token);
+                    inner.call(request).await
+                },
+                Err(err) => Ok(refusal(&err)),
+            }
+        })
+    }
+}
+
+
+/// The response to a request refused with `err`, its `WWW-Authenticate` header as of RFC 6750.
+fn refusal<R: Default>(err: &Error) -> Response<R> {
+    let mut response = Response::new(R::default());
+    *response.status_mut() = StatusCode::from_u16(err.code().status()).unwrap_or(StatusCode::UNAUTHORIZED);
+    let challenge = match err {
+        Error::InsufficientScope(scope) => format!("Bearer error=\"insufficient_scope\", scope=\"{}\"", scope),
+        Error::InvalidCredentials | Error::SessionExpired => String::from("Bearer error=\"invalid_token\""),
+        _ => return response,
+    };
+    if let Ok(challenge) = HeaderValue::from_str(&challenge) {
+        response.headers_mut().insert(WWW_AUTHENTICATE, challenge);
+    }
+    response
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::{server, sign, token};
+    use std::convert::Infallible;
+    use std::future::{Ready, ready};
+
+    /// answers with the subject of the token of the request.
+    #[derive(Clone)]
+    struct Subject;
+
+    impl Service<Request<()>> for Subject {
+        type Response = Response<String>;
+        type Error = Infallible;
+        type Future = Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: Request<()>) -> Self::Future {
+            ready(Ok(Response::new(request.extensions().get::<Token>().map(|token| token.subject.to_string()).unwrap_or_default())))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_requests_without_a_valid_token_are_refused() {
+        let mut service = RequireToken::new(Arc::new(server())).scopes("reports:read").layer(Subject);
+        let request = |token: Option<String>| {
+            let request = Request::builder();
+            let request = match token {
+                Some(token) => request.header(AUTHORIZATION, format!("Bearer {}", token)),
+                None => request,
+            };
+            request.body(()).unwrap()
+        };
+        let token = token("reports:read");
+        let response = service.call(request(Some(sign(&token)))).await.unwrap();
+        assert_eq!((response.status(), response.body().as_str()), (StatusCode::OK, token.subject.to_string().as_str()));
+        let response = service.call(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()[WWW_AUTHENTICATE], "Bearer error=\"invalid_token\"");
+        let response = service.call(request(Some(sign(&super::super::tests::token("profile"))))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(response.headers()[WWW_AUTHENTICATE], "Bearer error=\"insufficient_scope\", scope=\"reports:read\"");
+    }
+}
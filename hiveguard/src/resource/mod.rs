@@ -0,0 +1,254 @@
+//! Verifying the tokens hiveguard signs under `v4.public`, for the services it issues them for.
+//!
+//! [`ResourceServer`] verifies a token and checks its scopes, and [`RequireToken`] does so for every request of a tower service.
+
+use rusty_paseto::core::{Key, Paseto, PasetoAsymmetricPublicKey, Public, V4};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use crate::types::{ConfigError, ConfigIssue, Error, Token};
+use std::sync::{PoisonError, RwLock};
+use crate::config::ResourceServerConfig;
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+use tracing::instrument;
+use base64::Engine;
+use chrono::Utc;
+use url::Url;
+
+mod layer;
+
+pub use layer::{RequireToken, RequireTokenService};
+
+
+/// the least time between two fetches of the keys prompted by tokens signed with unknown keys.
+const MIN_REFRESH: Duration = Duration::from_secs(30);
+
+
+/// Verifies the tokens hiveguard signs, with the public keys it is configured with or fetches from hiveguard.
+///
+/// Fetched keys are kept for `keys_ttl_secs`, so verifying a token usually involves no request.
+pub struct ResourceServer {
+    issuer: String,
+    audience: Option<String>,
+    jwks_url: Option<Url>,
+    client: reqwest::Client,
+    ttl: Duration,
+    /// the keys of `public_keys`, always used.
+    configured: Vec<[u8; 32]>,
+    fetched: RwLock<Fetched>,
+}
+
+
+#[derive(Default)]
+struct Fetched {
+    keys: Vec<[u8; 32]>,
+    at: Option<Instant>,
+}
+
+
+/// The keys of a JSON Web Key Set. Keys other than Ed25519 ones are ignored.
+#[derive(Deserialize)]
+struct KeySet {
+    keys: Vec<Jwk>,
+}
+
+
+#[derive(Deserialize)]
+struct Jwk {
+    kty: String,
+    #[serde(default)]
+    crv: String,
+    #[serde(default)]
+    x: String,
+}
+
+
+impl ResourceServer {
+    pub fn new(config: &ResourceServerConfig, client: reqwest::Client) -> Result<Self, ConfigError> {
+        let mut issues = Vec::new();
+        if config.issuer.trim().is_empty() {
+            issues.push(ConfigIssue::new("issuer", "is required"));
+        }
+        let jwks_url = match config.jwks_url.as_deref().map(Url::parse) {
+            Some(Ok(url)) => Some(url),
+            Some(Err(err)) => {
+                issues.push(ConfigIssue::new("jwks_url", err.to_string()));
+                None
+            },
+            None => None,
+        };
+        if config.jwks_url.is_none() && config.public_keys.is_empty() {
+            issues.push(ConfigIssue::new("public_keys", "are required without a jwks_url"));
+        }
+        let mut configured = Vec::new();
+        for (i, key) in config.public_keys.iter().enumerate() {
+            // `Key` panics on keys of another length.
+            match Some(key).filter(|key| key.len() == 64).and_then(|key| Key::<32>::try_from(key.as_str()).ok()) {
+                Some(key) => configured.push(*key),
+                None => issues.push(ConfigIssue::new(format!("public_keys[{}]", i), "must be a hex encoded 32 byte key")),
+            }
+        }
+        if !issues.is_empty() {
+            return Err(ConfigError::Invalid(issues));
+        }
+        Ok(Self {
+            issuer: config.issuer.clone(),
+            audience: config.audience.clone(),
+            jwks_url,
+            client,
+            ttl: Duration::from_secs(config.keys_ttl_secs),
+            configured,
+            fetched: RwLock::new(Fetched::default()),
+        })
+    }
+
+    /// The token of an `Authorization` header, once verified and holding every scope of `scopes`.
+    pub async fn authorize(&self, authorization: Option<&str>, scopes: &[&str]) -> Result<Token, Error> {
+        let token = authorization
+            .and_then(|header| header.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, token)| token.trim())
+            .ok_or(Error::InvalidCredentials)?;
+        let token = self.verify(token).await?;
+        let granted = token.claims.get("scope").and_then(|scope| scope.as_str()).unwrap_or_default();
+        if let Some(missing) = scopes.iter().find(|scope| !granted.split_whitespace().any(|granted| granted == **scope)) {
+            return Err(Error::InsufficientScope(missing.to_string()));
+        }
+        Ok(token)
+    }
+
+    /// Checks the signature of `token` and its claims.
+    ///
+    /// A token signed with none of the known keys fetches the keys again, at most every 30 seconds, in case hiveguard rotated them.
+    #[instrument(skip_all, err(level = "debug"))]
+    pub async fn verify(&self, token: &str) -> Result<Token, Error> {
+        let payload = match self.verify_signature(token, &self.keys(false).await?) {
+            Some(payload) => payload,
+            None if self.jwks_url.is_some() => self.verify_signature(token, &self.keys(true).await?).ok_or(Error::InvalidCredentials)?,
+            None => return Err(Error::InvalidCredentials),
+        };
+        let token = serde_json::from_str::<Token>(&payload).map_err(|_| Error::InvalidCredentials)?;
+        let now = Utc::now();
+        if token.issuer != self.issuer || token.not_before.is_some_and(|not_before| not_before > now) {
+            return Err(Error::InvalidCredentials);
+        }
+        if self.audience.as_ref().is_some_and(|audience| !token.audience.contains(audience)) {
+            return Err(Error::InvalidCredentials);
+        }
+        if token.expiration <= now {
+            return Err(Error::SessionExpired);
+        }
+        Ok(token)
+    }
+
+    fn verify_signature(&self, token: &str, keys: &[[u8; 32]]) -> Option<String> {
+        keys.iter().find_map(|key| {
+            let key = Key::<32>::from(key);
+            Paseto::<V4, Public>::try_verify(token, &PasetoAsymmetricPublicKey::from(&key), None, None).ok()
+        })
+    }
+
+    /// The keys to verify tokens with. `stale` fetches them again unless they were fetched moments ago.
+    async fn keys(&self, stale: bool) -> Result<Vec<[u8; 32]>, Error> {
+        let Some(url) = &self.jwks_url else {
+            return Ok(self.configured.clone());
+        };
+        let (fetched, at) = {
+            let fetched = self.fetched.read().unwrap_or_else(PoisonError::into_inner);
+            (fetched.keys.clone(), fetched.at)
+        };
+        let fresh = match at {
+            Some(at) if stale => at.elapsed() < MIN_REFRESH,
+            Some(at) => at.elapsed() < self.ttl,
+            None => false,
+        };
+        let fetched = match fresh {
+            true => fetched,
+            false => {
+                let keys = self.fetch(url).await?;
+                *self.fetched.write().unwrap_or_else(PoisonError::into_inner) = Fetched { keys: keys.clone(), at: Some(Instant::now()) };
+                keys
+            },
+        };
+        Ok(self.configured.iter().chain(&fetched).copied().collect())
+    }
+
+    async fn fetch(&self, url: &Url) -> Result<Vec<[u8; 32]>, Error> {
+        let response = self.client.get(url.clone()).send().await.and_then(reqwest::Response::error_for_status);
+        let set = match response {
+            Ok(response) => response.json::<KeySet>().await,
+            Err(err) => Err(err),
+        };
+        match set {
+            Ok(set) => Ok(Self::ed25519(set)),
+            Err(err) => {
+                tracing::warn!(error = %err, "could not fetch the keys of hiveguard");
+                Err(Error::ProviderUnavailable("the key set of hiveguard"))
+            },
+        }
+    }
+
+    fn ed25519(set: KeySet) -> Vec<[u8; 32]> {
+        set.keys.into_iter()
+            .filter(|key| key.kty == "OKP" && key.crv == "Ed25519")
+            .filter_map(|key| URL_SAFE_NO_PAD.decode(key.x).ok()?.try_into().ok())
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusty_paseto::core::{PasetoAsymmetricPrivateKey, Payload};
+    use chrono::TimeDelta;
+    use crate::types::{Id, Audience};
+
+    pub(super) const PRIVATE_KEY: &str = "b4cbfb43df4ce210727d953e4a713307fa19bb7d9f85041438d9e11b942a37741eb9dbbbbc047c03fd70604e0071f0987e16b28b757225c11f00415d0e20b1a2";
+    pub(super) const PUBLIC_KEY: &str = "1eb9dbbbbc047c03fd70604e0071f0987e16b28b757225c11f00415d0e20b1a2";
+
+    pub(super) fn server() -> ResourceServer {
+        let config = ResourceServerConfig { issuer: String::from("hiveguard"), audience: Some(String::from("reports")), public_keys: vec![String::from(PUBLIC_KEY)], ..Default::default() };
+        ResourceServer::new(&config, reqwest::Client::new()).unwrap()
+    }
+
+    pub(super) fn sign(token: &Token) -> String {
+        let key = Key::<64>::try_from(PRIVATE_KEY).unwrap();
+        let payload = serde_json::to_string(token).unwrap();
+        Paseto::<V4, Public>::builder().set_payload(Payload::from(payload.as_str())).try_sign(&PasetoAsymmetricPrivateKey::from(&key)).unwrap()
+    }
+
+    pub(super) fn token(scope: &str) -> Token {
+        let mut claims = serde_json::Map::new();
+        claims.insert(String::from("scope"), scope.into());
+        Token { subject: Id::default(), issuer: String::from("hiveguard"), audience: Audience::One(String::from("reports")), expiration: Utc::now() + TimeDelta::minutes(5), issued_at: Utc::now(), claims, ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn test_tokens_are_verified() {
+        let server = server();
+        let token = token("profile reports:read");
+        let bearer = format!("Bearer {}", sign(&token));
+        assert_eq!(server.authorize(Some(&bearer), &["reports:read"]).await, Ok(token.clone()));
+        assert_eq!(server.authorize(Some(&bearer), &["reports:write"]).await, Err(Error::InsufficientScope(String::from("reports:write"))));
+        assert_eq!(server.authorize(None, &[]).await, Err(Error::InvalidCredentials));
+        assert_eq!(server.authorize(Some(&format!("Basic {}", sign(&token))), &[]).await, Err(Error::InvalidCredentials));
+        assert_eq!(server.verify(&sign(&Token { expiration: Utc::now() - TimeDelta::seconds(1), ..token.clone() })).await, Err(Error::SessionExpired));
+        assert_eq!(server.verify(&sign(&Token { issuer: String::from("elsewhere"), ..token.clone() })).await, Err(Error::InvalidCredentials));
+        assert_eq!(server.verify(&sign(&Token { audience: Audience::One(String::from("billing")), ..token.clone() })).await, Err(Error::InvalidCredentials));
+        let forged = sign(&token).replace(".public.", ".local.");
+        assert_eq!(server.verify(&forged).await, Err(Error::InvalidCredentials));
+    }
+
+    #[test]
+    fn test_only_ed25519_keys_are_read_from_key_sets() {
+        let x = URL_SAFE_NO_PAD.encode(*Key::<32>::try_from(PUBLIC_KEY).unwrap());
+        let set = serde_json::from_value::<KeySet>(serde_json::json!({"keys": [
+            {"kty": "OKP", "crv": "Ed25519", "kid": "2026-10", "x": x},
+            {"kty": "RSA", "n": "AQAB", "e": "AQAB"},
+            {"kty": "OKP", "crv": "X25519", "x": x},
+        ]})).unwrap();
+        assert_eq!(ResourceServer::ed25519(set), vec![*Key::<32>::try_from(PUBLIC_KEY).unwrap()]);
+        let config = ResourceServerConfig { issuer: String::from("hiveguard"), public_keys: vec![String::from("abc")], ..Default::default() };
+        assert!(ResourceServer::new(&config, reqwest::Client::new()).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::future::Future;
+use crate::types::Error;
+use cron::Schedule;
+use std::pin::Pin;
+
+
+type Task<'a> = Box<dyn Fn() -> Pin<Box<dyn Future<Output = Result<usize, Error>> + Send + 'a>> + Send + Sync + 'a>;
+
+
+/// Runs background jobs on cron schedules, within the process.
+///
+/// Jobs run one at a time on the task running [`Scheduler::run`], so a slow job delays the next ones rather than overlapping them.
+/// A job that fails is logged and runs again at its next time.
+pub struct Scheduler<'a> {
+    jobs: Vec<Job<'a>>,
+}
+
+
+struct Job<'a> {
+    name: &'static str,
+    schedule: Schedule,
+    task: Task<'a>,
+    stats: Mutex<JobStats>,
+    #[cfg(feature = "otlp")]
+    duration: opentelemetry::metrics::Histogram<f64>,
+}
+
+
+/// What a job did so far.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JobStats {
+    pub runs: u64,
+    pub failures: u64,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_duration: Duration,
+    /// the number of items the last successful run processed.
+    pub last_processed: usize,
+}
+
+
+impl<'a> Scheduler<'a> {
+    pub fn new() -> Self {
+        Self { jobs: Vec::new() }
+    }
+
+    /// Adds the job `name`, running `task` at every time of `schedule`. `task` returns the number of items it processed.
+    pub fn add<F, Fut>(&mut self, name: &'static str, schedule: Schedule, task: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'a,
+        Fut: Future<Output = Result<usize, Error>> + Send + 'a,
+    {
+        let task: Task<'a> = Box::new(move || Box::pin(task()));
+        self.jobs.push(Job {
+            name,
+            schedule,
+            task,
+            stats: Mutex::new(JobStats::default()),
+            #[cfg(feature = "otlp")]
+            duration: opentelemetry::global::meter("hiveguard")
+                .f64_histogram("hiveguard.job.duration")
+                .with_unit("s")
+                .with_description("duration of the runs of background jobs")
+                .build(),
+        });
+    }
+
+    /// Runs the jobs on their schedules. It never returns.
+    ///
+    /// Time is followed on tokio's clock from the moment it starts, so a paused clock drives the jobs in tests.
+    pub async fn run(&self) {
+        let (started, started_at) = (tokio::time::Instant::now(), Utc::now());
+        let now = || started_at + TimeDelta::from_std(started.elapsed()).unwrap_or(TimeDelta::MAX);
+        let mut next = self.jobs.iter().map(|job| job.schedule.after(&started_at).next()).collect::<Vec<_>>();
+        loop {
+            let Some(due) = next.iter().flatten().min().copied() else {
+                // no job will ever run again.
+                return std::future::pending().await;
+            };
+            tokio::time::sleep_until(started + (due - started_at).to_std().unwrap_or_default()).await;
+            for (job, next) in self.jobs.iter().zip(next.iter_mut()) {
+                if next.is_some_and(|next| next <= due) {
+                    let _ = job.run().await;
+                    *next = job.schedule.after(&now()).next();
+                }
+            }
+        }
+    }
+
+    /// Runs the job `name` right away, whatever its schedule. eg from an admin endpoint.
+    ///
+    /// Returns `None` when there is no such job.
+    pub async fn run_now(&self, name: &str) -> Option<Result<usize, Error>> {
+        let job = self.jobs.iter().find(|job| job.name == name)?;
+        Some(job.run().await)
+    }
+
+    /// The statistics of every job, by name.
+    pub fn stats(&self) -> Vec<(&'static str, JobStats)> {
+        self.jobs.iter().map(|job| (job.name, job.stats.lock().unwrap_or_else(PoisonError::into_inner).clone())).collect()
+    }
+}
+
+
+impl Default for Scheduler<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl Job<'_> {
+    async fn run(&self) -> Result<usize, Error> {
+        let started_at = Utc::now();
+        let start = Instant::now();
+        let result = (self.task)().await;
+        let elapsed = start.elapsed();
+        let mut stats = self.stats.lock().unwrap_or_else(PoisonError::into_inner);
+        stats.runs += 1;
+        stats.last_run_at = Some(started_at);
+        stats.last_duration = elapsed;
+        match &result {
+            Ok(processed) => {
+                stats.last_processed = *processed;
+                tracing::info!(job = self.name, processed, elapsed_ms = elapsed.as_millis() as u64, "job ran");
+            },
+            Err(err) => {
+                stats.failures += 1;
+                tracing::error!(job = self.name, error = %err, elapsed_ms = elapsed.as_millis() as u64, "job failed");
+            },
+        }
+        #[cfg(feature = "otlp")]
+        {
+            use opentelemetry::KeyValue;
+            let outcome = if result.is_ok() { "ok" } else { "error" };
+            self.duration.record(elapsed.as_secs_f64(), &[KeyValue::new("job", self.name), KeyValue::new("outcome", outcome)]);
+        }
+        result
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::types::DatabaseError;
+
+    #[tokio::test]
+    async fn test_jobs_run_on_schedule() {
+        let runs = AtomicUsize::new(0);
+        let mut scheduler = Scheduler::new();
+        scheduler.add("count", "* * * * * *".parse().unwrap(), || async {
+            Ok(runs.fetch_add(1, Ordering::Relaxed) + 1)
+        });
+        tokio::time::pause();
+        let run = scheduler.run();
+        tokio::pin!(run);
+        for _ in 0..3 {
+            // the paused clock is advanced to the next timer whenever the test waits, so a second passes at once.
+            assert!(tokio::time::timeout(Duration::from_secs(1), &mut run).await.is_err());
+        }
+        assert_eq!(runs.load(Ordering::Relaxed), 3);
+        let (name, stats) = &scheduler.stats()[0];
+        assert_eq!(*name, "count");
+        assert_eq!(stats.runs as usize, runs.load(Ordering::Relaxed));
+        assert_eq!(stats.failures, 0);
+    }
+
+    #[tokio::test]
+    async fn test_jobs_can_be_run_now() {
+        let mut scheduler = Scheduler::new();
+        // the 31st of February never comes.
+        scheduler.add("fail", "0 0 0 31 2 *".parse().unwrap(), || async { Err(Error::DatabaseError(DatabaseError::Internal("unavailable".into()))) });
+        assert!(scheduler.run_now("fail").await.unwrap().is_err());
+        assert!(scheduler.run_now("missing").await.is_none());
+        let stats = &scheduler.stats()[0].1;
+        assert_eq!((stats.runs, stats.failures), (1, 1));
+    }
+}
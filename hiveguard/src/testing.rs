@@ -0,0 +1,297 @@
+//! In-process test doubles and fixtures, so tests of the domain services and of the applications embedding them
+//! don't each copy the same setup. Built with the `testing` feature, and always for hiveguard's own tests.
+use crate::ports::outputs::database::{Database, tables::{UsersTable, SessionsTable}};
+use crate::types::{Error, User, Session, Id, Device, Login, Status, Namespace, Token, TokenBundle};
+use crate::adaptors::outputs::databases::memory::Memory;
+use crate::adaptors::outputs::security_events::Bus;
+use crate::adaptors::outputs::mail::memory::MemoryQueue;
+use crate::domain::{Password, PasswordService, Tokenizer};
+use std::sync::atomic::{AtomicU64, Ordering};
+use chrono::{TimeDelta, Utc};
+use serde_json::{Map, Value};
+use std::time::Duration;
+
+
+/// how long the access tokens of [`Tokens`] claim to last.
+const TOKEN_TTL: TimeDelta = TimeDelta::hours(1);
+
+
+/// numbers the users of `UserFixture::new` so their usernames and contacts never collide.
+static USERS: AtomicU64 = AtomicU64::new(1);
+
+
+/// The id `n`, padded with zeros. eg `000000000000000000000007` for 7, so that tests can name their ids.
+pub fn id(n: u8) -> Id {
+    Id::try_from(format!("{:024}", n)).expect("a valid id")
+}
+
+
+/// Everything the domain services need, kept in memory.
+///
+/// Security events go to `events`, where a test subscribes to them, and mails wait in `mails` to be read back.
+pub struct Harness {
+    pub db: Memory,
+    pub events: Bus,
+    pub mails: MemoryQueue,
+    pub tokens: Tokens,
+    pub passwords: PasswordService<Plain>,
+}
+
+
+impl Harness {
+    pub fn new() -> Self {
+        Self {
+            db: Memory::new(Duration::from_secs(1)),
+            events: Bus::new(16),
+            mails: MemoryQueue::new(),
+            tokens: Tokens,
+            passwords: PasswordService::new(Plain, 1),
+        }
+    }
+
+    /// Stores the user of `fixture` in `db`.
+    pub async fn create(&self, fixture: UserFixture) -> User {
+        fixture.create(&self.db).await
+    }
+}
+
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+/// Stores passwords as they are, since hashing is rarely what is tested.
+pub struct Plain;
+
+
+impl Password for Plain {
+    fn hash_password(&self, password: &str) -> Result<String, Error> {
+        Ok(password.to_string())
+    }
+
+    fn verify_password(&self, password: &str, hash: &str) -> Result<(), Error> {
+        if password == hash { Ok(()) } else { Err(Error::WrongPassword) }
+    }
+}
+
+
+/// Issues unsigned tokens naming their session, `<session id>` for access tokens and `<session id>.<refresh token id>` for refresh tokens.
+///
+/// Sessions are stored like a real tokenizer would, so what a test does with them shows in the database.
+pub struct Tokens;
+
+
+impl Tokens {
+    fn bundle(session: &Session, scope: Option<&str>) -> TokenBundle {
+        TokenBundle {
+            access_token: session.id.to_hex(),
+            refresh_token: format!("{}.{}", session.id.to_hex(), session.refresh_token_id.to_hex()),
+            token_type: String::from("Bearer"),
+            scope: scope.map(String::from),
+            id_token: None,
+            expires_at: Utc::now() + TOKEN_TTL,
+        }
+    }
+}
+
+
+impl Tokenizer for Tokens {
+    type Error = Error;
+
+    async fn generate_token<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, db: &DB, subject: Id, device: Device, scope: Option<&str>) -> Result<TokenBundle, Self::Error> where Self::Error: From<DB::Error> {
+        let now = Utc::now();
+        let session = Session { id: Id::default(), user_id: subject, refresh_token_id: Id::default(), previous_refresh_token_id: None, device, created_at: now, updated_at: now, last_active_at: now };
+        db.create_session(session.clone()).await?;
+        Ok(Self::bundle(&session, scope))
+    }
+
+    async fn renew_token<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, _: &DB, token: &Token) -> Result<Token, Self::Error> where Self::Error: From<DB::Error> {
+        Ok(Token { id: Id::default(), issued_at: Utc::now(), expiration: Utc::now() + TOKEN_TTL, ..token.clone() })
+    }
+
+    async fn renew_refresh_token<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, db: &DB, token: &Token) -> Result<Token, Self::Error> where Self::Error: From<DB::Error> {
+        let id = Id::default();
+        db.change_current_refresh_token(token.session_id, id).await?;
+        Ok(Token { id, issued_at: Utc::now(), ..token.clone() })
+    }
+
+    async fn renew_bundle<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, db: &DB, refresh_token: &Token) -> Result<TokenBundle, Self::Error> where Self::Error: From<DB::Error> {
+        db.change_current_refresh_token(refresh_token.session_id, Id::default()).await?;
+        let session = db.get_session_by_id(refresh_token.session_id).await?.ok_or(crate::types::DatabaseError::SessionNotFound)?;
+        Ok(Self::bundle(&session, refresh_token.claims.get("scope").and_then(serde_json::Value::as_str)))
+    }
+
+    async fn invalidate_token<DB: Database<SessionsTable: SessionsTable<DB::Client, Item = Session>>>(&self, db: &DB, token: &Token) -> Result<(), Self::Error> where Self::Error: From<DB::Error> {
+        Ok(db.delete_session(token.session_id).await?)
+    }
+
+    async fn validate_token(&self, _: &Token) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn decode_token(&self, token: &str) -> Result<Token, Self::Error> {
+        let (session_id, refresh_token_id) = token.split_once('.').map_or((token, None), |(session, refresh)| (session, Some(refresh)));
+        let id = match refresh_token_id {
+            Some(id) => Id::try_from(id.to_string())?,
+            None => Id::default(),
+        };
+        Ok(Token { session_id: Id::try_from(session_id.to_string())?, id, expiration: Utc::now() + TOKEN_TTL, ..Default::default() })
+    }
+}
+
+
+/// Builds a user for a test, active and logging in with the password `password` unless told otherwise.
+///
+/// ```ignore
+/// let user = UserFixture::new().username("jane").verified().create(&harness.db).await;
+/// ```
+pub struct UserFixture {
+    user: User,
+}
+
+
+impl UserFixture {
+    /// A user whose username and contacts are unique among the fixtures of the process.
+    pub fn new() -> Self {
+        let n = USERS.fetch_add(1, Ordering::Relaxed);
+        let now = Utc::now();
+        let user = User {
+            id: Id::default(),
+            username: format!("user{}", n),
+            fullname: format!("User {}", n),
+            #[cfg(feature = "email")]
+            email: Some(crate::types::Email::try_from(format!("user{}@example.com", n).as_str()).expect("a valid email")),
+            #[cfg(feature = "phone")]
+            phone: Some(crate::types::Phone::try_from(format!("+2547{:08}", n % 100_000_000)).expect("a valid phone")),
+            login: Login::Password(String::from("password")),
+            profile: None,
+            avatar: None,
+            status: Status::Active,
+            status_reason: None,
+            previous_username: None,
+            username_changed_at: None,
+            metadata: Default::default(),
+            consents: Default::default(),
+            created_at: now,
+            updated_at: now,
+        };
+        Self { user }
+    }
+
+    pub fn id(mut self, id: Id) -> Self {
+        self.user.id = id;
+        self
+    }
+
+    pub fn username(mut self, username: &str) -> Self {
+        self.user.username = username.to_string();
+        self
+    }
+
+    pub fn fullname(mut self, fullname: &str) -> Self {
+        self.user.fullname = fullname.to_string();
+        self
+    }
+
+    #[cfg(feature = "email")]
+    pub fn email(mut self, email: &str) -> Self {
+        self.user.email = Some(crate::types::Email::try_from(email).expect("a valid email"));
+        self
+    }
+
+    #[cfg(feature = "phone")]
+    pub fn phone(mut self, phone: &str) -> Self {
+        self.user.phone = Some(crate::types::Phone::try_from(phone.to_string()).expect("a valid phone"));
+        self
+    }
+
+    /// Marks the contacts of the user as verified.
+    #[cfg_attr(not(any(feature = "email", feature = "phone")), allow(unused_mut))]
+    pub fn verified(mut self) -> Self {
+        #[cfg(feature = "email")]
+        if let Some(crate::types::Email::New(address)) = &self.user.email {
+            self.user.email = Some(crate::types::Email::Verified(address.clone()));
+        }
+        #[cfg(feature = "phone")]
+        if let Some(crate::types::Phone::New(number)) = &self.user.phone {
+            self.user.phone = Some(crate::types::Phone::Verified(number.clone()));
+        }
+        self
+    }
+
+    /// Logs the user in with `password`, stored as [`Plain`] stores it.
+    pub fn password(mut self, password: &str) -> Self {
+        self.user.login = Login::Password(password.to_string());
+        self
+    }
+
+    pub fn login(mut self, login: Login) -> Self {
+        self.user.login = login;
+        self
+    }
+
+    pub fn status(mut self, status: Status) -> Self {
+        self.user.status = status;
+        self
+    }
+
+    pub fn metadata(mut self, namespace: Namespace, metadata: Map<String, Value>) -> Self {
+        *self.user.metadata.get_mut(namespace) = metadata;
+        self
+    }
+
+    pub fn build(self) -> User {
+        self.user
+    }
+
+    /// Stores the user in `db`, panicking if it cannot.
+    pub async fn create<DB: Database<UsersTable: UsersTable<DB::Client, Item = User>>>(self, db: &DB) -> User
+    where
+        Error: From<DB::Error>
+    {
+        let user = self.user;
+        if let Err(err) = db.create_user(user.clone()).await {
+            panic!("could not create the user fixture: {:?}", Error::from(err));
+        }
+        user
+    }
+}
+
+
+impl Default for UserFixture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fixtures_are_unique_and_created() {
+        let harness = Harness::new();
+        let (first, second) = (UserFixture::new().build(), UserFixture::new().build());
+        assert_ne!(first.username, second.username);
+        let user = harness.create(UserFixture::new().username("jane").status(Status::Suspended)).await;
+        assert_eq!(harness.db.get_user_by_id(user.id).await.unwrap(), Some(user));
+    }
+
+    #[tokio::test]
+    async fn test_tokens_follow_their_session() {
+        let harness = Harness::new();
+        let user = harness.create(UserFixture::new()).await;
+        let bundle = harness.tokens.generate_token(&harness.db, user.id, Device::default(), None).await.unwrap();
+        let refresh_token = harness.tokens.decode_token(&bundle.refresh_token).await.unwrap();
+        let renewed = harness.tokens.renew_bundle(&harness.db, &refresh_token).await.unwrap();
+        assert_eq!(renewed.access_token, bundle.access_token);
+        assert_ne!(renewed.refresh_token, bundle.refresh_token);
+        let access_token = harness.tokens.decode_token(&renewed.access_token).await.unwrap();
+        harness.tokens.invalidate_token(&harness.db, &access_token).await.unwrap();
+        assert_eq!(harness.db.get_sessions_by_user_id(user.id).await.unwrap(), vec![]);
+    }
+}
@@ -0,0 +1,78 @@
+use serde::{Serialize, Deserialize};
+use std::fmt::{self, Display};
+
+
+/// What a user agent string tells about the device it was sent from, for users telling their sessions apart.
+/// eg `Chrome on Windows 10`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Agent {
+    /// eg `Chrome` or `Safari`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub browser: Option<String>,
+    /// eg `Windows 10`, `Mac OSX` or `iPhone`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os: Option<String>,
+    #[serde(default)]
+    pub class: DeviceClass,
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceClass {
+    Desktop,
+    /// phones and tablets.
+    Mobile,
+    /// eg game consoles and TVs.
+    Appliance,
+    /// crawlers and other automated clients.
+    Bot,
+    #[default]
+    Unknown,
+}
+
+
+impl Agent {
+    /// Parses `user_agent`, leaving out what it does not tell.
+    pub fn parse(user_agent: &str) -> Self {
+        let Some(parsed) = woothee::parser::Parser::new().parse(user_agent) else {
+            return Self::default();
+        };
+        let known = |value: &str| (value != woothee::woothee::VALUE_UNKNOWN).then(|| value.to_string());
+        let class = match parsed.category {
+            "pc" => DeviceClass::Desktop,
+            "smartphone" | "mobilephone" => DeviceClass::Mobile,
+            "appliance" => DeviceClass::Appliance,
+            "crawler" => DeviceClass::Bot,
+            _ => DeviceClass::Unknown,
+        };
+        Self { browser: known(parsed.name), os: known(parsed.os), class }
+    }
+}
+
+
+impl Display for Agent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.browser, &self.os) {
+            (Some(browser), Some(os)) => write!(f, "{} on {}", browser, os),
+            (Some(name), None) | (None, Some(name)) => f.write_str(name),
+            (None, None) => f.write_str("Unknown device"),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_agents_are_described() {
+        let chrome = Agent::parse("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36");
+        assert_eq!((chrome.to_string(), chrome.class), (String::from("Chrome on Windows 10"), DeviceClass::Desktop));
+        let iphone = Agent::parse("Mozilla/5.0 (iPhone; CPU iPhone OS 17_1 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.1 Mobile/15E148 Safari/604.1");
+        assert_eq!((iphone.to_string(), iphone.class), (String::from("Safari on iPhone"), DeviceClass::Mobile));
+        assert_eq!(Agent::parse("Googlebot/2.1 (+http://www.google.com/bot.html)").class, DeviceClass::Bot);
+        assert_eq!(Agent::parse("").to_string(), "Unknown device");
+    }
+}
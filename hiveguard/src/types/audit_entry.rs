@@ -0,0 +1,47 @@
+use serde::{Serialize, Deserialize};
+use chrono::{Utc, DateTime};
+use super::Id;
+
+
+/// An entry of the audit log of an organisation: who did what to what, and when.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub id: Id,
+    pub org_id: Id,
+    /// the user who acted, if any. eg not for the changes of the maintenance jobs.
+    pub actor_id: Option<Id>,
+    /// eg `membership.added`
+    pub action: String,
+    /// what was acted on. eg the hex id of a user.
+    pub target: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+
+/// The formats audit logs are exported in.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditFormat {
+    /// a header line, then one line per entry.
+    Csv,
+    /// newline-delimited JSON, one entry per line.
+    Ndjson,
+}
+
+
+impl AuditEntry {
+    pub fn new(org_id: Id, actor_id: Option<Id>, action: impl Into<String>, target: Option<String>) -> Self {
+        Self { id: Id::new(), org_id, actor_id, action: action.into(), target, occurred_at: Utc::now() }
+    }
+}
+
+
+impl AuditFormat {
+    /// The media type of exports in the format.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            AuditFormat::Csv => "text/csv",
+            AuditFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+}
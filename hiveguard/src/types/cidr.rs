@@ -0,0 +1,123 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use super::ConversionError;
+use std::str::FromStr;
+
+
+/// A range of IP addresses in CIDR notation, eg `10.0.0.0/8` or `2001:db8::/32`. A bare address is a range of its own.
+///
+/// IPv4 addresses mapped to IPv6 (`::ffff:10.0.0.1`) are matched as the IPv4 address they map.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+
+impl Cidr {
+    /// Whether `ip` is within the range.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, canonical(ip)) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => mask(u32::from(ip).into(), 32, self.prefix) == u32::from(network) as u128,
+            (IpAddr::V6(network), IpAddr::V6(ip)) => mask(u128::from(ip), 128, self.prefix) == u128::from(network),
+            _ => false,
+        }
+    }
+}
+
+
+impl FromStr for Cidr {
+    type Err = ConversionError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || ConversionError::InvalidCidr(value.to_string());
+        let (address, prefix) = match value.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix.parse::<u8>().map_err(|_| invalid())?)),
+            None => (value, None),
+        };
+        let address = canonical(address.parse::<IpAddr>().map_err(|_| invalid())?);
+        let bits = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(bits);
+        if prefix > bits {
+            return Err(invalid());
+        }
+        // the bits past the prefix are dropped, so `10.1.2.3/8` is `10.0.0.0/8`.
+        let network = match address {
+            IpAddr::V4(ip) => IpAddr::V4(Ipv4Addr::from(mask(u32::from(ip).into(), 32, prefix) as u32)),
+            IpAddr::V6(ip) => IpAddr::V6(Ipv6Addr::from(mask(u128::from(ip), 128, prefix))),
+        };
+        Ok(Self { network, prefix })
+    }
+}
+
+
+impl TryFrom<String> for Cidr {
+    type Error = ConversionError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+
+impl From<Cidr> for String {
+    fn from(cidr: Cidr) -> Self {
+        cidr.to_string()
+    }
+}
+
+
+impl Display for Cidr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+
+/// `ip`, or the IPv4 address it maps.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(ip) => ip.to_ipv4_mapped().map_or(IpAddr::V6(ip), IpAddr::V4),
+        ip => ip,
+    }
+}
+
+
+/// The first `prefix` of the `bits` bits of `value`.
+fn mask(value: u128, bits: u8, prefix: u8) -> u128 {
+    if prefix == 0 {
+        return 0;
+    }
+    let mask = (u128::MAX << (128 - prefix)) >> (128 - bits);
+    value & mask
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn test_ranges_contain_their_addresses() {
+        let office = Cidr::from_str("10.1.2.3/8").unwrap();
+        assert_eq!(office.to_string(), "10.0.0.0/8");
+        assert!(office.contains(ip("10.255.0.1")) && office.contains(ip("::ffff:10.0.0.1")));
+        assert!(!office.contains(ip("11.0.0.1")) && !office.contains(ip("::1")));
+        assert!(Cidr::from_str("2001:db8::/32").unwrap().contains(ip("2001:db8:1::1")));
+        assert!(Cidr::from_str("0.0.0.0/0").unwrap().contains(ip("192.0.2.1")));
+        assert_eq!(Cidr::from_str("192.0.2.1").unwrap().to_string(), "192.0.2.1/32");
+    }
+
+    #[test]
+    fn test_invalid_ranges_are_rejected() {
+        for value in ["10.0.0.0/33", "10.0.0/8", "office", "::/129", "10.0.0.0/"] {
+            assert_eq!(Cidr::from_str(value), Err(ConversionError::InvalidCidr(value.to_string())));
+        }
+    }
+}
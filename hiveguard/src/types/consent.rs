@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Utc};
+
+
+/// A legal document users have to accept.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Document {
+    TermsOfService,
+    PrivacyPolicy,
+}
+
+
+/// The version of a document a user accepted, and when.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Consent {
+    pub version: String,
+    pub accepted_at: DateTime<Utc>,
+}
+
+
+/// The documents a user accepted. written through `Authentication::accept`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Consents {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terms_of_service: Option<Consent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub privacy_policy: Option<Consent>,
+}
+
+
+impl Document {
+    pub const ALL: [Document; 2] = [Document::TermsOfService, Document::PrivacyPolicy];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Document::TermsOfService => "terms_of_service",
+            Document::PrivacyPolicy => "privacy_policy",
+        }
+    }
+
+    /// The attribute the consent to the document is stored in.
+    pub fn attribute(&self) -> &'static str {
+        match self {
+            Document::TermsOfService => "terms_of_service_consent",
+            Document::PrivacyPolicy => "privacy_policy_consent",
+        }
+    }
+}
+
+
+impl Display for Document {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+
+impl Consent {
+    pub fn new(version: String) -> Self {
+        Self { version, accepted_at: Utc::now() }
+    }
+}
+
+
+impl Consents {
+    pub fn is_empty(&self) -> bool {
+        self.terms_of_service.is_none() && self.privacy_policy.is_none()
+    }
+
+    pub fn get(&self, document: Document) -> Option<&Consent> {
+        match document {
+            Document::TermsOfService => self.terms_of_service.as_ref(),
+            Document::PrivacyPolicy => self.privacy_policy.as_ref(),
+        }
+    }
+
+    pub fn get_mut(&mut self, document: Document) -> &mut Option<Consent> {
+        match document {
+            Document::TermsOfService => &mut self.terms_of_service,
+            Document::PrivacyPolicy => &mut self.privacy_policy,
+        }
+    }
+
+    /// The documents whose `current` version was not accepted. `current` returns `None` for the documents that are not required.
+    pub fn outdated(&self, current: impl Fn(Document) -> Option<String>) -> Vec<Document> {
+        Document::ALL.into_iter()
+            .filter(|document| current(*document).is_some_and(|version| self.get(*document).is_none_or(|consent| consent.version != version)))
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outdated_documents() {
+        let consents = Consents { terms_of_service: Some(Consent::new("2024-01".into())), privacy_policy: None };
+        let current = |version: &'static str| move |document| (document == Document::TermsOfService).then(|| String::from(version));
+        assert_eq!(consents.outdated(current("2024-01")), vec![]);
+        assert_eq!(consents.outdated(current("2025-06")), vec![Document::TermsOfService]);
+        assert_eq!(consents.outdated(|_| Some(String::from("2024-01"))), vec![Document::PrivacyPolicy]);
+    }
+}
@@ -0,0 +1,11 @@
+/// A user of an external directory, as it was when their credentials were checked.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DirectoryEntry {
+    /// the distinguished name of the entry. eg `uid=jane,ou=people,dc=example,dc=com`
+    pub dn: String,
+    /// the name the user logged in with.
+    pub username: String,
+    pub fullname: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+}
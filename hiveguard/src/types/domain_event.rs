@@ -0,0 +1,94 @@
+use serde::{Serialize, Deserialize};
+use chrono::{Utc, DateTime};
+use super::Id;
+
+
+/// Something that happened in hiveguard, published for analytics and for services keeping their own copy of users in sync.
+///
+/// Events are versioned: a change to the payload of an event that is not backward compatible bumps its `version`,
+/// so consumers can tell the payloads they understand apart.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DomainEvent {
+    pub id: Id,
+    #[serde(flatten)]
+    pub kind: DomainEventKind,
+    pub version: u32,
+    pub occurred_at: DateTime<Utc>,
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", content = "data")]
+pub enum DomainEventKind {
+    #[serde(rename = "user.created")]
+    UserCreated { user_id: Id, username: String },
+    #[serde(rename = "login.succeeded")]
+    LoginSucceeded { user_id: Id },
+    /// the user changed their username from `from` to `to`. the events of a user are the history of their usernames.
+    #[serde(rename = "user.renamed")]
+    UserRenamed { user_id: Id, from: String, to: String },
+    /// a guest started, without contacts. see `Guests`.
+    #[serde(rename = "guest.created")]
+    GuestCreated { user_id: Id, expires_at: DateTime<Utc> },
+    /// the guest became a full account, keeping its id.
+    #[serde(rename = "guest.upgraded")]
+    GuestUpgraded { user_id: Id, username: String },
+}
+
+
+impl DomainEvent {
+    pub fn new(kind: DomainEventKind) -> Self {
+        let version = kind.version();
+        Self { id: Id::default(), kind, version, occurred_at: Utc::now() }
+    }
+
+    /// the name of the event as it appears in the serialized `type` field. eg `user.created`
+    pub fn name(&self) -> &'static str {
+        match self.kind {
+            DomainEventKind::UserCreated { .. } => "user.created",
+            DomainEventKind::LoginSucceeded { .. } => "login.succeeded",
+            DomainEventKind::UserRenamed { .. } => "user.renamed",
+            DomainEventKind::GuestCreated { .. } => "guest.created",
+            DomainEventKind::GuestUpgraded { .. } => "guest.upgraded",
+        }
+    }
+}
+
+
+impl DomainEventKind {
+    /// the user the event is about. events of the same user are published in order.
+    pub fn user_id(&self) -> Id {
+        match self {
+            DomainEventKind::UserCreated { user_id, .. } | DomainEventKind::LoginSucceeded { user_id } | DomainEventKind::UserRenamed { user_id, .. } => *user_id,
+            DomainEventKind::GuestCreated { user_id, .. } | DomainEventKind::GuestUpgraded { user_id, .. } => *user_id,
+        }
+    }
+
+    /// the current version of the payload of the event.
+    pub fn version(&self) -> u32 {
+        match self {
+            DomainEventKind::UserCreated { .. } => 1,
+            DomainEventKind::LoginSucceeded { .. } => 1,
+            DomainEventKind::UserRenamed { .. } => 1,
+            DomainEventKind::GuestCreated { .. } => 1,
+            DomainEventKind::GuestUpgraded { .. } => 1,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialized_events_carry_their_type_and_version() {
+        let user_id = Id::try_from(String::from("000000000000000000000001")).unwrap();
+        let event = DomainEvent::new(DomainEventKind::UserCreated { user_id, username: "jane".into() });
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["type"], event.name());
+        assert_eq!(value["version"], 1);
+        assert_eq!(value["data"]["username"], "jane");
+        assert_eq!(serde_json::from_value::<DomainEvent>(value).unwrap(), event);
+    }
+}
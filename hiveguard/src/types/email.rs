@@ -4,6 +4,9 @@ use aws_sdk_dynamodb::types::AttributeValue;
 use std::fmt::{Display, Formatter};
 use std::collections::HashMap;
 use super::ConversionError;
+#[cfg(feature = "dynamodb")]
+use super::PiiCipher;
+use std::borrow::Cow;
 use lettre::Address;
 
 
@@ -16,7 +19,9 @@ pub enum Email {
 
 #[derive(Serialize, Deserialize, Default)]
 struct EmailData<'a> {
-    email: &'a str,
+    /// borrowed when it can be, owned when deserializing from a `serde_json::Value`.
+    #[serde(borrow)]
+    email: Cow<'a, str>,
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     email_verified: bool,
 }
@@ -26,11 +31,11 @@ impl<'a> From<&'a Email> for EmailData<'a> {
     fn from(email: &'a Email) -> Self {
         match email {
             Email::New(address) => EmailData {
-                email: address.as_ref(),
+                email: Cow::Borrowed(address.as_ref()),
                 email_verified: false,
             },
             Email::Verified(address) => EmailData {
-                email: address.as_ref(),
+                email: Cow::Borrowed(address.as_ref()),
                 email_verified: true,
             },
         }
@@ -60,6 +65,27 @@ impl TryFrom<&str> for Email {
     }
 }
 
+impl Email {
+    /// The domain of the address, as written. eg `acme.com`
+    pub fn domain(&self) -> &str {
+        match self {
+            Email::New(address) | Email::Verified(address) => address.domain(),
+        }
+    }
+
+    pub fn is_verified(&self) -> bool {
+        matches!(self, Email::Verified(_))
+    }
+
+    /// The address, verified.
+    pub fn verified(self) -> Self {
+        match self {
+            Email::New(address) | Email::Verified(address) => Email::Verified(address),
+        }
+    }
+}
+
+
 impl AsRef<str> for Email {
     fn as_ref(&self) -> &str {
         match self {
@@ -102,12 +128,34 @@ impl TryFrom<String> for Email {
 }
 
 
+/// The value the address is stored and looked up with, its blind index when a [`PiiCipher`] is installed.
+#[cfg(feature = "dynamodb")]
+impl From<Email> for AttributeValue {
+    fn from(email: Email) -> Self {
+        let address = EmailData::from(&email).email;
+        match PiiCipher::installed() {
+            Some(cipher) => AttributeValue::S(cipher.blind("email", &address)),
+            None => AttributeValue::S(address.into_owned()),
+        }
+    }
+}
+
+
+/// The address is sealed in `email_pii` when a [`PiiCipher`] is installed, `email` holding its blind index.
 #[cfg(feature = "dynamodb")]
 impl From<Email> for HashMap<String, AttributeValue> {
     fn from(email: Email) -> Self {
         let data = EmailData::from(&email);
         let mut map = HashMap::new();
-        map.insert("email".to_string(), AttributeValue::S(data.email.to_string()));
+        match PiiCipher::installed() {
+            Some(cipher) => {
+                map.insert("email".to_string(), AttributeValue::S(cipher.blind("email", &data.email)));
+                map.insert("email_pii".to_string(), AttributeValue::S(cipher.seal("email", &data.email)));
+            },
+            None => {
+                map.insert("email".to_string(), AttributeValue::S(data.email.to_string()));
+            },
+        }
         map.insert("email_verified".to_string(), AttributeValue::Bool(data.email_verified));
         map
     }
@@ -128,13 +176,19 @@ impl TryFrom<&mut HashMap<String, AttributeValue>> for Email {
 
     fn try_from(map: &mut HashMap<String, AttributeValue>) -> Result<Self, Self::Error> {
         let email = map.get("email").ok_or(ConversionError::MissingField("email"))?;
-        let email = email.as_s().map_err(|_| ConversionError::UnexpectedDataType("email"))?;
+        let email = email.as_s().map_err(|_| ConversionError::UnexpectedDataType("email"))?.clone();
+        // addresses stored before the cipher was installed are read in the clear.
+        let email = match map.remove("email_pii") {
+            Some(AttributeValue::S(envelope)) => PiiCipher::installed().ok_or(ConversionError::Undecryptable("email"))?.open("email", &envelope)?,
+            Some(_) => return Err(ConversionError::UnexpectedDataType("email_pii")),
+            None => email,
+        };
         let email_verified = match map.get("email_verified") {
             Some(value) => *value.as_bool().map_err(|_| ConversionError::UnexpectedDataType("email_verified"))?,
             None => false,
         };
         let email_data = EmailData {
-            email,
+            email: Cow::Owned(email),
             email_verified,
         };
         Email::try_from(email_data)
@@ -162,17 +216,24 @@ mod tests {
         assert_eq!(email.as_ref(), deserialized.as_ref());
     }
 
+    #[test]
+    fn test_email_deserialization_from_a_value() {
+        let value = serde_json::json!({"email": "user@example.com", "email_verified": true});
+        let email = serde_json::from_value::<Email>(value).unwrap();
+        assert_eq!(email, Email::Verified("user@example.com".parse().unwrap()));
+    }
+
     #[test]
     #[should_panic(expected = "InvalidEmailAddress")]
     fn test_email_from_invalid_email_data() {
-        let email_data = EmailData{email: "invalid-email", email_verified: false};
+        let email_data = EmailData{email: "invalid-email".into(), email_verified: false};
         Email::try_from(email_data).unwrap();
     }
 
     #[test]
     fn test_email_from_valid_email_data() {
         let email_str = "user@example.com";
-        let email_data = EmailData{email: email_str, email_verified: false};
+        let email_data = EmailData{email: email_str.into(), email_verified: false};
         let email = Email::try_from(email_data).unwrap();
         assert_eq!(email.as_ref(), email_str);
     }
@@ -0,0 +1,244 @@
+use super::{Error, DatabaseError, ConversionError, StorageError, SamlError};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+
+/// Declares the codes with, in order, their string, HTTP status, problem type and title.
+macro_rules! codes {
+    ($($variant:ident = $code:literal, $status:literal, $kind:literal, $title:literal;)*) => {
+        /// A stable, machine-readable code for every kind of error, so that clients branch on it rather than on messages.
+        ///
+        /// Codes are never renamed nor reused. A code is prefixed by the area it belongs to: `AUTH` for authentication,
+        /// `USR`, `SES` and `VER` for users, sessions and verifications, `ORG` for organisations, `REQ` for invalid requests, `UPL` for uploads,
+        /// `DB` for the database and `SYS` for the service itself.
+        #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum ErrorCode {
+            $(#[serde(rename = $code)] $variant,)*
+        }
+
+
+        impl ErrorCode {
+            pub const ALL: &[ErrorCode] = &[$(ErrorCode::$variant,)*];
+
+            /// eg `AUTH_001_INVALID_CREDENTIALS`
+            pub fn as_str(self) -> &'static str {
+                match self { $(ErrorCode::$variant => $code,)* }
+            }
+
+            /// The HTTP status code of responses failing with this code.
+            pub fn status(self) -> u16 {
+                match self { $(ErrorCode::$variant => $status,)* }
+            }
+
+            /// The last part of the `type` of the `Problem` of this code. eg `invalid-credentials`
+            pub fn kind(self) -> &'static str {
+                match self { $(ErrorCode::$variant => $kind,)* }
+            }
+
+            pub fn title(self) -> &'static str {
+                match self { $(ErrorCode::$variant => $title,)* }
+            }
+        }
+    };
+}
+
+
+codes! {
+    InvalidCredentials = "AUTH_001_INVALID_CREDENTIALS", 401, "invalid-credentials", "Invalid credentials";
+    SessionExpired = "AUTH_002_SESSION_EXPIRED", 401, "session-expired", "Session expired";
+    AccountDisabled = "AUTH_003_ACCOUNT_DISABLED", 403, "account-disabled", "Account disabled";
+    CsrfMismatch = "AUTH_004_CSRF_MISMATCH", 403, "csrf-mismatch", "CSRF token mismatch";
+    ConsentRequired = "AUTH_005_CONSENT_REQUIRED", 403, "consent-required", "Consent required";
+    SamlUnsuccessful = "AUTH_006_SAML_UNSUCCESSFUL", 401, "saml-unsuccessful", "Not authenticated by the identity provider";
+    InvalidSamlMessage = "AUTH_007_INVALID_SAML_MESSAGE", 400, "invalid-saml-message", "Invalid SAML message";
+    StepUpRequired = "AUTH_008_STEP_UP_REQUIRED", 401, "step-up-required", "Step-up authentication required";
+    VerificationRequired = "AUTH_009_VERIFICATION_REQUIRED", 403, "verification-required", "Verification required";
+    PasswordResetRequired = "AUTH_010_PASSWORD_RESET_REQUIRED", 403, "password-reset-required", "Password reset required";
+    UnknownService = "AUTH_011_UNKNOWN_SERVICE", 401, "unknown-service", "Unknown service";
+    ServiceNotTrusted = "AUTH_012_SERVICE_NOT_TRUSTED", 403, "service-not-trusted", "Service not trusted";
+    InsufficientScope = "AUTH_013_INSUFFICIENT_SCOPE", 403, "insufficient-scope", "Insufficient scope";
+    UserNotFound = "USR_001_NOT_FOUND", 404, "user-not-found", "User not found";
+    InvalidUsername = "USR_002_INVALID_USERNAME", 400, "invalid-username", "Invalid username";
+    InvalidStatusTransition = "USR_003_INVALID_STATUS_TRANSITION", 409, "invalid-status-transition", "Invalid status transition";
+    SignupClosed = "USR_004_SIGNUP_CLOSED", 403, "signup-closed", "Signup closed";
+    EmailDomainNotAllowed = "USR_005_EMAIL_DOMAIN_NOT_ALLOWED", 403, "email-domain-not-allowed", "Email domain not allowed";
+    DisposableEmail = "USR_006_DISPOSABLE_EMAIL", 400, "disposable-email", "Disposable email address";
+    InvalidMerge = "USR_007_INVALID_MERGE", 409, "invalid-merge", "Invalid merge";
+    UsernameChangeTooSoon = "USR_008_USERNAME_CHANGE_TOO_SOON", 429, "username-change-too-soon", "Username changed too recently";
+    GuestsDisabled = "USR_009_GUESTS_DISABLED", 403, "guests-disabled", "Guest accounts disabled";
+    NotAGuest = "USR_010_NOT_A_GUEST", 409, "not-a-guest", "Not a guest";
+    SessionNotFound = "SES_001_NOT_FOUND", 404, "session-not-found", "Session not found";
+    VerificationNotFound = "VER_001_NOT_FOUND", 404, "verification-not-found", "Verification not found";
+    OrganisationNotFound = "ORG_001_NOT_FOUND", 404, "organisation-not-found", "Organisation not found";
+    InvalidHierarchy = "ORG_002_INVALID_HIERARCHY", 409, "invalid-hierarchy", "Invalid organisation hierarchy";
+    OwnerRequired = "ORG_003_OWNER_REQUIRED", 403, "owner-required", "Organisation owner required";
+    KeyNotFound = "KEY_001_NOT_FOUND", 404, "key-not-found", "Key not found";
+    InvalidKeyChange = "KEY_002_INVALID_CHANGE", 409, "invalid-key-change", "Invalid key change";
+    UnknownTenant = "TEN_001_UNKNOWN", 404, "unknown-tenant", "Unknown tenant";
+    InvalidRequest = "REQ_001_INVALID", 400, "invalid-request", "Invalid request";
+    InvalidId = "REQ_002_INVALID_ID", 400, "invalid-id", "Invalid id";
+    InvalidPatch = "REQ_003_INVALID_PATCH", 400, "invalid-patch", "Invalid patch";
+    PatchTestFailed = "REQ_004_PATCH_TEST_FAILED", 409, "patch-test-failed", "Patch test failed";
+    PreconditionFailed = "REQ_005_PRECONDITION_FAILED", 412, "precondition-failed", "Precondition failed";
+    UnsupportedOAuthProvider = "REQ_006_UNSUPPORTED_OAUTH_PROVIDER", 400, "unsupported-oauth-provider", "Unsupported OAuth provider";
+    AddressNotAllowed = "REQ_007_ADDRESS_NOT_ALLOWED", 403, "address-not-allowed", "Address not allowed";
+    CountryNotAllowed = "REQ_008_COUNTRY_NOT_ALLOWED", 403, "country-not-allowed", "Country not allowed";
+    InvalidRedirectUri = "REQ_009_INVALID_REDIRECT_URI", 400, "invalid-redirect-uri", "Invalid redirect URI";
+    InvalidScope = "REQ_010_INVALID_SCOPE", 400, "invalid-scope", "Invalid scope";
+    PayloadTooLarge = "REQ_011_PAYLOAD_TOO_LARGE", 413, "payload-too-large", "Payload too large";
+    UnsupportedGrantType = "REQ_012_UNSUPPORTED_GRANT_TYPE", 400, "unsupported-grant-type", "Unsupported grant type";
+    UnsupportedTokenType = "REQ_013_UNSUPPORTED_TOKEN_TYPE", 400, "unsupported-token-type", "Unsupported token type";
+    InvalidTarget = "REQ_014_INVALID_TARGET", 400, "invalid-target", "Invalid target";
+    UnsupportedContentType = "UPL_001_UNSUPPORTED_CONTENT_TYPE", 415, "unsupported-content-type", "Unsupported content type";
+    TooLarge = "UPL_002_TOO_LARGE", 413, "too-large", "Too large";
+    UploadNotFound = "UPL_003_NOT_FOUND", 404, "upload-not-found", "Upload not found";
+    AlreadyExists = "DB_001_ALREADY_EXISTS", 409, "already-exists", "Already exists";
+    DatabaseUnavailable = "DB_002_UNAVAILABLE", 503, "database-unavailable", "Database unavailable";
+    Internal = "SYS_001_INTERNAL", 500, "internal", "Internal error";
+    ProviderUnavailable = "SYS_002_PROVIDER_UNAVAILABLE", 503, "provider-unavailable", "Provider unavailable";
+}
+
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+
+impl Error {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::ConversionError(err) => err.code(),
+            Error::DatabaseError(err) => err.code(),
+            Error::StorageError(err) => err.code(),
+            Error::SamlError(err) => err.code(),
+            Error::HashError(_) | Error::Io(_) => ErrorCode::Internal,
+            // a wrong password reads as an unknown user.
+            Error::InvalidCredentials | Error::WrongPassword => ErrorCode::InvalidCredentials,
+            Error::ProviderUnavailable(_) => ErrorCode::ProviderUnavailable,
+            Error::InvalidUsername(_) => ErrorCode::InvalidUsername,
+            Error::AccountDisabled(_) => ErrorCode::AccountDisabled,
+            Error::InvalidStatusTransition(..) => ErrorCode::InvalidStatusTransition,
+            Error::SessionExpired => ErrorCode::SessionExpired,
+            Error::CsrfMismatch => ErrorCode::CsrfMismatch,
+            Error::ConsentRequired(_) => ErrorCode::ConsentRequired,
+            Error::StepUpRequired => ErrorCode::StepUpRequired,
+            Error::VerificationRequired => ErrorCode::VerificationRequired,
+            Error::PasswordResetRequired => ErrorCode::PasswordResetRequired,
+            Error::PreconditionFailed => ErrorCode::PreconditionFailed,
+            Error::UnknownTenant(_) => ErrorCode::UnknownTenant,
+            Error::AddressNotAllowed(_) => ErrorCode::AddressNotAllowed,
+            Error::CountryNotAllowed(_) => ErrorCode::CountryNotAllowed,
+            Error::SignupClosed => ErrorCode::SignupClosed,
+            Error::EmailDomainNotAllowed(_) => ErrorCode::EmailDomainNotAllowed,
+            Error::DisposableEmail(_) => ErrorCode::DisposableEmail,
+            Error::InvalidMerge(_) => ErrorCode::InvalidMerge,
+            Error::UsernameChangeTooSoon(_) => ErrorCode::UsernameChangeTooSoon,
+            Error::GuestsDisabled => ErrorCode::GuestsDisabled,
+            Error::NotAGuest => ErrorCode::NotAGuest,
+            Error::InvalidHierarchy(_) => ErrorCode::InvalidHierarchy,
+            Error::OwnerRequired => ErrorCode::OwnerRequired,
+            Error::InvalidRedirectUri(_) => ErrorCode::InvalidRedirectUri,
+            Error::InvalidScope(_) => ErrorCode::InvalidScope,
+            Error::UnknownService(_) => ErrorCode::UnknownService,
+            Error::ServiceNotTrusted(_) => ErrorCode::ServiceNotTrusted,
+            Error::InsufficientScope(_) => ErrorCode::InsufficientScope,
+            Error::InvalidKeyChange(_) => ErrorCode::InvalidKeyChange,
+            Error::PayloadTooLarge(_) => ErrorCode::PayloadTooLarge,
+            Error::UnsupportedGrantType(_) => ErrorCode::UnsupportedGrantType,
+            Error::UnsupportedTokenType(_) => ErrorCode::UnsupportedTokenType,
+            Error::InvalidTarget(_) => ErrorCode::InvalidTarget,
+        }
+    }
+}
+
+
+impl DatabaseError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            DatabaseError::UserNotFound => ErrorCode::UserNotFound,
+            DatabaseError::SessionNotFound => ErrorCode::SessionNotFound,
+            DatabaseError::VerificationNotFound => ErrorCode::VerificationNotFound,
+            DatabaseError::OrganisationNotFound => ErrorCode::OrganisationNotFound,
+            DatabaseError::KeyNotFound => ErrorCode::KeyNotFound,
+            DatabaseError::AlreadyExists => ErrorCode::AlreadyExists,
+            DatabaseError::Changed => ErrorCode::PreconditionFailed,
+            DatabaseError::Unavailable => ErrorCode::DatabaseUnavailable,
+            // items the database cannot read back are a fault of the service.
+            DatabaseError::ConversionError(_) | DatabaseError::Internal(_) => ErrorCode::Internal,
+        }
+    }
+}
+
+
+impl ConversionError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ConversionError::CouldNotConvertBlobToID | ConversionError::CouldNotConvertStringToID => ErrorCode::InvalidId,
+            ConversionError::UnsupportedOAuthProvider(_) => ErrorCode::UnsupportedOAuthProvider,
+            ConversionError::InvalidPatch(_) => ErrorCode::InvalidPatch,
+            ConversionError::PatchTestFailed(_) => ErrorCode::PatchTestFailed,
+            ConversionError::UnexpectedDataType(_) | ConversionError::MissingField(_) | ConversionError::MissingFields(_) => ErrorCode::InvalidRequest,
+            ConversionError::UnknownField(_) | ConversionError::ImmutableField(_) | ConversionError::InvalidEmailAddress => ErrorCode::InvalidRequest,
+            ConversionError::InvalidPhoneNumber | ConversionError::InvalidMetadataKey(_) | ConversionError::MetadataTooLarge(_) => ErrorCode::InvalidRequest,
+            ConversionError::InvalidTenant(_) | ConversionError::InvalidCidr(_) | ConversionError::InvalidFields(_) => ErrorCode::InvalidRequest,
+            ConversionError::TooDeep(_) | ConversionError::ArrayTooLong(_) => ErrorCode::InvalidRequest,
+            ConversionError::Undecryptable(_) => ErrorCode::Internal,
+        }
+    }
+}
+
+
+impl StorageError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            StorageError::UnsupportedContentType(_) => ErrorCode::UnsupportedContentType,
+            StorageError::TooLarge(_) => ErrorCode::TooLarge,
+            StorageError::NotFound(_) => ErrorCode::UploadNotFound,
+            StorageError::Internal(_) => ErrorCode::Internal,
+        }
+    }
+}
+
+
+impl SamlError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            SamlError::InvalidKey(_) => ErrorCode::Internal,
+            SamlError::Unsuccessful(_) => ErrorCode::SamlUnsuccessful,
+            _ => ErrorCode::InvalidSamlMessage,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_codes_are_unique() {
+        let codes = ErrorCode::ALL.iter().map(|code| code.as_str()).collect::<HashSet<_>>();
+        let kinds = ErrorCode::ALL.iter().map(|code| code.kind()).collect::<HashSet<_>>();
+        assert_eq!((codes.len(), kinds.len()), (ErrorCode::ALL.len(), ErrorCode::ALL.len()));
+        assert_eq!(serde_json::to_value(ErrorCode::InvalidCredentials).unwrap(), "AUTH_001_INVALID_CREDENTIALS");
+        assert_eq!(serde_json::from_value::<ErrorCode>("USR_001_NOT_FOUND".into()).unwrap(), ErrorCode::UserNotFound);
+    }
+
+    #[test]
+    fn test_internal_errors_have_the_internal_code() {
+        let errors = [
+            Error::DatabaseError(DatabaseError::Internal("timeout".into())),
+            Error::DatabaseError(DatabaseError::ConversionError(ConversionError::MissingField("id"))),
+            Error::StorageError(StorageError::Internal("timeout".into())),
+            Error::SamlError(SamlError::InvalidKey(String::from("not a key"))),
+        ];
+        for err in errors {
+            assert!(err.is_internal());
+            assert_eq!(err.code(), ErrorCode::Internal);
+        }
+        assert_eq!(Error::WrongPassword.code(), ErrorCode::InvalidCredentials);
+    }
+}
@@ -0,0 +1,86 @@
+use std::fmt::{Display, Formatter};
+use std::error::Error as StdError;
+
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(String),
+    UnsupportedFormat(String),
+    InvalidOverride(String),
+    UnknownProfile(String),
+    Invalid(Vec<ConfigIssue>),
+}
+
+
+/// A single problem with a configuration field. `field` is the dotted path of the field. eg `tokens.key`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+    pub field: String,
+    pub message: String,
+}
+
+
+impl ConfigIssue {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { field: field.into(), message: message.into() }
+    }
+}
+
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "could not read the configuration file: {}", err),
+            ConfigError::Parse(err) => write!(f, "invalid configuration: {}", err),
+            ConfigError::UnsupportedFormat(path) => write!(f, "unsupported configuration format: {}. expected a .json, .toml, .yaml or .yml file", path),
+            ConfigError::InvalidOverride(var) => write!(f, "environment variable {} does not point to a configuration field", var),
+            ConfigError::UnknownProfile(profile) => write!(f, "the configuration has no profile named {}", profile),
+            ConfigError::Invalid(issues) => {
+                write!(f, "invalid configuration:")?;
+                for issue in issues {
+                    write!(f, "\n  {}", issue)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+
+impl Display for ConfigIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+
+impl StdError for ConfigError {}
+
+
+impl From<std::io::Error> for ConfigError {
+    fn from(err: std::io::Error) -> Self {
+        ConfigError::Io(err)
+    }
+}
+
+
+impl From<serde_json::Error> for ConfigError {
+    fn from(err: serde_json::Error) -> Self {
+        ConfigError::Parse(err.to_string())
+    }
+}
+
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(err: toml::de::Error) -> Self {
+        ConfigError::Parse(err.to_string())
+    }
+}
+
+
+impl From<serde_yaml::Error> for ConfigError {
+    fn from(err: serde_yaml::Error) -> Self {
+        ConfigError::Parse(err.to_string())
+    }
+}
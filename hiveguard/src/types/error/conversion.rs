@@ -1,5 +1,6 @@
 use std::fmt::{Display, Formatter};
 use std::error::Error as StdError;
+use crate::types::FieldError;
 
 
 #[derive(Debug, PartialEq)]
@@ -12,6 +13,30 @@ pub enum ConversionError {
     UnsupportedOAuthProvider(String),
     InvalidEmailAddress,
     InvalidPhoneNumber,
+    /// a patch sets a field the item does not have.
+    UnknownField(String),
+    /// a patch sets a field that cannot change once the item is created.
+    ImmutableField(&'static str),
+    /// a metadata key is empty, too long or holds characters other than letters, digits, `_` and `-`.
+    InvalidMetadataKey(String),
+    /// a metadata namespace would take more than this many bytes.
+    MetadataTooLarge(usize),
+    /// a JSON Patch operation addresses a path it cannot. eg a nested field, or one that does not exist.
+    InvalidPatch(String),
+    /// the `test` operation of a JSON Patch failed at this path.
+    PatchTestFailed(String),
+    /// a tenant id is empty, too long or holds characters other than lowercase letters, digits and `-`.
+    InvalidTenant(String),
+    /// a range of IP addresses is neither an address nor an address and a prefix length. eg `10.0.0.0/8`
+    InvalidCidr(String),
+    /// an encrypted field could not be decrypted. eg its key encryption key is no longer configured.
+    Undecryptable(&'static str),
+    /// the fields of a request payload that are invalid, all of them. see `Validator`.
+    InvalidFields(Vec<FieldError>),
+    /// a JSON body nests arrays and objects deeper than this.
+    TooDeep(usize),
+    /// an array of a JSON body has more items than this.
+    ArrayTooLong(usize),
 }
 
 
@@ -26,6 +51,18 @@ impl Display for ConversionError {
             ConversionError::UnsupportedOAuthProvider(provider) => write!(f, "unsupported OAuth provider: {}", provider),
             ConversionError::InvalidEmailAddress => write!(f, "Invalid email address"),
             ConversionError::InvalidPhoneNumber => write!(f, "Invalid phone number"),
+            ConversionError::UnknownField(field) => write!(f, "unknown field: {}", field),
+            ConversionError::ImmutableField(field) => write!(f, "field cannot be changed: {}", field),
+            ConversionError::InvalidMetadataKey(key) => write!(f, "invalid metadata key: {:?}", key),
+            ConversionError::MetadataTooLarge(size) => write!(f, "metadata must not take more than {} bytes", size),
+            ConversionError::InvalidPatch(reason) => write!(f, "invalid patch: {}", reason),
+            ConversionError::PatchTestFailed(path) => write!(f, "the test of {} failed", path),
+            ConversionError::InvalidTenant(id) => write!(f, "invalid tenant id: {:?}", id),
+            ConversionError::InvalidCidr(value) => write!(f, "invalid IP address range: {:?}", value),
+            ConversionError::Undecryptable(field) => write!(f, "could not decrypt field: {}", field),
+            ConversionError::TooDeep(depth) => write!(f, "arrays and objects must not be nested more than {} deep", depth),
+            ConversionError::ArrayTooLong(length) => write!(f, "arrays must not have more than {} items", length),
+            ConversionError::InvalidFields(errors) => write!(f, "invalid fields: {}", errors.iter().map(|error| error.field.as_str()).collect::<Vec<_>>().join(", ")),
         }
     }
 }
@@ -1,5 +1,5 @@
 #[cfg(feature = "dynamodb")]
-use aws_sdk_dynamodb::error::{SdkError, BuildError};
+use aws_sdk_dynamodb::error::{SdkError, BuildError, ProvideErrorMetadata};
 use std::fmt::{Display, Formatter};
 use std::error::Error as StdError;
 use super::ConversionError;
@@ -10,7 +10,15 @@ pub enum DatabaseError {
     UserNotFound,
     SessionNotFound,
     VerificationNotFound,
+    OrganisationNotFound,
+    KeyNotFound,
+    /// an item with the same key already exists.
+    AlreadyExists,
+    /// the item changed since it was read, failing a write conditioned on it.
+    Changed,
     ConversionError(ConversionError),
+    /// the database cannot take the call for now: it throttled or timed out the call, retries included, or it is full.
+    Unavailable,
     Internal(Box<dyn StdError + Send + Sync>)
 }
 
@@ -21,7 +29,12 @@ impl Display for DatabaseError {
             DatabaseError::UserNotFound => write!(f, "user not found"),
             DatabaseError::SessionNotFound => write!(f, "session not found"),
             DatabaseError::VerificationNotFound => write!(f, "verification not found"),
+            DatabaseError::OrganisationNotFound => write!(f, "organisation not found"),
+            DatabaseError::KeyNotFound => write!(f, "key not found"),
+            DatabaseError::AlreadyExists => write!(f, "already exists"),
+            DatabaseError::Changed => write!(f, "changed since it was read"),
             DatabaseError::ConversionError(err) => write!(f, "conversion error: {}", err),
+            DatabaseError::Unavailable => write!(f, "the database is unavailable"),
             DatabaseError::Internal(err) => write!(f, "internal error: {}", err)
         }
     }
@@ -34,19 +47,35 @@ impl StdError for DatabaseError {}
 impl PartialEq for DatabaseError {
     fn eq(&self, other: &Self) -> bool {
         match self {
-            DatabaseError::UserNotFound => match other {DatabaseError::UserNotFound => true, _ => false},
-            DatabaseError::SessionNotFound => match other {DatabaseError::SessionNotFound => true, _ => false},
-            DatabaseError::VerificationNotFound => match other {DatabaseError::VerificationNotFound => true, _ => false},
+            DatabaseError::UserNotFound => matches!(other, DatabaseError::UserNotFound),
+            DatabaseError::SessionNotFound => matches!(other, DatabaseError::SessionNotFound),
+            DatabaseError::VerificationNotFound => matches!(other, DatabaseError::VerificationNotFound),
+            DatabaseError::OrganisationNotFound => matches!(other, DatabaseError::OrganisationNotFound),
+            DatabaseError::KeyNotFound => matches!(other, DatabaseError::KeyNotFound),
+            DatabaseError::AlreadyExists => matches!(other, DatabaseError::AlreadyExists),
+            DatabaseError::Changed => matches!(other, DatabaseError::Changed),
             DatabaseError::ConversionError(err) => match other {DatabaseError::ConversionError(other_err) => err == other_err, _ => false},
+            DatabaseError::Unavailable => matches!(other, DatabaseError::Unavailable),
             DatabaseError::Internal(err) => match other {DatabaseError::Internal(other_err) => err.to_string() == other_err.to_string(), _ => false},
         }
     }
 }
 
 #[cfg(feature = "dynamodb")]
-impl<E: StdError + 'static + Send + Sync, R: std::fmt::Debug + Send + Sync + 'static> From<SdkError<E, R>> for DatabaseError {
+impl<E: StdError + ProvideErrorMetadata + 'static + Send + Sync, R: std::fmt::Debug + Send + Sync + 'static> From<SdkError<E, R>> for DatabaseError {
+    /// A failed `attribute_not_exists` condition means the item already exists,
+    /// also when it cancels a transaction, like one claiming a unique value that is taken.
+    /// Calls still throttled once the client gave up retrying them, timed out or not sent at all mean the database is unavailable.
     fn from(err: SdkError<E, R>) -> Self {
-        DatabaseError::Internal(Box::new(err))
+        if matches!(err, SdkError::TimeoutError(_) | SdkError::DispatchFailure(_)) {
+            return DatabaseError::Unavailable;
+        }
+        match err.code() {
+            Some("ConditionalCheckFailedException") => DatabaseError::AlreadyExists,
+            Some("ProvisionedThroughputExceededException" | "ThrottlingException" | "RequestLimitExceeded") => DatabaseError::Unavailable,
+            Some("TransactionCanceledException") if err.message().is_some_and(|message| message.contains("ConditionalCheckFailed")) => DatabaseError::AlreadyExists,
+            _ => DatabaseError::Internal(Box::new(err)),
+        }
     }
 }
 
@@ -58,6 +87,18 @@ impl From<BuildError> for DatabaseError {
 }
 
 
+#[cfg(feature = "redis")]
+impl From<redis::RedisError> for DatabaseError {
+    /// Timeouts and lost connections mean the database is unavailable.
+    fn from(err: redis::RedisError) -> Self {
+        if err.is_timeout() || err.is_connection_dropped() || err.is_connection_refusal() {
+            return DatabaseError::Unavailable;
+        }
+        DatabaseError::Internal(Box::new(err))
+    }
+}
+
+
 impl From<ConversionError> for DatabaseError {
     fn from(err: ConversionError) -> Self {
         DatabaseError::ConversionError(err)
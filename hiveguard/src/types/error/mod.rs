@@ -1,19 +1,103 @@
 pub use password_hash::errors::Error as HashError;
+pub use provider::ProviderUnavailable;
+pub use username::UsernameError;
 pub use conversion::ConversionError;
+pub use config::{ConfigError, ConfigIssue};
+pub use secret::SecretError;
+pub use storage::StorageError;
+pub use saml::SamlError;
 use std::fmt::{Display, Formatter};
 use std::error::Error as StdError;
+use super::{Document, Status};
 pub use db::DatabaseError;
+pub use code::ErrorCode;
 
 mod db;
+mod config;
+mod secret;
+mod storage;
+mod saml;
 mod conversion;
+mod provider;
+mod username;
+mod code;
 
 #[derive(Debug, PartialEq)]
 pub enum Error {
     ConversionError(ConversionError),
     DatabaseError(DatabaseError),
     HashError(HashError),
+    /// writing a response failed, usually because the client went away.
+    Io(std::io::ErrorKind),
     InvalidCredentials,
     WrongPassword,
+    /// the provider a code or message would be sent through is unavailable.
+    ProviderUnavailable(&'static str),
+    InvalidUsername(UsernameError),
+    StorageError(StorageError),
+    SamlError(SamlError),
+    /// the account is suspended or deactivated.
+    AccountDisabled(Status),
+    /// the account cannot go from the first status to the second.
+    InvalidStatusTransition(Status, Status),
+    /// the session went unused for too long or outlived its maximum lifetime.
+    SessionExpired,
+    /// a request authenticated by cookies did not repeat its CSRF token.
+    CsrfMismatch,
+    /// the user has to accept the current version of these documents before logging in.
+    ConsentRequired(Vec<Document>),
+    /// the login looks anomalous, and has to be confirmed with a second factor.
+    StepUpRequired,
+    /// the account has to verify a contact before logging in.
+    VerificationRequired,
+    /// the password has to be reset before logging in with it.
+    PasswordResetRequired,
+    /// the item changed since the client read it, its `If-Match` naming another version.
+    PreconditionFailed,
+    /// no tenant is served at the host of the request, and its token names none.
+    UnknownTenant(String),
+    /// the network ACL of the route group refuses requests from this address.
+    AddressNotAllowed(std::net::IpAddr),
+    /// geo-blocking refuses requests from this country, or from unknown countries when it is `None`.
+    CountryNotAllowed(Option<String>),
+    /// signing up is invite-only.
+    SignupClosed,
+    /// signing up is not open to this email domain.
+    EmailDomainNotAllowed(String),
+    /// the email address is at this disposable provider.
+    DisposableEmail(String),
+    /// the accounts cannot be merged, for this reason.
+    InvalidMerge(String),
+    /// the username was changed too recently to change again before this time.
+    UsernameChangeTooSoon(chrono::DateTime<chrono::Utc>),
+    /// guest accounts are not enabled.
+    GuestsDisabled,
+    /// the user is not a guest, so it has nothing to upgrade from.
+    NotAGuest,
+    /// the organisations cannot be arranged this way, for this reason. eg an organisation under one of its own.
+    InvalidHierarchy(String),
+    /// the redirect uri is not registered for the service, or could not be registered under the redirect uri policy.
+    InvalidRedirectUri(String),
+    /// the service may not request this scope.
+    InvalidScope(String),
+    /// no service is registered with this client id.
+    UnknownService(String),
+    /// what the service asked for is kept to first-party services.
+    ServiceNotTrusted(&'static str),
+    /// the token was not issued for this scope, which the resource requires.
+    InsufficientScope(String),
+    /// the signing key cannot be changed this way, for this reason. eg retiring the active key.
+    InvalidKeyChange(&'static str),
+    /// the body of the request is larger than this many bytes, the most its route takes.
+    PayloadTooLarge(usize),
+    /// the token endpoint has no grant of this `grant_type`.
+    UnsupportedGrantType(String),
+    /// tokens of this type cannot be exchanged, or exchanged for.
+    UnsupportedTokenType(String),
+    /// the service may not get tokens for this audience by token exchange, or no service is registered as it.
+    InvalidTarget(String),
+    /// only the owners of the organisation may do this.
+    OwnerRequired,
 }
 
 
@@ -23,8 +107,48 @@ impl Display for Error {
             Error::ConversionError(err) => write!(f, "conversion error: {}", err),
             Error::DatabaseError(err) => write!(f, "database error: {}", err),
             Error::HashError(err) => write!(f, "hash error: {}", err),
+            Error::Io(kind) => write!(f, "io error: {}", kind),
             Error::InvalidCredentials => write!(f, "invalid credentials"),
             Error::WrongPassword => write!(f, "wrong password"),
+            Error::ProviderUnavailable(provider) => write!(f, "{} is unavailable", provider),
+            Error::InvalidUsername(err) => write!(f, "{}", err),
+            Error::StorageError(err) => write!(f, "storage error: {}", err),
+            Error::SamlError(err) => write!(f, "{}", err),
+            Error::AccountDisabled(status) => write!(f, "the account is {}", status),
+            Error::InvalidStatusTransition(from, to) => write!(f, "the account cannot go from {} to {}", from, to),
+            Error::SessionExpired => write!(f, "the session has expired"),
+            Error::CsrfMismatch => write!(f, "the CSRF token is missing or does not match"),
+            Error::StepUpRequired => write!(f, "the login has to be confirmed with a second factor"),
+            Error::VerificationRequired => write!(f, "a contact has to be verified before logging in"),
+            Error::PasswordResetRequired => write!(f, "the password has to be reset before logging in"),
+            Error::ConsentRequired(documents) => {
+                let documents = documents.iter().map(Document::as_str).collect::<Vec<_>>();
+                write!(f, "the current {} must be accepted", documents.join(" and "))
+            },
+            Error::PreconditionFailed => write!(f, "the item changed since it was read"),
+            Error::UnknownTenant(host) => write!(f, "no tenant is served at {}", host),
+            Error::AddressNotAllowed(ip) => write!(f, "requests from {} are not allowed", ip),
+            Error::CountryNotAllowed(Some(country)) => write!(f, "requests from {} are not allowed", country),
+            Error::CountryNotAllowed(None) => write!(f, "requests from unknown countries are not allowed"),
+            Error::SignupClosed => write!(f, "signing up is by invitation only"),
+            Error::EmailDomainNotAllowed(domain) => write!(f, "signing up with an email address at {} is not allowed", domain),
+            Error::DisposableEmail(domain) => write!(f, "{} is a disposable email provider", domain),
+            Error::InvalidMerge(reason) => write!(f, "the accounts cannot be merged: {}", reason),
+            Error::UsernameChangeTooSoon(at) => write!(f, "the username can only be changed again at {}", at.to_rfc3339()),
+            Error::GuestsDisabled => write!(f, "guest accounts are not enabled"),
+            Error::NotAGuest => write!(f, "the user is not a guest"),
+            Error::InvalidHierarchy(reason) => write!(f, "the organisations cannot be arranged this way: {}", reason),
+            Error::InvalidRedirectUri(reason) => write!(f, "invalid redirect uri: {}", reason),
+            Error::InvalidScope(scope) => write!(f, "the service may not request the {} scope", scope),
+            Error::UnknownService(client_id) => write!(f, "no service is registered as {}", client_id),
+            Error::ServiceNotTrusted(what) => write!(f, "only first-party services may use {}", what),
+            Error::InsufficientScope(scope) => write!(f, "the token lacks the {} scope", scope),
+            Error::InvalidKeyChange(reason) => write!(f, "the key cannot be changed: {}", reason),
+            Error::PayloadTooLarge(limit) => write!(f, "the request body must not be larger than {} bytes", limit),
+            Error::UnsupportedGrantType(grant_type) => write!(f, "the {} grant is not supported", grant_type),
+            Error::UnsupportedTokenType(token_type) => write!(f, "tokens of type {} cannot be exchanged", token_type),
+            Error::InvalidTarget(audience) => write!(f, "the token cannot be exchanged for a token of {}", audience),
+            Error::OwnerRequired => write!(f, "only the owners of the organisation may do this"),
         }
     }
 }
@@ -33,6 +157,26 @@ impl Display for Error {
 impl StdError for Error{}
 
 
+impl Error {
+    /// Whether the error is a fault of the service rather than of the request, and should be reported.
+    pub fn is_internal(&self) -> bool {
+        match self {
+            Error::DatabaseError(err) => matches!(err, DatabaseError::Internal(_) | DatabaseError::ConversionError(_)),
+            Error::HashError(_) => true,
+            Error::StorageError(err) => matches!(err, StorageError::Internal(_)),
+            Error::SamlError(err) => matches!(err, SamlError::InvalidKey(_)),
+            Error::ConversionError(err) => matches!(err, ConversionError::Undecryptable(_)),
+            Error::Io(_) | Error::InvalidCredentials | Error::WrongPassword | Error::ProviderUnavailable(_) | Error::InvalidUsername(_) => false,
+            Error::AccountDisabled(_) | Error::InvalidStatusTransition(..) | Error::SessionExpired | Error::CsrfMismatch | Error::ConsentRequired(_) | Error::StepUpRequired | Error::VerificationRequired | Error::PasswordResetRequired | Error::PreconditionFailed => false,
+            Error::UnknownTenant(_) | Error::AddressNotAllowed(_) | Error::CountryNotAllowed(_) | Error::SignupClosed | Error::EmailDomainNotAllowed(_) => false,
+            Error::DisposableEmail(_) | Error::InvalidMerge(_) | Error::UsernameChangeTooSoon(_) | Error::GuestsDisabled | Error::NotAGuest | Error::InvalidHierarchy(_) | Error::OwnerRequired => false,
+            Error::InvalidRedirectUri(_) | Error::InvalidScope(_) | Error::UnknownService(_) | Error::ServiceNotTrusted(_) | Error::InsufficientScope(_) | Error::InvalidKeyChange(_) => false,
+            Error::PayloadTooLarge(_) | Error::UnsupportedGrantType(_) | Error::UnsupportedTokenType(_) | Error::InvalidTarget(_) => false,
+        }
+    }
+}
+
+
 impl From<DatabaseError> for Error {
     fn from(err: DatabaseError) -> Self {
         Error::DatabaseError(err)
@@ -45,6 +189,36 @@ impl From<ConversionError> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err.kind())
+    }
+}
+
+impl From<ProviderUnavailable> for Error {
+    fn from(err: ProviderUnavailable) -> Self {
+        Error::ProviderUnavailable(err.0)
+    }
+}
+
+impl From<StorageError> for Error {
+    fn from(err: StorageError) -> Self {
+        Error::StorageError(err)
+    }
+}
+
+impl From<SamlError> for Error {
+    fn from(err: SamlError) -> Self {
+        Error::SamlError(err)
+    }
+}
+
+impl From<UsernameError> for Error {
+    fn from(err: UsernameError) -> Self {
+        Error::InvalidUsername(err)
+    }
+}
+
 impl From<HashError> for Error {
     fn from(err: HashError) -> Self {
         match err {
@@ -0,0 +1,17 @@
+use std::fmt::{Display, Formatter};
+use std::error::Error as StdError;
+
+
+/// An outbound provider is not called because its circuit is open. see `CircuitBreaker`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProviderUnavailable(pub &'static str);
+
+
+impl Display for ProviderUnavailable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is unavailable", self.0)
+    }
+}
+
+
+impl StdError for ProviderUnavailable {}
@@ -0,0 +1,42 @@
+use std::fmt::{Display, Formatter};
+use std::error::Error as StdError;
+
+
+#[derive(Debug, PartialEq)]
+pub enum SamlError {
+    /// the message is not valid base64, deflate or XML, or misses a part it needs.
+    Malformed(String),
+    /// no service provider is configured with this entity id.
+    UnknownServiceProvider(String),
+    /// the assertion consumer service URL is not the one configured for the service provider.
+    AcsUrlMismatch(String),
+    /// the signing key or the certificate could not be read.
+    InvalidKey(String),
+    /// no identity provider is configured with this name or entity id.
+    UnknownIdentityProvider(String),
+    /// the response is not signed by the identity provider.
+    InvalidSignature(String),
+    /// the assertion is expired, or meant for another service provider or request.
+    InvalidAssertion(String),
+    /// the identity provider did not authenticate the user. holds its status code.
+    Unsuccessful(String),
+}
+
+
+impl Display for SamlError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SamlError::Malformed(reason) => write!(f, "malformed SAML message: {}", reason),
+            SamlError::UnknownServiceProvider(entity_id) => write!(f, "unknown service provider: {}", entity_id),
+            SamlError::AcsUrlMismatch(url) => write!(f, "{} is not the assertion consumer service of the service provider", url),
+            SamlError::InvalidKey(reason) => write!(f, "invalid SAML key: {}", reason),
+            SamlError::UnknownIdentityProvider(name) => write!(f, "unknown identity provider: {}", name),
+            SamlError::InvalidSignature(reason) => write!(f, "invalid SAML signature: {}", reason),
+            SamlError::InvalidAssertion(reason) => write!(f, "invalid SAML assertion: {}", reason),
+            SamlError::Unsuccessful(status) => write!(f, "the identity provider did not authenticate the user: {}", status),
+        }
+    }
+}
+
+
+impl StdError for SamlError {}
@@ -0,0 +1,41 @@
+use std::fmt::{Display, Formatter};
+use std::error::Error as StdError;
+
+
+#[derive(Debug)]
+pub enum SecretError {
+    UnsupportedBackend(&'static str),
+    NotFound(String),
+    MissingKey(String),
+    Internal(Box<dyn StdError + Send + Sync>),
+}
+
+
+impl Display for SecretError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SecretError::UnsupportedBackend(scheme) => write!(f, "no {} secret backend is configured", scheme),
+            SecretError::NotFound(reference) => write!(f, "secret not found: {}", reference),
+            SecretError::MissingKey(reference) => write!(f, "the secret {} does not contain the requested key", reference),
+            SecretError::Internal(err) => write!(f, "internal error: {}", err),
+        }
+    }
+}
+
+
+impl StdError for SecretError {}
+
+
+impl From<reqwest::Error> for SecretError {
+    fn from(err: reqwest::Error) -> Self {
+        SecretError::Internal(Box::new(err))
+    }
+}
+
+
+#[cfg(feature = "secretsmanager")]
+impl<E: StdError + 'static + Send + Sync, R: std::fmt::Debug + Send + Sync + 'static> From<aws_sdk_secretsmanager::error::SdkError<E, R>> for SecretError {
+    fn from(err: aws_sdk_secretsmanager::error::SdkError<E, R>) -> Self {
+        SecretError::Internal(Box::new(err))
+    }
+}
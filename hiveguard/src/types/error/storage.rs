@@ -0,0 +1,58 @@
+use std::fmt::{Display, Formatter};
+use std::error::Error as StdError;
+
+
+#[derive(Debug)]
+pub enum StorageError {
+    /// the content type is not one of those accepted.
+    UnsupportedContentType(String),
+    /// the file is larger than this many bytes.
+    TooLarge(u64),
+    /// nothing is stored under the key.
+    NotFound(String),
+    Internal(Box<dyn StdError + Send + Sync>),
+}
+
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StorageError::UnsupportedContentType(content_type) => write!(f, "unsupported content type: {}", content_type),
+            StorageError::TooLarge(max) => write!(f, "the file must not be larger than {} bytes", max),
+            StorageError::NotFound(key) => write!(f, "no file was uploaded to {}", key),
+            StorageError::Internal(err) => write!(f, "internal error: {}", err),
+        }
+    }
+}
+
+
+impl StdError for StorageError {}
+
+
+impl PartialEq for StorageError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (StorageError::UnsupportedContentType(this), StorageError::UnsupportedContentType(other)) => this == other,
+            (StorageError::TooLarge(this), StorageError::TooLarge(other)) => this == other,
+            (StorageError::NotFound(this), StorageError::NotFound(other)) => this == other,
+            (StorageError::Internal(this), StorageError::Internal(other)) => this.to_string() == other.to_string(),
+            _ => false,
+        }
+    }
+}
+
+
+#[cfg(feature = "s3")]
+impl<E: StdError + 'static + Send + Sync, R: std::fmt::Debug + Send + Sync + 'static> From<aws_sdk_s3::error::SdkError<E, R>> for StorageError {
+    fn from(err: aws_sdk_s3::error::SdkError<E, R>) -> Self {
+        StorageError::Internal(Box::new(err))
+    }
+}
+
+
+#[cfg(feature = "s3")]
+impl From<aws_sdk_s3::presigning::PresigningConfigError> for StorageError {
+    fn from(err: aws_sdk_s3::presigning::PresigningConfigError) -> Self {
+        StorageError::Internal(Box::new(err))
+    }
+}
@@ -0,0 +1,36 @@
+use std::fmt::{Display, Formatter};
+use std::error::Error as StdError;
+
+
+/// Why a username was rejected. see `UsernameRules`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UsernameError {
+    /// shorter than the minimum number of characters.
+    TooShort(usize),
+    /// longer than the maximum number of characters.
+    TooLong(usize),
+    InvalidCharacter(char),
+    /// the username is the reserved name.
+    Reserved(String),
+    /// the username looks like the reserved name. eg `аdmin` with a cyrillic `а`.
+    Confusable(String),
+    /// the username mixes letters of several scripts.
+    MixedScripts,
+}
+
+
+impl Display for UsernameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UsernameError::TooShort(min) => write!(f, "username must be at least {} characters long", min),
+            UsernameError::TooLong(max) => write!(f, "username must be at most {} characters long", max),
+            UsernameError::InvalidCharacter(c) => write!(f, "username must not contain {:?}", c),
+            UsernameError::Reserved(name) => write!(f, "username {} is reserved", name),
+            UsernameError::Confusable(name) => write!(f, "username can be mistaken for the reserved name {}", name),
+            UsernameError::MixedScripts => write!(f, "username must not mix letters of different scripts"),
+        }
+    }
+}
+
+
+impl StdError for UsernameError {}
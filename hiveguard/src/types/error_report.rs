@@ -0,0 +1,32 @@
+use serde::{Serialize, Deserialize};
+use chrono::{Utc, DateTime};
+
+
+/// An internal error captured for an error reporting service such as Sentry.
+///
+/// Reports deliberately leave out anything that could identify a user: no headers, bodies, query strings or user ids.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorReport {
+    pub message: String,
+    pub context: RequestContext,
+    pub occurred_at: DateTime<Utc>,
+}
+
+
+/// The request an error happened in.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct RequestContext {
+    pub request_id: Option<String>,
+    pub method: Option<String>,
+    /// the matched route pattern. eg `/users/{id}` rather than the requested path.
+    pub route: Option<String>,
+    /// the background job the error happened in, for errors outside of requests. eg `purge_sessions`
+    pub job: Option<String>,
+}
+
+
+impl ErrorReport {
+    pub fn new<E: std::fmt::Display>(error: &E, context: RequestContext) -> Self {
+        Self { message: error.to_string(), context, occurred_at: Utc::now() }
+    }
+}
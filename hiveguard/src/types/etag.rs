@@ -0,0 +1,59 @@
+use std::fmt::{Display, Formatter};
+use sha2::{Digest, Sha256};
+use serde::Serialize;
+
+
+/// An entity tag (RFC 9110), naming the version of an item so that a write can require it to still be the one read.
+///
+/// Items carry no version number, so the tag is a digest of the item as it is serialized: any change to it, including
+/// its `updated_at`, makes a new tag. It is returned as the `ETag` of reads and checked against the `If-Match` of writes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ETag(String);
+
+
+impl ETag {
+    /// The strong tag of `item`.
+    pub fn of<T: Serialize>(item: &T) -> Self {
+        let serialized = serde_json::to_vec(item).unwrap_or_default();
+        let digest = Sha256::digest(&serialized);
+        Self(digest[..16].iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    /// Whether an `If-Match` header value, `*` or a list of tags, lets a write to the item of this tag through.
+    ///
+    /// Tags are compared strongly, as `If-Match` requires, so weak tags (`W/"..."`) never match.
+    pub fn matches(&self, if_match: &str) -> bool {
+        let if_match = if_match.trim();
+        if if_match == "*" {
+            return true;
+        }
+        if_match.split(',').map(str::trim).any(|tag| tag.strip_prefix('"').and_then(|tag| tag.strip_suffix('"')) == Some(self.0.as_str()))
+    }
+}
+
+
+impl Display for ETag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"{}\"", self.0)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_tags_follow_the_item() {
+        let tag = ETag::of(&json!({"username": "jane"}));
+        assert_eq!(tag, ETag::of(&json!({"username": "jane"})));
+        assert_ne!(tag, ETag::of(&json!({"username": "john"})));
+        assert!(tag.matches(&tag.to_string()));
+        assert!(tag.matches(&format!("\"other\", {}", tag)));
+        assert!(tag.matches("*"));
+        assert!(!tag.matches(&format!("W/{}", tag)));
+        assert!(!tag.matches("\"other\""));
+        assert!(!tag.matches(""));
+    }
+}
@@ -0,0 +1,120 @@
+#[cfg(feature = "dynamodb")]
+use aws_sdk_dynamodb::types::AttributeValue;
+use serde::{Serialize, Deserialize};
+use super::{ConversionError, Id};
+use crate::create_date_from_map;
+use std::collections::HashMap;
+use serde_json::{Map, Value};
+use chrono::{Utc, DateTime};
+
+
+/// What a [`GrantRecord`] records.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GrantKind {
+    /// an authorization code, redeemed once at the token endpoint.
+    AuthorizationCode,
+    /// a device code, approved by the user on another device before it is redeemed.
+    DeviceCode,
+    /// the scopes a user consented to give a third-party service.
+    Consent,
+}
+
+
+/// A short-lived grant of the authorization flows. It is never returned once it expired, whether it was purged yet or not.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GrantRecord {
+    /// what the grant is looked up by. eg a hash of the authorization code, never the code itself.
+    pub id: String,
+    pub kind: GrantKind,
+    pub client_id: String,
+    /// the user the grant is for. unset until a device code is approved.
+    pub user_id: Option<Id>,
+    /// the space separated scopes granted.
+    pub scope: String,
+    /// what the flow needs back when the grant is redeemed. eg the redirect uri and code challenge of an authorization code.
+    #[serde(default)]
+    pub data: Map<String, Value>,
+    pub expires: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+
+impl GrantRecord {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires <= now
+    }
+}
+
+
+#[cfg(feature = "dynamodb")]
+impl From<GrantRecord> for HashMap<String, AttributeValue> {
+    fn from(grant: GrantRecord) -> Self {
+        let mut map = HashMap::new();
+        map.insert("id".into(), AttributeValue::S(grant.id));
+        map.insert("kind".into(), AttributeValue::S(serde_json::json!(grant.kind).as_str().unwrap_or_default().to_string()));
+        map.insert("client_id".into(), AttributeValue::S(grant.client_id));
+        if let Some(user_id) = grant.user_id {
+            map.insert("user_id".into(), user_id.into());
+        }
+        map.insert("scope".into(), AttributeValue::S(grant.scope));
+        if !grant.data.is_empty() {
+            map.insert("data".into(), AttributeValue::S(Value::Object(grant.data).to_string()));
+        }
+        map.insert("expires".into(), AttributeValue::N(grant.expires.timestamp().to_string()));
+        map.insert("created_at".into(), AttributeValue::N(grant.created_at.timestamp().to_string()));
+        map
+    }
+}
+
+
+#[cfg(feature = "dynamodb")]
+impl TryFrom<HashMap<String, AttributeValue>> for GrantRecord {
+    type Error = ConversionError;
+
+    fn try_from(mut map: HashMap<String, AttributeValue>) -> Result<Self, Self::Error> {
+        let mut string = |field: &'static str| match map.remove(field) {
+            Some(AttributeValue::S(string)) => Ok(string),
+            Some(_) => Err(ConversionError::UnexpectedDataType(field)),
+            None => Err(ConversionError::MissingField(field)),
+        };
+        let id = string("id")?;
+        let kind = serde_json::from_value(Value::String(string("kind")?)).map_err(|_| ConversionError::UnexpectedDataType("kind"))?;
+        let client_id = string("client_id")?;
+        let scope = string("scope")?;
+        let data = match map.remove("data") {
+            None => Map::new(),
+            Some(AttributeValue::S(json)) => serde_json::from_str(&json).map_err(|_| ConversionError::UnexpectedDataType("data"))?,
+            Some(_) => return Err(ConversionError::UnexpectedDataType("data")),
+        };
+        let user_id = match map.remove("user_id") {
+            Some(value) => Some(value.try_into()?),
+            None => None,
+        };
+        let expires = expires_date_from_map(&mut map)?;
+        let created_at = created_at_date_from_map(&mut map)?;
+        Ok(Self { id, kind, client_id, user_id, scope, data, expires, created_at })
+    }
+}
+
+
+create_date_from_map!(expires_date_from_map, "expires");
+create_date_from_map!(created_at_date_from_map, "created_at");
+
+
+#[cfg(all(test, feature = "dynamodb"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grants_round_trip_through_attributes() {
+        let now = DateTime::from_timestamp(Utc::now().timestamp(), 0).unwrap();
+        let data = Map::from_iter([(String::from("redirect_uri"), Value::from("https://app.example.com/callback"))]);
+        let grant = GrantRecord { id: String::from("code"), kind: GrantKind::AuthorizationCode, client_id: String::from("web"), user_id: Some(Id::default()), scope: String::from("openid"), data, expires: now, created_at: now };
+        assert_eq!(GrantRecord::try_from(HashMap::from(grant.clone())), Ok(grant.clone()));
+        let pending = GrantRecord { kind: GrantKind::DeviceCode, user_id: None, data: Map::new(), ..grant };
+        let map = HashMap::from(pending.clone());
+        assert_eq!(map["kind"], AttributeValue::S(String::from("device_code")));
+        assert_eq!(GrantRecord::try_from(map), Ok(pending));
+    }
+}
@@ -1,27 +1,92 @@
 #[cfg(feature = "dynamodb")]
 use aws_sdk_dynamodb::{primitives::Blob, types::AttributeValue};
-use serde::{Serialize, Deserialize, Serializer};
-use std::ops::{Deref, DerefMut};
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::fmt::{Display, Formatter};
 use super::ConversionError;
 use bson::oid::ObjectId;
 use std::str::FromStr;
+use uuid::Uuid;
 
 
-#[derive(Clone, Debug, Deserialize, Default, PartialEq, Hash, Eq, Copy)]
-pub struct Id(pub ObjectId);
+/// the strategy new ids are generated with. see [`Id::set_strategy`].
+static STRATEGY: AtomicU8 = AtomicU8::new(IdStrategy::ObjectId as u8);
 
 
+/// The identifier of every item.
+///
+/// Ids of every strategy are read back whatever the current strategy is, so switching strategies only affects new items.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Id {
+    ObjectId(ObjectId),
+    Uuid(Uuid),
+}
+
+
+/// How new [`Id`]s are generated.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IdStrategy {
+    /// 12 bytes, ordered by creation time to the second.
+    #[default]
+    ObjectId,
+    /// 16 random bytes.
+    UuidV4,
+    /// 16 bytes, ordered by creation time to the millisecond, which keeps indexes compact.
+    UuidV7,
+}
+
+
+impl Id {
+    /// A new id generated with the strategy set with [`Id::set_strategy`], [`IdStrategy::ObjectId`] by default.
+    pub fn new() -> Self {
+        match Self::strategy() {
+            IdStrategy::ObjectId => Id::ObjectId(ObjectId::new()),
+            IdStrategy::UuidV4 => Id::Uuid(Uuid::new_v4()),
+            IdStrategy::UuidV7 => Id::Uuid(Uuid::now_v7()),
+        }
+    }
+
+    /// Sets how new ids are generated for the whole process. meant to be called once at startup.
+    pub fn set_strategy(strategy: IdStrategy) {
+        STRATEGY.store(strategy as u8, Ordering::Relaxed);
+    }
+
+    pub fn strategy() -> IdStrategy {
+        match STRATEGY.load(Ordering::Relaxed) {
+            strategy if strategy == IdStrategy::UuidV4 as u8 => IdStrategy::UuidV4,
+            strategy if strategy == IdStrategy::UuidV7 as u8 => IdStrategy::UuidV7,
+            _ => IdStrategy::ObjectId,
+        }
+    }
+
+    /// The textual form of the id. 24 hex characters for an ObjectId, the hyphenated form for a UUID.
+    pub fn to_hex(self) -> String {
+        match self {
+            Id::ObjectId(id) => id.to_hex(),
+            Id::Uuid(id) => id.hyphenated().to_string(),
+        }
+    }
+
+    pub fn to_bytes(self) -> Vec<u8> {
+        match self {
+            Id::ObjectId(id) => id.bytes().to_vec(),
+            Id::Uuid(id) => id.as_bytes().to_vec(),
+        }
+    }
+}
+
 
-impl Deref for Id {
-    type Target = ObjectId;
-    fn deref(&self) -> &Self::Target {
-        &self.0
+impl Default for Id {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-impl DerefMut for Id {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+
+impl Display for Id {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_hex())
     }
 }
 
@@ -30,14 +95,39 @@ impl TryFrom<String> for Id {
     type Error = ConversionError;
 
     fn try_from(id: String) -> Result<Self, Self::Error> {
-        ObjectId::from_str(&id).map(Id).map_err(|_| ConversionError::CouldNotConvertStringToID)
+        match id.len() {
+            24 => ObjectId::from_str(&id).map(Id::ObjectId).map_err(|_| ConversionError::CouldNotConvertStringToID),
+            _ => Uuid::try_parse(&id).map(Id::Uuid).map_err(|_| ConversionError::CouldNotConvertStringToID),
+        }
+    }
+}
+
+
+impl TryFrom<&[u8]> for Id {
+    type Error = ConversionError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if let Ok(bytes) = <[u8; 12]>::try_from(bytes) {
+            Ok(Id::ObjectId(ObjectId::from_bytes(bytes)))
+        } else if let Ok(bytes) = <[u8; 16]>::try_from(bytes) {
+            Ok(Id::Uuid(Uuid::from_bytes(bytes)))
+        } else {
+            Err(ConversionError::CouldNotConvertBlobToID)
+        }
     }
 }
 
 
 impl From<Id> for Vec<u8> {
     fn from(id: Id) -> Self {
-        id.0.bytes().to_vec()
+        id.to_bytes()
+    }
+}
+
+
+impl From<Id> for serde_json::Value {
+    fn from(id: Id) -> Self {
+        serde_json::Value::String(id.to_hex())
     }
 }
 
@@ -46,7 +136,15 @@ impl Serialize for Id {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
         where
             S: Serializer {
-        serializer.serialize_str(&self.0.to_hex())
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+
+impl<'de> Deserialize<'de> for Id {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        Id::try_from(id).map_err(serde::de::Error::custom)
     }
 }
 
@@ -72,9 +170,7 @@ impl TryFrom<Blob> for Id {
     type Error = ConversionError;
 
     fn try_from(value: Blob) -> Result<Self, Self::Error> {
-        let bytes = TryInto::<[u8; 12]>::try_into(value.into_inner()).map_err(|_|ConversionError::CouldNotConvertBlobToID)?;
-        let id = ObjectId::from_bytes(bytes);
-        Ok(Id(id))
+        Id::try_from(value.as_ref())
     }
 }
 
@@ -91,3 +187,29 @@ impl TryFrom<AttributeValue> for Id {
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_of_every_strategy_round_trip() {
+        let ids = [Id::ObjectId(ObjectId::new()), Id::Uuid(Uuid::new_v4()), Id::Uuid(Uuid::now_v7())];
+        for id in ids {
+            assert_eq!(Id::try_from(id.to_hex()), Ok(id));
+            assert_eq!(Id::try_from(&id.to_bytes()[..]), Ok(id));
+            assert_eq!(serde_json::from_value::<Id>(serde_json::to_value(id).unwrap()).unwrap(), id);
+        }
+        assert_eq!(Id::try_from(String::from("not an id")), Err(ConversionError::CouldNotConvertStringToID));
+        assert_eq!(Id::try_from(&[0u8; 8][..]), Err(ConversionError::CouldNotConvertBlobToID));
+    }
+
+    #[test]
+    fn test_uuid_v7_ids_are_time_ordered() {
+        let first = Id::Uuid(Uuid::now_v7());
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let second = Id::Uuid(Uuid::now_v7());
+        assert!(first.to_hex() < second.to_hex());
+    }
+}
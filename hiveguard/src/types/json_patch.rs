@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use super::ConversionError;
+
+
+/// A JSON Patch (RFC 6902), the body of `application/json-patch+json` requests.
+///
+/// Items are patched field by field, so operations address top-level fields only, eg `/fullname`.
+/// [`JsonPatch::apply`] turns the operations into the `Map` updates the tables take, where `null` clears a field.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct JsonPatch(pub Vec<PatchOperation>);
+
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+
+impl JsonPatch {
+    /// Applies the operations to `document`, in order, and returns the fields they changed with their new value.
+    ///
+    /// Removed fields are `null`. Either every operation applies or the patch fails as a whole.
+    pub fn apply(&self, document: &Map<String, Value>) -> Result<Map<String, Value>, ConversionError> {
+        let mut document = document.clone();
+        let mut changed = Vec::new();
+        for operation in &self.0 {
+            match operation {
+                PatchOperation::Add { path, value } => {
+                    document.insert(field(path)?, value.clone());
+                    changed.push(field(path)?);
+                },
+                PatchOperation::Remove { path } => {
+                    remove(&mut document, path)?;
+                    changed.push(field(path)?);
+                },
+                PatchOperation::Replace { path, value } => {
+                    remove(&mut document, path)?;
+                    document.insert(field(path)?, value.clone());
+                    changed.push(field(path)?);
+                },
+                PatchOperation::Move { from, path } => {
+                    let value = remove(&mut document, from)?;
+                    document.insert(field(path)?, value);
+                    changed.extend([field(from)?, field(path)?]);
+                },
+                PatchOperation::Copy { from, path } => {
+                    let value = document.get(&field(from)?).cloned().ok_or_else(|| missing(from))?;
+                    document.insert(field(path)?, value);
+                    changed.push(field(path)?);
+                },
+                PatchOperation::Test { path, value } => {
+                    if document.get(&field(path)?) != Some(value) {
+                        return Err(ConversionError::PatchTestFailed(path.clone()));
+                    }
+                },
+            }
+        }
+        let mut update = Map::new();
+        for field in changed {
+            let value = document.get(&field).cloned().unwrap_or(Value::Null);
+            update.insert(field, value);
+        }
+        Ok(update)
+    }
+}
+
+
+/// The top-level field `path` points to.
+fn field(path: &str) -> Result<String, ConversionError> {
+    let Some(token) = path.strip_prefix('/') else {
+        return Err(ConversionError::InvalidPatch(format!("`{}` is not a JSON pointer", path)));
+    };
+    if token.is_empty() || token.contains('/') {
+        return Err(ConversionError::InvalidPatch(format!("`{}` is not a top-level field", path)));
+    }
+    Ok(token.replace("~1", "/").replace("~0", "~"))
+}
+
+
+fn remove(document: &mut Map<String, Value>, path: &str) -> Result<Value, ConversionError> {
+    document.remove(&field(path)?).ok_or_else(|| missing(path))
+}
+
+
+fn missing(path: &str) -> ConversionError {
+    ConversionError::InvalidPatch(format!("`{}` does not exist", path))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn patch(operations: Value) -> JsonPatch {
+        serde_json::from_value(operations).unwrap()
+    }
+
+    fn document() -> Map<String, Value> {
+        json!({"username": "jane", "fullname": "Jane", "profile": "hello", "avatar": null}).as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_operations_become_field_updates() {
+        let operations = patch(json!([
+            {"op": "test", "path": "/username", "value": "jane"},
+            {"op": "replace", "path": "/fullname", "value": "Jane Doe"},
+            {"op": "remove", "path": "/profile"},
+            {"op": "copy", "from": "/username", "path": "/avatar"},
+            {"op": "remove", "path": "/avatar"},
+        ]));
+        let expected = json!({"fullname": "Jane Doe", "profile": null, "avatar": null});
+        assert_eq!(Value::Object(operations.apply(&document()).unwrap()), expected);
+        let moved = patch(json!([{"op": "move", "from": "/profile", "path": "/fullname"}]));
+        assert_eq!(Value::Object(moved.apply(&document()).unwrap()), json!({"profile": null, "fullname": "hello"}));
+    }
+
+    #[test]
+    fn test_patches_fail_as_a_whole() {
+        let failed = patch(json!([{"op": "replace", "path": "/fullname", "value": "x"}, {"op": "test", "path": "/username", "value": "john"}]));
+        assert_eq!(failed.apply(&document()), Err(ConversionError::PatchTestFailed(String::from("/username"))));
+        let missing = patch(json!([{"op": "remove", "path": "/role"}]));
+        assert_eq!(missing.apply(&document()), Err(ConversionError::InvalidPatch(String::from("`/role` does not exist"))));
+        let nested = patch(json!([{"op": "add", "path": "/metadata/plan", "value": "pro"}]));
+        assert_eq!(nested.apply(&document()), Err(ConversionError::InvalidPatch(String::from("`/metadata/plan` is not a top-level field"))));
+        assert!(serde_json::from_value::<JsonPatch>(json!([{"op": "merge", "path": "/fullname"}])).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+use serde::{Serialize, Deserialize};
+use std::fmt::{self, Display};
+
+
+/// Where an IP address is, as far as the GeoIP database knows. eg `Nairobi, KE`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Location {
+    /// the ISO 3166-1 alpha-2 code of the country. eg `KE`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    /// the English name of the city. eg `Nairobi`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    /// the approximate coordinates of the address, in degrees.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+}
+
+
+impl Location {
+    /// The great-circle distance to `other`, in kilometres, `None` unless both have coordinates.
+    pub fn distance_km(&self, other: &Location) -> Option<f64> {
+        const EARTH_RADIUS_KM: f64 = 6371.0;
+        let (lat1, lon1) = (self.latitude?.to_radians(), self.longitude?.to_radians());
+        let (lat2, lon2) = (other.latitude?.to_radians(), other.longitude?.to_radians());
+        let a = ((lat2 - lat1) / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+        Some(2.0 * EARTH_RADIUS_KM * a.sqrt().asin())
+    }
+}
+
+
+impl Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.city, &self.country) {
+            (Some(city), Some(country)) => write!(f, "{}, {}", city, country),
+            (Some(name), None) | (None, Some(name)) => f.write_str(name),
+            (None, None) => f.write_str("Unknown location"),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distances_are_great_circle_distances() {
+        let at = |latitude, longitude| Location { latitude: Some(latitude), longitude: Some(longitude), ..Default::default() };
+        let nairobi_to_london = at(-1.2864, 36.8172).distance_km(&at(51.5072, -0.1276)).unwrap();
+        assert!((6700.0..6850.0).contains(&nairobi_to_london));
+        assert_eq!(Location::default().distance_km(&at(0.0, 0.0)), None);
+    }
+}
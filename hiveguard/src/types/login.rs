@@ -3,6 +3,7 @@ use super::{OAuthProvider, ConversionError, Error};
 use aws_sdk_dynamodb::types::AttributeValue;
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
+use chrono::{DateTime, Utc};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum Login {
@@ -10,6 +11,15 @@ pub enum Login {
     Password(String),
     #[serde(rename = "oauth")]
     OAuth(OAuthProvider),
+    /// the password is checked by an external directory. eg LDAP. holds the distinguished name of the user in the directory.
+    #[serde(rename = "directory")]
+    Directory(String),
+    /// the user logs in through an upstream SAML identity provider. holds its entity id.
+    #[serde(rename = "saml")]
+    Saml(String),
+    /// a guest, who logs in with nothing but the tokens it was issued. holds the time it expires at unless it upgrades. see `Guests`.
+    #[serde(rename = "guest")]
+    Guest(DateTime<Utc>),
 }
 
 impl Login {
@@ -30,7 +40,15 @@ impl Login {
     pub fn is_empty(&self) -> bool {
         match self {
             Login::Password(password) => password.is_empty(),
-            Login::OAuth(_) => false,
+            Login::OAuth(_) | Login::Directory(_) | Login::Saml(_) | Login::Guest(_) => false,
+        }
+    }
+
+    /// When the user is a guest, the time it expires at.
+    pub fn guest_expires_at(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Login::Guest(expires_at) => Some(*expires_at),
+            _ => None,
         }
     }
 }
@@ -47,6 +65,15 @@ impl From<Login> for HashMap<String, AttributeValue> {
             Login::OAuth(oauth) => {
                 map.insert("oauth".to_string(), AttributeValue::S(oauth.into()));
             },
+            Login::Directory(dn) => {
+                map.insert("directory".to_string(), AttributeValue::S(dn));
+            },
+            Login::Saml(entity_id) => {
+                map.insert("saml".to_string(), AttributeValue::S(entity_id));
+            },
+            Login::Guest(expires_at) => {
+                map.insert("guest".to_string(), AttributeValue::N(expires_at.timestamp().to_string()));
+            },
         }
         map
     }
@@ -75,7 +102,21 @@ impl TryFrom<&mut HashMap<String, AttributeValue>> for Login {
                         _ => Err(ConversionError::UnexpectedDataType("oauth"))
                     }
                 },
-                None => Err(ConversionError::MissingFields(&["password", "oauth"]))
+                None => match map.remove("directory") {
+                    Some(AttributeValue::S(dn)) => Ok(Login::Directory(dn)),
+                    Some(_) => Err(ConversionError::UnexpectedDataType("directory")),
+                    None => match map.remove("saml") {
+                        Some(AttributeValue::S(entity_id)) => Ok(Login::Saml(entity_id)),
+                        Some(_) => Err(ConversionError::UnexpectedDataType("saml")),
+                        None => match map.remove("guest") {
+                            Some(AttributeValue::N(timestamp)) => super::parse_timestamp(&timestamp)
+                                .map(Login::Guest)
+                                .ok_or(ConversionError::UnexpectedDataType("guest")),
+                            Some(_) => Err(ConversionError::UnexpectedDataType("guest")),
+                            None => Err(ConversionError::MissingFields(&["password", "oauth", "directory", "saml", "guest"]))
+                        }
+                    }
+                }
             }
         }
         
@@ -101,4 +142,21 @@ mod tests {
         let deserialized: Login = serde_json::from_value(data).unwrap();
         assert_eq!(deserialized, Login::OAuth(OAuthProvider::Github));
     }
+
+    #[cfg(feature = "dynamodb")]
+    #[test]
+    fn test_directory_login_attribute() {
+        let login = Login::Directory("uid=jane,ou=people,dc=example,dc=com".into());
+        let mut map = HashMap::from(login.clone());
+        assert_eq!(Login::try_from(&mut map), Ok(login));
+    }
+
+    #[cfg(feature = "dynamodb")]
+    #[test]
+    fn test_guest_login_attribute() {
+        let login = Login::Guest(DateTime::from_timestamp(1_700_000_000, 0).unwrap());
+        let mut map = HashMap::from(login.clone());
+        assert_eq!(map.get("guest"), Some(&AttributeValue::N(String::from("1700000000"))));
+        assert_eq!(Login::try_from(&mut map), Ok(login));
+    }
 }
\ No newline at end of file
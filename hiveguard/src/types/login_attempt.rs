@@ -0,0 +1,45 @@
+use serde::{Serialize, Deserialize};
+use super::{Id, Device, LoginFailure};
+use chrono::{Utc, DateTime};
+
+
+/// A login to the account of a user, as shown to them in their login history.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LoginAttempt {
+    pub id: Id,
+    pub user_id: Id,
+    pub device: Device,
+    #[serde(flatten)]
+    pub outcome: LoginOutcome,
+    pub occurred_at: DateTime<Utc>,
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum LoginOutcome {
+    Succeeded,
+    /// the attempt named an existing user, and was refused for `reason`.
+    Failed { reason: LoginFailure },
+}
+
+
+impl LoginAttempt {
+    pub fn new(user_id: Id, device: Device, outcome: LoginOutcome) -> Self {
+        Self { id: Id::default(), user_id, device, outcome, occurred_at: Utc::now() }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outcomes_are_flattened_into_attempts() {
+        let attempt = LoginAttempt::new(Id::default(), Device::default(), LoginOutcome::Failed { reason: LoginFailure::WrongPassword });
+        let json = serde_json::to_value(&attempt).unwrap();
+        assert_eq!((&json["outcome"], &json["reason"]), (&serde_json::json!("failed"), &serde_json::json!("wrong_password")));
+        assert_eq!(serde_json::from_value::<LoginAttempt>(json).unwrap(), attempt);
+    }
+}
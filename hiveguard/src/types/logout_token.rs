@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde_json::{Map, Value};
+use super::Id;
+
+
+/// the event of logout tokens, as of OpenID Connect Back-Channel Logout.
+pub const BACKCHANNEL_LOGOUT_EVENT: &str = "http://schemas.openid.net/event/backchannel-logout";
+
+
+/// Tells a service that a session of one of its users ended. Signed under `v4.public`, like the tokens of hiveguard.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LogoutToken {
+    #[serde(rename = "iss")]
+    pub issuer: String,
+    /// the client id of the service.
+    #[serde(rename = "aud")]
+    pub audience: String,
+    #[serde(rename = "sub")]
+    pub subject: Id,
+    #[serde(rename = "sid")]
+    pub session_id: Id,
+    #[serde(rename = "jti")]
+    pub id: Id,
+    #[serde(rename = "iat")]
+    pub issued_at: DateTime<Utc>,
+    #[serde(rename = "exp")]
+    pub expiration: DateTime<Utc>,
+    pub events: Map<String, Value>,
+}
+
+
+impl LogoutToken {
+    /// A token valid for two minutes, the time it takes to deliver it.
+    pub fn new(issuer: String, audience: String, subject: Id, session_id: Id) -> Self {
+        let now = Utc::now();
+        let events = Map::from_iter([(String::from(BACKCHANNEL_LOGOUT_EVENT), Value::Object(Map::new()))]);
+        Self { issuer, audience, subject, session_id, id: Id::default(), issued_at: now, expiration: now + TimeDelta::minutes(2), events }
+    }
+}
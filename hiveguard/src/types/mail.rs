@@ -0,0 +1,67 @@
+use serde::{Serialize, Deserialize};
+use chrono::{Utc, DateTime};
+use lettre::Address;
+use super::Id;
+
+
+/// A transactional email waiting in the mail queue, or delivered from it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Mail {
+    pub id: Id,
+    pub to: Address,
+    pub subject: String,
+    /// the plain text body.
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,
+    pub status: MailStatus,
+    /// the failed deliveries so far.
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MailStatus {
+    Pending,
+    Sent,
+    /// every attempt failed. the mail is kept for inspection and is not retried.
+    Dead,
+}
+
+
+impl Mail {
+    /// A mail due right away.
+    pub fn new(to: Address, subject: impl Into<String>, text: impl Into<String>, html: Option<String>) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Id::default(),
+            to,
+            subject: subject.into(),
+            text: text.into(),
+            html,
+            status: MailStatus::Pending,
+            attempts: 0,
+            next_attempt_at: now,
+            last_error: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+
+impl MailStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MailStatus::Pending => "pending",
+            MailStatus::Sent => "sent",
+            MailStatus::Dead => "dead",
+        }
+    }
+}
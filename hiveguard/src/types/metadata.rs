@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use super::ConversionError;
+
+
+/// the longest key of a metadata field.
+pub const MAX_KEY_LENGTH: usize = 64;
+/// the most bytes a namespace takes once serialized to JSON.
+pub const MAX_SIZE: usize = 16 * 1024;
+
+
+/// Who may write a namespace of [`Metadata`]. Users can read both.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Namespace {
+    /// written by the user, eg their preferences.
+    User,
+    /// written by services only, eg a plan or a customer id.
+    Service,
+}
+
+
+/// Arbitrary fields applications attach to a user.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Metadata {
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub user: Map<String, Value>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub service: Map<String, Value>,
+}
+
+
+impl Namespace {
+    pub const ALL: [Namespace; 2] = [Namespace::User, Namespace::Service];
+
+    /// The attribute the namespace is stored in.
+    pub fn attribute(&self) -> &'static str {
+        match self {
+            Namespace::User => "user_metadata",
+            Namespace::Service => "service_metadata",
+        }
+    }
+}
+
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        self.user.is_empty() && self.service.is_empty()
+    }
+
+    pub fn get(&self, namespace: Namespace) -> &Map<String, Value> {
+        match namespace {
+            Namespace::User => &self.user,
+            Namespace::Service => &self.service,
+        }
+    }
+
+    pub fn get_mut(&mut self, namespace: Namespace) -> &mut Map<String, Value> {
+        match namespace {
+            Namespace::User => &mut self.user,
+            Namespace::Service => &mut self.service,
+        }
+    }
+
+    /// Merges `changes` into `namespace`. a `null` value removes its key.
+    ///
+    /// Nothing is changed when a key is invalid or the namespace would outgrow [`MAX_SIZE`].
+    pub fn merge(&mut self, namespace: Namespace, changes: Map<String, Value>) -> Result<(), ConversionError> {
+        let mut merged = self.get(namespace).clone();
+        for (key, value) in changes {
+            validate_key(&key)?;
+            match value {
+                Value::Null => merged.remove(&key),
+                value => merged.insert(key, value),
+            };
+        }
+        let size = serde_json::to_vec(&merged).map_or(usize::MAX, |json| json.len());
+        if size > MAX_SIZE {
+            return Err(ConversionError::MetadataTooLarge(MAX_SIZE));
+        }
+        *self.get_mut(namespace) = merged;
+        Ok(())
+    }
+}
+
+
+/// Keys are made of ASCII letters, digits, `_` and `-`, and are at most [`MAX_KEY_LENGTH`] long.
+fn validate_key(key: &str) -> Result<(), ConversionError> {
+    let valid = !key.is_empty()
+        && key.len() <= MAX_KEY_LENGTH
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(ConversionError::InvalidMetadataKey(key.to_string()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn map(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => panic!("not an object"),
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut metadata = Metadata::default();
+        metadata.merge(Namespace::User, map(json!({"theme": "dark", "locale": "sw"}))).unwrap();
+        metadata.merge(Namespace::User, map(json!({"theme": null, "beta": true}))).unwrap();
+        assert_eq!(metadata.user, map(json!({"locale": "sw", "beta": true})));
+        assert!(metadata.service.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_keys_are_rejected() {
+        let mut metadata = Metadata::default();
+        for key in ["", "a.b", "with space", &"k".repeat(MAX_KEY_LENGTH + 1)] {
+            let changes = map(json!({key: 1}));
+            assert_eq!(metadata.merge(Namespace::Service, changes), Err(ConversionError::InvalidMetadataKey(key.to_string())));
+        }
+        assert!(metadata.is_empty());
+    }
+
+    #[test]
+    fn test_size_is_limited() {
+        let mut metadata = Metadata::default();
+        let changes = map(json!({"blob": "x".repeat(MAX_SIZE)}));
+        assert_eq!(metadata.merge(Namespace::User, changes), Err(ConversionError::MetadataTooLarge(MAX_SIZE)));
+        assert!(metadata.is_empty());
+    }
+}
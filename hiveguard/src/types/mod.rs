@@ -1,8 +1,27 @@
 mod oauth_provider;
+mod security_event;
+mod domain_event;
 mod verification;
+mod grant;
+mod signing_key;
+mod pii;
 mod token_bundle;
+mod token_exchange;
+mod logout_token;
+mod error_report;
+mod directory_entry;
 mod functions;
+mod upload;
+mod mail;
+mod login_attempt;
+mod organisation;
+mod audit_entry;
+mod metadata;
+mod consent;
 mod session;
+mod agent;
+mod location;
+mod status;
 mod either;
 mod token;
 mod error;
@@ -10,19 +29,60 @@ mod email;
 mod phone;
 mod login;
 mod user;
+mod user_filter;
+mod json_patch;
+mod tenant;
+mod cidr;
+mod etag;
+mod problem;
+mod payload;
+mod value;
+mod page;
 mod id;
 
 
-pub use error::{DatabaseError, ConversionError};
+pub use error::{ErrorCode, DatabaseError, ConversionError, ConfigError, ConfigIssue, SecretError, ProviderUnavailable, UsernameError, StorageError};
+pub use security_event::{SecurityEvent, SecurityEventKind, LoginFailure, Anomaly};
+pub use domain_event::{DomainEvent, DomainEventKind};
+pub use error_report::{ErrorReport, RequestContext};
 pub use oauth_provider::OAuthProvider;
+pub use directory_entry::DirectoryEntry;
 pub use verification::Verification;
+pub use grant::{GrantRecord, GrantKind};
+pub use signing_key::{SigningKey, KeyAlgorithm, KeyStatus};
+pub use pii::{PiiCipher, PII_FIELDS, BLIND};
 pub use token_bundle::TokenBundle;
-pub use session::Session;
+pub use token_exchange::{TokenExchangeRequest, ExchangedToken, TOKEN_EXCHANGE_GRANT, ACCESS_TOKEN_TYPE};
+pub use logout_token::{LogoutToken, BACKCHANNEL_LOGOUT_EVENT};
+pub use metadata::{Metadata, Namespace};
+pub use consent::{Consent, Consents, Document};
+pub use upload::{Upload, StoredObject};
+pub use session::{Session, Device};
+pub use agent::{Agent, DeviceClass};
+pub use location::Location;
+pub use mail::{Mail, MailStatus};
+pub use login_attempt::{LoginAttempt, LoginOutcome};
+pub use organisation::{Organisation, OrgRole, Membership};
+pub use audit_entry::{AuditEntry, AuditFormat};
+pub use status::Status;
 pub use either::Either;
-pub use token::Token;
+#[cfg(feature = "saml")]
+pub use error::SamlError;
+pub use token::{Token, Audience};
 pub use login::Login;
+pub use page::Page;
 pub use error::Error;
 pub use email::Email;
 pub use phone::Phone;
-pub use user::User;
-pub use id::Id;
\ No newline at end of file
+pub use user::{User, UserPatch, FULLNAME_MAX_LENGTH, PROFILE_MAX_LENGTH};
+pub use user_filter::UserFilter;
+pub use json_patch::{JsonPatch, PatchOperation};
+pub use etag::ETag;
+pub use tenant::TenantId;
+pub use cidr::Cidr;
+pub use problem::{Problem, FieldError};
+pub use payload::{Validator, Payload};
+pub use value::{Coerce, parse_timestamp, timestamp_value, decimal_value};
+#[cfg(feature = "dynamodb")]
+pub use value::{to_attribute_value, from_attribute_value, from_attribute_map, BINARY};
+pub use id::{Id, IdStrategy};
\ No newline at end of file
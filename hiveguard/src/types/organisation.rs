@@ -0,0 +1,54 @@
+use serde::{Serialize, Deserialize};
+use chrono::{Utc, DateTime};
+use super::Id;
+
+
+/// A group of users, eg a customer. Organisations form trees, eg a holding and its subsidiaries:
+/// the members of an organisation are members of every organisation under it too, with the same role.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Organisation {
+    pub id: Id,
+    pub name: String,
+    /// the organisation this one is under, if any.
+    pub parent: Option<Id>,
+    /// the days the entries of its audit log are kept for, `audit.retention_days` when it is not set.
+    #[serde(default)]
+    pub audit_retention_days: Option<u32>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+
+/// The role of a member of an organisation, from the least to the most privileged.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum OrgRole {
+    Member,
+    Admin,
+    Owner,
+}
+
+
+/// The role a user was given in an organisation, and so in every organisation under it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Membership {
+    pub org_id: Id,
+    pub user_id: Id,
+    pub role: OrgRole,
+    pub created_at: DateTime<Utc>,
+}
+
+
+impl Organisation {
+    pub fn new(name: String, parent: Option<Id>) -> Self {
+        let now = Utc::now();
+        Self { id: Id::default(), name, parent, audit_retention_days: None, created_at: now, updated_at: now }
+    }
+}
+
+
+impl Membership {
+    pub fn new(org_id: Id, user_id: Id, role: OrgRole) -> Self {
+        Self { org_id, user_id, role, created_at: Utc::now() }
+    }
+}
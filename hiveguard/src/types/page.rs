@@ -0,0 +1,13 @@
+/// A page of a listing, and the key to resume the listing after when there may be more items.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<Item, Key> {
+    pub items: Vec<Item>,
+    pub next: Option<Key>,
+}
+
+
+impl<Item, Key> From<(Vec<Item>, Option<Key>)> for Page<Item, Key> {
+    fn from((items, next): (Vec<Item>, Option<Key>)) -> Self {
+        Self { items, next }
+    }
+}
@@ -0,0 +1,111 @@
+use super::{ConversionError, FieldError};
+use serde::de::DeserializeOwned;
+use std::ops::RangeInclusive;
+use std::fmt::Display;
+use serde_json::{Map, Value};
+
+
+/// Checks the fields of a request payload as a whole, so that every invalid field is reported at once rather than the
+/// first one deserializing stops at.
+///
+/// Only `required` fails on a missing field. The other checks skip missing and `null` fields, which a payload leaves out
+/// or, in an update, clears.
+///
+/// ```ignore
+/// Validator::new(&payload).required("username").length("fullname", 1..=256).parses::<Url>("avatar").check()?;
+/// ```
+pub struct Validator<'a> {
+    payload: &'a Map<String, Value>,
+    errors: Vec<FieldError>,
+}
+
+
+impl<'a> Validator<'a> {
+    pub fn new(payload: &'a Map<String, Value>) -> Self {
+        Self { payload, errors: Vec::new() }
+    }
+
+    /// `field` has to be set, to anything but `null`.
+    pub fn required(mut self, field: &str) -> Self {
+        if self.value(field).is_none() {
+            self.error(field, String::from("is required"));
+        }
+        self
+    }
+
+    /// `field` has to be a string of `range` characters.
+    pub fn length(mut self, field: &str, range: RangeInclusive<usize>) -> Self {
+        match self.value(field) {
+            None => {},
+            Some(Value::String(string)) if range.contains(&string.chars().count()) => {},
+            Some(Value::String(_)) => {
+                let detail = format!("must be between {} and {} characters long", range.start(), range.end());
+                self.error(field, detail);
+            },
+            Some(_) => self.error(field, String::from("must be a string")),
+        }
+        self
+    }
+
+    /// `field` has to deserialize into a `T`. eg an `Email`, whose detail is then that of its error.
+    pub fn parses<T: DeserializeOwned>(mut self, field: &str) -> Self {
+        if let Some(value) = self.value(field)
+            && let Err(err) = serde_json::from_value::<T>(value.clone()) {
+            self.error(field, err.to_string());
+        }
+        self
+    }
+
+    /// `field` has to deserialize into a `T` that passes `check`. eg a `Phone`, which deserializes leniently, being read back too.
+    pub fn parses_with<T: DeserializeOwned, E: Display>(mut self, field: &str, check: fn(&T) -> Result<(), E>) -> Self {
+        if let Some(value) = self.value(field) {
+            match serde_json::from_value::<T>(value.clone()) {
+                Ok(parsed) => if let Err(err) = check(&parsed) {
+                    self.error(field, err.to_string());
+                },
+                Err(err) => self.error(field, err.to_string()),
+            }
+        }
+        self
+    }
+
+    /// Every problem found, as [`ConversionError::InvalidFields`].
+    pub fn check(self) -> Result<(), ConversionError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConversionError::InvalidFields(self.errors))
+        }
+    }
+
+    fn value(&self, field: &str) -> Option<&'a Value> {
+        self.payload.get(field).filter(|value| !value.is_null())
+    }
+
+    /// Records the first problem of `field` only, since the next ones usually follow from it.
+    fn error(&mut self, field: &str, detail: String) {
+        if !self.errors.iter().any(|error| error.field == field) {
+            self.errors.push(FieldError { field: field.to_string(), detail });
+        }
+    }
+}
+
+
+/// The body of a request, deserialized once its [`Validator`] checks passed.
+pub trait Payload: DeserializeOwned {
+    /// The checks of the fields of the payload.
+    fn rules(validator: Validator<'_>) -> Validator<'_>;
+
+    /// The payload of `body`, or every field that is invalid. A field the checks let through but that does not
+    /// deserialize is reported with its path.
+    fn parse(body: Value) -> Result<Self, ConversionError> {
+        let Value::Object(payload) = body else {
+            return Err(ConversionError::InvalidFields(vec![FieldError { field: String::new(), detail: String::from("must be a JSON object") }]));
+        };
+        Self::rules(Validator::new(&payload)).check()?;
+        serde_path_to_error::deserialize(Value::Object(payload)).map_err(|err| {
+            let field = err.path().to_string();
+            ConversionError::InvalidFields(vec![FieldError { field, detail: err.into_inner().to_string() }])
+        })
+    }
+}
@@ -2,10 +2,16 @@
 use aws_sdk_dynamodb::types::AttributeValue;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
+use phonenumber::{Mode, PhoneNumber};
 use std::collections::HashMap;
 use super::ConversionError;
+#[cfg(feature = "dynamodb")]
+use super::PiiCipher;
 use std::borrow::Cow;
 
+/// A phone number, held in its E.164 form. eg `+14155552671`
+///
+/// Numbers stored before they were validated are read as they are. see [`Phone::check`].
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Phone {
     New(String),
@@ -17,29 +23,63 @@ struct PhoneData<'a> {
     phone: Cow<'a, str>,
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     phone_verified: bool,
+    /// the ISO 3166 code of the country of the number, derived from it when serializing and ignored when deserializing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    phone_country: Option<String>,
 }
 
 
 impl Phone {
-    fn valid(phone: &str) -> bool {
-        let mut count = 0;
-        let pos = phone.find("+");
-        
-        for i in phone.as_bytes(){
-            if i < &48 && i !=&43 || i > &57 && i != &43{
-                return false;
+    /// Parses a number written in international format, eg `+1 (415) 555-2671`, into its E.164 form.
+    ///
+    /// Numbers that are not assigned to a country's numbering plan are rejected.
+    fn normalize(phone: &str) -> Result<String, ConversionError> {
+        let number = Self::parse(phone)?;
+        if !number.is_valid() {
+            return Err(ConversionError::InvalidPhoneNumber);
+        }
+        Ok(number.format().mode(Mode::E164).to_string())
+    }
+
+    /// Reads a stored number. Numbers stored before they were validated, eg `1234567890`, are kept as they are when they
+    /// don't normalize, as long as they are digits with an optional leading `+`, rather than failing the read of their user.
+    fn stored(phone: &str) -> Result<String, ConversionError> {
+        Self::normalize(phone).or_else(|err| {
+            let digits = phone.strip_prefix('+').unwrap_or(phone);
+            if !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit()) {
+                Ok(phone.to_string())
             } else {
-                if i == &43{
-                    count+=1;
-                }
-            }
-            if pos != None{
-                if count > 1 || pos.unwrap() != 0{
-                    return false;
-                }
+                Err(err)
             }
+        })
+    }
+
+    fn parse(phone: &str) -> Result<PhoneNumber, ConversionError> {
+        phonenumber::parse(None, phone).map_err(|_| ConversionError::InvalidPhoneNumber)
+    }
+
+    /// The ISO 3166 code of the country of the number. eg `US`
+    pub fn country(&self) -> Option<String> {
+        let number = Self::parse(self.as_ref()).ok()?;
+        number.country().id().map(|id| id.as_ref().to_string())
+    }
+
+    /// Checks that the number is valid, as numbers given by users must be.
+    ///
+    /// Numbers read back are only checked leniently, so that those stored before numbers were validated can still be read.
+    pub fn check(&self) -> Result<(), ConversionError> {
+        Self::normalize(self.as_ref()).map(|_| ())
+    }
+
+    pub fn is_verified(&self) -> bool {
+        matches!(self, Phone::Verified(_))
+    }
+
+    /// The number, verified.
+    pub fn verified(self) -> Self {
+        match self {
+            Phone::New(number) | Phone::Verified(number) => Phone::Verified(number),
         }
-        true
     }
 }
 
@@ -47,13 +87,15 @@ impl Phone {
 impl<'a> From<&'a Phone> for PhoneData<'a> {
     fn from(phone: &'a Phone) -> Self {
         match phone {
-            Phone::New(phone) => PhoneData {
-                phone: Cow::Borrowed(phone),
+            Phone::New(number) => PhoneData {
+                phone: Cow::Borrowed(number),
                 phone_verified: false,
+                phone_country: phone.country(),
             },
-            Phone::Verified(phone) => PhoneData {
-                phone: Cow::Borrowed(phone),
+            Phone::Verified(number) => PhoneData {
+                phone: Cow::Borrowed(number),
                 phone_verified: true,
+                phone_country: phone.country(),
             },
         }
     }
@@ -64,13 +106,11 @@ impl<'a> TryFrom<PhoneData<'a>> for Phone {
     type Error = ConversionError;
 
     fn try_from(data: PhoneData<'a>) -> Result<Self, Self::Error> {
-        if !Self::valid(&data.phone) {
-            return Err(ConversionError::InvalidPhoneNumber);
-        }
+        let phone = Self::stored(&data.phone)?;
         if data.phone_verified {
-            Ok(Phone::Verified(data.phone.into_owned()))
+            Ok(Phone::Verified(phone))
         } else {
-            Ok(Phone::New(data.phone.into_owned()))
+            Ok(Phone::New(phone))
         }
     }
 }
@@ -79,27 +119,46 @@ impl TryFrom<String> for Phone {
     type Error = ConversionError;
 
     fn try_from(phone: String) -> Result<Self, Self::Error> {
-        if !Self::valid(&phone) {
-            return Err(ConversionError::InvalidPhoneNumber);
+        Ok(Phone::New(Self::normalize(&phone)?))
+    }
+}
+
+
+/// The value the number is stored and looked up with, its blind index when a [`PiiCipher`] is installed.
+#[cfg(feature = "dynamodb")]
+impl From<Phone> for AttributeValue {
+    fn from(phone: Phone) -> Self {
+        let number = PhoneData::from(&phone).phone;
+        match PiiCipher::installed() {
+            Some(cipher) => AttributeValue::S(cipher.blind("phone", &number)),
+            None => AttributeValue::S(number.into_owned()),
         }
-        Ok(Phone::New(phone))
     }
 }
 
 
+/// The number is sealed in `phone_pii` when a [`PiiCipher`] is installed, `phone` holding its blind index.
 #[cfg(feature = "dynamodb")]
 impl From<Phone> for HashMap<String, AttributeValue> {
     fn from(phone: Phone) -> Self {
         let data = PhoneData::from(&phone);
         let mut map = HashMap::new();
-        map.insert(
-            "phone".to_string(),
-            AttributeValue::S(data.phone.into_owned()),
-        );
+        match PiiCipher::installed() {
+            Some(cipher) => {
+                map.insert("phone".to_string(), AttributeValue::S(cipher.blind("phone", &data.phone)));
+                map.insert("phone_pii".to_string(), AttributeValue::S(cipher.seal("phone", &data.phone)));
+            },
+            None => {
+                map.insert("phone".to_string(), AttributeValue::S(data.phone.into_owned()));
+            },
+        }
         map.insert(
             "phone_verified".to_string(),
             AttributeValue::Bool(data.phone_verified),
         );
+        if let Some(country) = data.phone_country {
+            map.insert("phone_country".to_string(), AttributeValue::S(country));
+        }
         map
     }
 }
@@ -119,6 +178,12 @@ impl TryFrom<&mut HashMap<String, AttributeValue>> for Phone {
             },
             None => return Err(ConversionError::MissingField("phone"))
         };
+        // numbers stored before the cipher was installed are read in the clear.
+        let phone = match map.remove("phone_pii") {
+            Some(AttributeValue::S(envelope)) => PiiCipher::installed().ok_or(ConversionError::Undecryptable("phone"))?.open("phone", &envelope)?,
+            Some(_) => return Err(ConversionError::UnexpectedDataType("phone_pii")),
+            None => phone,
+        };
         let phone_verified = match map.remove("phone_verified") {
             Some(value) => {
                 match value {
@@ -128,9 +193,11 @@ impl TryFrom<&mut HashMap<String, AttributeValue>> for Phone {
             },
             None => false,
         };
+        map.remove("phone_country");
         let data = PhoneData {
             phone: Cow::Owned(phone),
             phone_verified,
+            phone_country: None,
         };
         data.try_into()
     }
@@ -184,21 +251,35 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_phone_valid() {
-        assert!(Phone::valid("+1234567890"));
-        assert!(Phone::valid("1234567890"));
-        assert!(!Phone::valid("123456789a"));
-        assert!(!Phone::valid("++1234567890"));
-        assert!(!Phone::valid("+1234567890+"));
-        assert!(!Phone::valid("1234567890+"));
-        assert!(!Phone::valid("+1234567890+"));
+    fn test_phone_normalization() {
+        assert_eq!(Phone::normalize("+1 (415) 555-2671"), Ok("+14155552671".to_string()));
+        assert_eq!(Phone::normalize("+254 712 345 678"), Ok("+254712345678".to_string()));
+        assert_eq!(Phone::normalize("4155552671"), Err(ConversionError::InvalidPhoneNumber));
+        assert_eq!(Phone::normalize("+1234567890"), Err(ConversionError::InvalidPhoneNumber));
+        assert_eq!(Phone::normalize("+1 415 555"), Err(ConversionError::InvalidPhoneNumber));
+        assert_eq!(Phone::try_from(String::from("+44 20 7946 0958")).unwrap().country(), Some("GB".to_string()));
+    }
+
+    #[test]
+    fn test_legacy_phone_numbers_are_read() {
+        for legacy in ["1234567890", "+1234567890"] {
+            let data = PhoneData { phone: legacy.into(), phone_verified: true, phone_country: None };
+            let phone = Phone::try_from(data).unwrap();
+            assert_eq!(phone, Phone::Verified(legacy.to_string()));
+            assert_eq!(phone.check(), Err(ConversionError::InvalidPhoneNumber));
+        }
+        for invalid in ["123456789a", "++1234567890", "+1234567890+", "1234567890+", ""] {
+            let data = PhoneData { phone: invalid.into(), phone_verified: false, phone_country: None };
+            assert_eq!(Phone::try_from(data), Err(ConversionError::InvalidPhoneNumber));
+        }
+        assert_eq!(Phone::try_from(String::from("1234567890")), Err(ConversionError::InvalidPhoneNumber));
     }
 
     #[test]
     fn test_phone_serialization_and_deserialization() {
-        let phone = Phone::New("+1234567890".to_string());
+        let phone = Phone::New("+14155552671".to_string());
         let serialized = serde_json::to_string(&phone).unwrap();
-        assert_eq!(serialized, r#"{"phone":"+1234567890"}"#);
+        assert_eq!(serialized, r#"{"phone":"+14155552671","phone_country":"US"}"#);
 
         let deserialized: Phone = serde_json::from_str(&serialized).unwrap();
         assert_eq!(phone, deserialized);
@@ -207,10 +288,11 @@ mod tests {
     #[test]
     fn test_email_from_valid_email_data() {
         let invalid_phone_data = PhoneData {
-            phone: "+19113456812".into(),
+            phone: "+1 415 555 2671".into(),
             phone_verified: false,
+            phone_country: None,
         };
-        Phone::try_from(invalid_phone_data).unwrap();
+        assert_eq!(Phone::try_from(invalid_phone_data), Ok(Phone::New("+14155552671".to_string())));
     }
 
     #[test]
@@ -218,6 +300,7 @@ mod tests {
         let invalid_phone_data = PhoneData {
             phone: "123456789a".into(),
             phone_verified: false,
+            phone_country: None,
         };
         let result = Phone::try_from(invalid_phone_data);
         assert!(result.is_err());
@@ -0,0 +1,219 @@
+#[cfg(feature = "dynamodb")]
+use aws_sdk_dynamodb::types::AttributeValue;
+use aes_gcm::aead::{Aead, AeadCore, Payload};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use std::collections::HashMap;
+use arc_swap::ArcSwapOption;
+use super::ConversionError;
+use hmac::{Hmac, Mac};
+use base64::Engine;
+use sha2::Sha256;
+use std::sync::Arc;
+
+
+/// the cipher of the process. see [`PiiCipher::install`].
+static CIPHER: ArcSwapOption<PiiCipher> = ArcSwapOption::const_empty();
+
+#[cfg(test)]
+thread_local! {
+    /// the cipher of the test running on the thread, so that the tests installing one don't install it for every other test too.
+    static TEST_CIPHER: std::cell::RefCell<Option<Arc<PiiCipher>>> = const { std::cell::RefCell::new(None) };
+}
+
+
+/// the first part of every envelope, the version of its format.
+const ENVELOPE: &str = "pii1";
+/// the prefix of blind indexes, which no email address or phone number starts with.
+pub const BLIND: &str = "hmac:";
+/// the length of the nonces of AES-GCM.
+const NONCE_LENGTH: usize = 12;
+/// the fields encrypted at rest. the envelope of each is stored in `<field>_pii`.
+pub const PII_FIELDS: [&str; 2] = ["email", "phone"];
+
+
+/// Field-level encryption of the emails and phone numbers of users, as the database adaptors store them.
+///
+/// Every value is sealed in an envelope of its own: encrypted under a random data key, itself encrypted under the active
+/// key encryption key (KEK), both with AES-256-GCM. The field keeps a blind index of the value, an HMAC of it, so that it
+/// is still looked up and claimed as unique without being stored in the clear.
+pub struct PiiCipher {
+    keks: HashMap<String, Aes256Gcm>,
+    active: String,
+    index_key: [u8; 32],
+}
+
+
+impl PiiCipher {
+    /// The cipher sealing with the KEK `active` of `keks`, if it is one of them.
+    pub fn new(keks: HashMap<String, [u8; 32]>, active: String, index_key: [u8; 32]) -> Option<Self> {
+        if !keks.contains_key(&active) {
+            return None;
+        }
+        let keks = keks.into_iter().map(|(id, key)| (id, Aes256Gcm::new(&key.into()))).collect();
+        Some(Self { keks, active, index_key })
+    }
+
+    /// Sets the cipher of the whole process, `None` storing contacts in the clear. meant to be called once at startup.
+    pub fn install(cipher: Option<PiiCipher>) {
+        CIPHER.store(cipher.map(Arc::new));
+    }
+
+    /// The cipher set with [`PiiCipher::install`].
+    pub fn installed() -> Option<Arc<PiiCipher>> {
+        #[cfg(test)]
+        if let Some(cipher) = TEST_CIPHER.with_borrow(Clone::clone) {
+            return Some(cipher);
+        }
+        CIPHER.load_full()
+    }
+
+    /// Installs `cipher` for the current thread only, which is the whole of a `#[tokio::test]` on its default runtime.
+    #[cfg(test)]
+    pub(crate) fn install_for_test(cipher: PiiCipher) {
+        TEST_CIPHER.set(Some(Arc::new(cipher)));
+    }
+
+    /// The blind index of `value` of `field`, the same for the same value whatever the KEK.
+    ///
+    /// Values are lowercased first, so that values differing only in case have the same index and claim the same value,
+    /// like the values stored in the clear do.
+    pub fn blind(&self, field: &str, value: &str) -> String {
+        // the key is 32 bytes, which HMAC takes.
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.index_key).expect("an HMAC key of any length");
+        mac.update(field.as_bytes());
+        mac.update(&[0]);
+        mac.update(value.to_lowercase().as_bytes());
+        format!("{}{}", BLIND, URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()))
+    }
+
+    /// Whether `value` is a blind index rather than a value stored in the clear.
+    pub fn is_blind(value: &str) -> bool {
+        value.starts_with(BLIND)
+    }
+
+    /// Seals `value` of `field` in an envelope, under a new data key encrypted with the active KEK.
+    pub fn seal(&self, field: &str, value: &str) -> String {
+        let data_key = Aes256Gcm::generate_key(aes_gcm::aead::OsRng);
+        let data = encrypt(&Aes256Gcm::new(&data_key), field, value.as_bytes());
+        let wrapped = encrypt(&self.keks[&self.active], field, data_key.as_slice());
+        format!("{}.{}.{}.{}", ENVELOPE, self.active, URL_SAFE_NO_PAD.encode(wrapped), URL_SAFE_NO_PAD.encode(data))
+    }
+
+    /// The value of `field` sealed in `envelope`.
+    pub fn open(&self, field: &'static str, envelope: &str) -> Result<String, ConversionError> {
+        let (_, data_key, data) = self.parts(field, envelope)?;
+        let value = decrypt(&Aes256Gcm::new_from_slice(&data_key).map_err(|_| ConversionError::Undecryptable(field))?, field, &data).ok_or(ConversionError::Undecryptable(field))?;
+        String::from_utf8(value).map_err(|_| ConversionError::Undecryptable(field))
+    }
+
+    /// The envelope with its data key encrypted with the active KEK, if it was encrypted with another. The value is left as it is.
+    pub fn rewrap(&self, field: &'static str, envelope: &str) -> Result<Option<String>, ConversionError> {
+        let (kek, data_key, data) = self.parts(field, envelope)?;
+        if kek == self.active {
+            return Ok(None);
+        }
+        let wrapped = encrypt(&self.keks[&self.active], field, &data_key);
+        Ok(Some(format!("{}.{}.{}.{}", ENVELOPE, self.active, URL_SAFE_NO_PAD.encode(wrapped), URL_SAFE_NO_PAD.encode(data))))
+    }
+
+    /// Seals the emails and phone numbers among the attributes of an update, leaving their blind indexes in their place.
+    /// Removing one removes its envelope.
+    #[cfg(feature = "dynamodb")]
+    pub fn seal_attributes(&self, attributes: &mut HashMap<String, AttributeValue>) {
+        for field in PII_FIELDS {
+            let envelope = match attributes.get_mut(field) {
+                Some(AttributeValue::S(value)) => {
+                    let envelope = AttributeValue::S(self.seal(field, value));
+                    *value = self.blind(field, value);
+                    envelope
+                },
+                Some(AttributeValue::Null(_)) => AttributeValue::Null(true),
+                _ => continue,
+            };
+            attributes.insert(format!("{}_pii", field), envelope);
+        }
+    }
+
+    /// The KEK of `envelope`, with its data key decrypted and its encrypted value.
+    fn parts<'a>(&self, field: &'static str, envelope: &'a str) -> Result<(&'a str, Vec<u8>, Vec<u8>), ConversionError> {
+        let mut parts = envelope.split('.');
+        let (Some(ENVELOPE), Some(kek), Some(wrapped), Some(data), None) = (parts.next(), parts.next(), parts.next(), parts.next(), parts.next()) else {
+            return Err(ConversionError::Undecryptable(field));
+        };
+        // a KEK removed from the configuration before every record moved off it leaves them unreadable.
+        let cipher = self.keks.get(kek).ok_or(ConversionError::Undecryptable(field))?;
+        let wrapped = URL_SAFE_NO_PAD.decode(wrapped).map_err(|_| ConversionError::Undecryptable(field))?;
+        let data = URL_SAFE_NO_PAD.decode(data).map_err(|_| ConversionError::Undecryptable(field))?;
+        let data_key = decrypt(cipher, field, &wrapped).ok_or(ConversionError::Undecryptable(field))?;
+        Ok((kek, data_key, data))
+    }
+}
+
+
+/// The random nonce followed by the ciphertext of `plaintext`, authenticated along with `field`
+/// so that the envelope of one field is never read as another.
+fn encrypt(cipher: &Aes256Gcm, field: &str, plaintext: &[u8]) -> Vec<u8> {
+    let nonce = Aes256Gcm::generate_nonce(aes_gcm::aead::OsRng);
+    // encrypting into a vector only fails on plaintexts of gigabytes.
+    let ciphertext = cipher.encrypt(&nonce, Payload { msg: plaintext, aad: field.as_bytes() }).expect("a plaintext of a few bytes");
+    let mut sealed = nonce.to_vec();
+    sealed.extend(ciphertext);
+    sealed
+}
+
+
+fn decrypt(cipher: &Aes256Gcm, field: &str, sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < NONCE_LENGTH {
+        return None;
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+    cipher.decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: field.as_bytes() }).ok()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher(active: &str) -> PiiCipher {
+        let keks = HashMap::from([(String::from("2026-09"), [1; 32]), (String::from("2026-10"), [2; 32])]);
+        PiiCipher::new(keks, String::from(active), [3; 32]).unwrap()
+    }
+
+    #[test]
+    fn test_values_are_sealed_and_rewrapped() {
+        let (old, new) = (cipher("2026-09"), cipher("2026-10"));
+        let envelope = old.seal("email", "jane@acme.com");
+        assert!(!envelope.contains("jane"));
+        assert_ne!(envelope, old.seal("email", "jane@acme.com"));
+        assert_eq!(new.open("email", &envelope), Ok(String::from("jane@acme.com")));
+        assert_eq!(new.open("phone", &envelope), Err(ConversionError::Undecryptable("phone")));
+        let rewrapped = new.rewrap("email", &envelope).unwrap().unwrap();
+        assert!(rewrapped.starts_with("pii1.2026-10."));
+        assert_eq!(new.rewrap("email", &rewrapped), Ok(None));
+        assert_eq!(new.open("email", &rewrapped), Ok(String::from("jane@acme.com")));
+        assert_eq!(old.blind("email", "jane@acme.com"), new.blind("email", "jane@acme.com"));
+        assert_ne!(new.blind("email", "jane@acme.com"), new.blind("phone", "jane@acme.com"));
+        assert_eq!(new.blind("email", "Jane@ACME.com"), new.blind("email", "jane@acme.com"));
+        assert!(PiiCipher::new(HashMap::new(), String::from("2026-10"), [3; 32]).is_none());
+    }
+
+    #[cfg(feature = "dynamodb")]
+    #[test]
+    fn test_updates_are_sealed() {
+        let cipher = cipher("2026-10");
+        let mut attributes = HashMap::from([
+            (String::from("email"), AttributeValue::S(String::from("jane@acme.com"))),
+            (String::from("phone"), AttributeValue::Null(true)),
+            (String::from("fullname"), AttributeValue::S(String::from("Jane"))),
+        ]);
+        cipher.seal_attributes(&mut attributes);
+        assert_eq!(attributes["email"], AttributeValue::S(cipher.blind("email", "jane@acme.com")));
+        let envelope = attributes["email_pii"].as_s().unwrap();
+        assert_eq!(cipher.open("email", envelope), Ok(String::from("jane@acme.com")));
+        assert_eq!(attributes["phone_pii"], AttributeValue::Null(true));
+        assert_eq!(attributes["fullname"], AttributeValue::S(String::from("Jane")));
+        assert!(!attributes.contains_key("fullname_pii"));
+    }
+}
@@ -0,0 +1,118 @@
+use super::{Error, ErrorCode, ConversionError};
+use serde::{Deserialize, Serialize};
+
+
+/// the URN the `type` of problems is under.
+const TYPE_PREFIX: &str = "urn:hiveguard:problem:";
+
+
+/// An error as an `application/problem+json` body (RFC 7807).
+///
+/// `type` and `code` are stable and meant to be branched on, `title` summarizes them and `detail` explains this occurrence.
+/// Internal errors keep their details to themselves, they are for the logs and the `ErrorReport`, not for the client.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Problem {
+    /// eg `urn:hiveguard:problem:invalid-credentials`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub code: ErrorCode,
+    pub title: String,
+    /// the HTTP status code of the response.
+    pub status: u16,
+    pub detail: String,
+    /// the fields of the request that are invalid, for validation errors.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+
+/// A field of the request a [`Problem`] is about.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub detail: String,
+}
+
+
+impl Problem {
+    pub const CONTENT_TYPE: &str = "application/problem+json";
+
+    pub fn new(code: ErrorCode, detail: String) -> Self {
+        let kind = format!("{}{}", TYPE_PREFIX, code.kind());
+        Self { kind, code, title: code.title().to_string(), status: code.status(), detail, errors: Vec::new() }
+    }
+
+    fn with_fields(mut self, fields: &[&str]) -> Self {
+        self.errors.extend(fields.iter().map(|field| FieldError { field: field.to_string(), detail: self.detail.clone() }));
+        self
+    }
+}
+
+
+impl From<&Error> for Problem {
+    fn from(err: &Error) -> Self {
+        let code = err.code();
+        match err {
+            _ if code == ErrorCode::Internal => Problem::new(code, String::from("the request could not be completed, it has been reported")),
+            Error::WrongPassword => Problem::new(code, Error::InvalidCredentials.to_string()),
+            Error::InvalidUsername(_) => Problem::new(code, err.to_string()).with_fields(&["username"]),
+            Error::ConversionError(err) => Problem::from(err),
+            _ => Problem::new(code, err.to_string()),
+        }
+    }
+}
+
+
+impl From<&ConversionError> for Problem {
+    fn from(err: &ConversionError) -> Self {
+        let problem = Problem::new(err.code(), err.to_string());
+        match err {
+            ConversionError::UnexpectedDataType(field) | ConversionError::MissingField(field) | ConversionError::ImmutableField(field) => problem.with_fields(&[field]),
+            ConversionError::MissingFields(fields) => problem.with_fields(fields),
+            ConversionError::UnknownField(field) => problem.with_fields(&[field]),
+            ConversionError::InvalidEmailAddress => problem.with_fields(&["email"]),
+            ConversionError::InvalidPhoneNumber => problem.with_fields(&["phone"]),
+            ConversionError::InvalidMetadataKey(_) | ConversionError::MetadataTooLarge(_) => problem.with_fields(&["metadata"]),
+            ConversionError::InvalidFields(errors) => Problem { errors: errors.clone(), ..problem },
+            _ => problem,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DatabaseError;
+    use serde_json::json;
+
+    #[test]
+    fn test_errors_become_problems() {
+        let problem = Problem::from(&Error::ConversionError(ConversionError::MissingFields(&["username", "fullname"])));
+        assert_eq!(serde_json::to_value(&problem).unwrap(), json!({
+            "type": "urn:hiveguard:problem:invalid-request",
+            "code": "REQ_001_INVALID",
+            "title": "Invalid request",
+            "status": 400,
+            "detail": "missing fields: username, fullname",
+            "errors": [
+                {"field": "username", "detail": "missing fields: username, fullname"},
+                {"field": "fullname", "detail": "missing fields: username, fullname"},
+            ],
+        }));
+        assert_eq!(Problem::from(&Error::WrongPassword), Problem::from(&Error::InvalidCredentials));
+        assert_eq!(Problem::from(&Error::PreconditionFailed).status, 412);
+        assert!(serde_json::to_value(Problem::from(&Error::SessionExpired)).unwrap().get("errors").is_none());
+        let errors = vec![FieldError { field: String::from("avatar"), detail: String::from("relative URL without a base") }];
+        let problem = Problem::from(&Error::ConversionError(ConversionError::InvalidFields(errors.clone())));
+        assert_eq!((problem.status, problem.errors), (400, errors));
+    }
+
+    #[test]
+    fn test_internal_errors_keep_their_details() {
+        let err = Error::DatabaseError(DatabaseError::Internal("connection refused to 10.0.0.1".into()));
+        let problem = Problem::from(&err);
+        assert_eq!((problem.status, problem.kind.as_str()), (500, "urn:hiveguard:problem:internal"));
+        assert!(!problem.detail.contains("10.0.0.1"));
+    }
+}
@@ -0,0 +1,108 @@
+use serde::{Serialize, Deserialize};
+use chrono::{Utc, DateTime};
+use super::{Id, Status, Location};
+
+
+/// A security relevant event meant for audit and SIEM tooling.
+///
+/// Events never carry credentials or contact details, only identifiers.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SecurityEvent {
+    pub id: Id,
+    #[serde(flatten)]
+    pub kind: SecurityEventKind,
+    pub user_id: Option<Id>,
+    /// where the request behind the event came from, when it was located.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SecurityEventKind {
+    FailedLogin { reason: LoginFailure },
+    Lockout { until: DateTime<Utc> },
+    MfaDisabled,
+    /// a refresh token that was already rotated has been presented again.
+    TokenReuseDetected { session_id: Id },
+    /// an admin changed the status of the account of `user_id`.
+    StatusChanged { status: Status, reason: Option<String>, changed_by: Option<Id> },
+    /// the user logged out everywhere but the session `kept`.
+    SessionsRevoked { count: usize, kept: Option<Id> },
+    /// the erasure of the account was requested by its owner or the admin `requested_by`. it happens at `erase_after`.
+    ErasureRequested { requested_by: Option<Id>, erase_after: DateTime<Utc> },
+    /// the personal data of the account was erased. the proof that it happened.
+    AccountErased { sessions: usize },
+    /// the account was merged into the account `into` by the admin `merged_by`, handing over its sessions.
+    AccountMerged { into: Id, sessions: usize, merged_by: Option<Id> },
+    /// the login did not match the recent history of the user, and was refused until stepped up when `challenged`.
+    AnomalousLogin { anomalies: Vec<Anomaly>, challenged: bool },
+}
+
+
+/// How a login differs from the recent history of the user.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Anomaly {
+    /// the user would have travelled from `from`, where they last were, at `speed_kmh`.
+    ImpossibleTravel { from: Location, speed_kmh: u32 },
+    /// the user never logged in from `country` recently.
+    NewCountry { country: String },
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginFailure {
+    UnknownUser,
+    WrongPassword,
+    /// the password is right but the account is suspended or deactivated.
+    AccountDisabled,
+    /// the password is right but has to be reset before it is used again.
+    PasswordResetRequired,
+}
+
+
+impl SecurityEvent {
+    pub fn new(kind: SecurityEventKind, user_id: Option<Id>) -> Self {
+        Self { id: Id::default(), kind, user_id, location: None, occurred_at: Utc::now() }
+    }
+
+    /// The event, annotated with the location of the request behind it.
+    pub fn located(self, location: Option<Location>) -> Self {
+        Self { location, ..self }
+    }
+
+    /// the name of the event as it appears in the serialized `type` field.
+    pub fn name(&self) -> &'static str {
+        match self.kind {
+            SecurityEventKind::FailedLogin { .. } => "failed_login",
+            SecurityEventKind::Lockout { .. } => "lockout",
+            SecurityEventKind::MfaDisabled => "mfa_disabled",
+            SecurityEventKind::TokenReuseDetected { .. } => "token_reuse_detected",
+            SecurityEventKind::StatusChanged { .. } => "status_changed",
+            SecurityEventKind::SessionsRevoked { .. } => "sessions_revoked",
+            SecurityEventKind::ErasureRequested { .. } => "erasure_requested",
+            SecurityEventKind::AccountErased { .. } => "account_erased",
+            SecurityEventKind::AccountMerged { .. } => "account_merged",
+            SecurityEventKind::AnomalousLogin { .. } => "anomalous_login",
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialized_events_are_flat() {
+        let event = SecurityEvent::new(SecurityEventKind::FailedLogin { reason: LoginFailure::WrongPassword }, None);
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["type"], event.name());
+        assert_eq!(value["reason"], "wrong_password");
+        assert_eq!(value["user_id"], serde_json::Value::Null);
+    }
+}
@@ -1,20 +1,51 @@
 #[cfg(feature = "dynamodb")]
 use aws_sdk_dynamodb::types::AttributeValue;
 use serde::{Serialize, Deserialize};
-use super::{ConversionError, Id, };
+use super::{ConversionError, Id, Agent, Location};
 use crate::create_date_from_map;
 use std::collections::HashMap;
 use chrono::{Utc, DateTime};
+use std::net::IpAddr;
 
 // The Session struct represents a user session in the system.
+// Every token issued for a session carries its id as the `sid` claim.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Session {
     pub id: Id,
     pub user_id: Id,
     pub refresh_token_id: Id,
     pub previous_refresh_token_id: Option<Id>,
+    /// where the session was started from.
+    #[serde(flatten)]
+    pub device: Device,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// the last time a token of the session was used. see `SessionsTable::touch_session`.
+    pub last_active_at: DateTime<Utc>,
+}
+
+
+/// The client a session was started from, as seen by the request that started it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Device {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<IpAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// what `user_agent` tells about the device, parsed when the session started.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<Agent>,
+    /// where `ip` was when the session started. see `Geolocation::locate`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+}
+
+
+impl Device {
+    pub fn new(ip: Option<IpAddr>, user_agent: Option<String>) -> Self {
+        let agent = user_agent.as_deref().map(Agent::parse);
+        Self { ip, user_agent, agent, location: None }
+    }
 }
 
 
@@ -28,8 +59,21 @@ impl From<Session> for HashMap<String, AttributeValue> {
         if let Some(previous_refresh_token_id) = session.previous_refresh_token_id {
             map.insert("previous_refresh_token_id".into(), previous_refresh_token_id.into());
         }
+        if let Some(ip) = session.device.ip {
+            map.insert("ip".into(), AttributeValue::S(ip.to_string()));
+        }
+        if let Some(user_agent) = session.device.user_agent {
+            map.insert("user_agent".into(), AttributeValue::S(user_agent));
+        }
+        if let Some(agent) = session.device.agent {
+            map.insert("agent".into(), AttributeValue::S(serde_json::json!(agent).to_string()));
+        }
+        if let Some(location) = session.device.location {
+            map.insert("location".into(), AttributeValue::S(serde_json::json!(location).to_string()));
+        }
         map.insert("created_at".into(), AttributeValue::N(session.created_at.timestamp().to_string()));
         map.insert("updated_at".into(), AttributeValue::N(session.updated_at.timestamp().to_string()));
+        map.insert("last_active_at".into(), AttributeValue::N(session.last_active_at.timestamp().to_string()));
         map
     }
 }
@@ -46,19 +90,75 @@ impl TryFrom<HashMap<String, AttributeValue>> for Session {
             Some(value) => Some(value.try_into()?),
             None => None
         };
+        let ip = match map.remove("ip") {
+            None => None,
+            Some(AttributeValue::S(ip)) => Some(ip.parse().map_err(|_| ConversionError::UnexpectedDataType("ip"))?),
+            Some(_) => return Err(ConversionError::UnexpectedDataType("ip")),
+        };
+        let user_agent = match map.remove("user_agent") {
+            None => None,
+            Some(AttributeValue::S(user_agent)) => Some(user_agent),
+            Some(_) => return Err(ConversionError::UnexpectedDataType("user_agent")),
+        };
+        let agent = match map.remove("agent") {
+            None => None,
+            Some(AttributeValue::S(json)) => Some(serde_json::from_str(&json).map_err(|_| ConversionError::UnexpectedDataType("agent"))?),
+            Some(_) => return Err(ConversionError::UnexpectedDataType("agent")),
+        };
+        let location = match map.remove("location") {
+            None => None,
+            Some(AttributeValue::S(json)) => Some(serde_json::from_str(&json).map_err(|_| ConversionError::UnexpectedDataType("location"))?),
+            Some(_) => return Err(ConversionError::UnexpectedDataType("location")),
+        };
         let created_at = created_at_date_from_map(&mut map)?;
         let updated_at = updated_at_date_from_map(&mut map)?;
+        // sessions started before activity was tracked were last active when they were last updated, as far as we know.
+        let last_active_at = if map.contains_key("last_active_at") {
+            last_active_at_date_from_map(&mut map)?
+        } else {
+            updated_at
+        };
         Ok(Self {
             id,
             user_id,
             refresh_token_id,
             previous_refresh_token_id,
+            device: Device { ip, user_agent, agent, location },
             created_at,
             updated_at,
+            last_active_at,
         })
     }
 }
 
 
 create_date_from_map!(created_at_date_from_map, "created_at");
-create_date_from_map!(updated_at_date_from_map, "updated_at");
\ No newline at end of file
+create_date_from_map!(updated_at_date_from_map, "updated_at");
+create_date_from_map!(last_active_at_date_from_map, "last_active_at");
+
+#[cfg(all(test, feature = "dynamodb"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attributes() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let session = Session {
+            id: Id::try_from(String::from("000000000000000000000001")).unwrap(),
+            user_id: Id::try_from(String::from("000000000000000000000002")).unwrap(),
+            refresh_token_id: Id::try_from(String::from("000000000000000000000003")).unwrap(),
+            previous_refresh_token_id: None,
+            device: Device {
+                location: Some(Location { country: Some("KE".into()), city: Some("Nairobi".into()), latitude: Some(-1.28), longitude: Some(36.82) }),
+                ..Device::new(Some("2001:db8::1".parse().unwrap()), Some(String::from("Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0")))
+            },
+            created_at: now,
+            updated_at: now,
+            last_active_at: now + chrono::TimeDelta::minutes(5),
+        };
+        let mut map = HashMap::<String, AttributeValue>::from(session.clone());
+        assert_eq!(Session::try_from(map.clone()).unwrap(), session);
+        map.remove("last_active_at");
+        assert_eq!(Session::try_from(map).unwrap().last_active_at, session.updated_at);
+    }
+}
@@ -0,0 +1,59 @@
+use serde::{Serialize, Deserialize};
+use std::fmt::{Debug, Formatter};
+use chrono::{Utc, DateTime};
+
+
+/// How a [`SigningKey`] signs tokens.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyAlgorithm {
+    /// an Ed25519 key pair, signing tokens under `v4.public`.
+    #[default]
+    Ed25519,
+}
+
+
+/// Where a [`SigningKey`] is in its life, from its creation to its retirement.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyStatus {
+    /// published, so that resource servers know it before it signs anything.
+    Scheduled,
+    /// the key new tokens are signed with.
+    Active,
+    /// replaced by a newer key, but published while the tokens it signed are in use.
+    Superseded,
+    /// no longer published. the tokens it signed are refused.
+    Retired,
+}
+
+
+/// A key of the keyring tokens are signed with under `v4.public`. see `Keyring`.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct SigningKey {
+    /// the key id of the key in the key set of hiveguard.
+    pub kid: String,
+    pub algorithm: KeyAlgorithm,
+    /// the hex encoded 64 byte key pair, secret key first.
+    pub key_pair: String,
+    /// the hex encoded 32 byte public key.
+    pub public_key: String,
+    pub created_at: DateTime<Utc>,
+    /// when the key starts signing tokens, unless a key activating later is active by then.
+    pub activates_at: DateTime<Utc>,
+    pub retired_at: Option<DateTime<Utc>>,
+}
+
+
+impl Debug for SigningKey {
+    /// Leaves the key pair out, so that keys can be logged.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigningKey")
+            .field("kid", &self.kid)
+            .field("algorithm", &self.algorithm)
+            .field("public_key", &self.public_key)
+            .field("created_at", &self.created_at)
+            .field("activates_at", &self.activates_at)
+            .field("retired_at", &self.retired_at)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use super::ConversionError;
+
+
+/// Where an account is in its lifecycle.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// signed up but its email or phone is not verified yet.
+    PendingVerification,
+    #[default]
+    Active,
+    /// locked by an admin, eg after abuse. it can be reactivated.
+    Suspended,
+    /// closed, by its owner or an admin.
+    Deactivated,
+    /// the erasure of the account was requested. it is erased once the grace period ends, unless reactivated before.
+    PendingErasure,
+}
+
+
+impl Status {
+    /// Whether the account can log in.
+    pub fn can_login(&self) -> bool {
+        matches!(self, Status::PendingVerification | Status::Active)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::PendingVerification => "pending_verification",
+            Status::Active => "active",
+            Status::Suspended => "suspended",
+            Status::Deactivated => "deactivated",
+            Status::PendingErasure => "pending_erasure",
+        }
+    }
+}
+
+
+impl Display for Status {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+
+impl TryFrom<&str> for Status {
+    type Error = ConversionError;
+
+    fn try_from(status: &str) -> Result<Self, Self::Error> {
+        match status {
+            "pending_verification" => Ok(Status::PendingVerification),
+            "active" => Ok(Status::Active),
+            "suspended" => Ok(Status::Suspended),
+            "deactivated" => Ok(Status::Deactivated),
+            "pending_erasure" => Ok(Status::PendingErasure),
+            _ => Err(ConversionError::UnexpectedDataType("status")),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_names() {
+        for status in [Status::PendingVerification, Status::Active, Status::Suspended, Status::Deactivated, Status::PendingErasure] {
+            assert_eq!(serde_json::to_value(status).unwrap(), status.as_str());
+            assert_eq!(Status::try_from(status.as_str()), Ok(status));
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use super::ConversionError;
+
+
+/// The id of a tenant, a customer realm whose users, sessions and verifications are kept apart from those of the others.
+///
+/// Ids are 1 to 32 lowercase letters, digits and `-`, so that they can prefix the names of the tables of the tenant.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(try_from = "String", into = "String")]
+pub struct TenantId(String);
+
+
+impl TenantId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+
+impl TryFrom<String> for TenantId {
+    type Error = ConversionError;
+
+    fn try_from(id: String) -> Result<Self, Self::Error> {
+        let valid = id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if !(1..=32).contains(&id.len()) || !valid {
+            return Err(ConversionError::InvalidTenant(id));
+        }
+        Ok(Self(id))
+    }
+}
+
+
+impl TryFrom<&str> for TenantId {
+    type Error = ConversionError;
+
+    fn try_from(id: &str) -> Result<Self, Self::Error> {
+        Self::try_from(id.to_string())
+    }
+}
+
+
+impl From<TenantId> for String {
+    fn from(id: TenantId) -> Self {
+        id.0
+    }
+}
+
+
+impl Display for TenantId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_can_prefix_table_names() {
+        assert_eq!(TenantId::try_from("acme-eu1").unwrap().as_str(), "acme-eu1");
+        for id in ["", "Acme", "acme.eu", "acme_eu", &"a".repeat(33)] {
+            assert_eq!(TenantId::try_from(id), Err(ConversionError::InvalidTenant(id.to_string())));
+        }
+        assert!(serde_json::from_value::<TenantId>(serde_json::json!("ACME")).is_err());
+    }
+}
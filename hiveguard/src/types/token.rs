@@ -44,4 +44,13 @@ impl Audience {
             Audience::Many(aud) => aud.is_empty()
         }
     }
+
+    /// Whether the token was issued for `audience`.
+    pub fn contains(&self, audience: &str) -> bool {
+        match self {
+            Audience::None => false,
+            Audience::One(aud) => aud == audience,
+            Audience::Many(aud) => aud.iter().any(|aud| aud == audience)
+        }
+    }
 }
\ No newline at end of file
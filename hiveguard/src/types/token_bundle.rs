@@ -1,8 +1,13 @@
 use serde::{Serialize, Deserialize};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 
 
+/// The tokens issued to a user, serialized as an OAuth 2.0 access token response (RFC 6749 section 5.1).
+///
+/// The expiry is sent as `expires_in`, the seconds left until the access token expires,
+/// so that clients schedule its renewal without relying on their clock agreeing with the server's.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "Response", into = "Response")]
 pub struct TokenBundle {
     pub access_token: String,
     pub refresh_token: String,
@@ -10,4 +15,78 @@ pub struct TokenBundle {
     pub scope: Option<String>,
     pub id_token: Option<String>,
     pub expires_at: DateTime<Utc>,
-}
\ No newline at end of file
+}
+
+
+impl TokenBundle {
+    /// The seconds left at `now` until the access token expires, 0 once it expired.
+    pub fn expires_in(&self, now: DateTime<Utc>) -> i64 {
+        (self.expires_at - now).num_seconds().max(0)
+    }
+}
+
+
+#[derive(Serialize, Deserialize)]
+struct Response {
+    access_token: String,
+    token_type: String,
+    expires_in: i64,
+    refresh_token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id_token: Option<String>,
+}
+
+
+impl From<TokenBundle> for Response {
+    fn from(bundle: TokenBundle) -> Self {
+        Self {
+            expires_in: bundle.expires_in(Utc::now()),
+            access_token: bundle.access_token,
+            token_type: bundle.token_type,
+            refresh_token: bundle.refresh_token,
+            scope: bundle.scope,
+            id_token: bundle.id_token,
+        }
+    }
+}
+
+
+impl From<Response> for TokenBundle {
+    fn from(response: Response) -> Self {
+        Self {
+            access_token: response.access_token,
+            refresh_token: response.refresh_token,
+            token_type: response.token_type,
+            scope: response.scope,
+            id_token: response.id_token,
+            expires_at: Utc::now() + TimeDelta::seconds(response.expires_in),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_bundles_are_sent_with_the_seconds_left() {
+        let bundle = TokenBundle {
+            access_token: "access".into(),
+            refresh_token: "refresh".into(),
+            token_type: "Bearer".into(),
+            scope: Some("guest".into()),
+            id_token: None,
+            expires_at: Utc::now() + TimeDelta::seconds(901),
+        };
+        let mut value = serde_json::to_value(&bundle).unwrap();
+        let expires_in = value.as_object_mut().unwrap().remove("expires_in").and_then(|value| value.as_i64()).unwrap();
+        assert!((899..=901).contains(&expires_in));
+        assert_eq!(value, json!({"access_token": "access", "token_type": "Bearer", "refresh_token": "refresh", "scope": "guest"}));
+        let expired = TokenBundle { expires_at: Utc::now() - TimeDelta::seconds(1), ..bundle };
+        assert_eq!(serde_json::to_value(&expired).unwrap()["expires_in"], Value::from(0));
+    }
+}
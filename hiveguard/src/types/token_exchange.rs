@@ -0,0 +1,99 @@
+use serde::{Serialize, Deserialize};
+use super::{Payload, Validator, Token};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+
+/// The `grant_type` of token exchange requests (RFC 8693).
+pub const TOKEN_EXCHANGE_GRANT: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+/// The type of access tokens, the only tokens exchanged and exchanged for.
+pub const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+
+/// The parameters of a token exchange request, but for its `grant_type`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TokenExchangeRequest {
+    /// the token of the user the new token is issued for.
+    pub subject_token: String,
+    pub subject_token_type: String,
+    #[serde(default)]
+    pub requested_token_type: Option<String>,
+    /// where the new token is used. the service asking for it when missing.
+    #[serde(default)]
+    pub audience: Option<String>,
+    /// the scope of the new token, within the scope of the subject token.
+    #[serde(default)]
+    pub scope: Option<String>,
+}
+
+
+impl Payload for TokenExchangeRequest {
+    fn rules(validator: Validator<'_>) -> Validator<'_> {
+        validator.required("subject_token").required("subject_token_type")
+    }
+}
+
+
+/// The token issued by a token exchange, serialized as the response of RFC 8693 section 2.2.1.
+///
+/// Exchanged tokens are never refreshed: the service exchanges the token of the user again.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(into = "Response")]
+pub struct ExchangedToken {
+    pub access_token: String,
+    pub scope: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+
+impl ExchangedToken {
+    /// The response issuing `token`, signed or encrypted as `access_token`.
+    pub fn new(access_token: String, token: &Token) -> Self {
+        let scope = token.claims.get("scope").and_then(Value::as_str).map(String::from);
+        Self { access_token, scope, expires_at: token.expiration }
+    }
+}
+
+
+#[derive(Serialize)]
+struct Response {
+    access_token: String,
+    issued_token_type: &'static str,
+    token_type: &'static str,
+    expires_in: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+}
+
+
+impl From<ExchangedToken> for Response {
+    fn from(token: ExchangedToken) -> Self {
+        Self {
+            access_token: token.access_token,
+            issued_token_type: ACCESS_TOKEN_TYPE,
+            token_type: "Bearer",
+            expires_in: (token.expires_at - Utc::now()).num_seconds().max(0),
+            scope: token.scope,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Map};
+    use chrono::TimeDelta;
+
+    #[test]
+    fn test_exchanged_tokens_are_sent_as_rfc_8693_responses() {
+        let claims = Map::from_iter([(String::from("scope"), Value::from("profile"))]);
+        let token = Token { expiration: Utc::now() + TimeDelta::seconds(61), claims, ..Default::default() };
+        let mut value = serde_json::to_value(ExchangedToken::new(String::from("access"), &token)).unwrap();
+        let expires_in = value.as_object_mut().unwrap().remove("expires_in").and_then(|value| value.as_i64()).unwrap();
+        assert!((59..=61).contains(&expires_in));
+        assert_eq!(value, json!({"access_token": "access", "issued_token_type": ACCESS_TOKEN_TYPE, "token_type": "Bearer", "scope": "profile"}));
+        let missing = TokenExchangeRequest::parse(json!({"subject_token": "token", "audience": "reports"}));
+        assert!(matches!(missing, Err(crate::types::ConversionError::InvalidFields(fields)) if fields.len() == 1 && fields[0].field == "subject_token_type"));
+    }
+}
@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+
+
+/// Where and how a client uploads a file straight to the storage, without going through hiveguard.
+///
+/// The file is sent with a `PUT` to `url` along with every header of `headers`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Upload {
+    /// the key the file is stored under, to confirm the upload with.
+    pub key: String,
+    pub url: String,
+    pub headers: HashMap<String, String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+
+/// What the storage knows of a stored file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredObject {
+    pub size: u64,
+    pub content_type: Option<String>,
+}
@@ -1,26 +1,87 @@
-use super::{ConversionError, Id, Login};
+use super::{ConversionError, Consents, Id, Login, Metadata, Status, Validator};
+#[cfg(feature = "dynamodb")]
+use super::{Document, Namespace};
 #[cfg(feature = "dynamodb")]
 use aws_sdk_dynamodb::types::AttributeValue;
 use serde::{Deserialize, Serialize};
 use crate::create_date_from_map;
 use std::collections::HashMap;
 use chrono::{DateTime, Utc};
+use macros::Patch;
+use url::Url;
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Patch)]
 pub struct User {
+    #[patch(immutable)]
     pub id: Id,
+    /// changed through `Profiles::rename`.
     pub username: String,
+    /// the username before the last change, held for the user while `usernames.hold_days` run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username_changed_at: Option<DateTime<Utc>>,
     pub fullname: String,
+    /// every user has the contacts the build has but guests, who have none until they upgrade. see `Guests`.
     #[cfg(feature = "email")]
-    pub email: super::Email,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<super::Email>,
     #[cfg(feature = "phone")]
-    pub phone: super::Phone,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phone: Option<super::Phone>,
     #[serde(flatten, skip_serializing_if = "Login::is_empty")]
+    #[patch(immutable)]
     pub login: Login,
     #[serde(default)]
     pub profile: Option<String>,
+    /// the URL of the picture of the user. see `Avatars` for uploading one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<Url>,
+    #[serde(default)]
+    #[patch(immutable)]
+    pub status: Status,
+    /// why the status last changed. eg the reason of a suspension.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[patch(immutable)]
+    pub status_reason: Option<String>,
+    /// written through `UserMetadata` so that its limits are enforced.
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    #[patch(immutable)]
+    pub metadata: Metadata,
+    /// the versions of the legal documents the user accepted.
+    #[serde(default, skip_serializing_if = "Consents::is_empty")]
+    #[patch(immutable)]
+    pub consents: Consents,
     #[serde(default)]
+    #[patch(immutable)]
     pub created_at: DateTime<Utc>,
+    /// set by the database adaptors on every update.
+    #[serde(default)]
+    #[patch(immutable)]
+    pub updated_at: DateTime<Utc>,
+}
+
+
+/// the most characters of the full name of a user.
+pub const FULLNAME_MAX_LENGTH: usize = 256;
+/// the most characters of the profile of a user.
+pub const PROFILE_MAX_LENGTH: usize = 2048;
+
+
+impl User {
+    /// The checks of the writable fields of a user, as the payloads creating or updating one hold them.
+    /// Usernames are checked by `UsernameRules`, against the configuration.
+    pub fn rules(validator: Validator<'_>) -> Validator<'_> {
+        let validator = validator
+            .length("fullname", 0..=FULLNAME_MAX_LENGTH)
+            .length("profile", 0..=PROFILE_MAX_LENGTH)
+            .parses::<Url>("avatar");
+        #[cfg(feature = "email")]
+        let validator = validator.parses::<super::Email>("email");
+        #[cfg(feature = "phone")]
+        let validator = validator.parses_with("phone", super::Phone::check);
+        validator
+    }
 }
 
 #[cfg(test)]
@@ -39,22 +100,32 @@ mod tests {
         #[cfg(feature = "email")]
         let email = Email::try_from("user@example.com").unwrap();
         #[cfg(feature = "phone")]
-        let phone = Phone::try_from(String::from("+25478965439")).unwrap();
+        let phone = Phone::try_from(String::from("+254712345678")).unwrap();
         let password = String::from("password");
         let login = Login::Password(password);
         let profile = None;
+        let mut metadata = Metadata::default();
+        metadata.user.insert("theme".into(), serde_json::json!("dark"));
         let created_at = Utc::now();
         let user = User {
             id,
             username,
             fullname,
             #[cfg(feature = "email")]
-            email,
+            email: Some(email),
             #[cfg(feature = "phone")]
-            phone,
+            phone: Some(phone),
             login,
             profile,
+            avatar: Some(Url::parse("https://cdn.example.com/avatars/1.png").unwrap()),
+            status: Status::Active,
+            status_reason: None,
+            previous_username: None,
+            username_changed_at: None,
+            metadata,
+            consents: Default::default(),
             created_at,
+            updated_at: created_at,
         };
 
         let serialized = serde_json::to_string(&user).unwrap();
@@ -62,6 +133,89 @@ mod tests {
         let deserialized = serde_json::from_str::<User>(&serialized).unwrap();
         assert_eq!(user, deserialized);
     }
+
+    #[test]
+    fn test_patch() {
+        let mut user = User {
+            id: Id::try_from(String::from("000000000000000000000000")).unwrap(),
+            username: String::from("username"),
+            fullname: String::from("fullname"),
+            #[cfg(feature = "email")]
+            email: Some(Email::try_from("user@example.com").unwrap()),
+            #[cfg(feature = "phone")]
+            phone: Some(Phone::try_from(String::from("+254712345678")).unwrap()),
+            login: Login::Password(String::from("password")),
+            profile: Some(String::from("profile")),
+            avatar: None,
+            status: Status::Active,
+            status_reason: None,
+            previous_username: None,
+            username_changed_at: None,
+            metadata: Metadata::default(),
+            consents: Default::default(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        let update = |value: serde_json::Value| UserPatch::try_from(value.as_object().unwrap().clone());
+        let patch = update(serde_json::json!({"fullname": "full name", "profile": null})).unwrap();
+        patch.apply(&mut user);
+        assert_eq!(user.fullname, "full name");
+        assert_eq!(user.username, "username");
+        assert_eq!(user.profile, None);
+        let patch = update(serde_json::json!({"avatar": "https://cdn.example.com/avatars/1.png"})).unwrap();
+        patch.apply(&mut user);
+        assert_eq!(user.avatar.as_ref().map(Url::as_str), Some("https://cdn.example.com/avatars/1.png"));
+        assert_eq!(update(serde_json::json!({"avatar": "not a url"})).unwrap_err(), ConversionError::UnexpectedDataType("avatar"));
+        assert_eq!(update(serde_json::json!({"id": "000000000000000000000001"})).unwrap_err(), ConversionError::ImmutableField("id"));
+        assert_eq!(update(serde_json::json!({"role": "admin"})).unwrap_err(), ConversionError::UnknownField("role".into()));
+        assert_eq!(update(serde_json::json!({"status": "active"})).unwrap_err(), ConversionError::ImmutableField("status"));
+        assert_eq!(update(serde_json::json!({"metadata": {}})).unwrap_err(), ConversionError::ImmutableField("metadata"));
+        assert_eq!(update(serde_json::json!({"username": 1})).unwrap_err(), ConversionError::UnexpectedDataType("username"));
+    }
+
+    #[test]
+    fn test_every_invalid_field_is_reported() {
+        let payload = serde_json::json!({"fullname": 1, "avatar": "not a url", "profile": null});
+        let Err(ConversionError::InvalidFields(errors)) = User::rules(Validator::new(payload.as_object().unwrap())).check() else {
+            panic!("the payload is invalid");
+        };
+        assert_eq!(errors.iter().map(|error| error.field.as_str()).collect::<Vec<_>>(), vec!["fullname", "avatar"]);
+        assert_eq!(errors[0].detail, "must be a string");
+        let payload = serde_json::json!({"fullname": "Jane", "avatar": "https://cdn.example.com/avatars/1.png"});
+        assert_eq!(User::rules(Validator::new(payload.as_object().unwrap())).required("fullname").check(), Ok(()));
+    }
+
+    #[cfg(feature = "dynamodb")]
+    #[test]
+    fn test_metadata_attributes() {
+        let mut metadata = Metadata::default();
+        metadata.service.insert("plan".into(), serde_json::json!({"tier": "pro", "seats": [1, "two"]}));
+        let user = User {
+            id: Id::try_from(String::from("000000000000000000000000")).unwrap(),
+            username: String::from("username"),
+            fullname: String::from("fullname"),
+            #[cfg(feature = "email")]
+            email: Some(Email::try_from("user@example.com").unwrap()),
+            #[cfg(feature = "phone")]
+            phone: Some(Phone::try_from(String::from("+254712345678")).unwrap()),
+            login: Login::Password(String::from("password")),
+            profile: None,
+            avatar: Some(Url::parse("https://cdn.example.com/avatars/1.png").unwrap()),
+            status: Status::Suspended,
+            status_reason: Some(String::from("chargeback")),
+            previous_username: Some(String::from("previous")),
+            username_changed_at: Some(DateTime::from_timestamp(1_700_000_200, 0).unwrap()),
+            metadata,
+            consents: Consents { terms_of_service: Some(crate::types::Consent { version: "2024-01".into(), accepted_at: DateTime::from_timestamp(1_700_000_300, 0).unwrap() }), privacy_policy: None },
+            created_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            updated_at: DateTime::from_timestamp(1_700_000_600, 0).unwrap(),
+        };
+        let map = HashMap::<String, AttributeValue>::from(user.clone());
+        assert!(map.contains_key("service_metadata"));
+        assert!(!map.contains_key("user_metadata"));
+        assert!(map.contains_key("terms_of_service_consent"));
+        assert_eq!(User::try_from(map).unwrap(), user);
+    }
 }
 
 #[cfg(feature = "dynamodb")]
@@ -70,26 +224,51 @@ impl From<User> for HashMap<String, AttributeValue> {
         let mut map = HashMap::new();
         map.insert("id".into(), user.id.into());
         map.insert("username".into(), AttributeValue::S(user.username));
+        if let Some(previous) = user.previous_username {
+            map.insert("previous_username".into(), AttributeValue::S(previous));
+        }
+        if let Some(changed_at) = user.username_changed_at {
+            map.insert("username_changed_at".into(), AttributeValue::N(changed_at.timestamp().to_string()));
+        }
         map.insert("fullname".into(), AttributeValue::S(user.fullname));
         #[cfg(feature = "email")]
-        {
-            let iter = user.email.into();
-            map.extend::<HashMap<String, AttributeValue>>(iter);
+        if let Some(email) = user.email {
+            map.extend::<HashMap<String, AttributeValue>>(email.into());
         }
         #[cfg(feature = "phone")]
-        {
-            let iter = user.phone.into();
-            map.extend::<HashMap<String, AttributeValue>>(iter);
+        if let Some(phone) = user.phone {
+            map.extend::<HashMap<String, AttributeValue>>(phone.into());
         }
         let iter = user.login.into();
         map.extend::<HashMap<String, AttributeValue>>(iter);
         if let Some(profile) = user.profile {
             map.insert("profile".into(), AttributeValue::S(profile));
         }
+        if let Some(avatar) = user.avatar {
+            map.insert("avatar".into(), AttributeValue::S(avatar.into()));
+        }
+        map.insert("status".into(), AttributeValue::S(user.status.as_str().into()));
+        if let Some(reason) = user.status_reason {
+            map.insert("status_reason".into(), AttributeValue::S(reason));
+        }
+        let mut metadata = user.metadata;
+        for namespace in Namespace::ALL {
+            let fields = std::mem::take(metadata.get_mut(namespace));
+            if !fields.is_empty() {
+                map.insert(namespace.attribute().into(), AttributeValue::S(serde_json::Value::Object(fields).to_string()));
+            }
+        }
+        let mut consents = user.consents;
+        for document in Document::ALL {
+            if let Some(consent) = consents.get_mut(document).take() {
+                map.insert(document.attribute().into(), AttributeValue::S(serde_json::json!(consent).to_string()));
+            }
+        }
         map.insert(
             "created_at".into(),
             AttributeValue::N(user.created_at.timestamp().to_string()),
         );
+        map.insert("updated_at".into(), AttributeValue::N(user.updated_at.timestamp().to_string()));
         map
     }
 }
@@ -109,6 +288,17 @@ impl TryFrom<HashMap<String, AttributeValue>> for User {
             AttributeValue::S(username) => Ok(username),
             _ => Err(ConversionError::UnexpectedDataType("username")),
         }?;
+        let previous_username = match map.remove("previous_username") {
+            None | Some(AttributeValue::Null(_)) => None,
+            Some(AttributeValue::S(previous)) => Some(previous),
+            Some(_) => return Err(ConversionError::UnexpectedDataType("previous_username")),
+        };
+        // written as a timestamp by `create_user`, and as the RFC 3339 string of the patch by `update_user`.
+        let username_changed_at = match map.remove("username_changed_at") {
+            None | Some(AttributeValue::Null(_)) => None,
+            Some(AttributeValue::N(time) | AttributeValue::S(time)) => Some(super::parse_timestamp(&time).ok_or(ConversionError::UnexpectedDataType("username_changed_at"))?),
+            Some(_) => return Err(ConversionError::UnexpectedDataType("username_changed_at")),
+        };
         let fullname = map
             .remove("fullname")
             .map_or(Ok(String::new()), |value| match value {
@@ -116,9 +306,15 @@ impl TryFrom<HashMap<String, AttributeValue>> for User {
                 _ => Ok::<_, ConversionError>(String::new()),
             })?;
         #[cfg(feature = "email")]
-        let email = Email::try_from(&mut map)?;
+        let email = match map.get("email") {
+            None | Some(AttributeValue::Null(_)) => None,
+            Some(_) => Some(super::Email::try_from(&mut map)?),
+        };
         #[cfg(feature = "phone")]
-        let phone = Phone::try_from(&mut map)?;
+        let phone = match map.get("phone") {
+            None | Some(AttributeValue::Null(_)) => None,
+            Some(_) => Some(super::Phone::try_from(&mut map)?),
+        };
         let login = Login::try_from(&mut map)?;
         let profile = match map.remove("profile") {
             None => None,
@@ -127,10 +323,51 @@ impl TryFrom<HashMap<String, AttributeValue>> for User {
                 _ => return Err(ConversionError::UnexpectedDataType("profile")),
             },
         };
+        let avatar = match map.remove("avatar") {
+            None => None,
+            Some(AttributeValue::S(avatar)) => Some(Url::parse(&avatar).map_err(|_| ConversionError::UnexpectedDataType("avatar"))?),
+            Some(_) => return Err(ConversionError::UnexpectedDataType("avatar")),
+        };
+        // accounts created before statuses existed are active.
+        let status = match map.remove("status") {
+            None => Status::Active,
+            Some(AttributeValue::S(status)) => Status::try_from(status.as_str())?,
+            Some(_) => return Err(ConversionError::UnexpectedDataType("status")),
+        };
+        let status_reason = match map.remove("status_reason") {
+            None => None,
+            Some(AttributeValue::S(reason)) => Some(reason),
+            Some(_) => return Err(ConversionError::UnexpectedDataType("status_reason")),
+        };
+        let mut metadata = Metadata::default();
+        for namespace in Namespace::ALL {
+            let attribute = namespace.attribute();
+            match map.remove(attribute) {
+                None => {},
+                Some(AttributeValue::S(json)) => *metadata.get_mut(namespace) = serde_json::from_str(&json).map_err(|_| ConversionError::UnexpectedDataType(attribute))?,
+                Some(_) => return Err(ConversionError::UnexpectedDataType(attribute)),
+            }
+        }
+        let mut consents = Consents::default();
+        for document in Document::ALL {
+            let attribute = document.attribute();
+            match map.remove(attribute) {
+                None => {},
+                Some(AttributeValue::S(json)) => *consents.get_mut(document) = Some(serde_json::from_str(&json).map_err(|_| ConversionError::UnexpectedDataType(attribute))?),
+                Some(_) => return Err(ConversionError::UnexpectedDataType(attribute)),
+            }
+        }
         let created_at = created_at_date_from_map(&mut map)?;
-        Ok(User{id,username,fullname,#[cfg(feature = "email")]email,#[cfg(feature = "phone")]phone,login,profile,created_at,})
+        // users written before updates were tracked were last updated when they were created, as far as we know.
+        let updated_at = if map.contains_key("updated_at") {
+            updated_at_date_from_map(&mut map)?
+        } else {
+            created_at
+        };
+        Ok(User{id,username,previous_username,username_changed_at,fullname,#[cfg(feature = "email")]email,#[cfg(feature = "phone")]phone,login,profile,avatar,status,status_reason,metadata,consents,created_at,updated_at,})
     }
 }
 
 
-create_date_from_map!(created_at_date_from_map, "created_at");
\ No newline at end of file
+create_date_from_map!(created_at_date_from_map, "created_at");
+create_date_from_map!(updated_at_date_from_map, "updated_at");
\ No newline at end of file
@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use super::{Status, User, Payload, Validator};
+
+
+/// The conditions a user must meet to be part of the results of a search. Every condition that is set has to hold.
+///
+/// Matching is exact, but for `email` which matches any address containing it, and no guest. Both are case-sensitive.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct UserFilter {
+    /// a part of the email address. eg `@acme.com`
+    #[cfg(feature = "email")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+    /// the users created at or after.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<DateTime<Utc>>,
+    /// the users created before.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<DateTime<Utc>>,
+}
+
+
+impl UserFilter {
+    /// Whether `user` meets every condition of the filter.
+    pub fn matches(&self, user: &User) -> bool {
+        #[cfg(feature = "email")]
+        if self.email.as_ref().is_some_and(|email| !user.email.as_ref().is_some_and(|address| address.as_ref().contains(email.as_str()))) {
+            return false;
+        }
+        self.username.as_ref().is_none_or(|username| &user.username == username)
+            && self.status.is_none_or(|status| user.status == status)
+            && self.created_after.is_none_or(|after| user.created_at >= after)
+            && self.created_before.is_none_or(|before| user.created_at < before)
+    }
+}
+
+
+impl Payload for UserFilter {
+    fn rules(validator: Validator<'_>) -> Validator<'_> {
+        #[cfg(feature = "email")]
+        let validator = validator.length("email", 1..=320);
+        validator
+            .length("username", 1..=256)
+            .parses::<Status>("status")
+            .parses::<DateTime<Utc>>("created_after")
+            .parses::<DateTime<Utc>>("created_before")
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::UserFixture;
+    use crate::types::ConversionError;
+
+    #[test]
+    fn test_every_condition_has_to_hold() {
+        let user = UserFixture::new().username("jane").status(Status::Suspended).build();
+        assert!(UserFilter::default().matches(&user));
+        let filter = UserFilter { username: Some(String::from("jane")), status: Some(Status::Suspended), ..Default::default() };
+        assert!(filter.matches(&user));
+        assert!(!UserFilter { status: Some(Status::Active), ..filter.clone() }.matches(&user));
+        assert!(UserFilter { created_after: Some(user.created_at), ..filter.clone() }.matches(&user));
+        assert!(!UserFilter { created_before: Some(user.created_at), ..filter }.matches(&user));
+    }
+
+    #[test]
+    fn test_filters_are_parsed_with_every_invalid_field() {
+        let body = serde_json::json!({"username": "", "status": "asleep", "created_after": "yesterday"});
+        let Err(ConversionError::InvalidFields(errors)) = UserFilter::parse(body) else {
+            panic!("the filter is invalid");
+        };
+        assert_eq!(errors.iter().map(|error| error.field.as_str()).collect::<Vec<_>>(), vec!["username", "status", "created_after"]);
+        let filter = UserFilter::parse(serde_json::json!({"status": "suspended"})).unwrap();
+        assert_eq!(filter.status, Some(Status::Suspended));
+        let Err(ConversionError::InvalidFields(errors)) = UserFilter::parse(serde_json::json!({"role": "admin"})) else {
+            panic!("the filter has an unknown field");
+        };
+        assert_eq!(errors[0].field, "role");
+    }
+
+    #[cfg(feature = "email")]
+    #[test]
+    fn test_emails_match_by_substring() {
+        let user = UserFixture::new().email("jane@acme.com").build();
+        assert!(UserFilter { email: Some(String::from("@acme.com")), ..Default::default() }.matches(&user));
+        assert!(!UserFilter { email: Some(String::from("@ACME.com")), ..Default::default() }.matches(&user));
+    }
+}
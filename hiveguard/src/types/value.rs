@@ -0,0 +1,299 @@
+#[cfg(feature = "dynamodb")]
+use aws_sdk_dynamodb::{primitives::Blob, types::AttributeValue};
+use rust_decimal::prelude::{Decimal, FromPrimitive, ToPrimitive};
+#[cfg(feature = "dynamodb")]
+use base64::{Engine, engine::general_purpose::STANDARD};
+#[cfg(feature = "dynamodb")]
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "dynamodb")]
+use serde_json::Map;
+use serde_json::{Number, Value};
+use super::{ConversionError, Id};
+use chrono::{DateTime, Utc};
+use std::str::FromStr;
+
+
+/// the only field of the objects standing for binary data, holding it in standard base64.
+#[cfg(feature = "dynamodb")]
+pub const BINARY: &str = "$binary";
+
+
+/// The conversions of JSON values the patches, the `Map` updates and the database adaptors share, so that none of them
+/// converts numbers through `f64` or parses timestamps its own way.
+///
+/// Every conversion is `None` when the value is not of the kind asked for, the caller knowing which field it is.
+pub trait Coerce {
+    /// The exact number of a JSON number, or of a string holding one. Numbers out of the range of a [`Decimal`] are `None`.
+    fn to_decimal(&self) -> Option<Decimal>;
+
+    /// The time of a number of seconds since the epoch, or of a string holding one or an RFC 3339 date.
+    fn to_timestamp(&self) -> Option<DateTime<Utc>>;
+
+    /// The id of a string holding its hex form.
+    fn to_id(&self) -> Option<Id>;
+
+    /// The strings of an array of strings only.
+    fn to_strings(&self) -> Option<Vec<String>>;
+
+    /// The numbers of an array of numbers only, as they were written.
+    fn to_numbers(&self) -> Option<Vec<Number>>;
+
+    /// The exact sum of two numbers, a JSON number again.
+    fn add(&self, other: &Value) -> Option<Value> {
+        Some(decimal_value(self.to_decimal()?.checked_add(other.to_decimal()?)?))
+    }
+}
+
+
+impl Coerce for Value {
+    fn to_decimal(&self) -> Option<Decimal> {
+        match self {
+            Value::Number(number) => parse_decimal(&number.to_string()),
+            Value::String(string) => parse_decimal(string.trim()),
+            _ => None,
+        }
+    }
+
+    fn to_timestamp(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Value::Number(number) => DateTime::from_timestamp(number.as_i64()?, 0),
+            Value::String(string) => parse_timestamp(string),
+            _ => None,
+        }
+    }
+
+    fn to_id(&self) -> Option<Id> {
+        Id::try_from(String::from(self.as_str()?)).ok()
+    }
+
+    fn to_strings(&self) -> Option<Vec<String>> {
+        self.as_array()?.iter().map(|value| value.as_str().map(String::from)).collect()
+    }
+
+    fn to_numbers(&self) -> Option<Vec<Number>> {
+        self.as_array()?.iter().map(|value| value.as_number().cloned()).collect()
+    }
+}
+
+
+/// The time of `string`, a number of seconds since the epoch as the adaptors store times, or an RFC 3339 date.
+pub fn parse_timestamp(string: &str) -> Option<DateTime<Utc>> {
+    match string.parse() {
+        Ok(seconds) => DateTime::from_timestamp(seconds, 0),
+        Err(_) => DateTime::parse_from_rfc3339(string).ok().map(|date| date.to_utc()),
+    }
+}
+
+
+/// `time` as the adaptors store it, the number of seconds since the epoch.
+pub fn timestamp_value(time: DateTime<Utc>) -> Value {
+    Value::from(time.timestamp())
+}
+
+
+/// `decimal` as a JSON number, an integer whenever it is one. Fractions JSON numbers can only hold as an `f64` are rounded.
+pub fn decimal_value(decimal: Decimal) -> Value {
+    let decimal = decimal.normalize();
+    if decimal.is_integer() {
+        if let Some(integer) = decimal.to_i64() {
+            return Value::from(integer);
+        }
+        if let Some(integer) = decimal.to_u64() {
+            return Value::from(integer);
+        }
+    }
+    decimal.to_f64().and_then(Number::from_f64).map_or(Value::Null, Value::Number)
+}
+
+
+/// The attribute value of `value`, which [`from_attribute_value`] turns back into `value`.
+///
+/// Arrays of distinct strings, numbers or binaries are stored as sets, so that conditions can test what they contain.
+/// Every other array is a list, empty ones included since DynamoDB has no empty sets. Binary data is written in JSON as an
+/// object of [`BINARY`] only.
+#[cfg(feature = "dynamodb")]
+pub fn to_attribute_value(value: Value) -> Result<AttributeValue, ConversionError> {
+    if let Some(set) = set(&value) {
+        return Ok(set);
+    }
+    match value {
+        Value::String(string) => Ok(AttributeValue::S(string)),
+        Value::Number(number) => Ok(AttributeValue::N(number.to_string())),
+        Value::Bool(boolean) => Ok(AttributeValue::Bool(boolean)),
+        Value::Null => Ok(AttributeValue::Null(true)),
+        Value::Array(items) => Ok(AttributeValue::L(items.into_iter().map(to_attribute_value).collect::<Result<_, _>>()?)),
+        Value::Object(object) => match binary(&object) {
+            Some(blob) => Ok(AttributeValue::B(blob)),
+            None => Ok(AttributeValue::M(object.into_iter().map(|(key, value)| Ok((key, to_attribute_value(value)?))).collect::<Result<_, ConversionError>>()?)),
+        },
+    }
+}
+
+
+/// The JSON value of `value`, sets becoming arrays and binary data an object of [`BINARY`].
+#[cfg(feature = "dynamodb")]
+pub fn from_attribute_value(value: AttributeValue) -> Result<Value, ConversionError> {
+    let number = |number: &str| number.parse::<Number>().map(Value::Number).map_err(|_| ConversionError::UnexpectedDataType("N. not a JSON number"));
+    match value {
+        AttributeValue::S(string) => Ok(Value::String(string)),
+        AttributeValue::N(string) => number(&string),
+        AttributeValue::Bool(boolean) => Ok(Value::Bool(boolean)),
+        AttributeValue::Null(_) => Ok(Value::Null),
+        AttributeValue::B(blob) => Ok(binary_value(blob)),
+        AttributeValue::Ss(strings) => Ok(strings.into_iter().collect()),
+        AttributeValue::Ns(numbers) => numbers.iter().map(|string| number(string)).collect(),
+        AttributeValue::Bs(blobs) => Ok(blobs.into_iter().map(binary_value).collect()),
+        AttributeValue::L(items) => items.into_iter().map(from_attribute_value).collect(),
+        AttributeValue::M(map) => Ok(Value::Object(from_attribute_map(map)?)),
+        _ => Err(ConversionError::UnexpectedDataType("an attribute value of an unknown type")),
+    }
+}
+
+
+/// The JSON object of the attributes of an item.
+#[cfg(feature = "dynamodb")]
+pub fn from_attribute_map(map: HashMap<String, AttributeValue>) -> Result<Map<String, Value>, ConversionError> {
+    map.into_iter().map(|(key, value)| Ok((key, from_attribute_value(value)?))).collect()
+}
+
+
+/// The set of the items of `value`, when it is a non-empty array of distinct strings, numbers or binaries.
+#[cfg(feature = "dynamodb")]
+fn set(value: &Value) -> Option<AttributeValue> {
+    match value.as_array()?.first()? {
+        Value::String(_) => {
+            let strings = value.to_strings()?;
+            distinct(strings.iter()).then_some(AttributeValue::Ss(strings))
+        },
+        Value::Number(_) => {
+            let numbers = value.to_numbers()?;
+            // DynamoDB compares the numbers of a set by value, so 1 and 1.0 are the same number.
+            let keys = numbers.iter().map(|number| Value::Number(number.clone()).to_decimal().map_or_else(|| number.to_string(), |decimal| decimal.normalize().to_string()));
+            distinct(keys).then(|| AttributeValue::Ns(numbers.iter().map(Number::to_string).collect()))
+        },
+        Value::Object(_) => {
+            let blobs = value.as_array()?.iter().map(|item| binary(item.as_object()?)).collect::<Option<Vec<_>>>()?;
+            distinct(blobs.iter().map(Blob::as_ref)).then_some(AttributeValue::Bs(blobs))
+        },
+        _ => None,
+    }
+}
+
+
+#[cfg(feature = "dynamodb")]
+fn distinct<T: Eq + std::hash::Hash>(mut items: impl Iterator<Item = T>) -> bool {
+    let mut seen = HashSet::new();
+    items.all(|item| seen.insert(item))
+}
+
+
+/// The binary data of an object of [`BINARY`] only.
+#[cfg(feature = "dynamodb")]
+fn binary(object: &Map<String, Value>) -> Option<Blob> {
+    match (object.len(), object.get(BINARY)?) {
+        (1, Value::String(encoded)) => STANDARD.decode(encoded).ok().map(Blob::new),
+        _ => None,
+    }
+}
+
+
+#[cfg(feature = "dynamodb")]
+fn binary_value(blob: Blob) -> Value {
+    serde_json::json!({BINARY: STANDARD.encode(blob.as_ref())})
+}
+
+
+/// The decimal of `string`, in scientific notation too, which is how JSON writes large and small floats.
+fn parse_decimal(string: &str) -> Option<Decimal> {
+    Decimal::from_str(string).or_else(|_| Decimal::from_scientific(string)).ok()
+        // an f64 too precise for a decimal still converts, rounded to what a decimal holds.
+        .or_else(|| string.parse::<f64>().ok().filter(|float| float.is_finite()).and_then(Decimal::from_f64))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_numbers_convert_exactly() {
+        assert_eq!(json!(9007199254740993u64).to_decimal(), Some(Decimal::from(9007199254740993u64)));
+        assert_eq!(json!(0.1).add(&json!(0.2)), Some(json!(0.3)));
+        assert_eq!(json!(u64::MAX).add(&json!(-1)), Some(json!(u64::MAX - 1)));
+        assert_eq!(json!("12.50").to_decimal(), Some(Decimal::new(1250, 2)));
+        assert_eq!(json!(1e-7).to_decimal(), Some(Decimal::new(1, 7)));
+        assert_eq!(json!("twelve").to_decimal(), None);
+        assert_eq!(json!(true).to_decimal(), None);
+        assert_eq!(decimal_value(Decimal::new(1500, 2)), json!(15));
+        assert_eq!(decimal_value(Decimal::new(-25, 1)), json!(-2.5));
+    }
+
+    #[test]
+    fn test_timestamps_and_ids_convert() {
+        let time = DateTime::from_timestamp(1_760_000_000, 0).unwrap();
+        assert_eq!(json!(1_760_000_000).to_timestamp(), Some(time));
+        assert_eq!(json!("1760000000").to_timestamp(), Some(time));
+        assert_eq!(json!("2025-10-09T08:53:20Z").to_timestamp(), Some(time));
+        assert_eq!(json!("2025-10-09T10:53:20+02:00").to_timestamp(), Some(time));
+        assert_eq!(json!(1.5).to_timestamp(), None);
+        assert_eq!(json!("yesterday").to_timestamp(), None);
+        assert_eq!(timestamp_value(time).to_timestamp(), Some(time));
+        let id = Id::new();
+        assert_eq!(Value::from(id).to_id(), Some(id));
+        assert_eq!(json!("not an id").to_id(), None);
+    }
+
+    #[test]
+    fn test_arrays_convert_when_of_one_kind() {
+        assert_eq!(json!(["a", "b"]).to_strings(), Some(vec![String::from("a"), String::from("b")]));
+        assert_eq!(json!(["a", 1]).to_strings(), None);
+        assert_eq!(json!([1, 2.5]).to_numbers().map(|numbers| numbers.iter().map(Number::to_string).collect::<Vec<_>>()), Some(vec![String::from("1"), String::from("2.5")]));
+        assert_eq!(json!([1, "2"]).to_numbers(), None);
+        assert_eq!(json!("a").to_strings(), None);
+    }
+
+    #[cfg(feature = "dynamodb")]
+    #[test]
+    fn test_arrays_are_sets_only_when_they_can_be() {
+        let blob = |bytes: &[u8]| json!({BINARY: STANDARD.encode(bytes)});
+        assert_eq!(to_attribute_value(json!(["a", "b"])), Ok(AttributeValue::Ss(vec![String::from("a"), String::from("b")])));
+        assert_eq!(to_attribute_value(json!([1, 2.5])), Ok(AttributeValue::Ns(vec![String::from("1"), String::from("2.5")])));
+        assert_eq!(to_attribute_value(json!([blob(b"a"), blob(b"b")])), Ok(AttributeValue::Bs(vec![Blob::new(*b"a"), Blob::new(*b"b")])));
+        assert_eq!(to_attribute_value(blob(&[0, 255])), Ok(AttributeValue::B(Blob::new([0, 255]))));
+        assert_eq!(to_attribute_value(json!([])), Ok(AttributeValue::L(vec![])));
+        assert_eq!(to_attribute_value(json!(["a", "a"])), Ok(AttributeValue::L(vec![AttributeValue::S(String::from("a")), AttributeValue::S(String::from("a"))])));
+        assert!(matches!(to_attribute_value(json!([1, 1.0])), Ok(AttributeValue::L(_))));
+        assert!(matches!(to_attribute_value(json!(["a", 1, null])), Ok(AttributeValue::L(_))));
+        let map = to_attribute_value(json!({"avatar": null, BINARY: "not base64"})).unwrap();
+        assert_eq!(map.as_m().unwrap()["avatar"], AttributeValue::Null(true));
+        assert_eq!(from_attribute_value(AttributeValue::N(String::from("twelve"))), Err(ConversionError::UnexpectedDataType("N. not a JSON number")));
+    }
+
+    #[cfg(feature = "dynamodb")]
+    fn json() -> impl proptest::strategy::Strategy<Value = Value> {
+        use proptest::prelude::*;
+        let leaf = prop_oneof![
+            Just(Value::Null),
+            any::<bool>().prop_map(Value::from),
+            any::<i64>().prop_map(Value::from),
+            any::<u64>().prop_map(Value::from),
+            // quarters are written and read back exactly, unlike floats of many digits.
+            any::<i32>().prop_map(|quarters| Value::from(f64::from(quarters) / 4.0)),
+            ".{0,12}".prop_map(Value::from),
+            proptest::collection::vec(any::<u8>(), 0..16).prop_map(|bytes| json!({BINARY: STANDARD.encode(bytes)})),
+        ];
+        leaf.prop_recursive(4, 64, 6, |inner| prop_oneof![
+            proptest::collection::vec(inner.clone(), 0..6).prop_map(Value::Array),
+            proptest::collection::hash_map("[a-z_]{1,8}", inner, 0..6).prop_map(|map| Value::Object(map.into_iter().collect())),
+        ])
+    }
+
+    #[cfg(feature = "dynamodb")]
+    proptest::proptest! {
+        #[test]
+        fn test_values_round_trip_through_attribute_values(value in json()) {
+            proptest::prop_assert_eq!(from_attribute_value(to_attribute_value(value.clone()).unwrap()), Ok(value));
+        }
+    }
+}
@@ -13,16 +13,21 @@ pub struct Verification<ID = Id> {
     pub id: ID,
     pub code: u32,
     pub expires: DateTime<Utc>,
+    #[serde(default)]
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub updated_at: DateTime<Utc>,
 }
 
 #[cfg(feature = "dynamodb")]
 impl<ID: Into<AttributeValue>> From<Verification<ID>> for HashMap<String, AttributeValue> {
     fn from(verification: Verification<ID>) -> Self {
-        let mut map = HashMap::new();
-        map.insert("owner_contact".to_string(), AttributeValue::S(verification.owner_contact.to_string()));
+        let mut map = HashMap::from(verification.owner_contact);
         map.insert("id".to_string(), verification.id.into());
         map.insert("code".to_string(), AttributeValue::N(verification.code.to_string()));
         map.insert("expires".to_string(), AttributeValue::N(verification.expires.timestamp().to_string()));
+        map.insert("created_at".to_string(), AttributeValue::N(verification.created_at.timestamp().to_string()));
+        map.insert("updated_at".to_string(), AttributeValue::N(verification.updated_at.timestamp().to_string()));
         map
     }
 }
@@ -43,14 +48,21 @@ impl TryFrom<HashMap<String, AttributeValue>> for Verification {
             _ => Err(ConversionError::UnexpectedDataType("code"))
         }?;
         let expires = expires_date_from_map(&mut map)?;
+        // codes sent before timestamps were stored read as created at the epoch. they expire within minutes anyway.
+        let created_at = if map.contains_key("created_at") { created_at_date_from_map(&mut map)? } else { DateTime::default() };
+        let updated_at = if map.contains_key("updated_at") { updated_at_date_from_map(&mut map)? } else { created_at };
         Ok(Verification {
             owner_contact,
             id,
             code,
             expires,
+            created_at,
+            updated_at,
         })
     }
 }
 
 
-create_date_from_map!(expires_date_from_map, "expires");
\ No newline at end of file
+create_date_from_map!(expires_date_from_map, "expires");
+create_date_from_map!(created_at_date_from_map, "created_at");
+create_date_from_map!(updated_at_date_from_map, "updated_at");
\ No newline at end of file
@@ -0,0 +1,280 @@
+use syn::{Error, FnArg, GenericParam, Ident, ItemTrait, Pat, Path, ReturnType, Signature, TraitItem, Type};
+use quote::{format_ident, quote};
+use proc_macro2::TokenStream;
+use syn::visit_mut::VisitMut;
+
+
+/// A table of the database the mock is generated for.
+pub struct Mocked<'a> {
+    /// the associated type of the database. eg `UsersTable`
+    pub ty: &'a Ident,
+    /// the method of the database returning the table. eg `users_table`
+    pub getter: &'a Ident,
+    /// the path of the table trait. eg `tables::UsersTable`
+    pub path: Path,
+    pub table: &'a ItemTrait,
+}
+
+
+/// Generates `MockDatabase`, an implementation of `database` for unit tests, and the `MockTable` implementing each of its tables.
+///
+/// Every table method records its arguments and returns the next response programmed with the matching `<method>_returns`,
+/// panicking when none is left. The item type of every table and the error type are generic parameters of `MockDatabase`.
+pub fn mock(database: &ItemTrait, tables: &[Mocked], client: &Ident) -> syn::Result<TokenStream> {
+    let vis = &database.vis;
+    let name = &database.ident;
+    let items = tables.iter().map(|table| format_ident!("{}Item", table.ty)).collect::<Vec<_>>();
+    let getters = tables.iter().map(|table| table.getter).collect::<Vec<_>>();
+    let setters = tables.iter().map(|table| setters(table.table)).collect::<Vec<_>>();
+    let impls = tables.iter().map(|table| implementation(table, client)).collect::<syn::Result<Vec<_>>>()?;
+    let database_items = database_items(database, tables, &items, client)?;
+    let doc = format!("An implementation of [`{}`] whose tables are [`MockTable`]s, for unit tests.", name);
+    Ok(quote! {
+        /// A call made to a [`MockTable`].
+        #[cfg(test)]
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        #vis struct MockCall {
+            pub method: &'static str,
+            /// the arguments but the client, formatted with `Debug`.
+            pub args: ::std::vec::Vec<::std::string::String>,
+        }
+
+        /// A table recording its calls and returning programmed responses.
+        #[cfg(test)]
+        #vis struct MockTable<Item, Error> {
+            responses: ::std::sync::Mutex<::std::collections::HashMap<&'static str, ::std::collections::VecDeque<::std::boxed::Box<dyn ::std::any::Any + ::std::marker::Send>>>>,
+            calls: ::std::sync::Mutex<::std::vec::Vec<MockCall>>,
+            marker: ::std::marker::PhantomData<fn() -> (Item, Error)>,
+        }
+
+        #[cfg(test)]
+        impl<Item, Error> ::std::default::Default for MockTable<Item, Error> {
+            fn default() -> Self {
+                Self {
+                    responses: ::std::default::Default::default(),
+                    calls: ::std::default::Default::default(),
+                    marker: ::std::marker::PhantomData,
+                }
+            }
+        }
+
+        #[cfg(test)]
+        impl<Item: ::std::marker::Send + 'static, Error: ::std::marker::Send + 'static> MockTable<Item, Error> {
+            /// The calls made so far, in order.
+            pub fn calls(&self) -> ::std::vec::Vec<MockCall> {
+                self.calls.lock().unwrap_or_else(::std::sync::PoisonError::into_inner).clone()
+            }
+
+            fn respond<T: ::std::marker::Send + 'static>(&self, method: &'static str, response: T) {
+                let mut responses = self.responses.lock().unwrap_or_else(::std::sync::PoisonError::into_inner);
+                responses.entry(method).or_default().push_back(::std::boxed::Box::new(response));
+            }
+
+            fn call<T: 'static>(&self, method: &'static str, args: ::std::vec::Vec<::std::string::String>) -> T {
+                self.calls.lock().unwrap_or_else(::std::sync::PoisonError::into_inner).push(MockCall { method, args });
+                let response = self.responses.lock().unwrap_or_else(::std::sync::PoisonError::into_inner)
+                    .get_mut(method)
+                    .and_then(::std::collections::VecDeque::pop_front);
+                match response {
+                    Some(response) => *response.downcast::<T>().expect("responses are programmed with the type the method returns"),
+                    None => panic!("no response programmed for `{}`", method),
+                }
+            }
+
+            #(#setters)*
+        }
+
+        #(#impls)*
+
+        #[doc = #doc]
+        #[cfg(test)]
+        #vis struct MockDatabase<#(#items,)* Error> {
+            #(#getters: MockTable<#items, Error>,)*
+        }
+
+        #[cfg(test)]
+        impl<#(#items,)* Error> ::std::default::Default for MockDatabase<#(#items,)* Error> {
+            fn default() -> Self {
+                Self {
+                    #(#getters: ::std::default::Default::default(),)*
+                }
+            }
+        }
+
+        #[cfg(test)]
+        impl<#(#items: ::std::fmt::Debug + ::std::marker::Send + 'static,)* Error: ::std::marker::Send + 'static> #name for MockDatabase<#(#items,)* Error> {
+            #(#database_items)*
+        }
+    })
+}
+
+
+/// The `<method>_returns` methods programming the responses of the methods of `table`.
+fn setters(table: &ItemTrait) -> TokenStream {
+    let setters = table.items.iter().filter_map(|item| match item {
+        TraitItem::Fn(method) => Some(&method.sig),
+        _ => None,
+    }).map(|sig| {
+        let method = sig.ident.to_string();
+        let setter = format_ident!("{}_returns", sig.ident);
+        let output = match &sig.output {
+            ReturnType::Default => quote! { () },
+            ReturnType::Type(_, ty) => {
+                let mut ty = (**ty).clone();
+                AssociatedTypes.visit_type_mut(&mut ty);
+                quote! { #ty }
+            },
+        };
+        let doc = format!("Queues the response of the next call to `{}`.", method);
+        quote! {
+            #[doc = #doc]
+            pub fn #setter(&self, response: #output) -> &Self {
+                self.respond(#method, response);
+                self
+            }
+        }
+    });
+    quote! { #(#setters)* }
+}
+
+
+/// The implementation of the table trait for `MockTable`.
+fn implementation(mocked: &Mocked, client: &Ident) -> syn::Result<TokenStream> {
+    let path = &mocked.path;
+    let params = mocked.table.generics.params.iter();
+    let args = mocked.table.generics.params.iter().map(|param| match param {
+        GenericParam::Type(param) => {
+            let ident = &param.ident;
+            quote! { #ident }
+        },
+        GenericParam::Lifetime(param) => {
+            let lifetime = &param.lifetime;
+            quote! { #lifetime }
+        },
+        GenericParam::Const(param) => {
+            let ident = &param.ident;
+            quote! { #ident }
+        },
+    });
+    let mut items = Vec::new();
+    for item in &mocked.table.items {
+        match item {
+            TraitItem::Type(ty) if ty.ident == "Item" || ty.ident == "Error" => {
+                let ident = &ty.ident;
+                items.push(quote! { type #ident = #ident; });
+            },
+            TraitItem::Fn(method) => items.push(method_body(&method.sig, client)),
+            item => return Err(Error::new_spanned(item, "only the `Item` and `Error` types and methods of a table can be mocked")),
+        }
+    }
+    Ok(quote! {
+        #[cfg(test)]
+        impl<#(#params,)* Item: ::std::fmt::Debug + ::std::marker::Send + 'static, Error: ::std::marker::Send + 'static> #path<#(#args),*> for MockTable<Item, Error> {
+            #(#items)*
+        }
+    })
+}
+
+
+fn method_body(sig: &Signature, client: &Ident) -> TokenStream {
+    let method = sig.ident.to_string();
+    let args = sig.inputs.iter().filter_map(|arg| match arg {
+        FnArg::Typed(arg) => match &*arg.pat {
+            Pat::Ident(pat) => Some(&pat.ident),
+            _ => None,
+        },
+        FnArg::Receiver(_) => None,
+    });
+    let (clients, args): (Vec<_>, Vec<_>) = args.partition(|arg| *arg == client);
+    quote! {
+        #sig {
+            #(let _ = #clients;)*
+            self.call(#method, ::std::vec![#(::std::format!("{:?}", #args)),*])
+        }
+    }
+}
+
+
+/// The associated types and methods of the implementation of the database trait for `MockDatabase`.
+///
+/// The client is `()`, the tables are `MockTable`s and `Error` is the error type parameter.
+fn database_items(database: &ItemTrait, tables: &[Mocked], items: &[Ident], client: &Ident) -> syn::Result<Vec<TokenStream>> {
+    let client_type = database.items.iter().find_map(|item| match item {
+        TraitItem::Fn(method) if method.sig.ident == *client => returns(&method.sig),
+        _ => None,
+    });
+    let mut implementation = Vec::new();
+    for item in &database.items {
+        match item {
+            TraitItem::Type(ty) => {
+                let ident = &ty.ident;
+                if let Some(index) = tables.iter().position(|table| table.ty == ident) {
+                    let item = &items[index];
+                    implementation.push(quote! { type #ident = MockTable<#item, Error>; });
+                } else if client_type == Some(ident) {
+                    implementation.push(quote! { type #ident = (); });
+                } else if ident == "Error" {
+                    implementation.push(quote! { type #ident = Error; });
+                } else {
+                    return Err(Error::new_spanned(ident, "only the client, the error and the tables of a database can be mocked"));
+                }
+            },
+            TraitItem::Fn(method) => {
+                let sig = &method.sig;
+                if let Some(table) = tables.iter().find(|table| *table.getter == sig.ident) {
+                    let getter = table.getter;
+                    implementation.push(quote! { #sig { &self.#getter } });
+                } else if sig.ident == *client || returns(sig) == client_type {
+                    implementation.push(quote! { #sig { &() } });
+                } else if method.default.is_none() {
+                    return Err(Error::new_spanned(&sig.ident, "only the client and the tables of a database can be mocked"));
+                }
+            },
+            _ => (),
+        }
+    }
+    Ok(implementation)
+}
+
+
+/// `X` when `sig` returns `&Self::X`. eg the client of a replica.
+fn returns(sig: &Signature) -> Option<&Ident> {
+    match &sig.output {
+        ReturnType::Type(_, ty) => returned_type(ty),
+        ReturnType::Default => None,
+    }
+}
+
+
+/// `X` in `&Self::X`.
+fn returned_type(ty: &Type) -> Option<&Ident> {
+    let Type::Reference(reference) = ty else {
+        return None;
+    };
+    let Type::Path(path) = &*reference.elem else {
+        return None;
+    };
+    match path.path.segments.iter().collect::<Vec<_>>()[..] {
+        [this, ty] if this.ident == "Self" => Some(&ty.ident),
+        _ => None,
+    }
+}
+
+
+/// Replaces `Self::Item` and `Self::Error` with the type parameters of `MockTable`.
+struct AssociatedTypes;
+
+
+impl VisitMut for AssociatedTypes {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Type::Path(path) = ty
+            && path.qself.is_none()
+            && let [this, associated] = &path.path.segments.iter().collect::<Vec<_>>()[..]
+            && this.ident == "Self" {
+            let ident = associated.ident.clone();
+            *ty = Type::Verbatim(quote! { #ident });
+            return;
+        }
+        syn::visit_mut::visit_type_mut(self, ty);
+    }
+}
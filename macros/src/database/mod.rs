@@ -1,9 +1,16 @@
-use syn::{parse, parse_str, Error, Ident, ItemTrait, PathArguments, ReturnType, TraitItem, TraitItemFn, TraitItemType, Type, TypeParamBound, TypePath};
+use syn::{parse, parse2, braced, bracketed, Error, Ident, ItemTrait, Path, PathArguments, ReturnType, TraitItemFn, TraitItemType, Type, TypeParamBound, TypePath};
+use syn::parse::{Parse, ParseStream};
 use std::collections::HashMap;
 use proc_macro::TokenStream;
 use std::convert::TryFrom;
 use super::table::Table;
-use proc_macro2::Span;
+use mock::Mocked;
+use crate::respan::respan;
+use quote::quote;
+
+mod mock;
+mod validate;
+
 
 pub struct Database {
     item: ItemTrait,
@@ -18,24 +25,95 @@ impl TryFrom<TokenStream> for Database {
     }
 }
 
+/// The input of `expand_database!`: the collected table traits followed by the database trait.
+/// eg `[{ trait UsersTable<Client> { .. } } { trait SessionsTable<Client> { .. } }] trait Database { .. }`
+pub struct Expansion {
+    tables: Vec<ItemTrait>,
+    database: Database,
+}
+
+
+impl Parse for Expansion {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let content;
+        bracketed!(content in input);
+        let mut tables = Vec::new();
+        while !content.is_empty() {
+            let table;
+            braced!(table in content);
+            tables.push(table.parse()?);
+        }
+        let item = input.parse()?;
+        Ok(Self { tables, database: Database { item } })
+    }
+}
+
+
+impl Expansion {
+    pub fn expand(self) -> Result<TokenStream, Error> {
+        self.database.expand(self.tables)
+    }
+}
+
+
 impl Database {
-    pub fn expand(mut self, tables: HashMap<String, String>) -> Result<TokenStream, Error> {
+    /// Invokes the macro generated by `#[table]` for the first table, which passes the collected tables down the chain
+    /// until the last one calls `expand_database!`.
+    pub fn collect_tables(self) -> proc_macro2::TokenStream {
+        let mut tables = self.table_paths().into_iter();
+        let item = self.item;
+        match tables.next() {
+            Some(first) => quote! {
+                #first! { @database [#({ #tables })*] [] #item }
+            },
+            None => quote! {
+                ::macros::expand_database! { [] #item }
+            },
+        }
+    }
+
+    pub fn expand(mut self, tables: Vec<ItemTrait>) -> Result<TokenStream, Error> {
         let client = self.client();
-        let client = client.ok_or(Error::new(Span::call_site(), "No client found: make sure to give the client method the client attribute"))?;
-        let mut trait_method_map = self.table_trait_and_method(&tables);
+        let client = client.ok_or(Error::new_spanned(&self.item.ident, "no client found: give the method returning the client the #[client] attribute"))?;
+        let names = tables.iter().map(|table| table.ident.to_string()).collect::<Vec<_>>();
+        let mut trait_method_map = self.table_trait_and_method(&names);
+        let errors = tables.iter().filter_map(|table| {
+            let (_, ty, args) = trait_method_map.get(&table.ident)?;
+            let ty = self.types().into_iter().find(|item| item.ident == **ty)?;
+            validate::validate(table, ty, args, client).err()
+        }).reduce(|mut errors, error| {
+            errors.combine(error);
+            errors
+        });
+        if let Some(errors) = errors {
+            return Err(errors);
+        }
+        let mocked = tables.iter().filter_map(|table| {
+            let (getter, ty, _) = trait_method_map.get(&table.ident)?;
+            let path = self.table_path(ty)?;
+            Some(Mocked { ty, getter, path, table })
+        }).collect::<Vec<_>>();
+        let mock = mock::mock(&self.item, &mocked, client)?;
+        let replica = self.replica().unwrap_or(client);
         let mut all_methods = Vec::new();
-        for (_, table) in tables {
-            let table_trait: ItemTrait = parse_str(&table)?;
+        for table_trait in tables {
             let mut table: Table = table_trait.into();
             let table_trait_ident = table.name().clone();
             if let Some((table_method_ident, table_type_ident, table_trait_args)) = trait_method_map.remove(&table_trait_ident) {
-                let methods = table.methods(client, table_method_ident, table_type_ident, &table_trait_ident, table_trait_args)?; // Pass the table_trait_path
+                let methods = table.methods((client, replica), table_method_ident, table_type_ident, &table_trait_ident, table_trait_args)?; // Pass the table_trait_path
                 all_methods.extend(methods);
             }
         }
-        self.item.items.extend(all_methods);
+        let span = self.item.trait_token.span;
+        for method in all_methods {
+            self.item.items.push(parse2(respan(quote! {#method}, span))?);
+        }
+        let mock = respan(mock, span);
         let item = self.item;
-        Ok(quote::quote! {#item}.into())
+        Ok(quote::quote! {
+            #item
+            #mock
+        }.into())
     }
 
     
@@ -60,31 +138,60 @@ impl Database {
     }
 
     fn client(&self) -> Option<&Ident> {
-        for item in &self.item.items {
-            if let TraitItem::Fn(method) = item {
-                for attr in &method.attrs {
-                    if let Some(ident) = attr.path().get_ident() {
-                        return Some(ident);
-                    }
-                }
-            }
+        self.marked("client")
+    }
+
+    /// The method marked `#[replica]`, if any.
+    fn replica(&self) -> Option<&Ident> {
+        self.marked("replica")
+    }
+
+    /// The name of the method with the `attribute` attribute.
+    fn marked(&self, attribute: &str) -> Option<&Ident> {
+        self.methods().into_iter()
+            .find(|method| method.attrs.iter().any(|attr| attr.path().is_ident(attribute)))
+            .map(|method| &method.sig.ident)
+    }
+
+    /// The paths of the traits bounding the table types, without their generic arguments. eg `tables::UsersTable`
+    ///
+    /// A table type is an associated type with a trait bound that is returned by one of the database's methods.
+    fn table_paths(&self) -> Vec<Path> {
+        let returned = self.methods().into_iter().filter_map(|method| match &method.sig.output {
+            ReturnType::Type(_, ty) => get_path_from_type(ty).and_then(|path| path.path.segments.last()).map(|segment| &segment.ident),
+            ReturnType::Default => None,
+        }).collect::<Vec<_>>();
+        self.types().into_iter()
+            .filter(|ty| returned.contains(&&ty.ident))
+            .filter_map(|ty| self.table_path(&ty.ident))
+            .collect()
+    }
+
+    /// The path of the trait bounding the associated type `ty`, without its generic arguments.
+    fn table_path(&self, ty: &Ident) -> Option<Path> {
+        let ty = self.types().into_iter().find(|item| item.ident == *ty)?;
+        let Some(TypeParamBound::Trait(bound)) = ty.bounds.first() else {
+            return None;
+        };
+        let mut path = bound.path.clone();
+        if let Some(segment) = path.segments.last_mut() {
+            segment.arguments = PathArguments::None;
         }
-        None
+        Some(path)
     }
 
-    fn table_trait_and_method(&self, tables: &HashMap<String, String>) -> HashMap<&Ident, (&Ident, &Ident, &PathArguments)> {
+    fn table_trait_and_method(&self, tables: &[String]) -> HashMap<&Ident, (&Ident, &Ident, &PathArguments)> {
         let types = self.types();
         let mut types = types.into_iter().filter_map(|ty| {
             let type_name = &ty.ident;
             for bound in &ty.bounds {
-                if let TypeParamBound::Trait(bound) = bound {
-                    if let Some(segment) = bound.path.segments.last() {
-                        let trait_name = &segment.ident;
-                        let args = &segment.arguments;
-                        if let Some(_) = tables.get(&trait_name.to_string()) {
-                            // If the trait is in the tables map, we consider it valid
-                            return Some((type_name, (trait_name, args)));
-                        }
+                if let TypeParamBound::Trait(bound) = bound
+                    && let Some(segment) = bound.path.segments.last() {
+                    let trait_name = &segment.ident;
+                    let args = &segment.arguments;
+                    if tables.contains(&trait_name.to_string()) {
+                        // If the trait is in the tables map, we consider it valid
+                        return Some((type_name, (trait_name, args)));
                     }
                 }
             }
@@ -94,14 +201,12 @@ impl Database {
         let mut map = HashMap::new();
         for method in self.methods() {
             let method_name = &method.sig.ident;
-            if let ReturnType::Type(_, ty) = &method.sig.output {
-                if let Some(path) = get_path_from_type(ty) {
-                    if let Some(segment) = path.path.segments.last() {
-                        let type_name = &segment.ident;
-                        if let Some((trait_name, trait_args)) = types.remove(type_name) {
-                            map.insert(trait_name, (method_name, type_name, trait_args));
-                        }
-                    }
+            if let ReturnType::Type(_, ty) = &method.sig.output
+                && let Some(path) = get_path_from_type(ty)
+                && let Some(segment) = path.path.segments.last() {
+                let type_name = &segment.ident;
+                if let Some((trait_name, trait_args)) = types.remove(type_name) {
+                    map.insert(trait_name, (method_name, type_name, trait_args));
                 }
             }
         }
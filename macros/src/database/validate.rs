@@ -0,0 +1,143 @@
+use syn::{Error, FnArg, GenericArgument, GenericParam, Ident, ItemTrait, Pat, PathArguments, ReturnType, Signature, TraitItem, TraitItemType, Type};
+use crate::table::Schema;
+use quote::ToTokens;
+
+
+/// Checks that `table` is consistent with its schema and with `ty`, the associated type of the database it bounds with `args`.
+///
+/// - the table declares its `Item` and `Error` types, and the bound only constrains those.
+/// - the bound gives the table trait as many type arguments as it has type parameters.
+/// - `create_*` takes a `Self::Item` and `get_*` returns `Option<Self::Item>` or `Vec<Self::Item>`.
+/// - the key has the same type in every lookup by key and `delete_*`.
+///
+/// Every inconsistency is reported, spanned at the offending associated type or method.
+pub fn validate(table: &ItemTrait, ty: &TraitItemType, args: &PathArguments, client: &Ident) -> syn::Result<()> {
+    let mut errors = Vec::new();
+    let schema = Schema::from_attributes(&table.attrs)?;
+    let associated = table.items.iter().filter_map(|item| match item {
+        TraitItem::Type(ty) => Some(&ty.ident),
+        _ => None,
+    }).collect::<Vec<_>>();
+    for required in ["Item", "Error"] {
+        if !associated.iter().any(|ident| *ident == required) {
+            let message = format!("the table `{}` must declare `type {};`", table.ident, required);
+            errors.push(Error::new_spanned(&table.ident, message));
+        }
+    }
+    let parameters = table.generics.params.iter().filter(|param| matches!(param, GenericParam::Type(_))).count();
+    let mut arguments = 0;
+    if let PathArguments::AngleBracketed(args) = args {
+        for arg in &args.args {
+            match arg {
+                GenericArgument::Type(_) => arguments += 1,
+                GenericArgument::AssocType(constraint) if !associated.contains(&&constraint.ident) => {
+                    let message = format!("the table `{}` has no associated type `{}`", table.ident, constraint.ident);
+                    errors.push(Error::new_spanned(&constraint.ident, message));
+                },
+                GenericArgument::Constraint(constraint) if !associated.contains(&&constraint.ident) => {
+                    let message = format!("the table `{}` has no associated type `{}`", table.ident, constraint.ident);
+                    errors.push(Error::new_spanned(&constraint.ident, message));
+                },
+                _ => (),
+            }
+        }
+    }
+    if arguments != parameters {
+        let message = format!("`{}` is bounded by `{}` with {} type arguments but the table takes {}", ty.ident, table.ident, arguments, parameters);
+        errors.push(Error::new_spanned(&ty.ident, message));
+    }
+    let mut key: Option<(&Ident, &Type)> = None;
+    for item in &table.items {
+        let TraitItem::Fn(method) = item else {
+            continue;
+        };
+        let sig = &method.sig;
+        let name = sig.ident.to_string();
+        let values = values(sig, client);
+        if name.starts_with("create_") {
+            if let [value] = &values[..] && !is_self_item(value) {
+                errors.push(Error::new_spanned(value, format!("`{}` must take a `Self::Item`", name)));
+            }
+            continue;
+        }
+        let field = name.strip_prefix("get_").and_then(|name| name.rsplit_once("_by_")).map(|(_, field)| field);
+        if field.is_some() && !returns_items(&sig.output) {
+            let message = format!("`{}` must return `Option<Self::Item>` or `Vec<Self::Item>`", name);
+            errors.push(Error::new_spanned(&sig.output, message));
+        }
+        if field != Some(&schema.key) && !name.starts_with("delete_") {
+            continue;
+        }
+        let [value] = &values[..] else {
+            continue;
+        };
+        match key {
+            Some((first, ty)) if ty.to_token_stream().to_string() != value.to_token_stream().to_string() => {
+                let message = format!("the key `{}` is `{}` in `{}` but `{}` here", schema.key, ty.to_token_stream(), first, value.to_token_stream());
+                errors.push(Error::new_spanned(value, message));
+            },
+            Some(_) => (),
+            None => key = Some((&sig.ident, value)),
+        }
+    }
+    match errors.into_iter().reduce(|mut errors, error| {
+        errors.combine(error);
+        errors
+    }) {
+        Some(errors) => Err(errors),
+        None => Ok(()),
+    }
+}
+
+
+/// The types of the arguments of `sig` but the client.
+fn values<'a>(sig: &'a Signature, client: &Ident) -> Vec<&'a Type> {
+    sig.inputs.iter().filter_map(|arg| match arg {
+        FnArg::Typed(arg) => match &*arg.pat {
+            Pat::Ident(pat) if pat.ident == *client => None,
+            _ => Some(&*arg.ty),
+        },
+        FnArg::Receiver(_) => None,
+    }).collect()
+}
+
+
+fn is_self_item(ty: &Type) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
+    };
+    path.qself.is_none() && matches!(&path.path.segments.iter().map(|segment| segment.ident.to_string()).collect::<Vec<_>>()[..], [this, item] if this == "Self" && item == "Item")
+}
+
+
+/// Whether `output` is `Result<Option<Self::Item>, ..>` or `Result<Vec<Self::Item>, ..>`.
+fn returns_items(output: &ReturnType) -> bool {
+    let ReturnType::Type(_, ty) = output else {
+        return false;
+    };
+    let Some(ok) = first_argument(ty) else {
+        return false;
+    };
+    let Type::Path(path) = ok else {
+        return false;
+    };
+    let Some(segment) = path.path.segments.last() else {
+        return false;
+    };
+    (segment.ident == "Option" || segment.ident == "Vec") && first_argument(ok).is_some_and(is_self_item)
+}
+
+
+/// `T` in `X<T, ..>`.
+fn first_argument(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let PathArguments::AngleBracketed(arguments) = &path.path.segments.last()?.arguments else {
+        return None;
+    };
+    match arguments.args.first()? {
+        GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    }
+}
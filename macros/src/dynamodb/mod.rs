@@ -0,0 +1,214 @@
+use crate::implementation::{Operation, Range};
+use proc_macro2::TokenStream;
+use crate::table::Schema;
+use quote::quote;
+use syn::Ident;
+
+
+/// Generates a DynamoDB request for `operation`.
+///
+/// Items are created with an `attribute_not_exists` condition on the key.
+/// With `unique` attributes, every value is claimed by a lookup item of `self.unique_table`, keyed by `self.lookup(attribute, &value)`,
+/// put in the same transaction as the item under the same condition. Deleting the item deletes its lookup items in one transaction,
+/// on the condition that its unique values are still the ones read, so that no lookup item is left claiming a value of nobody.
+/// Lookups by the full key read the item directly while lookups by index query the `<field>-index` global secondary index.
+/// With a composite key, lookups by partition and range conditions on the sort key query the table.
+/// Listings scan a page of the table, resuming from the key of the last item of the previous page.
+pub fn body(schema: &Schema, client: &Ident, operation: Operation) -> TokenStream {
+    let key = &schema.key;
+    let unique = &schema.unique;
+    match operation {
+        Operation::Create { item } if !unique.is_empty() => quote! {
+            let item: ::std::collections::HashMap<::std::string::String, ::aws_sdk_dynamodb::types::AttributeValue> = #item.into();
+            let put = ::aws_sdk_dynamodb::types::Put::builder()
+                .table_name(&self.name)
+                .set_item(Some(item.clone()))
+                .condition_expression("attribute_not_exists(#key)")
+                .expression_attribute_names("#key", #key)
+                .build()?;
+            let mut request = #client.transact_write_items()
+                .transact_items(::aws_sdk_dynamodb::types::TransactWriteItem::builder().put(put).build());
+            for field in [#(#unique),*] {
+                if let Some(::aws_sdk_dynamodb::types::AttributeValue::S(value)) = item.get(field) {
+                    let lookup = ::std::collections::HashMap::from([
+                        (::std::string::String::from("id"), self.lookup(field, value)),
+                        (::std::string::String::from("owner"), item[#key].clone()),
+                    ]);
+                    let put = ::aws_sdk_dynamodb::types::Put::builder()
+                        .table_name(&self.unique_table)
+                        .set_item(Some(lookup))
+                        .condition_expression("attribute_not_exists(id)")
+                        .build()?;
+                    request = request.transact_items(::aws_sdk_dynamodb::types::TransactWriteItem::builder().put(put).build());
+                }
+            }
+            request.send().await?;
+            Ok(())
+        },
+        Operation::Delete { key: value, sort: None } if !unique.is_empty() => quote! {
+            let key = ::aws_sdk_dynamodb::types::AttributeValue::from(#value);
+            let output = #client.get_item()
+                .table_name(&self.name)
+                .key(#key, key.clone())
+                .consistent_read(true)
+                .send()
+                .await?;
+            let Some(item) = output.item else {
+                return Ok(());
+            };
+            let mut conditions = ::std::vec::Vec::new();
+            let mut lookups = ::std::vec::Vec::new();
+            let mut delete = ::aws_sdk_dynamodb::types::Delete::builder().table_name(&self.name).key(#key, key);
+            for (n, field) in [#(#unique),*].into_iter().enumerate() {
+                delete = delete.expression_attribute_names(format!("#unique{}", n), field);
+                match item.get(field) {
+                    Some(::aws_sdk_dynamodb::types::AttributeValue::S(value)) => {
+                        conditions.push(format!("#unique{} = :unique{}", n, n));
+                        delete = delete.expression_attribute_values(format!(":unique{}", n), ::aws_sdk_dynamodb::types::AttributeValue::S(value.clone()));
+                        lookups.push(::aws_sdk_dynamodb::types::Delete::builder().table_name(&self.unique_table).key("id", self.lookup(field, value)).build()?);
+                    },
+                    _ => conditions.push(format!("attribute_not_exists(#unique{})", n)),
+                }
+            }
+            let delete = delete.condition_expression(conditions.join(" AND ")).build()?;
+            let mut request = #client.transact_write_items()
+                .transact_items(::aws_sdk_dynamodb::types::TransactWriteItem::builder().delete(delete).build());
+            for lookup in lookups {
+                request = request.transact_items(::aws_sdk_dynamodb::types::TransactWriteItem::builder().delete(lookup).build());
+            }
+            request.send().await?;
+            Ok(())
+        },
+        Operation::Create { item } => quote! {
+            #client.put_item()
+                .table_name(&self.name)
+                .set_item(Some(#item.into()))
+                .condition_expression("attribute_not_exists(#key)")
+                .expression_attribute_names("#key", #key)
+                .send()
+                .await?;
+            Ok(())
+        },
+        Operation::Delete { key: value, sort } => {
+            let sort = sort.map(|sort| sort_key(schema, sort));
+            quote! {
+                #client.delete_item().table_name(&self.name).key(#key, ::aws_sdk_dynamodb::types::AttributeValue::from(#value)) #sort .send().await?;
+                Ok(())
+            }
+        },
+        Operation::GetByKey { key: value, sort } => {
+            let sort = sort_key(schema, sort);
+            get_item(client, quote! { .key(#key, ::aws_sdk_dynamodb::types::AttributeValue::from(#value)) #sort })
+        },
+        Operation::Get { field, value, many: false } if &field == key && schema.sort.is_none() => {
+            get_item(client, quote! { .key(#key, ::aws_sdk_dynamodb::types::AttributeValue::from(#value)) })
+        },
+        Operation::Get { field, value, many } => {
+            let index = (&field != key).then(|| {
+                let index = format!("{}-index", field);
+                quote! { .index_name(#index) }
+            });
+            let query = quote! {
+                #client.query()
+                    .table_name(&self.name)
+                    #index
+                    .key_condition_expression("#field = :value")
+                    .expression_attribute_names("#field", #field)
+                    .expression_attribute_values(":value", ::aws_sdk_dynamodb::types::AttributeValue::from(#value))
+            };
+            if many {
+                paginate(query)
+            } else {
+                quote! {
+                    let output = #query.limit(1).send().await?;
+                    match output.items.and_then(|items| items.into_iter().next()) {
+                        Some(item) => Ok(Some(item.try_into()?)),
+                        None => Ok(None),
+                    }
+                }
+            }
+        },
+        Operation::Range { key: value, range } => {
+            let sort = schema.sort.as_ref().expect("ranges are only recognised with a sort key");
+            let (condition, values) = match range {
+                Range::Between { from, to } => (
+                    "#key = :key AND #sort BETWEEN :from AND :to",
+                    quote! {
+                        .expression_attribute_values(":from", ::aws_sdk_dynamodb::types::AttributeValue::from(#from))
+                        .expression_attribute_values(":to", ::aws_sdk_dynamodb::types::AttributeValue::from(#to))
+                    },
+                ),
+                Range::StartingWith { prefix } => (
+                    "#key = :key AND begins_with(#sort, :prefix)",
+                    quote! {
+                        .expression_attribute_values(":prefix", ::aws_sdk_dynamodb::types::AttributeValue::from(#prefix))
+                    },
+                ),
+            };
+            paginate(quote! {
+                #client.query()
+                    .table_name(&self.name)
+                    .key_condition_expression(#condition)
+                    .expression_attribute_names("#key", #key)
+                    .expression_attribute_names("#sort", #sort)
+                    .expression_attribute_values(":key", ::aws_sdk_dynamodb::types::AttributeValue::from(#value))
+                    #values
+            })
+        },
+        Operation::List { after, limit } => quote! {
+            let output = #client.scan()
+                .table_name(&self.name)
+                .limit(::std::primitive::i32::try_from(#limit).unwrap_or(::std::primitive::i32::MAX))
+                .set_exclusive_start_key(#after.map(|after| ::std::collections::HashMap::from([(::std::string::String::from(#key), ::aws_sdk_dynamodb::types::AttributeValue::from(after))])))
+                .send()
+                .await?;
+            let mut items = ::std::vec::Vec::new();
+            for item in output.items.unwrap_or_default() {
+                items.push(item.try_into()?);
+            }
+            let next = match output.last_evaluated_key.and_then(|mut key| key.remove(#key)) {
+                Some(key) => Some(key.try_into()?),
+                None => None,
+            };
+            Ok((items, next).into())
+        },
+    }
+}
+
+
+/// The sort key of a request on a table with a composite key.
+fn sort_key(schema: &Schema, value: &Ident) -> TokenStream {
+    let sort = schema.sort.as_ref().expect("composite keys are only recognised with a sort key");
+    quote! { .key(#sort, ::aws_sdk_dynamodb::types::AttributeValue::from(#value)) }
+}
+
+
+/// Reads the item with the key set by `key`.
+fn get_item(client: &Ident, key: TokenStream) -> TokenStream {
+    quote! {
+        let output = #client.get_item().table_name(&self.name) #key .send().await?;
+        match output.item {
+            Some(item) => Ok(Some(item.try_into()?)),
+            None => Ok(None),
+        }
+    }
+}
+
+
+/// Collects every page of `query`.
+fn paginate(query: TokenStream) -> TokenStream {
+    quote! {
+        let mut items = ::std::vec::Vec::new();
+        let mut start = None;
+        loop {
+            let output = #query.set_exclusive_start_key(start).send().await?;
+            for item in output.items.unwrap_or_default() {
+                items.push(item.try_into()?);
+            }
+            start = output.last_evaluated_key;
+            if start.is_none() {
+                break Ok(items);
+            }
+        }
+    }
+}
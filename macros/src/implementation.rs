@@ -0,0 +1,210 @@
+use syn::{braced, parse2, Error, FnArg, GenericArgument, GenericParam, Ident, ImplItem, ItemImpl, ItemTrait, Pat, PathArguments, ReturnType, Signature, TraitItem, Type};
+use syn::parse::{Parse, ParseStream};
+use proc_macro2::TokenStream;
+use std::collections::HashMap;
+use syn::visit_mut::VisitMut;
+use crate::respan::respan;
+use crate::table::Schema;
+use quote::quote;
+
+
+/// The input of the macros completing the implementation of a table: the table trait followed by the partial implementation.
+pub struct Expansion {
+    table: ItemTrait,
+    item: ItemImpl,
+}
+
+
+/// A table operation recognised from the name of a trait method.
+///
+/// - `create_*(item)` stores a new item.
+/// - `get_*_by_<field>(value)` finds the item, or every item when returning a `Vec`, whose `field` is `value`.
+///   `field` has to be the key or one of the indexes of the table.
+/// - `get_*_by_<key>_and_<sort>(key, sort)` finds the item with the given composite key.
+/// - `get_*_by_<key>_and_<sort>_between(key, from, to)` finds the items of `key` whose sort key is within `from..=to`.
+/// - `get_*_by_<key>_and_<sort>_starting_with(key, prefix)` finds the items of `key` whose sort key starts with `prefix`.
+/// - `delete_*(key)`, or `delete_*(key, sort)` with a composite key, deletes the item with the given key.
+/// - `list_*(after, limit)` reads a page of at most `limit` items, resuming after the item whose key is `after` when it is set.
+///   the order is up to the storage but stable across pages. it returns a type built `From<(Vec<Self::Item>, Option<Key>)>` holding the page and the key to resume after, if any.
+///   tables with a composite key cannot be listed.
+///
+/// The last argument of every method is the client.
+pub enum Operation<'a> {
+    Create { item: &'a Ident },
+    Get { field: String, value: &'a Ident, many: bool },
+    GetByKey { key: &'a Ident, sort: &'a Ident },
+    Range { key: &'a Ident, range: Range<'a> },
+    Delete { key: &'a Ident, sort: Option<&'a Ident> },
+    List { after: &'a Ident, limit: &'a Ident },
+}
+
+
+/// The condition on the sort key of a range query.
+pub enum Range<'a> {
+    Between { from: &'a Ident, to: &'a Ident },
+    StartingWith { prefix: &'a Ident },
+}
+
+
+impl Parse for Expansion {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let table;
+        braced!(table in input);
+        Ok(Self { table: table.parse()?, item: input.parse()? })
+    }
+}
+
+
+/// Hands `item` to the macro generated by `#[table]` for the implemented trait, which calls back `callback`.
+pub fn collect_table(item: ItemImpl, attribute: &str, callback: TokenStream) -> syn::Result<TokenStream> {
+    let Some((_, path, _)) = &item.trait_ else {
+        let message = format!("#[{}] must be placed on the implementation of a #[table] trait", attribute);
+        return Err(Error::new_spanned(&item.self_ty, message));
+    };
+    let mut path = path.clone();
+    if let Some(segment) = path.segments.last_mut() {
+        segment.arguments = PathArguments::None;
+    }
+    Ok(quote! {
+        #path! { @callback { #callback } #item }
+    })
+}
+
+
+impl Expansion {
+    /// Adds every method of the table that the implementation does not define itself.
+    ///
+    /// `body` generates the body of each method from its operation and the client argument. `attrs` are added to every generated method.
+    pub fn complete<F: Fn(&Schema, &Ident, Operation) -> TokenStream>(mut self, attribute: &str, attrs: TokenStream, body: F) -> syn::Result<TokenStream> {
+        let schema = Schema::from_attributes(&self.table.attrs)?;
+        let mut generics = self.generics();
+        let implemented = self.item.items.iter().filter_map(|item| match item {
+            ImplItem::Fn(method) => Some(method.sig.ident.to_string()),
+            _ => None,
+        }).collect::<Vec<_>>();
+        let span = self.item.impl_token.span;
+        for item in &self.table.items {
+            let TraitItem::Fn(method) = item else {
+                continue;
+            };
+            if implemented.contains(&method.sig.ident.to_string()) {
+                continue;
+            }
+            let mut sig = method.sig.clone();
+            generics.visit_signature_mut(&mut sig);
+            let (client, operation) = operation(&schema, &sig, attribute)?;
+            let body = body(&schema, client, operation);
+            let method = quote! {
+                #attrs
+                #sig {
+                    #body
+                }
+            };
+            self.item.items.push(parse2(respan(method, span))?);
+        }
+        let item = self.item;
+        Ok(quote! {#item})
+    }
+
+    /// Maps the type parameters of the trait to the arguments they are given in the implementation. eg `Client` to `aws_sdk_dynamodb::Client`
+    fn generics(&self) -> Generics {
+        let mut generics = Generics(HashMap::new());
+        let Some((_, path, _)) = &self.item.trait_ else {
+            return generics;
+        };
+        let Some(PathArguments::AngleBracketed(arguments)) = path.segments.last().map(|segment| &segment.arguments) else {
+            return generics;
+        };
+        let params = self.table.generics.params.iter().filter_map(|param| match param {
+            GenericParam::Type(param) => Some(param.ident.to_string()),
+            _ => None,
+        });
+        let arguments = arguments.args.iter().filter_map(|argument| match argument {
+            GenericArgument::Type(ty) => Some(ty.clone()),
+            _ => None,
+        });
+        generics.0.extend(params.zip(arguments));
+        generics
+    }
+}
+
+
+struct Generics(HashMap<String, Type>);
+
+
+impl VisitMut for Generics {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Type::Path(path) = ty
+            && path.qself.is_none()
+            && let Some(ident) = path.path.get_ident()
+            && let Some(argument) = self.0.get(&ident.to_string()) {
+            *ty = argument.clone();
+            return;
+        }
+        syn::visit_mut::visit_type_mut(self, ty);
+    }
+}
+
+
+/// Recognises the operation of `sig` from its name. see [`Operation`]
+fn operation<'a>(schema: &Schema, sig: &'a Signature, attribute: &str) -> syn::Result<(&'a Ident, Operation<'a>)> {
+    let name = sig.ident.to_string();
+    let mut args = sig.inputs.iter().filter_map(|arg| match arg {
+        FnArg::Typed(arg) => match &*arg.pat {
+            Pat::Ident(pat) => Some(&pat.ident),
+            _ => None,
+        },
+        FnArg::Receiver(_) => None,
+    }).collect::<Vec<_>>();
+    let unsupported = || {
+        let message = format!("#[{}] cannot generate `{}`: only the `create_*`, `get_*_by_*`, `delete_*` and `list_*` methods described by the schema of the table are generated. implement it by hand", attribute, name);
+        Error::new_spanned(&sig.ident, message)
+    };
+    let Some(client) = args.pop() else {
+        return Err(unsupported());
+    };
+    let composite = schema.sort.as_ref().map(|sort| format!("{}_and_{}", schema.key, sort));
+    let field = name.strip_prefix("get_").and_then(|name| name.rsplit_once("_by_")).map(|(_, field)| field);
+    let operation = match (&args[..], field) {
+        (&[item], _) if name.starts_with("create_") => Operation::Create { item },
+        (&[key], _) if name.starts_with("delete_") && composite.is_none() => Operation::Delete { key, sort: None },
+        (&[key, sort], _) if name.starts_with("delete_") && composite.is_some() => Operation::Delete { key, sort: Some(sort) },
+        (&[key, sort], Some(field)) if composite.as_deref() == Some(field) => Operation::GetByKey { key, sort },
+        (&[key, from, to], Some(field)) if composite.as_ref().is_some_and(|composite| field == format!("{}_between", composite)) => {
+            Operation::Range { key, range: Range::Between { from, to } }
+        },
+        (&[key, prefix], Some(field)) if composite.as_ref().is_some_and(|composite| field == format!("{}_starting_with", composite)) => {
+            Operation::Range { key, range: Range::StartingWith { prefix } }
+        },
+        (&[after, limit], _) if name.starts_with("list_") && composite.is_none() => Operation::List { after, limit },
+        (&[value], Some(field)) => return get(schema, sig, client, field, value),
+        _ => return Err(unsupported()),
+    };
+    Ok((client, operation))
+}
+
+
+/// A lookup of the items whose `field` is `value`.
+fn get<'a>(schema: &Schema, sig: &'a Signature, client: &'a Ident, field: &str, value: &'a Ident) -> syn::Result<(&'a Ident, Operation<'a>)> {
+    if field != schema.key && !schema.indexes.iter().any(|index| index == field) {
+        let message = format!("`{}` is neither the key nor one of the indexes of the table. declare it with #[table(indexes(\"{}\"))]", field, field);
+        return Err(Error::new_spanned(&sig.ident, message));
+    }
+    let many = returns_vec(&sig.output);
+    Ok((client, Operation::Get { field: field.to_string(), value, many }))
+}
+
+
+/// Whether `output` is `Result<Vec<..>, ..>`.
+fn returns_vec(output: &ReturnType) -> bool {
+    let ReturnType::Type(_, ty) = output else {
+        return false;
+    };
+    let Type::Path(path) = &**ty else {
+        return false;
+    };
+    let Some(PathArguments::AngleBracketed(arguments)) = path.path.segments.last().map(|segment| &segment.arguments) else {
+        return false;
+    };
+    matches!(arguments.args.first(), Some(GenericArgument::Type(Type::Path(ok))) if ok.path.segments.last().is_some_and(|segment| segment.ident == "Vec"))
+}
@@ -1,13 +1,15 @@
-use syn::{parse_macro_input, ItemTrait};
+use syn::{parse_macro_input, DeriveInput, ItemImpl, ItemTrait};
 use proc_macro::TokenStream;
 use quote::quote;
 
-use crate::io::{add_table, delete_tables_file};
 
-
-mod io;
 mod table;
+mod respan;
 mod database;
+mod dynamodb;
+mod memory;
+mod implementation;
+mod patch;
 
 
 #[proc_macro_attribute]
@@ -22,39 +24,144 @@ pub fn client(_: TokenStream, input: TokenStream) -> TokenStream {
 }
 
 
+/// Marks the method of a `#[database]` returning the client reads are sent to. eg the client of a read replica.
+///
+/// The methods generated for the `get_*`, `list_*` and `search_*` methods of the tables call it rather than the `#[client]` method,
+/// unless they are marked [`macro@primary`]. Without it, every method calls the `#[client]` method.
+#[proc_macro_attribute]
+pub fn replica(_: TokenStream, input: TokenStream) -> TokenStream {
+    input
+}
+
+
+/// Marks a read of a `#[table]` that has to see the latest writes, so `#[database]` sends it to the `#[client]` rather than the [`macro@replica`].
+#[proc_macro_attribute]
+pub fn primary(_: TokenStream, input: TokenStream) -> TokenStream {
+    input
+}
+
+
+/// Marks a trait as a database table, optionally declaring its schema. eg `#[table(key = "id", indexes("email"))]`
+///
+/// A composite primary key is declared with a sort key, eg `#[table(key = "org_id", sort = "user_id")]`.
+/// Attributes no two items may share are declared with `unique("username", "email")`.
+///
+/// Alongside the trait, a hidden `macro_rules!` macro with the same name is generated. `#[database]` and `#[dynamodb]` invoke it
+/// to receive the tokens of the trait, so tables can live in any module without sharing state between macro invocations.
 #[proc_macro_attribute]
-pub fn table(_: TokenStream, input: TokenStream) -> TokenStream {
+pub fn table(args: TokenStream, input: TokenStream) -> TokenStream {
+    let schema = args.clone();
+    parse_macro_input!(schema as table::Schema);
     let item = parse_macro_input!(input as ItemTrait);
-    let trait_name = item.ident.to_string();
-    let value = quote!{#item}.to_string();
-    add_table(trait_name, value);
-    quote!{#item}.into()
+    let callback = table::callback(&item, args.into());
+    quote!{
+        #item
+        #callback
+    }.into()
 }
 
 
+/// Generates a method on the database trait for every method of its tables.
+///
+/// The tables are the associated types bounded by a `#[table]` trait that are returned by one of the database's methods.
+/// Their definitions are collected by invoking each table's generated macro in turn, the last of which calls [`expand_database!`].
+/// Each table is checked against the associated type it bounds and its schema, and inconsistencies are reported where they are.
+///
+/// A `MockDatabase` implementing the trait is also generated for unit tests. Its tables are `MockTable`s that record every call
+/// and return the responses queued with `<method>_returns`, eg `db.users_table().get_user_by_id_returns(Ok(None))`.
 #[proc_macro_attribute]
 pub fn database(_: TokenStream, input: TokenStream) -> TokenStream {
     let database: database::Database = match input.try_into() {
         Ok(db) => db,
-        Err(err) => {
-            let err_msg = format!("Error parsing Database trait: {}", err);
-            return quote! {
-                compile_error!(#err_msg);
-            }
-            .into();
-        }
+        Err(err) => return err.to_compile_error().into(),
     };
-    let tables = io::get_tables();
-    delete_tables_file().expect("Could not delete the tables file");
-    let expanded = database.expand(tables);
-    match expanded {
+    database.collect_tables().into()
+}
+
+
+/// Completes a DynamoDB implementation of a `#[table]` trait.
+///
+/// Every method missing from the implementation is generated from its name and the schema of the table:
+/// `create_*` puts an item unless its key is taken, `get_*_by_<field>` reads by key or queries the `<field>-index` index
+/// and `delete_*` deletes by key. With a sort key, `get_*_by_<key>_and_<sort>` reads by the composite key while
+/// `get_*_by_<key>_and_<sort>_between` and `get_*_by_<key>_and_<sort>_starting_with` query a range of the partition.
+/// Other methods have to be written by hand.
+/// The implementing type must have a `name: String` field holding the name of the DynamoDB table,
+/// and with `unique` attributes a `unique_table: String` field holding the table of their lookup items,
+/// and a `lookup(&self, attribute: &str, value: &str) -> AttributeValue` method giving the key of the lookup item claiming `value`.
+/// see [`macro@table`].
+/// The types used in the trait's method signatures must be in scope where it is implemented.
+#[proc_macro_attribute]
+pub fn dynamodb(_: TokenStream, input: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(input as ItemImpl);
+    match implementation::collect_table(item, "dynamodb", quote!(::macros::expand_dynamodb)) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+
+#[doc(hidden)]
+#[proc_macro]
+pub fn expand_dynamodb(input: TokenStream) -> TokenStream {
+    let expansion = parse_macro_input!(input as implementation::Expansion);
+    let instrument = quote!(#[::tracing::instrument(skip_all, fields(table = %self.name), err)]);
+    match expansion.complete("dynamodb", instrument, dynamodb::body) {
         Ok(tokens) => tokens.into(),
-        Err(err) => {
-            let err_msg = format!("Error expanding database macro: {}", err);
-            quote! {
-                compile_error!(#err_msg);
-            }
-            .into()
-        }
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+
+/// Completes an in-memory implementation of a `#[table]` trait, meant for tests and local development.
+///
+/// Methods are recognised like with [`macro@dynamodb`], but only lookups by the key or a declared index are generated.
+/// The implementing type provides the storage through three methods:
+/// `insert(key, indexes, unique, item)` failing when the key or a unique value is taken, `find(field, &value)` returning every matching item
+/// and `remove(key, &value)`.
+#[proc_macro_attribute]
+pub fn memory(_: TokenStream, input: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(input as ItemImpl);
+    match implementation::collect_table(item, "memory", quote!(::macros::expand_memory)) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+
+#[doc(hidden)]
+#[proc_macro]
+pub fn expand_memory(input: TokenStream) -> TokenStream {
+    let expansion = parse_macro_input!(input as implementation::Expansion);
+    match expansion.complete("memory", quote!(), memory::body) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+
+/// Derives `<Name>Patch`, a partial update of the struct built from a JSON object with `TryFrom<Map<String, Value>>`
+/// and applied with `apply(&mut item)`.
+///
+/// Fields marked `#[patch(immutable)]` cannot be patched. Unknown keys, immutable fields and values of the wrong type are rejected
+/// with the `UnknownField`, `ImmutableField` and `UnexpectedDataType` variants of `ConversionError`,
+/// or of the error type given with `#[patch(error = Path)]`.
+#[proc_macro_derive(Patch, attributes(patch))]
+pub fn patch(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match patch::expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+
+#[doc(hidden)]
+#[proc_macro]
+pub fn expand_database(input: TokenStream) -> TokenStream {
+    let expansion = parse_macro_input!(input as database::Expansion);
+    match expansion.expand() {
+        Ok(tokens) => tokens,
+        Err(err) => err.to_compile_error().into(),
     }
 }
@@ -0,0 +1,52 @@
+use crate::implementation::{Operation, Range};
+use proc_macro2::TokenStream;
+use crate::table::Schema;
+use quote::quote;
+use syn::Ident;
+
+
+/// Generates the call to the in-memory table for `operation`.
+///
+/// The implementing type provides `insert(keys, indexes, unique, item)`, `find(field, &value)`, `get(key, &value, sort, &value)`,
+/// `between(key, &value, sort, &from, &to)`, `starting_with(key, &value, sort, &prefix)`
+/// `remove(key, &value)` or, with a composite key, `remove_sorted(key, &value, sort, &value)`, and `list(key, after, limit)`.
+pub fn body(schema: &Schema, client: &Ident, operation: Operation) -> TokenStream {
+    let key = &schema.key;
+    let sort = &schema.sort;
+    let keys = std::iter::once(key).chain(sort);
+    let indexes = &schema.indexes;
+    let unique = &schema.unique;
+    let call = match operation {
+        Operation::Create { item } => quote! {
+            self.insert(&[#(#keys),*], &[#(#indexes),*], &[#(#unique),*], #item)
+        },
+        Operation::Get { field, value, many: true } => quote! {
+            self.find(#field, &#value)
+        },
+        Operation::Get { field, value, many: false } => quote! {
+            Ok(self.find(#field, &#value)?.into_iter().next())
+        },
+        Operation::GetByKey { key: value, sort: sort_value } => quote! {
+            self.get(#key, &#value, #sort, &#sort_value)
+        },
+        Operation::Range { key: value, range: Range::Between { from, to } } => quote! {
+            self.between(#key, &#value, #sort, &#from, &#to)
+        },
+        Operation::Range { key: value, range: Range::StartingWith { prefix } } => quote! {
+            self.starting_with(#key, &#value, #sort, &#prefix)
+        },
+        Operation::Delete { key: value, sort: None } => quote! {
+            self.remove(#key, &#value)
+        },
+        Operation::Delete { key: value, sort: Some(sort_value) } => quote! {
+            self.remove_sorted(#key, &#value, #sort, &#sort_value)
+        },
+        Operation::List { after, limit } => quote! {
+            self.list(#key, #after.as_ref(), #limit).map(::std::convert::Into::into)
+        },
+    };
+    quote! {
+        let _ = #client;
+        #call
+    }
+}
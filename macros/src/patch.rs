@@ -0,0 +1,123 @@
+use syn::{Attribute, Data, DeriveInput, Error, Fields, LitStr, Path, Token};
+use quote::{format_ident, quote};
+use proc_macro2::TokenStream;
+
+
+/// A field of the patched struct.
+struct Field<'a> {
+    ident: &'a syn::Ident,
+    ty: &'a syn::Type,
+    /// the key of the field in a patch. its `#[serde(rename)]` or its name.
+    key: String,
+    /// the `#[cfg(..)]` attributes of the field, repeated wherever it is used.
+    cfgs: Vec<&'a Attribute>,
+    immutable: bool,
+}
+
+
+/// Generates `<Name>Patch`, a partial update of the struct built from a JSON object with `TryFrom<Map<String, Value>>`.
+///
+/// Keys are the field names, or their `#[serde(rename)]`. Fields marked `#[patch(immutable)]` are rejected with
+/// `ImmutableField(key)`, unknown keys with `UnknownField(key)` and values of the wrong type with `UnexpectedDataType(key)`,
+/// all variants of the error type given with `#[patch(error = Path)]` on the struct, `ConversionError` by default.
+pub fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(&input.ident, "Patch can only be derived for structs"));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new_spanned(&input.ident, "Patch can only be derived for structs with named fields"));
+    };
+    let error = error_type(&input.attrs)?;
+    let fields = fields.named.iter().map(field).collect::<syn::Result<Vec<_>>>()?;
+    let (immutable, mutable): (Vec<_>, Vec<_>) = fields.iter().partition(|field| field.immutable);
+    let vis = &input.vis;
+    let name = &input.ident;
+    let patch = format_ident!("{}Patch", name);
+    let doc = format!("A partial update of [`{}`]. Unset fields are left untouched.", name);
+    let idents = mutable.iter().map(|field| field.ident).collect::<Vec<_>>();
+    let types = mutable.iter().map(|field| field.ty);
+    let keys = mutable.iter().map(|field| &field.key).collect::<Vec<_>>();
+    let cfgs = mutable.iter().map(|field| &field.cfgs).collect::<Vec<_>>();
+    let immutable_keys = immutable.iter().map(|field| &field.key).collect::<Vec<_>>();
+    let immutable_cfgs = immutable.iter().map(|field| &field.cfgs);
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Debug, Clone, Default)]
+        #vis struct #patch {
+            #(#(#cfgs)* pub #idents: ::std::option::Option<#types>,)*
+        }
+
+        impl ::std::convert::TryFrom<::serde_json::Map<::std::string::String, ::serde_json::Value>> for #patch {
+            type Error = #error;
+
+            fn try_from(map: ::serde_json::Map<::std::string::String, ::serde_json::Value>) -> ::std::result::Result<Self, Self::Error> {
+                let mut patch = Self::default();
+                for (key, value) in map {
+                    match key.as_str() {
+                        #(#(#cfgs)* #keys => patch.#idents = Some(::serde_json::from_value(value).map_err(|_| #error::UnexpectedDataType(#keys))?),)*
+                        #(#(#immutable_cfgs)* #immutable_keys => return Err(#error::ImmutableField(#immutable_keys)),)*
+                        _ => return Err(#error::UnknownField(key)),
+                    }
+                }
+                Ok(patch)
+            }
+        }
+
+        impl #patch {
+            /// Sets the fields of `item` this patch sets.
+            pub fn apply(self, item: &mut #name) {
+                #(#(#cfgs)* if let Some(value) = self.#idents {
+                    item.#idents = value;
+                })*
+            }
+        }
+    })
+}
+
+
+fn field(field: &syn::Field) -> syn::Result<Field<'_>> {
+    let ident = field.ident.as_ref().expect("the fields are named");
+    let mut key = ident.to_string();
+    let mut immutable = false;
+    for attr in &field.attrs {
+        if attr.path().is_ident("patch") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("immutable") {
+                    immutable = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `immutable`"))
+                }
+            })?;
+        } else if attr.path().is_ident("serde") {
+            // only `rename` matters here, the values of other serde attributes are skipped.
+            let _ = attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    key = meta.value()?.parse::<LitStr>()?.value();
+                } else if meta.input.peek(Token![=]) {
+                    meta.value()?.parse::<syn::Expr>()?;
+                }
+                Ok(())
+            });
+        }
+    }
+    let cfgs = field.attrs.iter().filter(|attr| attr.path().is_ident("cfg")).collect();
+    Ok(Field { ident, ty: &field.ty, key, cfgs, immutable })
+}
+
+
+/// The error type set with `#[patch(error = Path)]`.
+fn error_type(attrs: &[Attribute]) -> syn::Result<Path> {
+    let mut error = syn::parse_quote!(ConversionError);
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("patch")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("error") {
+                error = meta.value()?.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("expected `error`"))
+            }
+        })?;
+    }
+    Ok(error)
+}
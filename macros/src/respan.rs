@@ -0,0 +1,20 @@
+use proc_macro2::{Group, Span, TokenStream, TokenTree};
+
+
+/// Gives every token the hygiene of `span` while keeping its location.
+///
+/// The tokens of the tables reach the expanding macros through the `macro_rules!` macros generated by `#[table]`,
+/// so on their own they would resolve names like `self` and the method arguments in the scope of those macros.
+pub fn respan(tokens: TokenStream, span: Span) -> TokenStream {
+    tokens.into_iter().map(|token| match token {
+        TokenTree::Group(group) => {
+            let mut respanned = Group::new(group.delimiter(), respan(group.stream(), span));
+            respanned.set_span(span.located_at(group.span()));
+            TokenTree::Group(respanned)
+        },
+        mut token => {
+            token.set_span(span.located_at(token.span()));
+            token
+        },
+    }).collect()
+}
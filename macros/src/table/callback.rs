@@ -0,0 +1,38 @@
+use quote::{format_ident, quote};
+use proc_macro2::TokenStream;
+use syn::ItemTrait;
+
+
+/// Generates the `macro_rules!` macro other macros use to receive the tokens of `item`.
+///
+/// `#[database]` passes the paths of the tables left to collect and the tables collected so far.
+/// The macro appends `item` and either invokes the next table's macro or, when none are left, `expand_database!`.
+/// Any other macro can receive the table with `Table! { @callback { path::to::macro } args.. }`
+/// which invokes `path::to::macro! { { trait.. } args.. }`.
+///
+/// The trait is passed along with its `#[table(..)]` attribute so the schema reaches the receiving macro.
+/// The macro is exported under the name of the trait, which lives in a different namespace than the macro.
+pub fn callback(item: &ItemTrait, args: TokenStream) -> TokenStream {
+    let name = &item.ident;
+    let macro_name = format_ident!("__{}_table", name);
+    let table = quote! {
+        #[table(#args)]
+        #item
+    };
+    quote! {
+        #[doc(hidden)]
+        macro_rules! #macro_name {
+            (@database [{ $($next:tt)* } $($pending:tt)*] [$($tables:tt)*] $($database:tt)*) => {
+                $($next)*! { @database [$($pending)*] [$($tables)* { #table }] $($database)* }
+            };
+            (@database [] [$($tables:tt)*] $($database:tt)*) => {
+                ::macros::expand_database! { [$($tables)* { #table }] $($database)* }
+            };
+            (@callback { $($callback:tt)* } $($args:tt)*) => {
+                $($callback)*! { { #table } $($args)* }
+            };
+        }
+        #[allow(unused_imports)]
+        pub(crate) use #macro_name as #name;
+    }
+}
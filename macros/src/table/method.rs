@@ -19,7 +19,9 @@ pub struct Method {
     outputs: ReturnType,
     fields: Vec<Pat>,
     /// Identifiers to skip during transformation
-    skip: HashSet<String>
+    skip: HashSet<String>,
+    /// Whether the method reads, and can be sent to a replica. `get_*`, `list_*` and `search_*` methods not marked `#[primary]`.
+    read: bool,
 }
 
 impl Method {
@@ -51,10 +53,9 @@ impl Method {
     fn update_args(&mut self, type_ident: Ident, (trait_ident, trait_args): (&Ident, &PathArguments)) {
         // Update the arguments to include the table type
         for arg in &mut self.args {
-            if let FnArg::Typed(pat_type) = arg {
-                if let Some(type_path) = get_path_from_type(&mut pat_type.ty) {
-                    transform_type_path_in_place(type_path, type_ident.clone(), (trait_ident, trait_args), &self.skip);
-                }
+            if let FnArg::Typed(pat_type) = arg
+                && let Some(type_path) = get_path_from_type(&mut pat_type.ty) {
+                transform_type_path_in_place(type_path, type_ident.clone(), (trait_ident, trait_args), &self.skip);
             }
         }
     }
@@ -68,10 +69,9 @@ impl Method {
     }
 
     fn field_name(argument: &FnArg) -> Option<Ident> {
-        if let FnArg::Typed(pat_type) = argument {
-            if let syn::Pat::Ident(pat_ident) = &*pat_type.pat {
-                return Some(pat_ident.ident.clone());
-            }
+        if let FnArg::Typed(pat_type) = argument
+            && let syn::Pat::Ident(pat_ident) = &*pat_type.pat {
+            return Some(pat_ident.ident.clone());
         }
         None
     }
@@ -91,13 +91,12 @@ impl Method {
         let mut skip_set = HashSet::new();
         
         for attr in attrs {
-            if attr.path().is_ident("skip") {
-                if let Meta::List(meta_list) = &attr.meta {
-                    for token in meta_list.tokens.clone() {
-                        // Parse each token as an identifier
-                        if let Ok(ident) = syn::parse2::<Ident>(token.into()) {
-                            skip_set.insert(ident.to_string());
-                        }
+            if attr.path().is_ident("skip")
+                && let Meta::List(meta_list) = &attr.meta {
+                for token in meta_list.tokens.clone() {
+                    // Parse each token as an identifier
+                    if let Ok(ident) = syn::parse2::<Ident>(token.into()) {
+                        skip_set.insert(ident.to_string());
                     }
                 }
             }
@@ -106,9 +105,9 @@ impl Method {
         skip_set
     }
 
-    pub fn to_tokens(&mut self, table_method_name: &Ident, client_name: &Ident, table_type_name: &Ident, (trait_name, trait_args): (&Ident, &PathArguments)) -> Result<TraitItem> {
+    pub fn expand(&mut self, table_method_name: &Ident, (client_name, replica_name): (&Ident, &Ident), table_type_name: &Ident, (trait_name, trait_args): (&Ident, &PathArguments)) -> Result<TraitItem> {
         // Remove the client argument from the method arguments
-        self.remove_arg(&client_name);
+        self.remove_arg(client_name);
 
         // Update the return type to include the table type
         self.update_return(table_type_name.clone(), (trait_name, trait_args));
@@ -122,11 +121,12 @@ impl Method {
         let generics = &self.generics;
         let outputs = &self.outputs;
         let fields = &self.fields;
+        let getter = if self.read { replica_name } else { client_name };
 
         // Generate the method signature
         let method = quote! {
             #async_kw fn #name #generics(#(#args),*) #outputs {
-                let #client_name = self.#client_name();
+                let #client_name = self.#getter();
                 let table = self.#table_method_name();
                 table.#name(#(#fields),*)#await_kw.map_err(Into::into)
             }
@@ -146,8 +146,10 @@ impl From<TraitItemFn> for Method {
         let outputs = method_item.sig.output.clone();
         let fields = Method::fields_from_args(&args);
         let skip = Method::parse_skip_attribute(&method_item.attrs);
+        let primary = method_item.attrs.iter().any(|attr| attr.path().is_ident("primary"));
+        let read = ["get_", "list_", "search_"].iter().any(|prefix| name.to_string().starts_with(prefix)) && !primary;
 
-        Self { name, future, args, generics, outputs, fields, skip }
+        Self { name, future, args, generics, outputs, fields, skip, read }
     }
 }
 
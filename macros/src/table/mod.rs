@@ -1,7 +1,12 @@
+#[allow(clippy::module_inception)]
 mod table;
+mod callback;
+mod schema;
 mod method;
 mod path;
 
+pub use callback::callback;
+pub use schema::Schema;
 pub use table::*;
 pub use method::*;
 pub use path::*;
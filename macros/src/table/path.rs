@@ -17,10 +17,9 @@ pub fn transform_type_path_in_place(type_path: &mut TypePath, type_ident: Ident,
     }
 
     // Check if the identifier following "Self" should be skipped
-    if let Some(second_segment) = type_path.path.segments.iter().nth(1) {
-        if skip.contains(&second_segment.ident.to_string()) {
-            return;
-        }
+    if let Some(second_segment) = type_path.path.segments.iter().nth(1)
+        && skip.contains(&second_segment.ident.to_string()) {
+        return;
     }
 
     // Create the Self::U type for the QSelf
@@ -0,0 +1,64 @@
+use syn::{Token, Attribute, Error, Expr, ExprLit, Lit, LitStr, Meta, punctuated::Punctuated};
+use syn::parse::{Parse, ParseStream};
+
+
+/// The storage independent layout of a table, declared with `#[table(key = "id", indexes("email"))]`.
+///
+/// A composite primary key adds a sort key, eg `#[table(key = "org_id", sort = "user_id")]`.
+pub struct Schema {
+    /// the attribute uniquely identifying an item, or its partition with a sort key. defaults to `id`.
+    pub key: String,
+    /// the attribute ordering the items sharing a `key`.
+    pub sort: Option<String>,
+    /// the attributes items can also be looked up by.
+    pub indexes: Vec<String>,
+    /// the string attributes no two items may share, eg `unique("username", "email")`. items without the attribute are not constrained.
+    pub unique: Vec<String>,
+}
+
+
+impl Schema {
+    /// Reads the schema from the `#[table(..)]` attribute among `attrs`, falling back to the default one.
+    pub fn from_attributes(attrs: &[Attribute]) -> syn::Result<Self> {
+        match attrs.iter().find(|attr| attr.path().is_ident("table")) {
+            Some(attr) if matches!(attr.meta, Meta::List(_)) => attr.parse_args(),
+            _ => Ok(Self::default()),
+        }
+    }
+}
+
+
+impl Default for Schema {
+    fn default() -> Self {
+        Self { key: "id".into(), sort: None, indexes: Vec::new(), unique: Vec::new() }
+    }
+}
+
+
+impl Parse for Schema {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut schema = Self::default();
+        for meta in Punctuated::<Meta, Token![,]>::parse_terminated(input)? {
+            match &meta {
+                Meta::NameValue(name_value) if name_value.path.is_ident("key") => match &name_value.value {
+                    Expr::Lit(ExprLit { lit: Lit::Str(key), .. }) => schema.key = key.value(),
+                    value => return Err(Error::new_spanned(value, "the key must be a string literal")),
+                },
+                Meta::NameValue(name_value) if name_value.path.is_ident("sort") => match &name_value.value {
+                    Expr::Lit(ExprLit { lit: Lit::Str(sort), .. }) => schema.sort = Some(sort.value()),
+                    value => return Err(Error::new_spanned(value, "the sort key must be a string literal")),
+                },
+                Meta::List(list) if list.path.is_ident("indexes") => {
+                    let indexes = list.parse_args_with(Punctuated::<LitStr, Token![,]>::parse_terminated)?;
+                    schema.indexes.extend(indexes.iter().map(LitStr::value));
+                },
+                Meta::List(list) if list.path.is_ident("unique") => {
+                    let unique = list.parse_args_with(Punctuated::<LitStr, Token![,]>::parse_terminated)?;
+                    schema.unique.extend(unique.iter().map(LitStr::value));
+                },
+                _ => return Err(Error::new_spanned(meta, "expected `key = \"..\"`, `sort = \"..\"`, `indexes(\"..\", ..)` or `unique(\"..\", ..)`")),
+            }
+        }
+        Ok(schema)
+    }
+}
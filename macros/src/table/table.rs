@@ -30,10 +30,11 @@ impl Table {
         methods
     }
 
-    pub fn methods(&mut self, client: &Ident, table_method_ident: &Ident, table_type_ident: &Ident, table_trait_ident: &Ident, table_trait_args: &PathArguments) -> Result<Vec<TraitItem>> {
+    /// `clients` are the methods returning the client and the client reads are sent to.
+    pub fn methods(&mut self, clients: (&Ident, &Ident), table_method_ident: &Ident, table_type_ident: &Ident, table_trait_ident: &Ident, table_trait_args: &PathArguments) -> Result<Vec<TraitItem>> {
         let mut items = Vec::new();
         for method in &mut self.methods {
-            let item = method.to_tokens(table_method_ident, client, table_type_ident, (table_trait_ident, table_trait_args))?;
+            let item = method.expand(table_method_ident, clients, table_type_ident, (table_trait_ident, table_trait_args))?;
             items.push(item);
         }
         Ok(items)